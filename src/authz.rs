@@ -0,0 +1,184 @@
+//! Authorization checks for calendars and events
+//!
+//! REST, web UI and CalDAV handlers all go through these helpers instead of
+//! comparing owner ids themselves, so share permissions are enforced in one place.
+
+use uuid::Uuid;
+
+use crate::error::AppError;
+use crate::models::{Calendar, Event, Group, GroupRole, PermissionLevel};
+use crate::services::CalendarService;
+
+/// Level of access an operation requires
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Access {
+    /// Read the calendar and its events
+    View,
+    /// Create, change and delete events
+    Edit,
+    /// Change or delete the calendar itself and manage its shares
+    Admin,
+}
+
+/// Permission a user holds on a calendar: owners have full access, everyone
+/// else gets the highest permission of the shares addressed to them and of a
+/// delegation from the owner or, for group calendars, of their group role
+pub async fn calendar_permission(
+    service: &CalendarService,
+    user_id: Uuid,
+    calendar: &Calendar,
+) -> Result<Option<PermissionLevel>, AppError> {
+    if calendar.user_id == user_id {
+        return Ok(Some(calendar.owner_permission()));
+    }
+    // Only the owner sees calendars generated for them
+    if calendar.is_generated() {
+        return Ok(None);
+    }
+
+    let shared = service.get_share_permission(calendar.id, user_id).await?;
+    let Some(group_id) = calendar.group_id else {
+        // Delegates act on all of the owner's personal calendars
+        let delegated = service.get_delegation_permission(calendar.user_id, user_id).await?;
+        return Ok(shared.max(delegated));
+    };
+
+    // Group members get access by role, whichever is higher alongside a direct share
+    let from_group = service.get_group_role(group_id, user_id).await?.map(|role| role.permission());
+    Ok(shared.max(from_group))
+}
+
+/// Whether the user may read the calendar (public calendars are readable by everyone)
+pub async fn can_view(service: &CalendarService, user_id: Uuid, calendar: &Calendar) -> Result<bool, AppError> {
+    Ok(calendar.is_public || calendar_permission(service, user_id, calendar).await?.is_some())
+}
+
+/// Whether the user may create, change and delete events in the calendar
+pub async fn can_edit(service: &CalendarService, user_id: Uuid, calendar: &Calendar) -> Result<bool, AppError> {
+    Ok(calendar_permission(service, user_id, calendar).await? >= Some(PermissionLevel::Write))
+}
+
+/// Whether the user may change or delete the calendar and manage its shares
+pub async fn can_admin(service: &CalendarService, user_id: Uuid, calendar: &Calendar) -> Result<bool, AppError> {
+    Ok(calendar_permission(service, user_id, calendar).await? == Some(PermissionLevel::Admin))
+}
+
+/// Check that the user has the required access to a calendar
+pub async fn require(
+    service: &CalendarService,
+    user_id: Uuid,
+    calendar: &Calendar,
+    access: Access,
+) -> Result<(), AppError> {
+    let allowed = match access {
+        Access::View => can_view(service, user_id, calendar).await?,
+        Access::Edit => can_edit(service, user_id, calendar).await?,
+        Access::Admin => can_admin(service, user_id, calendar).await?,
+    };
+
+    if !allowed {
+        return Err(AppError::AuthenticationError("Access denied".to_string()));
+    }
+
+    Ok(())
+}
+
+/// Load a calendar, checking that the user has the required access to it
+pub async fn authorize_calendar(
+    service: &CalendarService,
+    user_id: Uuid,
+    calendar_id: Uuid,
+    access: Access,
+) -> Result<Calendar, AppError> {
+    let calendar = service.get_calendar_by_id(calendar_id).await?
+        .ok_or(AppError::NotFoundError("Calendar not found".to_string()))?;
+
+    require(service, user_id, &calendar, access).await?;
+
+    Ok(calendar)
+}
+
+/// Load a calendar for an endpoint that also serves anonymous requests. Public calendars
+/// are readable by everyone; anyone else gets a 404 so private calendars stay hidden.
+pub async fn authorize_calendar_read(
+    service: &CalendarService,
+    user_id: Option<Uuid>,
+    calendar_id: Uuid,
+) -> Result<Calendar, AppError> {
+    let calendar = service.get_calendar_by_id(calendar_id).await?
+        .ok_or(AppError::NotFoundError("Calendar not found".to_string()))?;
+
+    let readable = match user_id {
+        Some(user_id) => can_view(service, user_id, &calendar).await?,
+        None => calendar.is_public,
+    };
+    if !readable {
+        return Err(AppError::NotFoundError("Calendar not found".to_string()));
+    }
+
+    Ok(calendar)
+}
+
+/// Load an event, checking that the user has the required access to its calendar
+pub async fn authorize_event(
+    service: &CalendarService,
+    user_id: Uuid,
+    event_id: Uuid,
+    access: Access,
+) -> Result<Event, AppError> {
+    let event = service.get_event_by_id(event_id).await?
+        .ok_or(AppError::NotFoundError("Event not found".to_string()))?;
+
+    authorize_calendar(service, user_id, event.calendar_id, access).await?;
+
+    Ok(event)
+}
+
+/// Load the calendar of an event, or of a deleted event that can still be restored,
+/// checking that the user has the required access to it
+pub async fn authorize_event_history(
+    service: &CalendarService,
+    user_id: Uuid,
+    event_id: Uuid,
+    access: Access,
+) -> Result<Calendar, AppError> {
+    let calendar_id = service.get_event_history_calendar_id(event_id).await?
+        .ok_or(AppError::NotFoundError("Event not found".to_string()))?;
+
+    authorize_calendar(service, user_id, calendar_id, access).await
+}
+
+/// Load a group the user belongs to; managing it (`Access::Admin`) needs the admin role
+pub async fn authorize_group(
+    service: &CalendarService,
+    user_id: Uuid,
+    group_id: Uuid,
+    access: Access,
+) -> Result<(Group, GroupRole), AppError> {
+    let group = service.get_group_by_id(group_id).await?
+        .ok_or(AppError::NotFoundError("Group not found".to_string()))?;
+    let role = service.get_group_role(group_id, user_id).await?
+        .ok_or(AppError::NotFoundError("Group not found".to_string()))?;
+
+    if access == Access::Admin && role != GroupRole::Admin {
+        return Err(AppError::AuthenticationError("Access denied".to_string()));
+    }
+
+    Ok((group, role))
+}
+
+/// Load an event for an endpoint that also serves anonymous requests, with the same
+/// rules as [`authorize_calendar_read`]
+pub async fn authorize_event_read(
+    service: &CalendarService,
+    user_id: Option<Uuid>,
+    event_id: Uuid,
+) -> Result<Event, AppError> {
+    let event = service.get_event_by_id(event_id).await?
+        .ok_or(AppError::NotFoundError("Event not found".to_string()))?;
+
+    authorize_calendar_read(service, user_id, event.calendar_id).await
+        .map_err(|_| AppError::NotFoundError("Event not found".to_string()))?;
+
+    Ok(event)
+}
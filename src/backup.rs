@@ -0,0 +1,121 @@
+//! Scheduled SQLite backups and restoring from them
+//!
+//! Backups are consistent snapshots written with `VACUUM INTO` while the server keeps
+//! running. Only the newest `keep` files are kept. Restoring replaces the database file
+//! and must happen while the server is stopped (`my_cal_dav_server restore <file>`).
+
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use crate::services::CalendarService;
+
+/// Default directory backups are written to
+pub const DEFAULT_BACKUP_DIR: &str = "./data/backups";
+
+/// Default time between scheduled backups in seconds (daily)
+pub const DEFAULT_BACKUP_INTERVAL_SECS: u64 = 86_400;
+
+/// Default number of backups kept
+pub const DEFAULT_BACKUP_KEEP: usize = 7;
+
+const FILE_PREFIX: &str = "calendar-";
+const FILE_SUFFIX: &str = ".db";
+
+/// First bytes of every SQLite 3 database file
+const SQLITE_HEADER: &[u8] = b"SQLite format 3\0";
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct BackupConfig {
+    pub dir: String,
+    /// Seconds between scheduled backups; 0 disables them
+    pub interval_secs: u64,
+    /// Number of backups kept; older ones are deleted after each backup
+    pub keep: usize,
+}
+
+impl Default for BackupConfig {
+    fn default() -> Self {
+        Self {
+            dir: DEFAULT_BACKUP_DIR.to_string(),
+            interval_secs: DEFAULT_BACKUP_INTERVAL_SECS,
+            keep: DEFAULT_BACKUP_KEEP,
+        }
+    }
+}
+
+/// A backup file in the backup directory
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BackupInfo {
+    pub name: String,
+    pub size: u64,
+    pub created_at: DateTime<Utc>,
+}
+
+/// File name of a backup taken at `time`; names sort by age
+pub fn file_name(time: DateTime<Utc>) -> String {
+    format!("{}{}{}", FILE_PREFIX, time.format("%Y%m%d-%H%M%S"), FILE_SUFFIX)
+}
+
+/// Whether `name` is a backup file name, which also rules out path traversal
+pub fn is_backup_name(name: &str) -> bool {
+    name.strip_prefix(FILE_PREFIX)
+        .and_then(|rest| rest.strip_suffix(FILE_SUFFIX))
+        .is_some_and(|stamp| stamp.len() == 15 && stamp.chars().all(|c| c.is_ascii_digit() || c == '-'))
+}
+
+/// Back up every `interval` for as long as the server runs. The first backup is taken
+/// one interval after startup; a zero interval disables scheduled backups.
+pub fn spawn_backups(service: CalendarService, interval: Duration) {
+    if interval.is_zero() {
+        tracing::info!("Scheduled backups are disabled");
+        return;
+    }
+
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval_at(tokio::time::Instant::now() + interval, interval);
+        loop {
+            ticker.tick().await;
+            match service.create_backup().await {
+                Ok(backup) => tracing::info!(name = %backup.name, size = backup.size, "Database backed up"),
+                Err(e) => tracing::error!("Database backup failed: {:?}", e),
+            }
+        }
+    });
+}
+
+/// Path of the database file behind a `sqlite:` URL
+pub fn database_path(database_url: &str) -> Option<PathBuf> {
+    let path = database_url.strip_prefix("sqlite://").or_else(|| database_url.strip_prefix("sqlite:"))?;
+    let path = path.split('?').next().unwrap_or_default();
+    (!path.is_empty() && path != ":memory:").then(|| PathBuf::from(path))
+}
+
+/// Replace the database behind `database_url` with `backup`. The current database is
+/// kept next to it as `<name>.before-restore`. Run only while the server is stopped.
+pub fn restore(database_url: &str, backup: &Path) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    let database = database_path(database_url)
+        .ok_or_else(|| format!("{} is not a database file URL", database_url))?;
+
+    let mut header = [0u8; SQLITE_HEADER.len()];
+    std::io::Read::read_exact(&mut std::fs::File::open(backup)?, &mut header)
+        .map_err(|_| format!("{} is not an SQLite database", backup.display()))?;
+    if header != SQLITE_HEADER {
+        return Err(format!("{} is not an SQLite database", backup.display()).into());
+    }
+
+    if database.exists() {
+        let mut previous = database.clone().into_os_string();
+        previous.push(".before-restore");
+        std::fs::rename(&database, &previous)?;
+    }
+    // Leftover journal files belong to the replaced database
+    for suffix in ["-wal", "-shm"] {
+        let mut journal = database.clone().into_os_string();
+        journal.push(suffix);
+        let _ = std::fs::remove_file(journal);
+    }
+    std::fs::copy(backup, &database)?;
+
+    Ok(database)
+}
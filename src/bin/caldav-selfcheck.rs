@@ -0,0 +1,298 @@
+//! End-to-end CalDAV sync check against a running instance
+//!
+//! Runs the same cycle a calendar client does (discovery, create calendar, PUT event,
+//! query, update, delete) through whatever sits in front of the server, so a broken
+//! reverse proxy or TLS setup shows up as a failing step.
+//!
+//! Usage: `caldav-selfcheck <base-url> <email> [password]`. The password may instead be
+//! given in `CALDAV_SELFCHECK_PASSWORD` to keep it out of the shell history.
+
+use reqwest::{header, redirect, Client, Method, StatusCode, Url};
+
+const PROPFIND_BODY: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<d:propfind xmlns:d="DAV:">
+    <d:prop>
+        <d:resourcetype/>
+        <d:displayname/>
+    </d:prop>
+</d:propfind>"#;
+
+const CALENDAR_QUERY_BODY: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<c:calendar-query xmlns:d="DAV:" xmlns:c="urn:ietf:params:xml:ns:caldav">
+    <d:prop>
+        <d:getetag/>
+        <c:calendar-data/>
+    </d:prop>
+    <c:filter>
+        <c:comp-filter name="VCALENDAR">
+            <c:comp-filter name="VEVENT"/>
+        </c:comp-filter>
+    </c:filter>
+</c:calendar-query>"#;
+
+struct Reply {
+    status: StatusCode,
+    location: Option<String>,
+    body: String,
+}
+
+struct SelfCheck {
+    client: Client,
+    email: String,
+    password: String,
+    /// Outcome per step; `None` when the step was skipped after an earlier failure
+    results: Vec<(&'static str, Option<Result<String, String>>)>,
+}
+
+impl SelfCheck {
+    async fn send(&self, method: &str, url: &Url, depth: Option<&str>, body: Option<(&str, String)>) -> Result<Reply, String> {
+        let method = Method::from_bytes(method.as_bytes()).map_err(|e| e.to_string())?;
+        let mut request = self.client
+            .request(method, url.clone())
+            .basic_auth(&self.email, Some(&self.password));
+        if let Some(depth) = depth {
+            request = request.header("Depth", depth);
+        }
+        if let Some((content_type, body)) = body {
+            request = request.header(header::CONTENT_TYPE, content_type).body(body);
+        }
+
+        let response = request.send().await.map_err(|e| format!("request failed: {}", e))?;
+        let status = response.status();
+        let location = response.headers().get(header::LOCATION)
+            .and_then(|v| v.to_str().ok())
+            .map(str::to_string);
+        let body = response.text().await.map_err(|e| format!("reading response failed: {}", e))?;
+
+        if status == StatusCode::UNAUTHORIZED {
+            return Err("401 Unauthorized - check the credentials".to_string());
+        }
+        Ok(Reply { status, location, body })
+    }
+
+    fn record<T>(&mut self, step: &'static str, result: Result<(T, String), String>) -> Option<T> {
+        match result {
+            Ok((value, detail)) => {
+                self.results.push((step, Some(Ok(detail))));
+                Some(value)
+            }
+            Err(e) => {
+                self.results.push((step, Some(Err(e))));
+                None
+            }
+        }
+    }
+
+    fn skip(&mut self, steps: &[&'static str]) {
+        for step in steps {
+            self.results.push((step, None));
+        }
+    }
+}
+
+/// Resolve a `Location` header (absolute or relative) against the request URL
+fn resolve(base: &Url, location: Option<&str>) -> Result<Url, String> {
+    let location = location.ok_or("response has no Location header")?;
+    base.join(location).map_err(|e| format!("invalid Location {:?}: {}", location, e))
+}
+
+fn expect(reply: &Reply, expected: &[StatusCode], what: &str) -> Result<(), String> {
+    if expected.contains(&reply.status) {
+        Ok(())
+    } else {
+        Err(format!("{} returned {}", what, reply.status))
+    }
+}
+
+fn event_ics(uid: &str, summary: &str) -> String {
+    let start = chrono::Utc::now() + chrono::Duration::days(1);
+    let end = start + chrono::Duration::hours(1);
+    format!(
+        "BEGIN:VCALENDAR\r\n\
+         VERSION:2.0\r\n\
+         PRODID:-//My CalDAV Server//selfcheck//EN\r\n\
+         BEGIN:VEVENT\r\n\
+         UID:{}\r\n\
+         DTSTAMP:{}\r\n\
+         DTSTART:{}\r\n\
+         DTEND:{}\r\n\
+         SUMMARY:{}\r\n\
+         END:VEVENT\r\n\
+         END:VCALENDAR\r\n",
+        uid,
+        chrono::Utc::now().format("%Y%m%dT%H%M%SZ"),
+        start.format("%Y%m%dT%H%M%SZ"),
+        end.format("%Y%m%dT%H%M%SZ"),
+        summary,
+    )
+}
+
+async fn discover(check: &SelfCheck, base_url: &Url) -> Result<(Url, String), String> {
+    let url = base_url.join(".well-known/caldav").map_err(|e| e.to_string())?;
+    let reply = check.send("GET", &url, None, None).await?;
+
+    let root = if reply.status.is_redirection() {
+        resolve(&url, reply.location.as_deref())?
+    } else if reply.status == StatusCode::OK {
+        resolve(&url, Some(reply.body.trim()))?
+    } else {
+        return Err(format!("GET {} returned {}", url, reply.status));
+    };
+
+    let detail = format!("CalDAV root is {}", root);
+    Ok((root, detail))
+}
+
+async fn run(check: &mut SelfCheck, base_url: Url) {
+    let discovered = discover(check, &base_url).await;
+    let Some(root) = check.record("discovery", discovered) else {
+        check.skip(&["list calendars", "create calendar", "create event", "query events", "update event", "delete event", "delete calendar"]);
+        return;
+    };
+
+    let listing = check.send("PROPFIND", &root, Some("1"), Some(("application/xml; charset=utf-8", PROPFIND_BODY.to_string()))).await
+        .and_then(|reply| {
+            expect(&reply, &[StatusCode::MULTI_STATUS], "PROPFIND")?;
+            Ok(((), format!("{} calendar(s) listed", reply.body.matches("<cal:calendar/>").count())))
+        });
+    check.record("list calendars", listing);
+
+    let stamp = chrono::Utc::now().format("%Y%m%dT%H%M%S");
+    let mkcol_body = format!(
+        r#"<?xml version="1.0" encoding="utf-8"?>
+<d:mkcol xmlns:d="DAV:"><d:set><d:prop><d:displayname>Selfcheck {}</d:displayname></d:prop></d:set></d:mkcol>"#,
+        stamp
+    );
+    let created = async {
+        let url = root.join("new").map_err(|e| e.to_string())?;
+        let reply = check.send("MKCOL", &url, None, Some(("application/xml; charset=utf-8", mkcol_body))).await?;
+        expect(&reply, &[StatusCode::CREATED], "MKCOL")?;
+        let calendar = resolve(&url, reply.location.as_deref())?;
+        let detail = format!("created {}", calendar);
+        Ok((calendar, detail))
+    }.await;
+    let Some(calendar) = check.record("create calendar", created) else {
+        check.skip(&["create event", "query events", "update event", "delete event", "delete calendar"]);
+        return;
+    };
+
+    let uid = format!("selfcheck-{}", uuid::Uuid::new_v4());
+    let summary = format!("Selfcheck event {}", stamp);
+    let put = async {
+        let url = calendar.join(&format!("{}.ics", uid)).map_err(|e| e.to_string())?;
+        let reply = check.send("PUT", &url, None, Some(("text/calendar; charset=utf-8", event_ics(&uid, &summary)))).await?;
+        expect(&reply, &[StatusCode::CREATED, StatusCode::NO_CONTENT], "PUT")?;
+        let event = match reply.location.as_deref() {
+            Some(location) => resolve(&url, Some(location))?,
+            None => url,
+        };
+        let detail = format!("created {}", event);
+        Ok((event, detail))
+    }.await;
+
+    if let Some(event) = check.record("create event", put) {
+        let query = check.send("REPORT", &calendar, Some("1"), Some(("application/xml; charset=utf-8", CALENDAR_QUERY_BODY.to_string()))).await
+            .and_then(|reply| {
+                expect(&reply, &[StatusCode::MULTI_STATUS], "REPORT")?;
+                if !reply.body.contains(&summary) {
+                    return Err("REPORT response does not contain the new event".to_string());
+                }
+                Ok(((), "event found via calendar-query".to_string()))
+            });
+        check.record("query events", query);
+
+        let updated_summary = format!("{} (updated)", summary);
+        let update = async {
+            let reply = check.send("PUT", &event, None, Some(("text/calendar; charset=utf-8", event_ics(&uid, &updated_summary)))).await?;
+            expect(&reply, &[StatusCode::OK, StatusCode::CREATED, StatusCode::NO_CONTENT], "PUT")?;
+            let reply = check.send("GET", &event, None, None).await?;
+            expect(&reply, &[StatusCode::OK], "GET")?;
+            if !reply.body.contains(&updated_summary) {
+                return Err("GET after update still returns the old data".to_string());
+            }
+            Ok(((), "change visible on GET".to_string()))
+        }.await;
+        check.record("update event", update);
+
+        let delete = async {
+            let reply = check.send("DELETE", &event, None, None).await?;
+            expect(&reply, &[StatusCode::OK, StatusCode::NO_CONTENT], "DELETE")?;
+            let reply = check.send("GET", &event, None, None).await?;
+            expect(&reply, &[StatusCode::NOT_FOUND, StatusCode::GONE], "GET after DELETE")?;
+            Ok(((), "event gone".to_string()))
+        }.await;
+        check.record("delete event", delete);
+    } else {
+        check.skip(&["query events", "update event", "delete event"]);
+    }
+
+    let cleanup = check.send("DELETE", &calendar, None, None).await
+        .and_then(|reply| {
+            expect(&reply, &[StatusCode::OK, StatusCode::NO_CONTENT], "DELETE")?;
+            Ok(((), "test calendar removed".to_string()))
+        });
+    check.record("delete calendar", cleanup);
+}
+
+#[tokio::main]
+async fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let (base_url, email) = match (args.first(), args.get(1)) {
+        (Some(base_url), Some(email)) => (base_url.clone(), email.clone()),
+        _ => {
+            eprintln!("Usage: caldav-selfcheck <base-url> <email> [password]");
+            eprintln!("The password can also be set in CALDAV_SELFCHECK_PASSWORD.");
+            std::process::exit(2);
+        }
+    };
+    let Some(password) = args.get(2).cloned().or_else(|| std::env::var("CALDAV_SELFCHECK_PASSWORD").ok()) else {
+        eprintln!("Error: no password given (argument or CALDAV_SELFCHECK_PASSWORD)");
+        std::process::exit(2);
+    };
+
+    // Keep a trailing slash so relative joins stay below a sub-path deployment
+    let base_url = match Url::parse(&format!("{}/", base_url.trim_end_matches('/'))) {
+        Ok(url) => url,
+        Err(e) => {
+            eprintln!("Error: invalid base URL: {}", e);
+            std::process::exit(2);
+        }
+    };
+
+    // Redirects are followed by hand so the discovery step can report them
+    let client = Client::builder()
+        .redirect(redirect::Policy::none())
+        .build()
+        .expect("failed to build HTTP client");
+
+    let mut check = SelfCheck {
+        client,
+        email,
+        password,
+        results: Vec::new(),
+    };
+
+    println!("CalDAV selfcheck against {}", base_url);
+    run(&mut check, base_url).await;
+
+    let mut failed = false;
+    for (step, result) in &check.results {
+        match result {
+            Some(Ok(detail)) => println!("  PASS  {:<16} {}", step, detail),
+            None => {
+                failed = true;
+                println!("  SKIP  {}", step);
+            }
+            Some(Err(e)) => {
+                failed = true;
+                println!("  FAIL  {:<16} {}", step, e);
+            }
+        }
+    }
+
+    if failed {
+        println!("Result: FAILED");
+        std::process::exit(1);
+    }
+    println!("Result: all checks passed");
+}
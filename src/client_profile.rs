@@ -0,0 +1,103 @@
+//! Downloadable connection settings for calendar clients, so devices can be set up
+//! without DNS-based autodiscovery
+
+use serde::Serialize;
+use uuid::Uuid;
+
+/// Prefix of the payload identifiers in generated Apple profiles
+const PROFILE_IDENTIFIER: &str = "local.mycaldav.profile";
+
+/// Everything a client needs to connect one account
+#[derive(Debug, Clone, Serialize)]
+pub struct ConnectionProfile {
+    /// Instance name chosen during setup, used to label the account on the device
+    pub instance_name: String,
+    /// Public server URL, including any path prefix
+    pub server_url: String,
+    /// Full URL of the CalDAV root
+    pub caldav_url: String,
+    /// Path of the principal / calendar home collection
+    pub principal_path: String,
+    pub username: String,
+}
+
+impl ConnectionProfile {
+    /// Apple configuration profile (`.mobileconfig`) with a single CalDAV account
+    /// payload. The password is left out; the device asks for it on install.
+    pub fn to_mobileconfig(&self) -> String {
+        let url = reqwest::Url::parse(&self.server_url).ok();
+        let host = url.as_ref().and_then(|u| u.host_str()).unwrap_or("localhost").to_string();
+        let use_ssl = url.as_ref().map(|u| u.scheme() == "https").unwrap_or(false);
+        let port = url.as_ref()
+            .and_then(|u| u.port_or_known_default())
+            .unwrap_or(if use_ssl { 443 } else { 80 });
+
+        format!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+    <key>PayloadContent</key>
+    <array>
+        <dict>
+            <key>CalDAVAccountDescription</key>
+            <string>{instance_name}</string>
+            <key>CalDAVHostName</key>
+            <string>{host}</string>
+            <key>CalDAVPort</key>
+            <integer>{port}</integer>
+            <key>CalDAVPrincipalURL</key>
+            <string>{principal}</string>
+            <key>CalDAVUseSSL</key>
+            <{use_ssl}/>
+            <key>CalDAVUsername</key>
+            <string>{username}</string>
+            <key>PayloadDescription</key>
+            <string>Configures the CalDAV account</string>
+            <key>PayloadDisplayName</key>
+            <string>CalDAV ({host})</string>
+            <key>PayloadIdentifier</key>
+            <string>{identifier}.caldav</string>
+            <key>PayloadType</key>
+            <string>com.apple.caldav.account</string>
+            <key>PayloadUUID</key>
+            <string>{account_uuid}</string>
+            <key>PayloadVersion</key>
+            <integer>1</integer>
+        </dict>
+    </array>
+    <key>PayloadDisplayName</key>
+    <string>{instance_name} ({username})</string>
+    <key>PayloadIdentifier</key>
+    <string>{identifier}</string>
+    <key>PayloadRemovalDisallowed</key>
+    <false/>
+    <key>PayloadType</key>
+    <string>Configuration</string>
+    <key>PayloadUUID</key>
+    <string>{profile_uuid}</string>
+    <key>PayloadVersion</key>
+    <integer>1</integer>
+</dict>
+</plist>
+"#,
+            host = escape_plist(&host),
+            instance_name = escape_plist(&self.instance_name),
+            port = port,
+            principal = escape_plist(&self.principal_path),
+            use_ssl = use_ssl,
+            username = escape_plist(&self.username),
+            // Stable per host and account, so installing again replaces the old profile
+            identifier = escape_plist(&format!("{}.{}.{}", PROFILE_IDENTIFIER, host, self.username)),
+            account_uuid = Uuid::new_v4(),
+            profile_uuid = Uuid::new_v4(),
+        )
+    }
+}
+
+/// Escape text for a plist `<string>` value
+fn escape_plist(s: &str) -> String {
+    s.replace('&', "&amp;")
+     .replace('<', "&lt;")
+     .replace('>', "&gt;")
+}
@@ -0,0 +1,35 @@
+//! Compression of large text responses
+//!
+//! Multistatus responses, iCalendar exports and feeds and JSON listings are sent
+//! compressed with gzip or deflate to clients that accept it, which saves most of the
+//! bandwidth of syncing a big calendar. Other content types are sent as they are:
+//! attachments and images are usually compressed already.
+
+use axum::http::{header, Extensions, HeaderMap, StatusCode, Version};
+use tower_http::compression::predicate::{Predicate, SizeAbove};
+use tower_http::compression::CompressionLayer;
+
+/// Smallest body worth compressing, in bytes
+const MIN_SIZE: u16 = 1024;
+
+/// Media types compressed, besides any `+xml` and `+json` type
+const COMPRESSED_TYPES: [&str; 5] = ["application/xml", "text/xml", "text/calendar", "application/json", "text/csv"];
+
+/// Whether responses of a content type are compressed
+fn is_compressible(content_type: &str) -> bool {
+    let media_type = content_type.split(';').next().unwrap_or_default().trim().to_ascii_lowercase();
+    COMPRESSED_TYPES.contains(&media_type.as_str())
+        || media_type.ends_with("+xml")
+        || media_type.ends_with("+json")
+}
+
+/// Layer compressing the allowed content types; with `enabled` off every response is
+/// sent uncompressed
+pub fn compression_layer(enabled: bool) -> CompressionLayer<impl Predicate> {
+    let allowed = move |_: StatusCode, _: Version, headers: &HeaderMap, _: &Extensions| {
+        enabled && headers.get(header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(is_compressible)
+    };
+    CompressionLayer::new().compress_when(SizeAbove::new(MIN_SIZE).and(allowed))
+}
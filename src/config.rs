@@ -1,10 +1,62 @@
+use std::fmt::Display;
+use std::path::Path;
+use std::str::FromStr;
+
 use serde::{Deserialize, Serialize};
 
+use crate::backup::BackupConfig;
+use crate::database::PoolConfig;
+use crate::lockout::LockoutPolicy;
+use crate::maintenance::{RetentionPolicy, DEFAULT_PRUNE_INTERVAL_SECS};
+use crate::oidc::OidcConfig;
+use crate::onboarding::{parse_default_calendars, Onboarding};
+use crate::quota::Quotas;
+use crate::rate_limit::{DEFAULT_RATE_LIMIT_REQUESTS, DEFAULT_RATE_LIMIT_WINDOW_SECS};
+use crate::settings::{RegistrationPolicy, SettingsOverrides, SmtpSettings, DEFAULT_SMTP_PORT};
+use crate::storage::{DEFAULT_ATTACHMENTS_DIR, DEFAULT_MAX_ATTACHMENT_SIZE};
+use crate::tls::{TlsConfig, DEFAULT_ACME_CACHE_DIR};
+
+/// Configuration file read when `CONFIG_FILE` isn't set; it's optional
+pub const DEFAULT_CONFIG_FILE: &str = "./config.toml";
+
+const DEFAULT_JWT_SECRET: &str = "your-secret-key-change-in-production";
+
+/// Keyring service secrets are stored under when referenced as `keyring:<entry>`
+#[cfg(feature = "keyring")]
+pub const KEYRING_SERVICE: &str = "my-caldav-server";
+
+const KEYRING_PREFIX: &str = "keyring:";
+
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct Config {
     pub port: u16,
     pub database_url: String,
+    pub database_pool: PoolConfig,
     pub jwt_secret: String,
+    pub attachments_dir: String,
+    pub max_attachment_size: usize,
+    pub rate_limit_requests: u32,
+    pub rate_limit_window_secs: u64,
+    pub onboarding: Onboarding,
+    pub oidc: Option<OidcConfig>,
+    /// Public URL of the server, used for generated links and DAV hrefs
+    pub base_url: String,
+    /// Seconds between pruning runs; 0 disables scheduled pruning
+    pub prune_interval_secs: u64,
+    pub retention: RetentionPolicy,
+    /// Native HTTPS; `None` serves plain HTTP, e.g. behind a TLS-terminating proxy
+    pub tls: Option<TlsConfig>,
+    /// Registration policy and mail server pinned over the setup wizard's values
+    pub settings_overrides: SettingsOverrides,
+    pub backup: BackupConfig,
+    pub quotas: Quotas,
+    pub lockout: LockoutPolicy,
+    /// Count requests for DAV features the server doesn't implement
+    pub dav_usage_stats: bool,
+    /// Origins of browser apps allowed to call the API and CalDAV; `*` allows any
+    pub cors_origins: Vec<String>,
+    /// Compress XML, iCalendar and JSON responses for clients that accept it
+    pub compression: bool,
 }
 
 impl Default for Config {
@@ -12,23 +64,445 @@ impl Default for Config {
         Self {
             port: 8080,
             database_url: "sqlite:./data/calendar.db?mode=rwc".to_string(),
-            jwt_secret: "your-secret-key-change-in-production".to_string(),
+            database_pool: PoolConfig::default(),
+            jwt_secret: DEFAULT_JWT_SECRET.to_string(),
+            attachments_dir: DEFAULT_ATTACHMENTS_DIR.to_string(),
+            max_attachment_size: DEFAULT_MAX_ATTACHMENT_SIZE,
+            rate_limit_requests: DEFAULT_RATE_LIMIT_REQUESTS,
+            rate_limit_window_secs: DEFAULT_RATE_LIMIT_WINDOW_SECS,
+            onboarding: Onboarding::default(),
+            oidc: None,
+            base_url: String::new(),
+            prune_interval_secs: DEFAULT_PRUNE_INTERVAL_SECS,
+            retention: RetentionPolicy::default(),
+            tls: None,
+            settings_overrides: SettingsOverrides::default(),
+            backup: BackupConfig::default(),
+            quotas: Quotas::default(),
+            lockout: LockoutPolicy::default(),
+            dav_usage_stats: false,
+            cors_origins: Vec::new(),
+            compression: true,
         }
     }
 }
 
 impl Config {
+    /// Defaults, overlaid with the configuration file (`CONFIG_FILE`, or `config.toml`
+    /// if present) and then with environment variables. Fails on unreadable files,
+    /// unknown keys, unparsable variables and invalid values instead of falling back.
+    pub fn load() -> Result<Self, ConfigError> {
+        let mut config = match std::env::var("CONFIG_FILE") {
+            Ok(path) => Self::from_file(&path)?,
+            Err(_) if Path::new(DEFAULT_CONFIG_FILE).exists() => Self::from_file(DEFAULT_CONFIG_FILE)?,
+            Err(_) => Self::default(),
+        };
+        config.apply_env()?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Defaults overlaid with a TOML configuration file
+    pub fn from_file(path: &str) -> Result<Self, ConfigError> {
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| ConfigError(format!("cannot read {}: {}", path, e)))?;
+        let file: FileConfig = toml::from_str(&contents)
+            .map_err(|e| ConfigError(format!("invalid {}: {}", path, e)))?;
+
+        let mut config = Self::default();
+        file.apply(&mut config)?;
+        Ok(config)
+    }
+
+    /// Defaults overlaid with environment variables only
     pub fn from_env() -> Result<Self, ConfigError> {
-        Ok(Self {
-            port: std::env::var("PORT")
-                .ok()
-                .and_then(|s| s.parse().ok())
-                .unwrap_or(8080),
-            database_url: std::env::var("DATABASE_URL")
-                .unwrap_or_else(|_| "sqlite:./data/calendar.db?mode=rwc".to_string()),
-            jwt_secret: std::env::var("JWT_SECRET")
-                .unwrap_or_else(|_| "your-secret-key-change-in-production".to_string()),
-        })
+        let mut config = Self::default();
+        config.apply_env()?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Override settings with the environment variables that are set
+    fn apply_env(&mut self) -> Result<(), ConfigError> {
+        set(&mut self.port, env_parse("PORT")?);
+        set(&mut self.database_url, env_string("DATABASE_URL"));
+        set(&mut self.database_pool.max_connections, env_parse("DATABASE_MAX_CONNECTIONS")?);
+        set(&mut self.database_pool.busy_timeout_secs, env_parse("DATABASE_BUSY_TIMEOUT_SECS")?);
+        set(&mut self.jwt_secret, env_secret("JWT_SECRET")?);
+        set(&mut self.attachments_dir, env_string("ATTACHMENTS_DIR"));
+        set(&mut self.max_attachment_size, env_parse("MAX_ATTACHMENT_SIZE")?);
+        set(&mut self.rate_limit_requests, env_parse("RATE_LIMIT_REQUESTS")?);
+        set(&mut self.rate_limit_window_secs, env_parse("RATE_LIMIT_WINDOW_SECS")?);
+        if let Some(max) = env_parse("MAX_CALENDARS_PER_USER")? {
+            self.quotas.max_calendars_per_user = Some(max);
+        }
+        if let Some(max) = env_parse("MAX_EVENTS_PER_CALENDAR")? {
+            self.quotas.max_events_per_calendar = Some(max);
+        }
+        set(&mut self.quotas.max_ics_size, env_parse("MAX_ICS_SIZE")?);
+        set(&mut self.lockout.max_failed_logins, env_parse("MAX_FAILED_LOGINS")?);
+        set(&mut self.lockout.lockout_minutes, env_parse("LOCKOUT_MINUTES")?);
+        if let Some(calendars) = env_string("DEFAULT_CALENDARS") {
+            self.onboarding.calendars = parse_default_calendars(&calendars);
+        }
+        set(&mut self.onboarding.starter_event, env_parse("STARTER_EVENT")?);
+        set(&mut self.dav_usage_stats, env_parse("DAV_USAGE_STATS")?);
+        if let Some(origins) = env_string("CORS_ORIGINS") {
+            self.cors_origins = parse_origins(&origins);
+        }
+        set(&mut self.compression, env_parse("COMPRESSION")?);
+        if let Some(oidc) = OidcConfig::from_env() {
+            self.oidc = Some(oidc);
+        }
+        if let (Some(secret), Some(oidc)) = (env_secret("OIDC_CLIENT_SECRET")?, self.oidc.as_mut()) {
+            oidc.client_secret = secret;
+        }
+        set(&mut self.base_url, env_string("BASE_URL").or_else(|| env_string("PUBLIC_URL")));
+        set(&mut self.prune_interval_secs, env_parse("PRUNE_INTERVAL_SECS")?);
+        if let Some(days) = env_parse("AUDIT_LOG_RETENTION_DAYS")? {
+            self.retention.audit_log_days = Some(days);
+        }
+        set(&mut self.retention.event_versions, env_parse("EVENT_HISTORY_VERSIONS")?);
+        if let Some(days) = env_parse("EVENT_HISTORY_RETENTION_DAYS")? {
+            self.retention.event_version_days = Some(days);
+        }
+        set(&mut self.retention.login_attempt_days, env_parse("LOGIN_HISTORY_RETENTION_DAYS")?);
+        set(&mut self.backup.dir, env_string("BACKUP_DIR"));
+        set(&mut self.backup.interval_secs, env_parse("BACKUP_INTERVAL_SECS")?);
+        set(&mut self.backup.keep, env_parse("BACKUP_KEEP")?);
+        if let Some(tls) = TlsConfig::from_env() {
+            self.tls = Some(tls);
+        }
+        if let Some(registration) = env_string("REGISTRATION") {
+            self.settings_overrides.registration = Some(parse_registration(&registration)?);
+        }
+        let smtp_password = env_secret("SMTP_PASSWORD")?;
+        if let Some(host) = env_string("SMTP_HOST") {
+            let smtp = self.settings_overrides.smtp.take();
+            self.settings_overrides.smtp = Some(SmtpSettings {
+                host,
+                port: env_parse("SMTP_PORT")?.or(smtp.as_ref().map(|s| s.port)).unwrap_or(DEFAULT_SMTP_PORT),
+                username: env_string("SMTP_USERNAME").or(smtp.as_ref().and_then(|s| s.username.clone())),
+                password: smtp_password.or(smtp.as_ref().and_then(|s| s.password.clone())),
+                from_address: env_string("SMTP_FROM").or(smtp.map(|s| s.from_address)).unwrap_or_default(),
+            });
+        } else if let (Some(password), Some(smtp)) = (smtp_password, self.settings_overrides.smtp.as_mut()) {
+            smtp.password = Some(password);
+        }
+        Ok(())
+    }
+
+    /// Reject settings the server can't run with
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        if self.port == 0 {
+            return Err(ConfigError("port must not be 0".to_string()));
+        }
+        if !self.database_url.starts_with("sqlite:") {
+            return Err(ConfigError(format!("unsupported database URL {:?}", self.database_url)));
+        }
+        if self.database_pool.max_connections == 0 {
+            return Err(ConfigError("database max_connections must be greater than 0".to_string()));
+        }
+        if self.jwt_secret.is_empty() {
+            return Err(ConfigError("JWT secret must not be empty".to_string()));
+        }
+        if self.jwt_secret == DEFAULT_JWT_SECRET {
+            tracing::warn!("Using the default JWT secret; set JWT_SECRET for any real deployment");
+        }
+        if self.max_attachment_size == 0 {
+            return Err(ConfigError("max_attachment_size must be greater than 0".to_string()));
+        }
+        if self.rate_limit_requests == 0 || self.rate_limit_window_secs == 0 {
+            return Err(ConfigError("rate limits must be greater than 0".to_string()));
+        }
+        if self.quotas.max_calendars_per_user == Some(0) || self.quotas.max_events_per_calendar == Some(0) || self.quotas.max_ics_size == 0 {
+            return Err(ConfigError("quotas must be greater than 0".to_string()));
+        }
+        if self.quotas.max_calendars_per_user.is_some_and(|max| max < self.onboarding.calendars.len()) {
+            return Err(ConfigError("max_calendars_per_user is lower than the number of default calendars".to_string()));
+        }
+        if self.lockout.lockout_minutes == 0 {
+            return Err(ConfigError("lockout_minutes must be greater than 0".to_string()));
+        }
+        if self.backup.keep == 0 {
+            return Err(ConfigError("backup keep must be greater than 0".to_string()));
+        }
+        if let Some(origin) = self.cors_origins.iter().find(|origin| !crate::cors::is_valid_origin(origin)) {
+            return Err(ConfigError(format!("CORS origin {:?} must be * or a scheme and host like https://app.example.com", origin)));
+        }
+        if !(self.base_url.is_empty() || self.base_url.starts_with("http://") || self.base_url.starts_with("https://")) {
+            return Err(ConfigError(format!("base URL {:?} must start with http:// or https://", self.base_url)));
+        }
+        if let Some(smtp) = &self.settings_overrides.smtp
+            && (smtp.host.is_empty() || !smtp.from_address.contains('@'))
+        {
+            return Err(ConfigError("SMTP needs a host and a sender address".to_string()));
+        }
+        match &self.tls {
+            Some(TlsConfig::Files { cert_path, key_path }) => {
+                for path in [cert_path, key_path] {
+                    if !Path::new(path).is_file() {
+                        return Err(ConfigError(format!("TLS file {} does not exist", path)));
+                    }
+                }
+            }
+            Some(TlsConfig::Acme { domains, .. }) if domains.is_empty() => {
+                return Err(ConfigError("ACME needs at least one domain".to_string()));
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+fn set<T>(field: &mut T, value: Option<T>) {
+    if let Some(value) = value {
+        *field = value;
+    }
+}
+
+fn env_string(name: &str) -> Option<String> {
+    std::env::var(name).ok()
+}
+
+/// Value of an environment variable; one that is set but doesn't parse is an error
+fn env_parse<T: FromStr>(name: &str) -> Result<Option<T>, ConfigError>
+where
+    T::Err: Display,
+{
+    env_string(name)
+        .map(|value| value.parse().map_err(|e| ConfigError(format!("invalid {}={:?}: {}", name, value, e))))
+        .transpose()
+}
+
+/// A secret given either directly or as the path of a file holding it, e.g. a Docker
+/// secret. Giving both is an error. `keyring:<entry>` values are read from the OS keyring.
+fn secret(name: &str, value: Option<String>, file: Option<String>) -> Result<Option<String>, ConfigError> {
+    let value = match (value, file) {
+        (Some(_), Some(_)) => return Err(ConfigError(format!("{} is set both directly and as a file", name))),
+        (Some(value), None) => value,
+        (None, Some(path)) => read_secret_file(name, &path)?,
+        (None, None) => return Ok(None),
+    };
+
+    match value.strip_prefix(KEYRING_PREFIX) {
+        Some(entry) => keyring_secret(name, entry).map(Some),
+        None => Ok(Some(value)),
+    }
+}
+
+/// Secret from `NAME` or from the file named by `NAME_FILE`
+fn env_secret(name: &str) -> Result<Option<String>, ConfigError> {
+    secret(name, env_string(name), env_string(&format!("{}_FILE", name)))
+}
+
+/// Contents of a secret file without the trailing line break editors and `echo` add
+fn read_secret_file(name: &str, path: &str) -> Result<String, ConfigError> {
+    let contents = std::fs::read_to_string(path)
+        .map_err(|e| ConfigError(format!("cannot read {} from {}: {}", name, path, e)))?;
+    let value = contents.trim_end_matches(['\r', '\n']);
+    if value.is_empty() {
+        return Err(ConfigError(format!("{} file {} is empty", name, path)));
+    }
+    Ok(value.to_string())
+}
+
+#[cfg(feature = "keyring")]
+fn keyring_secret(name: &str, entry: &str) -> Result<String, ConfigError> {
+    keyring::Entry::new(KEYRING_SERVICE, entry)
+        .and_then(|credential| credential.get_password())
+        .map_err(|e| ConfigError(format!("cannot read {} from keyring entry {:?}: {}", name, entry, e)))
+}
+
+#[cfg(not(feature = "keyring"))]
+fn keyring_secret(name: &str, _entry: &str) -> Result<String, ConfigError> {
+    Err(ConfigError(format!("{} refers to the OS keyring, but the server was built without the keyring feature", name)))
+}
+
+/// Comma-separated origins; a trailing slash is dropped, since browsers never send one
+fn parse_origins(value: &str) -> Vec<String> {
+    value.split(',')
+        .map(|origin| origin.trim().trim_end_matches('/').to_string())
+        .filter(|origin| !origin.is_empty())
+        .collect()
+}
+
+fn parse_registration(value: &str) -> Result<RegistrationPolicy, ConfigError> {
+    match value {
+        "open" => Ok(RegistrationPolicy::Open),
+        "closed" => Ok(RegistrationPolicy::Closed),
+        _ => Err(ConfigError(format!("registration must be \"open\" or \"closed\", not {:?}", value))),
+    }
+}
+
+/// Layout of `config.toml`; every key is optional and unknown keys are rejected
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct FileConfig {
+    port: Option<u16>,
+    base_url: Option<String>,
+    registration: Option<RegistrationPolicy>,
+    attachments_dir: Option<String>,
+    default_calendars: Option<String>,
+    starter_event: Option<bool>,
+    prune_interval_secs: Option<u64>,
+    audit_log_retention_days: Option<u32>,
+    event_history_versions: Option<usize>,
+    event_history_retention_days: Option<u32>,
+    login_history_retention_days: Option<u32>,
+    dav_usage_stats: Option<bool>,
+    cors_origins: Option<Vec<String>>,
+    compression: Option<bool>,
+    #[serde(default)]
+    database: FileDatabase,
+    #[serde(default)]
+    jwt: FileJwt,
+    #[serde(default)]
+    limits: FileLimits,
+    #[serde(default)]
+    backup: FileBackup,
+    smtp: Option<FileSmtp>,
+    tls: Option<FileTls>,
+    oidc: Option<FileOidc>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct FileDatabase {
+    url: Option<String>,
+    max_connections: Option<u32>,
+    busy_timeout_secs: Option<u64>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct FileJwt {
+    secret: Option<String>,
+    secret_file: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct FileLimits {
+    max_attachment_size: Option<usize>,
+    rate_limit_requests: Option<u32>,
+    rate_limit_window_secs: Option<u64>,
+    max_calendars_per_user: Option<usize>,
+    max_events_per_calendar: Option<usize>,
+    max_ics_size: Option<usize>,
+    max_failed_logins: Option<u32>,
+    lockout_minutes: Option<u32>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct FileBackup {
+    dir: Option<String>,
+    interval_secs: Option<u64>,
+    keep: Option<usize>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct FileSmtp {
+    host: String,
+    port: Option<u16>,
+    username: Option<String>,
+    password: Option<String>,
+    password_file: Option<String>,
+    from_address: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct FileOidc {
+    issuer: String,
+    client_id: String,
+    client_secret: Option<String>,
+    client_secret_file: Option<String>,
+    redirect_url: String,
+}
+
+/// Either `cert_path` and `key_path`, or `acme_domains`
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct FileTls {
+    cert_path: Option<String>,
+    key_path: Option<String>,
+    #[serde(default)]
+    acme_domains: Vec<String>,
+    acme_email: Option<String>,
+    acme_cache_dir: Option<String>,
+    #[serde(default)]
+    acme_staging: bool,
+}
+
+impl FileConfig {
+    fn apply(self, config: &mut Config) -> Result<(), ConfigError> {
+        set(&mut config.port, self.port);
+        set(&mut config.base_url, self.base_url);
+        set(&mut config.attachments_dir, self.attachments_dir);
+        if let Some(calendars) = self.default_calendars {
+            config.onboarding.calendars = parse_default_calendars(&calendars);
+        }
+        set(&mut config.onboarding.starter_event, self.starter_event);
+        set(&mut config.prune_interval_secs, self.prune_interval_secs);
+        config.retention.audit_log_days = self.audit_log_retention_days;
+        set(&mut config.retention.event_versions, self.event_history_versions);
+        config.retention.event_version_days = self.event_history_retention_days;
+        set(&mut config.retention.login_attempt_days, self.login_history_retention_days);
+        set(&mut config.dav_usage_stats, self.dav_usage_stats);
+        if let Some(origins) = self.cors_origins {
+            config.cors_origins = parse_origins(&origins.join(","));
+        }
+        set(&mut config.compression, self.compression);
+        set(&mut config.database_url, self.database.url);
+        set(&mut config.database_pool.max_connections, self.database.max_connections);
+        set(&mut config.database_pool.busy_timeout_secs, self.database.busy_timeout_secs);
+        set(&mut config.jwt_secret, secret("jwt.secret", self.jwt.secret, self.jwt.secret_file)?);
+        set(&mut config.max_attachment_size, self.limits.max_attachment_size);
+        set(&mut config.rate_limit_requests, self.limits.rate_limit_requests);
+        set(&mut config.rate_limit_window_secs, self.limits.rate_limit_window_secs);
+        config.quotas.max_calendars_per_user = self.limits.max_calendars_per_user;
+        config.quotas.max_events_per_calendar = self.limits.max_events_per_calendar;
+        set(&mut config.quotas.max_ics_size, self.limits.max_ics_size);
+        set(&mut config.lockout.max_failed_logins, self.limits.max_failed_logins);
+        set(&mut config.lockout.lockout_minutes, self.limits.lockout_minutes);
+        set(&mut config.backup.dir, self.backup.dir);
+        set(&mut config.backup.interval_secs, self.backup.interval_secs);
+        set(&mut config.backup.keep, self.backup.keep);
+        config.oidc = match self.oidc {
+            Some(oidc) => Some(OidcConfig {
+                client_secret: secret("oidc.client_secret", oidc.client_secret, oidc.client_secret_file)?.unwrap_or_default(),
+                issuer: oidc.issuer,
+                client_id: oidc.client_id,
+                redirect_url: oidc.redirect_url,
+            }),
+            None => None,
+        };
+        config.settings_overrides = SettingsOverrides {
+            registration: self.registration,
+            smtp: match self.smtp {
+                Some(smtp) => Some(SmtpSettings {
+                    password: secret("smtp.password", smtp.password, smtp.password_file)?,
+                    host: smtp.host,
+                    port: smtp.port.unwrap_or(DEFAULT_SMTP_PORT),
+                    username: smtp.username,
+                    from_address: smtp.from_address,
+                }),
+                None => None,
+            },
+        };
+        config.tls = self.tls.map(|tls| match (tls.cert_path, tls.key_path) {
+            (Some(cert_path), Some(key_path)) => TlsConfig::Files { cert_path, key_path },
+            _ => TlsConfig::Acme {
+                domains: tls.acme_domains,
+                contact_email: tls.acme_email,
+                cache_dir: tls.acme_cache_dir.unwrap_or_else(|| DEFAULT_ACME_CACHE_DIR.to_string()),
+                staging: tls.acme_staging,
+            },
+        });
+        Ok(())
     }
 }
 
@@ -0,0 +1,79 @@
+//! Cross-origin access for browser apps calling the REST API and CalDAV
+//!
+//! Only origins listed in `cors_origins` are allowed. The web UI authenticates with a
+//! cookie and never gets CORS headers, so other sites can't read its pages. API
+//! clients send their token in the `Authorization` header, which is why credentialed
+//! requests stay disabled.
+
+use std::time::Duration;
+
+use axum::http::{header, HeaderName, HeaderValue, Method, Request};
+use tower_http::cors::{AllowOrigin, CorsLayer};
+
+/// How long browsers may cache a preflight response
+const PREFLIGHT_MAX_AGE_SECS: u64 = 600;
+
+/// Methods browser apps may use, including the WebDAV ones CalDAV clients need
+const ALLOWED_METHODS: &[&str] = &[
+    "GET", "HEAD", "POST", "PUT", "PATCH", "DELETE",
+    "PROPFIND", "PROPPATCH", "REPORT", "MKCOL", "MKCALENDAR", "COPY", "MOVE",
+];
+
+/// `*` or an origin such as `https://app.example.com`, without a path
+pub fn is_valid_origin(origin: &str) -> bool {
+    if origin == "*" {
+        return true;
+    }
+    let Some(host) = origin.strip_prefix("https://").or_else(|| origin.strip_prefix("http://")) else {
+        return false;
+    };
+    !host.is_empty() && !host.contains('/') && HeaderValue::from_str(origin).is_ok()
+}
+
+/// CORS policy for the configured origins; `None` when cross-origin access is off
+pub fn cors_layer(origins: &[String]) -> Option<CorsLayer> {
+    if origins.is_empty() {
+        return None;
+    }
+
+    let allow_origin = if origins.iter().any(|origin| origin == "*") {
+        AllowOrigin::any()
+    } else {
+        AllowOrigin::list(origins.iter().filter_map(|origin| HeaderValue::from_str(origin).ok()))
+    };
+    let methods: Vec<Method> = ALLOWED_METHODS.iter()
+        .filter_map(|method| Method::from_bytes(method.as_bytes()).ok())
+        .collect();
+
+    Some(
+        CorsLayer::new()
+            .allow_origin(allow_origin)
+            .allow_methods(methods)
+            .allow_headers([
+                header::AUTHORIZATION,
+                header::CONTENT_TYPE,
+                header::ACCEPT,
+                header::IF_MATCH,
+                header::IF_NONE_MATCH,
+                HeaderName::from_static("depth"),
+                HeaderName::from_static("prefer"),
+                HeaderName::from_static("x-request-id"),
+            ])
+            .expose_headers([header::ETAG, header::LOCATION, HeaderName::from_static("x-request-id")])
+            .max_age(Duration::from_secs(PREFLIGHT_MAX_AGE_SECS)),
+    )
+}
+
+/// Whether CORS handling applies to a request: cross-origin requests from browsers to
+/// anything but the cookie-authenticated web UI. A plain `OPTIONS` without
+/// `Access-Control-Request-Method` is a CalDAV capability probe, not a preflight.
+pub fn applies_to<B>(req: &Request<B>) -> bool {
+    let path = req.uri().path();
+    if path.starts_with("/web/") || path.starts_with("/share/") || path == "/setup" {
+        return false;
+    }
+    if !req.headers().contains_key(header::ORIGIN) {
+        return false;
+    }
+    req.method() != Method::OPTIONS || req.headers().contains_key(header::ACCESS_CONTROL_REQUEST_METHOD)
+}
@@ -1,9 +1,50 @@
-use sqlx::{sqlite::SqlitePool, Executor};
+use sqlx::sqlite::{SqliteConnectOptions, SqliteJournalMode, SqlitePool, SqlitePoolOptions, SqliteSynchronous};
+use sqlx::Executor;
+use serde::{Deserialize, Serialize};
 use std::fs;
-use tracing::info;
-use bcrypt::{hash, DEFAULT_COST};
+use std::str::FromStr;
+use std::time::Duration;
+use tracing::{info, warn};
 use chrono::Utc;
-use uuid::Uuid;
+
+/// Default number of database connections in the pool
+pub const DEFAULT_MAX_CONNECTIONS: u32 = 8;
+
+/// Default time a connection waits for another one's write to finish, in seconds
+pub const DEFAULT_BUSY_TIMEOUT_SECS: u64 = 5;
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct PoolConfig {
+    pub max_connections: u32,
+    /// Seconds a query waits for a locked database before failing with `database is locked`
+    pub busy_timeout_secs: u64,
+}
+
+impl Default for PoolConfig {
+    fn default() -> Self {
+        Self {
+            max_connections: DEFAULT_MAX_CONNECTIONS,
+            busy_timeout_secs: DEFAULT_BUSY_TIMEOUT_SECS,
+        }
+    }
+}
+
+/// Open the connection pool. The database is switched to write-ahead logging, so
+/// devices syncing at the same time read while another request writes, with
+/// `synchronous=NORMAL`, which is durable in WAL mode apart from the last transactions
+/// before a power loss. Foreign keys are enforced on every connection.
+pub async fn connect(database_url: &str, config: &PoolConfig) -> Result<SqlitePool, sqlx::Error> {
+    let options = SqliteConnectOptions::from_str(database_url)?
+        .journal_mode(SqliteJournalMode::Wal)
+        .synchronous(SqliteSynchronous::Normal)
+        .foreign_keys(true)
+        .busy_timeout(Duration::from_secs(config.busy_timeout_secs));
+
+    SqlitePoolOptions::new()
+        .max_connections(config.max_connections)
+        .connect_with(options)
+        .await
+}
 
 pub async fn initialize_database(pool: &SqlitePool) -> Result<(), sqlx::Error> {
     let migration_dir = "./src/migrations";
@@ -24,49 +65,42 @@ pub async fn initialize_database(pool: &SqlitePool) -> Result<(), sqlx::Error> {
         
         migrations.sort();
         
+        // Track applied migrations so ALTER TABLE migrations only run once
+        pool.execute(
+            "CREATE TABLE IF NOT EXISTS schema_migrations (name TEXT PRIMARY KEY, applied_at TEXT NOT NULL)"
+        ).await?;
+        
         for migration in migrations {
+            let applied: Option<(String,)> = sqlx::query_as("SELECT name FROM schema_migrations WHERE name = ?")
+                .bind(&migration)
+                .fetch_optional(pool)
+                .await?;
+            
+            if applied.is_some() {
+                continue;
+            }
+            
             let path = format!("{}/{}", migration_dir, migration);
             if let Ok(content) = fs::read_to_string(&path) {
                 info!("Running migration: {}", migration);
-                pool.execute(content.as_str()).await?;
+                if let Err(e) = pool.execute(content.as_str()).await {
+                    // Databases created before migration tracking already contain these columns
+                    if e.to_string().contains("duplicate column name") {
+                        warn!("Migration {} was already applied, marking as done", migration);
+                    } else {
+                        return Err(e);
+                    }
+                }
+                
+                sqlx::query("INSERT INTO schema_migrations (name, applied_at) VALUES (?, ?)")
+                    .bind(&migration)
+                    .bind(Utc::now())
+                    .execute(pool)
+                    .await?;
             }
         }
     }
     
-    // Create default user if not exists
-    create_default_user(pool).await?;
-    
-    Ok(())
-}
-
-async fn create_default_user(pool: &SqlitePool) -> Result<(), sqlx::Error> {
-    // Check if default user exists
-    let existing_user: Option<(String,)> = sqlx::query_as("SELECT email FROM users WHERE email = ?")
-        .bind("test@test.com")
-        .fetch_optional(pool)
-        .await?;
-    
-    if existing_user.is_none() {
-        info!("Creating default user: test@test.com");
-        let password_hash = hash("password123", DEFAULT_COST).expect("Failed to hash password");
-        let now = Utc::now();
-        let id = Uuid::new_v4();
-        
-        sqlx::query(
-            "INSERT INTO users (id, name, email, password_hash, created_at, updated_at) VALUES (?, ?, ?, ?, ?, ?)"
-        )
-        .bind(id.to_string())
-        .bind("Test User")
-        .bind("test@test.com")
-        .bind(&password_hash)
-        .bind(now)
-        .bind(now)
-        .execute(pool)
-        .await?;
-        
-        info!("Default user created successfully. Email: test@test.com, Password: password123");
-    }
-    
     Ok(())
 }
 
@@ -0,0 +1,101 @@
+//! COPY and MOVE of calendar object resources between calendars
+
+use axum::{
+    http::{header, HeaderMap, Method, StatusCode},
+    response::Response,
+    body::Body,
+};
+use uuid::Uuid;
+use crate::authz::{self, Access};
+use crate::error::AppError;
+use crate::services::CalendarService;
+use super::xml::Element;
+use super::{condition_response, empty_response, find_object, find_object_by_uid, CalendarObject};
+
+/// Extract the target calendar and resource name of a COPY/MOVE `Destination` header,
+/// which may be an absolute URL or an absolute path like /calendars/{calendar_id}/{name}
+fn parse_destination(service: &CalendarService, destination: &str) -> Result<(Uuid, String), AppError> {
+    let path = match destination.split_once("://") {
+        Some((_, rest)) => rest.find('/').map(|i| &rest[i..]).unwrap_or("/"),
+        None => destination,
+    };
+    // Clients address resources by their public URL, which may carry a path prefix
+    let path = path.strip_prefix(service.base_path()).unwrap_or(path);
+
+    let parts: Vec<&str> = path.trim_start_matches('/').split('/').collect();
+    if parts.len() != 3 || parts[0] != "calendars" || parts[2].is_empty() {
+        return Err(AppError::ValidationError("Destination must be a calendar object URL".to_string()));
+    }
+
+    Ok((Uuid::parse_str(parts[1])?, parts[2].to_string()))
+}
+
+/// Copy or move an event resource to the resource named by the Destination header,
+/// replacing what is stored there unless `Overwrite: F` is sent
+pub(super) async fn object(
+    service: &CalendarService,
+    user_id: Uuid,
+    method: &Method,
+    calendar_id: Uuid,
+    name: &str,
+    headers: &HeaderMap,
+) -> Result<Response, AppError> {
+    let destination = headers.get("Destination").and_then(|d| d.to_str().ok())
+        .ok_or_else(|| AppError::ValidationError("Missing Destination header".to_string()))?;
+    let (target_calendar_id, target_name) = parse_destination(service, destination)?;
+    let overwrite = headers.get("Overwrite").and_then(|o| o.to_str().ok()).is_none_or(|o| !o.eq_ignore_ascii_case("F"));
+
+    let is_move = method.as_str() == "MOVE";
+    // Moving removes the event from its calendar, copying only reads it
+    let source_access = if is_move { Access::Edit } else { Access::View };
+    authz::authorize_calendar(service, user_id, calendar_id, source_access).await?;
+    let event = service.get_event_by_resource_name(calendar_id, name).await?
+        .ok_or(AppError::NotFoundError("Event not found".to_string()))?;
+    let target_calendar = authz::authorize_calendar(service, user_id, target_calendar_id, Access::Edit).await?;
+
+    // RFC 4918 section 9.8.5: source and destination must differ
+    if target_calendar_id == calendar_id && target_name == name {
+        return Ok(empty_response(StatusCode::FORBIDDEN));
+    }
+
+    let replaced = find_object(service, target_calendar_id, &target_name).await?;
+    if replaced.is_some() && !overwrite {
+        return Ok(empty_response(StatusCode::PRECONDITION_FAILED));
+    }
+
+    // A moved event keeps its UID, which must stay unique within the target calendar.
+    // Copies get a UID of their own.
+    if is_move
+        && target_calendar_id != calendar_id
+        && let Some(other) = find_object_by_uid(service, target_calendar_id, &event.ical_uid()).await?
+        && replaced.as_ref().is_none_or(|r| r.id() != other.id())
+    {
+        let href = service.href(&format!("/calendars/{}/{}", target_calendar_id, other.object_name()));
+        return Ok(condition_response(
+            StatusCode::CONFLICT,
+            Element::caldav("no-uid-conflict").child(Element::dav("href").text(href)),
+            "Another resource in the destination calendar already has this UID",
+        ));
+    }
+
+    let event = if is_move {
+        service.move_event(event.id, target_calendar_id).await?
+    } else {
+        service.copy_event(event.id, target_calendar_id).await?
+    };
+    // The replaced resource goes only once its successor exists
+    match &replaced {
+        Some(CalendarObject::Event(existing)) => service.delete_event(existing.id).await?,
+        Some(CalendarObject::Journal(existing)) => service.delete_journal(existing.id).await?,
+        None => {}
+    }
+    let event = service.rename_event_resource(event.id, &target_name).await?;
+
+    let status = if replaced.is_some() { StatusCode::NO_CONTENT } else { StatusCode::CREATED };
+    Ok(Response::builder()
+        .status(status)
+        .header(header::LOCATION, service.url(&format!("/calendars/{}/{}", target_calendar_id, event.object_name())))
+        .header("ETag", CalendarObject::Event(event).etag(service, &target_calendar).await?)
+        .body(Body::from(""))
+        .unwrap())
+}
@@ -0,0 +1,31 @@
+//! DELETE of calendar collections and calendar object resources
+
+use axum::http::StatusCode;
+use axum::response::Response;
+use uuid::Uuid;
+use crate::authz::{self, Access};
+use crate::error::AppError;
+use crate::services::CalendarService;
+use super::{empty_response, find_object, CalendarObject};
+
+/// Remove a calendar with all its content
+pub(super) async fn calendar(service: &CalendarService, user_id: Uuid, calendar_id: Uuid) -> Result<Response, AppError> {
+    authz::authorize_calendar(service, user_id, calendar_id, Access::Admin).await?;
+
+    service.delete_calendar(calendar_id).await?;
+
+    Ok(empty_response(StatusCode::NO_CONTENT))
+}
+
+/// Remove an event or journal entry resource
+pub(super) async fn object(service: &CalendarService, user_id: Uuid, calendar_id: Uuid, name: &str) -> Result<Response, AppError> {
+    authz::authorize_calendar(service, user_id, calendar_id, Access::Edit).await?;
+
+    match find_object(service, calendar_id, name).await? {
+        Some(CalendarObject::Event(event)) => service.delete_event(event.id).await?,
+        Some(CalendarObject::Journal(journal)) => service.delete_journal(journal.id).await?,
+        None => return Err(AppError::NotFoundError("Event not found".to_string())),
+    }
+
+    Ok(empty_response(StatusCode::NO_CONTENT))
+}
@@ -0,0 +1,59 @@
+//! GET / HEAD of calendar collections and calendar object resources
+
+use axum::{
+    http::HeaderMap,
+    response::Response,
+};
+use uuid::Uuid;
+use crate::authz::{self, Access};
+use crate::error::AppError;
+use crate::etag;
+use crate::models::Journal;
+use crate::services::CalendarService;
+use crate::streaming;
+use super::{calendar_object_response, event_etag, find_object, journal_etag, CalendarObject};
+
+/// The whole calendar as one iCalendar file, streamed, or 304 if the client's copy is current
+pub(super) async fn calendar(service: &CalendarService, user_id: Uuid, calendar_id: Uuid, headers: &HeaderMap) -> Result<Response, AppError> {
+    let calendar = authz::authorize_calendar(service, user_id, calendar_id, Access::View).await?;
+    Ok(streaming::calendar_ics_response(service, headers, calendar, true))
+}
+
+/// A single event resource, including the overridden instances of a series, or a
+/// journal entry. A client that has the current version gets 304 without the object
+/// being rendered.
+pub(super) async fn object(service: &CalendarService, user_id: Uuid, calendar_id: Uuid, name: &str, headers: &HeaderMap) -> Result<Response, AppError> {
+    let calendar = authz::authorize_calendar(service, user_id, calendar_id, Access::View).await?;
+
+    match find_object(service, calendar_id, name).await? {
+        Some(CalendarObject::Event(event)) => {
+            let object_events = service.get_calendar_object_events(&event).await?;
+            let etag = event_etag(&calendar, &event, &object_events);
+            if etag::not_modified(headers, &etag) {
+                return Ok(etag::not_modified_response(&etag));
+            }
+            let ical_content = service.calendar_object_ics(&object_events).await?;
+            Ok(calendar_object_response(etag, ical_content))
+        }
+        Some(CalendarObject::Journal(journal)) => {
+            let etag = journal_etag(&calendar, &journal);
+            if etag::not_modified(headers, &etag) {
+                return Ok(etag::not_modified_response(&etag));
+            }
+            Ok(calendar_object_response(etag, journal_object_ics(&journal)))
+        }
+        None => Err(AppError::NotFoundError("Event not found".to_string())),
+    }
+}
+
+/// iCalendar object of a journal entry resource
+pub(super) fn journal_object_ics(journal: &Journal) -> String {
+    format!(
+        "BEGIN:VCALENDAR\r\n\
+         VERSION:2.0\r\n\
+         PRODID:-//My CalDAV Server//EN\r\n\
+         {}\
+         END:VCALENDAR\r\n",
+        journal.to_ical_string()
+    )
+}
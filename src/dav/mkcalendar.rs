@@ -0,0 +1,33 @@
+//! MKCOL / MKCALENDAR on the calendar creation URL
+
+use axum::{
+    http::{header, StatusCode},
+    response::Response,
+    body::Body,
+};
+use uuid::Uuid;
+use crate::error::AppError;
+use crate::models::NewCalendar;
+use crate::services::CalendarService;
+use super::xml;
+
+/// Create a calendar named by the `displayname` in the request body
+pub(super) async fn create(service: &CalendarService, user_id: Uuid, body: &str) -> Result<Response, AppError> {
+    let calendar_name = xml::mkcol_displayname(body)?.unwrap_or_else(|| "New Calendar".to_string());
+
+    let new_calendar = NewCalendar {
+        name: calendar_name,
+        description: None,
+        color: Some("#3B82F6".to_string()), // Default blue color
+        is_public: false,
+        default_alarm_minutes: None,
+    };
+
+    let calendar = service.create_calendar(user_id, new_calendar).await?;
+
+    Ok(Response::builder()
+        .status(StatusCode::CREATED)
+        .header(header::LOCATION, service.url(&format!("/calendars/{}/", calendar.id)))
+        .body(Body::from(""))
+        .unwrap())
+}
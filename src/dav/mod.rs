@@ -0,0 +1,543 @@
+//! WebDAV / CalDAV protocol support
+//!
+//! Everything below `/calendars` goes through [`dispatch`], which resolves the
+//! addressed resource from the path and hands the request to the module for its
+//! method. Adding a verb means adding a module and a match arm here.
+
+use axum::{
+    extract::{ConnectInfo, Extension, Request, State},
+    http::{header, HeaderMap, Method, StatusCode, Uri},
+    response::{IntoResponse, Response},
+    body::Body,
+};
+use crate::lockout::LoginSource;
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+use crate::error::AppError;
+use crate::middleware::BasicAuthCredentials;
+use crate::models::{Calendar, Event, Journal, PermissionLevel};
+use crate::dav_usage::FeatureKind;
+use crate::devices::{self, DeviceCredential};
+use crate::services::CalendarService;
+use crate::sync_log::NewSyncLogEntry;
+use std::net::SocketAddr;
+use std::time::Instant;
+use xml::{Element, Multistatus, Namespace};
+
+mod copy_move;
+mod delete;
+mod get;
+mod mkcalendar;
+mod principal;
+mod propfind;
+mod proppatch;
+mod public;
+mod push;
+mod put;
+mod report;
+pub mod xml;
+
+pub use principal::group_principal;
+pub use public::{public_dav_calendar, public_dav_event, public_dav_root};
+pub use push::caldav_push_unregister;
+
+/// Methods implemented on the CalDAV root (`/calendars/`)
+const CALDAV_ROOT_ALLOW: &str = "OPTIONS, GET, PROPFIND, REPORT";
+
+/// Methods implemented on a calendar collection (`/calendars/{id}/`)
+const CALDAV_CALENDAR_ALLOW: &str = "OPTIONS, GET, HEAD, POST, DELETE, PROPFIND, PROPPATCH, REPORT";
+
+/// Methods implemented on a calendar object resource (`/calendars/{id}/{event}.ics`)
+const CALDAV_OBJECT_ALLOW: &str = "OPTIONS, GET, HEAD, PUT, DELETE, COPY, MOVE";
+
+/// Methods implemented on the calendar creation URL (`/calendars/new`)
+const CALDAV_MKCOL_ALLOW: &str = "OPTIONS, MKCOL, MKCALENDAR";
+
+/// HTTP and WebDAV methods this server recognizes. Recognized methods that a resource
+/// doesn't support get 405 with an `Allow` header; anything else gets 501.
+const KNOWN_DAV_METHODS: &[&str] = &[
+    "OPTIONS", "GET", "HEAD", "POST", "PUT", "DELETE", "PATCH",
+    "PROPFIND", "PROPPATCH", "MKCOL", "MKCALENDAR", "REPORT",
+    "COPY", "MOVE", "LOCK", "UNLOCK", "ACL",
+];
+
+/// Resource addressed by a request below `/calendars`
+#[derive(Debug, Clone, PartialEq)]
+enum Target {
+    /// `/calendars/`, listing the user's calendars
+    Root,
+    /// `/calendars/new`, where clients create calendars
+    NewCalendar,
+    /// `/calendars/{calendar_id}/`
+    Calendar(Uuid),
+    /// `/calendars/{calendar_id}/{name}`; `name` is the one a client picked when it
+    /// uploaded the object, or `{id}.ics` for objects created elsewhere
+    Object { calendar_id: Uuid, name: String },
+}
+
+impl Target {
+    fn parse(path: &str) -> Result<Self, AppError> {
+        let mut parts = path.trim_start_matches('/').split('/').skip(1).filter(|p| !p.is_empty());
+
+        let Some(calendar) = parts.next() else {
+            return Ok(Target::Root);
+        };
+        if calendar == "new" {
+            return Ok(Target::NewCalendar);
+        }
+        let calendar_id = Uuid::parse_str(calendar)?;
+
+        match (parts.next(), parts.next()) {
+            (None, _) => Ok(Target::Calendar(calendar_id)),
+            (Some(name), None) => Ok(Target::Object { calendar_id, name: name.to_string() }),
+            (Some(_), Some(_)) => Err(AppError::NotFoundError("Resource not found".to_string())),
+        }
+    }
+
+    /// Methods implemented on the resource, as advertised in `Allow`
+    fn allow(&self) -> &'static str {
+        match self {
+            Target::Root => CALDAV_ROOT_ALLOW,
+            Target::NewCalendar => CALDAV_MKCOL_ALLOW,
+            Target::Calendar(_) => CALDAV_CALENDAR_ALLOW,
+            Target::Object { .. } => CALDAV_OBJECT_ALLOW,
+        }
+    }
+}
+
+/// What a calendar object resource holds
+enum CalendarObject {
+    Event(Event),
+    Journal(Journal),
+}
+
+impl CalendarObject {
+    fn id(&self) -> Uuid {
+        match self {
+            CalendarObject::Event(event) => event.id,
+            CalendarObject::Journal(journal) => journal.id,
+        }
+    }
+
+    fn object_name(&self) -> String {
+        match self {
+            CalendarObject::Event(event) => event.object_name(),
+            CalendarObject::Journal(journal) => journal.object_name(),
+        }
+    }
+
+    /// The object's current ETag
+    async fn etag(&self, service: &CalendarService, calendar: &Calendar) -> Result<String, AppError> {
+        match self {
+            CalendarObject::Event(event) => {
+                let object_events = service.get_calendar_object_events(event).await?;
+                Ok(event_etag(calendar, event, &object_events))
+            }
+            CalendarObject::Journal(journal) => Ok(journal_etag(calendar, journal)),
+        }
+    }
+
+    /// The UID the object is exported with
+    fn ical_uid(&self) -> String {
+        match self {
+            CalendarObject::Event(event) => event.ical_uid(),
+            CalendarObject::Journal(journal) => journal.uid.clone().unwrap_or_else(|| journal.id.to_string()),
+        }
+    }
+}
+
+/// The object resource stored under `name` in a calendar collection
+async fn find_object(service: &CalendarService, calendar_id: Uuid, name: &str) -> Result<Option<CalendarObject>, AppError> {
+    if let Some(event) = service.get_event_by_resource_name(calendar_id, name).await? {
+        return Ok(Some(CalendarObject::Event(event)));
+    }
+    Ok(service.get_journal_by_resource_name(calendar_id, name).await?.map(CalendarObject::Journal))
+}
+
+/// The object resource of a calendar whose component has the iCalendar `uid`
+async fn find_object_by_uid(service: &CalendarService, calendar_id: Uuid, uid: &str) -> Result<Option<CalendarObject>, AppError> {
+    if let Some(event) = service.get_event_by_uid(calendar_id, uid).await? {
+        return Ok(Some(CalendarObject::Event(event)));
+    }
+    Ok(service.get_journal_by_uid(calendar_id, uid).await?.map(CalendarObject::Journal))
+}
+
+/// Single entry point for the authenticated CalDAV namespace
+pub async fn dispatch(
+    State(service): State<CalendarService>,
+    user_id_ext: Option<Extension<Uuid>>,
+    basic_auth: Option<Extension<BasicAuthCredentials>>,
+    request: Request,
+) -> Result<Response, AppError> {
+    let (parts, body) = request.into_parts();
+    let (method, uri, headers) = (parts.method, parts.uri, parts.headers);
+    let target = Target::parse(uri.path())?;
+    let allow = target.allow();
+
+    if method == Method::OPTIONS {
+        return Ok(dav_options(allow));
+    }
+    if !allow.split(", ").any(|m| m == method.as_str()) {
+        service.dav_usage().record(FeatureKind::Method, method.as_str());
+        return Ok(dav_method_not_supported(&method, allow));
+    }
+
+    let started = Instant::now();
+    let credentials = basic_auth.map(|ext| ext.0);
+    let credential = if user_id_ext.is_some() { DeviceCredential::Token } else { DeviceCredential::Password };
+    let user_id = match get_user_id(&service, user_id_ext.map(|ext| ext.0), credentials.clone()).await {
+        Ok(user_id) => user_id,
+        Err(e) => {
+            // A password that was changed elsewhere is the most common reason a device stops syncing
+            if let Some(credentials) = credentials
+                && let Ok(Some(user)) = service.get_user_by_email(&credentials.email).await
+            {
+                let result = Err(e);
+                let outcome = outcome(&result);
+                log_request(&service, user.id, &method, &uri, &headers, started, outcome).await;
+                return result;
+            }
+            return Err(e);
+        }
+    };
+
+    let ip = parts.extensions.get::<ConnectInfo<SocketAddr>>().map(|ConnectInfo(addr)| addr.ip().to_string());
+    let result = if !register_device(&service, user_id, credential, &headers, ip.as_deref()).await {
+        Err(AppError::AuthenticationError("This device was revoked; remove it from your connected devices to sync again".to_string()))
+    } else {
+        match read_body(&headers, body, service.quotas().max_ics_size).await {
+            Ok(body) => handle(&service, user_id, &target, &method, &headers, &body).await,
+            Err(response) => Ok(response),
+        }
+    };
+    let outcome = outcome(&result);
+    log_request(&service, user_id, &method, &uri, &headers, started, outcome).await;
+    result
+}
+
+/// Answer an authenticated request for a resource with a method it supports
+async fn handle(
+    service: &CalendarService,
+    user_id: Uuid,
+    target: &Target,
+    method: &Method,
+    headers: &HeaderMap,
+    body: &str,
+) -> Result<Response, AppError> {
+    let result = match (target, method.as_str()) {
+        (Target::Root, "GET" | "PROPFIND") => propfind::root(service, user_id, body).await,
+        (Target::Root, "REPORT") => report::root(service, user_id, body).await,
+        (Target::NewCalendar, "MKCOL" | "MKCALENDAR") => mkcalendar::create(service, user_id, body).await,
+        (Target::Calendar(calendar_id), "GET" | "HEAD") => get::calendar(service, user_id, *calendar_id, headers).await,
+        (Target::Calendar(calendar_id), "POST") => push::register(service, user_id, *calendar_id, body).await,
+        (Target::Calendar(calendar_id), "PROPFIND") => propfind::calendar(service, user_id, *calendar_id, depth(headers), body).await,
+        (Target::Calendar(calendar_id), "REPORT") => report::calendar(service, user_id, *calendar_id, body).await,
+        (Target::Calendar(calendar_id), "PROPPATCH") => proppatch::calendar(service, user_id, *calendar_id, body).await,
+        (Target::Calendar(calendar_id), "DELETE") => delete::calendar(service, user_id, *calendar_id).await,
+        (Target::Object { calendar_id, name }, "GET" | "HEAD") => get::object(service, user_id, *calendar_id, name, headers).await,
+        (Target::Object { calendar_id, name }, "PUT") => put::object(service, user_id, *calendar_id, name, headers, body).await,
+        (Target::Object { calendar_id, name }, "DELETE") => delete::object(service, user_id, *calendar_id, name).await,
+        (Target::Object { calendar_id, name }, "COPY" | "MOVE") => copy_move::object(service, user_id, method, *calendar_id, name, headers).await,
+        _ => Ok(dav_method_not_supported(method, target.allow())),
+    };
+
+    // RFC 4331: running out of quota is reported as a failed precondition
+    match result {
+        Err(AppError::QuotaExceeded(message)) => Ok(condition_response(
+            StatusCode::INSUFFICIENT_STORAGE,
+            Element::dav("quota-not-exceeded"),
+            &message,
+        )),
+        result => result,
+    }
+}
+
+/// Status of a request's response and the error message the client was given
+fn outcome(result: &Result<Response, AppError>) -> (StatusCode, Option<String>) {
+    match result {
+        Ok(response) => (response.status(), None),
+        // Internal failures aren't explained to clients, so they aren't in the log either
+        Err(e) => (e.status(), (!e.status().is_server_error()).then(|| e.public_message())),
+    }
+}
+
+/// Update the device a request came from with its last sync, returning whether the
+/// device may sync. A registry that can't be written doesn't fail the request.
+async fn register_device(
+    service: &CalendarService,
+    user_id: Uuid,
+    credential: DeviceCredential,
+    headers: &HeaderMap,
+    ip: Option<&str>,
+) -> bool {
+    let user_agent = devices::client_name(headers.get(header::USER_AGENT).and_then(|v| v.to_str().ok()));
+    match service.record_device_request(user_id, &user_agent, credential, ip).await {
+        Ok(allowed) => allowed,
+        Err(e) => {
+            tracing::warn!(%user_id, "Failed to register device: {}", e);
+            true
+        }
+    }
+}
+
+/// Keep a request in the user's sync log if they turned it on. A log that can't be
+/// written doesn't fail the request.
+async fn log_request(
+    service: &CalendarService,
+    user_id: Uuid,
+    method: &Method,
+    uri: &Uri,
+    headers: &HeaderMap,
+    started: Instant,
+    (status, error): (StatusCode, Option<String>),
+) {
+    match service.get_user_settings(user_id).await {
+        Ok(settings) if settings.sync_log => {}
+        Ok(_) => return,
+        Err(e) => {
+            tracing::warn!(%user_id, "Failed to read sync log setting: {}", e);
+            return;
+        }
+    }
+
+    let user_agent = headers.get(header::USER_AGENT).and_then(|v| v.to_str().ok());
+    let entry = NewSyncLogEntry::new(
+        method.as_str(),
+        uri.path(),
+        status.as_u16(),
+        user_agent,
+        started.elapsed().as_millis() as i64,
+        error,
+    );
+    if let Err(e) = service.record_sync_request(user_id, entry).await {
+        tracing::warn!(%user_id, "Failed to write sync log: {}", e);
+    }
+}
+
+/// CalDAV well-known discovery endpoint. RFC 6764 asks for a redirect to the
+/// context path; clients then authenticate there.
+pub async fn caldav_discovery(State(service): State<CalendarService>) -> impl IntoResponse {
+    Response::builder()
+        .status(StatusCode::MOVED_PERMANENTLY)
+        .header(header::LOCATION, service.url("/calendars/"))
+        .body(Body::from(""))
+        .unwrap()
+}
+
+/// Helper function to authenticate Basic Auth credentials and get user_id
+async fn authenticate_basic_auth(
+    service: &CalendarService,
+    credentials: &BasicAuthCredentials,
+) -> Result<Uuid, AppError> {
+    let user = service.authenticate(&credentials.email, &credentials.password, LoginSource::Dav).await?;
+    Ok(user.id)
+}
+
+/// Helper to get user_id from either Extension (JWT auth) or Basic Auth
+pub(crate) async fn get_user_id(
+    service: &CalendarService,
+    user_id_ext: Option<Uuid>,
+    basic_auth: Option<BasicAuthCredentials>,
+) -> Result<Uuid, AppError> {
+    // First try JWT auth (user_id from extension)
+    if let Some(user_id) = user_id_ext {
+        return Ok(user_id);
+    }
+
+    // Then try Basic Auth
+    if let Some(credentials) = basic_auth {
+        let user_id = authenticate_basic_auth(service, &credentials).await?;
+        crate::logging::record_user(user_id);
+        return Ok(user_id);
+    }
+
+    Err(AppError::AuthenticationError("Authentication required".to_string()))
+}
+
+/// Buffer a request body of at most `limit` bytes. Oversized bodies are refused with
+/// 413 before they are read when the client announces their length.
+async fn read_body(headers: &HeaderMap, body: Body, limit: usize) -> Result<String, Response> {
+    let too_large = || condition_response(
+        StatusCode::PAYLOAD_TOO_LARGE,
+        Element::caldav("max-resource-size"),
+        &format!("Request body exceeds the limit of {} bytes", limit),
+    );
+
+    let announced = headers.get(header::CONTENT_LENGTH)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse::<usize>().ok());
+    if announced.is_some_and(|length| length > limit) {
+        return Err(too_large());
+    }
+
+    let bytes = axum::body::to_bytes(body, limit).await.map_err(|_| too_large())?;
+    String::from_utf8(bytes.to_vec())
+        .map_err(|_| text_response(StatusCode::BAD_REQUEST, "Request body is not valid UTF-8".to_string()))
+}
+
+/// Value of the `Depth` header, defaulting to 0
+fn depth(headers: &HeaderMap) -> &str {
+    headers.get("Depth").and_then(|d| d.to_str().ok()).unwrap_or("0")
+}
+
+/// Response for methods a CalDAV resource doesn't implement. Clients that see 405/501
+/// stop retrying instead of looping on a misleading success.
+fn dav_method_not_supported(method: &Method, allow: &str) -> Response {
+    let status = if KNOWN_DAV_METHODS.contains(&method.as_str()) {
+        StatusCode::METHOD_NOT_ALLOWED
+    } else {
+        StatusCode::NOT_IMPLEMENTED
+    };
+
+    Response::builder()
+        .status(status)
+        .header(header::ALLOW, allow)
+        .body(Body::from(format!("{} is not supported on this resource", method)))
+        .unwrap()
+}
+
+/// Answer OPTIONS on a CalDAV resource with its allowed methods and DAV capabilities
+fn dav_options(allow: &str) -> Response {
+    Response::builder()
+        .status(StatusCode::OK)
+        .header(header::ALLOW, allow)
+        .header("DAV", "1, calendar-access")
+        .body(Body::from(""))
+        .unwrap()
+}
+
+/// Plain-text response explaining why a request was refused
+fn text_response(status: StatusCode, message: String) -> Response {
+    Response::builder()
+        .status(status)
+        .header(header::CONTENT_TYPE, "text/plain; charset=utf-8")
+        .body(Body::from(message))
+        .unwrap()
+}
+
+/// Response for a request that failed a DAV precondition, naming the condition in a
+/// `DAV:error` body that clients turn into a meaningful message
+fn condition_response(status: StatusCode, condition: Element, description: &str) -> Response {
+    Response::builder()
+        .status(status)
+        .header(header::CONTENT_TYPE, "application/xml; charset=utf-8")
+        .body(Body::from(xml::error_xml(condition, Some(description))))
+        .unwrap()
+}
+
+/// Empty response with the given status
+fn empty_response(status: StatusCode) -> Response {
+    Response::builder()
+        .status(status)
+        .body(Body::from(""))
+        .unwrap()
+}
+
+/// Count the properties a multistatus reports as missing as unsupported features
+fn record_missing_props(service: &CalendarService, multistatus: &Multistatus) {
+    for name in multistatus.missing_props() {
+        service.dav_usage().record(FeatureKind::Property, &name);
+    }
+}
+
+/// Multi-status response; properties it reports as missing count as unsupported features
+fn multistatus_response(service: &CalendarService, multistatus: Multistatus) -> Response {
+    record_missing_props(service, &multistatus);
+
+    Response::builder()
+        .status(StatusCode::MULTI_STATUS)
+        .header(header::CONTENT_TYPE, "application/xml; charset=utf-8")
+        .body(Body::from(multistatus.to_xml()))
+        .unwrap()
+}
+
+/// ETag of the calendar object resource named after `object_id`, the id of its event
+/// or journal entry, last changed at `modified`. It changes with every edit and when the
+/// calendar is re-synced, so clients download the object again.
+fn object_etag(calendar: &Calendar, object_id: Uuid, modified: DateTime<Utc>) -> String {
+    let version = modified.timestamp_micros();
+    if calendar.sync_epoch == 0 {
+        format!("\"{}-{}\"", object_id, version)
+    } else {
+        format!("\"{}-{}-{}\"", object_id, version, calendar.sync_epoch)
+    }
+}
+
+/// ETag of the resource of a series master or standalone event; `events` holds its
+/// overridden instances
+fn event_etag(calendar: &Calendar, event: &Event, events: &[Event]) -> String {
+    object_etag(calendar, event.id, event.object_modified(events))
+}
+
+fn journal_etag(calendar: &Calendar, journal: &Journal) -> String {
+    object_etag(calendar, journal.id, journal.updated_at)
+}
+
+/// iCalendar response for a calendar object resource
+fn calendar_object_response(etag: String, ical_content: String) -> Response {
+    Response::builder()
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, "text/calendar; charset=utf-8")
+        .header("ETag", etag)
+        .body(Body::from(ical_content))
+        .unwrap()
+}
+
+/// Properties of an authenticated calendar collection, as seen by a user holding
+/// `permission` on it
+fn calendar_props(calendar: &Calendar, permission: PermissionLevel) -> Vec<Element> {
+    let mut props = vec![
+        Element::dav("resourcetype")
+            .child(Element::dav("collection"))
+            .child(Element::caldav("calendar")),
+        Element::dav("displayname").text(calendar.name.as_str()),
+        supported_component_set(calendar),
+        Element::new(Namespace::CalendarServer, "getctag").text(calendar.ctag.to_string()),
+    ];
+    props.extend(calendar_color_prop(calendar));
+    props.push(Element::new(Namespace::Apple, "calendar-order").text(calendar.sort_order.to_string()));
+    props.push(
+        Element::new(Namespace::Push, "push-transports")
+            .child(Element::new(Namespace::Push, "transport").child(Element::new(Namespace::Push, "web-push"))),
+    );
+    props.push(Element::new(Namespace::Push, "topic").text(calendar.id.to_string()));
+    props.push(current_user_privilege_set(permission));
+    props
+}
+
+/// Component types clients may store in a calendar. Calendars the server generates
+/// only ever hold events.
+fn supported_component_set(calendar: &Calendar) -> Element {
+    let comp = |name: &str| Element::caldav("comp").attr("name", name);
+    let mut set = Element::caldav("supported-calendar-component-set").child(comp("VEVENT"));
+    if !calendar.is_generated() {
+        set = set.child(comp("VJOURNAL"));
+    }
+    set
+}
+
+/// RFC 3744 `current-user-privilege-set`. Clients show calendars without `write`
+/// as read-only instead of failing on the first edit.
+fn current_user_privilege_set(permission: PermissionLevel) -> Element {
+    let privilege = |name: &str| Element::dav("privilege").child(Element::dav(name));
+    let mut set = Element::dav("current-user-privilege-set").child(privilege("read"));
+    if permission >= PermissionLevel::Write {
+        set = set.child(privilege("write"));
+    }
+    set
+}
+
+/// `calendar-color` property of a calendar, if it has a color
+fn calendar_color_prop(calendar: &Calendar) -> Option<Element> {
+    calendar.color.as_deref()
+        .map(|color| Element::new(Namespace::Apple, "calendar-color").text(color))
+}
+
+/// Properties of a calendar object resource as listed in a Depth 1 PROPFIND
+fn object_resource_props(etag: String) -> Vec<Element> {
+    vec![
+        Element::dav("getetag").text(etag),
+        Element::dav("getcontenttype").text("text/calendar; charset=utf-8"),
+        Element::dav("resourcetype"),
+    ]
+}
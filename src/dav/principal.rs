@@ -0,0 +1,213 @@
+//! Principals and the principal REPORTs. The user's own principal is the calendar
+//! home `/calendars/`; groups have read-only principals under `/principals/groups/{id}/`,
+//! so clients that understand RFC 3744 group principals can show who a shared calendar
+//! belongs to.
+
+use std::future::Future;
+use std::pin::Pin;
+
+use axum::{
+    extract::{Extension, Path, State},
+    http::Method,
+    response::Response,
+};
+use uuid::Uuid;
+use crate::authz::{self, Access};
+use crate::error::AppError;
+use crate::middleware::BasicAuthCredentials;
+use crate::models::Group;
+use crate::services::CalendarService;
+use super::xml::{DavResponse, Element, ExpandProperty, Multistatus, Namespace, PropfindRequest, ReportKind, ReportRequest};
+use super::{dav_method_not_supported, dav_options, get_user_id, multistatus_response};
+
+/// Methods implemented on a group principal
+const GROUP_PRINCIPAL_ALLOW: &str = "OPTIONS, PROPFIND, REPORT";
+
+/// How many levels of hrefs an `expand-property` REPORT follows
+const MAX_EXPAND_DEPTH: usize = 3;
+
+/// Handle requests on a group principal; only members of the group can see it
+pub async fn group_principal(
+    State(service): State<CalendarService>,
+    user_id_ext: Option<Extension<Uuid>>,
+    basic_auth: Option<Extension<BasicAuthCredentials>>,
+    method: Method,
+    Path(group_id): Path<Uuid>,
+    body: String,
+) -> Result<Response, AppError> {
+    if method == Method::OPTIONS {
+        return Ok(dav_options(GROUP_PRINCIPAL_ALLOW));
+    }
+    if !matches!(method.as_str(), "PROPFIND" | "REPORT") {
+        return Ok(dav_method_not_supported(&method, GROUP_PRINCIPAL_ALLOW));
+    }
+
+    let user_id = get_user_id(&service, user_id_ext.map(|ext| ext.0), basic_auth.map(|ext| ext.0)).await?;
+    let (group, _) = authz::authorize_group(&service, user_id, group_id, Access::View).await?;
+    let href = group_href(&service, group.id);
+
+    if method.as_str() == "REPORT" {
+        return report(&service, user_id, &href, &ReportRequest::parse(&body)?).await;
+    }
+
+    let request = PropfindRequest::parse(&body)?;
+    let mut multistatus = Multistatus::new();
+    multistatus.push(request.response(href, group_principal_props(&service, &group).await?));
+
+    Ok(multistatus_response(&service, multistatus))
+}
+
+/// Answer a principal REPORT sent to the principal at `href`: `expand-property`
+/// reports that principal's properties, `principal-property-search` searches the
+/// principals the user can see
+pub(super) async fn report(
+    service: &CalendarService,
+    user_id: Uuid,
+    href: &str,
+    report: &ReportRequest,
+) -> Result<Response, AppError> {
+    let mut multistatus = Multistatus::new();
+
+    match report.kind {
+        ReportKind::ExpandProperty => {
+            let available = principal_props(service, user_id, href).await?.unwrap_or_default();
+            multistatus.push(expand(service, user_id, href.to_string(), available, &report.expand, 1).await?);
+        }
+        ReportKind::PrincipalPropertySearch => {
+            for (href, available) in visible_principals(service, user_id).await? {
+                let mut matches = report.searches.iter().map(|search| search.matches(&available));
+                let found = if report.match_any { matches.any(|m| m) } else { matches.all(|m| m) };
+                if found {
+                    multistatus.push(report.response(href, available));
+                }
+            }
+        }
+        _ => return Err(AppError::ValidationError("Expected an expand-property or principal-property-search report".to_string())),
+    }
+
+    Ok(multistatus_response(service, multistatus))
+}
+
+/// Response for a resource limited to the `requested` properties, with the hrefs of
+/// those that ask for it replaced by the properties of the resources they point to
+fn expand<'a>(
+    service: &'a CalendarService,
+    user_id: Uuid,
+    href: String,
+    available: Vec<Element>,
+    requested: &'a [ExpandProperty],
+    depth: usize,
+) -> Pin<Box<dyn Future<Output = Result<DavResponse, AppError>> + Send + 'a>> {
+    Box::pin(async move {
+        let mut props = Vec::with_capacity(available.len());
+        for prop in available {
+            let nested = requested.iter()
+                .find(|r| r.name.name == prop.name && r.name.ns.as_deref() == Some(prop.ns.uri()))
+                .map(|r| r.properties.as_slice())
+                .unwrap_or_default();
+            if nested.is_empty() || depth >= MAX_EXPAND_DEPTH {
+                props.push(prop);
+                continue;
+            }
+
+            let mut responses = Vec::new();
+            for target in prop.hrefs() {
+                let target_props = principal_props(service, user_id, target).await?.unwrap_or_default();
+                responses.push(expand(service, user_id, target.to_string(), target_props, nested, depth + 1).await?.to_element());
+            }
+            props.push(Element::new(prop.ns.clone(), &prop.name).children(responses));
+        }
+
+        let names = requested.iter().map(|r| r.name.clone()).collect();
+        Ok(PropfindRequest::Prop(names).response(href, props))
+    })
+}
+
+/// Properties of the principal at `href`, if it is one the user can see
+async fn principal_props(service: &CalendarService, user_id: Uuid, href: &str) -> Result<Option<Vec<Element>>, AppError> {
+    if href == service.href("/calendars/") {
+        return user_principal_props(service, user_id).await.map(Some);
+    }
+
+    let group_id = href.strip_prefix(&service.href("/principals/groups/"))
+        .map(|id| id.trim_end_matches('/'))
+        .and_then(|id| Uuid::parse_str(id).ok());
+    let Some(group_id) = group_id else {
+        return Ok(None);
+    };
+    if service.get_group_role(group_id, user_id).await?.is_none() {
+        return Ok(None);
+    }
+    match service.get_group_by_id(group_id).await? {
+        Some(group) => group_principal_props(service, &group).await.map(Some),
+        None => Ok(None),
+    }
+}
+
+/// The user's own principal and those of their groups, with their properties
+async fn visible_principals(service: &CalendarService, user_id: Uuid) -> Result<Vec<(String, Vec<Element>)>, AppError> {
+    let mut principals = vec![(service.href("/calendars/"), user_principal_props(service, user_id).await?)];
+    for membership in service.get_groups_for_user(user_id).await? {
+        let props = group_principal_props(service, &membership.group).await?;
+        principals.push((group_href(service, membership.group.id), props));
+    }
+    Ok(principals)
+}
+
+/// Properties of the user's principal. Delegation isn't supported, so the proxy
+/// properties Apple clients look for are empty. There is no scheduling inbox, so the
+/// RFC 6638 `schedule-default-calendar-URL` is given on the principal.
+async fn user_principal_props(service: &CalendarService, user_id: Uuid) -> Result<Vec<Element>, AppError> {
+    let user = service.get_user_by_id(user_id).await?
+        .ok_or(AppError::NotFoundError("User not found".to_string()))?;
+    let groups = service.get_groups_for_user(user_id).await?;
+    let home = service.href("/calendars/");
+    let displayname = if user.name.trim().is_empty() { user.email.clone() } else { user.name.clone() };
+    let default_calendar = service.get_default_calendar(user_id).await?;
+
+    let mut props = vec![
+        Element::dav("resourcetype").child(Element::dav("collection")).child(Element::dav("principal")),
+        Element::dav("displayname").text(displayname),
+        Element::dav("principal-URL").child(Element::dav("href").text(home.as_str())),
+        Element::dav("current-user-principal").child(Element::dav("href").text(home.as_str())),
+        Element::dav("group-membership").children(
+            groups.iter().map(|membership| Element::dav("href").text(group_href(service, membership.group.id))),
+        ),
+        Element::caldav("calendar-home-set").child(Element::dav("href").text(home.as_str())),
+        Element::caldav("calendar-user-address-set")
+            .child(Element::dav("href").text(format!("mailto:{}", user.email)))
+            .child(Element::dav("href").text(home.as_str())),
+        Element::new(Namespace::CalendarServer, "email-address-set")
+            .child(Element::new(Namespace::CalendarServer, "email-address").text(user.email.as_str())),
+        Element::new(Namespace::CalendarServer, "calendar-proxy-read-for"),
+        Element::new(Namespace::CalendarServer, "calendar-proxy-write-for"),
+    ];
+    if let Some(calendar) = default_calendar {
+        props.push(
+            Element::caldav("schedule-default-calendar-URL")
+                .child(Element::dav("href").text(service.href(&format!("/calendars/{}/", calendar.id)))),
+        );
+    }
+    Ok(props)
+}
+
+/// Properties of a group principal: its name and members
+async fn group_principal_props(service: &CalendarService, group: &Group) -> Result<Vec<Element>, AppError> {
+    let members = service.get_group_members(group.id).await?;
+    let member_set = Element::dav("group-member-set").children(
+        members.iter().map(|member| Element::dav("href").text(format!("mailto:{}", member.email))),
+    );
+
+    Ok(vec![
+        Element::dav("resourcetype").child(Element::dav("principal")),
+        Element::dav("displayname").text(group.name.as_str()),
+        Element::dav("principal-URL").child(Element::dav("href").text(group_href(service, group.id))),
+        member_set,
+        Element::caldav("calendar-home-set").child(Element::dav("href").text(service.href("/calendars/"))),
+    ])
+}
+
+fn group_href(service: &CalendarService, group_id: Uuid) -> String {
+    service.href(&format!("/principals/groups/{}/", group_id))
+}
+
@@ -0,0 +1,75 @@
+//! PROPFIND on the CalDAV root and on calendar collections
+
+use axum::response::Response;
+use uuid::Uuid;
+use crate::authz::{self, Access};
+use crate::error::AppError;
+use crate::models::PermissionLevel;
+use crate::services::CalendarService;
+use super::xml::{Multistatus, PropfindRequest};
+use super::{calendar_props, event_etag, journal_etag, multistatus_response, object_resource_props};
+
+/// List the user's calendar collections, followed by the calendars shared with them,
+/// in the order and colors the user gave them
+pub(super) async fn root(service: &CalendarService, user_id: Uuid, body: &str) -> Result<Response, AppError> {
+    let request = PropfindRequest::parse(body)?;
+    let calendars = service.get_calendars_by_user_id(user_id).await?;
+    let shared = service.get_calendars_shared_with_user(user_id).await?;
+    let layout = service.get_calendar_layout(user_id).await?;
+
+    let mut multistatus = Multistatus::new();
+
+    let owned = calendars.into_iter().map(|calendar| {
+        let permission = calendar.owner_permission();
+        (calendar, permission)
+    });
+    for (calendar, permission) in owned.chain(shared) {
+        let calendar = layout.apply(calendar);
+        multistatus.push(request.response(
+            service.href(&format!("/calendars/{}/", calendar.id)),
+            calendar_props(&calendar, permission),
+        ));
+    }
+
+    Ok(multistatus_response(service, multistatus))
+}
+
+/// Properties of a single calendar collection, plus its event and journal entry
+/// resources at Depth 1
+pub(super) async fn calendar(
+    service: &CalendarService,
+    user_id: Uuid,
+    calendar_id: Uuid,
+    depth: &str,
+    body: &str,
+) -> Result<Response, AppError> {
+    let calendar = authz::authorize_calendar(service, user_id, calendar_id, Access::View).await?;
+    let request = PropfindRequest::parse(body)?;
+    // Public calendars are viewable without a share
+    let permission = authz::calendar_permission(service, user_id, &calendar).await?.unwrap_or(PermissionLevel::Read);
+    let calendar = service.get_calendar_layout(user_id).await?.apply(calendar);
+
+    let mut multistatus = Multistatus::new();
+    multistatus.push(request.response(
+        service.href(&format!("/calendars/{}/", calendar.id)),
+        calendar_props(&calendar, permission),
+    ));
+
+    if depth != "0" {
+        let events = service.get_events_by_calendar_id(calendar.id).await?;
+        for event in events.iter().filter(|e| !e.is_exception()) {
+            multistatus.push(request.response(
+                service.href(&format!("/calendars/{}/{}", calendar.id, event.object_name())),
+                object_resource_props(event_etag(&calendar, event, &events)),
+            ));
+        }
+        for journal in service.get_journals_by_calendar_id(calendar.id).await? {
+            multistatus.push(request.response(
+                service.href(&format!("/calendars/{}/{}", calendar.id, journal.object_name())),
+                object_resource_props(journal_etag(&calendar, &journal)),
+            ));
+        }
+    }
+
+    Ok(multistatus_response(service, multistatus))
+}
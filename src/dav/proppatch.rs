@@ -0,0 +1,95 @@
+//! PROPPATCH on calendar collections
+
+use axum::http::StatusCode;
+use axum::response::Response;
+use uuid::Uuid;
+use crate::authz;
+use crate::error::AppError;
+use crate::models::{PermissionLevel, UpdateCalendar, UpdateCalendarPreference};
+use crate::services::CalendarService;
+use super::xml::{DavResponse, Element, Multistatus, Namespace, ProppatchRequest};
+use super::multistatus_response;
+
+/// Normalize a client-supplied calendar color to the `#RRGGBB` form used by the web UI,
+/// dropping the alpha channel Apple clients append (`#RRGGBBAA`)
+fn normalize_calendar_color(value: &str) -> Option<String> {
+    let hex = value.trim().strip_prefix('#')?;
+    if !(hex.len() == 6 || hex.len() == 8) || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    Some(format!("#{}", &hex[..6].to_ascii_uppercase()))
+}
+
+/// Display name and `calendar-color` are stored on the calendar and show up in the web
+/// UI as well. `calendar-order` is kept per user, like the color users who can't
+/// administer a shared calendar give it.
+pub(super) async fn calendar(service: &CalendarService, user_id: Uuid, calendar_id: Uuid, body: &str) -> Result<Response, AppError> {
+    let calendar = service.get_calendar_by_id(calendar_id).await?
+        .ok_or(AppError::NotFoundError("Calendar not found".to_string()))?;
+    // Everyone the calendar is shared with may arrange it for themselves; public
+    // calendars can't be changed by anyone else
+    let permission = authz::calendar_permission(service, user_id, &calendar).await?
+        .ok_or_else(|| AppError::AuthenticationError("Access denied".to_string()))?;
+    let is_admin = permission == PermissionLevel::Admin;
+
+    let request = ProppatchRequest::parse(body)?;
+    let mut update = UpdateCalendar::default();
+    let mut preference = UpdateCalendarPreference::default();
+    let mut accepted = Vec::new();
+    let mut rejected = Vec::new();
+    let mut forbidden = Vec::new();
+
+    if let Some(name) = request.value(&Namespace::Dav, "displayname").filter(|n| !n.is_empty()) {
+        if is_admin {
+            update.name = Some(name.to_string());
+            accepted.push(Element::dav("displayname"));
+        } else {
+            forbidden.push(Element::dav("displayname"));
+        }
+    }
+    if let Some(color) = request.value(&Namespace::Apple, "calendar-color") {
+        match normalize_calendar_color(color) {
+            // A new calendar color replaces the administrator's own override
+            Some(color) if is_admin => {
+                update.color = Some(Some(color));
+                preference.color = Some(None);
+                accepted.push(Element::new(Namespace::Apple, "calendar-color"));
+            }
+            Some(color) => {
+                preference.color = Some(Some(color));
+                accepted.push(Element::new(Namespace::Apple, "calendar-color"));
+            }
+            None => rejected.push(Element::new(Namespace::Apple, "calendar-color")),
+        }
+    }
+    if let Some(order) = request.value(&Namespace::Apple, "calendar-order") {
+        match order.trim().parse() {
+            Ok(order) => {
+                preference.sort_order = Some(order);
+                accepted.push(Element::new(Namespace::Apple, "calendar-order"));
+            }
+            Err(_) => rejected.push(Element::new(Namespace::Apple, "calendar-order")),
+        }
+    }
+
+    // PROPPATCH is all-or-nothing: one invalid value fails the whole request
+    let response = DavResponse::new(service.href(&format!("/calendars/{}/", calendar_id)));
+    let response = if rejected.is_empty() && forbidden.is_empty() {
+        if update.name.is_some() || update.color.is_some() {
+            service.update_calendar(calendar_id, update).await?;
+        }
+        if preference.sort_order.is_some() || preference.color.is_some() {
+            service.update_calendar_preference(user_id, calendar_id, preference).await?;
+        }
+        response.propstat(StatusCode::OK, accepted)
+    } else {
+        response
+            .propstat(StatusCode::FORBIDDEN, forbidden)
+            .propstat(StatusCode::CONFLICT, rejected)
+            .propstat(StatusCode::FAILED_DEPENDENCY, accepted)
+    };
+
+    let mut multistatus = Multistatus::new();
+    multistatus.push(response);
+    Ok(multistatus_response(service, multistatus))
+}
@@ -0,0 +1,161 @@
+//! Read-only anonymous CalDAV access to public calendars under `/public-dav`
+
+use axum::{
+    extract::{Path, State},
+    http::{header, HeaderMap, Method, StatusCode, Uri},
+    response::Response,
+    body::Body,
+};
+use uuid::Uuid;
+use crate::error::AppError;
+use crate::etag;
+use crate::models::Calendar;
+use crate::services::CalendarService;
+use crate::streaming;
+use super::report::{self, calendar_data_response};
+use super::xml::{Element, Multistatus, Namespace, PropfindRequest};
+use super::{calendar_color_prop, calendar_object_response, depth, event_etag, multistatus_response, object_resource_props};
+
+/// Methods allowed on the anonymous /public-dav namespace
+const PUBLIC_DAV_ALLOW: &str = "OPTIONS, GET, HEAD, PROPFIND, REPORT";
+
+/// Response for write attempts on the read-only public namespace
+fn public_dav_method_not_allowed() -> Response {
+    Response::builder()
+        .status(StatusCode::METHOD_NOT_ALLOWED)
+        .header(header::ALLOW, PUBLIC_DAV_ALLOW)
+        .body(Body::from("Public calendars are read-only"))
+        .unwrap()
+}
+
+fn public_dav_options() -> Response {
+    Response::builder()
+        .status(StatusCode::OK)
+        .header(header::ALLOW, PUBLIC_DAV_ALLOW)
+        .header("DAV", "1, calendar-access")
+        .body(Body::from(""))
+        .unwrap()
+}
+
+/// Properties of a public calendar collection
+fn public_calendar_props(calendar: &Calendar) -> Vec<Element> {
+    let mut props = vec![
+        Element::dav("resourcetype")
+            .child(Element::dav("collection"))
+            .child(Element::caldav("calendar")),
+        Element::dav("displayname").text(calendar.name.as_str()),
+        Element::caldav("supported-calendar-component-set")
+            .child(Element::caldav("comp").attr("name", "VEVENT")),
+        Element::new(Namespace::CalendarServer, "getctag").text(calendar.ctag.to_string()),
+    ];
+    props.extend(calendar_color_prop(calendar));
+    props.push(
+        Element::dav("current-user-privilege-set")
+            .child(Element::dav("privilege").child(Element::dav("read"))),
+    );
+    props
+}
+
+/// Load a calendar for anonymous access, hiding private calendars entirely
+async fn load_public_calendar(service: &CalendarService, calendar_id: &str) -> Result<Calendar, AppError> {
+    let calendar_id = Uuid::parse_str(calendar_id)?;
+    service.get_calendar_by_id(calendar_id).await?
+        .filter(|c| c.is_public)
+        .ok_or(AppError::NotFoundError("Calendar not found".to_string()))
+}
+
+/// Handle requests on the public CalDAV root, listing all public calendars
+pub async fn public_dav_root(
+    State(service): State<CalendarService>,
+    method: Method,
+    body: String,
+) -> Result<Response, AppError> {
+    match method.as_str() {
+        "PROPFIND" => {
+            let request = PropfindRequest::parse(&body)?;
+            let mut multistatus = Multistatus::new();
+            multistatus.push(request.response(service.href("/public-dav/"), vec![
+                Element::dav("resourcetype").child(Element::dav("collection")),
+                Element::dav("displayname").text("Public Calendars"),
+            ]));
+            for calendar in service.get_public_calendars().await? {
+                multistatus.push(request.response(
+                    service.href(&format!("/public-dav/{}/", calendar.id)),
+                    public_calendar_props(&calendar),
+                ));
+            }
+            Ok(multistatus_response(&service, multistatus))
+        }
+        "OPTIONS" => Ok(public_dav_options()),
+        _ => Ok(public_dav_method_not_allowed()),
+    }
+}
+
+/// Handle read-only requests on a public calendar collection
+pub async fn public_dav_calendar(
+    State(service): State<CalendarService>,
+    Path(calendar_id): Path<String>,
+    method: Method,
+    headers: HeaderMap,
+    body: String,
+) -> Result<Response, AppError> {
+    let calendar = load_public_calendar(&service, &calendar_id).await?;
+
+    match method.as_str() {
+        "GET" | "HEAD" => Ok(streaming::calendar_ics_response(&service, &headers, calendar, false)),
+        "PROPFIND" => {
+            let request = PropfindRequest::parse(&body)?;
+            let mut multistatus = Multistatus::new();
+            multistatus.push(request.response(
+                service.href(&format!("/public-dav/{}/", calendar.id)),
+                public_calendar_props(&calendar),
+            ));
+
+            if depth(&headers) != "0" {
+                let events = service.get_events_by_calendar_id(calendar.id).await?;
+                for event in events.iter().filter(|e| !e.is_exception()) {
+                    multistatus.push(request.response(
+                        service.href(&format!("/public-dav/{}/{}", calendar.id, event.object_name())),
+                        object_resource_props(event_etag(&calendar, event, &events)),
+                    ));
+                }
+            }
+            Ok(multistatus_response(&service, multistatus))
+        }
+        "REPORT" => {
+            let report = report::parse(&service, &body)?;
+            Ok(calendar_data_response(&service, vec![calendar], "/public-dav", report, false))
+        }
+        "OPTIONS" => Ok(public_dav_options()),
+        _ => Ok(public_dav_method_not_allowed()),
+    }
+}
+
+/// Handle read-only requests on an event resource of a public calendar
+pub async fn public_dav_event(
+    State(service): State<CalendarService>,
+    Path((calendar_id, _)): Path<(String, String)>,
+    method: Method,
+    uri: Uri,
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
+    if !matches!(method.as_str(), "GET" | "HEAD") {
+        return Ok(public_dav_method_not_allowed());
+    }
+
+    let calendar = load_public_calendar(&service, &calendar_id).await?;
+    // Resource names are listed as stored, so they are looked up without decoding
+    let name = uri.path().rsplit('/').next().unwrap_or_default();
+
+    let event = service.get_event_by_resource_name(calendar.id, name).await?
+        .ok_or(AppError::NotFoundError("Event not found".to_string()))?;
+
+    let object_events = service.get_calendar_object_events(&event).await?;
+    let etag = event_etag(&calendar, &event, &object_events);
+    if etag::not_modified(&headers, &etag) {
+        return Ok(etag::not_modified_response(&etag));
+    }
+    let ical_content = service.calendar_object_ics(&object_events).await?;
+
+    Ok(calendar_object_response(etag, ical_content))
+}
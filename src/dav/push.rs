@@ -0,0 +1,71 @@
+//! WebDAV-Push subscriptions: registration via POST on a calendar collection and
+//! removal via DELETE on the subscription URL
+
+use axum::{
+    extract::{Extension, Path, State},
+    http::{header, StatusCode},
+    response::Response,
+    body::Body,
+};
+use uuid::Uuid;
+use crate::authz::{self, Access};
+use crate::error::AppError;
+use crate::middleware::BasicAuthCredentials;
+use crate::services::CalendarService;
+use super::xml::{self, Namespace};
+use super::{empty_response, get_user_id};
+
+/// Maximum lifetime of a WebDAV-Push subscription before the client has to renew it
+const PUSH_SUBSCRIPTION_MAX_DAYS: i64 = 7;
+
+/// Register a subscription from a push-register body
+pub(super) async fn register(service: &CalendarService, user_id: Uuid, calendar_id: Uuid, body: &str) -> Result<Response, AppError> {
+    authz::authorize_calendar(service, user_id, calendar_id, Access::View).await?;
+
+    let request = xml::parse(body)?;
+    if !request.is(&Namespace::Push, "push-register") {
+        return Err(AppError::ValidationError("Expected push-register request body".to_string()));
+    }
+
+    let push_resource = request.find_text(&Namespace::Push, "push-resource")
+        .ok_or(AppError::ValidationError("Missing push-resource".to_string()))?;
+    let push_resource = crate::push::check_push_resource(&push_resource).await?.to_string();
+
+    // Honor the requested expiration, but never beyond the server maximum
+    let max_expires = service.now() + chrono::Duration::days(PUSH_SUBSCRIPTION_MAX_DAYS);
+    let expires_at = request.find_text(&Namespace::Push, "expires")
+        .and_then(|e| chrono::DateTime::parse_from_rfc2822(&e).ok())
+        .map(|e| e.with_timezone(&chrono::Utc))
+        .filter(|e| *e < max_expires)
+        .unwrap_or(max_expires);
+
+    let subscription = service.create_push_subscription(calendar_id, user_id, &push_resource, expires_at).await?;
+
+    Ok(Response::builder()
+        .status(StatusCode::CREATED)
+        .header(header::LOCATION, service.url(&format!("/dav/push/{}", subscription.id)))
+        .header(header::EXPIRES, subscription.expires_at.format("%a, %d %b %Y %H:%M:%S GMT").to_string())
+        .body(Body::from(""))
+        .unwrap())
+}
+
+/// Handle WebDAV-Push subscription removal
+pub async fn caldav_push_unregister(
+    State(service): State<CalendarService>,
+    user_id_ext: Option<Extension<Uuid>>,
+    basic_auth: Option<Extension<BasicAuthCredentials>>,
+    Path(subscription_id): Path<Uuid>,
+) -> Result<Response, AppError> {
+    let user_id = get_user_id(&service, user_id_ext.map(|ext| ext.0), basic_auth.map(|ext| ext.0)).await?;
+
+    let subscription = service.get_push_subscription_by_id(subscription_id).await?
+        .ok_or(AppError::NotFoundError("Push subscription not found".to_string()))?;
+
+    if subscription.user_id != user_id {
+        return Err(AppError::AuthenticationError("You don't own this push subscription".to_string()));
+    }
+
+    service.delete_push_subscription(subscription_id).await?;
+
+    Ok(empty_response(StatusCode::NO_CONTENT))
+}
@@ -0,0 +1,322 @@
+//! PUT of calendar object resources
+
+use axum::{
+    http::{header, HeaderMap, StatusCode},
+    response::Response,
+    body::Body,
+};
+use uuid::Uuid;
+use crate::authz::{self, Access};
+use crate::error::AppError;
+use crate::handlers::{parse_icalendar, parse_icalendar_journal};
+use crate::models::{Calendar, Event, Journal, UpdateEvent};
+use crate::outlook;
+use crate::services::CalendarService;
+use super::xml::Element;
+use super::{condition_response, empty_response, find_object, find_object_by_uid, journal_etag, text_response, CalendarObject};
+
+/// Whether the `If-Match` / `If-None-Match` preconditions of a PUT hold, given the
+/// ETag of the resource currently stored under the target name. Clients send
+/// `If-None-Match: *` to create without overwriting and `If-Match` to update
+/// only what they last saw.
+fn preconditions_hold(headers: &HeaderMap, current_etag: Option<&str>) -> bool {
+    let matches = |value: &str| {
+        value.split(',').map(str::trim).any(|tag| tag == "*" || Some(tag) == current_etag)
+    };
+
+    if let Some(if_match) = headers.get(header::IF_MATCH).and_then(|v| v.to_str().ok())
+        && (current_etag.is_none() || !matches(if_match))
+    {
+        return false;
+    }
+    if let Some(if_none_match) = headers.get(header::IF_NONE_MATCH).and_then(|v| v.to_str().ok())
+        && current_etag.is_some()
+        && matches(if_none_match)
+    {
+        return false;
+    }
+    true
+}
+
+/// Why a PUT body can't be an iCalendar object, answered before parsing it: a media
+/// type other than `text/calendar` (415) or content that isn't a VCALENDAR (400)
+fn reject_body(headers: &HeaderMap, body: &str) -> Option<Response> {
+    if let Some(content_type) = headers.get(header::CONTENT_TYPE) {
+        let media_type = content_type.to_str().unwrap_or_default()
+            .split(';')
+            .next()
+            .unwrap_or_default()
+            .trim()
+            .to_ascii_lowercase();
+        if media_type != "text/calendar" {
+            return Some(condition_response(
+                StatusCode::UNSUPPORTED_MEDIA_TYPE,
+                Element::caldav("supported-calendar-data"),
+                &format!("Calendar objects must be sent as text/calendar, not {:?}", media_type),
+            ));
+        }
+    }
+
+    let body = body.trim_start_matches('\u{feff}').trim();
+    let upper = body.get(..15).unwrap_or_default().to_ascii_uppercase();
+    if upper != "BEGIN:VCALENDAR" || !body.to_ascii_uppercase().ends_with("END:VCALENDAR") {
+        return Some(invalid_calendar_data("Body is not an iCalendar object (BEGIN:VCALENDAR ... END:VCALENDAR)"));
+    }
+    if body.contains('\0') {
+        return Some(invalid_calendar_data("iCalendar data must not contain NUL bytes"));
+    }
+    None
+}
+
+/// 400 for iCalendar data that can't be parsed or stored
+fn invalid_calendar_data(description: &str) -> Response {
+    condition_response(StatusCode::BAD_REQUEST, Element::caldav("valid-calendar-data"), description)
+}
+
+/// 409 for an object whose UID is already taken by `other`, naming the resource that
+/// holds it
+fn uid_conflict(service: &CalendarService, calendar_id: Uuid, other: &CalendarObject, description: &str) -> Response {
+    let href = service.href(&format!("/calendars/{}/{}", calendar_id, other.object_name()));
+    condition_response(
+        StatusCode::CONFLICT,
+        Element::caldav("no-uid-conflict").child(Element::dav("href").text(href)),
+        description,
+    )
+}
+
+/// Component types a calendar object resource can hold on this server
+enum ObjectKind {
+    Event,
+    Journal,
+}
+
+/// What a calendar object holds, if it is something this server can store
+fn object_kind(body: &str) -> Option<ObjectKind> {
+    let upper = body.to_ascii_uppercase();
+    if upper.contains("BEGIN:VEVENT") {
+        Some(ObjectKind::Event)
+    } else if upper.contains("BEGIN:VJOURNAL") {
+        Some(ObjectKind::Journal)
+    } else {
+        None
+    }
+}
+
+/// UID of the component in a calendar object, ignoring those of nested components
+/// such as alarms
+fn object_uid(body: &str) -> Option<String> {
+    let mut depth = 0usize;
+    for line in body.lines().map(str::trim) {
+        let upper = line.to_ascii_uppercase();
+        if upper.starts_with("BEGIN:") {
+            depth += 1;
+        } else if upper.starts_with("END:") {
+            depth = depth.saturating_sub(1);
+        } else if depth == 2 && (upper.starts_with("UID:") || upper.starts_with("UID;")) {
+            return line.split_once(':').map(|(_, uid)| uid.to_string());
+        }
+    }
+    None
+}
+
+/// SEQUENCE of the component in a calendar object, ignoring those of nested components
+fn object_sequence(body: &str) -> Option<i64> {
+    let mut depth = 0usize;
+    for line in body.lines().map(str::trim) {
+        let upper = line.to_ascii_uppercase();
+        if upper.starts_with("BEGIN:") {
+            depth += 1;
+        } else if upper.starts_with("END:") {
+            depth = depth.saturating_sub(1);
+        } else if depth == 2 && (upper.starts_with("SEQUENCE:") || upper.starts_with("SEQUENCE;")) {
+            return line.split_once(':').and_then(|(_, sequence)| sequence.trim().parse().ok());
+        }
+    }
+    None
+}
+
+/// Keep the event a PUT would have replaced `existing` with as a conflict copy, so the
+/// refused change isn't lost. Bodies that don't parse are only refused.
+async fn keep_conflict(service: &CalendarService, existing: &Event, headers: &HeaderMap, body: &str) -> Result<(), AppError> {
+    let version = match outlook::written_by_outlook(body) {
+        true => parse_icalendar(&outlook::normalize(body).0),
+        false => parse_icalendar(body),
+    };
+    if let Ok(version) = version {
+        let client = headers.get(header::USER_AGENT).and_then(|v| v.to_str().ok());
+        service.store_edit_conflict(existing.id, client, version).await?;
+    }
+    Ok(())
+}
+
+/// 204 for a resource that replaced an existing one
+fn replaced_response(etag: String) -> Response {
+    Response::builder()
+        .status(StatusCode::NO_CONTENT)
+        .header("ETag", etag)
+        .body(Body::from(""))
+        .unwrap()
+}
+
+/// 201 for a new resource, stored under the name the client chose
+fn created_response(service: &CalendarService, calendar: &Calendar, etag: String, name: &str) -> Response {
+    Response::builder()
+        .status(StatusCode::CREATED)
+        .header(header::LOCATION, service.url(&format!("/calendars/{}/{}", calendar.id, name)))
+        .header("ETag", etag)
+        .body(Body::from(""))
+        .unwrap()
+}
+
+/// Create an event or journal entry from iCalendar data, or replace the one stored
+/// under `name`. Objects the server can't accept are refused with the CalDAV
+/// precondition they violate (RFC 4791 section 5.3.2.1).
+pub(super) async fn object(
+    service: &CalendarService,
+    user_id: Uuid,
+    calendar_id: Uuid,
+    name: &str,
+    headers: &HeaderMap,
+    body: &str,
+) -> Result<Response, AppError> {
+    let calendar = authz::authorize_calendar(service, user_id, calendar_id, Access::Edit).await?;
+    if let Some(rejection) = reject_body(headers, body) {
+        return Ok(rejection);
+    }
+    let Some(kind) = object_kind(body) else {
+        return Ok(condition_response(
+            StatusCode::FORBIDDEN,
+            Element::caldav("supported-calendar-component"),
+            "Only events (VEVENT) and journal entries (VJOURNAL) can be stored in this calendar",
+        ));
+    };
+
+    // A PUT to an existing object resource replaces it
+    let existing = find_object(service, calendar.id, name).await?;
+    let current_etag = match &existing {
+        Some(object) => Some(object.etag(service, &calendar).await?),
+        None => None,
+    };
+    if !preconditions_hold(headers, current_etag.as_deref()) {
+        // An outdated If-Match means the client changed an event it no longer has
+        if let Some(CalendarObject::Event(existing)) = &existing
+            && matches!(kind, ObjectKind::Event)
+            && headers.contains_key(header::IF_MATCH)
+        {
+            keep_conflict(service, existing, headers, body).await?;
+        }
+        return Ok(empty_response(StatusCode::PRECONDITION_FAILED));
+    }
+
+    // A UID identifies one resource of the collection: it can't be reused by another
+    // resource, nor changed by replacing the resource that has it
+    let uid = object_uid(body);
+    if let Some(uid) = &uid {
+        if let Some(other) = find_object_by_uid(service, calendar.id, uid).await?
+            && existing.as_ref().is_none_or(|o| o.id() != other.id())
+        {
+            return Ok(uid_conflict(service, calendar.id, &other, "Another resource in this calendar already has this UID"));
+        }
+        if let Some(existing) = &existing
+            && existing.ical_uid() != *uid
+        {
+            return Ok(uid_conflict(service, calendar.id, existing, "The UID of an existing resource can't be changed"));
+        }
+    }
+
+    let result = match (kind, existing) {
+        (ObjectKind::Event, None) => event(service, &calendar, None, name, uid.as_deref(), headers, body).await,
+        (ObjectKind::Event, Some(CalendarObject::Event(existing))) => {
+            event(service, &calendar, Some(existing), name, uid.as_deref(), headers, body).await
+        }
+        (ObjectKind::Journal, None) => journal(service, &calendar, None, name, body).await,
+        (ObjectKind::Journal, Some(CalendarObject::Journal(existing))) => {
+            journal(service, &calendar, Some(existing), name, body).await
+        }
+        _ => return Ok(condition_response(
+            StatusCode::FORBIDDEN,
+            Element::caldav("valid-calendar-object-resource"),
+            "A resource can't change between an event and a journal entry",
+        )),
+    };
+
+    match result {
+        Err(AppError::ValidationError(message) | AppError::FieldValidationError { message, .. }) => {
+            Ok(invalid_calendar_data(&message))
+        }
+        result => result,
+    }
+}
+
+/// Create an event from a VEVENT under `name`, or replace `existing`. The highest
+/// SEQUENCE a client writes is kept; without `If-Match`, replacing an event with a
+/// lower one is a change to an older revision and is kept as a conflict copy instead.
+async fn event(
+    service: &CalendarService,
+    calendar: &Calendar,
+    existing: Option<Event>,
+    name: &str,
+    uid: Option<&str>,
+    headers: &HeaderMap,
+    body: &str,
+) -> Result<Response, AppError> {
+    let new_event = match outlook::written_by_outlook(body) {
+        true => parse_icalendar(&outlook::normalize(body).0)?,
+        false => parse_icalendar(body)?,
+    };
+    let sequence = object_sequence(body);
+    if let Some(existing) = existing {
+        if !headers.contains_key(header::IF_MATCH) && sequence.is_some_and(|sequence| sequence < existing.sequence) {
+            let client = headers.get(header::USER_AGENT).and_then(|v| v.to_str().ok());
+            service.store_edit_conflict(existing.id, client, new_event).await?;
+            return Ok(text_response(
+                StatusCode::CONFLICT,
+                format!("The event was changed in revision {}; this change was kept as a conflict copy", existing.sequence),
+            ));
+        }
+
+        let mut event = service.update_event(existing.id, UpdateEvent {
+            title: Some(new_event.title),
+            // The new resource replaces the old one, so missing properties are removed
+            description: Some(new_event.description),
+            location: Some(new_event.location),
+            start_time: Some(new_event.start_time),
+            end_time: Some(new_event.end_time),
+            is_all_day: Some(new_event.is_all_day),
+            rrule: new_event.rrule,
+            geo_lat: Some(new_event.geo_lat),
+            geo_lon: Some(new_event.geo_lon),
+            alarm_minutes: Some(new_event.alarm_minutes),
+        }).await?;
+        if let Some(sequence) = sequence {
+            event = service.raise_event_sequence(event.id, sequence).await?;
+        }
+
+        return Ok(replaced_response(CalendarObject::Event(event).etag(service, calendar).await?));
+    }
+
+    let mut event = service.create_event_resource(calendar.id, new_event, uid, name).await?;
+    if let Some(sequence) = sequence {
+        event = service.raise_event_sequence(event.id, sequence).await?;
+    }
+    let etag = CalendarObject::Event(event).etag(service, calendar).await?;
+    Ok(created_response(service, calendar, etag, name))
+}
+
+/// Create a journal entry from a VJOURNAL under `name`, or replace `existing`
+async fn journal(
+    service: &CalendarService,
+    calendar: &Calendar,
+    existing: Option<Journal>,
+    name: &str,
+    body: &str,
+) -> Result<Response, AppError> {
+    let new_journal = parse_icalendar_journal(body)?;
+    if let Some(existing) = existing {
+        let journal = service.replace_journal(existing.id, new_journal).await?;
+        return Ok(replaced_response(journal_etag(calendar, &journal)));
+    }
+
+    let journal = service.create_journal_resource(calendar.id, new_journal, name).await?;
+    Ok(created_response(service, calendar, journal_etag(calendar, &journal), name))
+}
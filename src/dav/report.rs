@@ -0,0 +1,148 @@
+//! REPORT (calendar-query, calendar-multiget and free-busy-query, and the principal
+//! reports on the root)
+
+use axum::body::Body;
+use axum::http::{header, StatusCode};
+use axum::response::Response;
+use uuid::Uuid;
+use crate::authz::{self, Access};
+use crate::dav_usage::FeatureKind;
+use crate::error::AppError;
+use crate::models::Calendar;
+use crate::services::CalendarService;
+use crate::streaming::{self, Chunks};
+use super::xml::{Element, Multistatus, ReportKind, ReportRequest};
+use super::get::journal_object_ics;
+use super::{event_etag, journal_etag, principal, record_missing_props};
+
+/// Parse a REPORT body. Report types other than calendar-query and calendar-multiget
+/// are answered like a calendar-query and counted as unsupported.
+pub(super) fn parse(service: &CalendarService, body: &str) -> Result<ReportRequest, AppError> {
+    let report = ReportRequest::parse(body)?;
+    if let ReportKind::Other(name) = &report.kind {
+        service.dav_usage().record(FeatureKind::Report, name);
+    }
+    Ok(report)
+}
+
+/// Multistatus of `calendar-data` entries for the calendar objects in `calendars`,
+/// streamed a page of events at a time. `collection` is the namespace the hrefs are
+/// generated under, e.g. `/calendars`; journal entries are only included with `journals`.
+pub(super) fn calendar_data_response(
+    service: &CalendarService,
+    calendars: Vec<Calendar>,
+    collection: &'static str,
+    report: ReportRequest,
+    journals: bool,
+) -> Response {
+    // Every entry has the same properties, so one stands in for all of them
+    let mut probe = Multistatus::new();
+    probe.push(report.response(String::new(), vec![Element::dav("getetag"), Element::caldav("calendar-data")]));
+    record_missing_props(service, &probe);
+
+    let service = service.clone();
+    let body = streaming::body(move |chunks| async move {
+        chunks.send(Multistatus::start_xml()).await?;
+        for calendar in &calendars {
+            write_calendar_data(&service, calendar, collection, &report, journals, &chunks).await?;
+        }
+        chunks.send(Multistatus::end_xml()).await
+    });
+
+    Response::builder()
+        .status(StatusCode::MULTI_STATUS)
+        .header(header::CONTENT_TYPE, "application/xml; charset=utf-8")
+        .body(body)
+        .unwrap()
+}
+
+/// Write the `calendar-data` entries of one calendar, a chunk per page of events
+async fn write_calendar_data(
+    service: &CalendarService,
+    calendar: &Calendar,
+    collection: &str,
+    report: &ReportRequest,
+    journals: bool,
+    chunks: &Chunks,
+) -> Result<(), AppError> {
+    let mut after = None;
+    loop {
+        let events = service.get_events_page(calendar.id, after).await?;
+        let Some(last) = events.last() else { break };
+        after = Some(last.id);
+
+        // Overridden instances are returned inside their series master's resource
+        let mut chunk = String::new();
+        for event in events.iter().filter(|e| !e.is_exception()) {
+            let href = service.href(&format!("{}/{}/{}", collection, calendar.id, event.object_name()));
+            if !report.includes(&href) {
+                continue;
+            }
+            let object_events = service.get_calendar_object_events(event).await?;
+
+            chunk.push_str(&report.response(href, vec![
+                Element::dav("getetag").text(event_etag(calendar, event, &object_events)),
+                Element::caldav("calendar-data").text(service.calendar_object_ics(&object_events).await?),
+            ]).to_xml());
+        }
+        chunks.send(chunk).await?;
+    }
+
+    if !journals {
+        return Ok(());
+    }
+    let mut chunk = String::new();
+    for journal in service.get_journals_by_calendar_id(calendar.id).await? {
+        let href = service.href(&format!("{}/{}/{}", collection, calendar.id, journal.object_name()));
+        if !report.includes(&href) {
+            continue;
+        }
+        chunk.push_str(&report.response(href, vec![
+            Element::dav("getetag").text(journal_etag(calendar, &journal)),
+            Element::caldav("calendar-data").text(journal_object_ics(&journal)),
+        ]).to_xml());
+    }
+    chunks.send(chunk).await
+}
+
+/// REPORT on the CalDAV root, covering the user's own and shared calendars. The root
+/// is also the user's principal, so principal reports are answered for it.
+pub(super) async fn root(service: &CalendarService, user_id: Uuid, body: &str) -> Result<Response, AppError> {
+    let report = parse(service, body)?;
+    if matches!(report.kind, ReportKind::ExpandProperty | ReportKind::PrincipalPropertySearch) {
+        return principal::report(service, user_id, &service.href("/calendars/"), &report).await;
+    }
+    if report.kind == ReportKind::FreeBusyQuery {
+        return Err(AppError::ValidationError("Free-busy is reported for a single calendar".to_string()));
+    }
+    let mut calendars = service.get_calendars_by_user_id(user_id).await?;
+    let shared = service.get_calendars_shared_with_user(user_id).await?;
+    calendars.extend(shared.into_iter().map(|(calendar, _)| calendar));
+
+    Ok(calendar_data_response(service, calendars, "/calendars", report, true))
+}
+
+/// REPORT on a single calendar collection
+pub(super) async fn calendar(service: &CalendarService, user_id: Uuid, calendar_id: Uuid, body: &str) -> Result<Response, AppError> {
+    let calendar = authz::authorize_calendar(service, user_id, calendar_id, Access::View).await?;
+    let report = parse(service, body)?;
+    if report.kind == ReportKind::FreeBusyQuery {
+        return free_busy(service, &calendar, &report).await;
+    }
+
+    Ok(calendar_data_response(service, vec![calendar], "/calendars", report, true))
+}
+
+/// Answer a free-busy-query with a VFREEBUSY of the calendar within the requested window
+async fn free_busy(service: &CalendarService, calendar: &Calendar, report: &ReportRequest) -> Result<Response, AppError> {
+    let (start, end) = report.time_range
+        .filter(|(start, end)| start < end)
+        .ok_or_else(|| AppError::ValidationError("A free-busy-query needs a time-range with a start and an end".to_string()))?;
+
+    let free_busy = service.get_free_busy(calendar, start, end).await?;
+    Ok(Response::builder()
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, "text/calendar; charset=utf-8")
+        .body(Body::from(free_busy.to_ical_string(service.now())))
+        .unwrap())
+}
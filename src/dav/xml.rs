@@ -0,0 +1,720 @@
+//! XML parsing of DAV request bodies and generation of multistatus and error responses
+
+use axum::http::StatusCode;
+use chrono::{DateTime, NaiveDateTime, Utc};
+use quick_xml::events::{BytesDecl, BytesEnd, BytesStart, BytesText, Event};
+use quick_xml::name::ResolveResult;
+use quick_xml::{NsReader, Writer};
+
+use crate::error::AppError;
+
+/// XML namespaces used in DAV bodies, with the prefixes this server writes them with
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Namespace {
+    Dav,
+    CalDav,
+    CalendarServer,
+    Push,
+    Apple,
+    /// Any other namespace, written with an inline declaration
+    Other(String),
+}
+
+/// Namespaces declared on the root element of response bodies
+const DECLARED_NAMESPACES: [Namespace; 5] = [
+    Namespace::Dav,
+    Namespace::CalDav,
+    Namespace::CalendarServer,
+    Namespace::Push,
+    Namespace::Apple,
+];
+
+impl Namespace {
+    pub fn uri(&self) -> &str {
+        match self {
+            Namespace::Dav => "DAV:",
+            Namespace::CalDav => "urn:ietf:params:xml:ns:caldav",
+            Namespace::CalendarServer => "http://calendarserver.org/ns/",
+            Namespace::Push => "https://bitfire.at/webdav-push",
+            Namespace::Apple => "http://apple.com/ns/ical/",
+            Namespace::Other(uri) => uri,
+        }
+    }
+
+    fn prefix(&self) -> &'static str {
+        match self {
+            Namespace::Dav => "d",
+            Namespace::CalDav => "cal",
+            Namespace::CalendarServer => "cs",
+            Namespace::Push => "P",
+            Namespace::Apple => "A",
+            Namespace::Other(_) => "x",
+        }
+    }
+
+    fn from_uri(uri: &str) -> Self {
+        DECLARED_NAMESPACES.into_iter()
+            .find(|ns| ns.uri() == uri)
+            .unwrap_or_else(|| Namespace::Other(uri.to_string()))
+    }
+}
+
+// Parsing
+
+/// Element of a parsed request body with its namespace resolved
+#[derive(Debug, Clone)]
+pub struct XmlNode {
+    /// Namespace URI, `None` for elements without a namespace
+    pub ns: Option<String>,
+    pub name: String,
+    /// Attributes by local name, e.g. `name` and `namespace` of an expand-property `property`
+    pub attributes: Vec<(String, String)>,
+    /// Text content, trimmed
+    pub text: String,
+    pub children: Vec<XmlNode>,
+}
+
+impl XmlNode {
+    pub fn is(&self, ns: &Namespace, name: &str) -> bool {
+        self.name == name && self.ns.as_deref() == Some(ns.uri())
+    }
+
+    /// First direct child with the given namespace and name
+    pub fn child(&self, ns: &Namespace, name: &str) -> Option<&XmlNode> {
+        self.children.iter().find(|c| c.is(ns, name))
+    }
+
+    /// First element, this one or a descendant (depth-first), with the given namespace and name
+    pub fn find(&self, ns: &Namespace, name: &str) -> Option<&XmlNode> {
+        if self.is(ns, name) {
+            return Some(self);
+        }
+        self.children.iter().find_map(|c| c.find(ns, name))
+    }
+
+    /// Text of the first element with the given namespace and name
+    pub fn find_text(&self, ns: &Namespace, name: &str) -> Option<String> {
+        self.find(ns, name).map(|node| node.text.clone())
+    }
+
+    pub fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes.iter().find(|(n, _)| n == name).map(|(_, value)| value.as_str())
+    }
+
+    fn prop_name(&self) -> PropName {
+        PropName {
+            ns: self.ns.clone(),
+            name: self.name.clone(),
+        }
+    }
+}
+
+fn invalid_xml(e: impl std::fmt::Display) -> AppError {
+    AppError::ValidationError(format!("Invalid XML body: {}", e))
+}
+
+fn new_node(ns: ResolveResult, start: &BytesStart) -> Result<XmlNode, AppError> {
+    let ns = match ns {
+        ResolveResult::Bound(ns) => Some(String::from_utf8_lossy(ns.as_ref()).into_owned()),
+        ResolveResult::Unbound => None,
+        ResolveResult::Unknown(prefix) => {
+            return Err(invalid_xml(format!("undeclared namespace prefix {}", String::from_utf8_lossy(&prefix))));
+        }
+    };
+
+    let attributes = start.attributes()
+        .map(|attribute| {
+            let attribute = attribute.map_err(invalid_xml)?;
+            let value = attribute.unescape_value().map_err(invalid_xml)?.into_owned();
+            Ok((String::from_utf8_lossy(attribute.key.local_name().as_ref()).into_owned(), value))
+        })
+        .collect::<Result<Vec<_>, AppError>>()?;
+
+    Ok(XmlNode {
+        ns,
+        name: String::from_utf8_lossy(start.local_name().as_ref()).into_owned(),
+        attributes,
+        text: String::new(),
+        children: Vec::new(),
+    })
+}
+
+/// Attach a finished element to its parent, or return it when it is the root
+fn close_node(stack: &mut [XmlNode], node: XmlNode) -> Option<XmlNode> {
+    match stack.last_mut() {
+        Some(parent) => {
+            parent.children.push(node);
+            None
+        }
+        None => Some(node),
+    }
+}
+
+/// Parse a request body into its root element
+pub fn parse(body: &str) -> Result<XmlNode, AppError> {
+    let mut reader = NsReader::from_str(body);
+    reader.config_mut().trim_text(true);
+
+    let mut stack: Vec<XmlNode> = Vec::new();
+
+    loop {
+        let (ns, event) = reader.read_resolved_event().map_err(invalid_xml)?;
+        match event {
+            Event::Start(start) => stack.push(new_node(ns, &start)?),
+            Event::Empty(start) => {
+                let node = new_node(ns, &start)?;
+                if let Some(root) = close_node(&mut stack, node) {
+                    return Ok(root);
+                }
+            }
+            Event::End(_) => {
+                let node = stack.pop().ok_or_else(|| invalid_xml("unexpected end tag"))?;
+                if let Some(root) = close_node(&mut stack, node) {
+                    return Ok(root);
+                }
+            }
+            Event::Text(text) => {
+                if let Some(node) = stack.last_mut() {
+                    node.text.push_str(&text.unescape().map_err(invalid_xml)?);
+                }
+            }
+            Event::CData(data) => {
+                if let Some(node) = stack.last_mut() {
+                    node.text.push_str(&String::from_utf8_lossy(&data.into_inner()));
+                }
+            }
+            Event::Eof => return Err(invalid_xml("unexpected end of document")),
+            _ => {}
+        }
+    }
+}
+
+/// Parse an optional request body; an empty body yields `None`
+fn parse_optional(body: &str) -> Result<Option<XmlNode>, AppError> {
+    if body.trim().is_empty() {
+        Ok(None)
+    } else {
+        parse(body).map(Some)
+    }
+}
+
+/// Property named in a request body
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PropName {
+    pub ns: Option<String>,
+    pub name: String,
+}
+
+impl PropName {
+    fn matches(&self, element: &Element) -> bool {
+        self.name == element.name && self.ns.as_deref() == Some(element.ns.uri())
+    }
+
+    /// Empty element naming this property, e.g. for a 404 propstat
+    fn to_element(&self) -> Element {
+        let ns = self.ns.as_deref().map(Namespace::from_uri).unwrap_or(Namespace::Other(String::new()));
+        Element::new(ns, &self.name)
+    }
+}
+
+/// Properties of the `prop` child of a request element, if any
+fn requested_props(node: &XmlNode) -> Vec<PropName> {
+    node.child(&Namespace::Dav, "prop")
+        .map(|prop| prop.children.iter().map(XmlNode::prop_name).collect())
+        .unwrap_or_default()
+}
+
+/// What a PROPFIND asks for
+#[derive(Debug, Clone, PartialEq)]
+pub enum PropfindRequest {
+    /// All properties; also what an empty body means
+    AllProp,
+    /// Only the names of the available properties
+    PropName,
+    Prop(Vec<PropName>),
+}
+
+impl PropfindRequest {
+    pub fn parse(body: &str) -> Result<Self, AppError> {
+        let Some(root) = parse_optional(body)? else {
+            return Ok(PropfindRequest::AllProp);
+        };
+        if !root.is(&Namespace::Dav, "propfind") {
+            return Err(AppError::ValidationError("Expected a propfind request body".to_string()));
+        }
+
+        if root.child(&Namespace::Dav, "propname").is_some() {
+            Ok(PropfindRequest::PropName)
+        } else if root.child(&Namespace::Dav, "prop").is_some() {
+            Ok(PropfindRequest::Prop(requested_props(&root)))
+        } else {
+            Ok(PropfindRequest::AllProp)
+        }
+    }
+
+    /// Split the properties a resource has into a 200 propstat and, for requested
+    /// properties it doesn't have, a 404 propstat
+    pub fn response(&self, href: impl Into<String>, available: Vec<Element>) -> DavResponse {
+        let response = DavResponse::new(href);
+        match self {
+            PropfindRequest::AllProp => response.propstat(StatusCode::OK, available),
+            PropfindRequest::PropName => response.propstat(
+                StatusCode::OK,
+                available.iter().map(|e| Element::new(e.ns.clone(), &e.name)).collect(),
+            ),
+            PropfindRequest::Prop(requested) => {
+                let missing = requested.iter()
+                    .filter(|p| !available.iter().any(|e| p.matches(e)))
+                    .map(PropName::to_element)
+                    .collect();
+                let found = available.into_iter()
+                    .filter(|e| requested.iter().any(|p| p.matches(e)))
+                    .collect();
+                response
+                    .propstat(StatusCode::OK, found)
+                    .propstat(StatusCode::NOT_FOUND, missing)
+            }
+        }
+    }
+}
+
+/// Kind of a REPORT request
+#[derive(Debug, Clone, PartialEq)]
+pub enum ReportKind {
+    CalendarQuery,
+    CalendarMultiget,
+    /// RFC 3253 `expand-property`, answered for principals
+    ExpandProperty,
+    /// RFC 3744 `principal-property-search`
+    PrincipalPropertySearch,
+    /// RFC 4791 `free-busy-query`
+    FreeBusyQuery,
+    /// Any other report, as `{namespace}name`
+    Other(String),
+}
+
+/// A parsed REPORT request
+#[derive(Debug, Clone)]
+pub struct ReportRequest {
+    pub kind: ReportKind,
+    /// Requested properties; empty means all
+    pub props: Vec<PropName>,
+    /// Resources named by a `calendar-multiget`
+    pub hrefs: Vec<String>,
+    /// Properties of an `expand-property`
+    pub expand: Vec<ExpandProperty>,
+    /// Conditions of a `principal-property-search`
+    pub searches: Vec<PropertySearch>,
+    /// Whether a principal must match any of `searches` rather than all of them
+    pub match_any: bool,
+    /// Window of a `time-range`, as `start` and `end`
+    pub time_range: Option<(DateTime<Utc>, DateTime<Utc>)>,
+}
+
+/// Property asked for by an `expand-property` REPORT. Its `properties` are reported
+/// for each resource the property's hrefs point to, instead of the hrefs themselves.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExpandProperty {
+    pub name: PropName,
+    pub properties: Vec<ExpandProperty>,
+}
+
+impl ExpandProperty {
+    fn parse(node: &XmlNode) -> Option<Self> {
+        Some(ExpandProperty {
+            name: PropName {
+                // The namespace defaults to DAV: (RFC 3253 section 3.8)
+                ns: Some(node.attribute("namespace").unwrap_or(Namespace::Dav.uri()).to_string()),
+                name: node.attribute("name")?.to_string(),
+            },
+            properties: expand_properties(node),
+        })
+    }
+}
+
+fn expand_properties(node: &XmlNode) -> Vec<ExpandProperty> {
+    node.children.iter()
+        .filter(|c| c.is(&Namespace::Dav, "property"))
+        .filter_map(ExpandProperty::parse)
+        .collect()
+}
+
+/// One `property-search` of a `principal-property-search`: a principal matches when
+/// one of `props` has a value containing `text`, ignoring case
+#[derive(Debug, Clone, PartialEq)]
+pub struct PropertySearch {
+    pub props: Vec<PropName>,
+    pub text: String,
+    /// `match-type="starts-with"`, sent by Apple clients; otherwise a substring match
+    pub starts_with: bool,
+}
+
+impl PropertySearch {
+    pub fn matches(&self, available: &[Element]) -> bool {
+        let text = self.text.to_lowercase();
+        available.iter()
+            .filter(|e| self.props.iter().any(|p| p.matches(e)))
+            .flat_map(Element::texts)
+            .map(|value| value.strip_prefix("mailto:").unwrap_or(value).to_lowercase())
+            .any(|value| if self.starts_with { value.starts_with(&text) } else { value.contains(&text) })
+    }
+}
+
+impl ReportRequest {
+    /// Parse a REPORT body. Clients that send none get a `calendar-query` for everything.
+    pub fn parse(body: &str) -> Result<Self, AppError> {
+        let Some(root) = parse_optional(body)? else {
+            return Ok(ReportRequest {
+                kind: ReportKind::CalendarQuery,
+                props: Vec::new(),
+                hrefs: Vec::new(),
+                expand: Vec::new(),
+                searches: Vec::new(),
+                match_any: false,
+                time_range: None,
+            });
+        };
+
+        let kind = if root.is(&Namespace::CalDav, "calendar-query") {
+            ReportKind::CalendarQuery
+        } else if root.is(&Namespace::CalDav, "calendar-multiget") {
+            ReportKind::CalendarMultiget
+        } else if root.is(&Namespace::Dav, "expand-property") {
+            ReportKind::ExpandProperty
+        } else if root.is(&Namespace::Dav, "principal-property-search") {
+            ReportKind::PrincipalPropertySearch
+        } else if root.is(&Namespace::CalDav, "free-busy-query") {
+            ReportKind::FreeBusyQuery
+        } else {
+            ReportKind::Other(format!("{{{}}}{}", root.ns.as_deref().unwrap_or_default(), root.name))
+        };
+        let hrefs = root.children.iter()
+            .filter(|c| c.is(&Namespace::Dav, "href"))
+            .map(|c| c.text.clone())
+            .collect();
+
+        let searches = root.children.iter()
+            .filter(|c| c.is(&Namespace::Dav, "property-search"))
+            .map(|search| PropertySearch {
+                props: requested_props(search),
+                text: search.find_text(&Namespace::Dav, "match").unwrap_or_default(),
+                starts_with: search.child(&Namespace::Dav, "match")
+                    .and_then(|m| m.attribute("match-type"))
+                    .is_some_and(|t| t == "starts-with"),
+            })
+            .collect();
+
+        Ok(ReportRequest {
+            kind,
+            props: requested_props(&root),
+            hrefs,
+            expand: expand_properties(&root),
+            searches,
+            match_any: root.attribute("test") == Some("anyof"),
+            time_range: root.find(&Namespace::CalDav, "time-range").and_then(|range| {
+                let time = |name| range.attribute(name)
+                    .and_then(|t| NaiveDateTime::parse_from_str(t, "%Y%m%dT%H%M%SZ").ok())
+                    .map(|t| t.and_utc());
+                Some((time("start")?, time("end")?))
+            }),
+        })
+    }
+
+    /// Whether a resource is part of the result (multiget lists its resources by href)
+    pub fn includes(&self, href: &str) -> bool {
+        match self.kind {
+            ReportKind::CalendarMultiget => self.hrefs.iter().any(|h| h == href),
+            _ => true,
+        }
+    }
+
+    /// Response for one resource, limited to the requested properties
+    pub fn response(&self, href: impl Into<String>, available: Vec<Element>) -> DavResponse {
+        if self.props.is_empty() {
+            DavResponse::new(href).propstat(StatusCode::OK, available)
+        } else {
+            PropfindRequest::Prop(self.props.clone()).response(href, available)
+        }
+    }
+}
+
+/// Properties set by a PROPPATCH request, in document order, with their text value
+#[derive(Debug, Clone, Default)]
+pub struct ProppatchRequest {
+    pub set: Vec<(PropName, String)>,
+}
+
+impl ProppatchRequest {
+    pub fn parse(body: &str) -> Result<Self, AppError> {
+        let root = parse(body)?;
+        if !root.is(&Namespace::Dav, "propertyupdate") {
+            return Err(AppError::ValidationError("Expected a propertyupdate request body".to_string()));
+        }
+
+        let mut request = ProppatchRequest::default();
+        for instruction in &root.children {
+            let Some(prop) = instruction.child(&Namespace::Dav, "prop") else {
+                continue;
+            };
+            if instruction.is(&Namespace::Dav, "set") {
+                request.set.extend(prop.children.iter().map(|p| (p.prop_name(), p.text.clone())));
+            }
+        }
+
+        Ok(request)
+    }
+
+    /// Value set for a property, if the request sets it
+    pub fn value(&self, ns: &Namespace, name: &str) -> Option<&str> {
+        self.set.iter()
+            .find(|(p, _)| p.name == name && p.ns.as_deref() == Some(ns.uri()))
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// Display name requested by an MKCOL / MKCALENDAR body, if any
+pub fn mkcol_displayname(body: &str) -> Result<Option<String>, AppError> {
+    Ok(parse_optional(body)?
+        .and_then(|root| root.find_text(&Namespace::Dav, "displayname"))
+        .filter(|name| !name.is_empty()))
+}
+
+// Generation
+
+/// XML element to be written into a response
+#[derive(Debug, Clone, PartialEq)]
+pub struct Element {
+    pub ns: Namespace,
+    pub name: String,
+    attributes: Vec<(String, String)>,
+    text: Option<String>,
+    children: Vec<Element>,
+}
+
+impl Element {
+    pub fn new(ns: Namespace, name: &str) -> Self {
+        Self {
+            ns,
+            name: name.to_string(),
+            attributes: Vec::new(),
+            text: None,
+            children: Vec::new(),
+        }
+    }
+
+    /// Element in the DAV: namespace
+    pub fn dav(name: &str) -> Self {
+        Self::new(Namespace::Dav, name)
+    }
+
+    /// Element in the CalDAV namespace
+    pub fn caldav(name: &str) -> Self {
+        Self::new(Namespace::CalDav, name)
+    }
+
+    pub fn attr(mut self, name: &str, value: impl Into<String>) -> Self {
+        self.attributes.push((name.to_string(), value.into()));
+        self
+    }
+
+    /// Text content; escaped when written
+    pub fn text(mut self, text: impl Into<String>) -> Self {
+        self.text = Some(text.into());
+        self
+    }
+
+    pub fn child(mut self, child: Element) -> Self {
+        self.children.push(child);
+        self
+    }
+
+    pub fn children(mut self, children: impl IntoIterator<Item = Element>) -> Self {
+        self.children.extend(children);
+        self
+    }
+
+    /// Text of this element and its descendants, e.g. the hrefs of a property
+    pub fn texts(&self) -> Vec<&str> {
+        self.text.as_deref().into_iter()
+            .chain(self.children.iter().flat_map(Element::texts))
+            .collect()
+    }
+
+    /// Targets of the `DAV:href` children of a property
+    pub fn hrefs(&self) -> Vec<&str> {
+        self.children.iter()
+            .filter(|c| c.ns == Namespace::Dav && c.name == "href")
+            .filter_map(|c| c.text.as_deref())
+            .collect()
+    }
+
+    fn write(&self, writer: &mut Writer<Vec<u8>>) {
+        let qualified = match &self.ns {
+            Namespace::Other(uri) if uri.is_empty() => self.name.clone(),
+            ns => format!("{}:{}", ns.prefix(), self.name),
+        };
+
+        let mut start = BytesStart::new(qualified.as_str());
+        if let Namespace::Other(uri) = &self.ns
+            && !uri.is_empty()
+        {
+            start.push_attribute(("xmlns:x", uri.as_str()));
+        }
+        for (name, value) in &self.attributes {
+            start.push_attribute((name.as_str(), value.as_str()));
+        }
+
+        if self.text.is_none() && self.children.is_empty() {
+            write_event(writer, Event::Empty(start));
+            return;
+        }
+
+        write_event(writer, Event::Start(start));
+        if let Some(text) = &self.text {
+            write_event(writer, Event::Text(BytesText::new(text)));
+        }
+        for child in &self.children {
+            child.write(writer);
+        }
+        write_event(writer, Event::End(BytesEnd::new(qualified.as_str())));
+    }
+}
+
+fn write_event(writer: &mut Writer<Vec<u8>>, event: Event) {
+    // Writing into a Vec can't fail
+    let _ = writer.write_event(event);
+}
+
+/// Status line of a propstat, e.g. `HTTP/1.1 404 Not Found`
+fn status_line(status: StatusCode) -> String {
+    format!("HTTP/1.1 {}", status)
+}
+
+/// One `response` of a multistatus
+#[derive(Debug, Clone)]
+pub struct DavResponse {
+    href: String,
+    propstats: Vec<(StatusCode, Vec<Element>)>,
+}
+
+impl DavResponse {
+    pub fn new(href: impl Into<String>) -> Self {
+        Self {
+            href: href.into(),
+            propstats: Vec::new(),
+        }
+    }
+
+    /// Add a propstat; an empty property list is left out
+    pub fn propstat(mut self, status: StatusCode, props: Vec<Element>) -> Self {
+        if !props.is_empty() {
+            self.propstats.push((status, props));
+        }
+        self
+    }
+
+    /// The response as an XML fragment, for multistatus bodies written piece by piece
+    pub fn to_xml(&self) -> String {
+        let mut writer = Writer::new(Vec::new());
+        self.to_element().write(&mut writer);
+        String::from_utf8(writer.into_inner()).unwrap_or_default()
+    }
+
+    pub fn to_element(&self) -> Element {
+        Element::dav("response")
+            .child(Element::dav("href").text(self.href.as_str()))
+            .children(self.propstats.iter().map(|(status, props)| {
+                Element::dav("propstat")
+                    .child(Element::dav("prop").children(props.iter().cloned()))
+                    .child(Element::dav("status").text(status_line(*status)))
+            }))
+    }
+}
+
+/// A `multistatus` response body
+#[derive(Debug, Clone, Default)]
+pub struct Multistatus {
+    responses: Vec<DavResponse>,
+}
+
+impl Multistatus {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, response: DavResponse) {
+        self.responses.push(response);
+    }
+
+    /// Properties any response reported as missing (404), each once, as `{namespace}name`
+    pub fn missing_props(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.responses.iter()
+            .flat_map(|response| response.propstats.iter())
+            .filter(|(status, _)| *status == StatusCode::NOT_FOUND)
+            .flat_map(|(_, props)| props.iter().map(|prop| format!("{{{}}}{}", prop.ns.uri(), prop.name)))
+            .collect();
+        names.sort();
+        names.dedup();
+        names
+    }
+
+    pub fn to_xml(&self) -> String {
+        document("multistatus", self.responses.iter().map(DavResponse::to_element))
+    }
+
+    /// Opening of a multistatus body whose responses follow as [`DavResponse::to_xml`]
+    pub fn start_xml() -> String {
+        let mut writer = Writer::new(Vec::new());
+        write_event(&mut writer, Event::Decl(BytesDecl::new("1.0", Some("utf-8"), None)));
+        write_event(&mut writer, Event::Start(root_start("multistatus")));
+        String::from_utf8(writer.into_inner()).unwrap_or_default()
+    }
+
+    /// Closing of a multistatus body started with [`Multistatus::start_xml`]
+    pub fn end_xml() -> String {
+        format!("</{}:multistatus>", Namespace::Dav.prefix())
+    }
+}
+
+impl Extend<DavResponse> for Multistatus {
+    fn extend<I: IntoIterator<Item = DavResponse>>(&mut self, iter: I) {
+        self.responses.extend(iter);
+    }
+}
+
+/// A `DAV:error` response body naming the precondition or postcondition a request
+/// failed (RFC 4918 section 16), optionally with a description for the user
+pub fn error_xml(condition: Element, description: Option<&str>) -> String {
+    let description = description.map(|text| {
+        Element::new(Namespace::Other(ERROR_DESCRIPTION_NAMESPACE.to_string()), "error-description").text(text)
+    });
+    document("error", std::iter::once(condition).chain(description))
+}
+
+/// Namespace of the human-readable `error-description` some clients (iOS, macOS)
+/// show next to a failed precondition
+const ERROR_DESCRIPTION_NAMESPACE: &str = "http://twistedmatrix.com/xml_namespace/dav/";
+
+/// Start tag of a `DAV:` root element declaring the namespaces this server uses
+fn root_start(root_name: &str) -> BytesStart<'static> {
+    let mut root = BytesStart::new(format!("{}:{}", Namespace::Dav.prefix(), root_name));
+    for ns in &DECLARED_NAMESPACES {
+        root.push_attribute((format!("xmlns:{}", ns.prefix()).as_str(), ns.uri()));
+    }
+    root
+}
+
+/// XML document with a `DAV:` root element declaring the namespaces this server uses
+fn document(root_name: &str, children: impl Iterator<Item = Element>) -> String {
+    let mut writer = Writer::new(Vec::new());
+    write_event(&mut writer, Event::Decl(BytesDecl::new("1.0", Some("utf-8"), None)));
+
+    let qualified = format!("{}:{}", Namespace::Dav.prefix(), root_name);
+    write_event(&mut writer, Event::Start(root_start(root_name)));
+    for child in children {
+        child.write(&mut writer);
+    }
+    write_event(&mut writer, Event::End(BytesEnd::new(qualified.as_str())));
+
+    String::from_utf8(writer.into_inner()).unwrap_or_default()
+}
@@ -0,0 +1,102 @@
+//! Opt-in counters of DAV features clients ask for but the server doesn't implement
+//!
+//! Counting is off unless `DAV_USAGE_STATS` is set. Only the feature and how often it
+//! was requested are kept, never who asked, so the numbers can be shared with the
+//! project to decide which CalDAV feature to build next. Counters live in memory and
+//! start from zero on every restart.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+use serde::Serialize;
+
+/// Distinct features tracked at most, so clients can't grow the table without bound
+const MAX_TRACKED_FEATURES: usize = 500;
+
+/// What part of the protocol an unsupported request used
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FeatureKind {
+    /// A REPORT type, e.g. `sync-collection`
+    Report,
+    /// A property requested by PROPFIND or REPORT that came back 404
+    Property,
+    /// An HTTP or WebDAV method, e.g. `LOCK`
+    Method,
+}
+
+impl fmt::Display for FeatureKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FeatureKind::Report => write!(f, "report"),
+            FeatureKind::Property => write!(f, "property"),
+            FeatureKind::Method => write!(f, "method"),
+        }
+    }
+}
+
+/// How often an unsupported feature was requested
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct UnsupportedFeature {
+    pub kind: FeatureKind,
+    /// Feature name; properties and reports are given as `{namespace}name`
+    pub name: String,
+    pub count: u64,
+}
+
+/// Shared counters of unsupported DAV features; a disabled instance ignores everything
+#[derive(Debug, Clone, Default)]
+pub struct DavUsage {
+    enabled: bool,
+    counts: Arc<Mutex<HashMap<(FeatureKind, String), u64>>>,
+}
+
+impl DavUsage {
+    pub fn new(enabled: bool) -> Self {
+        Self {
+            enabled,
+            ..Self::default()
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Count a request for an unsupported feature. The first request for each feature
+    /// is logged.
+    pub fn record(&self, kind: FeatureKind, name: &str) {
+        if !self.enabled {
+            return;
+        }
+
+        let mut counts = self.counts.lock().unwrap_or_else(|e| e.into_inner());
+        let key = (kind, name.to_string());
+        let tracked = counts.len();
+        match counts.get_mut(&key) {
+            Some(count) => *count += 1,
+            None if tracked < MAX_TRACKED_FEATURES => {
+                tracing::info!(kind = %kind, feature = name, "Client requested an unsupported DAV feature");
+                counts.insert(key, 1);
+            }
+            None => {}
+        }
+    }
+
+    /// Features requested so far, most requested first
+    pub fn unsupported_features(&self) -> Vec<UnsupportedFeature> {
+        let counts = self.counts.lock().unwrap_or_else(|e| e.into_inner());
+        let mut features: Vec<_> = counts
+            .iter()
+            .map(|((kind, name), count)| UnsupportedFeature {
+                kind: *kind,
+                name: name.clone(),
+                count: *count,
+            })
+            .collect();
+        features.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| (a.kind, &a.name).cmp(&(b.kind, &b.name))));
+
+        features
+    }
+}
@@ -0,0 +1,93 @@
+//! Registry of the CalDAV clients that sync with each account
+//!
+//! Every authenticated CalDAV request updates the device it came from with the time and
+//! address of its last sync. There are no per-device passwords, so a device is a user
+//! agent together with the kind of credential it signs in with. Revoking a device
+//! refuses its requests until the user removes it from the list; a client that changes
+//! its user agent shows up as a new device.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, Row};
+use uuid::Uuid;
+
+/// Longest user agent stored, in characters
+const MAX_USER_AGENT_LENGTH: usize = 255;
+
+/// Name a device without a user agent is listed under
+pub const UNKNOWN_CLIENT: &str = "Unknown client";
+
+/// How a device signs in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DeviceCredential {
+    /// Basic auth with the account password
+    Password,
+    /// Bearer token from the API
+    Token,
+}
+
+impl DeviceCredential {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DeviceCredential::Password => "password",
+            DeviceCredential::Token => "token",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "password" => Some(DeviceCredential::Password),
+            "token" => Some(DeviceCredential::Token),
+            _ => None,
+        }
+    }
+}
+
+/// The name a request's user agent is registered under
+pub fn client_name(user_agent: Option<&str>) -> String {
+    match user_agent.map(str::trim).filter(|agent| !agent.is_empty()) {
+        Some(agent) => agent.chars().take(MAX_USER_AGENT_LENGTH).collect(),
+        None => UNKNOWN_CLIENT.to_string(),
+    }
+}
+
+/// CalDAV client of a user
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Device {
+    pub id: Uuid,
+    pub user_agent: String,
+    pub credential: DeviceCredential,
+    /// Address of the last request
+    pub ip: Option<String>,
+    pub first_seen_at: DateTime<Utc>,
+    pub last_seen_at: DateTime<Utc>,
+    pub request_count: i64,
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
+impl FromRow<'_, sqlx::sqlite::SqliteRow> for Device {
+    fn from_row(row: &'_ sqlx::sqlite::SqliteRow) -> Result<Self, sqlx::Error> {
+        let id: String = row.try_get("id")?;
+        let id = Uuid::parse_str(&id).map_err(|e| sqlx::Error::ColumnDecode {
+            index: "id".to_string(),
+            source: Box::new(e),
+        })?;
+        let credential: String = row.try_get("credential")?;
+        let credential = DeviceCredential::parse(&credential).ok_or_else(|| sqlx::Error::ColumnDecode {
+            index: "credential".to_string(),
+            source: format!("unknown credential {}", credential).into(),
+        })?;
+
+        Ok(Device {
+            id,
+            user_agent: row.try_get("user_agent")?,
+            credential,
+            ip: row.try_get("ip")?,
+            first_seen_at: row.try_get("first_seen_at")?,
+            last_seen_at: row.try_get("last_seen_at")?,
+            request_count: row.try_get("request_count")?,
+            revoked_at: row.try_get("revoked_at")?,
+        })
+    }
+}
@@ -0,0 +1,145 @@
+//! Conflicting edits of events, kept for users to resolve
+//!
+//! A change made to an older state of an event than the stored one would silently
+//! undo whatever changed in between. Such a change is refused and kept as a conflict
+//! copy instead: a CalDAV PUT whose `If-Match` ETag is out of date, or that carries a
+//! lower SEQUENCE than the event was already written with, and an edit form submitted
+//! after the event changed elsewhere. Users compare each copy with the stored event in
+//! the web UI and either keep the stored event or apply the copy; applying it is
+//! recorded in the event's history like any other change.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, Row};
+use uuid::Uuid;
+
+use crate::event_history::{self, EventField};
+use crate::models::{Event, NewEvent, UpdateEvent};
+
+/// Longest client name stored, in characters
+const MAX_CLIENT_LENGTH: usize = 255;
+
+/// Change that lost against a newer state of its event
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EditConflict {
+    pub id: Uuid,
+    pub event_id: Uuid,
+    /// User agent of the CalDAV client that sent the change; `None` for the web UI
+    pub client: Option<String>,
+    /// The event as the change would have left it
+    pub version: NewEvent,
+    pub created_at: DateTime<Utc>,
+}
+
+impl EditConflict {
+    /// Client name as it is stored, cut to a sensible length
+    pub fn client_name(user_agent: Option<&str>) -> Option<String> {
+        user_agent.map(|agent| agent.chars().take(MAX_CLIENT_LENGTH).collect())
+    }
+
+    /// `event` with the conflicting version applied
+    pub fn apply_to(&self, event: &Event) -> Event {
+        let version = self.version.clone();
+        Event {
+            title: version.title,
+            description: version.description,
+            location: version.location,
+            geo_lat: version.geo_lat,
+            geo_lon: version.geo_lon,
+            start_time: version.start_time,
+            end_time: version.end_time,
+            is_all_day: version.is_all_day,
+            rrule: version.rrule,
+            alarm_minutes: version.alarm_minutes,
+            ..event.clone()
+        }
+    }
+
+    /// Changes that replace the stored event with the conflicting version
+    pub fn update(&self) -> UpdateEvent {
+        let version = self.version.clone();
+        UpdateEvent {
+            title: Some(version.title),
+            description: Some(version.description),
+            location: Some(version.location),
+            start_time: Some(version.start_time),
+            end_time: Some(version.end_time),
+            is_all_day: Some(version.is_all_day),
+            // An empty rule turns a series back into a single event
+            rrule: Some(version.rrule.unwrap_or_default()),
+            geo_lat: Some(version.geo_lat),
+            geo_lon: Some(version.geo_lon),
+            alarm_minutes: Some(version.alarm_minutes),
+        }
+    }
+}
+
+impl FromRow<'_, sqlx::sqlite::SqliteRow> for EditConflict {
+    fn from_row(row: &'_ sqlx::sqlite::SqliteRow) -> Result<Self, sqlx::Error> {
+        let parse = |column: &str| -> Result<Uuid, sqlx::Error> {
+            let value: String = row.try_get(column)?;
+            Uuid::parse_str(&value).map_err(|e| sqlx::Error::ColumnDecode {
+                index: column.to_string(),
+                source: Box::new(e),
+            })
+        };
+
+        Ok(EditConflict {
+            id: parse("id")?,
+            event_id: parse("event_id")?,
+            client: row.try_get("client")?,
+            version: NewEvent {
+                title: row.try_get("title")?,
+                description: row.try_get("description")?,
+                location: row.try_get("location")?,
+                start_time: row.try_get("start_time")?,
+                end_time: row.try_get("end_time")?,
+                is_all_day: row.try_get("is_all_day")?,
+                rrule: row.try_get("rrule")?,
+                geo_lat: row.try_get("geo_lat")?,
+                geo_lon: row.try_get("geo_lon")?,
+                alarm_minutes: row.try_get("alarm_minutes")?,
+            },
+            created_at: row.try_get("created_at")?,
+        })
+    }
+}
+
+/// Conflict copy next to the stored event it lost against
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ConflictEntry {
+    #[serde(flatten)]
+    pub conflict: EditConflict,
+    /// The conflicting version as a whole event, for showing it like the stored one
+    #[serde(skip)]
+    pub theirs: Event,
+    /// Fields in which the conflicting version differs from the stored event
+    pub changes: Vec<EventField>,
+}
+
+/// Compare `conflicts` of `event` with it
+pub fn compare(event: &Event, conflicts: Vec<EditConflict>) -> Vec<ConflictEntry> {
+    conflicts.into_iter()
+        .map(|conflict| {
+            let theirs = conflict.apply_to(event);
+            let changes = event_history::changed_fields(event, &theirs);
+            ConflictEntry { conflict, theirs, changes }
+        })
+        .collect()
+}
+
+/// Version that resolves a conflict
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ConflictSide {
+    /// Keep the stored event and discard the conflict copy
+    Current,
+    /// Apply the conflict copy to the stored event
+    Conflict,
+}
+
+/// How a user resolves a conflict
+#[derive(Debug, Clone, Deserialize)]
+pub struct ResolveConflict {
+    pub keep: ConflictSide,
+}
@@ -14,9 +14,15 @@ pub enum AppError {
     #[error("Validation error: {0}")]
     ValidationError(String),
     
+    #[error("Validation error: {field}: {message}")]
+    FieldValidationError { field: String, message: String },
+    
     #[error("Not found: {0}")]
     NotFoundError(String),
     
+    #[error("Quota exceeded: {0}")]
+    QuotaExceeded(String),
+    
     #[error("Internal server error: {0}")]
     InternalServerError(String),
     
@@ -30,24 +36,74 @@ pub enum AppError {
     UuidError(#[from] uuid::Error),
 }
 
+impl AppError {
+    /// Validation error caused by one named request field
+    pub fn invalid_field(field: &str, message: impl Into<String>) -> Self {
+        AppError::FieldValidationError { field: field.to_string(), message: message.into() }
+    }
+
+    /// Stable, machine-readable error code returned in API error bodies
+    pub fn code(&self) -> &'static str {
+        match self {
+            AppError::DatabaseError(_)
+            | AppError::InternalServerError(_)
+            | AppError::PasswordHashError(_) => "internal_error",
+            AppError::AuthenticationError(_) => "unauthorized",
+            AppError::ValidationError(_) | AppError::FieldValidationError { .. } => "validation_failed",
+            AppError::NotFoundError(_) => "not_found",
+            AppError::QuotaExceeded(_) => "quota_exceeded",
+            AppError::JwtError(_) => "invalid_token",
+            AppError::UuidError(_) => "invalid_id",
+        }
+    }
+
+    pub fn status(&self) -> StatusCode {
+        match self {
+            AppError::DatabaseError(_)
+            | AppError::InternalServerError(_)
+            | AppError::PasswordHashError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            AppError::AuthenticationError(_) | AppError::JwtError(_) => StatusCode::UNAUTHORIZED,
+            AppError::ValidationError(_)
+            | AppError::FieldValidationError { .. }
+            | AppError::UuidError(_) => StatusCode::BAD_REQUEST,
+            AppError::NotFoundError(_) => StatusCode::NOT_FOUND,
+            AppError::QuotaExceeded(_) => StatusCode::INSUFFICIENT_STORAGE,
+        }
+    }
+
+    /// Message safe to show to API clients. Internal failures are logged and
+    /// replaced with a generic message so database details never leak.
+    pub(crate) fn public_message(&self) -> String {
+        match self {
+            AppError::DatabaseError(_)
+            | AppError::InternalServerError(_)
+            | AppError::PasswordHashError(_) => {
+                tracing::error!("Request failed: {}", self);
+                "Internal server error".to_string()
+            }
+            AppError::JwtError(_) => "Invalid or expired token".to_string(),
+            AppError::UuidError(_) => "Invalid UUID".to_string(),
+            AppError::AuthenticationError(message)
+            | AppError::ValidationError(message)
+            | AppError::FieldValidationError { message, .. }
+            | AppError::NotFoundError(message)
+            | AppError::QuotaExceeded(message) => message.clone(),
+        }
+    }
+}
+
 impl IntoResponse for AppError {
     fn into_response(self) -> Response {
-        let (status, message) = match self {
-            AppError::DatabaseError(_) => (StatusCode::INTERNAL_SERVER_ERROR, "Database error"),
-            AppError::AuthenticationError(_) => (StatusCode::UNAUTHORIZED, "Authentication error"),
-            AppError::ValidationError(_) => (StatusCode::BAD_REQUEST, "Validation error"),
-            AppError::NotFoundError(_) => (StatusCode::NOT_FOUND, "Not found"),
-            AppError::InternalServerError(_) => (StatusCode::INTERNAL_SERVER_ERROR, "Internal server error"),
-            AppError::PasswordHashError(_) => (StatusCode::INTERNAL_SERVER_ERROR, "Password hashing error"),
-            AppError::JwtError(_) => (StatusCode::UNAUTHORIZED, "JWT error"),
-            AppError::UuidError(_) => (StatusCode::BAD_REQUEST, "Invalid UUID"),
-        };
-        
-        let body = json!({
-            "error": message,
+        let status = self.status();
+        let mut body = json!({
+            "code": self.code(),
+            "message": self.public_message(),
             "status": status.as_u16(),
         });
+        if let AppError::FieldValidationError { field, .. } = &self {
+            body["field"] = json!(field);
+        }
         
         (status, axum::Json(body)).into_response()
     }
-}
\ No newline at end of file
+}
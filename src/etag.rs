@@ -0,0 +1,96 @@
+//! Conditional GETs of iCalendar data
+//!
+//! Calendar object resources carry ETags that change with every edit of the object.
+//! Whole calendars carry their ctag, which changes with every edit in them, so the ETag
+//! is known before the streamed body is written. Other feeds get an ETag computed from
+//! their content. A client that
+//! sends one of these back in `If-None-Match` gets `304 Not Modified` while nothing
+//! changed, instead of the full download. Serialized events are kept in an
+//! [`IcsCache`] keyed on the event's version, so polling a large calendar doesn't render
+//! unchanged events again.
+
+use std::collections::HashMap;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::sync::{Arc, Mutex};
+
+use axum::body::Body;
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::response::Response;
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+
+use crate::models::Calendar;
+
+/// Most serialized events kept; the cache is emptied when it is full
+const CACHE_CAPACITY: usize = 16_384;
+
+/// Strong ETag of generated content. The hash may change with the Rust version the
+/// server is built with, which only costs clients one full download.
+pub fn content_etag(content: &str) -> String {
+    let mut hasher = DefaultHasher::new();
+    content.hash(&mut hasher);
+    format!("\"{:016x}\"", hasher.finish())
+}
+
+/// Whether the request's `If-None-Match` names `etag`, so the client already has it.
+/// Weak and strong ETags compare alike, as RFC 9110 asks for GET.
+pub fn not_modified(headers: &HeaderMap, etag: &str) -> bool {
+    headers.get_all(header::IF_NONE_MATCH)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(|tag| tag.trim())
+        .any(|tag| tag == "*" || tag.trim_start_matches("W/") == etag)
+}
+
+/// `304 Not Modified` for a representation the client already has
+pub fn not_modified_response(etag: &str) -> Response {
+    Response::builder()
+        .status(StatusCode::NOT_MODIFIED)
+        .header(header::ETAG, etag)
+        .body(Body::empty())
+        .unwrap()
+}
+
+/// Strong ETag of a whole calendar's iCalendar file
+pub fn calendar_etag(calendar: &Calendar) -> String {
+    format!("\"{}-{}\"", calendar.id, calendar.ctag)
+}
+
+/// iCalendar response with an ETag of its content, or 304 if the client has it already
+pub fn ics_response(headers: &HeaderMap, ical_content: String) -> Response {
+    let etag = content_etag(&ical_content);
+    if not_modified(headers, &etag) {
+        return not_modified_response(&etag);
+    }
+    Response::builder()
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, "text/calendar; charset=utf-8")
+        .header(header::ETAG, etag)
+        .body(Body::from(ical_content))
+        .unwrap()
+}
+
+/// Event and version a VEVENT was rendered for
+type CacheKey = (Uuid, DateTime<Utc>);
+
+/// Serialized VEVENTs of events, keyed on the event and its `updated_at`; an edited
+/// event is rendered afresh
+#[derive(Debug, Clone, Default)]
+pub struct IcsCache {
+    entries: Arc<Mutex<HashMap<CacheKey, Arc<str>>>>,
+}
+
+impl IcsCache {
+    pub fn get(&self, event_id: Uuid, updated_at: DateTime<Utc>) -> Option<Arc<str>> {
+        self.entries.lock().unwrap_or_else(|e| e.into_inner()).get(&(event_id, updated_at)).cloned()
+    }
+
+    pub fn insert(&self, event_id: Uuid, updated_at: DateTime<Utc>, ics: Arc<str>) {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        if entries.len() >= CACHE_CAPACITY {
+            entries.clear();
+        }
+        entries.insert((event_id, updated_at), ics);
+    }
+}
@@ -0,0 +1,257 @@
+//! CSV export and import of events, for spreadsheets and reporting tools
+//!
+//! Exports have one row per stored event with the columns in [`COLUMNS`]: times in
+//! RFC 3339 UTC, and all-day events as dates from their first to their last day.
+//! Imports map the columns of any CSV file with a header row onto event fields. The
+//! preview step suggests a [`CsvMapping`] from the header names, which the client can
+//! correct before importing. Times without an offset are read on the clock of the
+//! chosen time zone.
+
+use chrono::{DateTime, Duration, NaiveDate, NaiveDateTime, TimeZone, Utc};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+
+use crate::error::AppError;
+use crate::models::{Event, NewEvent};
+use crate::validation::Validate;
+
+/// Media type of CSV exports
+pub const CONTENT_TYPE: &str = "text/csv; charset=utf-8";
+
+/// Header row of exports; an export imports again without a mapping
+pub const COLUMNS: [&str; 8] = ["id", "title", "start_time", "end_time", "all_day", "location", "description", "rrule"];
+
+/// Data rows shown in an import preview
+pub const PREVIEW_ROWS: usize = 5;
+
+/// Write events as CSV with the [`COLUMNS`] header
+pub fn write_events(events: &[Event]) -> Result<Vec<u8>, AppError> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    writer.write_record(COLUMNS).map_err(write_error)?;
+    for event in events {
+        let (start, end) = if event.is_all_day {
+            let last = event.end_time - Duration::days(1);
+            (event.start_time.format("%Y-%m-%d").to_string(), last.max(event.start_time).format("%Y-%m-%d").to_string())
+        } else {
+            (event.start_time.to_rfc3339(), event.end_time.to_rfc3339())
+        };
+        writer.write_record([
+            event.id.to_string(),
+            event.title.clone(),
+            start,
+            end,
+            event.is_all_day.to_string(),
+            event.location.clone().unwrap_or_default(),
+            event.description.clone().unwrap_or_default(),
+            event.rrule.clone().unwrap_or_default(),
+        ]).map_err(write_error)?;
+    }
+    writer.into_inner().map_err(|e| AppError::InternalServerError(format!("Failed to write CSV: {}", e)))
+}
+
+fn write_error(e: csv::Error) -> AppError {
+    AppError::InternalServerError(format!("Failed to write CSV: {}", e))
+}
+
+/// Which column, by header name, holds each event field. Fields left out aren't imported;
+/// `title` and `start_time` are required.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct CsvMapping {
+    pub title: Option<String>,
+    pub start_time: Option<String>,
+    /// Without it, events last an hour, or one day if all-day
+    pub end_time: Option<String>,
+    /// Without it, events whose times are dates without a time of day are all-day
+    pub all_day: Option<String>,
+    pub location: Option<String>,
+    pub description: Option<String>,
+    pub rrule: Option<String>,
+}
+
+impl CsvMapping {
+    /// Mapping suggested by the header names, e.g. `Subject` for the title or
+    /// `Start Date` for the start
+    pub fn guess(columns: &[String]) -> Self {
+        let find = |names: &[&str]| {
+            columns.iter().find(|column| {
+                let column = column.trim().to_lowercase().replace(['_', '-'], " ");
+                names.contains(&column.as_str())
+            }).cloned()
+        };
+
+        Self {
+            title: find(&["title", "subject", "summary", "name", "event"]),
+            start_time: find(&["start time", "start", "start date", "starts", "begin", "dtstart"]),
+            end_time: find(&["end time", "end", "end date", "ends", "dtend"]),
+            all_day: find(&["all day", "all day event", "allday"]),
+            location: find(&["location", "place", "where"]),
+            description: find(&["description", "notes", "details", "body"]),
+            rrule: find(&["rrule", "recurrence", "recurrence rule"]),
+        }
+    }
+
+    /// This mapping with the fields it leaves out taken from `other`
+    pub fn or(self, other: Self) -> Self {
+        Self {
+            title: self.title.or(other.title),
+            start_time: self.start_time.or(other.start_time),
+            end_time: self.end_time.or(other.end_time),
+            all_day: self.all_day.or(other.all_day),
+            location: self.location.or(other.location),
+            description: self.description.or(other.description),
+            rrule: self.rrule.or(other.rrule),
+        }
+    }
+}
+
+/// Header and first rows of a CSV file, with the mapping suggested for it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CsvPreview {
+    pub columns: Vec<String>,
+    pub mapping: CsvMapping,
+    pub rows: Vec<Vec<String>>,
+}
+
+/// The header and first [`PREVIEW_ROWS`] rows of `data`, with the mapping suggested for it
+pub fn preview(data: &[u8]) -> Result<CsvPreview, AppError> {
+    let mut reader = reader(data);
+    let columns = headers(&mut reader)?;
+    let rows = reader.records()
+        .take(PREVIEW_ROWS)
+        .map(|record| record.map(|record| record.iter().map(str::to_string).collect()).map_err(read_error))
+        .collect::<Result<_, _>>()?;
+    Ok(CsvPreview { mapping: CsvMapping::guess(&columns), columns, rows })
+}
+
+/// Event fields resolved to column positions
+#[derive(Debug)]
+pub struct RowReader {
+    title: usize,
+    start_time: usize,
+    end_time: Option<usize>,
+    all_day: Option<usize>,
+    location: Option<usize>,
+    description: Option<usize>,
+    rrule: Option<usize>,
+    timezone: Tz,
+}
+
+impl RowReader {
+    /// Resolve `mapping` against the header row, failing on columns the file doesn't have
+    pub fn new(columns: &[String], mapping: &CsvMapping, timezone: Tz) -> Result<Self, AppError> {
+        let position = |field: &str, column: &Option<String>| -> Result<Option<usize>, AppError> {
+            column.as_ref().map(|column| {
+                columns.iter().position(|c| c == column).ok_or_else(|| {
+                    AppError::invalid_field(field, format!("The file has no column named {}", column))
+                })
+            }).transpose()
+        };
+        let required = |field: &str, label: &str, column: &Option<String>| {
+            position(field, column)?
+                .ok_or_else(|| AppError::invalid_field(field, format!("Choose the column holding the {}", label)))
+        };
+
+        Ok(Self {
+            title: required("title", "title", &mapping.title)?,
+            start_time: required("start_time", "start", &mapping.start_time)?,
+            end_time: position("end_time", &mapping.end_time)?,
+            all_day: position("all_day", &mapping.all_day)?,
+            location: position("location", &mapping.location)?,
+            description: position("description", &mapping.description)?,
+            rrule: position("rrule", &mapping.rrule)?,
+            timezone,
+        })
+    }
+
+    /// The event in a data row, checked like one created through the API
+    pub fn event(&self, record: &csv::StringRecord) -> Result<NewEvent, AppError> {
+        let text = |index: Option<usize>| {
+            index.and_then(|index| record.get(index)).map(str::trim).filter(|value| !value.is_empty()).map(str::to_string)
+        };
+
+        let start = parse_time(&text(Some(self.start_time)).unwrap_or_default(), self.timezone)
+            .ok_or_else(|| AppError::invalid_field("start_time", "Start must look like 2030-01-07 09:00 or 2030-01-07"))?;
+        let end = text(self.end_time)
+            .map(|value| parse_time(&value, self.timezone)
+                .ok_or_else(|| AppError::invalid_field("end_time", "End must look like 2030-01-07 10:00 or 2030-01-07")))
+            .transpose()?;
+        let is_all_day = match text(self.all_day) {
+            Some(value) => parse_flag(&value)
+                .ok_or_else(|| AppError::invalid_field("all_day", "All day must be true or false"))?,
+            None => start.is_date && end.is_none_or(|end| end.is_date),
+        };
+
+        let start_time = start.time;
+        let end_time = match end {
+            // The last day of an all-day event is part of it
+            Some(end) if is_all_day => end.time + Duration::days(1),
+            Some(end) => end.time,
+            None if is_all_day => start_time + Duration::days(1),
+            None => start_time + Duration::hours(1),
+        };
+
+        let event = NewEvent {
+            title: text(Some(self.title)).unwrap_or_default(),
+            description: text(self.description),
+            location: text(self.location),
+            start_time,
+            end_time,
+            is_all_day,
+            rrule: text(self.rrule),
+            geo_lat: None,
+            geo_lon: None,
+            alarm_minutes: None,
+        };
+        event.validate()?;
+        Ok(event)
+    }
+}
+
+/// Reader of a CSV file with a header row; rows may have fewer or more fields than it
+pub fn reader(data: &[u8]) -> csv::Reader<&[u8]> {
+    csv::ReaderBuilder::new().flexible(true).trim(csv::Trim::Headers).from_reader(data)
+}
+
+/// Names of the columns, from the header row
+pub fn headers(reader: &mut csv::Reader<&[u8]>) -> Result<Vec<String>, AppError> {
+    let columns: Vec<String> = reader.headers().map_err(read_error)?.iter().map(str::to_string).collect();
+    if columns.iter().all(String::is_empty) {
+        return Err(AppError::ValidationError("Expected a CSV file with a header row".to_string()));
+    }
+    Ok(columns)
+}
+
+pub fn read_error(e: csv::Error) -> AppError {
+    AppError::ValidationError(format!("Invalid CSV: {}", e))
+}
+
+/// A time read from a cell, and whether it was a date without a time of day
+#[derive(Debug, Clone, Copy)]
+struct CellTime {
+    time: DateTime<Utc>,
+    is_date: bool,
+}
+
+/// RFC 3339, `YYYY-MM-DD HH:MM[:SS]` or `YYYY-MM-DDTHH:MM[:SS]` on the clock of
+/// `timezone`, or a date `YYYY-MM-DD` taken as midnight UTC like stored all-day events
+fn parse_time(value: &str, timezone: Tz) -> Option<CellTime> {
+    if let Ok(time) = DateTime::parse_from_rfc3339(value) {
+        return Some(CellTime { time: time.with_timezone(&Utc), is_date: false });
+    }
+    for format in ["%Y-%m-%d %H:%M:%S", "%Y-%m-%d %H:%M", "%Y-%m-%dT%H:%M:%S", "%Y-%m-%dT%H:%M"] {
+        if let Ok(local) = NaiveDateTime::parse_from_str(value, format) {
+            let time = timezone.from_local_datetime(&local).earliest()?;
+            return Some(CellTime { time: time.with_timezone(&Utc), is_date: false });
+        }
+    }
+    let date = NaiveDate::parse_from_str(value, "%Y-%m-%d").ok()?;
+    Some(CellTime { time: date.and_hms_opt(0, 0, 0)?.and_utc(), is_date: true })
+}
+
+fn parse_flag(value: &str) -> Option<bool> {
+    match value.to_ascii_lowercase().as_str() {
+        "true" | "yes" | "y" | "1" | "x" => Some(true),
+        "false" | "no" | "n" | "0" => Some(false),
+        _ => None,
+    }
+}
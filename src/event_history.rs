@@ -0,0 +1,151 @@
+//! Earlier versions of events, so edits and deletions can be undone
+//!
+//! Before an event is changed or deleted, the state it is leaving is stored as a
+//! version. A buggy client that overwrites or deletes events can then be undone from
+//! the web UI or the API by restoring an earlier version; restoring is itself recorded,
+//! so it can be undone too. Deleted events stay restorable for as long as their last
+//! version is kept. Only the newest [`RetentionPolicy::event_versions`] versions of
+//! each event are kept, and versions older than
+//! [`RetentionPolicy::event_version_days`] are pruned.
+//!
+//! [`RetentionPolicy::event_versions`]: crate::maintenance::RetentionPolicy::event_versions
+//! [`RetentionPolicy::event_version_days`]: crate::maintenance::RetentionPolicy::event_version_days
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, Row};
+
+use crate::models::Event;
+
+/// Versions kept per event unless configured otherwise
+pub const DEFAULT_EVENT_VERSIONS: usize = 20;
+
+/// What happened to an event after a version of it was stored
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum VersionAction {
+    Updated,
+    Deleted,
+}
+
+impl VersionAction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            VersionAction::Updated => "updated",
+            VersionAction::Deleted => "deleted",
+        }
+    }
+}
+
+/// State of an event before it was changed or deleted
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EventVersion {
+    pub id: i64,
+    pub action: VersionAction,
+    pub recorded_at: DateTime<Utc>,
+    pub event: Event,
+}
+
+impl FromRow<'_, sqlx::sqlite::SqliteRow> for EventVersion {
+    fn from_row(row: &'_ sqlx::sqlite::SqliteRow) -> Result<Self, sqlx::Error> {
+        let action: String = row.try_get("action")?;
+        let action = match action.as_str() {
+            "deleted" => VersionAction::Deleted,
+            _ => VersionAction::Updated,
+        };
+
+        Ok(EventVersion {
+            id: row.try_get("version_id")?,
+            action,
+            recorded_at: row.try_get("recorded_at")?,
+            event: Event::from_row(row)?,
+        })
+    }
+}
+
+/// Part of an event a change can touch
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EventField {
+    Title,
+    Description,
+    Location,
+    Geo,
+    StartTime,
+    EndTime,
+    IsAllDay,
+    Rrule,
+    Exdates,
+    AlarmMinutes,
+}
+
+impl EventField {
+    pub fn name(&self) -> &'static str {
+        match self {
+            EventField::Title => "title",
+            EventField::Description => "description",
+            EventField::Location => "location",
+            EventField::Geo => "geo",
+            EventField::StartTime => "start_time",
+            EventField::EndTime => "end_time",
+            EventField::IsAllDay => "is_all_day",
+            EventField::Rrule => "rrule",
+            EventField::Exdates => "exdates",
+            EventField::AlarmMinutes => "alarm_minutes",
+        }
+    }
+}
+
+/// Fields that differ between two states of an event; bookkeeping such as
+/// `updated_at` doesn't count
+pub fn changed_fields(before: &Event, after: &Event) -> Vec<EventField> {
+    let fields = [
+        (EventField::Title, before.title != after.title),
+        (EventField::Description, before.description != after.description),
+        (EventField::Location, before.location != after.location),
+        (EventField::Geo, (before.geo_lat, before.geo_lon) != (after.geo_lat, after.geo_lon)),
+        (EventField::StartTime, before.start_time != after.start_time),
+        (EventField::EndTime, before.end_time != after.end_time),
+        (EventField::IsAllDay, before.is_all_day != after.is_all_day),
+        (EventField::Rrule, before.rrule != after.rrule),
+        (EventField::Exdates, before.exdates != after.exdates),
+        (EventField::AlarmMinutes, before.alarm_minutes != after.alarm_minutes),
+    ];
+    fields.into_iter().filter(|(_, changed)| *changed).map(|(field, _)| field).collect()
+}
+
+/// Version in an event's history, with what the change after it touched
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct HistoryEntry {
+    #[serde(flatten)]
+    pub version: EventVersion,
+    /// State the change left the event in; `None` for deletions
+    #[serde(skip)]
+    pub after: Option<Event>,
+    /// Fields the change touched; empty for deletions
+    pub changes: Vec<EventField>,
+}
+
+/// History of an event from its `versions`, newest first, and its `current` state
+/// unless it is deleted
+pub fn timeline(versions: Vec<EventVersion>, current: Option<&Event>) -> Vec<HistoryEntry> {
+    let mut after = current.cloned();
+    let mut entries = Vec::with_capacity(versions.len());
+
+    for version in versions {
+        let next = Some(version.event.clone());
+        let after = match version.action {
+            VersionAction::Updated => std::mem::replace(&mut after, next),
+            VersionAction::Deleted => {
+                after = next;
+                None
+            }
+        };
+        let changes = after.as_ref()
+            .map(|after| changed_fields(&version.event, after))
+            .unwrap_or_default();
+        entries.push(HistoryEntry { version, after, changes });
+    }
+
+    entries
+}
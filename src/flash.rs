@@ -0,0 +1,78 @@
+//! One-shot messages shown on the page a form submission redirects to
+//!
+//! Handlers put the message in a `flash` cookie signed with the app secret, so what a
+//! page shows as a flash always comes from the server, never from a link someone was
+//! sent. [`crate::middleware::flash_middleware`] verifies the cookie and offers the
+//! message to the next page rendered; the base layout takes it, and the cookie is cleared
+//! once a page has shown it.
+
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+
+use serde::{Deserialize, Serialize};
+
+/// Name of the cookie holding the signed flash
+pub const FLASH_COOKIE: &str = "flash";
+
+/// Seconds a flash waits for the page it is meant for
+pub const FLASH_COOKIE_SECONDS: u32 = 60;
+
+/// Look of a flash message
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FlashKind {
+    Success,
+    Warning,
+    Error,
+}
+
+impl FlashKind {
+    /// Suffix of the `flash-*` CSS class
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FlashKind::Success => "success",
+            FlashKind::Warning => "warning",
+            FlashKind::Error => "error",
+        }
+    }
+}
+
+/// Message for the next page
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Flash {
+    pub kind: FlashKind,
+    pub message: String,
+}
+
+impl Flash {
+    pub fn success(message: impl Into<String>) -> Self {
+        Flash { kind: FlashKind::Success, message: message.into() }
+    }
+
+    pub fn warning(message: impl Into<String>) -> Self {
+        Flash { kind: FlashKind::Warning, message: message.into() }
+    }
+
+    pub fn error(message: impl Into<String>) -> Self {
+        Flash { kind: FlashKind::Error, message: message.into() }
+    }
+}
+
+tokio::task_local! {
+    static FLASH: Arc<Mutex<Option<Flash>>>;
+}
+
+/// Run `future` with `flash` waiting to be shown, returning its output and whether a
+/// page took the flash
+pub async fn with_flash<F: Future>(flash: Option<Flash>, future: F) -> (F::Output, bool) {
+    let offered = flash.is_some();
+    let slot = Arc::new(Mutex::new(flash));
+    let output = FLASH.scope(slot.clone(), future).await;
+    let taken = offered && slot.lock().map(|flash| flash.is_none()).unwrap_or(false);
+    (output, taken)
+}
+
+/// The flash waiting for the current page, if any; later calls get `None`
+pub fn take_flash() -> Option<Flash> {
+    FLASH.try_with(|slot| slot.lock().ok().and_then(|mut flash| flash.take())).ok().flatten()
+}
@@ -0,0 +1,106 @@
+//! Free-busy information of a calendar (RFC 4791 section 7.10): when its events keep the
+//! owner busy and, for owners who choose so, when they are outside their working hours
+
+use chrono::{Datelike, DateTime, Days, NaiveDate, NaiveTime, TimeZone, Utc};
+use chrono_tz::Tz;
+
+use crate::models::Event;
+use crate::settings::WorkingHours;
+
+/// A span of time, start inclusive and end exclusive
+pub type Period = (DateTime<Utc>, DateTime<Utc>);
+
+/// Busy and unavailable time of a calendar within a window
+#[derive(Debug, Clone, PartialEq)]
+pub struct FreeBusy {
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    pub busy: Vec<Period>,
+    /// Time outside the owner's working hours
+    pub unavailable: Vec<Period>,
+}
+
+impl FreeBusy {
+    /// Free-busy of event instances within `[start, end)`. All-day events don't block time.
+    pub fn new(start: DateTime<Utc>, end: DateTime<Utc>, instances: &[Event]) -> Self {
+        let busy = instances
+            .iter()
+            .filter(|event| !event.is_all_day)
+            .filter_map(|event| clip((event.start_time, event.end_time), start, end))
+            .collect();
+
+        Self { start, end, busy: merge(busy), unavailable: Vec::new() }
+    }
+
+    /// Mark the time outside `hours`, on the clock of `timezone`, as unavailable
+    pub fn with_working_hours(mut self, hours: &WorkingHours, timezone: Tz) -> Self {
+        let first = self.start.with_timezone(&timezone).date_naive();
+        let last = self.end.with_timezone(&timezone).date_naive();
+
+        let mut unavailable = Vec::new();
+        for day in first.iter_days().take_while(|day| *day <= last) {
+            let next = day + Days::new(1);
+            let periods = if hours.is_working_day(day.weekday()) {
+                vec![
+                    (local(timezone, day, NaiveTime::MIN), local(timezone, day, hours.start)),
+                    (local(timezone, day, hours.end), local(timezone, next, NaiveTime::MIN)),
+                ]
+            } else {
+                vec![(local(timezone, day, NaiveTime::MIN), local(timezone, next, NaiveTime::MIN))]
+            };
+            unavailable.extend(periods.into_iter()
+                .filter_map(|(from, until)| clip((from?, until?), self.start, self.end)));
+        }
+
+        self.unavailable = merge(unavailable);
+        self
+    }
+
+    /// A VCALENDAR holding this as a VFREEBUSY component
+    pub fn to_ical_string(&self, stamp: DateTime<Utc>) -> String {
+        let format = |time: DateTime<Utc>| time.format("%Y%m%dT%H%M%SZ").to_string();
+        let mut ical = format!(
+            "BEGIN:VCALENDAR\r\n\
+             VERSION:2.0\r\n\
+             PRODID:-//My CalDAV Server//EN\r\n\
+             BEGIN:VFREEBUSY\r\n\
+             DTSTAMP:{}\r\n\
+             DTSTART:{}\r\n\
+             DTEND:{}\r\n",
+            format(stamp),
+            format(self.start),
+            format(self.end),
+        );
+        for (kind, periods) in [("BUSY", &self.busy), ("BUSY-UNAVAILABLE", &self.unavailable)] {
+            for (start, end) in periods {
+                ical.push_str(&format!("FREEBUSY;FBTYPE={}:{}/{}\r\n", kind, format(*start), format(*end)));
+            }
+        }
+        ical.push_str("END:VFREEBUSY\r\nEND:VCALENDAR\r\n");
+        ical
+    }
+}
+
+/// `time` on `day` on the clock of `timezone`; `None` when a clock change skips it
+fn local(timezone: Tz, day: NaiveDate, time: NaiveTime) -> Option<DateTime<Utc>> {
+    timezone.from_local_datetime(&day.and_time(time)).earliest().map(|t| t.with_timezone(&Utc))
+}
+
+/// The part of `period` within `[start, end)`, if any
+fn clip(period: Period, start: DateTime<Utc>, end: DateTime<Utc>) -> Option<Period> {
+    let clipped = (period.0.max(start), period.1.min(end));
+    (clipped.0 < clipped.1).then_some(clipped)
+}
+
+/// Periods sorted by start, with those that overlap or touch joined
+fn merge(mut periods: Vec<Period>) -> Vec<Period> {
+    periods.sort();
+    let mut merged: Vec<Period> = Vec::with_capacity(periods.len());
+    for period in periods {
+        match merged.last_mut() {
+            Some(last) if period.0 <= last.1 => last.1 = last.1.max(period.1),
+            _ => merged.push(period),
+        }
+    }
+    merged
+}
@@ -0,0 +1,70 @@
+//! Geo-distance helpers for location-based event search
+
+/// Mean Earth radius in kilometers
+const EARTH_RADIUS_KM: f64 = 6371.0;
+
+/// Great-circle distance between two WGS84 coordinates in kilometers
+pub fn distance_km(lat1: f64, lon1: f64, lat2: f64, lon2: f64) -> f64 {
+    let d_lat = (lat2 - lat1).to_radians();
+    let d_lon = (lon2 - lon1).to_radians();
+    let a = (d_lat / 2.0).sin().powi(2)
+        + lat1.to_radians().cos() * lat2.to_radians().cos() * (d_lon / 2.0).sin().powi(2);
+
+    2.0 * EARTH_RADIUS_KM * a.sqrt().asin()
+}
+
+/// Latitude/longitude bounds enclosing a circle, used to pre-filter candidates in SQL
+/// (SQLite has no trigonometric functions) before the exact distance check.
+///
+/// When the box crosses the antimeridian, `min_lon` is greater than `max_lon` and the
+/// box covers the longitudes from `min_lon` up to 180 and from -180 up to `max_lon`.
+#[derive(Debug, Clone, Copy)]
+pub struct BoundingBox {
+    pub min_lat: f64,
+    pub max_lat: f64,
+    pub min_lon: f64,
+    pub max_lon: f64,
+}
+
+impl BoundingBox {
+    pub fn around(lat: f64, lon: f64, radius_km: f64) -> Self {
+        let lat = lat.clamp(-90.0, 90.0);
+        let angle = radius_km / EARTH_RADIUS_KM;
+        let min_lat = lat - angle.to_degrees();
+        let max_lat = lat + angle.to_degrees();
+
+        // A circle reaching over a pole covers every longitude
+        if min_lat <= -90.0 || max_lat >= 90.0 {
+            return Self {
+                min_lat: min_lat.max(-90.0),
+                max_lat: max_lat.min(90.0),
+                min_lon: -180.0,
+                max_lon: 180.0,
+            };
+        }
+
+        // Widest longitude span of the circle, which lies poleward of its centre
+        let d_lon = (angle.sin() / lat.to_radians().cos()).asin().to_degrees();
+        Self {
+            min_lat,
+            max_lat,
+            min_lon: wrap_longitude(lon - d_lon),
+            max_lon: wrap_longitude(lon + d_lon),
+        }
+    }
+
+    /// Longitude ranges covered by the box: the same range twice, or the two halves
+    /// on either side of the antimeridian
+    pub fn lon_ranges(&self) -> [(f64, f64); 2] {
+        if self.min_lon <= self.max_lon {
+            [(self.min_lon, self.max_lon); 2]
+        } else {
+            [(self.min_lon, 180.0), (-180.0, self.max_lon)]
+        }
+    }
+}
+
+/// Bring a longitude into `[-180, 180)`
+fn wrap_longitude(lon: f64) -> f64 {
+    (lon + 180.0).rem_euclid(360.0) - 180.0
+}
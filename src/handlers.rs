@@ -1,6 +1,6 @@
 use axum::{
-    extract::{Path, State, Extension, Query},
-    http::{header, StatusCode, Uri},
+    extract::{Multipart, Path, State, Extension, Query},
+    http::{header, HeaderMap, StatusCode},
     response::{Html, IntoResponse, Response},
     body::Body,
     Json,
@@ -9,51 +9,48 @@ use uuid::Uuid;
 use crate::models::*;
 use crate::services::CalendarService;
 use crate::error::AppError;
-use crate::middleware::BasicAuthCredentials;
-use bcrypt::verify;
+use crate::middleware::{BasicAuthCredentials, OptionalUser};
+use crate::authz::{self, Access};
+use crate::dav::get_user_id;
+use crate::etag;
+use crate::streaming;
+use crate::event_csv;
+use crate::edit_conflicts::{self, ConflictEntry, ResolveConflict};
+use crate::event_history::{self, EventVersion, HistoryEntry};
+use crate::ical_check;
+use crate::import;
+use crate::outlook;
+use crate::validation::Validate;
+use crate::settings::{UpdateUserSettings, UserSettings};
 use serde::Deserialize;
+use tokio_stream::StreamExt;
 
 pub mod auth;
 pub mod web;
 
-/// Query parameters for event search
-#[derive(Debug, Deserialize)]
-pub struct SearchQuery {
-    pub q: String,
+/// Query parameters of event creates and updates
+#[derive(Debug, Default, Deserialize)]
+pub struct ConflictCheckQuery {
+    /// Report the events the stored event overlaps in `conflicts`
+    #[serde(default)]
+    pub check_conflicts: bool,
 }
 
-/// Helper function to authenticate Basic Auth credentials and get user_id
-async fn authenticate_basic_auth(
-    service: &CalendarService,
-    credentials: &BasicAuthCredentials,
-) -> Result<Uuid, AppError> {
-    let user = service.get_user_by_email(&credentials.email).await?
-        .ok_or(AppError::AuthenticationError("Invalid credentials".to_string()))?;
-    
-    if !verify(&credentials.password, &user.password_hash)? {
-        return Err(AppError::AuthenticationError("Invalid credentials".to_string()));
+impl ConflictCheckQuery {
+    /// `event` with its conflicts, if they were asked for
+    pub async fn check(&self, service: &CalendarService, event: Event) -> Result<CheckedEvent, AppError> {
+        let conflicts = match self.check_conflicts {
+            true => Some(service.find_event_conflicts(&event).await?),
+            false => None,
+        };
+        Ok(CheckedEvent { event, conflicts })
     }
-    
-    Ok(user.id)
 }
 
-/// Helper to get user_id from either Extension (JWT auth) or Basic Auth
-async fn get_user_id(
-    service: &CalendarService,
-    user_id_ext: Option<Uuid>,
-    basic_auth: Option<BasicAuthCredentials>,
-) -> Result<Uuid, AppError> {
-    // First try JWT auth (user_id from extension)
-    if let Some(user_id) = user_id_ext {
-        return Ok(user_id);
-    }
-    
-    // Then try Basic Auth
-    if let Some(credentials) = basic_auth {
-        return authenticate_basic_auth(service, &credentials).await;
-    }
-    
-    Err(AppError::AuthenticationError("Authentication required".to_string()))
+/// Query parameters for event search
+#[derive(Debug, Deserialize)]
+pub struct SearchQuery {
+    pub q: String,
 }
 
 // Root endpoint
@@ -78,7 +75,7 @@ pub async fn root() -> impl IntoResponse {
         <li><code>POST /api/auth/calendars</code> - Create calendar</li>
         <li><code>GET /api/auth/events/:id</code> - Get event by ID</li>
         <li><code>POST /api/auth/events</code> - Create event</li>
-        <li><code>GET /.well-known/caldav</code> - CalDAV discovery</li>
+        <li><code>GET /.well-known/caldav</code> - CalDAV discovery (redirects to <code>/calendars/</code>)</li>
     </ul>
 </body>
 </html>"#;
@@ -97,20 +94,22 @@ pub async fn health() -> impl IntoResponse {
 // User endpoints
 pub async fn get_user_by_id(
     State(service): State<CalendarService>,
+    Extension(_user_id): Extension<Uuid>,
     Path(user_id): Path<Uuid>,
-) -> Result<Json<User>, AppError> {
+) -> Result<Json<PublicUser>, AppError> {
     let user = service.get_user_by_id(user_id).await?
         .ok_or(AppError::NotFoundError("User not found".to_string()))?;
-    Ok(Json(user))
+    Ok(Json(PublicUser::from(user)))
 }
 
 // Calendar endpoints
+/// Get a calendar by id: public calendars for everyone, others for users with access
 pub async fn get_calendar_by_id(
     State(service): State<CalendarService>,
+    Extension(user): Extension<OptionalUser>,
     Path(calendar_id): Path<Uuid>,
 ) -> Result<Json<Calendar>, AppError> {
-    let calendar = service.get_calendar_by_id(calendar_id).await?
-        .ok_or(AppError::NotFoundError("Calendar not found".to_string()))?;
+    let calendar = authz::authorize_calendar_read(&service, user.0, calendar_id).await?;
     Ok(Json(calendar))
 }
 
@@ -120,13 +119,8 @@ pub async fn update_calendar(
     Path(calendar_id): Path<Uuid>,
     Json(updates): Json<UpdateCalendar>,
 ) -> Result<Json<Calendar>, AppError> {
-    // Check ownership
-    let calendar = service.get_calendar_by_id(calendar_id).await?
-        .ok_or(AppError::NotFoundError("Calendar not found".to_string()))?;
-    
-    if calendar.user_id != user_id {
-        return Err(AppError::AuthenticationError("You don't own this calendar".to_string()));
-    }
+    authz::authorize_calendar(&service, user_id, calendar_id, Access::Admin).await?;
+    updates.validate()?;
     
     let updated = service.update_calendar(calendar_id, updates).await?;
     Ok(Json(updated))
@@ -137,25 +131,53 @@ pub async fn delete_calendar(
     Extension(user_id): Extension<Uuid>,
     Path(calendar_id): Path<Uuid>,
 ) -> Result<StatusCode, AppError> {
-    // Check ownership
-    let calendar = service.get_calendar_by_id(calendar_id).await?
-        .ok_or(AppError::NotFoundError("Calendar not found".to_string()))?;
-    
-    if calendar.user_id != user_id {
-        return Err(AppError::AuthenticationError("You don't own this calendar".to_string()));
-    }
+    authz::authorize_calendar(&service, user_id, calendar_id, Access::Admin).await?;
     
     service.delete_calendar(calendar_id).await?;
     Ok(StatusCode::NO_CONTENT)
 }
 
+/// Make clients re-download the whole calendar on their next sync
+pub async fn resync_calendar(
+    State(service): State<CalendarService>,
+    Extension(user_id): Extension<Uuid>,
+    Path(calendar_id): Path<Uuid>,
+) -> Result<Json<Calendar>, AppError> {
+    authz::authorize_calendar(&service, user_id, calendar_id, Access::Admin).await?;
+
+    let calendar = service.resync_calendar(calendar_id).await?;
+    Ok(Json(calendar))
+}
+
+/// Hand a calendar over to another user. Only the owner can do this, not users it is
+/// shared with.
+pub async fn transfer_calendar(
+    State(service): State<CalendarService>,
+    Extension(user_id): Extension<Uuid>,
+    Path(calendar_id): Path<Uuid>,
+    Json(transfer): Json<TransferCalendar>,
+) -> Result<Json<Calendar>, AppError> {
+    let calendar = authz::authorize_calendar(&service, user_id, calendar_id, Access::Admin).await?;
+    if calendar.user_id != user_id {
+        return Err(AppError::AuthenticationError("Only the owner can transfer a calendar".to_string()));
+    }
+
+    let new_owner = service.transfer_calendar(calendar_id, transfer).await?;
+    service.record_audit(user_id, None, "calendar_transferred", Some(&format!("{} to {}", calendar_id, new_owner.email))).await?;
+
+    let calendar = service.get_calendar_by_id(calendar_id).await?
+        .ok_or(AppError::NotFoundError("Calendar not found".to_string()))?;
+    Ok(Json(calendar))
+}
+
 // Event endpoints
+/// Get an event by id, with the same access rules as its calendar
 pub async fn get_event_by_id(
     State(service): State<CalendarService>,
+    Extension(user): Extension<OptionalUser>,
     Path(event_id): Path<Uuid>,
 ) -> Result<Json<Event>, AppError> {
-    let event = service.get_event_by_id(event_id).await?
-        .ok_or(AppError::NotFoundError("Event not found".to_string()))?;
+    let event = authz::authorize_event_read(&service, user.0, event_id).await?;
     Ok(Json(event))
 }
 
@@ -163,21 +185,14 @@ pub async fn update_event(
     State(service): State<CalendarService>,
     Extension(user_id): Extension<Uuid>,
     Path(event_id): Path<Uuid>,
+    Query(query): Query<ConflictCheckQuery>,
     Json(updates): Json<UpdateEvent>,
-) -> Result<Json<Event>, AppError> {
-    // Check ownership
-    let event = service.get_event_by_id(event_id).await?
-        .ok_or(AppError::NotFoundError("Event not found".to_string()))?;
-    
-    let calendar = service.get_calendar_by_id(event.calendar_id).await?
-        .ok_or(AppError::NotFoundError("Calendar not found".to_string()))?;
-    
-    if calendar.user_id != user_id {
-        return Err(AppError::AuthenticationError("You don't have access to this event".to_string()));
-    }
+) -> Result<Json<CheckedEvent>, AppError> {
+    authz::authorize_event(&service, user_id, event_id, Access::Edit).await?;
+    updates.validate()?;
     
     let updated = service.update_event(event_id, updates).await?;
-    Ok(Json(updated))
+    Ok(Json(query.check(&service, updated).await?))
 }
 
 pub async fn delete_event(
@@ -185,426 +200,961 @@ pub async fn delete_event(
     Extension(user_id): Extension<Uuid>,
     Path(event_id): Path<Uuid>,
 ) -> Result<StatusCode, AppError> {
-    // Check ownership
-    let event = service.get_event_by_id(event_id).await?
-        .ok_or(AppError::NotFoundError("Event not found".to_string()))?;
-    
-    let calendar = service.get_calendar_by_id(event.calendar_id).await?
-        .ok_or(AppError::NotFoundError("Calendar not found".to_string()))?;
-    
-    if calendar.user_id != user_id {
-        return Err(AppError::AuthenticationError("You don't have access to this event".to_string()));
-    }
+    authz::authorize_event(&service, user_id, event_id, Access::Edit).await?;
     
     service.delete_event(event_id).await?;
     Ok(StatusCode::NO_CONTENT)
 }
 
-// Share endpoints
-pub async fn get_calendar_shares(
+/// Earlier versions of an event, newest first, with the fields each change touched.
+/// Deleted events keep their history for as long as their versions are kept.
+pub async fn get_event_history(
     State(service): State<CalendarService>,
     Extension(user_id): Extension<Uuid>,
-    Path(calendar_id): Path<Uuid>,
-) -> Result<Json<Vec<Share>>, AppError> {
-    // Check ownership
-    let calendar = service.get_calendar_by_id(calendar_id).await?
-        .ok_or(AppError::NotFoundError("Calendar not found".to_string()))?;
-    
-    if calendar.user_id != user_id {
-        return Err(AppError::AuthenticationError("You don't own this calendar".to_string()));
-    }
-    
-    let shares = service.get_shares_by_calendar_id(calendar_id).await?;
-    Ok(Json(shares))
+    Path(event_id): Path<Uuid>,
+) -> Result<Json<Vec<HistoryEntry>>, AppError> {
+    authz::authorize_event_history(&service, user_id, event_id, Access::View).await?;
+
+    let current = service.get_event_by_id(event_id).await?;
+    let versions = service.get_event_versions(event_id).await?;
+    Ok(Json(event_history::timeline(versions, current.as_ref())))
 }
 
-pub async fn create_share(
+/// Bring an event back to an earlier version, or back from deletion
+pub async fn restore_event_version(
+    State(service): State<CalendarService>,
+    Extension(user_id): Extension<Uuid>,
+    Path((event_id, version_id)): Path<(Uuid, i64)>,
+) -> Result<Json<Event>, AppError> {
+    authz::authorize_event_history(&service, user_id, event_id, Access::Edit).await?;
+
+    let event = service.restore_event_version(event_id, version_id).await?;
+    Ok(Json(event))
+}
+
+/// Deleted events of a calendar that can still be restored
+pub async fn get_deleted_events(
     State(service): State<CalendarService>,
     Extension(user_id): Extension<Uuid>,
     Path(calendar_id): Path<Uuid>,
-    Json(new_share): Json<NewShare>,
-) -> Result<Json<Share>, AppError> {
-    // Check ownership
-    let calendar = service.get_calendar_by_id(calendar_id).await?
-        .ok_or(AppError::NotFoundError("Calendar not found".to_string()))?;
-    
-    if calendar.user_id != user_id {
-        return Err(AppError::AuthenticationError("You don't own this calendar".to_string()));
-    }
-    
-    let share = service.create_share(calendar_id, user_id, new_share).await?;
-    Ok(Json(share))
+) -> Result<Json<Vec<EventVersion>>, AppError> {
+    authz::authorize_calendar(&service, user_id, calendar_id, Access::View).await?;
+
+    Ok(Json(service.get_deleted_events(calendar_id).await?))
 }
 
-pub async fn delete_share(
+/// Conflict copies of an event, with the fields in which each differs from it
+pub async fn get_edit_conflicts(
     State(service): State<CalendarService>,
-    Extension(_user_id): Extension<Uuid>,
-    Path(share_id): Path<Uuid>,
-) -> Result<StatusCode, AppError> {
-    service.delete_share(share_id).await?;
-    Ok(StatusCode::NO_CONTENT)
+    Extension(user_id): Extension<Uuid>,
+    Path(event_id): Path<Uuid>,
+) -> Result<Json<Vec<ConflictEntry>>, AppError> {
+    let event = authz::authorize_event(&service, user_id, event_id, Access::View).await?;
+
+    let conflicts = service.get_edit_conflicts(event_id).await?;
+    Ok(Json(edit_conflicts::compare(&event, conflicts)))
 }
 
-// CalDAV Protocol Handlers
+/// Resolve a conflict by keeping the stored event or applying the conflict copy
+pub async fn resolve_edit_conflict(
+    State(service): State<CalendarService>,
+    Extension(user_id): Extension<Uuid>,
+    Path((event_id, conflict_id)): Path<(Uuid, Uuid)>,
+    Json(input): Json<ResolveConflict>,
+) -> Result<Json<Event>, AppError> {
+    authz::authorize_event(&service, user_id, event_id, Access::Edit).await?;
 
-/// CalDAV well-known discovery endpoint
-pub async fn caldav_discovery() -> impl IntoResponse {
-    Response::builder()
-        .status(StatusCode::OK)
-        .header(header::CONTENT_TYPE, "text/plain")
-        .body(Body::from("/calendars/"))
-        .unwrap()
+    let event = service.resolve_edit_conflict(event_id, conflict_id, input.keep).await?;
+    Ok(Json(event))
 }
 
-/// Handle CalDAV PROPFIND requests
-pub async fn caldav_propfind(
+/// Query parameters identifying a single occurrence of a recurring event
+#[derive(Debug, Deserialize)]
+pub struct OccurrenceQuery {
+    pub recurrence_id: chrono::DateTime<chrono::Utc>,
+}
+
+/// Edit a single occurrence of a recurring event ("this event only")
+pub async fn update_event_occurrence(
     State(service): State<CalendarService>,
-    user_id_ext: Option<Extension<Uuid>>,
-    basic_auth: Option<Extension<BasicAuthCredentials>>,
-    _uri: Uri,
-) -> Result<Response, AppError> {
-    let user_id = get_user_id(&service, user_id_ext.map(|ext| ext.0), basic_auth.map(|ext| ext.0)).await?;
-    let calendars = service.get_calendars_by_user_id(user_id).await?;
-    
-    let mut responses = String::new();
-    
-    for calendar in calendars {
-        let calendar_url = format!("/calendars/{}/", calendar.id);
-        responses.push_str(&format!(
-            r#"<d:response>
-                <d:href>{}</d:href>
-                <d:propstat>
-                    <d:prop>
-                        <d:resourcetype>
-                            <d:collection/>
-                            <cal:calendar/>
-                        </d:resourcetype>
-                        <d:displayname>{}</d:displayname>
-                        <cal:supported-calendar-component-set>
-                            <cal:comp name="VEVENT"/>
-                            <cal:comp name="VTODO"/>
-                        </cal:supported-calendar-component-set>
-                    </d:prop>
-                    <d:status>HTTP/1.1 200 OK</d:status>
-                </d:propstat>
-            </d:response>"#,
-            calendar_url,
-            calendar.name
-        ));
-    }
-    
-    let body = format!(
-        r#"<?xml version="1.0" encoding="utf-8"?>
-<d:multistatus xmlns:d="DAV:" xmlns:cal="urn:ietf:params:xml:ns:caldav">
-    {}
-</d:multistatus>"#,
-        responses
-    );
+    Extension(user_id): Extension<Uuid>,
+    Path(event_id): Path<Uuid>,
+    Json(occurrence): Json<UpdateOccurrence>,
+) -> Result<Json<Event>, AppError> {
+    let event = authz::authorize_event(&service, user_id, event_id, Access::Edit).await?;
+    occurrence.changes.validate()?;
     
-    Ok(Response::builder()
-        .status(StatusCode::MULTI_STATUS)
-        .header(header::CONTENT_TYPE, "application/xml; charset=utf-8")
-        .body(Body::from(body))
-        .unwrap())
+    let updated = service.update_event_occurrence(event.parent_id.unwrap_or(event.id), occurrence).await?;
+    Ok(Json(updated))
 }
 
-/// Handle CalDAV REPORT requests for calendar queries
-#[allow(dead_code)]
-pub async fn caldav_report(
+/// Delete a single occurrence of a recurring event ("this event only")
+pub async fn delete_event_occurrence(
     State(service): State<CalendarService>,
-    user_id_ext: Option<Extension<Uuid>>,
-    basic_auth: Option<Extension<BasicAuthCredentials>>,
-    _body: String,
-) -> Result<Response, AppError> {
-    let user_id = get_user_id(&service, user_id_ext.map(|ext| ext.0), basic_auth.map(|ext| ext.0)).await?;
-    let calendars = service.get_calendars_by_user_id(user_id).await?;
+    Extension(user_id): Extension<Uuid>,
+    Path(event_id): Path<Uuid>,
+    Query(query): Query<OccurrenceQuery>,
+) -> Result<StatusCode, AppError> {
+    let event = authz::authorize_event(&service, user_id, event_id, Access::Edit).await?;
     
-    let mut responses = String::new();
+    service.delete_event_occurrence(event.parent_id.unwrap_or(event.id), query.recurrence_id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Detach a single occurrence of a recurring event into a standalone event
+pub async fn detach_event_occurrence(
+    State(service): State<CalendarService>,
+    Extension(user_id): Extension<Uuid>,
+    Path(event_id): Path<Uuid>,
+    Json(query): Json<OccurrenceQuery>,
+) -> Result<(StatusCode, Json<Event>), AppError> {
+    let event = authz::authorize_event(&service, user_id, event_id, Access::Edit).await?;
     
-    for calendar in calendars {
-        let events = service.get_events_by_calendar_id(calendar.id).await?;
-        
-        for event in events {
-            let event_url = format!("/calendars/{}/{}.ics", calendar.id, event.id);
-            let ical_event = ICalendarEvent::from(&event);
-            
-            responses.push_str(&format!(
-                r#"<d:response>
-                    <d:href>{}</d:href>
-                    <d:propstat>
-                        <d:prop>
-                            <d:getetag>"{}"</d:getetag>
-                            <cal:calendar-data>{}</cal:calendar-data>
-                        </d:prop>
-                        <d:status>HTTP/1.1 200 OK</d:status>
-                    </d:propstat>
-                </d:response>"#,
-                event_url,
-                event.id,
-                escape_xml(&ical_event.to_ical_string())
-            ));
+    let detached = service.detach_event_occurrence(event.parent_id.unwrap_or(event.id), query.recurrence_id).await?;
+    Ok((StatusCode::CREATED, Json(detached)))
+}
+
+/// Target of a duplicated event; defaults to the event's own calendar
+#[derive(Debug, Default, Deserialize)]
+pub struct DuplicateEvent {
+    pub calendar_id: Option<Uuid>,
+}
+
+/// Copy an event, with its recurrence, overridden instances and attachments, into its
+/// own or another calendar
+pub async fn duplicate_event(
+    State(service): State<CalendarService>,
+    Extension(user_id): Extension<Uuid>,
+    Path(event_id): Path<Uuid>,
+    body: Option<Json<DuplicateEvent>>,
+) -> Result<(StatusCode, Json<Event>), AppError> {
+    let event = authz::authorize_event(&service, user_id, event_id, Access::View).await?;
+    let target = body.and_then(|Json(body)| body.calendar_id).unwrap_or(event.calendar_id);
+    authz::authorize_calendar(&service, user_id, target, Access::Edit).await?;
+
+    let copy = service.copy_event(event_id, target).await?;
+    Ok((StatusCode::CREATED, Json(copy)))
+}
+
+// Offline sync and batch endpoints
+
+/// Largest number of operations accepted in one sync commit or event batch
+const MAX_BATCH_OPERATIONS: usize = 500;
+
+/// Check that the user may perform a batch operation. Missing and forbidden events or
+/// calendars are returned as the operation's status instead of failing the batch.
+async fn authorize_batch_operation(
+    service: &CalendarService,
+    user_id: Uuid,
+    operation: &BatchOperation,
+) -> Result<Option<SyncStatus>, AppError> {
+    let access = match operation {
+        BatchOperation::Create { calendar_id, .. } => {
+            authz::authorize_calendar(service, user_id, *calendar_id, Access::Edit).await.map(drop)
+        }
+        BatchOperation::Update { event_id, .. } | BatchOperation::Delete { event_id, .. } => {
+            authz::authorize_event(service, user_id, *event_id, Access::Edit).await.map(drop)
         }
+    };
+
+    match access {
+        Ok(()) => Ok(None),
+        Err(AppError::NotFoundError(_)) => Ok(Some(SyncStatus::NotFound)),
+        Err(AppError::AuthenticationError(_)) => Ok(Some(SyncStatus::Forbidden)),
+        Err(e) => Err(e),
     }
-    
-    let response_body = format!(
-        r#"<?xml version="1.0" encoding="utf-8"?>
-<d:multistatus xmlns:d="DAV:" xmlns:cal="urn:ietf:params:xml:ns:caldav">
-    {}
-</d:multistatus>"#,
-        responses
-    );
-    
-    Ok(Response::builder()
-        .status(StatusCode::MULTI_STATUS)
-        .header(header::CONTENT_TYPE, "application/xml; charset=utf-8")
-        .body(Body::from(response_body))
-        .unwrap())
 }
 
-/// Handle CalDAV GET requests for calendar data
-pub async fn caldav_get(
-    State(service): State<CalendarService>,
-    user_id_ext: Option<Extension<Uuid>>,
-    basic_auth: Option<Extension<BasicAuthCredentials>>,
-    uri: Uri,
-) -> Result<Response, AppError> {
-    let user_id = get_user_id(&service, user_id_ext.map(|ext| ext.0), basic_auth.map(|ext| ext.0)).await?;
-    let path = uri.path();
-    
-    // Parse path like /calendars/{calendar_id}/ or /calendars/{calendar_id}/{event_id}.ics
-    let parts: Vec<&str> = path.trim_start_matches('/').split('/').collect();
-    
-    if parts.len() < 2 {
-        return Err(AppError::ValidationError("Invalid calendar path".to_string()));
+/// Check every operation of a batch; a bad one fails the whole batch, with its
+/// position in the `list` field named in the error
+fn validate_operations(list: &str, operations: &[BatchOperation]) -> Result<(), AppError> {
+    for (index, operation) in operations.iter().enumerate() {
+        operation.validate().map_err(|e| match e {
+            AppError::FieldValidationError { field, message } => {
+                AppError::invalid_field(&format!("{}[{}].{}", list, index, field), message)
+            }
+            e => e,
+        })?;
     }
-    
-    let calendar_id = Uuid::parse_str(parts[1])?;
-    let calendar = service.get_calendar_by_id(calendar_id).await?
-        .ok_or(AppError::NotFoundError("Calendar not found".to_string()))?;
-    
-    // Check access
-    if calendar.user_id != user_id && !calendar.is_public {
-        return Err(AppError::AuthenticationError("Access denied".to_string()));
-    }
-    
-    if parts.len() == 2 || parts[2].is_empty() {
-        // Return entire calendar
-        let events = service.get_events_by_calendar_id(calendar_id).await?;
-        let mut ical_content = format!(
-            "BEGIN:VCALENDAR\r\n\
-             VERSION:2.0\r\n\
-             PRODID:-//My CalDAV Server//EN\r\n\
-             CALSCALE:GREGORIAN\r\n\
-             X-WR-CALNAME:{}\r\n",
-            calendar.name
-        );
-        
-        for event in events {
-            let ical_event = ICalendarEvent::from(&event);
-            ical_content.push_str(&ical_event.to_ical_string());
+    Ok(())
+}
+
+/// Authorize and apply a list of operations, returning one outcome per operation in
+/// request order. In an atomic batch a denied operation skips all others.
+async fn run_event_batch(
+    service: &CalendarService,
+    user_id: Uuid,
+    list: &str,
+    operations: Vec<BatchOperation>,
+    atomic: bool,
+) -> Result<Vec<(SyncStatus, Option<Event>)>, AppError> {
+    if operations.len() > MAX_BATCH_OPERATIONS {
+        return Err(AppError::invalid_field(list, format!("At most {} {} per batch", MAX_BATCH_OPERATIONS, list)));
+    }
+
+    // `None` marks the slots filled from the applied batch
+    let mut outcomes: Vec<Option<(SyncStatus, Option<Event>)>> = Vec::with_capacity(operations.len());
+    let mut accepted = Vec::new();
+
+    for operation in operations {
+        match authorize_batch_operation(service, user_id, &operation).await? {
+            Some(status) => outcomes.push(Some((status, None))),
+            None => {
+                accepted.push(operation);
+                outcomes.push(None);
+            }
         }
-        
-        ical_content.push_str("END:VCALENDAR\r\n");
-        
-        return Ok(Response::builder()
-            .status(StatusCode::OK)
-            .header(header::CONTENT_TYPE, "text/calendar; charset=utf-8")
-            .body(Body::from(ical_content))
-            .unwrap());
     }
-    
-    // Return specific event
-    let event_filename = parts[2];
-    let event_id_str = event_filename.trim_end_matches(".ics");
-    let event_id = Uuid::parse_str(event_id_str)?;
-    
-    let event = service.get_event_by_id(event_id).await?
-        .ok_or(AppError::NotFoundError("Event not found".to_string()))?;
-    
-    let ical_event = ICalendarEvent::from(&event);
-    let ical_content = format!(
-        "BEGIN:VCALENDAR\r\n\
-         VERSION:2.0\r\n\
-         PRODID:-//My CalDAV Server//EN\r\n\
-         {}\
-         END:VCALENDAR\r\n",
-        ical_event.to_ical_string()
-    );
-    
-    Ok(Response::builder()
-        .status(StatusCode::OK)
-        .header(header::CONTENT_TYPE, "text/calendar; charset=utf-8")
-        .header("ETag", format!("\"{}\"", event.id))
-        .body(Body::from(ical_content))
-        .unwrap())
+
+    if atomic && accepted.len() < outcomes.len() {
+        return Ok(outcomes.into_iter()
+            .map(|outcome| outcome.unwrap_or((SyncStatus::Skipped, None)))
+            .collect());
+    }
+
+    let mut applied = service.apply_event_batch(accepted, atomic).await?.into_iter();
+    Ok(outcomes.into_iter()
+        .filter_map(|outcome| outcome.or_else(|| applied.next()))
+        .collect())
 }
 
-/// Handle CalDAV PUT requests to create/update events
-#[allow(dead_code)]
-pub async fn caldav_put(
+/// Apply a batch of queued offline mutations, returning one result per mutation in
+/// request order. Mutations the user may not perform are reported, not applied.
+pub async fn commit_sync_batch(
     State(service): State<CalendarService>,
-    user_id_ext: Option<Extension<Uuid>>,
-    basic_auth: Option<Extension<BasicAuthCredentials>>,
-    uri: Uri,
-    body: String,
-) -> Result<Response, AppError> {
-    let user_id = get_user_id(&service, user_id_ext.map(|ext| ext.0), basic_auth.map(|ext| ext.0)).await?;
-    let path = uri.path();
-    
-    // Parse path like /calendars/{calendar_id}/{event_id}.ics
-    let parts: Vec<&str> = path.trim_start_matches('/').split('/').collect();
-    
-    if parts.len() < 3 {
-        return Err(AppError::ValidationError("Invalid event path".to_string()));
+    Extension(user_id): Extension<Uuid>,
+    Json(request): Json<SyncCommitRequest>,
+) -> Result<Json<Vec<SyncResult>>, AppError> {
+    let (client_ids, operations): (Vec<_>, Vec<_>) = request.mutations.into_iter()
+        .map(|mutation| (mutation.client_id, BatchOperation::from(mutation.operation)))
+        .unzip();
+    validate_operations("mutations", &operations)?;
+
+    let outcomes = run_event_batch(&service, user_id, "mutations", operations, false).await?;
+    let results = client_ids.into_iter()
+        .zip(outcomes)
+        .map(|(client_id, (status, event))| SyncResult { client_id, status, event })
+        .collect();
+    Ok(Json(results))
+}
+
+/// Create, update and delete events in one transaction, with one result per operation
+/// in request order
+pub async fn batch_events(
+    State(service): State<CalendarService>,
+    Extension(user_id): Extension<Uuid>,
+    Json(request): Json<EventBatchRequest>,
+) -> Result<Json<Vec<BatchResult>>, AppError> {
+    validate_operations("operations", &request.operations)?;
+    let outcomes = run_event_batch(&service, user_id, "operations", request.operations, request.atomic).await?;
+    let results = outcomes.into_iter()
+        .enumerate()
+        .map(|(index, (status, event))| BatchResult { index, status, event })
+        .collect();
+    Ok(Json(results))
+}
+
+// Attachment endpoints
+
+/// Read the `file` field of a multipart attachment upload as (filename, content type, data)
+async fn read_attachment_upload(multipart: &mut Multipart) -> Result<(String, String, Vec<u8>), AppError> {
+    while let Some(field) = multipart.next_field().await
+        .map_err(|e| AppError::ValidationError(format!("Invalid upload: {}", e)))?
+    {
+        if field.name() != Some("file") {
+            continue;
+        }
+
+        // Keep only the last path segment of client-supplied file names
+        let filename = field.file_name()
+            .and_then(|name| name.rsplit(['/', '\\']).next())
+            .filter(|name| !name.is_empty())
+            .unwrap_or("attachment")
+            .replace('"', "");
+        let content_type = field.content_type()
+            .unwrap_or("application/octet-stream")
+            .to_string();
+        let data = field.bytes().await
+            .map_err(|e| AppError::ValidationError(format!("Invalid upload: {}", e)))?;
+
+        if data.is_empty() {
+            return Err(AppError::ValidationError("Uploaded file is empty".to_string()));
+        }
+
+        return Ok((filename, content_type, data.to_vec()));
     }
+
+    Err(AppError::invalid_field("file", "Missing file field"))
+}
+
+pub async fn get_event_attachments(
+    State(service): State<CalendarService>,
+    Extension(user_id): Extension<Uuid>,
+    Path(event_id): Path<Uuid>,
+) -> Result<Json<Vec<Attachment>>, AppError> {
+    authz::authorize_event(&service, user_id, event_id, Access::View).await?;
     
-    let calendar_id = Uuid::parse_str(parts[1])?;
-    let calendar = service.get_calendar_by_id(calendar_id).await?
-        .ok_or(AppError::NotFoundError("Calendar not found".to_string()))?;
+    let attachments = service.get_attachments_by_event_id(event_id).await?;
+    Ok(Json(attachments))
+}
+
+pub async fn upload_event_attachment(
+    State(service): State<CalendarService>,
+    Extension(user_id): Extension<Uuid>,
+    Path(event_id): Path<Uuid>,
+    mut multipart: Multipart,
+) -> Result<Json<Attachment>, AppError> {
+    authz::authorize_event(&service, user_id, event_id, Access::Edit).await?;
     
-    // Check ownership
-    if calendar.user_id != user_id {
-        return Err(AppError::AuthenticationError("You don't own this calendar".to_string()));
-    }
+    let (filename, content_type, data) = read_attachment_upload(&mut multipart).await?;
+    let attachment = service.create_attachment(event_id, &filename, &content_type, &data).await?;
+    Ok(Json(attachment))
+}
+
+pub async fn delete_attachment(
+    State(service): State<CalendarService>,
+    Extension(user_id): Extension<Uuid>,
+    Path(attachment_id): Path<Uuid>,
+) -> Result<StatusCode, AppError> {
+    let attachment = service.get_attachment_by_id(attachment_id).await?
+        .ok_or(AppError::NotFoundError("Attachment not found".to_string()))?;
     
-    // Parse iCalendar data
-    let new_event = parse_icalendar(&body)?;
-    let event = service.create_event(calendar_id, new_event).await?;
+    authz::authorize_event(&service, user_id, attachment.event_id, Access::Edit).await?;
     
-    Ok(Response::builder()
-        .status(StatusCode::CREATED)
-        .header(header::LOCATION, format!("/calendars/{}/{}.ics", calendar_id, event.id))
-        .header("ETag", format!("\"{}\"", event.id))
-        .body(Body::from(""))
-        .unwrap())
+    service.delete_attachment(attachment_id).await?;
+    Ok(StatusCode::NO_CONTENT)
 }
 
-/// Handle CalDAV DELETE requests
-#[allow(dead_code)]
-pub async fn caldav_delete(
+/// Download an attachment (the URL referenced by ATTACH properties)
+pub async fn download_attachment(
     State(service): State<CalendarService>,
     user_id_ext: Option<Extension<Uuid>>,
     basic_auth: Option<Extension<BasicAuthCredentials>>,
-    uri: Uri,
+    Path(attachment_id): Path<Uuid>,
 ) -> Result<Response, AppError> {
     let user_id = get_user_id(&service, user_id_ext.map(|ext| ext.0), basic_auth.map(|ext| ext.0)).await?;
-    let path = uri.path();
-    
-    // Parse path like /calendars/{calendar_id}/{event_id}.ics
-    let parts: Vec<&str> = path.trim_start_matches('/').split('/').collect();
-    
-    if parts.len() < 3 {
-        return Err(AppError::ValidationError("Invalid event path".to_string()));
-    }
-    
-    let event_filename = parts[2];
-    let event_id_str = event_filename.trim_end_matches(".ics");
-    let event_id = Uuid::parse_str(event_id_str)?;
-    
-    // Check ownership
-    let event = service.get_event_by_id(event_id).await?
-        .ok_or(AppError::NotFoundError("Event not found".to_string()))?;
-    
-    let calendar = service.get_calendar_by_id(event.calendar_id).await?
-        .ok_or(AppError::NotFoundError("Calendar not found".to_string()))?;
-    
-    if calendar.user_id != user_id {
-        return Err(AppError::AuthenticationError("You don't have access to this event".to_string()));
-    }
-    
-    service.delete_event(event_id).await?;
-    
+
+    let attachment = service.get_attachment_by_id(attachment_id).await?
+        .ok_or(AppError::NotFoundError("Attachment not found".to_string()))?;
+
+    authz::authorize_event(&service, user_id, attachment.event_id, Access::View).await?;
+
+    let data = service.read_attachment(&attachment).await?;
+
     Ok(Response::builder()
-        .status(StatusCode::NO_CONTENT)
-        .body(Body::from(""))
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, &attachment.content_type)
+        .header(header::CONTENT_DISPOSITION, format!("attachment; filename=\"{}\"", attachment.filename))
+        .body(Body::from(data))
         .unwrap())
 }
 
-/// Handle CalDAV MKCOL requests to create new calendars
-pub async fn caldav_mkcol(
+// Share endpoints
+pub async fn get_calendar_shares(
     State(service): State<CalendarService>,
-    user_id_ext: Option<Extension<Uuid>>,
-    basic_auth: Option<Extension<BasicAuthCredentials>>,
-    uri: Uri,
-    body: String,
-) -> Result<Response, AppError> {
-    let user_id = get_user_id(&service, user_id_ext.map(|ext| ext.0), basic_auth.map(|ext| ext.0)).await?;
-    let path = uri.path();
-    
-    // Parse path like /calendars/{calendar_name}/
-    let parts: Vec<&str> = path.trim_start_matches('/').split('/').collect();
-    
-    if parts.len() < 2 {
-        return Err(AppError::ValidationError("Invalid calendar path".to_string()));
-    }
+    Extension(user_id): Extension<Uuid>,
+    Path(calendar_id): Path<Uuid>,
+) -> Result<Json<Vec<Share>>, AppError> {
+    authz::authorize_calendar(&service, user_id, calendar_id, Access::Admin).await?;
     
-    // Extract calendar name from path or request body
-    let calendar_name = if parts[1].is_empty() {
-        // Try to parse name from XML body
-        parse_calendar_name_from_mkcol(&body).unwrap_or_else(|| "New Calendar".to_string())
-    } else {
-        parts[1].to_string()
-    };
+    let shares = service.get_shares_by_calendar_id(calendar_id).await?;
+    Ok(Json(shares))
+}
+
+pub async fn create_share(
+    State(service): State<CalendarService>,
+    Extension(user_id): Extension<Uuid>,
+    Path(calendar_id): Path<Uuid>,
+    Json(new_share): Json<NewShare>,
+) -> Result<Json<Share>, AppError> {
+    authz::authorize_calendar(&service, user_id, calendar_id, Access::Admin).await?;
+    new_share.validate()?;
     
-    // Create the calendar
-    let new_calendar = NewCalendar {
-        name: calendar_name,
-        description: None,
-        color: Some("#3B82F6".to_string()), // Default blue color
-        is_public: false,
-    };
+    let share = service.create_share(calendar_id, user_id, new_share).await?;
+    Ok(Json(share))
+}
+
+pub async fn delete_share(
+    State(service): State<CalendarService>,
+    Extension(user_id): Extension<Uuid>,
+    Path(share_id): Path<Uuid>,
+) -> Result<StatusCode, AppError> {
+    let share = service.get_share_by_id(share_id).await?
+        .ok_or(AppError::NotFoundError("Share not found".to_string()))?;
     
-    let calendar = service.create_calendar(user_id, new_calendar).await?;
+    authz::authorize_calendar(&service, user_id, share.calendar_id, Access::Admin).await?;
     
-    Ok(Response::builder()
-        .status(StatusCode::CREATED)
-        .header(header::LOCATION, format!("/calendars/{}/", calendar.id))
-        .body(Body::from(""))
-        .unwrap())
+    service.delete_share(share_id).await?;
+    Ok(StatusCode::NO_CONTENT)
 }
 
-/// Parse calendar name from MKCOL request body
-fn parse_calendar_name_from_mkcol(body: &str) -> Option<String> {
-    // Try to extract display name from XML body
-    // Example: <d:prop><d:displayname>My Calendar</d:displayname></d:prop>
-    if let Some(start) = body.find("<d:displayname>") {
-        let start = start + "<d:displayname>".len();
-        if let Some(end) = body[start..].find("</d:displayname>") {
-            return Some(body[start..start + end].to_string());
-        }
-    }
-    if let Some(start) = body.find("<displayname>") {
-        let start = start + "<displayname>".len();
-        if let Some(end) = body[start..].find("</displayname>") {
-            return Some(body[start..start + end].to_string());
-        }
+// Share link endpoints
+pub async fn get_share_links(
+    State(service): State<CalendarService>,
+    Extension(user_id): Extension<Uuid>,
+    Path(calendar_id): Path<Uuid>,
+) -> Result<Json<Vec<ShareLinkDetails>>, AppError> {
+    authz::authorize_calendar(&service, user_id, calendar_id, Access::Admin).await?;
+
+    let links = service.get_share_links(calendar_id).await?;
+    Ok(Json(links))
+}
+
+pub async fn create_share_link(
+    State(service): State<CalendarService>,
+    Extension(user_id): Extension<Uuid>,
+    Path(calendar_id): Path<Uuid>,
+    Json(new_link): Json<NewShareLink>,
+) -> Result<(StatusCode, Json<ShareLinkDetails>), AppError> {
+    authz::authorize_calendar(&service, user_id, calendar_id, Access::Admin).await?;
+
+    let link = service.create_share_link(calendar_id, user_id, new_link).await?;
+    service.record_audit(user_id, None, "share_link_created", Some(&format!("{} ({})", link.id, link.permission_level))).await?;
+    Ok((StatusCode::CREATED, Json(service.share_link_details(link)?)))
+}
+
+pub async fn revoke_share_link(
+    State(service): State<CalendarService>,
+    Extension(user_id): Extension<Uuid>,
+    Path(link_id): Path<Uuid>,
+) -> Result<StatusCode, AppError> {
+    let link = service.get_share_link_by_id(link_id).await?
+        .ok_or(AppError::NotFoundError("Share link not found".to_string()))?;
+
+    authz::authorize_calendar(&service, user_id, link.calendar_id, Access::Admin).await?;
+
+    service.revoke_share_link(link_id).await?;
+    service.record_audit(user_id, None, "share_link_revoked", Some(&link.id.to_string())).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Token of a share link to redeem, the last segment of its URL
+#[derive(Debug, Deserialize)]
+pub struct RedeemShareLink {
+    pub token: String,
+}
+
+/// Redeem a share link for the signed-in user, returning the calendar it grants access to
+pub async fn redeem_share_link(
+    State(service): State<CalendarService>,
+    Extension(user_id): Extension<Uuid>,
+    Json(request): Json<RedeemShareLink>,
+) -> Result<Json<Calendar>, AppError> {
+    let calendar_id = service.redeem_share_link(&request.token, user_id).await?;
+
+    let calendar = service.get_calendar_by_id(calendar_id).await?
+        .ok_or(AppError::NotFoundError("Calendar not found".to_string()))?;
+    Ok(Json(calendar))
+}
+
+// Group endpoints
+pub async fn get_groups(
+    State(service): State<CalendarService>,
+    Extension(user_id): Extension<Uuid>,
+) -> Result<Json<Vec<UserGroup>>, AppError> {
+    let groups = service.get_groups_for_user(user_id).await?;
+    Ok(Json(groups))
+}
+
+pub async fn create_group(
+    State(service): State<CalendarService>,
+    Extension(user_id): Extension<Uuid>,
+    Json(new_group): Json<NewGroup>,
+) -> Result<(StatusCode, Json<Group>), AppError> {
+    let group = service.create_group(user_id, new_group).await?;
+    service.record_audit(user_id, None, "group_created", Some(&format!("{} ({})", group.id, group.name))).await?;
+    Ok((StatusCode::CREATED, Json(group)))
+}
+
+pub async fn get_group(
+    State(service): State<CalendarService>,
+    Extension(user_id): Extension<Uuid>,
+    Path(group_id): Path<Uuid>,
+) -> Result<Json<GroupDetails>, AppError> {
+    let (group, role) = authz::authorize_group(&service, user_id, group_id, Access::View).await?;
+
+    let members = service.get_group_members(group_id).await?;
+    let calendars = service.get_group_calendars(group_id).await?;
+    Ok(Json(GroupDetails { group, role, members, calendars }))
+}
+
+/// Delete a group together with all of its calendars
+pub async fn delete_group(
+    State(service): State<CalendarService>,
+    Extension(user_id): Extension<Uuid>,
+    Path(group_id): Path<Uuid>,
+) -> Result<StatusCode, AppError> {
+    authz::authorize_group(&service, user_id, group_id, Access::Admin).await?;
+
+    service.delete_group(group_id).await?;
+    service.record_audit(user_id, None, "group_deleted", Some(&group_id.to_string())).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn get_group_members(
+    State(service): State<CalendarService>,
+    Extension(user_id): Extension<Uuid>,
+    Path(group_id): Path<Uuid>,
+) -> Result<Json<Vec<GroupMember>>, AppError> {
+    authz::authorize_group(&service, user_id, group_id, Access::View).await?;
+
+    let members = service.get_group_members(group_id).await?;
+    Ok(Json(members))
+}
+
+pub async fn add_group_member(
+    State(service): State<CalendarService>,
+    Extension(user_id): Extension<Uuid>,
+    Path(group_id): Path<Uuid>,
+    Json(new_member): Json<NewGroupMember>,
+) -> Result<(StatusCode, Json<GroupMember>), AppError> {
+    authz::authorize_group(&service, user_id, group_id, Access::Admin).await?;
+
+    let member = service.add_group_member(group_id, new_member).await?;
+    service.record_audit(user_id, None, "group_member_added", Some(&format!("{} to {}", member.email, group_id))).await?;
+    Ok((StatusCode::CREATED, Json(member)))
+}
+
+pub async fn update_group_member(
+    State(service): State<CalendarService>,
+    Extension(user_id): Extension<Uuid>,
+    Path((group_id, member_id)): Path<(Uuid, Uuid)>,
+    Json(update): Json<UpdateGroupMember>,
+) -> Result<StatusCode, AppError> {
+    authz::authorize_group(&service, user_id, group_id, Access::Admin).await?;
+
+    service.update_group_member_role(group_id, member_id, update.role).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Remove a member from a group. Admins can remove anyone; members can only leave.
+pub async fn remove_group_member(
+    State(service): State<CalendarService>,
+    Extension(user_id): Extension<Uuid>,
+    Path((group_id, member_id)): Path<(Uuid, Uuid)>,
+) -> Result<StatusCode, AppError> {
+    let access = if member_id == user_id { Access::View } else { Access::Admin };
+    authz::authorize_group(&service, user_id, group_id, access).await?;
+
+    service.remove_group_member(group_id, member_id).await?;
+    service.record_audit(user_id, None, "group_member_removed", Some(&format!("{} from {}", member_id, group_id))).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn create_group_calendar(
+    State(service): State<CalendarService>,
+    Extension(user_id): Extension<Uuid>,
+    Path(group_id): Path<Uuid>,
+    Json(new_calendar): Json<NewCalendar>,
+) -> Result<(StatusCode, Json<Calendar>), AppError> {
+    authz::authorize_group(&service, user_id, group_id, Access::Admin).await?;
+    new_calendar.validate()?;
+
+    let calendar = service.create_group_calendar(group_id, user_id, new_calendar).await?;
+    Ok((StatusCode::CREATED, Json(calendar)))
+}
+
+// Delegation endpoints
+pub async fn get_delegations(
+    State(service): State<CalendarService>,
+    Extension(user_id): Extension<Uuid>,
+) -> Result<Json<Delegations>, AppError> {
+    let delegations = service.get_delegations(user_id).await?;
+    Ok(Json(delegations))
+}
+
+/// Let another user act on all of your personal calendars
+pub async fn create_delegation(
+    State(service): State<CalendarService>,
+    Extension(user_id): Extension<Uuid>,
+    Json(new_delegation): Json<NewDelegation>,
+) -> Result<(StatusCode, Json<Delegation>), AppError> {
+    let delegation = service.create_delegation(user_id, new_delegation).await?;
+    service.record_audit(user_id, None, "delegation_granted", Some(&format!("{} ({})", delegation.delegate_email, delegation.permission_level))).await?;
+    Ok((StatusCode::CREATED, Json(delegation)))
+}
+
+/// Revoke a delegation. Both the owner and the delegate can end it.
+pub async fn delete_delegation(
+    State(service): State<CalendarService>,
+    Extension(user_id): Extension<Uuid>,
+    Path(delegation_id): Path<Uuid>,
+) -> Result<StatusCode, AppError> {
+    let delegation = service.get_delegation_by_id(delegation_id).await?
+        .filter(|d| d.owner_id == user_id || d.delegate_id == user_id)
+        .ok_or(AppError::NotFoundError("Delegation not found".to_string()))?;
+
+    service.delete_delegation(delegation_id).await?;
+    service.record_audit(user_id, None, "delegation_revoked", Some(&delegation.delegate_email)).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+// Notification preference endpoints
+pub async fn get_notification_preferences(
+    State(service): State<CalendarService>,
+    Extension(user_id): Extension<Uuid>,
+) -> Result<Json<Vec<NotificationPreference>>, AppError> {
+    let preferences = service.get_notification_preferences(user_id).await?;
+    Ok(Json(preferences))
+}
+
+/// Choose how reminders of a calendar the user owns or that is shared with them are delivered
+pub async fn update_notification_preference(
+    State(service): State<CalendarService>,
+    Extension(user_id): Extension<Uuid>,
+    Path(calendar_id): Path<Uuid>,
+    Json(update): Json<UpdateNotificationPreference>,
+) -> Result<Json<NotificationPreference>, AppError> {
+    let mut preference = service.get_notification_preferences(user_id).await?
+        .into_iter()
+        .find(|p| p.calendar_id == calendar_id)
+        .ok_or(AppError::NotFoundError("Calendar not found".to_string()))?;
+
+    service.set_notification_preference(user_id, calendar_id, update.channel).await?;
+    preference.channel = update.channel;
+    Ok(Json(preference))
+}
+
+// User settings endpoints
+pub async fn get_user_settings(
+    State(service): State<CalendarService>,
+    Extension(user_id): Extension<Uuid>,
+) -> Result<Json<UserSettings>, AppError> {
+    let settings = service.get_user_settings(user_id).await?;
+    Ok(Json(settings))
+}
+
+pub async fn update_user_settings(
+    State(service): State<CalendarService>,
+    Extension(user_id): Extension<Uuid>,
+    Json(update): Json<UpdateUserSettings>,
+) -> Result<Json<UserSettings>, AppError> {
+    let settings = service.update_user_settings(user_id, update).await?;
+    Ok(Json(settings))
+}
+
+// Calendar preference endpoints
+pub async fn get_calendar_preferences(
+    State(service): State<CalendarService>,
+    Extension(user_id): Extension<Uuid>,
+) -> Result<Json<Vec<CalendarPreference>>, AppError> {
+    let preferences = service.get_calendar_preferences(user_id).await?;
+    Ok(Json(preferences))
+}
+
+/// Reorder the calendars the user owns or that are shared with them
+pub async fn reorder_calendars(
+    State(service): State<CalendarService>,
+    Extension(user_id): Extension<Uuid>,
+    Json(order): Json<CalendarOrder>,
+) -> Result<Json<Vec<CalendarPreference>>, AppError> {
+    service.reorder_calendars(user_id, &order.calendar_ids).await?;
+    let preferences = service.get_calendar_preferences(user_id).await?;
+    Ok(Json(preferences))
+}
+
+/// Move, hide or recolor a calendar the user owns or that is shared with them, for
+/// this user only
+pub async fn update_calendar_preference(
+    State(service): State<CalendarService>,
+    Extension(user_id): Extension<Uuid>,
+    Path(calendar_id): Path<Uuid>,
+    Json(update): Json<UpdateCalendarPreference>,
+) -> Result<Json<CalendarPreference>, AppError> {
+    if !service.get_calendar_preferences(user_id).await?.iter().any(|p| p.calendar_id == calendar_id) {
+        return Err(AppError::NotFoundError("Calendar not found".to_string()));
     }
-    None
+
+    service.update_calendar_preference(user_id, calendar_id, update).await?;
+    service.get_calendar_preferences(user_id).await?
+        .into_iter()
+        .find(|p| p.calendar_id == calendar_id)
+        .ok_or_else(|| AppError::InternalServerError("Failed to fetch calendar preference".to_string()))
+        .map(Json)
 }
 
+// Feed token endpoints
+pub async fn get_feed_tokens(
+    State(service): State<CalendarService>,
+    Extension(user_id): Extension<Uuid>,
+    Path(calendar_id): Path<Uuid>,
+) -> Result<Json<Vec<FeedTokenDetails>>, AppError> {
+    authz::authorize_calendar(&service, user_id, calendar_id, Access::Admin).await?;
+
+    let tokens = service.get_feed_tokens(calendar_id).await?;
+    Ok(Json(tokens))
+}
+
+pub async fn create_feed_token(
+    State(service): State<CalendarService>,
+    Extension(user_id): Extension<Uuid>,
+    Path(calendar_id): Path<Uuid>,
+    Json(new_token): Json<NewFeedToken>,
+) -> Result<(StatusCode, Json<FeedTokenDetails>), AppError> {
+    authz::authorize_calendar(&service, user_id, calendar_id, Access::Admin).await?;
+
+    let token = service.create_feed_token(calendar_id, user_id, new_token).await?;
+    service.record_audit(user_id, None, "feed_token_created", Some(&format!("{} ({})", token.id, token.label))).await?;
+    Ok((StatusCode::CREATED, Json(service.feed_token_details(token).await?)))
+}
+
+pub async fn revoke_feed_token(
+    State(service): State<CalendarService>,
+    Extension(user_id): Extension<Uuid>,
+    Path(token_id): Path<Uuid>,
+) -> Result<StatusCode, AppError> {
+    let token = service.get_feed_token_by_id(token_id).await?
+        .ok_or(AppError::NotFoundError("Feed token not found".to_string()))?;
+
+    authz::authorize_calendar(&service, user_id, token.calendar_id, Access::Admin).await?;
+
+    service.revoke_feed_token(token_id).await?;
+    service.record_audit(user_id, None, "feed_token_revoked", Some(&format!("{} ({})", token.id, token.label))).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Serve a calendar's iCalendar feed to anyone holding one of its unrevoked feed links
+pub async fn get_feed(
+    State(service): State<CalendarService>,
+    Path(token): Path<String>,
+    request: axum::extract::Request,
+) -> Result<Response, AppError> {
+    let token = service.resolve_feed_token(token.trim_end_matches(".ics")).await?;
+
+    let ip = request.extensions()
+        .get::<axum::extract::ConnectInfo<std::net::SocketAddr>>()
+        .map(|info| info.0.ip().to_string())
+        .unwrap_or_else(|| "unknown".to_string());
+    service.record_feed_fetch(token.id, &ip).await?;
+
+    let calendar = service.get_calendar_by_id(token.calendar_id).await?
+        .ok_or(AppError::NotFoundError("Calendar not found".to_string()))?;
+
+    Ok(streaming::calendar_ics_response(&service, request.headers(), calendar, false))
+}
+
+// User feed endpoints
+
+/// Look up a feed token of `user_id`; other users' tokens are reported as missing
+async fn own_user_feed_token(service: &CalendarService, user_id: Uuid, token_id: Uuid) -> Result<UserFeedToken, AppError> {
+    service.get_user_feed_token_by_id(token_id).await?
+        .filter(|token| token.user_id == user_id)
+        .ok_or(AppError::NotFoundError("Feed token not found".to_string()))
+}
+
+pub async fn get_user_feed_tokens(
+    State(service): State<CalendarService>,
+    Extension(user_id): Extension<Uuid>,
+) -> Result<Json<Vec<UserFeedTokenDetails>>, AppError> {
+    let tokens = service.get_user_feed_tokens(user_id).await?;
+    Ok(Json(tokens))
+}
+
+pub async fn create_user_feed_token(
+    State(service): State<CalendarService>,
+    Extension(user_id): Extension<Uuid>,
+    Json(new_token): Json<NewUserFeedToken>,
+) -> Result<(StatusCode, Json<UserFeedTokenDetails>), AppError> {
+    let token = service.create_user_feed_token(user_id, new_token).await?;
+    service.record_audit(user_id, None, "user_feed_token_created", Some(&format!("{} ({})", token.id, token.label))).await?;
+    Ok((StatusCode::CREATED, Json(service.user_feed_token_details(token)?)))
+}
+
+pub async fn revoke_user_feed_token(
+    State(service): State<CalendarService>,
+    Extension(user_id): Extension<Uuid>,
+    Path(token_id): Path<Uuid>,
+) -> Result<StatusCode, AppError> {
+    let token = own_user_feed_token(&service, user_id, token_id).await?;
+
+    service.revoke_user_feed_token(token_id).await?;
+    service.record_audit(user_id, None, "user_feed_token_revoked", Some(&format!("{} ({})", token.id, token.label))).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Serve the merged iCalendar feed of several of a user's calendars
+pub async fn get_user_feed(
+    State(service): State<CalendarService>,
+    Path(token): Path<String>,
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
+    let token = service.resolve_user_feed_token(&token).await?;
+    service.record_user_feed_fetch(token.id).await?;
+
+    let ics_content = service.export_user_feed_ics(&token).await?;
+
+    Ok(etag::ics_response(&headers, ics_content))
+}
+
+// Subscription endpoints
+
+/// Look up a subscription of `user_id`; other users' subscriptions are reported as missing
+async fn own_subscription(service: &CalendarService, user_id: Uuid, subscription_id: Uuid) -> Result<CalendarSubscription, AppError> {
+    service.get_subscription_by_id(subscription_id).await?
+        .filter(|subscription| subscription.user_id == user_id)
+        .ok_or(AppError::NotFoundError("Subscription not found".to_string()))
+}
+
+pub async fn get_subscriptions(
+    State(service): State<CalendarService>,
+    Extension(user_id): Extension<Uuid>,
+) -> Result<Json<Vec<CalendarSubscription>>, AppError> {
+    let subscriptions = service.get_subscriptions_by_user_id(user_id).await?;
+    Ok(Json(subscriptions))
+}
+
+pub async fn create_subscription(
+    State(service): State<CalendarService>,
+    Extension(user_id): Extension<Uuid>,
+    Json(new_subscription): Json<NewCalendarSubscription>,
+) -> Result<(StatusCode, Json<CalendarSubscription>), AppError> {
+    let subscription = service.create_subscription(user_id, new_subscription).await?;
+    Ok((StatusCode::CREATED, Json(subscription)))
+}
+
+pub async fn update_subscription(
+    State(service): State<CalendarService>,
+    Extension(user_id): Extension<Uuid>,
+    Path(subscription_id): Path<Uuid>,
+    Json(updates): Json<UpdateCalendarSubscription>,
+) -> Result<Json<CalendarSubscription>, AppError> {
+    own_subscription(&service, user_id, subscription_id).await?;
+
+    let subscription = service.update_subscription(subscription_id, updates).await?;
+    Ok(Json(subscription))
+}
+
+pub async fn delete_subscription(
+    State(service): State<CalendarService>,
+    Extension(user_id): Extension<Uuid>,
+    Path(subscription_id): Path<Uuid>,
+) -> Result<StatusCode, AppError> {
+    own_subscription(&service, user_id, subscription_id).await?;
+
+    service.delete_subscription(subscription_id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+// Holiday pack endpoints
+pub async fn get_holiday_packs(
+    State(service): State<CalendarService>,
+    Extension(user_id): Extension<Uuid>,
+) -> Result<Json<Vec<HolidayPackStatus>>, AppError> {
+    let packs = service.get_holiday_packs(user_id).await?;
+    Ok(Json(packs))
+}
+
+/// Enable a holiday pack, returning its read-only calendar
+pub async fn enable_holiday_pack(
+    State(service): State<CalendarService>,
+    Extension(user_id): Extension<Uuid>,
+    Path(pack_id): Path<String>,
+) -> Result<Json<Calendar>, AppError> {
+    let calendar = service.enable_holiday_pack(user_id, &pack_id).await?;
+    Ok(Json(calendar))
+}
+
+pub async fn disable_holiday_pack(
+    State(service): State<CalendarService>,
+    Extension(user_id): Extension<Uuid>,
+    Path(pack_id): Path<String>,
+) -> Result<StatusCode, AppError> {
+    service.disable_holiday_pack(user_id, &pack_id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+// Contact endpoints
+
+/// Look up a contact of `user_id`; other users' contacts are reported as missing
+async fn own_contact(service: &CalendarService, user_id: Uuid, contact_id: Uuid) -> Result<Contact, AppError> {
+    service.get_contact_by_id(contact_id).await?
+        .filter(|contact| contact.user_id == user_id)
+        .ok_or(AppError::NotFoundError("Contact not found".to_string()))
+}
+
+pub async fn get_contacts(
+    State(service): State<CalendarService>,
+    Extension(user_id): Extension<Uuid>,
+) -> Result<Json<Vec<Contact>>, AppError> {
+    let contacts = service.get_contacts_by_user_id(user_id).await?;
+    Ok(Json(contacts))
+}
+
+pub async fn create_contact(
+    State(service): State<CalendarService>,
+    Extension(user_id): Extension<Uuid>,
+    Json(new_contact): Json<NewContact>,
+) -> Result<(StatusCode, Json<Contact>), AppError> {
+    let contact = service.create_contact(user_id, new_contact).await?;
+    Ok((StatusCode::CREATED, Json(contact)))
+}
+
+pub async fn update_contact(
+    State(service): State<CalendarService>,
+    Extension(user_id): Extension<Uuid>,
+    Path(contact_id): Path<Uuid>,
+    Json(updates): Json<UpdateContact>,
+) -> Result<Json<Contact>, AppError> {
+    own_contact(&service, user_id, contact_id).await?;
+
+    let contact = service.update_contact(contact_id, updates).await?;
+    Ok(Json(contact))
+}
+
+pub async fn delete_contact(
+    State(service): State<CalendarService>,
+    Extension(user_id): Extension<Uuid>,
+    Path(contact_id): Path<Uuid>,
+) -> Result<StatusCode, AppError> {
+    own_contact(&service, user_id, contact_id).await?;
+
+    service.delete_contact(contact_id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+// iCalendar parsing
+
 /// Parse iCalendar VEVENT data into NewEvent
-#[allow(dead_code)]
-fn parse_icalendar(data: &str) -> Result<NewEvent, AppError> {
+pub(crate) fn parse_icalendar(data: &str) -> Result<NewEvent, AppError> {
     let mut title = None;
     let mut description = None;
     let mut location = None;
     let mut start_time = None;
     let mut end_time = None;
     let mut is_all_day = false;
+    let mut rrule = None;
+    let mut geo = None;
+    let mut alarm_minutes = None;
+    let mut in_alarm = false;
     
     for line in data.lines() {
         let line = line.trim();
         
-        if line.starts_with("SUMMARY:") {
-            title = Some(line[8..].to_string());
-        } else if line.starts_with("DESCRIPTION:") {
-            description = Some(line[12..].to_string());
-        } else if line.starts_with("LOCATION:") {
-            location = Some(line[9..].to_string());
+        // Only the trigger of the first alarm is kept; its other properties aren't the event's
+        if line == "BEGIN:VALARM" {
+            in_alarm = true;
+        } else if line == "END:VALARM" {
+            in_alarm = false;
+        } else if in_alarm {
+            if alarm_minutes.is_none() && line.starts_with("TRIGGER") {
+                alarm_minutes = parse_alarm_trigger(line);
+            }
+        } else if let Some(value) = line.strip_prefix("SUMMARY:") {
+            title = Some(unescape_ical_text(value));
+        } else if let Some(value) = line.strip_prefix("DESCRIPTION:") {
+            description = Some(unescape_ical_text(value));
+        } else if let Some(value) = line.strip_prefix("LOCATION:") {
+            location = Some(unescape_ical_text(value));
         } else if line.starts_with("DTSTART") {
-            start_time = Some(parse_ical_datetime(&line.split(':').last().unwrap_or(""))?);
+            is_all_day |= line.contains("VALUE=DATE:");
+            start_time = Some(parse_ical_time(line)?);
         } else if line.starts_with("DTEND") {
-            end_time = Some(parse_ical_datetime(&line.split(':').last().unwrap_or(""))?);
+            end_time = Some(parse_ical_time(line)?);
+        } else if let Some(value) = line.strip_prefix("RRULE:") {
+            rrule = Some(value.to_string());
+        } else if let Some(value) = line.strip_prefix("GEO:") {
+            geo = value.split_once(';')
+                .and_then(|(lat, lon)| Some((lat.parse::<f64>().ok()?, lon.parse::<f64>().ok()?)));
         } else if line.contains("VALUE=DATE") {
             is_all_day = true;
         }
@@ -612,7 +1162,12 @@ fn parse_icalendar(data: &str) -> Result<NewEvent, AppError> {
     
     let title = title.ok_or(AppError::ValidationError("Missing SUMMARY".to_string()))?;
     let start_time = start_time.ok_or(AppError::ValidationError("Missing DTSTART".to_string()))?;
-    let end_time = end_time.ok_or(AppError::ValidationError("Missing DTEND".to_string()))?;
+    // An all-day event without DTEND takes up the day it starts on (RFC 5545, 3.6.1)
+    let end_time = match end_time {
+        Some(end_time) => end_time,
+        None if is_all_day => start_time + chrono::Duration::days(1),
+        None => return Err(AppError::ValidationError("Missing DTEND".to_string())),
+    };
     
     Ok(NewEvent {
         title,
@@ -621,9 +1176,56 @@ fn parse_icalendar(data: &str) -> Result<NewEvent, AppError> {
         start_time,
         end_time,
         is_all_day,
+        rrule,
+        geo_lat: geo.map(|(lat, _)| lat),
+        geo_lon: geo.map(|(_, lon)| lon),
+        alarm_minutes,
     })
 }
 
+/// Minutes before the start of a `TRIGGER` line such as `TRIGGER:-PT15M`. Triggers
+/// relative to the end, at an absolute time or after the start aren't supported.
+fn parse_alarm_trigger(line: &str) -> Option<i64> {
+    let (params, value) = line.split_once(':')?;
+    if params.contains("RELATED=END") || params.contains("VALUE=DATE-TIME") {
+        return None;
+    }
+    let before_start = value.strip_prefix('-')?;
+    crate::jscalendar::parse_duration(before_start)
+        .map(|duration| duration.num_minutes())
+        .filter(|minutes| *minutes <= crate::reminders::MAX_ALARM_MINUTES)
+}
+
+/// Times of a DTSTART, DTEND, RECURRENCE-ID or EXDATE line. Local times with a TZID
+/// are read on that zone's clock; floating times and unknown zones are taken as UTC.
+pub(crate) fn parse_ical_times(line: &str) -> Result<Vec<chrono::DateTime<chrono::Utc>>, AppError> {
+    use chrono::TimeZone;
+
+    let (params, values) = line.rsplit_once(':')
+        .ok_or(AppError::ValidationError("Invalid datetime format".to_string()))?;
+    let timezone = params.split(';')
+        .find_map(|param| param.strip_prefix("TZID="))
+        .and_then(|name| name.trim_matches('"').parse::<chrono_tz::Tz>().ok());
+
+    values.split(',').map(|value| {
+        let value = value.trim();
+        match timezone {
+            Some(timezone) if value.len() == 15 => chrono::NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S")
+                .ok()
+                .and_then(|local| timezone.from_local_datetime(&local).earliest())
+                .map(|time| time.with_timezone(&chrono::Utc))
+                .ok_or(AppError::ValidationError("Invalid datetime format".to_string())),
+            _ => parse_ical_datetime(value),
+        }
+    }).collect()
+}
+
+/// The first time of a DTSTART, DTEND or RECURRENCE-ID line
+pub(crate) fn parse_ical_time(line: &str) -> Result<chrono::DateTime<chrono::Utc>, AppError> {
+    parse_ical_times(line)?.into_iter().next()
+        .ok_or(AppError::ValidationError("Invalid datetime format".to_string()))
+}
+
 /// Parse iCalendar datetime format
 #[allow(dead_code)]
 fn parse_ical_datetime(date_str: &str) -> Result<chrono::DateTime<chrono::Utc>, AppError> {
@@ -648,14 +1250,343 @@ fn parse_ical_datetime(date_str: &str) -> Result<chrono::DateTime<chrono::Utc>,
     }
 }
 
-/// Escape XML special characters
-#[allow(dead_code)]
-fn escape_xml(s: &str) -> String {
-    s.replace('&', "&amp;")
-     .replace('<', "&lt;")
-     .replace('>', "&gt;")
-     .replace('"', "&quot;")
-     .replace('\'', "&apos;")
+// ICS import
+
+/// Properties of a component as (uppercased name, value) pairs, skipping those
+/// of nested components
+fn ical_component_properties(data: &str) -> Vec<(String, String)> {
+    let mut properties = Vec::new();
+    let mut depth = 0usize;
+
+    for line in data.lines() {
+        let line = line.trim();
+        let upper = line.to_ascii_uppercase();
+        if upper.starts_with("BEGIN:") {
+            depth += 1;
+            continue;
+        }
+        if upper.starts_with("END:") {
+            depth = depth.saturating_sub(1);
+            continue;
+        }
+        if depth != 1 {
+            continue;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            let name = name.split(';').next().unwrap_or(name).to_ascii_uppercase();
+            properties.push((name, value.to_string()));
+        }
+    }
+
+    properties
+}
+
+/// Parse a VTODO component
+fn parse_ical_task(data: &str) -> Result<NewTask, AppError> {
+    let mut task = NewTask {
+        uid: None,
+        summary: None,
+        description: None,
+        due: None,
+        status: None,
+    };
+
+    for (name, value) in ical_component_properties(data) {
+        match name.as_str() {
+            "UID" => task.uid = Some(value),
+            "SUMMARY" => task.summary = Some(value),
+            "DESCRIPTION" => task.description = Some(value),
+            "DUE" => task.due = Some(parse_ical_datetime(&value)?),
+            "STATUS" => task.status = Some(value.to_ascii_uppercase()),
+            _ => {}
+        }
+    }
+
+    Ok(task)
+}
+
+/// Parse a VJOURNAL component
+fn parse_ical_journal(data: &str) -> Result<NewJournal, AppError> {
+    let mut journal = NewJournal {
+        uid: None,
+        summary: None,
+        description: None,
+        dtstart: None,
+    };
+
+    for (name, value) in ical_component_properties(data) {
+        match name.as_str() {
+            "UID" => journal.uid = Some(value),
+            "SUMMARY" => journal.summary = Some(unescape_ical_text(&value)),
+            "DESCRIPTION" => journal.description = Some(unescape_ical_text(&value)),
+            "DTSTART" => journal.dtstart = Some(parse_ical_datetime(&value)?),
+            _ => {}
+        }
+    }
+
+    Ok(journal)
+}
+
+/// Parse the VJOURNAL of an iCalendar object, as sent in a CalDAV PUT
+pub(crate) fn parse_icalendar_journal(data: &str) -> Result<NewJournal, AppError> {
+    let mut reader = import::ComponentReader::new(data.len());
+    let mut components = reader.push(data.as_bytes())?;
+    components.extend(reader.finish()?);
+
+    let journal = components.into_iter()
+        .find(|component| component.kind == "VJOURNAL")
+        .ok_or(AppError::ValidationError("Missing VJOURNAL".to_string()))?;
+    parse_ical_journal(&journal.data)
+}
+
+/// Undo the escaping of commas, semicolons, backslashes and newlines in a TEXT value
+pub(crate) fn unescape_ical_text(value: &str) -> String {
+    let mut text = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            text.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n' | 'N') => text.push('\n'),
+            Some(escaped) => text.push(escaped),
+            None => text.push('\\'),
+        }
+    }
+    text
+}
+
+/// Check an iCalendar object for what would keep it from syncing, without storing it
+pub async fn validate_ical(body: axum::body::Bytes) -> Json<ical_check::IcalReport> {
+    Json(ical_check::check(&body))
+}
+
+/// Query parameters of an iCalendar import
+#[derive(Debug, Deserialize)]
+pub struct ImportQuery {
+    /// `outlook` rewrites the Outlook quirks of any file, not only of those whose PRODID
+    /// names Microsoft
+    pub compat: Option<String>,
+}
+
+/// Import an iCalendar file into a calendar. Events, tasks and journal entries
+/// go to their respective stores; other component types are reported as skipped.
+/// The upload is parsed as it streams in and stored in batches, so large exports
+/// don't have to fit in memory.
+pub async fn import_calendar_ics(
+    State(service): State<CalendarService>,
+    Extension(user_id): Extension<Uuid>,
+    Path(calendar_id): Path<Uuid>,
+    Query(query): Query<ImportQuery>,
+    body: Body,
+) -> Result<Json<ImportSummary>, AppError> {
+    authz::authorize_calendar(&service, user_id, calendar_id, Access::Edit).await?;
+    let force_outlook = match query.compat.as_deref() {
+        None => false,
+        Some("outlook") => true,
+        Some(_) => return Err(AppError::invalid_field("compat", "Compatibility mode must be outlook")),
+    };
+
+    let mut reader = import::ComponentReader::new(service.quotas().max_ics_size);
+    let mut batch = import::ImportBatch::default();
+    let mut summary = ImportSummary::default();
+    let mut stream = body.into_data_stream();
+
+    // The next chunk is only read once the previous one's batch is stored
+    while let Some(chunk) = stream.next().await {
+        let chunk = chunk.map_err(|e| AppError::ValidationError(format!("Failed to read upload: {}", e)))?;
+        for component in reader.push(&chunk)? {
+            let outlook = force_outlook || reader.prod_id().is_some_and(outlook::is_microsoft);
+            add_import_component(&service, component, outlook, &mut batch, &mut summary);
+            if batch.len() >= import::IMPORT_BATCH_SIZE {
+                flush_import_batch(&service, calendar_id, &mut batch, &mut summary).await;
+            }
+        }
+    }
+    let outlook = force_outlook || reader.prod_id().is_some_and(outlook::is_microsoft);
+    for component in reader.finish()? {
+        add_import_component(&service, component, outlook, &mut batch, &mut summary);
+    }
+    flush_import_batch(&service, calendar_id, &mut batch, &mut summary).await;
+
+    Ok(Json(summary))
+}
+
+/// Parse an imported component into the pending batch, counting it as failed if it
+/// can't be parsed. With `outlook`, events have the Outlook quirks rewritten first.
+fn add_import_component(
+    service: &CalendarService,
+    mut component: import::IcalComponent,
+    outlook: bool,
+    batch: &mut import::ImportBatch,
+    summary: &mut ImportSummary,
+) {
+    if outlook && component.kind == "VEVENT" {
+        let (data, quirks) = outlook::normalize(&component.data);
+        component.data = data;
+        for quirk in quirks {
+            if !summary.quirks.iter().any(|id| id == quirk.id) {
+                summary.quirks.push(quirk.id.to_string());
+            }
+        }
+    }
+
+    let (count, outcome) = match component.kind.as_str() {
+        "VEVENT" => (&mut summary.events, service.check_ics_size(component.size)
+            .and_then(|()| parse_icalendar(&component.data))
+            .and_then(|event| crate::validation::event_times(event.start_time, event.end_time).map(|()| event))
+            .map(|event| batch.events.push(event))),
+        "VTODO" => (&mut summary.tasks, service.check_ics_size(component.size)
+            .and_then(|()| parse_ical_task(&component.data))
+            .map(|task| batch.tasks.push(task))),
+        "VJOURNAL" => (&mut summary.journals, service.check_ics_size(component.size)
+            .and_then(|()| parse_ical_journal(&component.data))
+            .map(|journal| batch.journals.push(journal))),
+        // Time zone definitions describe the other components, they are not content
+        "VTIMEZONE" => return,
+        other => {
+            *summary.skipped.entry(other.to_string()).or_default() += 1;
+            return;
+        }
+    };
+
+    if let Err(e) = outcome {
+        count.failed += 1;
+        if summary.errors.len() < import::MAX_REPORTED_ERRORS {
+            summary.errors.push(format!("{}: {}", component.kind, e));
+        }
+    }
+}
+
+/// Store the pending batch in one transaction and count its components as imported,
+/// or all as failed if it couldn't be stored
+async fn flush_import_batch(
+    service: &CalendarService,
+    calendar_id: Uuid,
+    batch: &mut import::ImportBatch,
+    summary: &mut ImportSummary,
+) {
+    if batch.is_empty() {
+        return;
+    }
+    let batch = std::mem::take(batch);
+
+    match service.import_batch(calendar_id, &batch).await {
+        Ok(()) => {
+            summary.events.imported += batch.events.len();
+            summary.tasks.imported += batch.tasks.len();
+            summary.journals.imported += batch.journals.len();
+            tracing::info!(
+                %calendar_id,
+                events = summary.events.imported,
+                tasks = summary.tasks.imported,
+                journals = summary.journals.imported,
+                "Import batch committed"
+            );
+        }
+        Err(e) => {
+            summary.events.failed += batch.events.len();
+            summary.tasks.failed += batch.tasks.len();
+            summary.journals.failed += batch.journals.len();
+            if summary.errors.len() < import::MAX_REPORTED_ERRORS {
+                summary.errors.push(format!("Batch of {} components: {}", batch.len(), e));
+            }
+        }
+    }
+}
+
+/// Query parameters of a CSV import: the column mapping, and the time zone of times
+/// written without an offset (the user's own by default)
+#[derive(Debug, Deserialize)]
+pub struct CsvImportQuery {
+    #[serde(flatten)]
+    pub mapping: event_csv::CsvMapping,
+    pub timezone: Option<String>,
+}
+
+/// Download a calendar's events as CSV
+pub async fn export_calendar_csv(
+    State(service): State<CalendarService>,
+    Extension(user_id): Extension<Uuid>,
+    Path(calendar_id): Path<Uuid>,
+) -> Result<Response, AppError> {
+    let calendar = authz::authorize_calendar(&service, user_id, calendar_id, Access::View).await?;
+    let events = service.get_events_by_calendar_id(calendar_id).await?;
+    let filename = format!("{}.csv", calendar.name.replace(' ', "_"));
+
+    Ok(Response::builder()
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, event_csv::CONTENT_TYPE)
+        .header(header::CONTENT_DISPOSITION, format!("attachment; filename=\"{}\"", filename))
+        .body(Body::from(event_csv::write_events(&events)?))
+        .unwrap())
+}
+
+/// First step of a CSV import: the file's columns and first rows, with the column
+/// mapping suggested by their names
+pub async fn preview_calendar_csv(
+    State(service): State<CalendarService>,
+    Extension(user_id): Extension<Uuid>,
+    Path(calendar_id): Path<Uuid>,
+    body: axum::body::Bytes,
+) -> Result<Json<event_csv::CsvPreview>, AppError> {
+    authz::authorize_calendar(&service, user_id, calendar_id, Access::Edit).await?;
+    Ok(Json(event_csv::preview(&body)?))
+}
+
+/// Import the events of a CSV file with the column mapping from the query; columns
+/// left out are mapped as in the preview. Rows are stored in batches like an ICS
+/// import, and rows that can't be read are counted as failed with their row number.
+pub async fn import_calendar_csv(
+    State(service): State<CalendarService>,
+    Extension(user_id): Extension<Uuid>,
+    Path(calendar_id): Path<Uuid>,
+    Query(query): Query<CsvImportQuery>,
+    body: axum::body::Bytes,
+) -> Result<Json<ImportSummary>, AppError> {
+    authz::authorize_calendar(&service, user_id, calendar_id, Access::Edit).await?;
+    let timezone = match query.timezone.as_deref() {
+        Some(name) => name.parse::<chrono_tz::Tz>()
+            .map_err(|_| AppError::invalid_field("timezone", format!("Unknown time zone: {}", name)))?,
+        None => service.get_user_settings(user_id).await?.timezone,
+    };
+
+    let mut reader = event_csv::reader(&body);
+    let columns = event_csv::headers(&mut reader)?;
+    let mapping = query.mapping.or(event_csv::CsvMapping::guess(&columns));
+    let rows = event_csv::RowReader::new(&columns, &mapping, timezone)?;
+
+    let mut batch = import::ImportBatch::default();
+    let mut summary = ImportSummary::default();
+    for (index, record) in reader.records().enumerate() {
+        // Row 1 is the header
+        let row = index + 2;
+        match record.map_err(event_csv::read_error).and_then(|record| rows.event(&record)) {
+            Ok(event) => batch.events.push(event),
+            Err(e) => {
+                summary.events.failed += 1;
+                if summary.errors.len() < import::MAX_REPORTED_ERRORS {
+                    summary.errors.push(format!("Row {}: {}", row, e));
+                }
+            }
+        }
+        if batch.len() >= import::IMPORT_BATCH_SIZE {
+            flush_import_batch(&service, calendar_id, &mut batch, &mut summary).await;
+        }
+    }
+    flush_import_batch(&service, calendar_id, &mut batch, &mut summary).await;
+
+    Ok(Json(summary))
+}
+
+/// Import the calendars of a Google Takeout archive (request body) into new calendars
+pub async fn import_google_takeout(
+    State(service): State<CalendarService>,
+    Extension(user_id): Extension<Uuid>,
+    body: axum::body::Bytes,
+) -> Result<Json<crate::takeout::TakeoutSummary>, AppError> {
+    Ok(Json(crate::takeout::import(&service, user_id, &body).await?))
 }
 
 // Public API endpoints (no authentication required)
@@ -683,24 +1614,47 @@ pub async fn get_public_calendar_by_id(
     Ok(Json(calendar))
 }
 
-/// Export calendar as ICS file
+/// Query parameters for calendar export
+#[derive(Debug, Deserialize)]
+pub struct ExportQuery {
+    /// `ics` (default) or `jscalendar`
+    pub format: Option<String>,
+}
+
+/// Export calendar as ICS file, or as a JSCalendar (RFC 8984) group with `?format=jscalendar`
 pub async fn export_calendar_ics(
     State(service): State<CalendarService>,
     Path(calendar_id): Path<Uuid>,
+    Query(query): Query<ExportQuery>,
 ) -> Result<Response, AppError> {
     let calendar = service.get_calendar_by_id(calendar_id).await?
         .ok_or(AppError::NotFoundError("Calendar not found".to_string()))?;
+
+    match query.format.as_deref() {
+        None | Some("ics") => {}
+        Some("jscalendar") => {
+            let group = service.export_calendar_jscalendar(calendar_id).await?;
+            let body = serde_json::to_string_pretty(&group)
+                .map_err(|e| AppError::InternalServerError(format!("Failed to serialize export: {}", e)))?;
+            let filename = format!("{}.json", calendar.name.replace(' ', "_"));
+            return Ok(Response::builder()
+                .status(StatusCode::OK)
+                .header(header::CONTENT_TYPE, crate::jscalendar::CONTENT_TYPE)
+                .header(header::CONTENT_DISPOSITION, format!("attachment; filename=\"{}\"", filename))
+                .body(Body::from(body))
+                .unwrap());
+        }
+        Some(other) => return Err(AppError::ValidationError(format!("Unknown export format: {}", other))),
+    }
     
     // Allow export for public calendars or for owners
-    let ics_content = service.export_calendar_ics(calendar_id).await?;
-    
     let filename = format!("{}.ics", calendar.name.replace(' ', "_"));
     
     Ok(Response::builder()
         .status(StatusCode::OK)
         .header(header::CONTENT_TYPE, "text/calendar; charset=utf-8")
         .header(header::CONTENT_DISPOSITION, format!("attachment; filename=\"{}\"", filename))
-        .body(Body::from(ics_content))
+        .body(streaming::calendar_ics(&service, calendar, false))
         .unwrap())
 }
 
@@ -722,6 +1676,51 @@ pub async fn get_public_calendar_events(
 
 // Search endpoints
 
+/// Default search radius for nearby events
+const DEFAULT_NEARBY_RADIUS_KM: f64 = 10.0;
+
+/// Largest accepted search radius for nearby events
+const MAX_NEARBY_RADIUS_KM: f64 = 500.0;
+
+/// Query parameters for location-based event search
+#[derive(Debug, Deserialize)]
+pub struct NearbyQuery {
+    pub lat: f64,
+    pub lon: f64,
+    pub radius_km: Option<f64>,
+    pub start: Option<chrono::DateTime<chrono::Utc>>,
+    pub end: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+/// Longest span a conflict check may cover, bounding the expansion of recurring events
+const MAX_CONFLICT_WINDOW_DAYS: i64 = 366;
+
+/// Query parameters for a conflict check
+#[derive(Debug, Deserialize)]
+pub struct ConflictQuery {
+    pub start: chrono::DateTime<chrono::Utc>,
+    pub end: chrono::DateTime<chrono::Utc>,
+    /// Event to leave out, typically the one being rescheduled
+    pub exclude: Option<Uuid>,
+}
+
+/// Events in the user's calendars overlapping a time span, recurring ones expanded
+pub async fn find_conflicts(
+    State(service): State<CalendarService>,
+    Extension(user_id): Extension<Uuid>,
+    Query(query): Query<ConflictQuery>,
+) -> Result<Json<Vec<Event>>, AppError> {
+    if query.end <= query.start {
+        return Err(AppError::invalid_field("end", "end must be after start"));
+    }
+    if query.end - query.start > chrono::Duration::days(MAX_CONFLICT_WINDOW_DAYS) {
+        return Err(AppError::invalid_field("end", format!("A conflict check may span at most {} days", MAX_CONFLICT_WINDOW_DAYS)));
+    }
+
+    let conflicts = service.find_conflicts(user_id, query.start, query.end, query.exclude).await?;
+    Ok(Json(conflicts))
+}
+
 /// Search events in user's calendars
 pub async fn search_events(
     State(service): State<CalendarService>,
@@ -732,6 +1731,75 @@ pub async fn search_events(
     Ok(Json(events))
 }
 
+/// Search events of public calendars near a point (defaults: 10 km radius, next 7 days)
+pub async fn search_public_events_nearby(
+    State(service): State<CalendarService>,
+    Query(query): Query<NearbyQuery>,
+) -> Result<Json<Vec<NearbyEvent>>, AppError> {
+    if !(-90.0..=90.0).contains(&query.lat) || !(-180.0..=180.0).contains(&query.lon) {
+        return Err(AppError::ValidationError("Coordinates out of range".to_string()));
+    }
+
+    let radius_km = query.radius_km.unwrap_or(DEFAULT_NEARBY_RADIUS_KM);
+    if radius_km <= 0.0 || radius_km > MAX_NEARBY_RADIUS_KM {
+        return Err(AppError::invalid_field("radius_km", format!("radius_km must be between 0 and {}", MAX_NEARBY_RADIUS_KM)));
+    }
+
+    let start = query.start.unwrap_or_else(chrono::Utc::now);
+    let end = query.end.unwrap_or(start + chrono::Duration::days(7));
+    if end < start {
+        return Err(AppError::invalid_field("end", "end must not be before start"));
+    }
+
+    let events = service.search_public_events_near(query.lat, query.lon, radius_km, start, end).await?;
+    Ok(Json(events))
+}
+
+// Insights endpoints
+
+/// Default and maximum number of weeks covered by a workload forecast
+const DEFAULT_FORECAST_WEEKS: u32 = 4;
+const MAX_FORECAST_WEEKS: u32 = 52;
+
+/// Query parameters for the workload forecast
+#[derive(Debug, Deserialize)]
+pub struct ForecastQuery {
+    pub weeks: Option<u32>,
+    /// Comma-separated calendar ids; defaults to all of the user's calendars
+    pub calendars: Option<String>,
+}
+
+/// Committed hours per week across the selected calendars
+pub async fn get_workload_forecast(
+    State(service): State<CalendarService>,
+    Extension(user_id): Extension<Uuid>,
+    Query(query): Query<ForecastQuery>,
+) -> Result<Json<Vec<ForecastWeek>>, AppError> {
+    let weeks = query.weeks.unwrap_or(DEFAULT_FORECAST_WEEKS);
+    if weeks == 0 || weeks > MAX_FORECAST_WEEKS {
+        return Err(AppError::invalid_field("weeks", format!("weeks must be between 1 and {}", MAX_FORECAST_WEEKS)));
+    }
+
+    let calendar_ids = match query.calendars.as_deref().filter(|c| !c.is_empty()) {
+        Some(ids) => {
+            let mut calendar_ids = Vec::new();
+            for id in ids.split(',') {
+                let calendar_id = Uuid::parse_str(id.trim())?;
+                authz::authorize_calendar(&service, user_id, calendar_id, Access::View).await?;
+                calendar_ids.push(calendar_id);
+            }
+            calendar_ids
+        }
+        None => service.get_calendars_by_user_id(user_id).await?
+            .into_iter()
+            .map(|c| c.id)
+            .collect(),
+    };
+
+    let forecast = service.get_workload_forecast(&calendar_ids, weeks).await?;
+    Ok(Json(forecast))
+}
+
 // QR Code generation endpoints
 
 /// Generate QR code for a calendar
@@ -743,7 +1811,7 @@ pub async fn get_calendar_qr_code(
         .ok_or(AppError::NotFoundError("Calendar not found".to_string()))?;
     
     // Generate URL for the calendar
-    let calendar_url = format!("/api/public/calendars/{}", calendar_id);
+    let calendar_url = service.url(&format!("/api/public/calendars/{}", calendar_id));
     
     // Generate QR code
     let qr_code = generate_qr_code(&calendar_url)?;
@@ -764,7 +1832,7 @@ pub async fn get_event_qr_code(
         .ok_or(AppError::NotFoundError("Event not found".to_string()))?;
     
     // Generate URL for the event
-    let event_url = format!("/api/events/{}", event_id);
+    let event_url = service.url(&format!("/api/events/{}", event_id));
     
     // Generate QR code
     let qr_code = generate_qr_code(&event_url)?;
@@ -1,5 +1,6 @@
 use axum::{
     extract::{Path, State, Extension, Query},
+    response::{IntoResponse, Response},
     Json,
 };
 use serde::{Deserialize, Serialize};
@@ -7,8 +8,13 @@ use uuid::Uuid;
 use crate::models::*;
 use crate::services::CalendarService;
 use crate::error::AppError;
+use crate::maintenance::PruneReport;
+use crate::dav_usage::UnsupportedFeature;
 use crate::middleware::UserRoleExt;
-use bcrypt::verify;
+use crate::authz::{self, Access};
+use crate::lockout::LoginSource;
+use crate::validation::Validate;
+use crate::handlers::ConflictCheckQuery;
 use jsonwebtoken::{encode, Header, EncodingKey};
 use chrono::Utc;
 
@@ -21,26 +27,7 @@ pub struct LoginRequest {
 #[derive(Debug, Serialize)]
 pub struct LoginResponse {
     pub token: String,
-    pub user: UserResponse,
-}
-
-#[derive(Debug, Serialize)]
-pub struct UserResponse {
-    pub id: Uuid,
-    pub email: String,
-    pub name: String,
-    pub role: UserRole,
-}
-
-impl From<User> for UserResponse {
-    fn from(user: User) -> Self {
-        Self {
-            id: user.id,
-            email: user.email,
-            name: user.name,
-            role: user.role,
-        }
-    }
+    pub user: PublicUser,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -57,31 +44,16 @@ pub async fn login(
 ) -> Result<Json<LoginResponse>, AppError> {
     tracing::info!("API login attempt for email: {}", payload.email);
     
-    let user = match service.get_user_by_email(&payload.email).await? {
-        Some(u) => u,
-        None => {
-            tracing::warn!("User not found: {}", payload.email);
-            return Err(AppError::AuthenticationError("Invalid credentials".to_string()));
-        }
-    };
-    
-    tracing::info!("User found: {}, verifying password", user.email);
-
-    match verify(&payload.password, &user.password_hash) {
-        Ok(true) => {
-            tracing::info!("Password verified for user: {}", user.email);
-        }
-        Ok(false) => {
-            tracing::warn!("Invalid password for user: {}", user.email);
-            return Err(AppError::AuthenticationError("Invalid credentials".to_string()));
-        }
+    let user = match service.authenticate(&payload.email, &payload.password, LoginSource::Api).await {
+        Ok(user) => user,
         Err(e) => {
-            tracing::error!("Password verification error for user {}: {:?}", user.email, e);
-            return Err(AppError::PasswordHashError(e));
+            tracing::warn!("API login failed for {}: {}", payload.email, e);
+            return Err(e);
         }
-    }
+    };
+    tracing::info!("Password verified for user: {}", user.email);
 
-    let now = Utc::now().timestamp() as usize;
+    let now = service.now().timestamp() as usize;
     let claims = Claims {
         sub: user.id.to_string(),
         iat: now,
@@ -99,36 +71,48 @@ pub async fn login(
 
     Ok(Json(LoginResponse { 
         token,
-        user: UserResponse::from(user),
+        user: PublicUser::from(user),
     }))
 }
 
 pub async fn register(
     State(service): State<CalendarService>,
     Json(payload): Json<NewUser>,
-) -> Result<Json<UserResponse>, AppError> {
+) -> Result<Json<PublicUser>, AppError> {
+    if !service.registration_open().await? {
+        return Err(AppError::AuthenticationError("Registration is closed".to_string()));
+    }
+    payload.validate()?;
+
     // Check if user already exists
     if service.get_user_by_email(&payload.email).await?.is_some() {
-        return Err(AppError::ValidationError("Email already registered".to_string()));
+        return Err(AppError::invalid_field("email", "Email already registered"));
     }
     
     let user = service.create_user(payload).await?;
-    Ok(Json(UserResponse::from(user)))
+    Ok(Json(PublicUser::from(user)))
 }
 
 #[derive(Debug, Deserialize)]
-#[allow(dead_code)]
 pub struct GetCalendarsParams {
     pub include_events: Option<bool>,
 }
 
+/// Number of upcoming events embedded per calendar when `include_events=true`
+const UPCOMING_EVENTS_PER_CALENDAR: usize = 5;
+
 pub async fn get_user_calendars(
     State(service): State<CalendarService>,
     Extension(user_id): Extension<Uuid>,
-    Query(_params): Query<GetCalendarsParams>,
-) -> Result<Json<Vec<Calendar>>, AppError> {
+    Query(params): Query<GetCalendarsParams>,
+) -> Result<Response, AppError> {
+    if params.include_events.unwrap_or(false) {
+        let calendars = service.get_calendars_with_upcoming_events(user_id, UPCOMING_EVENTS_PER_CALENDAR).await?;
+        return Ok(Json(calendars).into_response());
+    }
+
     let calendars = service.get_calendars_by_user_id(user_id).await?;
-    Ok(Json(calendars))
+    Ok(Json(calendars).into_response())
 }
 
 pub async fn create_calendar(
@@ -136,31 +120,36 @@ pub async fn create_calendar(
     Extension(user_id): Extension<Uuid>,
     Json(payload): Json<NewCalendar>,
 ) -> Result<Json<Calendar>, AppError> {
+    payload.validate()?;
     let calendar = service.create_calendar(user_id, payload).await?;
     Ok(Json(calendar))
 }
 
 #[derive(Debug, Deserialize)]
 pub struct CreateEventRequest {
-    pub calendar_id: Uuid,
+    /// Left out, the event goes to the user's default calendar
+    #[serde(default)]
+    pub calendar_id: Option<Uuid>,
     pub event: NewEvent,
 }
 
 pub async fn create_event(
     State(service): State<CalendarService>,
     Extension(user_id): Extension<Uuid>,
+    Query(query): Query<ConflictCheckQuery>,
     Json(payload): Json<CreateEventRequest>,
-) -> Result<Json<Event>, AppError> {
-    // Validate user owns the calendar
-    let calendar = service.get_calendar_by_id(payload.calendar_id).await?.ok_or(
-        AppError::NotFoundError("Calendar not found".to_string()))?;
-    
-    if calendar.user_id != user_id {
-        return Err(AppError::AuthenticationError("You don't own this calendar".to_string()));
-    }
+) -> Result<Json<CheckedEvent>, AppError> {
+    payload.event.validate()?;
+    let calendar_id = match payload.calendar_id {
+        Some(calendar_id) => calendar_id,
+        None => service.get_default_calendar(user_id).await?
+            .ok_or_else(|| AppError::invalid_field("calendar_id", "Choose a calendar; you have no default calendar"))?
+            .id,
+    };
+    authz::authorize_calendar(&service, user_id, calendar_id, Access::Edit).await?;
     
-    let event = service.create_event(payload.calendar_id, payload.event).await?;
-    Ok(Json(event))
+    let event = service.create_event(calendar_id, payload.event).await?;
+    Ok(Json(query.check(&service, event).await?))
 }
 
 pub async fn get_event(
@@ -168,16 +157,7 @@ pub async fn get_event(
     Extension(user_id): Extension<Uuid>,
     Path(event_id): Path<Uuid>,
 ) -> Result<Json<Event>, AppError> {
-    let event = service.get_event_by_id(event_id).await?.ok_or(
-        AppError::NotFoundError("Event not found".to_string()))?;
-    
-    // Check if user has access to this event
-    let calendar = service.get_calendar_by_id(event.calendar_id).await?.ok_or(
-        AppError::NotFoundError("Calendar not found".to_string()))?;
-    
-    if calendar.user_id != user_id {
-        return Err(AppError::AuthenticationError("You don't have access to this event".to_string()));
-    }
+    let event = authz::authorize_event(&service, user_id, event_id, Access::View).await?;
     
     Ok(Json(event))
 }
@@ -186,18 +166,35 @@ pub async fn get_events(
     State(service): State<CalendarService>,
     Extension(user_id): Extension<Uuid>,
     Path(calendar_id): Path<Uuid>,
-) -> Result<Json<Vec<Event>>, AppError> {
-    let calendar = service.get_calendar_by_id(calendar_id).await?.ok_or(
-        AppError::NotFoundError("Calendar not found".to_string()))?;
-    
-    if calendar.user_id != user_id {
-        return Err(AppError::AuthenticationError("You don't have access to this calendar".to_string()));
-    }
+) -> Result<Json<Vec<ListedEvent>>, AppError> {
+    authz::authorize_calendar(&service, user_id, calendar_id, Access::View).await?;
     
-    let events = service.get_events_by_calendar_id(calendar_id).await?;
+    let events = service.get_listed_events_by_calendar_id(calendar_id).await?;
     Ok(Json(events))
 }
 
+pub async fn get_tasks(
+    State(service): State<CalendarService>,
+    Extension(user_id): Extension<Uuid>,
+    Path(calendar_id): Path<Uuid>,
+) -> Result<Json<Vec<Task>>, AppError> {
+    authz::authorize_calendar(&service, user_id, calendar_id, Access::View).await?;
+    
+    let tasks = service.get_tasks_by_calendar_id(calendar_id).await?;
+    Ok(Json(tasks))
+}
+
+pub async fn get_journals(
+    State(service): State<CalendarService>,
+    Extension(user_id): Extension<Uuid>,
+    Path(calendar_id): Path<Uuid>,
+) -> Result<Json<Vec<Journal>>, AppError> {
+    authz::authorize_calendar(&service, user_id, calendar_id, Access::View).await?;
+    
+    let journals = service.get_journals_by_calendar_id(calendar_id).await?;
+    Ok(Json(journals))
+}
+
 // Admin-only endpoints
 
 /// Get all users (admin only)
@@ -205,13 +202,13 @@ pub async fn admin_get_all_users(
     State(service): State<CalendarService>,
     Extension(_user_id): Extension<Uuid>,
     Extension(role): Extension<UserRoleExt>,
-) -> Result<Json<Vec<UserResponse>>, AppError> {
+) -> Result<Json<Vec<PublicUser>>, AppError> {
     if !role.is_admin() {
         return Err(AppError::AuthenticationError("Admin access required".to_string()));
     }
     
     let users = service.get_all_users().await?;
-    Ok(Json(users.into_iter().map(UserResponse::from).collect()))
+    Ok(Json(users.into_iter().map(PublicUser::from).collect()))
 }
 
 /// Create user with specific role (admin only)
@@ -221,6 +218,8 @@ pub struct AdminCreateUserRequest {
     pub email: String,
     pub password: String,
     pub role: Option<String>,
+    #[serde(default)]
+    pub timezone: Option<String>,
 }
 
 pub async fn admin_create_user(
@@ -228,28 +227,30 @@ pub async fn admin_create_user(
     Extension(_user_id): Extension<Uuid>,
     Extension(role): Extension<UserRoleExt>,
     Json(payload): Json<AdminCreateUserRequest>,
-) -> Result<Json<UserResponse>, AppError> {
+) -> Result<Json<PublicUser>, AppError> {
     if !role.is_admin() {
         return Err(AppError::AuthenticationError("Admin access required".to_string()));
     }
     
     // Check if user already exists
     if service.get_user_by_email(&payload.email).await?.is_some() {
-        return Err(AppError::ValidationError("Email already registered".to_string()));
+        return Err(AppError::invalid_field("email", "Email already registered"));
     }
     
     let new_user = NewUser {
         name: payload.name,
         email: payload.email,
         password: payload.password,
+        timezone: payload.timezone,
     };
+    new_user.validate()?;
     
     let user_role = payload.role
         .map(|r| UserRole::from_str(&r))
         .unwrap_or_default();
     
     let user = service.create_user_with_role(new_user, user_role).await?;
-    Ok(Json(UserResponse::from(user)))
+    Ok(Json(PublicUser::from(user)))
 }
 
 /// Update user role (admin only)
@@ -264,20 +265,20 @@ pub async fn admin_update_user_role(
     Extension(role): Extension<UserRoleExt>,
     Path(target_user_id): Path<Uuid>,
     Json(payload): Json<AdminUpdateRoleRequest>,
-) -> Result<Json<UserResponse>, AppError> {
+) -> Result<Json<PublicUser>, AppError> {
     if !role.is_admin() {
         return Err(AppError::AuthenticationError("Admin access required".to_string()));
     }
     
     let new_role = UserRole::from_str(&payload.role);
     let user = service.update_user_role(target_user_id, new_role).await?;
-    Ok(Json(UserResponse::from(user)))
+    Ok(Json(PublicUser::from(user)))
 }
 
-/// Delete user (admin only)
+/// Delete user with all their data (admin only)
 pub async fn admin_delete_user(
     State(service): State<CalendarService>,
-    Extension(_user_id): Extension<Uuid>,
+    Extension(admin_id): Extension<Uuid>,
     Extension(role): Extension<UserRoleExt>,
     Path(target_user_id): Path<Uuid>,
 ) -> Result<Json<serde_json::Value>, AppError> {
@@ -285,6 +286,98 @@ pub async fn admin_delete_user(
         return Err(AppError::AuthenticationError("Admin access required".to_string()));
     }
     
+    let user = service.get_user_by_id(target_user_id).await?
+        .ok_or(AppError::NotFoundError("User not found".to_string()))?;
     service.delete_user(target_user_id).await?;
+    service.record_audit(admin_id, None, "user_deleted", Some(&user.email)).await?;
     Ok(Json(serde_json::json!({ "success": true, "message": "User deleted" })))
 }
+
+/// Delete everything a user has stored while keeping their account (admin only)
+pub async fn admin_purge_user_data(
+    State(service): State<CalendarService>,
+    Extension(admin_id): Extension<Uuid>,
+    Extension(role): Extension<UserRoleExt>,
+    Path(target_user_id): Path<Uuid>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    if !role.is_admin() {
+        return Err(AppError::AuthenticationError("Admin access required".to_string()));
+    }
+
+    let user = service.get_user_by_id(target_user_id).await?
+        .ok_or(AppError::NotFoundError("User not found".to_string()))?;
+    service.purge_user_data(target_user_id).await?;
+    service.record_audit(admin_id, None, "user_data_purged", Some(&user.email)).await?;
+    Ok(Json(serde_json::json!({ "success": true, "message": "User data purged" })))
+}
+
+#[derive(Debug, Serialize)]
+pub struct ImpersonationResponse {
+    pub token: String,
+    pub expires_at: chrono::DateTime<Utc>,
+    pub user: PublicUser,
+}
+
+/// Issue a short-lived impersonation token for a user (admin only)
+pub async fn admin_impersonate_user(
+    State(service): State<CalendarService>,
+    Extension(admin_id): Extension<Uuid>,
+    Extension(role): Extension<UserRoleExt>,
+    Path(target_user_id): Path<Uuid>,
+) -> Result<Json<ImpersonationResponse>, AppError> {
+    if !role.is_admin() {
+        return Err(AppError::AuthenticationError("Admin access required".to_string()));
+    }
+
+    let (token, expires_at, user) = service.start_impersonation(admin_id, target_user_id).await?;
+    Ok(Json(ImpersonationResponse {
+        token,
+        expires_at,
+        user: PublicUser::from(user),
+    }))
+}
+
+/// Number of audit log entries returned by the admin audit endpoint
+const AUDIT_LOG_LIMIT: i64 = 100;
+
+/// Get the most recent audit log entries (admin only)
+pub async fn admin_get_audit_log(
+    State(service): State<CalendarService>,
+    Extension(_user_id): Extension<Uuid>,
+    Extension(role): Extension<UserRoleExt>,
+) -> Result<Json<Vec<AuditLogEntry>>, AppError> {
+    if !role.is_admin() {
+        return Err(AppError::AuthenticationError("Admin access required".to_string()));
+    }
+
+    let entries = service.get_audit_log(AUDIT_LOG_LIMIT).await?;
+    Ok(Json(entries))
+}
+
+/// Prune expired data right away instead of waiting for the next scheduled run (admin only)
+pub async fn admin_prune_expired_data(
+    State(service): State<CalendarService>,
+    Extension(_user_id): Extension<Uuid>,
+    Extension(role): Extension<UserRoleExt>,
+) -> Result<Json<PruneReport>, AppError> {
+    if !role.is_admin() {
+        return Err(AppError::AuthenticationError("Admin access required".to_string()));
+    }
+
+    let report = service.prune_expired_data(service.now()).await?;
+    Ok(Json(report))
+}
+
+/// Counts of DAV features clients requested but the server doesn't implement (admin
+/// only); empty unless `DAV_USAGE_STATS` is on
+pub async fn admin_get_dav_usage(
+    State(service): State<CalendarService>,
+    Extension(_user_id): Extension<Uuid>,
+    Extension(role): Extension<UserRoleExt>,
+) -> Result<Json<Vec<UnsupportedFeature>>, AppError> {
+    if !role.is_admin() {
+        return Err(AppError::AuthenticationError("Admin access required".to_string()));
+    }
+
+    Ok(Json(service.dav_usage().unsupported_features()))
+}
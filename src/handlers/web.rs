@@ -1,25 +1,33 @@
 use axum::{
-    extract::{Form, Path, Query, State, Extension},
-    http::StatusCode,
+    extract::{Form, Multipart, Path, Query, State, Extension},
+    http::{header, HeaderMap, StatusCode},
     response::{Html, IntoResponse, Redirect, Response},
+    Json,
 };
 use serde::Deserialize;
 use std::collections::HashMap;
 use uuid::Uuid;
-use chrono::{Duration, Utc};
+use chrono::{Duration, Timelike, Utc};
 use dioxus::prelude::*;
 
 use crate::services::CalendarService;
 use crate::error::AppError;
-use crate::models::{NewCalendar, NewEvent, NewShare, NewUser, UpdateCalendar, UpdateEvent, UserRole};
-use crate::middleware::{OptionalUser, UserRoleExt};
+use crate::flash::{Flash, FlashKind, FLASH_COOKIE, FLASH_COOKIE_SECONDS};
+use crate::models::{Event, GroupRole, NewCalendar, NewContact, NewDelegation, NewEvent, NewGroup, NewGroupMember, NewJournal, NewShare, NewShareLink, NewUser, RecurrenceScope, TransferCalendar, UpdateCalendar, UpdateCalendarPreference, UpdateEvent, UpdateNotificationPreference, UpdateOccurrence, User, UserRole};
+use crate::middleware::{self, Impersonation, OptionalUser, UserRoleExt};
+use crate::authz::{self, Access};
+use crate::client_profile::ConnectionProfile;
+use crate::edit_conflicts::ResolveConflict;
+use crate::i18n::Language;
+use crate::lockout::LoginSource;
+use crate::settings::{InstanceSettings, RegistrationPolicy, SmtpSettings, Theme, TimeFormat, UpdateUserSettings, WorkingHours, DEFAULT_SMTP_PORT, THEME_COOKIE};
 use crate::ui::*;
+use crate::validation::Validate;
 
-/// Query parameters for flash messages
+/// Query parameters of the calendars page; `account` switches to a delegator's calendars
 #[derive(Debug, Deserialize)]
-pub struct FlashQuery {
-    pub message: Option<String>,
-    pub flash_type: Option<String>,
+pub struct AccountQuery {
+    pub account: Option<Uuid>,
 }
 
 /// Query parameters for event filtering
@@ -43,6 +51,35 @@ pub struct RegisterForm {
     pub password: String,
     #[serde(rename = "confirm_password")]
     pub confirm_password: String,
+    /// Time zone the browser reports, filled in by the page's script
+    #[serde(default)]
+    pub timezone: Option<String>,
+}
+
+/// First-run setup form data
+#[derive(Debug, Deserialize)]
+pub struct SetupForm {
+    pub name: String,
+    pub email: String,
+    pub password: String,
+    pub confirm_password: String,
+    pub instance_name: String,
+    #[serde(default)]
+    pub base_url: String,
+    pub registration: String,
+    #[serde(default)]
+    pub smtp_host: String,
+    #[serde(default)]
+    pub smtp_port: String,
+    #[serde(default)]
+    pub smtp_username: String,
+    #[serde(default)]
+    pub smtp_password: String,
+    #[serde(default)]
+    pub smtp_from: String,
+    /// Time zone the browser reports, filled in by the page's script
+    #[serde(default)]
+    pub timezone: Option<String>,
 }
 
 /// Calendar form data
@@ -52,6 +89,7 @@ pub struct CalendarFormInput {
     pub description: Option<String>,
     pub color: Option<String>,
     pub is_public: Option<String>,
+    pub default_alarm_minutes: Option<String>,
 }
 
 /// Event form data
@@ -64,6 +102,77 @@ pub struct EventFormInput {
     pub is_all_day: Option<String>,
     pub location: Option<String>,
     pub description: Option<String>,
+    pub rrule: Option<String>,
+    pub geo_lat: Option<String>,
+    pub geo_lon: Option<String>,
+    pub scope: Option<RecurrenceScope>,
+    pub occurrence: Option<String>,
+    /// `updated_at` of the event when the edit form was opened
+    pub base_version: Option<chrono::DateTime<Utc>>,
+}
+
+/// Query parameters of the event dialog
+#[derive(Debug, Deserialize)]
+pub struct EventDialogQuery {
+    /// Start of the new event on the user's clock, as in a `datetime-local` input
+    pub start: Option<String>,
+}
+
+/// Event added from the event dialog, sent as JSON
+#[derive(Debug, Deserialize)]
+pub struct EventDialogInput {
+    pub calendar_id: Uuid,
+    pub title: String,
+    pub location: Option<String>,
+    pub start_time: String,
+    pub end_time: String,
+    #[serde(default)]
+    pub is_all_day: bool,
+}
+
+/// Recurrence scope of an event deletion
+#[derive(Debug, Deserialize)]
+pub struct EventDeleteForm {
+    pub scope: Option<RecurrenceScope>,
+    pub occurrence: Option<String>,
+}
+
+/// Occurrence to detach from a recurring event
+#[derive(Debug, Deserialize)]
+pub struct DetachOccurrenceForm {
+    pub occurrence: Option<String>,
+}
+
+/// Calendar to put a duplicated event in
+#[derive(Debug, Deserialize)]
+pub struct DuplicateEventForm {
+    pub calendar_id: Uuid,
+}
+
+/// Parse an optional coordinate field of the event form (empty means unset)
+fn parse_coordinate(value: Option<&str>) -> Result<Option<f64>, AppError> {
+    match value.map(str::trim).filter(|v| !v.is_empty()) {
+        Some(v) => v.parse().map(Some)
+            .map_err(|_| AppError::ValidationError("Invalid coordinate".to_string())),
+        None => Ok(None),
+    }
+}
+
+/// Parse the reminder field of the calendar form (empty means no reminder)
+fn parse_alarm_minutes(value: Option<&str>) -> Result<Option<i64>, AppError> {
+    match value.map(str::trim).filter(|v| !v.is_empty()) {
+        Some(v) => v.parse().map(Some)
+            .map_err(|_| AppError::invalid_field("default_alarm_minutes", "Invalid reminder")),
+        None => Ok(None),
+    }
+}
+
+/// Parse the occurrence start submitted by the event form, on the user's clock unless the
+/// series is all-day
+fn parse_occurrence(occurrence: Option<&str>, all_day: bool) -> Result<chrono::DateTime<Utc>, AppError> {
+    occurrence
+        .and_then(|o| parse_input_value(o, all_day))
+        .ok_or_else(|| AppError::ValidationError("Invalid occurrence format".to_string()))
 }
 
 /// Share form data
@@ -73,32 +182,209 @@ pub struct ShareFormInput {
     pub permission: String,
 }
 
-// Helper function to render Dioxus component to HTML using dioxus_ssr
-fn render_to_html(element: Element) -> Result<String, AppError> {
-    let content = dioxus_ssr::render_element(element);
-    Ok(format!(r#"<!DOCTYPE html>
-<html lang="en">
-{}
-</html>"#, content))
+/// Calendar transfer form data
+#[derive(Debug, Deserialize)]
+pub struct TransferCalendarForm {
+    pub new_owner_email: String,
+    pub confirm_name: String,
+    pub keep_access: Option<String>,
+}
+
+/// New group form data
+#[derive(Debug, Deserialize)]
+pub struct GroupForm {
+    pub name: String,
+}
+
+/// New contact form data; empty fields are left unset
+#[derive(Debug, Deserialize)]
+pub struct ContactForm {
+    pub name: String,
+    pub email: Option<String>,
+    pub birthday: Option<String>,
+    pub anniversary: Option<String>,
+}
+
+/// New journal entry form data; an empty date leaves the entry undated
+#[derive(Debug, Deserialize)]
+pub struct JournalForm {
+    pub summary: Option<String>,
+    pub date: Option<String>,
+    pub description: Option<String>,
+}
+
+/// New calendar order from the calendars page, as comma-separated calendar ids
+#[derive(Debug, Deserialize)]
+pub struct CalendarOrderForm {
+    pub calendar_ids: String,
+}
+
+/// Calendar visibility form data, hiding or showing the calendar on the dashboard
+#[derive(Debug, Deserialize)]
+pub struct CalendarVisibilityForm {
+    pub hidden: bool,
+}
+
+/// Theme form data, from the settings page or the navbar toggle
+#[derive(Debug, Deserialize)]
+pub struct ThemeForm {
+    pub theme: Theme,
+}
+
+/// Language form data from the settings page; empty follows the browser
+#[derive(Debug, Deserialize)]
+pub struct LanguageForm {
+    pub language: String,
+}
+
+/// Time zone and clock form data from the settings page
+#[derive(Debug, Deserialize)]
+pub struct TimeSettingsForm {
+    pub timezone: String,
+    pub time_format: TimeFormat,
+}
+
+/// Holiday pack form data, adding or removing the pack's calendar
+#[derive(Debug, Deserialize)]
+pub struct HolidayPackForm {
+    pub enabled: bool,
+}
+
+/// Sync log form data, turning the log on or off
+#[derive(Debug, Deserialize)]
+pub struct SyncLogForm {
+    pub enabled: bool,
+}
+
+/// Query of the sync log page
+#[derive(Debug, Deserialize)]
+pub struct SyncLogQuery {
+    /// User agent whose requests are shown; all clients' without it
+    pub client: Option<String>,
+}
+
+/// Group member form data, for adding members and changing their role
+#[derive(Debug, Deserialize)]
+pub struct GroupMemberForm {
+    pub email: Option<String>,
+    pub role: String,
+}
+
+/// Share link form data; an empty expiry makes a link that lasts until revoked
+#[derive(Debug, Deserialize)]
+pub struct ShareLinkFormInput {
+    pub permission: String,
+    pub expires_in_days: Option<String>,
+}
+
+/// Cookie carrying `flash` to the next page
+fn flash_cookie(service: &CalendarService, flash: &Flash) -> Result<String, AppError> {
+    Ok(format!(
+        "{}={}; Path=/; HttpOnly; SameSite=Lax; Max-Age={}",
+        FLASH_COOKIE,
+        service.sign_flash(flash)?,
+        FLASH_COOKIE_SECONDS,
+    ))
+}
+
+/// Redirect to `page` and show `flash` there
+fn redirect_with_flash(service: &CalendarService, page: &str, flash: Flash) -> Result<Response, AppError> {
+    Ok(([(header::SET_COOKIE, flash_cookie(service, &flash)?)], Redirect::to(page)).into_response())
+}
+
+/// Send the user back to the form at `page`, with the message of a field error as a
+/// flash; other errors are passed on
+fn field_error_redirect(service: &CalendarService, page: &str, error: AppError) -> Result<Response, AppError> {
+    match error {
+        AppError::FieldValidationError { message, .. } => redirect_with_flash(service, page, Flash::error(message)),
+        e => Err(e),
+    }
+}
+
+/// Whether a request was sent by htmx and expects a fragment instead of a redirect
+fn is_htmx(headers: &HeaderMap) -> bool {
+    headers.get("HX-Request").is_some_and(|v| v == "true")
+}
+
+/// What the user may do, so pages only offer links and buttons that work. A failed
+/// quota lookup keeps the buttons; creating the calendar is checked again anyway.
+async fn user_capabilities(service: &CalendarService, user: &User) -> Capabilities {
+    Capabilities {
+        is_admin: user.role == UserRole::Admin,
+        can_create_calendar: service.can_create_calendar(user.id).await.unwrap_or(true),
+        oidc_enabled: service.oidc().is_some(),
+        jmap_enabled: cfg!(feature = "jmap"),
+    }
+}
+
+/// Title and explanation shown on the error page for a status
+fn error_page_text(status: StatusCode) -> (String, String) {
+    let key = match status {
+        StatusCode::NOT_FOUND => "not_found",
+        StatusCode::FORBIDDEN => "forbidden",
+        StatusCode::BAD_REQUEST => "bad_request",
+        StatusCode::INSUFFICIENT_STORAGE => "quota_exceeded",
+        StatusCode::TOO_MANY_REQUESTS => "too_many_requests",
+        _ => "internal",
+    };
+    (t(&format!("error.{}.title", key)), t(&format!("error.{}.message", key)))
+}
+
+/// Styled error page for web UI routes, quoting the request ID for bug reports. Denied
+/// access is shown as 403, since signed-out users are redirected to the login before.
+pub fn error_page(status: StatusCode, request_id: Option<String>) -> Response {
+    let status = if status == StatusCode::UNAUTHORIZED { StatusCode::FORBIDDEN } else { status };
+    let (title, message) = error_page_text(status);
+
+    let html = render_to_html(
+        "ErrorPage",
+        rsx! {
+            ErrorPage {
+                status: status.as_u16(),
+                title: title.clone(),
+                message: message,
+                request_id: request_id,
+            }
+        }
+    )
+    // The error page itself failing must not recurse; fall back to plain text
+    .unwrap_or_else(|_| format!("{} {}", status.as_u16(), title));
+
+    (status, Html(html)).into_response()
+}
+
+/// Value of a part of a page that can be left out when loading it fails, so one broken
+/// section doesn't take the whole page down
+fn or_fallback<T: Default>(section: &str, result: Result<T, AppError>) -> T {
+    result.unwrap_or_else(|e| {
+        tracing::warn!(section, error = %e, "Rendering page without section");
+        T::default()
+    })
 }
 
 // ============== Login/Register Pages ==============
 
 /// Show login page
 pub async fn login_page(
+    State(service): State<CalendarService>,
     Extension(user): Extension<OptionalUser>,
-    Query(query): Query<FlashQuery>,
 ) -> Result<Html<String>, AppError> {
     // If already logged in, redirect to dashboard
     if user.0.is_some() {
         return Ok(Html("<script>window.location.href='/web/dashboard';</script>".to_string()));
     }
     
+    if service.needs_setup().await? {
+        return Ok(Html("<script>window.location.href='/setup';</script>".to_string()));
+    }
+    let registration_open = service.registration_open().await?;
+    
     let html = render_to_html(
+        "LoginPage",
         rsx! {
             LoginPage { 
-                flash_message: query.message,
-                flash_type: query.flash_type
+                oidc_enabled: service.oidc().is_some(),
+                registration_open: registration_open
             }
         }
     )?;
@@ -109,34 +395,20 @@ pub async fn login_page(
 /// Handle login form submission
 pub async fn login_handler(
     State(service): State<CalendarService>,
+    headers: HeaderMap,
     Form(form): Form<LoginForm>,
 ) -> Result<Response, AppError> {
     tracing::info!("Login attempt for email: {}", form.email);
     
-    let user = match service.get_user_by_email(&form.email).await? {
-        Some(u) => u,
-        None => {
-            tracing::warn!("User not found: {}", form.email);
-            return Ok(Redirect::to("/web/login?message=Invalid credentials&flash_type=error").into_response());
-        }
-    };
-    
-    tracing::info!("User found: {}", user.email);
-    
-    // Verify password
-    let valid = match bcrypt::verify(&form.password, &user.password_hash) {
-        Ok(v) => v,
-        Err(e) => {
-            tracing::error!("Password verification error: {:?}", e);
-            return Ok(Redirect::to("/web/login?message=Invalid credentials&flash_type=error").into_response());
+    let user = match service.authenticate(&form.email, &form.password, LoginSource::Web).await {
+        Ok(user) => user,
+        Err(AppError::AuthenticationError(message)) => {
+            tracing::warn!("Login failed for {}: {}", form.email, message);
+            return redirect_with_flash(&service, "/web/login", Flash::error(message));
         }
+        Err(e) => return Err(e),
     };
     
-    if !valid {
-        tracing::warn!("Invalid password for user: {}", form.email);
-        return Ok(Redirect::to("/web/login?message=Invalid credentials&flash_type=error").into_response());
-    }
-    
     tracing::info!("Password verified for user: {}", form.email);
     
     // Generate JWT token
@@ -147,7 +419,7 @@ pub async fn login_handler(
     // Set cookie and redirect
     Ok(Response::builder()
         .status(StatusCode::FOUND)
-        .header("Location", "/web/dashboard")
+        .header("Location", after_sign_in(&headers))
         .header("Set-Cookie", format!("auth_token={}; Path=/; HttpOnly; SameSite=Strict", token))
         .body(axum::body::Body::empty())
         .unwrap()
@@ -156,59 +428,145 @@ pub async fn login_handler(
 
 /// Show register page
 pub async fn register_page(
+    State(service): State<CalendarService>,
     Extension(user): Extension<OptionalUser>,
-    Query(query): Query<FlashQuery>,
-) -> Result<Html<String>, AppError> {
+) -> Result<Response, AppError> {
     // If already logged in, redirect to dashboard
     if user.0.is_some() {
-        return Ok(Html("<script>window.location.href='/web/dashboard';</script>".to_string()));
+        return Ok(Html("<script>window.location.href='/web/dashboard';</script>".to_string()).into_response());
+    }
+    
+    if !service.registration_open().await? {
+        return redirect_with_flash(&service, "/web/login", Flash::error("Registration is closed"));
     }
     
     let html = render_to_html(
+        "RegisterPage",
         rsx! {
-            RegisterPage { 
-                flash_message: query.message,
-                flash_type: query.flash_type
-            }
+            RegisterPage {}
         }
     )?;
     
-    Ok(Html(html))
+    Ok(Html(html).into_response())
 }
 
 /// Handle register form submission
 pub async fn register_handler(
     State(service): State<CalendarService>,
+    headers: HeaderMap,
     Form(form): Form<RegisterForm>,
 ) -> Result<Response, AppError> {
+    if !service.registration_open().await? {
+        return redirect_with_flash(&service, "/web/login", Flash::error("Registration is closed"));
+    }
+    
     // Validate passwords match
     if form.password != form.confirm_password {
-        return Ok(Redirect::to("/web/register?message=Passwords do not match&flash_type=error").into_response());
+        return redirect_with_flash(&service, "/web/register", Flash::error("Passwords do not match"));
     }
     
-    // Validate password length
-    if form.password.len() < 6 {
-        return Ok(Redirect::to("/web/register?message=Password must be at least 6 characters&flash_type=error").into_response());
+    let new_user = NewUser {
+        name: form.name,
+        email: form.email,
+        password: form.password,
+        timezone: form.timezone,
+    };
+    if let Err(e) = new_user.validate() {
+        return field_error_redirect(&service, "/web/register", e);
     }
     
     // Check if user already exists
-    if service.get_user_by_email(&form.email).await?.is_some() {
-        return Ok(Redirect::to("/web/register?message=Email already registered&flash_type=error").into_response());
+    if service.get_user_by_email(&new_user.email).await?.is_some() {
+        return redirect_with_flash(&service, "/web/register", Flash::error("Email already registered"));
     }
     
-    // Create user
-    let new_user = NewUser {
+    let user = service.create_user(new_user).await?;
+    
+    // Generate JWT token
+    let token = service.generate_jwt(user.id, &user.role)?;
+    
+    // Set cookie and redirect
+    Ok(Response::builder()
+        .status(StatusCode::FOUND)
+        .header("Location", after_sign_in(&headers))
+        .header("Set-Cookie", format!("auth_token={}; Path=/; HttpOnly; SameSite=Strict", token))
+        .body(axum::body::Body::empty())
+        .unwrap()
+        .into_response())
+}
+
+// ============== First-Run Setup ==============
+
+/// Show the setup wizard while the instance has no accounts
+pub async fn setup_page(
+    State(service): State<CalendarService>,
+) -> Result<Response, AppError> {
+    if !service.needs_setup().await? {
+        return Ok(Redirect::to("/web/login").into_response());
+    }
+    
+    let html = render_to_html(
+        "SetupPage",
+        rsx! {
+            SetupPage {
+                base_url: service.base_url().to_string(),
+            }
+        }
+    )?;
+    
+    Ok(Html(html).into_response())
+}
+
+/// Handle the setup form: store the instance settings, create the admin account and
+/// sign it in
+pub async fn setup_handler(
+    State(service): State<CalendarService>,
+    Form(form): Form<SetupForm>,
+) -> Result<Response, AppError> {
+    if !service.needs_setup().await? {
+        return Ok(Redirect::to("/web/login").into_response());
+    }
+    
+    if form.password != form.confirm_password {
+        return redirect_with_flash(&service, "/setup", Flash::error("Passwords do not match"));
+    }
+    
+    let base_url = form.base_url.trim().trim_end_matches('/').to_string();
+    if !base_url.is_empty() && !base_url.starts_with("https://") && !base_url.starts_with("http://") {
+        return redirect_with_flash(&service, "/setup", Flash::error("Base URL must start with http:// or https://"));
+    }
+    
+    let non_empty = |value: &str| Some(value.trim().to_string()).filter(|v| !v.is_empty());
+    let smtp = non_empty(&form.smtp_host).map(|host| SmtpSettings {
+        host,
+        port: form.smtp_port.trim().parse().unwrap_or(DEFAULT_SMTP_PORT),
+        username: non_empty(&form.smtp_username),
+        password: non_empty(&form.smtp_password),
+        from_address: form.smtp_from.trim().to_string(),
+    });
+    
+    let settings = InstanceSettings {
+        instance_name: non_empty(&form.instance_name).unwrap_or_else(|| InstanceSettings::default().instance_name),
+        base_url,
+        registration: RegistrationPolicy::from_str(&form.registration),
+        smtp,
+    };
+    
+    let admin = NewUser {
         name: form.name,
         email: form.email,
         password: form.password,
+        timezone: form.timezone,
     };
+    if let Err(e) = admin.validate() {
+        return field_error_redirect(&service, "/setup", e);
+    }
     
-    let user = service.create_user(new_user).await?;
+    let user = service.complete_setup(admin, &settings).await?;
+    tracing::info!("Setup completed, admin account created for {}", user.email);
     
-    // Generate JWT token
     let token = service.generate_jwt(user.id, &user.role)?;
     
-    // Set cookie and redirect
     Ok(Response::builder()
         .status(StatusCode::FOUND)
         .header("Location", "/web/dashboard")
@@ -218,12 +576,103 @@ pub async fn register_handler(
         .into_response())
 }
 
+/// Handle switching the theme. It is stored for signed-in users and remembered in a
+/// cookie for the login pages; the browser goes back to the page it came from.
+pub async fn update_theme_handler(
+    State(service): State<CalendarService>,
+    Extension(user): Extension<OptionalUser>,
+    headers: HeaderMap,
+    Form(form): Form<ThemeForm>,
+) -> Result<Response, AppError> {
+    if let Some(user_id) = user.0 {
+        service.update_user_settings(user_id, UpdateUserSettings { theme: Some(form.theme), ..Default::default() }).await?;
+    }
+
+    // Only paths on this server, so the form can't be used to redirect elsewhere
+    let back = headers.get(header::REFERER)
+        .and_then(|referer| referer.to_str().ok())
+        .and_then(|referer| referer.parse::<axum::http::Uri>().ok())
+        .and_then(|uri| uri.path_and_query().map(|p| p.as_str().to_string()))
+        .filter(|path| path.starts_with("/web/"))
+        .unwrap_or_else(|| "/web/settings".to_string());
+
+    Ok(Response::builder()
+        .status(StatusCode::SEE_OTHER)
+        .header("Location", back)
+        .header("Set-Cookie", format!("{}={}; Path=/; Max-Age=31536000; SameSite=Lax", THEME_COOKIE, form.theme.as_str()))
+        .body(axum::body::Body::empty())
+        .unwrap())
+}
+
+/// Handle choosing the language of the web UI and of mails
+pub async fn update_language_handler(
+    State(service): State<CalendarService>,
+    Extension(user): Extension<Uuid>,
+    Form(form): Form<LanguageForm>,
+) -> Result<Response, AppError> {
+    let language = Language::from_code(&form.language);
+    service.update_user_settings(user, UpdateUserSettings { language: Some(language), ..Default::default() }).await?;
+
+    Ok(Redirect::to("/web/settings").into_response())
+}
+
+/// Handle choosing the time zone and clock times are shown and entered in
+pub async fn update_time_settings_handler(
+    State(service): State<CalendarService>,
+    Extension(user): Extension<Uuid>,
+    Form(form): Form<TimeSettingsForm>,
+) -> Result<Response, AppError> {
+    let timezone = form.timezone.parse::<chrono_tz::Tz>()
+        .map_err(|_| AppError::invalid_field("timezone", "Unknown time zone"))?;
+    service.update_user_settings(user, UpdateUserSettings {
+        timezone: Some(timezone),
+        time_format: Some(form.time_format),
+        ..Default::default()
+    }).await?;
+
+    redirect_with_flash(&service, "/web/settings", Flash::success("Date and time settings saved"))
+}
+
+/// Handle choosing the first day of the week and working hours. Working days come as
+/// repeated `work_day` fields, so the form is read as a list of pairs.
+pub async fn update_week_settings_handler(
+    State(service): State<CalendarService>,
+    Extension(user): Extension<Uuid>,
+    Form(fields): Form<Vec<(String, String)>>,
+) -> Result<Response, AppError> {
+    let field = |name: &str| fields.iter().find(|(n, _)| n == name).map(|(_, value)| value.as_str());
+    let week_start = field("week_start")
+        .and_then(|day| day.parse::<chrono::Weekday>().ok())
+        .ok_or_else(|| AppError::invalid_field("week_start", "Unknown weekday"))?;
+    let time = |name: &'static str| field(name)
+        .and_then(|time| chrono::NaiveTime::parse_from_str(time, "%H:%M").ok())
+        .ok_or_else(|| AppError::invalid_field(name, "Time must look like 09:00"));
+    let working_hours = WorkingHours {
+        start: time("work_start")?,
+        end: time("work_end")?,
+        days: fields.iter()
+            .filter(|(name, _)| name == "work_day")
+            .filter_map(|(_, day)| day.parse().ok())
+            .collect(),
+        free_busy: field("free_busy").is_some(),
+    };
+
+    service.update_user_settings(user, UpdateUserSettings {
+        week_start: Some(week_start),
+        working_hours: Some(working_hours),
+        ..Default::default()
+    }).await?;
+
+    redirect_with_flash(&service, "/web/settings", Flash::success("Week settings saved"))
+}
+
 /// Handle logout
 pub async fn logout_handler() -> Response {
     Response::builder()
         .status(StatusCode::FOUND)
         .header("Location", "/web/login")
         .header("Set-Cookie", "auth_token=; Path=/; HttpOnly; SameSite=Strict; Max-Age=0")
+        .header("Set-Cookie", "admin_token=; Path=/; HttpOnly; SameSite=Strict; Max-Age=0")
         .body(axum::body::Body::empty())
         .unwrap()
 }
@@ -240,43 +689,41 @@ pub async fn dashboard_page(
     
     let calendars = service.get_calendars_by_user_id(user).await?;
     let calendar_count = calendars.len();
+    let layout = or_fallback("calendar layout", service.get_calendar_layout(user).await);
+    let calendars: Vec<_> = layout.arrange(calendars)
+        .into_iter()
+        .filter(|c| !layout.is_hidden(c.id))
+        .collect();
     
-    // Get all events and count
-    let mut all_events = Vec::new();
-    for cal in &calendars {
-        let events = service.get_events_by_calendar_id(cal.id).await?;
-        all_events.extend(events);
-    }
+    let event_count: usize = or_fallback("event count", service.count_events_per_calendar(user).await)
+        .values()
+        .sum();
     
-    // Get upcoming events (next 7 days)
-    let now = Utc::now();
-    let week_later = now + Duration::days(7);
-    let upcoming_events: Vec<_> = all_events
-        .iter()
-        .filter(|e| e.start_time >= now && e.start_time <= week_later)
-        .take(10)
-        .cloned()
-        .collect();
+    // Upcoming events (next 7 days), showing recurring events at their next instance
+    let upcoming_events = or_fallback("upcoming events", service.get_upcoming_events(user, Duration::days(7), 10).await);
     
-    let event_count = all_events.len();
+    let share_count = or_fallback("share count", service.count_shares_for_user(user).await);
     
-    // Count shares
-    let mut share_count = 0;
-    for cal in &calendars {
-        let shares = service.get_shares_by_calendar_id(cal.id).await?;
-        share_count += shares.len();
-    }
+    let usage = or_fallback("quota usage", service.get_quota_usage(user).await);
+
+    let conflicted_events = or_fallback("conflicted events", service.get_events_with_edit_conflicts(user).await);
     
+    let capabilities = user_capabilities(&service, &user_model).await;
+
     let html = render_to_html(
+        "DashboardPage",
         rsx! {
             DashboardPage {
                 current_user: user_model,
+                capabilities: capabilities,
                 calendar_count: calendar_count,
                 event_count: event_count,
                 share_count: share_count,
+                usage: usage,
                 calendars: calendars,
                 upcoming_events: upcoming_events,
-                caldav_url: "/".to_string(),
+                conflicted_events: conflicted_events,
+                caldav_url: service.url("/.well-known/caldav"),
             }
         }
     )?;
@@ -290,25 +737,52 @@ pub async fn dashboard_page(
 pub async fn calendars_page(
     State(service): State<CalendarService>,
     Extension(user): Extension<Uuid>,
+    Query(query): Query<AccountQuery>,
 ) -> Result<Html<String>, AppError> {
     let user_model = service.get_user_by_id(user).await?
         .ok_or_else(|| AppError::AuthenticationError("User not found".to_string()))?;
     
-    let calendars = service.get_calendars_by_user_id(user).await?;
+    let delegations = or_fallback("delegations", service.get_delegations(user).await).received;
+    let account = match query.account.filter(|account| *account != user) {
+        Some(owner_id) => Some(
+            delegations.iter()
+                .find(|d| d.owner_id == owner_id)
+                .cloned()
+                .ok_or(AppError::NotFoundError("Account not found".to_string()))?,
+        ),
+        None => None,
+    };
+    let owner_id = account.as_ref().map_or(user, |d| d.owner_id);
+
+    // Delegates see the owner's personal calendars, not those of the owner's groups
+    let calendars: Vec<_> = service.get_calendars_by_user_id(owner_id).await?
+        .into_iter()
+        .filter(|c| account.is_none() || c.group_id.is_none())
+        .collect();
     
-    // Get event counts
-    let mut event_counts = HashMap::new();
-    for cal in &calendars {
-        let events = service.get_events_by_calendar_id(cal.id).await?;
-        event_counts.insert(cal.id, events.len());
-    }
+    // Users arrange their own calendar list; delegates see the owner's order
+    let layout = service.get_calendar_layout(owner_id).await?;
+    let calendars = layout.arrange(calendars);
+    let hidden = match account {
+        Some(_) => Vec::new(),
+        None => calendars.iter().map(|c| c.id).filter(|id| layout.is_hidden(*id)).collect(),
+    };
+    
+    let event_counts = service.count_events_per_calendar(owner_id).await?;
     
+    let capabilities = user_capabilities(&service, &user_model).await;
+
     let html = render_to_html(
+        "CalendarsPage",
         rsx! {
             CalendarsPage {
                 current_user: user_model,
+                capabilities: capabilities,
                 calendars: calendars,
                 event_counts: event_counts,
+                hidden: hidden,
+                delegations: delegations,
+                account: account,
             }
         }
     )?;
@@ -324,10 +798,14 @@ pub async fn new_calendar_page(
     let user_model = service.get_user_by_id(user).await?
         .ok_or_else(|| AppError::AuthenticationError("User not found".to_string()))?;
     
+    let capabilities = user_capabilities(&service, &user_model).await;
+
     let html = render_to_html(
+        "CalendarFormPage",
         rsx! {
             CalendarFormPage {
                 current_user: user_model,
+                capabilities: capabilities,
                 is_edit: false,
                 calendar_id: None,
                 calendar: None,
@@ -349,7 +827,11 @@ pub async fn create_calendar_handler(
         description: form.description,
         color: form.color,
         is_public: form.is_public == Some("on".to_string()),
+        default_alarm_minutes: parse_alarm_minutes(form.default_alarm_minutes.as_deref())?,
     };
+    if let Err(e) = new_calendar.validate() {
+        return field_error_redirect(&service, "/web/calendars/new", e);
+    }
     
     let calendar = service.create_calendar(user, new_calendar).await?;
     
@@ -361,32 +843,36 @@ pub async fn calendar_detail_page(
     State(service): State<CalendarService>,
     Extension(user): Extension<Uuid>,
     Path(calendar_id): Path<Uuid>,
-    Query(query): Query<FlashQuery>,
 ) -> Result<Html<String>, AppError> {
     let user_model = service.get_user_by_id(user).await?
         .ok_or_else(|| AppError::AuthenticationError("User not found".to_string()))?;
     
-    let calendar = service.get_calendar_by_id(calendar_id).await?
-        .ok_or_else(|| AppError::NotFoundError("Calendar not found".to_string()))?;
-    
-    // Check ownership
-    if calendar.user_id != user {
-        return Err(AppError::AuthenticationError("Access denied".to_string()));
-    }
+    let calendar = authz::authorize_calendar(&service, user, calendar_id, Access::View).await?;
     
     let events = service.get_events_by_calendar_id(calendar_id).await?;
-    let shares = service.get_shares_by_calendar_id(calendar_id).await?;
+    let shares = or_fallback("shares", service.get_shares_by_calendar_id(calendar_id).await);
+    // Share links are only shown to those who may hand out access
+    let can_manage_shares = authz::authorize_calendar(&service, user, calendar_id, Access::Admin).await.is_ok();
+    let share_links = if can_manage_shares {
+        or_fallback("share links", service.get_share_links(calendar_id).await)
+    } else {
+        Vec::new()
+    };
     
+    let capabilities = user_capabilities(&service, &user_model).await;
+
     let html = render_to_html(
+        "CalendarDetailPage",
         rsx! {
             CalendarDetailPage {
                 current_user: user_model,
+                capabilities: capabilities,
                 calendar: calendar,
                 events: events,
                 shares: shares,
-                caldav_url: "/".to_string(),
-                flash_message: query.message,
-                flash_type: query.flash_type,
+                share_links: share_links,
+                can_manage_shares: can_manage_shares,
+                caldav_url: service.base_url().to_string(),
             }
         }
     )?;
@@ -403,18 +889,16 @@ pub async fn edit_calendar_page(
     let user_model = service.get_user_by_id(user).await?
         .ok_or_else(|| AppError::AuthenticationError("User not found".to_string()))?;
     
-    let calendar = service.get_calendar_by_id(calendar_id).await?
-        .ok_or_else(|| AppError::NotFoundError("Calendar not found".to_string()))?;
-    
-    // Check ownership
-    if calendar.user_id != user {
-        return Err(AppError::AuthenticationError("Access denied".to_string()));
-    }
+    let calendar = authz::authorize_calendar(&service, user, calendar_id, Access::Admin).await?;
     
+    let capabilities = user_capabilities(&service, &user_model).await;
+
     let html = render_to_html(
+        "CalendarFormPage",
         rsx! {
             CalendarFormPage {
                 current_user: user_model,
+                capabilities: capabilities,
                 is_edit: true,
                 calendar_id: Some(calendar_id),
                 calendar: Some(calendar),
@@ -432,24 +916,25 @@ pub async fn update_calendar_handler(
     Path(calendar_id): Path<Uuid>,
     Form(form): Form<CalendarFormInput>,
 ) -> Result<Response, AppError> {
-    let calendar = service.get_calendar_by_id(calendar_id).await?
-        .ok_or_else(|| AppError::NotFoundError("Calendar not found".to_string()))?;
-    
-    // Check ownership
-    if calendar.user_id != user {
-        return Err(AppError::AuthenticationError("Access denied".to_string()));
-    }
+    authz::authorize_calendar(&service, user, calendar_id, Access::Admin).await?;
     
     let update = UpdateCalendar {
         name: Some(form.name),
-        description: form.description,
-        color: form.color,
+        // Blank fields clear the stored value
+        description: Some(form.description.filter(|d| !d.trim().is_empty())),
+        color: Some(form.color.filter(|c| !c.trim().is_empty())),
         is_public: Some(form.is_public == Some("on".to_string())),
+        sort_order: None,
+        is_default: None,
+        default_alarm_minutes: Some(parse_alarm_minutes(form.default_alarm_minutes.as_deref())?),
     };
+    if let Err(e) = update.validate() {
+        return field_error_redirect(&service, &format!("/web/calendars/{}/edit", calendar_id), e);
+    }
     
     service.update_calendar(calendar_id, update).await?;
     
-    Ok(Redirect::to(&format!("/web/calendars/{}?message=Calendar updated&flash_type=success", calendar_id)).into_response())
+    redirect_with_flash(&service, &format!("/web/calendars/{}", calendar_id), Flash::success("Calendar updated"))
 }
 
 /// Handle delete calendar
@@ -458,62 +943,79 @@ pub async fn delete_calendar_handler(
     Extension(user): Extension<Uuid>,
     Path(calendar_id): Path<Uuid>,
 ) -> Result<Response, AppError> {
-    let calendar = service.get_calendar_by_id(calendar_id).await?
-        .ok_or_else(|| AppError::NotFoundError("Calendar not found".to_string()))?;
-    
-    // Check ownership
-    if calendar.user_id != user {
-        return Err(AppError::AuthenticationError("Access denied".to_string()));
-    }
+    authz::authorize_calendar(&service, user, calendar_id, Access::Admin).await?;
     
     service.delete_calendar(calendar_id).await?;
     
-    Ok(Redirect::to("/web/calendars?message=Calendar deleted&flash_type=success").into_response())
+    redirect_with_flash(&service, "/web/calendars", Flash::success("Calendar deleted"))
 }
 
-// ============== Event Pages ==============
-
-/// Show events list page
-pub async fn events_page(
+/// Handle the "force clients to re-download" button
+pub async fn resync_calendar_handler(
     State(service): State<CalendarService>,
     Extension(user): Extension<Uuid>,
-    Query(query): Query<EventFilterQuery>,
-) -> Result<Html<String>, AppError> {
-    let user_model = service.get_user_by_id(user).await?
-        .ok_or_else(|| AppError::AuthenticationError("User not found".to_string()))?;
-    
-    let calendars = service.get_calendars_by_user_id(user).await?;
-    let calendar_names: HashMap<Uuid, String> = calendars
-        .iter()
-        .map(|c| (c.id, c.name.clone()))
-        .collect();
-    
-    // Get all events from user's calendars
-    let mut all_events = Vec::new();
-    for cal in &calendars {
-        let events = service.get_events_by_calendar_id(cal.id).await?;
-        all_events.extend(events);
+    Path(calendar_id): Path<Uuid>,
+) -> Result<Response, AppError> {
+    authz::authorize_calendar(&service, user, calendar_id, Access::Admin).await?;
+
+    service.resync_calendar(calendar_id).await?;
+
+    redirect_with_flash(&service, &format!("/web/calendars/{}", calendar_id), Flash::success("Clients will re-download this calendar on their next sync"))
+}
+
+/// Handle handing a calendar over to another user
+pub async fn transfer_calendar_handler(
+    State(service): State<CalendarService>,
+    Extension(user): Extension<Uuid>,
+    Path(calendar_id): Path<Uuid>,
+    Form(form): Form<TransferCalendarForm>,
+) -> Result<Response, AppError> {
+    let calendar = authz::authorize_calendar(&service, user, calendar_id, Access::Admin).await?;
+    if calendar.user_id != user {
+        return Err(AppError::AuthenticationError("Only the owner can transfer a calendar".to_string()));
     }
     
-    // Filter by calendar if specified
-    let filtered_events: Vec<_> = if let Some(cal_id) = query.calendar {
-        all_events
-            .iter()
-            .filter(|e| e.calendar_id == cal_id)
-            .cloned()
-            .collect()
-    } else {
-        all_events
+    let keep_access = form.keep_access == Some("on".to_string());
+    let transfer = TransferCalendar {
+        new_owner_email: form.new_owner_email,
+        confirm_name: form.confirm_name,
+        keep_access,
+    };
+    let new_owner = match service.transfer_calendar(calendar_id, transfer).await {
+        Ok(new_owner) => new_owner,
+        Err(AppError::FieldValidationError { message, .. } | AppError::QuotaExceeded(message)) => {
+            return redirect_with_flash(&service, &format!("/web/calendars/{}", calendar_id), Flash::error(message));
+        }
+        Err(e) => return Err(e),
     };
+    service.record_audit(user, None, "calendar_transferred", Some(&format!("{} to {}", calendar_id, new_owner.email))).await?;
+    
+    if keep_access {
+        return redirect_with_flash(&service, &format!("/web/calendars/{}", calendar_id), Flash::success(format!("Calendar handed over to {}", new_owner.name)));
+    }
+    Ok(Redirect::to("/web/calendars").into_response())
+}
+
+// ============== Group Pages ==============
+
+/// Show the groups the user belongs to
+pub async fn groups_page(
+    State(service): State<CalendarService>,
+    Extension(user): Extension<Uuid>,
+) -> Result<Html<String>, AppError> {
+    let user_model = service.get_user_by_id(user).await?
+        .ok_or_else(|| AppError::AuthenticationError("User not found".to_string()))?;
     
+    let capabilities = user_capabilities(&service, &user_model).await;
+    let groups = service.get_groups_for_user(user).await?;
+
     let html = render_to_html(
+        "GroupsPage",
         rsx! {
-            EventsPage {
+            GroupsPage {
                 current_user: user_model,
-                events: filtered_events,
-                calendars: calendars,
-                calendar_names: calendar_names,
-                selected_calendar: query.calendar,
+                capabilities: capabilities,
+                groups: groups,
             }
         }
     )?;
@@ -521,259 +1023,244 @@ pub async fn events_page(
     Ok(Html(html))
 }
 
-/// Show new event form
-pub async fn new_event_page(
+/// Handle group creation
+pub async fn create_group_handler(
+    State(service): State<CalendarService>,
+    Extension(user): Extension<Uuid>,
+    Form(form): Form<GroupForm>,
+) -> Result<Response, AppError> {
+    let group = match service.create_group(user, NewGroup { name: form.name }).await {
+        Ok(group) => group,
+        Err(AppError::FieldValidationError { message, .. }) => {
+            return redirect_with_flash(&service, "/web/groups", Flash::error(message));
+        }
+        Err(e) => return Err(e),
+    };
+    service.record_audit(user, None, "group_created", Some(&format!("{} ({})", group.id, group.name))).await?;
+    
+    Ok(Redirect::to(&format!("/web/groups/{}", group.id)).into_response())
+}
+
+/// List the user's contacts
+pub async fn contacts_page(
     State(service): State<CalendarService>,
     Extension(user): Extension<Uuid>,
-    Query(query): Query<EventFilterQuery>,
 ) -> Result<Html<String>, AppError> {
     let user_model = service.get_user_by_id(user).await?
         .ok_or_else(|| AppError::AuthenticationError("User not found".to_string()))?;
-    
-    let calendars = service.get_calendars_by_user_id(user).await?;
-    
+
+    let capabilities = user_capabilities(&service, &user_model).await;
+    let contacts = service.get_contacts_by_user_id(user).await?;
+
     let html = render_to_html(
+        "ContactsPage",
         rsx! {
-            EventFormPage {
+            ContactsPage {
                 current_user: user_model,
-                is_edit: false,
-                event_id: None,
-                event: None,
-                calendars: calendars,
-                selected_calendar_id: query.calendar,
+                capabilities: capabilities,
+                contacts: contacts,
             }
         }
     )?;
-    
+
     Ok(Html(html))
 }
 
-/// Handle new event form submission
-pub async fn create_event_handler(
+/// Show the guided import from Google Calendar
+pub async fn import_google_page(
     State(service): State<CalendarService>,
     Extension(user): Extension<Uuid>,
-    Form(form): Form<EventFormInput>,
-) -> Result<Response, AppError> {
-    // Verify calendar ownership
-    let calendar = service.get_calendar_by_id(form.calendar_id).await?
-        .ok_or_else(|| AppError::NotFoundError("Calendar not found".to_string()))?;
-    
-    if calendar.user_id != user {
-        return Err(AppError::AuthenticationError("Access denied".to_string()));
-    }
-    
-    // Parse datetime
-    let start_time = chrono::NaiveDateTime::parse_from_str(&form.start_time, "%Y-%m-%dT%H:%M")
-        .map(|dt| dt.and_utc())
-        .map_err(|_| AppError::ValidationError("Invalid start time format".to_string()))?;
-    
-    let end_time = chrono::NaiveDateTime::parse_from_str(&form.end_time, "%Y-%m-%dT%H:%M")
-        .map(|dt| dt.and_utc())
-        .map_err(|_| AppError::ValidationError("Invalid end time format".to_string()))?;
-    
-    let new_event = NewEvent {
-        title: form.title,
-        description: form.description,
-        location: form.location,
-        start_time,
-        end_time,
-        is_all_day: form.is_all_day == Some("on".to_string()),
-    };
-    
-    let event = service.create_event(form.calendar_id, new_event).await?;
-    
-    Ok(Redirect::to(&format!("/web/calendars/{}?message=Event created&flash_type=success", event.calendar_id)).into_response())
+) -> Result<Html<String>, AppError> {
+    render_import_google_page(&service, user, None).await
 }
 
-/// Show edit event form
-pub async fn edit_event_page(
+/// Import the Google Takeout archive uploaded on the import page and show what was
+/// imported
+pub async fn import_google_handler(
     State(service): State<CalendarService>,
     Extension(user): Extension<Uuid>,
-    Path(event_id): Path<Uuid>,
+    mut multipart: Multipart,
+) -> Result<Response, AppError> {
+    let outcome = match super::read_attachment_upload(&mut multipart).await {
+        Ok((_, _, archive)) => crate::takeout::import(&service, user, &archive).await,
+        Err(e) => Err(e),
+    };
+    match outcome {
+        Ok(summary) => Ok(render_import_google_page(&service, user, Some(summary)).await?.into_response()),
+        // A file that isn't a Takeout archive is reported on the page
+        Err(AppError::ValidationError(message)) => redirect_with_flash(&service, "/web/import/google", Flash::error(message)),
+        Err(e) => field_error_redirect(&service, "/web/import/google", e),
+    }
+}
+
+async fn render_import_google_page(
+    service: &CalendarService,
+    user: Uuid,
+    summary: Option<crate::takeout::TakeoutSummary>,
 ) -> Result<Html<String>, AppError> {
     let user_model = service.get_user_by_id(user).await?
         .ok_or_else(|| AppError::AuthenticationError("User not found".to_string()))?;
-    
-    let event = service.get_event_by_id(event_id).await?
-        .ok_or_else(|| AppError::NotFoundError("Event not found".to_string()))?;
-    
-    let calendar = service.get_calendar_by_id(event.calendar_id).await?
-        .ok_or_else(|| AppError::NotFoundError("Calendar not found".to_string()))?;
-    
-    // Check ownership
-    if calendar.user_id != user {
-        return Err(AppError::AuthenticationError("Access denied".to_string()));
-    }
-    
-    let calendars = service.get_calendars_by_user_id(user).await?;
-    let selected_calendar_id = event.calendar_id;
-    
+
+    let capabilities = user_capabilities(service, &user_model).await;
+
     let html = render_to_html(
+        "ImportGooglePage",
         rsx! {
-            EventFormPage {
+            ImportGooglePage {
                 current_user: user_model,
-                is_edit: true,
-                event_id: Some(event_id),
-                event: Some(event),
-                calendars: calendars,
-                selected_calendar_id: Some(selected_calendar_id),
+                capabilities: capabilities,
+                summary: summary,
             }
         }
     )?;
-    
+
     Ok(Html(html))
 }
 
-/// Handle edit event form submission
-pub async fn update_event_handler(
+/// Parse an optional `YYYY-MM-DD` date from a form field
+fn parse_form_date(field: &str, value: Option<&str>) -> Result<Option<chrono::NaiveDate>, AppError> {
+    match value.map(str::trim).filter(|v| !v.is_empty()) {
+        Some(v) => chrono::NaiveDate::parse_from_str(v, "%Y-%m-%d").map(Some)
+            .map_err(|_| AppError::invalid_field(field, "Invalid date")),
+        None => Ok(None),
+    }
+}
+
+/// Handle contact creation
+pub async fn create_contact_handler(
     State(service): State<CalendarService>,
     Extension(user): Extension<Uuid>,
-    Path(event_id): Path<Uuid>,
-    Form(form): Form<EventFormInput>,
+    Form(form): Form<ContactForm>,
 ) -> Result<Response, AppError> {
-    let event = service.get_event_by_id(event_id).await?
-        .ok_or_else(|| AppError::NotFoundError("Event not found".to_string()))?;
-    
-    let calendar = service.get_calendar_by_id(event.calendar_id).await?
-        .ok_or_else(|| AppError::NotFoundError("Calendar not found".to_string()))?;
-    
-    // Check ownership
-    if calendar.user_id != user {
-        return Err(AppError::AuthenticationError("Access denied".to_string()));
-    }
-    
-    // Parse datetime
-    let start_time = chrono::NaiveDateTime::parse_from_str(&form.start_time, "%Y-%m-%dT%H:%M")
-        .map(|dt| dt.and_utc())
-        .map_err(|_| AppError::ValidationError("Invalid start time format".to_string()))?;
-    
-    let end_time = chrono::NaiveDateTime::parse_from_str(&form.end_time, "%Y-%m-%dT%H:%M")
-        .map(|dt| dt.and_utc())
-        .map_err(|_| AppError::ValidationError("Invalid end time format".to_string()))?;
-    
-    let update = UpdateEvent {
-        title: Some(form.title),
-        description: form.description,
-        location: form.location,
-        start_time: Some(start_time),
-        end_time: Some(end_time),
-        is_all_day: Some(form.is_all_day == Some("on".to_string())),
+    let new_contact = NewContact {
+        name: form.name,
+        email: form.email,
+        birthday: parse_form_date("birthday", form.birthday.as_deref())?,
+        anniversary: parse_form_date("anniversary", form.anniversary.as_deref())?,
     };
-    
-    service.update_event(event_id, update).await?;
-    
-    Ok(Redirect::to(&format!("/web/calendars/{}?message=Event updated&flash_type=success", form.calendar_id)).into_response())
+    let contact = match service.create_contact(user, new_contact).await {
+        Ok(contact) => contact,
+        Err(AppError::FieldValidationError { message, .. }) => {
+            return redirect_with_flash(&service, "/web/contacts", Flash::error(message));
+        }
+        Err(e) => return Err(e),
+    };
+
+    redirect_with_flash(&service, "/web/contacts", Flash::success(format!("{} added", contact.name)))
 }
 
-/// Handle delete event
-pub async fn delete_event_handler(
+/// Handle contact deletion
+pub async fn delete_contact_handler(
     State(service): State<CalendarService>,
     Extension(user): Extension<Uuid>,
-    Path(event_id): Path<Uuid>,
+    Path(contact_id): Path<Uuid>,
 ) -> Result<Response, AppError> {
-    let event = service.get_event_by_id(event_id).await?
-        .ok_or_else(|| AppError::NotFoundError("Event not found".to_string()))?;
-    
-    let calendar = service.get_calendar_by_id(event.calendar_id).await?
-        .ok_or_else(|| AppError::NotFoundError("Calendar not found".to_string()))?;
-    
-    // Check ownership
-    if calendar.user_id != user {
-        return Err(AppError::AuthenticationError("Access denied".to_string()));
-    }
-    
-    let calendar_id = event.calendar_id;
-    service.delete_event(event_id).await?;
-    
-    Ok(Redirect::to(&format!("/web/calendars/{}?message=Event deleted&flash_type=success", calendar_id)).into_response())
+    service.get_contact_by_id(contact_id).await?
+        .filter(|contact| contact.user_id == user)
+        .ok_or(AppError::NotFoundError("Contact not found".to_string()))?;
+
+    service.delete_contact(contact_id).await?;
+
+    redirect_with_flash(&service, "/web/contacts", Flash::success("Contact deleted"))
 }
 
-// ============== Share Handlers ==============
+/// List the journal entries of a calendar
+pub async fn journal_page(
+    State(service): State<CalendarService>,
+    Extension(user): Extension<Uuid>,
+    Path(calendar_id): Path<Uuid>,
+) -> Result<Html<String>, AppError> {
+    let user_model = service.get_user_by_id(user).await?
+        .ok_or_else(|| AppError::AuthenticationError("User not found".to_string()))?;
 
-/// Handle create share
-pub async fn create_share_handler(
+    let calendar = authz::authorize_calendar(&service, user, calendar_id, Access::View).await?;
+    let can_edit = authz::authorize_calendar(&service, user, calendar_id, Access::Edit).await.is_ok();
+    let journals = service.get_journals_by_calendar_id(calendar_id).await?;
+    let capabilities = user_capabilities(&service, &user_model).await;
+
+    let html = render_to_html(
+        "JournalPage",
+        rsx! {
+            JournalPage {
+                current_user: user_model,
+                capabilities: capabilities,
+                calendar: calendar,
+                journals: journals,
+                can_edit: can_edit,
+            }
+        }
+    )?;
+
+    Ok(Html(html))
+}
+
+/// Handle journal entry creation
+pub async fn create_journal_handler(
     State(service): State<CalendarService>,
     Extension(user): Extension<Uuid>,
     Path(calendar_id): Path<Uuid>,
-    Form(form): Form<ShareFormInput>,
+    Form(form): Form<JournalForm>,
 ) -> Result<Response, AppError> {
-    let calendar = service.get_calendar_by_id(calendar_id).await?
-        .ok_or_else(|| AppError::NotFoundError("Calendar not found".to_string()))?;
-    
-    // Check ownership
-    if calendar.user_id != user {
-        return Err(AppError::AuthenticationError("Access denied".to_string()));
-    }
-    
-    let new_share = NewShare {
-        shared_with_email: form.shared_with_email,
-        permission: form.permission,
+    authz::authorize_calendar(&service, user, calendar_id, Access::Edit).await?;
+
+    let non_empty = |value: Option<String>| value.map(|v| v.trim().to_string()).filter(|v| !v.is_empty());
+    let new_journal = NewJournal {
+        uid: None,
+        summary: non_empty(form.summary),
+        description: non_empty(form.description),
+        dtstart: parse_form_date("date", form.date.as_deref())?
+            .map(|date| date.and_time(chrono::NaiveTime::MIN).and_utc()),
     };
-    
-    service.create_share(calendar_id, user, new_share).await?;
-    
-    Ok(Redirect::to(&format!("/web/calendars/{}?message=Share created&flash_type=success", calendar_id)).into_response())
+    if new_journal.summary.is_none() && new_journal.description.is_none() {
+        return redirect_with_flash(&service, &format!("/web/calendars/{}/journal", calendar_id), Flash::error("Enter a title or a note"));
+    }
+
+    service.create_journal(calendar_id, new_journal).await?;
+
+    redirect_with_flash(&service, &format!("/web/calendars/{}/journal", calendar_id), Flash::success("Entry added"))
 }
 
-/// Handle delete share
-pub async fn delete_share_handler(
+/// Handle journal entry deletion
+pub async fn delete_journal_handler(
     State(service): State<CalendarService>,
     Extension(user): Extension<Uuid>,
-    Path(share_id): Path<Uuid>,
+    Path((calendar_id, journal_id)): Path<(Uuid, Uuid)>,
 ) -> Result<Response, AppError> {
-    // Get share to find calendar_id for redirect
-    let shares = service.get_all_shares().await?;
-    let share = shares.iter()
-        .find(|s| s.id == share_id)
-        .ok_or_else(|| AppError::NotFoundError("Share not found".to_string()))?;
-    
-    let calendar_id = share.calendar_id;
-    
-    // Verify ownership of the calendar
-    let calendar = service.get_calendar_by_id(calendar_id).await?
-        .ok_or_else(|| AppError::NotFoundError("Calendar not found".to_string()))?;
-    
-    if calendar.user_id != user {
-        return Err(AppError::AuthenticationError("Access denied".to_string()));
-    }
-    
-    service.delete_share(share_id).await?;
-    
-    Ok(Redirect::to(&format!("/web/calendars/{}?message=Share removed&flash_type=success", calendar_id)).into_response())
-}
+    authz::authorize_calendar(&service, user, calendar_id, Access::Edit).await?;
+    service.get_journal_by_id(journal_id).await?
+        .filter(|journal| journal.calendar_id == calendar_id)
+        .ok_or(AppError::NotFoundError("Journal entry not found".to_string()))?;
 
-// ============== Admin Pages ==============
+    service.delete_journal(journal_id).await?;
 
-/// Role update form data
-#[derive(Debug, Deserialize)]
-pub struct RoleFormInput {
-    pub role: String,
+    redirect_with_flash(&service, &format!("/web/calendars/{}/journal", calendar_id), Flash::success("Entry deleted"))
 }
 
-/// Show admin page (admin only)
-pub async fn admin_page(
+/// Show a group's members and calendars
+pub async fn group_detail_page(
     State(service): State<CalendarService>,
     Extension(user): Extension<Uuid>,
-    Extension(role): Extension<UserRoleExt>,
-    Query(query): Query<FlashQuery>,
+    Path(group_id): Path<Uuid>,
 ) -> Result<Html<String>, AppError> {
-    // Check if user is admin
-    if role.0 != UserRole::Admin {
-        return Err(AppError::AuthenticationError("Admin access required".to_string()));
-    }
-    
+    let (group, role) = authz::authorize_group(&service, user, group_id, Access::View).await?;
+
     let user_model = service.get_user_by_id(user).await?
         .ok_or_else(|| AppError::AuthenticationError("User not found".to_string()))?;
     
-    let users = service.get_all_users().await?;
-    
+    let capabilities = user_capabilities(&service, &user_model).await;
+    let members = service.get_group_members(group_id).await?;
+    let calendars = or_fallback("group calendars", service.get_group_calendars(group_id).await);
+
     let html = render_to_html(
+        "GroupDetailPage",
         rsx! {
-            AdminPage {
+            GroupDetailPage {
                 current_user: user_model,
-                users: users,
-                flash_message: query.message,
-                flash_type: query.flash_type,
+                capabilities: capabilities,
+                group: group,
+                role: role,
+                members: members,
+                calendars: calendars,
             }
         }
     )?;
@@ -781,25 +1268,1717 @@ pub async fn admin_page(
     Ok(Html(html))
 }
 
-/// Handle role update (admin only)
-pub async fn update_user_role_handler(
+/// Handle group deletion, which also deletes the group's calendars
+pub async fn delete_group_handler(
     State(service): State<CalendarService>,
-    Extension(role): Extension<UserRoleExt>,
-    Path(user_id): Path<Uuid>,
-    Form(form): Form<RoleFormInput>,
+    Extension(user): Extension<Uuid>,
+    Path(group_id): Path<Uuid>,
 ) -> Result<Response, AppError> {
-    // Check if user is admin
-    if role.0 != UserRole::Admin {
-        return Err(AppError::AuthenticationError("Admin access required".to_string()));
-    }
+    authz::authorize_group(&service, user, group_id, Access::Admin).await?;
+
+    service.delete_group(group_id).await?;
+    service.record_audit(user, None, "group_deleted", Some(&group_id.to_string())).await?;
     
-    let new_role = match form.role.as_str() {
-        "admin" => UserRole::Admin,
-        "user" => UserRole::User,
-        _ => return Err(AppError::ValidationError("Invalid role".to_string())),
+    redirect_with_flash(&service, "/web/groups", Flash::success("Group deleted"))
+}
+
+/// Handle adding a member to a group
+pub async fn add_group_member_handler(
+    State(service): State<CalendarService>,
+    Extension(user): Extension<Uuid>,
+    Path(group_id): Path<Uuid>,
+    Form(form): Form<GroupMemberForm>,
+) -> Result<Response, AppError> {
+    authz::authorize_group(&service, user, group_id, Access::Admin).await?;
+
+    let new_member = NewGroupMember {
+        email: form.email.unwrap_or_default(),
+        role: GroupRole::from_str(&form.role),
     };
+    let member = match service.add_group_member(group_id, new_member).await {
+        Ok(member) => member,
+        Err(AppError::FieldValidationError { message, .. }) => {
+            return redirect_with_flash(&service, &format!("/web/groups/{}", group_id), Flash::error(message));
+        }
+        Err(e) => return Err(e),
+    };
+    service.record_audit(user, None, "group_member_added", Some(&format!("{} to {}", member.email, group_id))).await?;
     
-    service.update_user_role(user_id, new_role).await?;
-    
-    Ok(Redirect::to("/web/admin?message=User role updated&flash_type=success").into_response())
+    redirect_with_flash(&service, &format!("/web/groups/{}", group_id), Flash::success(format!("{} added", member.name)))
+}
+
+/// Handle changing a member's role
+pub async fn update_group_member_handler(
+    State(service): State<CalendarService>,
+    Extension(user): Extension<Uuid>,
+    Path((group_id, member_id)): Path<(Uuid, Uuid)>,
+    Form(form): Form<GroupMemberForm>,
+) -> Result<Response, AppError> {
+    authz::authorize_group(&service, user, group_id, Access::Admin).await?;
+
+    match service.update_group_member_role(group_id, member_id, GroupRole::from_str(&form.role)).await {
+        Ok(()) => {}
+        Err(AppError::ValidationError(message)) => {
+            return redirect_with_flash(&service, &format!("/web/groups/{}", group_id), Flash::error(message));
+        }
+        Err(e) => return Err(e),
+    }
+    
+    Ok(Redirect::to(&format!("/web/groups/{}", group_id)).into_response())
+}
+
+/// Handle removing a member; members can remove themselves to leave the group
+pub async fn remove_group_member_handler(
+    State(service): State<CalendarService>,
+    Extension(user): Extension<Uuid>,
+    Path((group_id, member_id)): Path<(Uuid, Uuid)>,
+) -> Result<Response, AppError> {
+    let access = if member_id == user { Access::View } else { Access::Admin };
+    authz::authorize_group(&service, user, group_id, access).await?;
+
+    match service.remove_group_member(group_id, member_id).await {
+        Ok(()) => {}
+        Err(AppError::ValidationError(message)) => {
+            return redirect_with_flash(&service, &format!("/web/groups/{}", group_id), Flash::error(message));
+        }
+        Err(e) => return Err(e),
+    }
+    service.record_audit(user, None, "group_member_removed", Some(&format!("{} from {}", member_id, group_id))).await?;
+    
+    if member_id == user {
+        return redirect_with_flash(&service, "/web/groups", Flash::success("You left the group"));
+    }
+    Ok(Redirect::to(&format!("/web/groups/{}", group_id)).into_response())
+}
+
+/// Handle creating a calendar owned by the group
+pub async fn create_group_calendar_handler(
+    State(service): State<CalendarService>,
+    Extension(user): Extension<Uuid>,
+    Path(group_id): Path<Uuid>,
+    Form(form): Form<GroupForm>,
+) -> Result<Response, AppError> {
+    authz::authorize_group(&service, user, group_id, Access::Admin).await?;
+
+    let new_calendar = NewCalendar {
+        name: form.name,
+        description: None,
+        color: None,
+        is_public: false,
+        default_alarm_minutes: None,
+    };
+    let calendar = match service.create_group_calendar(group_id, user, new_calendar).await {
+        Ok(calendar) => calendar,
+        Err(AppError::QuotaExceeded(message)) => {
+            return redirect_with_flash(&service, &format!("/web/groups/{}", group_id), Flash::error(message));
+        }
+        Err(e) => return Err(e),
+    };
+    
+    Ok(Redirect::to(&format!("/web/calendars/{}", calendar.id)).into_response())
+}
+
+// ============== Event Pages ==============
+
+/// Show events list page
+pub async fn events_page(
+    State(service): State<CalendarService>,
+    Extension(user): Extension<Uuid>,
+    Query(query): Query<EventFilterQuery>,
+) -> Result<Html<String>, AppError> {
+    let user_model = service.get_user_by_id(user).await?
+        .ok_or_else(|| AppError::AuthenticationError("User not found".to_string()))?;
+    
+    let calendars = service.get_calendars_by_user_id(user).await?;
+    let calendar_names: HashMap<Uuid, String> = calendars
+        .iter()
+        .map(|c| (c.id, c.name.clone()))
+        .collect();
+    
+    // Get all events from user's calendars
+    let mut all_events = Vec::new();
+    for cal in &calendars {
+        let events = service.get_events_by_calendar_id(cal.id).await?;
+        all_events.extend(events);
+    }
+    
+    // Filter by calendar if specified
+    let filtered_events: Vec<_> = if let Some(cal_id) = query.calendar {
+        all_events
+            .iter()
+            .filter(|e| e.calendar_id == cal_id)
+            .cloned()
+            .collect()
+    } else {
+        all_events
+    };
+    
+    let capabilities = user_capabilities(&service, &user_model).await;
+
+    let html = render_to_html(
+        "EventsPage",
+        rsx! {
+            EventsPage {
+                current_user: user_model,
+                capabilities: capabilities,
+                events: filtered_events,
+                calendars: calendars,
+                calendar_names: calendar_names,
+                selected_calendar: query.calendar,
+            }
+        }
+    )?;
+    
+    Ok(Html(html))
+}
+
+/// Show new event form
+pub async fn new_event_page(
+    State(service): State<CalendarService>,
+    Extension(user): Extension<Uuid>,
+    Query(query): Query<EventFilterQuery>,
+) -> Result<Html<String>, AppError> {
+    let user_model = service.get_user_by_id(user).await?
+        .ok_or_else(|| AppError::AuthenticationError("User not found".to_string()))?;
+    
+    // Generated calendars take no events
+    let calendars: Vec<_> = service.get_calendars_by_user_id(user).await?
+        .into_iter()
+        .filter(|c| !c.is_generated())
+        .collect();
+    let selected_calendar_id = query.calendar.or_else(|| calendars.iter().find(|c| c.is_default).map(|c| c.id));
+    
+    let capabilities = user_capabilities(&service, &user_model).await;
+
+    let html = render_to_html(
+        "EventFormPage",
+        rsx! {
+            EventFormPage {
+                current_user: user_model,
+                capabilities: capabilities,
+                is_edit: false,
+                event_id: None,
+                event: None,
+                attachments: Vec::new(),
+                calendars: calendars,
+                selected_calendar_id: selected_calendar_id,
+                conflicts: 0,
+            }
+        }
+    )?;
+    
+    Ok(Html(html))
+}
+
+/// Handle new event form submission
+pub async fn create_event_handler(
+    State(service): State<CalendarService>,
+    Extension(user): Extension<Uuid>,
+    Form(form): Form<EventFormInput>,
+) -> Result<Response, AppError> {
+    authz::authorize_calendar(&service, user, form.calendar_id, Access::Edit).await?;
+    
+    // Times are entered on the user's clock, all-day dates as they are
+    let is_all_day = form.is_all_day == Some("on".to_string());
+    let start_time = parse_input_value(&form.start_time, is_all_day)
+        .ok_or_else(|| AppError::ValidationError("Invalid start time format".to_string()))?;
+    
+    let end_time = parse_end_input_value(&form.end_time, is_all_day)
+        .ok_or_else(|| AppError::ValidationError("Invalid end time format".to_string()))?;
+    
+    let new_event = NewEvent {
+        title: form.title,
+        description: form.description,
+        location: form.location,
+        start_time,
+        end_time,
+        is_all_day,
+        rrule: form.rrule.filter(|r| !r.is_empty()),
+        geo_lat: parse_coordinate(form.geo_lat.as_deref())?,
+        geo_lon: parse_coordinate(form.geo_lon.as_deref())?,
+        alarm_minutes: None,
+    };
+    if let Err(e) = new_event.validate() {
+        return field_error_redirect(&service, "/web/events/new", e);
+    }
+    
+    let event = service.create_event(form.calendar_id, new_event).await?;
+    let flash = event_saved_flash(&service, &event, "Event created").await?;
+    
+    redirect_with_flash(&service, &format!("/web/calendars/{}", event.calendar_id), flash)
+}
+
+/// Flash for the page shown after storing `event`: `done`, or a warning naming the events
+/// it overlaps in its calendar. Overlaps are allowed; the user decides whether they mind.
+async fn event_saved_flash(service: &CalendarService, event: &Event, done: &str) -> Result<Flash, AppError> {
+    let mut titles: Vec<String> = Vec::new();
+    for conflict in service.find_event_conflicts(event).await? {
+        if !titles.contains(&conflict.title) {
+            titles.push(conflict.title);
+        }
+    }
+    Ok(if titles.is_empty() {
+        Flash::success(done)
+    } else {
+        Flash::warning(format!("{}, but it overlaps {}", done, titles.join(", ")))
+    })
+}
+
+/// Dialog for adding an event from the dashboard or schedule grid, as an htmx fragment.
+/// The event starts at `start`, or at the next full hour, and lasts an hour.
+pub async fn event_dialog(
+    State(service): State<CalendarService>,
+    Extension(user): Extension<Uuid>,
+    Query(query): Query<EventDialogQuery>,
+) -> Result<Html<String>, AppError> {
+    // Generated calendars take no events
+    let calendars: Vec<_> = service.get_calendars_by_user_id(user).await?
+        .into_iter()
+        .filter(|c| !c.is_generated())
+        .collect();
+    let selected_calendar_id = calendars.iter().find(|c| c.is_default).map(|c| c.id);
+
+    let start = match query.start.as_deref() {
+        Some(start) => chrono::NaiveDateTime::parse_from_str(start, "%Y-%m-%dT%H:%M")
+            .map_err(|_| AppError::invalid_field("start", "Start is not a valid time"))?,
+        None => {
+            let now = user_settings().local_time(service.now()).naive_local();
+            now.date().and_hms_opt(now.hour(), 0, 0).unwrap_or(now) + Duration::hours(1)
+        }
+    };
+    let end = start + Duration::hours(1);
+
+    render_fragment(
+        "EventDialog",
+        rsx! {
+            EventDialog {
+                calendars: calendars,
+                selected_calendar_id: selected_calendar_id,
+                start_time: start.format("%Y-%m-%dT%H:%M").to_string(),
+                end_time: end.format("%Y-%m-%dT%H:%M").to_string(),
+            }
+        }
+    )
+}
+
+/// Add an event sent by the event dialog. The dialog checks the same rules in the
+/// browser; a broken rule is answered with the field it concerns.
+pub async fn create_event_from_dialog(
+    State(service): State<CalendarService>,
+    Extension(user): Extension<Uuid>,
+    Json(input): Json<EventDialogInput>,
+) -> Result<Response, AppError> {
+    authz::authorize_calendar(&service, user, input.calendar_id, Access::Edit).await?;
+
+    // Times are entered on the user's clock, all-day dates as they are
+    let start_time = parse_input_value(&input.start_time, input.is_all_day)
+        .ok_or_else(|| AppError::invalid_field("start_time", "Start is not a valid time"))?;
+    let end_time = parse_end_input_value(&input.end_time, input.is_all_day)
+        .ok_or_else(|| AppError::invalid_field("end_time", "End is not a valid time"))?;
+    if end_time <= start_time {
+        return Err(AppError::invalid_field("end_time", "End must be after the start"));
+    }
+
+    let new_event = NewEvent {
+        title: input.title.trim().to_string(),
+        description: None,
+        location: input.location
+            .map(|location| location.trim().to_string())
+            .filter(|location| !location.is_empty()),
+        start_time,
+        end_time,
+        is_all_day: input.is_all_day,
+        rrule: None,
+        geo_lat: None,
+        geo_lon: None,
+        alarm_minutes: None,
+    };
+    new_event.validate()?;
+    let event = service.create_event(input.calendar_id, new_event).await?;
+
+    // The dialog reloads the page, which shows any overlap warning
+    let flash = event_saved_flash(&service, &event, "Event created").await?;
+    if flash.kind == FlashKind::Warning {
+        return Ok((StatusCode::CREATED, [(header::SET_COOKIE, flash_cookie(&service, &flash)?)], Json(event)).into_response());
+    }
+    Ok((StatusCode::CREATED, Json(event)).into_response())
+}
+
+/// Show edit event form
+pub async fn edit_event_page(
+    State(service): State<CalendarService>,
+    Extension(user): Extension<Uuid>,
+    Path(event_id): Path<Uuid>,
+) -> Result<Html<String>, AppError> {
+    let user_model = service.get_user_by_id(user).await?
+        .ok_or_else(|| AppError::AuthenticationError("User not found".to_string()))?;
+    
+    let event = authz::authorize_event(&service, user, event_id, Access::Edit).await?;
+    
+    let mut calendars: Vec<_> = service.get_calendars_by_user_id(user).await?
+        .into_iter()
+        .filter(|c| !c.is_generated())
+        .collect();
+    // Events in shared calendars stay selectable so saving doesn't move them by accident
+    if !calendars.iter().any(|c| c.id == event.calendar_id)
+        && let Some(calendar) = service.get_calendar_by_id(event.calendar_id).await?
+    {
+        calendars.insert(0, calendar);
+    }
+    let attachments = service.get_attachments_by_event_id(event_id).await?;
+    let conflicts = service.get_edit_conflicts(event_id).await?.len();
+    let selected_calendar_id = event.calendar_id;
+    
+    let capabilities = user_capabilities(&service, &user_model).await;
+
+    let html = render_to_html(
+        "EventFormPage",
+        rsx! {
+            EventFormPage {
+                current_user: user_model,
+                capabilities: capabilities,
+                is_edit: true,
+                event_id: Some(event_id),
+                event: Some(event),
+                attachments: attachments,
+                calendars: calendars,
+                selected_calendar_id: Some(selected_calendar_id),
+                conflicts: conflicts,
+            }
+        }
+    )?;
+    
+    Ok(Html(html))
+}
+
+/// Handle edit event form submission
+pub async fn update_event_handler(
+    State(service): State<CalendarService>,
+    Extension(user): Extension<Uuid>,
+    Path(event_id): Path<Uuid>,
+    Form(form): Form<EventFormInput>,
+) -> Result<Response, AppError> {
+    let event = authz::authorize_event(&service, user, event_id, Access::Edit).await?;
+    
+    // Times are entered on the user's clock, all-day dates as they are
+    let is_all_day = form.is_all_day == Some("on".to_string());
+    let start_time = parse_input_value(&form.start_time, is_all_day)
+        .ok_or_else(|| AppError::ValidationError("Invalid start time format".to_string()))?;
+    
+    let end_time = parse_end_input_value(&form.end_time, is_all_day)
+        .ok_or_else(|| AppError::ValidationError("Invalid end time format".to_string()))?;
+    
+    let update = UpdateEvent {
+        title: Some(form.title),
+        // Blank fields clear the stored value
+        description: Some(form.description.filter(|d| !d.trim().is_empty())),
+        location: Some(form.location.filter(|l| !l.trim().is_empty())),
+        start_time: Some(start_time),
+        end_time: Some(end_time),
+        is_all_day: Some(is_all_day),
+        rrule: form.rrule,
+        geo_lat: Some(parse_coordinate(form.geo_lat.as_deref())?),
+        geo_lon: Some(parse_coordinate(form.geo_lon.as_deref())?),
+        alarm_minutes: None,
+    };
+    if let Err(e) = update.validate() {
+        return field_error_redirect(&service, &format!("/web/events/{}/edit", event_id), e);
+    }
+
+    // Saving a form opened before the event changed elsewhere would undo that change,
+    // so the edit is kept as a conflict copy to compare and resolve
+    let whole_event = !(event.is_recurring() && form.scope == Some(RecurrenceScope::This));
+    if whole_event && form.base_version.is_some_and(|base| base != event.updated_at) {
+        let version = NewEvent {
+            title: update.title.clone().unwrap_or_default(),
+            description: update.description.clone().flatten(),
+            location: update.location.clone().flatten(),
+            start_time,
+            end_time,
+            is_all_day,
+            rrule: update.rrule.clone().or(event.rrule.clone()).filter(|rrule| !rrule.trim().is_empty()),
+            geo_lat: update.geo_lat.flatten(),
+            geo_lon: update.geo_lon.flatten(),
+            alarm_minutes: event.alarm_minutes,
+        };
+        service.store_edit_conflict(event_id, None, version).await?;
+        return redirect_with_flash(
+            &service,
+            &format!("/web/events/{}/edit-conflicts", event_id),
+            Flash::error("The event was changed while you were editing it, so your changes were kept as a conflict"),
+        );
+    }
+    
+    let saved = if event.is_recurring() && form.scope == Some(RecurrenceScope::This) {
+        // The form shows the series times, so apply the edit relative to the chosen occurrence
+        let recurrence_id = parse_occurrence(form.occurrence.as_deref(), event.is_all_day)?;
+        let occurrence_start = recurrence_id + (start_time - event.start_time);
+        let occurrence = UpdateOccurrence {
+            recurrence_id,
+            changes: UpdateEvent {
+                start_time: Some(occurrence_start),
+                end_time: Some(occurrence_start + (end_time - start_time)),
+                ..update
+            },
+        };
+        service.update_event_occurrence(event_id, occurrence).await?
+    } else {
+        let updated = service.update_event(event_id, update).await?;
+        
+        // Picking another calendar moves the event (and its whole series) there
+        if form.calendar_id != event.calendar_id && !event.is_exception() {
+            authz::authorize_calendar(&service, user, form.calendar_id, Access::Edit).await?;
+            service.move_event(event_id, form.calendar_id).await?
+        } else {
+            updated
+        }
+    };
+    let flash = event_saved_flash(&service, &saved, "Event updated").await?;
+    
+    redirect_with_flash(&service, &format!("/web/calendars/{}", saved.calendar_id), flash)
+}
+
+/// Handle delete event
+pub async fn delete_event_handler(
+    State(service): State<CalendarService>,
+    Extension(user): Extension<Uuid>,
+    Path(event_id): Path<Uuid>,
+    headers: HeaderMap,
+    Form(form): Form<EventDeleteForm>,
+) -> Result<Response, AppError> {
+    let event = authz::authorize_event(&service, user, event_id, Access::Edit).await?;
+    
+    let calendar_id = event.calendar_id;
+    if event.is_recurring() && form.scope == Some(RecurrenceScope::This) {
+        service.delete_event_occurrence(event_id, parse_occurrence(form.occurrence.as_deref(), event.is_all_day)?).await?;
+    } else {
+        service.delete_event(event_id).await?;
+    }
+    
+    // htmx swaps the item out and lets event lists on the page reload themselves
+    if is_htmx(&headers) {
+        return Ok(([("HX-Trigger", "events-changed")], Html(String::new())).into_response());
+    }
+    
+    redirect_with_flash(&service, &format!("/web/calendars/{}", calendar_id), Flash::success("Event deleted"))
+}
+
+/// Handle detaching a single occurrence into a standalone event
+pub async fn detach_occurrence_handler(
+    State(service): State<CalendarService>,
+    Extension(user): Extension<Uuid>,
+    Path(event_id): Path<Uuid>,
+    Form(form): Form<DetachOccurrenceForm>,
+) -> Result<Response, AppError> {
+    let event = authz::authorize_event(&service, user, event_id, Access::Edit).await?;
+    
+    let recurrence_id = parse_occurrence(form.occurrence.as_deref(), event.is_all_day)?;
+    let detached = service.detach_event_occurrence(event.parent_id.unwrap_or(event.id), recurrence_id).await?;
+    
+    redirect_with_flash(&service, &format!("/web/calendars/{}", detached.calendar_id), Flash::success("Occurrence detached from the series"))
+}
+
+/// Handle duplicating an event into the chosen calendar, then open the copy for editing
+pub async fn duplicate_event_handler(
+    State(service): State<CalendarService>,
+    Extension(user): Extension<Uuid>,
+    Path(event_id): Path<Uuid>,
+    Form(form): Form<DuplicateEventForm>,
+) -> Result<Response, AppError> {
+    authz::authorize_event(&service, user, event_id, Access::View).await?;
+    authz::authorize_calendar(&service, user, form.calendar_id, Access::Edit).await?;
+    
+    let copy = service.copy_event(event_id, form.calendar_id).await?;
+    
+    Ok(Redirect::to(&format!("/web/events/{}/edit", copy.id)).into_response())
+}
+
+// ============== Event History ==============
+
+/// Show the earlier versions of an event, deleted or not, with what each change touched
+pub async fn event_history_page(
+    State(service): State<CalendarService>,
+    Extension(user): Extension<Uuid>,
+    Path(event_id): Path<Uuid>,
+) -> Result<Html<String>, AppError> {
+    let user_model = service.get_user_by_id(user).await?
+        .ok_or_else(|| AppError::AuthenticationError("User not found".to_string()))?;
+
+    let calendar = authz::authorize_event_history(&service, user, event_id, Access::View).await?;
+    let can_restore = authz::can_edit(&service, user, &calendar).await?;
+    let event = service.get_event_by_id(event_id).await?;
+    let versions = service.get_event_versions(event_id).await?;
+    let title = event.as_ref()
+        .or(versions.first().map(|version| &version.event))
+        .map(|event| event.title.clone())
+        .unwrap_or_default();
+    let entries = crate::event_history::timeline(versions, event.as_ref());
+
+    let capabilities = user_capabilities(&service, &user_model).await;
+
+    let html = render_to_html(
+        "EventHistoryPage",
+        rsx! {
+            EventHistoryPage {
+                current_user: user_model,
+                capabilities: capabilities,
+                event_id: event_id,
+                title: title,
+                calendar: calendar,
+                deleted: event.is_none(),
+                can_restore: can_restore,
+                entries: entries,
+            }
+        }
+    )?;
+
+    Ok(Html(html))
+}
+
+/// Handle restoring an earlier version of an event, or a deleted event
+pub async fn restore_event_version_handler(
+    State(service): State<CalendarService>,
+    Extension(user): Extension<Uuid>,
+    Path((event_id, version_id)): Path<(Uuid, i64)>,
+) -> Result<Response, AppError> {
+    authz::authorize_event_history(&service, user, event_id, Access::Edit).await?;
+
+    let history = format!("/web/events/{}/history", event_id);
+    match service.restore_event_version(event_id, version_id).await {
+        Ok(_) => redirect_with_flash(&service, &history, Flash::success("Event restored")),
+        Err(AppError::ValidationError(message) | AppError::QuotaExceeded(message)) => {
+            redirect_with_flash(&service, &history, Flash::error(message))
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// Show the deleted events of a calendar that can still be restored
+pub async fn deleted_events_page(
+    State(service): State<CalendarService>,
+    Extension(user): Extension<Uuid>,
+    Path(calendar_id): Path<Uuid>,
+) -> Result<Html<String>, AppError> {
+    let user_model = service.get_user_by_id(user).await?
+        .ok_or_else(|| AppError::AuthenticationError("User not found".to_string()))?;
+
+    let calendar = authz::authorize_calendar(&service, user, calendar_id, Access::View).await?;
+    let can_restore = authz::can_edit(&service, user, &calendar).await?;
+    let deleted = service.get_deleted_events(calendar_id).await?;
+
+    let capabilities = user_capabilities(&service, &user_model).await;
+
+    let html = render_to_html(
+        "DeletedEventsPage",
+        rsx! {
+            DeletedEventsPage {
+                current_user: user_model,
+                capabilities: capabilities,
+                calendar: calendar,
+                can_restore: can_restore,
+                deleted: deleted,
+            }
+        }
+    )?;
+
+    Ok(Html(html))
+}
+
+// ============== Event Conflicts ==============
+
+/// Show the conflict copies of an event next to the stored event
+pub async fn edit_conflicts_page(
+    State(service): State<CalendarService>,
+    Extension(user): Extension<Uuid>,
+    Path(event_id): Path<Uuid>,
+) -> Result<Html<String>, AppError> {
+    let user_model = service.get_user_by_id(user).await?
+        .ok_or_else(|| AppError::AuthenticationError("User not found".to_string()))?;
+
+    let event = authz::authorize_event(&service, user, event_id, Access::View).await?;
+    let calendar = authz::authorize_calendar(&service, user, event.calendar_id, Access::View).await?;
+    let can_resolve = authz::can_edit(&service, user, &calendar).await?;
+    let conflicts = service.get_edit_conflicts(event_id).await?;
+    let entries = crate::edit_conflicts::compare(&event, conflicts);
+
+    let capabilities = user_capabilities(&service, &user_model).await;
+
+    let html = render_to_html(
+        "EditConflictsPage",
+        rsx! {
+            EditConflictsPage {
+                current_user: user_model,
+                capabilities: capabilities,
+                event: event,
+                calendar: calendar,
+                can_resolve: can_resolve,
+                entries: entries,
+            }
+        }
+    )?;
+
+    Ok(Html(html))
+}
+
+/// Handle resolving a conflict, back to the event once none are left
+pub async fn resolve_edit_conflict_handler(
+    State(service): State<CalendarService>,
+    Extension(user): Extension<Uuid>,
+    Path((event_id, conflict_id)): Path<(Uuid, Uuid)>,
+    Form(form): Form<ResolveConflict>,
+) -> Result<Response, AppError> {
+    authz::authorize_event(&service, user, event_id, Access::Edit).await?;
+
+    let flash = match service.resolve_edit_conflict(event_id, conflict_id, form.keep).await {
+        Ok(_) => Flash::success("Conflict resolved"),
+        Err(AppError::ValidationError(message)) => Flash::error(message),
+        Err(e) => return Err(e),
+    };
+    let target = if service.get_edit_conflicts(event_id).await?.is_empty() {
+        format!("/web/events/{}/edit", event_id)
+    } else {
+        format!("/web/events/{}/edit-conflicts", event_id)
+    };
+    redirect_with_flash(&service, &target, flash)
+}
+
+// ============== Insights Page ==============
+
+/// Query parameters for the schedule grid
+#[derive(Debug, Deserialize)]
+pub struct ScheduleQuery {
+    #[serde(default)]
+    pub view: ScheduleView,
+    /// Day the grid shows; today when left out
+    pub date: Option<chrono::NaiveDate>,
+}
+
+/// Show the month or week grid of the user's visible calendars
+pub async fn schedule_page(
+    State(service): State<CalendarService>,
+    Extension(user): Extension<Uuid>,
+    Query(query): Query<ScheduleQuery>,
+) -> Result<Html<String>, AppError> {
+    let user_model = service.get_user_by_id(user).await?
+        .ok_or_else(|| AppError::AuthenticationError("User not found".to_string()))?;
+
+    let settings = user_settings();
+    let today = settings.local_time(service.now()).date_naive();
+    let date = query.date.unwrap_or(today);
+    let (first_day, day_count) = query.view.days(date, settings.week_start);
+    let start = start_of_day(first_day);
+    let end = start_of_day(first_day + chrono::Days::new(day_count));
+
+    let mut events = Vec::new();
+    for preference in service.get_calendar_preferences(user).await?.into_iter().filter(|p| !p.hidden) {
+        events.extend(service.get_event_instances(preference.calendar_id, start, end).await?);
+    }
+    events.sort_by_key(|event| event.start_time);
+
+    let capabilities = user_capabilities(&service, &user_model).await;
+
+    let html = render_to_html(
+        "SchedulePage",
+        rsx! {
+            SchedulePage {
+                current_user: user_model,
+                capabilities: capabilities,
+                view: query.view,
+                date: date,
+                today: today,
+                events: events,
+            }
+        }
+    )?;
+
+    Ok(Html(html))
+}
+
+/// Query parameters for the insights page
+#[derive(Debug, Deserialize)]
+pub struct InsightsQuery {
+    pub calendar: Option<String>,
+    pub weeks: Option<u32>,
+}
+
+/// Show workload forecast
+pub async fn insights_page(
+    State(service): State<CalendarService>,
+    Extension(user): Extension<Uuid>,
+    Query(query): Query<InsightsQuery>,
+) -> Result<Html<String>, AppError> {
+    let user_model = service.get_user_by_id(user).await?
+        .ok_or_else(|| AppError::AuthenticationError("User not found".to_string()))?;
+    
+    let calendars = service.get_calendars_by_user_id(user).await?;
+    let weeks = query.weeks.unwrap_or(4).clamp(1, 52);
+    
+    // An empty selection means all calendars
+    let selected_calendar = match query.calendar.as_deref().filter(|c| !c.is_empty()) {
+        Some(id) => {
+            let calendar_id = Uuid::parse_str(id)?;
+            authz::authorize_calendar(&service, user, calendar_id, Access::View).await?;
+            Some(calendar_id)
+        }
+        None => None,
+    };
+    let calendar_ids: Vec<Uuid> = match selected_calendar {
+        Some(id) => vec![id],
+        None => calendars.iter().map(|c| c.id).collect(),
+    };
+    
+    let forecast = service.get_workload_forecast(&calendar_ids, weeks).await?;
+    
+    let capabilities = user_capabilities(&service, &user_model).await;
+
+    let html = render_to_html(
+        "InsightsPage",
+        rsx! {
+            InsightsPage {
+                current_user: user_model,
+                capabilities: capabilities,
+                calendars: calendars,
+                selected_calendar: selected_calendar,
+                weeks: weeks,
+                forecast: forecast,
+            }
+        }
+    )?;
+    
+    Ok(Html(html))
+}
+
+// ============== Attachment Handlers ==============
+
+/// Handle attachment upload from the event form
+pub async fn upload_attachment_handler(
+    State(service): State<CalendarService>,
+    Extension(user): Extension<Uuid>,
+    Path(event_id): Path<Uuid>,
+    mut multipart: Multipart,
+) -> Result<Response, AppError> {
+    authz::authorize_event(&service, user, event_id, Access::Edit).await?;
+    
+    let (filename, content_type, data) = super::read_attachment_upload(&mut multipart).await?;
+    service.create_attachment(event_id, &filename, &content_type, &data).await?;
+    
+    Ok(Redirect::to(&format!("/web/events/{}/edit", event_id)).into_response())
+}
+
+/// Handle delete attachment
+pub async fn delete_attachment_handler(
+    State(service): State<CalendarService>,
+    Extension(user): Extension<Uuid>,
+    Path(attachment_id): Path<Uuid>,
+) -> Result<Response, AppError> {
+    let attachment = service.get_attachment_by_id(attachment_id).await?
+        .ok_or_else(|| AppError::NotFoundError("Attachment not found".to_string()))?;
+    
+    authz::authorize_event(&service, user, attachment.event_id, Access::Edit).await?;
+    
+    service.delete_attachment(attachment_id).await?;
+    
+    Ok(Redirect::to(&format!("/web/events/{}/edit", attachment.event_id)).into_response())
+}
+
+// ============== Share Handlers ==============
+
+/// Handle create share
+pub async fn create_share_handler(
+    State(service): State<CalendarService>,
+    Extension(user): Extension<Uuid>,
+    Path(calendar_id): Path<Uuid>,
+    headers: HeaderMap,
+    Form(form): Form<ShareFormInput>,
+) -> Result<Response, AppError> {
+    authz::authorize_calendar(&service, user, calendar_id, Access::Admin).await?;
+    
+    let new_share = NewShare {
+        shared_with_email: form.shared_with_email,
+        permission: form.permission,
+    };
+    if let Err(e) = new_share.validate() {
+        let mut response = field_error_redirect(&service, &format!("/web/calendars/{}", calendar_id), e)?;
+        // htmx would swap the whole page into the share list; have it load the page instead
+        if is_htmx(&headers) && let Some(location) = response.headers_mut().remove(header::LOCATION) {
+            *response.status_mut() = StatusCode::OK;
+            response.headers_mut().insert("HX-Redirect", location);
+        }
+        return Ok(response);
+    }
+    
+    service.create_share(calendar_id, user, new_share).await?;
+    
+    if is_htmx(&headers) {
+        return Ok(share_list(&service, calendar_id).await?.into_response());
+    }
+    
+    redirect_with_flash(&service, &format!("/web/calendars/{}", calendar_id), Flash::success("Share created"))
+}
+
+/// Handle delete share
+pub async fn delete_share_handler(
+    State(service): State<CalendarService>,
+    Extension(user): Extension<Uuid>,
+    Path(share_id): Path<Uuid>,
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
+    let share = service.get_share_by_id(share_id).await?
+        .ok_or_else(|| AppError::NotFoundError("Share not found".to_string()))?;
+    
+    let calendar_id = share.calendar_id;
+    authz::authorize_calendar(&service, user, calendar_id, Access::Admin).await?;
+    
+    service.delete_share(share_id).await?;
+    
+    if is_htmx(&headers) {
+        return Ok(share_list(&service, calendar_id).await?.into_response());
+    }
+    
+    redirect_with_flash(&service, &format!("/web/calendars/{}", calendar_id), Flash::success("Share removed"))
+}
+
+/// Handle creating a share link
+pub async fn create_share_link_handler(
+    State(service): State<CalendarService>,
+    Extension(user): Extension<Uuid>,
+    Path(calendar_id): Path<Uuid>,
+    Form(form): Form<ShareLinkFormInput>,
+) -> Result<Response, AppError> {
+    authz::authorize_calendar(&service, user, calendar_id, Access::Admin).await?;
+    
+    let expires_in_days = match form.expires_in_days.as_deref().map(str::trim).filter(|d| !d.is_empty()) {
+        Some(days) => Some(days.parse().map_err(|_| AppError::invalid_field("expires_in_days", "Invalid expiry"))?),
+        None => None,
+    };
+    let new_link = NewShareLink {
+        permission: form.permission,
+        expires_in_days,
+    };
+    
+    let link = service.create_share_link(calendar_id, user, new_link).await?;
+    service.record_audit(user, None, "share_link_created", Some(&format!("{} ({})", link.id, link.permission_level))).await?;
+    
+    redirect_with_flash(&service, &format!("/web/calendars/{}", calendar_id), Flash::success("Share link created"))
+}
+
+/// Handle revoking a share link
+pub async fn revoke_share_link_handler(
+    State(service): State<CalendarService>,
+    Extension(user): Extension<Uuid>,
+    Path(link_id): Path<Uuid>,
+) -> Result<Response, AppError> {
+    let link = service.get_share_link_by_id(link_id).await?
+        .ok_or_else(|| AppError::NotFoundError("Share link not found".to_string()))?;
+    
+    authz::authorize_calendar(&service, user, link.calendar_id, Access::Admin).await?;
+    
+    service.revoke_share_link(link_id).await?;
+    service.record_audit(user, None, "share_link_revoked", Some(&link.id.to_string())).await?;
+    
+    redirect_with_flash(&service, &format!("/web/calendars/{}", link.calendar_id), Flash::success("Share link revoked"))
+}
+
+/// Cookie remembering a share link opened while signed out
+const SHARE_LINK_COOKIE: &str = "share_link";
+
+/// Page after signing in or registering: a pending share link is redeemed first
+fn after_sign_in(headers: &HeaderMap) -> &'static str {
+    let pending = headers
+        .get(header::COOKIE)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|cookies| middleware::parse_cookie(cookies, SHARE_LINK_COOKIE));
+    if pending.is_some() { "/web/share-links/continue" } else { "/web/dashboard" }
+}
+
+/// Open a share link. The token is kept in a cookie while the visitor signs in or
+/// registers; the redirect goes through a page of our own so the `SameSite=Strict`
+/// session cookie of a signed-in visitor arriving from another site is sent along.
+pub async fn open_share_link_handler(
+    State(service): State<CalendarService>,
+    Path(token): Path<String>,
+) -> Result<Response, AppError> {
+    service.resolve_share_link(&token).await?;
+    
+    let cookie = format!("{}={}; Path=/web; HttpOnly; SameSite=Lax; Max-Age=3600", SHARE_LINK_COOKIE, token);
+    Ok(meta_refresh("/web/share-links/continue", [cookie]))
+}
+
+/// Redeem the pending share link for the signed-in user and show its calendar
+pub async fn continue_share_link_handler(
+    State(service): State<CalendarService>,
+    Extension(user): Extension<Uuid>,
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
+    let token = headers
+        .get(header::COOKIE)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|cookies| middleware::parse_cookie(cookies, SHARE_LINK_COOKIE));
+    let Some(token) = token else {
+        return Ok(Redirect::to("/web/dashboard").into_response());
+    };
+    
+    // The link may have expired or been revoked while the visitor signed in
+    let mut response = match service.redeem_share_link(&token, user).await {
+        Ok(calendar_id) => redirect_with_flash(&service, &format!("/web/calendars/{}", calendar_id), Flash::success("Calendar shared with you"))?,
+        Err(AppError::NotFoundError(_)) => error_page(StatusCode::NOT_FOUND, None),
+        Err(e) => return Err(e),
+    };
+    let clear = format!("{}=; Path=/web; HttpOnly; SameSite=Lax; Max-Age=0", SHARE_LINK_COOKIE);
+    response.headers_mut().append(header::SET_COOKIE, clear.parse().expect("valid cookie header"));
+    Ok(response)
+}
+
+/// Share list fragment of a calendar
+async fn share_list(service: &CalendarService, calendar_id: Uuid) -> Result<Html<String>, AppError> {
+    let shares = service.get_shares_by_calendar_id(calendar_id).await?;
+    render_fragment("ShareList", rsx! { ShareList { shares: shares } })
+}
+
+/// Share list of a calendar as an htmx fragment
+pub async fn share_list_fragment(
+    State(service): State<CalendarService>,
+    Extension(user): Extension<Uuid>,
+    Path(calendar_id): Path<Uuid>,
+) -> Result<Html<String>, AppError> {
+    authz::authorize_calendar(&service, user, calendar_id, Access::Admin).await?;
+    share_list(&service, calendar_id).await
+}
+
+/// Event list of a calendar as an htmx fragment
+pub async fn event_list_fragment(
+    State(service): State<CalendarService>,
+    Extension(user): Extension<Uuid>,
+    Path(calendar_id): Path<Uuid>,
+) -> Result<Html<String>, AppError> {
+    authz::authorize_calendar(&service, user, calendar_id, Access::View).await?;
+    let events = service.get_events_by_calendar_id(calendar_id).await?;
+    render_fragment("EventList", rsx! { EventList { events: events, calendar_id: calendar_id } })
+}
+
+// ============== Admin Pages ==============
+
+/// Role update form data
+#[derive(Debug, Deserialize)]
+pub struct RoleFormInput {
+    pub role: String,
+}
+
+/// Purge form data; the checkbox confirms the purge
+#[derive(Debug, Deserialize)]
+pub struct PurgeFormInput {
+    pub confirm: Option<String>,
+}
+
+/// Show admin page (admin only)
+pub async fn admin_page(
+    State(service): State<CalendarService>,
+    Extension(user): Extension<Uuid>,
+    Extension(role): Extension<UserRoleExt>,
+) -> Result<Html<String>, AppError> {
+    // Check if user is admin
+    if role.0 != UserRole::Admin {
+        return Err(AppError::AuthenticationError("Admin access required".to_string()));
+    }
+    
+    let user_model = service.get_user_by_id(user).await?
+        .ok_or_else(|| AppError::AuthenticationError("User not found".to_string()))?;
+    
+    let users = service.get_all_users().await?;
+    let audit_log = service.get_audit_log(ADMIN_AUDIT_LOG_ENTRIES).await?;
+    
+    let capabilities = user_capabilities(&service, &user_model).await;
+
+    let html = render_to_html(
+        "AdminPage",
+        rsx! {
+            AdminPage {
+                current_user: user_model,
+                capabilities: capabilities,
+                users: users,
+                audit_log: audit_log,
+                unsupported_dav_features: service.dav_usage().is_enabled().then(|| service.dav_usage().unsupported_features()),
+            }
+        }
+    )?;
+    
+    Ok(Html(html))
+}
+
+/// Handle role update (admin only)
+pub async fn update_user_role_handler(
+    State(service): State<CalendarService>,
+    Extension(role): Extension<UserRoleExt>,
+    Path(user_id): Path<Uuid>,
+    Form(form): Form<RoleFormInput>,
+) -> Result<Response, AppError> {
+    // Check if user is admin
+    if role.0 != UserRole::Admin {
+        return Err(AppError::AuthenticationError("Admin access required".to_string()));
+    }
+    
+    let new_role = match form.role.as_str() {
+        "admin" => UserRole::Admin,
+        "user" => UserRole::User,
+        _ => return Err(AppError::ValidationError("Invalid role".to_string())),
+    };
+    
+    service.update_user_role(user_id, new_role).await?;
+    
+    redirect_with_flash(&service, "/web/admin", Flash::success("User role updated"))
+}
+
+/// Delete everything a user has stored while keeping their account (admin only)
+pub async fn purge_user_data_handler(
+    State(service): State<CalendarService>,
+    Extension(admin_id): Extension<Uuid>,
+    Extension(role): Extension<UserRoleExt>,
+    Path(user_id): Path<Uuid>,
+    Form(form): Form<PurgeFormInput>,
+) -> Result<Response, AppError> {
+    if role.0 != UserRole::Admin {
+        return Err(AppError::AuthenticationError("Admin access required".to_string()));
+    }
+    if form.confirm.is_none() {
+        return redirect_with_flash(&service, "/web/admin", Flash::error("Confirm the purge to delete the user's data"));
+    }
+
+    let user = service.get_user_by_id(user_id).await?
+        .ok_or(AppError::NotFoundError("User not found".to_string()))?;
+    service.purge_user_data(user_id).await?;
+    service.record_audit(admin_id, None, "user_data_purged", Some(&user.email)).await?;
+
+    redirect_with_flash(&service, "/web/admin", Flash::success(format!("Data of {} purged", user.email)))
+}
+
+/// Show the backups page (admin only)
+pub async fn admin_backups_page(
+    State(service): State<CalendarService>,
+    Extension(user): Extension<Uuid>,
+    Extension(role): Extension<UserRoleExt>,
+) -> Result<Html<String>, AppError> {
+    if role.0 != UserRole::Admin {
+        return Err(AppError::AuthenticationError("Admin access required".to_string()));
+    }
+    
+    let user_model = service.get_user_by_id(user).await?
+        .ok_or_else(|| AppError::AuthenticationError("User not found".to_string()))?;
+    
+    let capabilities = user_capabilities(&service, &user_model).await;
+
+    let html = render_to_html(
+        "BackupsPage",
+        rsx! {
+            BackupsPage {
+                current_user: user_model,
+                capabilities: capabilities,
+                backups: service.list_backups()?,
+                backup_dir: service.backup_dir().to_string(),
+            }
+        }
+    )?;
+    
+    Ok(Html(html))
+}
+
+/// Take a backup right away (admin only)
+pub async fn create_backup_handler(
+    State(service): State<CalendarService>,
+    Extension(user): Extension<Uuid>,
+    Extension(role): Extension<UserRoleExt>,
+) -> Result<Response, AppError> {
+    if role.0 != UserRole::Admin {
+        return Err(AppError::AuthenticationError("Admin access required".to_string()));
+    }
+    
+    let backup = service.create_backup().await?;
+    service.record_audit(user, None, "backup_created", Some(&backup.name)).await?;
+    
+    redirect_with_flash(&service, "/web/admin/backups", Flash::success(format!("Backup {} created", backup.name)))
+}
+
+/// Download a backup file (admin only)
+pub async fn download_backup_handler(
+    State(service): State<CalendarService>,
+    Extension(user): Extension<Uuid>,
+    Extension(role): Extension<UserRoleExt>,
+    Path(name): Path<String>,
+) -> Result<Response, AppError> {
+    if role.0 != UserRole::Admin {
+        return Err(AppError::AuthenticationError("Admin access required".to_string()));
+    }
+    
+    let path = service.backup_path(&name)
+        .ok_or_else(|| AppError::NotFoundError("Backup not found".to_string()))?;
+    let contents = tokio::fs::read(&path).await
+        .map_err(|e| AppError::InternalServerError(format!("Reading backup failed: {}", e)))?;
+    service.record_audit(user, None, "backup_downloaded", Some(&name)).await?;
+    
+    Ok(Response::builder()
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, "application/vnd.sqlite3")
+        .header(header::CONTENT_DISPOSITION, format!("attachment; filename=\"{}\"", name))
+        .body(axum::body::Body::from(contents))
+        .unwrap())
+}
+
+/// Number of audit log entries shown on the admin page
+const ADMIN_AUDIT_LOG_ENTRIES: i64 = 50;
+
+/// Start impersonating a user (admin only). The admin's own session token is parked
+/// in `admin_token` so it can be restored when impersonation stops.
+pub async fn impersonate_user_handler(
+    State(service): State<CalendarService>,
+    Extension(admin_id): Extension<Uuid>,
+    Extension(role): Extension<UserRoleExt>,
+    Path(user_id): Path<Uuid>,
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
+    if role.0 != UserRole::Admin {
+        return Err(AppError::AuthenticationError("Admin access required".to_string()));
+    }
+
+    let admin_token = headers
+        .get(header::COOKIE)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|cookies| middleware::parse_cookie(cookies, "auth_token"))
+        .ok_or_else(|| AppError::AuthenticationError("Missing session".to_string()))?;
+
+    let (token, _, _) = match service.start_impersonation(admin_id, user_id).await {
+        Ok(result) => result,
+        Err(AppError::ValidationError(msg)) => {
+            return redirect_with_flash(&service, "/web/admin", Flash::error(msg));
+        }
+        Err(e) => return Err(e),
+    };
+
+    Ok(Response::builder()
+        .status(StatusCode::FOUND)
+        .header("Location", "/web/dashboard")
+        .header("Set-Cookie", format!("admin_token={}; Path=/; HttpOnly; SameSite=Strict", admin_token))
+        .header("Set-Cookie", format!("auth_token={}; Path=/; HttpOnly; SameSite=Strict", token))
+        .body(axum::body::Body::empty())
+        .unwrap()
+        .into_response())
+}
+
+/// Stop impersonating and restore the admin's own session
+pub async fn stop_impersonation_handler(
+    State(service): State<CalendarService>,
+    Extension(user): Extension<Uuid>,
+    impersonation: Option<Extension<Impersonation>>,
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
+    let Some(Extension(impersonation)) = impersonation else {
+        return Ok(Redirect::to("/web/dashboard").into_response());
+    };
+
+    service.record_audit(impersonation.admin_id, Some(user), "impersonation_stopped", None).await?;
+
+    let admin_token = headers
+        .get(header::COOKIE)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|cookies| middleware::parse_cookie(cookies, "admin_token"));
+
+    let response = match admin_token {
+        Some(token) => Response::builder()
+            .status(StatusCode::FOUND)
+            .header("Location", "/web/admin")
+            .header("Set-Cookie", format!("auth_token={}; Path=/; HttpOnly; SameSite=Strict", token)),
+        None => Response::builder()
+            .status(StatusCode::FOUND)
+            .header("Location", "/web/login")
+            .header("Set-Cookie", "auth_token=; Path=/; HttpOnly; SameSite=Strict; Max-Age=0"),
+    };
+
+    Ok(response
+        .header("Set-Cookie", "admin_token=; Path=/; HttpOnly; SameSite=Strict; Max-Age=0")
+        .body(axum::body::Body::empty())
+        .unwrap()
+        .into_response())
+}
+
+/// Password confirmation for linking or unlinking an external identity
+#[derive(Debug, Deserialize)]
+pub struct ReauthForm {
+    pub password: String,
+}
+
+/// Delegation form data
+#[derive(Debug, Deserialize)]
+pub struct DelegationForm {
+    pub delegate_email: String,
+    pub permission: String,
+}
+
+/// Callback parameters sent back by the OIDC provider
+#[derive(Debug, Deserialize)]
+pub struct OidcCallbackQuery {
+    pub code: Option<String>,
+    pub state: Option<String>,
+    pub error: Option<String>,
+}
+
+/// Cookie carrying the signed OIDC state between the redirect and the callback.
+/// `SameSite=Lax` so it survives the top-level navigation back from the provider.
+const OIDC_STATE_COOKIE: &str = "oidc_state";
+
+/// Redirect to the OIDC provider, remembering the signed state in a cookie
+async fn start_oidc_flow(service: &CalendarService, link_user: Option<Uuid>) -> Result<Response, AppError> {
+    let oidc = service.oidc()
+        .ok_or_else(|| AppError::NotFoundError("Single sign-on is not configured".to_string()))?;
+    let (cookie, state) = service.create_oidc_state(link_user)?;
+    let url = oidc.authorization_url(&state.state, &state.nonce).await?;
+
+    Ok(Response::builder()
+        .status(StatusCode::FOUND)
+        .header("Location", url)
+        .header("Set-Cookie", format!("{}={}; Path=/web/login/oidc; HttpOnly; SameSite=Lax; Max-Age=600", OIDC_STATE_COOKIE, cookie))
+        .body(axum::body::Body::empty())
+        .unwrap()
+        .into_response())
+}
+
+/// Redirect via a page of our own, so `SameSite=Strict` session cookies are sent
+/// on the follow-up request even though the callback came from the provider's site.
+/// `cookie` is the session or flash cookie to set along the way.
+fn same_site_redirect(location: &str, cookie: Option<String>) -> Response {
+    let clear_state = format!("{}=; Path=/web/login/oidc; HttpOnly; SameSite=Lax; Max-Age=0", OIDC_STATE_COOKIE);
+    meta_refresh(location, std::iter::once(clear_state).chain(cookie))
+}
+
+/// Page that sends the browser on to `location`, setting the given cookies
+fn meta_refresh(location: &str, cookies: impl IntoIterator<Item = String>) -> Response {
+    let mut response = Response::builder()
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, "text/html; charset=utf-8");
+    for cookie in cookies {
+        response = response.header("Set-Cookie", cookie);
+    }
+    response
+        .body(axum::body::Body::from(format!(
+            "<!DOCTYPE html><html><head><meta http-equiv=\"refresh\" content=\"0;url={0}\"></head><body><a href=\"{0}\">Continue</a></body></html>",
+            location
+        )))
+        .unwrap()
+}
+
+/// Start signing in with the configured OIDC provider
+pub async fn oidc_login_handler(
+    State(service): State<CalendarService>,
+) -> Result<Response, AppError> {
+    start_oidc_flow(&service, None).await
+}
+
+/// Handle the OIDC provider's callback: sign in with a linked identity, or finish
+/// linking an identity when the flow was started from settings
+pub async fn oidc_callback_handler(
+    State(service): State<CalendarService>,
+    Query(query): Query<OidcCallbackQuery>,
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
+    let oidc = service.oidc()
+        .ok_or_else(|| AppError::NotFoundError("Single sign-on is not configured".to_string()))?;
+
+    let state_cookie = headers
+        .get(header::COOKIE)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|cookies| middleware::parse_cookie(cookies, OIDC_STATE_COOKIE));
+
+    let (Some(code), Some(state), Some(state_cookie)) = (query.code, query.state, state_cookie) else {
+        let reason = query.error.unwrap_or_else(|| "invalid callback".to_string());
+        tracing::warn!("OIDC callback rejected: {}", reason);
+        let flash = flash_cookie(&service, &Flash::error("Single sign-on failed"))?;
+        return Ok(same_site_redirect("/web/login", Some(flash)));
+    };
+
+    let state = service.verify_oidc_state(&state_cookie, &state)?;
+    let identity = oidc.exchange_code(&code, &state.nonce).await?;
+
+    if let Some(user_id) = state.link_user {
+        let flash = match service.link_identity(user_id, &identity).await {
+            Ok(_) => Flash::success("Identity linked"),
+            Err(AppError::ValidationError(msg)) => Flash::error(msg),
+            Err(e) => return Err(e),
+        };
+        return Ok(same_site_redirect("/web/settings", Some(flash_cookie(&service, &flash)?)));
+    }
+
+    let Some(user) = service.get_user_by_identity(&identity.issuer, &identity.subject).await? else {
+        let flash = Flash::error("No account is linked to this identity. Sign in with your password and link it under Settings.");
+        return Ok(same_site_redirect("/web/login", Some(flash_cookie(&service, &flash)?)));
+    };
+
+    let token = service.generate_jwt(user.id, &user.role)?;
+    tracing::info!("OIDC login successful for user: {}", user.email);
+
+    Ok(same_site_redirect(
+        "/web/dashboard",
+        Some(format!("auth_token={}; Path=/; HttpOnly; SameSite=Strict", token)),
+    ))
+}
+
+/// Show account settings
+pub async fn settings_page(
+    State(service): State<CalendarService>,
+    Extension(user): Extension<Uuid>,
+) -> Result<Html<String>, AppError> {
+    let user_model = service.get_user_by_id(user).await?
+        .ok_or_else(|| AppError::AuthenticationError("User not found".to_string()))?;
+    let identities = service.get_user_identities(user).await?;
+    let delegations = or_fallback("delegations", service.get_delegations(user).await);
+    let login_activity = or_fallback("login activity", service.get_login_activity(user).await);
+    let notification_preferences = or_fallback("notification preferences", service.get_notification_preferences(user).await);
+    let holiday_packs = or_fallback("holiday packs", service.get_holiday_packs(user).await);
+    let user_settings = or_fallback("user settings", service.get_user_settings(user).await);
+
+    let capabilities = user_capabilities(&service, &user_model).await;
+
+    let html = render_to_html(
+        "SettingsPage",
+        rsx! {
+            SettingsPage {
+                current_user: user_model,
+                capabilities: capabilities,
+                identities: identities,
+                delegations: delegations,
+                login_activity: login_activity,
+                notification_preferences: notification_preferences,
+                holiday_packs: holiday_packs,
+                user_settings: user_settings,
+            }
+        }
+    )?;
+
+    Ok(Html(html))
+}
+
+/// Re-authenticate with the current password, then link an external identity
+pub async fn link_identity_handler(
+    State(service): State<CalendarService>,
+    Extension(user): Extension<Uuid>,
+    Form(form): Form<ReauthForm>,
+) -> Result<Response, AppError> {
+    let user_model = service.get_user_by_id(user).await?
+        .ok_or_else(|| AppError::AuthenticationError("User not found".to_string()))?;
+
+    if !service.verify_password(&user_model, &form.password).await? {
+        return redirect_with_flash(&service, "/web/settings", Flash::error("Incorrect password"));
+    }
+
+    start_oidc_flow(&service, Some(user)).await
+}
+
+/// Re-authenticate with the current password, then unlink an external identity
+pub async fn unlink_identity_handler(
+    State(service): State<CalendarService>,
+    Extension(user): Extension<Uuid>,
+    Path(identity_id): Path<Uuid>,
+    Form(form): Form<ReauthForm>,
+) -> Result<Response, AppError> {
+    let user_model = service.get_user_by_id(user).await?
+        .ok_or_else(|| AppError::AuthenticationError("User not found".to_string()))?;
+
+    if !service.verify_password(&user_model, &form.password).await? {
+        return redirect_with_flash(&service, "/web/settings", Flash::error("Incorrect password"));
+    }
+
+    service.unlink_identity(user, identity_id).await?;
+
+    redirect_with_flash(&service, "/web/settings", Flash::success("Identity unlinked"))
+}
+
+/// Handle granting a delegate access to all of the user's calendars
+pub async fn create_delegation_handler(
+    State(service): State<CalendarService>,
+    Extension(user): Extension<Uuid>,
+    Form(form): Form<DelegationForm>,
+) -> Result<Response, AppError> {
+    let new_delegation = NewDelegation {
+        delegate_email: form.delegate_email,
+        permission: form.permission,
+    };
+    let delegation = match service.create_delegation(user, new_delegation).await {
+        Ok(delegation) => delegation,
+        Err(AppError::FieldValidationError { message, .. }) => {
+            return redirect_with_flash(&service, "/web/settings", Flash::error(message));
+        }
+        Err(e) => return Err(e),
+    };
+    service.record_audit(user, None, "delegation_granted", Some(&format!("{} ({})", delegation.delegate_email, delegation.permission_level))).await?;
+
+    redirect_with_flash(&service, "/web/settings", Flash::success(format!("{} can now manage your calendars", delegation.delegate_name)))
+}
+
+/// Handle choosing how reminders of a calendar are delivered
+pub async fn update_notification_preference_handler(
+    State(service): State<CalendarService>,
+    Extension(user): Extension<Uuid>,
+    Path(calendar_id): Path<Uuid>,
+    Form(form): Form<UpdateNotificationPreference>,
+) -> Result<Response, AppError> {
+    let preference = service.get_notification_preferences(user).await?
+        .into_iter()
+        .find(|p| p.calendar_id == calendar_id)
+        .ok_or(AppError::NotFoundError("Calendar not found".to_string()))?;
+
+    service.set_notification_preference(user, calendar_id, form.channel).await?;
+
+    redirect_with_flash(&service, "/web/settings", Flash::success(format!("Notifications for {} saved", preference.calendar_name)))
+}
+
+/// Handle reordering the calendars list by drag and drop
+pub async fn reorder_calendars_handler(
+    State(service): State<CalendarService>,
+    Extension(user): Extension<Uuid>,
+    Form(form): Form<CalendarOrderForm>,
+) -> Result<StatusCode, AppError> {
+    let calendar_ids = form.calendar_ids
+        .split(',')
+        .filter(|id| !id.is_empty())
+        .map(Uuid::parse_str)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|_| AppError::ValidationError("Invalid calendar id".to_string()))?;
+
+    service.reorder_calendars(user, &calendar_ids).await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Handle making a calendar the default for new events
+pub async fn set_default_calendar_handler(
+    State(service): State<CalendarService>,
+    Extension(user): Extension<Uuid>,
+    Path(calendar_id): Path<Uuid>,
+) -> Result<Response, AppError> {
+    let calendar = authz::authorize_calendar(&service, user, calendar_id, Access::Admin).await?;
+    service.set_default_calendar(user, calendar_id).await?;
+
+    redirect_with_flash(&service, "/web/calendars", Flash::success(format!("{} is now your default calendar", calendar.name)))
+}
+
+/// Handle hiding or showing a calendar on the dashboard
+pub async fn update_calendar_visibility_handler(
+    State(service): State<CalendarService>,
+    Extension(user): Extension<Uuid>,
+    Path(calendar_id): Path<Uuid>,
+    Form(form): Form<CalendarVisibilityForm>,
+) -> Result<Response, AppError> {
+    let preference = service.get_calendar_preferences(user).await?
+        .into_iter()
+        .find(|p| p.calendar_id == calendar_id)
+        .ok_or(AppError::NotFoundError("Calendar not found".to_string()))?;
+
+    let update = UpdateCalendarPreference { hidden: Some(form.hidden), ..Default::default() };
+    service.update_calendar_preference(user, calendar_id, update).await?;
+
+    let action = if form.hidden { "hidden" } else { "shown" };
+    redirect_with_flash(&service, "/web/calendars", Flash::success(format!("{} is {} on the dashboard", preference.calendar_name, action)))
+}
+
+/// Handle adding or removing a holiday calendar
+pub async fn update_holiday_pack_handler(
+    State(service): State<CalendarService>,
+    Extension(user): Extension<Uuid>,
+    Path(pack_id): Path<String>,
+    Form(form): Form<HolidayPackForm>,
+) -> Result<Response, AppError> {
+    let message = if form.enabled {
+        let calendar = service.enable_holiday_pack(user, &pack_id).await?;
+        format!("{} added", calendar.name)
+    } else {
+        service.disable_holiday_pack(user, &pack_id).await?;
+        "Holiday calendar removed".to_string()
+    };
+
+    redirect_with_flash(&service, "/web/settings", Flash::success(message))
+}
+
+/// Handle revoking a delegation, by either the owner or the delegate
+pub async fn delete_delegation_handler(
+    State(service): State<CalendarService>,
+    Extension(user): Extension<Uuid>,
+    Path(delegation_id): Path<Uuid>,
+) -> Result<Response, AppError> {
+    let delegation = service.get_delegation_by_id(delegation_id).await?
+        .filter(|d| d.owner_id == user || d.delegate_id == user)
+        .ok_or(AppError::NotFoundError("Delegation not found".to_string()))?;
+
+    service.delete_delegation(delegation_id).await?;
+    service.record_audit(user, None, "delegation_revoked", Some(&delegation.delegate_email)).await?;
+
+    redirect_with_flash(&service, "/web/settings", Flash::success("Delegation removed"))
+}
+
+/// Show the user's logged CalDAV requests, by client
+pub async fn sync_log_page(
+    State(service): State<CalendarService>,
+    Extension(user): Extension<Uuid>,
+    Query(query): Query<SyncLogQuery>,
+) -> Result<Html<String>, AppError> {
+    let user_model = service.get_user_by_id(user).await?
+        .ok_or_else(|| AppError::AuthenticationError("User not found".to_string()))?;
+    let enabled = service.get_user_settings(user).await?.sync_log;
+    let entries = service.get_sync_log(user).await?;
+    let clients = crate::sync_log::clients(&entries);
+    let client = query.client.filter(|client| !client.is_empty());
+    let entries = match &client {
+        Some(client) => entries.into_iter().filter(|entry| entry.client() == client).collect(),
+        None => entries,
+    };
+
+    let capabilities = user_capabilities(&service, &user_model).await;
+
+    let html = render_to_html(
+        "SyncLogPage",
+        rsx! {
+            SyncLogPage {
+                current_user: user_model,
+                capabilities: capabilities,
+                enabled: enabled,
+                clients: clients,
+                entries: entries,
+                client: client,
+            }
+        }
+    )?;
+
+    Ok(Html(html))
+}
+
+/// Handle turning the sync log on or off; turning it off deletes it
+pub async fn update_sync_log_handler(
+    State(service): State<CalendarService>,
+    Extension(user): Extension<Uuid>,
+    Form(form): Form<SyncLogForm>,
+) -> Result<Response, AppError> {
+    service.update_user_settings(user, UpdateUserSettings { sync_log: Some(form.enabled), ..Default::default() }).await?;
+
+    let message = if form.enabled { "Sync log turned on" } else { "Sync log turned off and deleted" };
+    redirect_with_flash(&service, "/web/settings/sync-log", Flash::success(message))
+}
+
+/// Handle deleting the logged requests, keeping the log on
+pub async fn clear_sync_log_handler(
+    State(service): State<CalendarService>,
+    Extension(user): Extension<Uuid>,
+) -> Result<Response, AppError> {
+    service.clear_sync_log(user).await?;
+
+    redirect_with_flash(&service, "/web/settings/sync-log", Flash::success("Sync log cleared"))
+}
+
+/// Show the CalDAV clients that synced with the user's account
+pub async fn devices_page(
+    State(service): State<CalendarService>,
+    Extension(user): Extension<Uuid>,
+) -> Result<Html<String>, AppError> {
+    let user_model = service.get_user_by_id(user).await?
+        .ok_or_else(|| AppError::AuthenticationError("User not found".to_string()))?;
+    let devices = service.get_devices(user).await?;
+
+    let capabilities = user_capabilities(&service, &user_model).await;
+
+    let html = render_to_html(
+        "DevicesPage",
+        rsx! {
+            DevicesPage {
+                current_user: user_model,
+                capabilities: capabilities,
+                devices: devices,
+            }
+        }
+    )?;
+
+    Ok(Html(html))
+}
+
+/// Handle revoking a device, refusing its CalDAV requests
+pub async fn revoke_device_handler(
+    State(service): State<CalendarService>,
+    Extension(user): Extension<Uuid>,
+    Path(device_id): Path<Uuid>,
+) -> Result<Response, AppError> {
+    let device = service.get_device(user, device_id).await?;
+
+    service.revoke_device(user, device_id).await?;
+    service.record_audit(user, None, "device_revoked", Some(&device.user_agent)).await?;
+
+    redirect_with_flash(&service, "/web/settings/devices", Flash::success("Device revoked"))
+}
+
+/// Handle removing a device from the list, which lets a revoked device sync again
+pub async fn remove_device_handler(
+    State(service): State<CalendarService>,
+    Extension(user): Extension<Uuid>,
+    Path(device_id): Path<Uuid>,
+) -> Result<Response, AppError> {
+    let device = service.get_device(user, device_id).await?;
+
+    service.remove_device(user, device_id).await?;
+    service.record_audit(user, None, "device_removed", Some(&device.user_agent)).await?;
+
+    redirect_with_flash(&service, "/web/settings/devices", Flash::success("Device removed"))
+}
+
+/// Connection settings of the signed-in user. Without a configured `BASE_URL` the
+/// server URL is taken from the request, honoring `X-Forwarded-Proto` behind a proxy.
+async fn connection_profile(
+    service: &CalendarService,
+    headers: &HeaderMap,
+    user: Uuid,
+) -> Result<ConnectionProfile, AppError> {
+    let user_model = service.get_user_by_id(user).await?
+        .ok_or_else(|| AppError::AuthenticationError("User not found".to_string()))?;
+
+    let server_url = if service.base_url().is_empty() {
+        let header_value = |name: &str| headers.get(name).and_then(|v| v.to_str().ok());
+        let scheme = header_value("x-forwarded-proto").unwrap_or("http");
+        let host = header_value(header::HOST.as_str()).unwrap_or("localhost");
+        format!("{}://{}", scheme, host)
+    } else {
+        service.base_url().to_string()
+    };
+
+    Ok(ConnectionProfile {
+        instance_name: service.get_settings().await?.instance_name,
+        caldav_url: format!("{}/calendars/", server_url),
+        principal_path: service.href("/calendars/"),
+        server_url,
+        username: user_model.email,
+    })
+}
+
+/// Download an Apple configuration profile for the signed-in user
+pub async fn download_mobileconfig_handler(
+    State(service): State<CalendarService>,
+    Extension(user): Extension<Uuid>,
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
+    let profile = connection_profile(&service, &headers, user).await?;
+
+    Ok(Response::builder()
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, "application/x-apple-aspen-config")
+        .header(header::CONTENT_DISPOSITION, "attachment; filename=\"caldav.mobileconfig\"")
+        .body(axum::body::Body::from(profile.to_mobileconfig()))
+        .unwrap())
+}
+
+/// Download the connection settings as JSON, for clients without profile support
+pub async fn download_connection_json_handler(
+    State(service): State<CalendarService>,
+    Extension(user): Extension<Uuid>,
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
+    let profile = connection_profile(&service, &headers, user).await?;
+    let body = serde_json::to_string_pretty(&profile)
+        .map_err(|e| AppError::InternalServerError(format!("Failed to serialize settings: {}", e)))?;
+
+    Ok(Response::builder()
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, "application/json")
+        .header(header::CONTENT_DISPOSITION, "attachment; filename=\"caldav-settings.json\"")
+        .body(axum::body::Body::from(body))
+        .unwrap())
 }
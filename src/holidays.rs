@@ -0,0 +1,177 @@
+//! Built-in holiday calendars
+//!
+//! Each pack lists the public holidays of a country or region as rules, so no
+//! holiday data has to be downloaded. Enabling a pack gives the user a read-only
+//! calendar with one all-day event per holiday and year in `HOLIDAY_YEARS`
+//! around the year it is enabled. Substitute days for holidays falling on a
+//! weekend are not included.
+
+use chrono::{Datelike, Duration, NaiveDate, Weekday};
+use serde::Serialize;
+
+/// Years before and after the current one that holiday events are generated for
+pub const HOLIDAY_YEARS: (i32, i32) = (1, 10);
+
+/// How a holiday's date is worked out for a given year
+#[derive(Debug, Clone, Copy)]
+enum Rule {
+    /// The same day every year
+    Fixed { month: u32, day: u32 },
+    /// Days after Easter Sunday (negative for before)
+    Easter(i64),
+    /// The `n`th weekday of a month, counting from its end when negative
+    NthWeekday { month: u32, weekday: Weekday, n: i32 },
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Holiday {
+    name: &'static str,
+    rule: Rule,
+}
+
+const fn fixed(name: &'static str, month: u32, day: u32) -> Holiday {
+    Holiday { name, rule: Rule::Fixed { month, day } }
+}
+
+const fn easter(name: &'static str, offset: i64) -> Holiday {
+    Holiday { name, rule: Rule::Easter(offset) }
+}
+
+const fn nth(name: &'static str, month: u32, weekday: Weekday, n: i32) -> Holiday {
+    Holiday { name, rule: Rule::NthWeekday { month, weekday, n } }
+}
+
+/// Holidays of a country, or of a region on top of its country's
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct HolidayPack {
+    /// Country code, with the region code appended for regions (`de-by`)
+    pub id: &'static str,
+    pub name: &'static str,
+    #[serde(skip)]
+    extends: Option<&'static str>,
+    #[serde(skip)]
+    holidays: &'static [Holiday],
+}
+
+const US: &[Holiday] = &[
+    fixed("New Year's Day", 1, 1),
+    nth("Martin Luther King Jr. Day", 1, Weekday::Mon, 3),
+    nth("Washington's Birthday", 2, Weekday::Mon, 3),
+    nth("Memorial Day", 5, Weekday::Mon, -1),
+    fixed("Juneteenth", 6, 19),
+    fixed("Independence Day", 7, 4),
+    nth("Labor Day", 9, Weekday::Mon, 1),
+    nth("Columbus Day", 10, Weekday::Mon, 2),
+    fixed("Veterans Day", 11, 11),
+    nth("Thanksgiving Day", 11, Weekday::Thu, 4),
+    fixed("Christmas Day", 12, 25),
+];
+
+const GB_ENG: &[Holiday] = &[
+    fixed("New Year's Day", 1, 1),
+    easter("Good Friday", -2),
+    easter("Easter Monday", 1),
+    nth("Early May bank holiday", 5, Weekday::Mon, 1),
+    nth("Spring bank holiday", 5, Weekday::Mon, -1),
+    nth("Summer bank holiday", 8, Weekday::Mon, -1),
+    fixed("Christmas Day", 12, 25),
+    fixed("Boxing Day", 12, 26),
+];
+
+const DE: &[Holiday] = &[
+    fixed("Neujahr", 1, 1),
+    easter("Karfreitag", -2),
+    easter("Ostermontag", 1),
+    fixed("Tag der Arbeit", 5, 1),
+    easter("Christi Himmelfahrt", 39),
+    easter("Pfingstmontag", 50),
+    fixed("Tag der Deutschen Einheit", 10, 3),
+    fixed("1. Weihnachtstag", 12, 25),
+    fixed("2. Weihnachtstag", 12, 26),
+];
+
+const DE_BY: &[Holiday] = &[
+    fixed("Heilige Drei Könige", 1, 6),
+    easter("Fronleichnam", 60),
+    fixed("Mariä Himmelfahrt", 8, 15),
+    fixed("Allerheiligen", 11, 1),
+];
+
+const FR: &[Holiday] = &[
+    fixed("Jour de l'an", 1, 1),
+    easter("Lundi de Pâques", 1),
+    fixed("Fête du Travail", 5, 1),
+    fixed("Victoire 1945", 5, 8),
+    easter("Ascension", 39),
+    easter("Lundi de Pentecôte", 50),
+    fixed("Fête nationale", 7, 14),
+    fixed("Assomption", 8, 15),
+    fixed("Toussaint", 11, 1),
+    fixed("Armistice 1918", 11, 11),
+    fixed("Noël", 12, 25),
+];
+
+/// Every pack users can enable
+pub const PACKS: &[HolidayPack] = &[
+    HolidayPack { id: "us", name: "United States", extends: None, holidays: US },
+    HolidayPack { id: "gb-eng", name: "United Kingdom (England and Wales)", extends: None, holidays: GB_ENG },
+    HolidayPack { id: "de", name: "Germany", extends: None, holidays: DE },
+    HolidayPack { id: "de-by", name: "Germany (Bavaria)", extends: Some("de"), holidays: DE_BY },
+    HolidayPack { id: "fr", name: "France", extends: None, holidays: FR },
+];
+
+pub fn find(id: &str) -> Option<&'static HolidayPack> {
+    PACKS.iter().find(|pack| pack.id == id)
+}
+
+impl HolidayPack {
+    /// Name and date of every holiday of the pack in `year`, in date order
+    pub fn holidays_in(&self, year: i32) -> Vec<(&'static str, NaiveDate)> {
+        let mut holidays: Vec<_> = self.extends.and_then(find)
+            .map(|country| country.holidays_in(year))
+            .unwrap_or_default();
+        holidays.extend(self.holidays.iter().filter_map(|h| Some((h.name, h.rule.date_in(year)?))));
+        holidays.sort_by_key(|(_, date)| *date);
+        holidays
+    }
+}
+
+impl Rule {
+    fn date_in(self, year: i32) -> Option<NaiveDate> {
+        match self {
+            Rule::Fixed { month, day } => NaiveDate::from_ymd_opt(year, month, day),
+            Rule::Easter(offset) => Some(easter_sunday(year)? + Duration::days(offset)),
+            Rule::NthWeekday { month, weekday, n } if n > 0 => NaiveDate::from_weekday_of_month_opt(year, month, weekday, n as u8),
+            Rule::NthWeekday { month, weekday, n } => {
+                // Count back from the last day of the month
+                let next_month = if month == 12 {
+                    NaiveDate::from_ymd_opt(year + 1, 1, 1)?
+                } else {
+                    NaiveDate::from_ymd_opt(year, month + 1, 1)?
+                };
+                let last_day = next_month.pred_opt()?;
+                let back = (7 + last_day.weekday().num_days_from_monday() - weekday.num_days_from_monday()) % 7;
+                Some(last_day - Duration::days(back as i64 + 7 * (-n - 1) as i64))
+            }
+        }
+    }
+}
+
+/// Easter Sunday of the Gregorian calendar (anonymous Gregorian algorithm)
+fn easter_sunday(year: i32) -> Option<NaiveDate> {
+    let a = year % 19;
+    let b = year / 100;
+    let c = year % 100;
+    let d = b / 4;
+    let e = b % 4;
+    let f = (b + 8) / 25;
+    let g = (b - f + 1) / 3;
+    let h = (19 * a + b - d - g + 15) % 30;
+    let i = c / 4;
+    let k = c % 4;
+    let l = (32 + 2 * e + 2 * i - h - k) % 7;
+    let m = (a + 11 * h + 22 * l) / 451;
+    let month = (h + l - 7 * m + 114) / 31;
+    let day = (h + l - 7 * m + 114) % 31 + 1;
+    NaiveDate::from_ymd_opt(year, month as u32, day as u32)
+}
@@ -0,0 +1,138 @@
+//! Translations of the web UI and notification mails
+//!
+//! Each language has a catalog in `locales/<code>.toml`, compiled into the binary, with
+//! messages grouped into tables by page (`[dashboard]`, `[settings]`, ...) and looked up
+//! by dotted key (`dashboard.title`). Messages can take named arguments written as
+//! `{name}`. Keys missing from a catalog fall back to English, so a partial translation
+//! never leaves a blank on the page.
+
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+use serde::{Deserialize, Serialize};
+
+/// Languages the web UI and mails are translated into
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Language {
+    #[default]
+    En,
+    De,
+}
+
+impl Language {
+    pub const ALL: [Language; 2] = [Language::En, Language::De];
+
+    /// ISO 639-1 code, as used in `lang` attributes and `Accept-Language`
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Language::En => "en",
+            Language::De => "de",
+        }
+    }
+
+    /// Name of the language in the language itself
+    pub fn native_name(&self) -> &'static str {
+        match self {
+            Language::En => "English",
+            Language::De => "Deutsch",
+        }
+    }
+
+    /// The language of a code such as `de` or `de-AT`, if it is one we translate into
+    pub fn from_code(code: &str) -> Option<Self> {
+        let primary = code.split(['-', '_']).next().unwrap_or_default().trim();
+        Self::ALL.into_iter().find(|language| language.as_str().eq_ignore_ascii_case(primary))
+    }
+
+    /// The language the browser prefers most among those we translate into, from an
+    /// `Accept-Language` header
+    pub fn negotiate(accept_language: &str) -> Option<Self> {
+        let mut ranges: Vec<(f32, Language)> = accept_language
+            .split(',')
+            .filter_map(|range| {
+                let mut parts = range.split(';');
+                let language = Self::from_code(parts.next()?)?;
+                let quality = parts
+                    .find_map(|param| param.trim().strip_prefix("q="))
+                    .map_or(Some(1.0), |q| q.trim().parse().ok())?;
+                (quality > 0.0).then_some((quality, language))
+            })
+            .collect();
+        // Stable, so equally preferred languages keep the browser's order
+        ranges.sort_by(|a, b| b.0.total_cmp(&a.0));
+        ranges.first().map(|(_, language)| *language)
+    }
+
+    fn catalog_source(&self) -> &'static str {
+        match self {
+            Language::En => include_str!("../locales/en.toml"),
+            Language::De => include_str!("../locales/de.toml"),
+        }
+    }
+}
+
+type Catalog = HashMap<String, String>;
+
+fn catalogs() -> &'static HashMap<Language, Catalog> {
+    static CATALOGS: OnceLock<HashMap<Language, Catalog>> = OnceLock::new();
+    CATALOGS.get_or_init(|| {
+        Language::ALL
+            .into_iter()
+            .map(|language| (language, parse_catalog(language.catalog_source())))
+            .collect()
+    })
+}
+
+/// Flatten the tables of a catalog into dotted keys
+fn parse_catalog(source: &str) -> Catalog {
+    fn flatten(prefix: &str, table: toml::Table, catalog: &mut Catalog) {
+        for (key, value) in table {
+            let key = if prefix.is_empty() { key } else { format!("{}.{}", prefix, key) };
+            match value {
+                toml::Value::Table(table) => flatten(&key, table, catalog),
+                toml::Value::String(message) => {
+                    catalog.insert(key, message);
+                }
+                other => {
+                    catalog.insert(key, other.to_string());
+                }
+            }
+        }
+    }
+
+    let table: toml::Table = source.parse().expect("translation catalogs are valid TOML");
+    let mut catalog = Catalog::new();
+    flatten("", table, &mut catalog);
+    catalog
+}
+
+/// The message for `key` in `language`, in English if it isn't translated, or the key
+/// itself if there is no such message
+pub fn translate(language: Language, key: &str) -> String {
+    let catalogs = catalogs();
+    catalogs[&language]
+        .get(key)
+        .or_else(|| catalogs[&Language::En].get(key))
+        .cloned()
+        .unwrap_or_else(|| key.to_string())
+}
+
+/// [`translate`] with the `{name}` arguments of the message filled in
+pub fn translate_with(language: Language, key: &str, args: &[(&str, &str)]) -> String {
+    args.iter().fold(translate(language, key), |message, (name, value)| {
+        message.replace(&format!("{{{}}}", name), value)
+    })
+}
+
+/// Keys of the English catalog, which every other catalog should translate
+pub fn keys() -> Vec<&'static str> {
+    let mut keys: Vec<_> = catalogs()[&Language::En].keys().map(String::as_str).collect();
+    keys.sort_unstable();
+    keys
+}
+
+/// Keys of the English catalog that `language` doesn't translate
+pub fn missing_keys(language: Language) -> Vec<&'static str> {
+    keys().into_iter().filter(|key| !catalogs()[&language].contains_key(*key)).collect()
+}
@@ -0,0 +1,574 @@
+//! Checks of iCalendar objects for the problems that make clients refuse them
+//!
+//! When a phone won't sync an event, the reason is rarely visible to the user. The
+//! validation endpoint reads an object line by line and reports what is wrong with it,
+//! with the line it starts on: broken folding and line endings, components that aren't
+//! closed, missing UIDs, dates that don't parse, recurrence rules that break RFC 5545 and
+//! time zones nobody defines. Errors are problems that make this server or a client reject
+//! the object; warnings are departures from the standard that some clients trip over.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use chrono::{NaiveDate, NaiveDateTime};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+
+use crate::outlook;
+use crate::recurrence::Rule;
+
+/// Errors and warnings listed in a report, each; further ones are only counted
+pub const MAX_REPORTED_ISSUES: usize = 100;
+
+/// Longest line RFC 5545 allows before it has to be folded, in octets
+const MAX_LINE_LENGTH: usize = 75;
+
+/// Components whose UID identifies them
+const CONTENT_COMPONENTS: [&str; 3] = ["VEVENT", "VTODO", "VJOURNAL"];
+
+/// Properties holding dates or date-times
+const TIME_PROPERTIES: [&str; 7] = ["DTSTART", "DTEND", "DUE", "RECURRENCE-ID", "EXDATE", "RDATE", "DTSTAMP"];
+
+/// Rule parts RFC 5545 defines for RRULE
+const RULE_PARTS: [&str; 14] = [
+    "FREQ", "UNTIL", "COUNT", "INTERVAL", "BYSECOND", "BYMINUTE", "BYHOUR", "BYDAY",
+    "BYMONTHDAY", "BYYEARDAY", "BYWEEKNO", "BYMONTH", "BYSETPOS", "WKST",
+];
+
+/// Problem found in an iCalendar object
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct IcalIssue {
+    /// Stable name of the check, e.g. `missing-uid`
+    pub code: String,
+    pub message: String,
+    /// Line the problem starts on, counting from 1
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub line: Option<usize>,
+    /// Component the problem is in, e.g. `VEVENT`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub component: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub uid: Option<String>,
+}
+
+/// Result of checking an iCalendar object
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct IcalReport {
+    /// Whether no errors were found; warnings don't count
+    pub valid: bool,
+    /// Components found, by type, including nested ones such as VALARM
+    pub components: BTreeMap<String, usize>,
+    pub error_count: usize,
+    pub warning_count: usize,
+    /// The first [`MAX_REPORTED_ISSUES`] errors
+    pub errors: Vec<IcalIssue>,
+    /// The first [`MAX_REPORTED_ISSUES`] warnings
+    pub warnings: Vec<IcalIssue>,
+}
+
+impl IcalReport {
+    fn error(&mut self, issue: IcalIssue) {
+        self.error_count += 1;
+        if self.errors.len() < MAX_REPORTED_ISSUES {
+            self.errors.push(issue);
+        }
+    }
+
+    fn warning(&mut self, issue: IcalIssue) {
+        self.warning_count += 1;
+        if self.warnings.len() < MAX_REPORTED_ISSUES {
+            self.warnings.push(issue);
+        }
+    }
+}
+
+/// Check an iCalendar object as a client would receive it
+pub fn check(data: &[u8]) -> IcalReport {
+    let mut report = IcalReport::default();
+
+    if let Err(e) = std::str::from_utf8(data) {
+        let line = line_number(data, e.valid_up_to());
+        report.error(issue("encoding", format!("Line {} is not valid UTF-8", line)).at(line));
+    }
+
+    let lines = logical_lines(data, &mut report);
+    let mut checker = Checker::default();
+    for line in &lines {
+        if !checker.line(line, &mut report) {
+            break;
+        }
+    }
+    checker.finish(&mut report);
+
+    report.valid = report.error_count == 0;
+    report
+}
+
+/// Line of an iCalendar object with its folded continuations joined
+#[derive(Debug)]
+struct LogicalLine {
+    /// Physical line it starts on
+    number: usize,
+    text: String,
+}
+
+/// Join folded lines, reporting line endings, overlong lines and broken folding
+fn logical_lines(data: &[u8], report: &mut IcalReport) -> Vec<LogicalLine> {
+    let mut lines: Vec<LogicalLine> = Vec::new();
+    let mut bad_ending = None;
+    let mut too_long = Vec::new();
+    let mut previous: &[u8] = &[];
+    let mut rest = data;
+    let mut number = 0;
+
+    while !rest.is_empty() {
+        number += 1;
+        let end = rest.iter().position(|&b| b == b'\n' || b == b'\r').unwrap_or(rest.len());
+        let line = &rest[..end];
+        let ending = &rest[end..(end + 2).min(rest.len())];
+        let crlf = ending.starts_with(b"\r\n");
+        if !crlf && bad_ending.is_none() {
+            bad_ending = Some(number);
+        }
+        rest = &rest[(end + if crlf { 2 } else { 1 }).min(rest.len())..];
+
+        if line.len() > MAX_LINE_LENGTH {
+            too_long.push(number);
+        }
+        if line.is_empty() {
+            continue;
+        }
+
+        if matches!(line[0], b' ' | b'\t') {
+            match lines.last_mut() {
+                Some(logical) => {
+                    if std::str::from_utf8(previous).is_err() && std::str::from_utf8(data).is_ok() {
+                        report.warning(issue("folding", format!("The fold before line {} splits a multi-byte character", number)).at(number));
+                    }
+                    logical.text.push_str(&String::from_utf8_lossy(&line[1..]));
+                }
+                None => report.error(issue("folding", "The object starts with a continuation line").at(number)),
+            }
+        } else {
+            let text = String::from_utf8_lossy(line).into_owned();
+            if !text.contains(':') {
+                report.error(issue("folding", format!(
+                    "Line {} has no colon; if it continues the line before, it must start with a space", number,
+                )).at(number));
+            } else {
+                lines.push(LogicalLine { number, text });
+            }
+        }
+        previous = line;
+    }
+
+    if let Some(number) = bad_ending {
+        report.warning(issue("line-endings", format!("Lines must end in CRLF; line {} doesn't", number)).at(number));
+    }
+    if let Some(&first) = too_long.first() {
+        report.warning(issue("line-length", format!(
+            "{} lines are longer than {} octets and should be folded, the first is line {}",
+            too_long.len(), MAX_LINE_LENGTH, first,
+        )).at(first));
+    }
+    lines
+}
+
+/// Property of a component: name, parameters and value
+#[derive(Debug, Clone)]
+struct Property {
+    line: usize,
+    name: String,
+    params: Vec<(String, String)>,
+    value: String,
+}
+
+impl Property {
+    fn param(&self, name: &str) -> Option<&str> {
+        self.params.iter().find(|(param, _)| param == name).map(|(_, value)| value.as_str())
+    }
+
+    fn is_date(&self) -> bool {
+        self.param("VALUE").is_some_and(|value| value.eq_ignore_ascii_case("DATE"))
+    }
+}
+
+/// Component being read, with its own properties
+#[derive(Debug)]
+struct Component {
+    kind: String,
+    line: usize,
+    properties: Vec<Property>,
+    /// Number of components it contains
+    children: usize,
+}
+
+impl Component {
+    fn property(&self, name: &str) -> Option<&Property> {
+        self.properties.iter().find(|property| property.name == name)
+    }
+}
+
+/// Place a TZID was used
+#[derive(Debug)]
+struct ZoneUse {
+    zone: String,
+    line: usize,
+    component: String,
+    uid: Option<String>,
+}
+
+#[derive(Debug, Default)]
+struct Checker {
+    stack: Vec<Component>,
+    started: bool,
+    microsoft: bool,
+    /// TZIDs defined by VTIMEZONE components
+    defined_zones: HashSet<String>,
+    zone_uses: Vec<ZoneUse>,
+    /// Line of the first component without RECURRENCE-ID for each UID
+    masters: HashMap<String, usize>,
+}
+
+impl Checker {
+    /// Read a logical line; `false` once the object can't be read further
+    fn line(&mut self, line: &LogicalLine, report: &mut IcalReport) -> bool {
+        let Some(property) = parse_property(line) else {
+            report.error(issue("syntax", format!("Line {} is not a property", line.number)).at(line.number));
+            return true;
+        };
+
+        match property.name.as_str() {
+            "BEGIN" => {
+                let kind = property.value.trim().to_ascii_uppercase();
+                if !self.started && kind != "VCALENDAR" {
+                    report.error(issue("not-icalendar", "The object must start with BEGIN:VCALENDAR").at(line.number));
+                    return false;
+                }
+                self.started = true;
+                if let Some(parent) = self.stack.last_mut() {
+                    parent.children += 1;
+                }
+                *report.components.entry(kind.clone()).or_default() += 1;
+                self.stack.push(Component { kind, line: line.number, properties: Vec::new(), children: 0 });
+            }
+            "END" => {
+                let kind = property.value.trim().to_ascii_uppercase();
+                match self.stack.pop() {
+                    Some(component) if component.kind == kind => self.close(component, report),
+                    Some(component) => {
+                        report.error(issue("unbalanced", format!(
+                            "END:{} on line {} closes BEGIN:{} from line {}", kind, line.number, component.kind, component.line,
+                        )).at(line.number).within(&component));
+                        self.close(component, report);
+                    }
+                    None => report.error(issue("unbalanced", format!("END:{} on line {} closes nothing", kind, line.number)).at(line.number)),
+                }
+            }
+            _ => match self.stack.last_mut() {
+                Some(component) => component.properties.push(property),
+                None if !self.started => {
+                    report.error(issue("not-icalendar", "The object must start with BEGIN:VCALENDAR").at(line.number));
+                    return false;
+                }
+                None => report.error(issue("syntax", format!("Line {} is outside of any component", line.number)).at(line.number)),
+            },
+        }
+        true
+    }
+
+    fn finish(&mut self, report: &mut IcalReport) {
+        if !self.started {
+            report.error(issue("not-icalendar", "The object must start with BEGIN:VCALENDAR"));
+        }
+        while let Some(component) = self.stack.pop() {
+            report.error(issue("unbalanced", format!(
+                "BEGIN:{} on line {} is never closed", component.kind, component.line,
+            )).at(component.line).within(&component));
+            self.close(component, report);
+        }
+    }
+
+    fn close(&mut self, component: Component, report: &mut IcalReport) {
+        match component.kind.as_str() {
+            "VCALENDAR" => self.check_calendar(&component, report),
+            "VTIMEZONE" => {
+                if let Some(zone) = component.property("TZID") {
+                    self.defined_zones.insert(zone.value.trim().to_string());
+                }
+            }
+            kind if CONTENT_COMPONENTS.contains(&kind) => self.check_content(&component, report),
+            _ => {}
+        }
+    }
+
+    fn check_calendar(&mut self, calendar: &Component, report: &mut IcalReport) {
+        match calendar.property("VERSION") {
+            None => report.error(issue("missing-version", "The calendar has no VERSION:2.0").at(calendar.line)),
+            Some(version) if version.value.trim() != "2.0" => report.error(issue("version", format!(
+                "VERSION is {}; only iCalendar 2.0 is understood", version.value.trim(),
+            )).at(version.line)),
+            Some(_) => {}
+        }
+        match calendar.property("PRODID") {
+            None => report.warning(issue("missing-prodid", "The calendar has no PRODID").at(calendar.line)),
+            Some(prod_id) => self.microsoft |= outlook::is_microsoft(&prod_id.value),
+        }
+        if calendar.children == 0 {
+            report.warning(issue("empty", "The calendar has no components").at(calendar.line));
+        }
+
+        for zone_use in std::mem::take(&mut self.zone_uses) {
+            let defined = self.defined_zones.contains(&zone_use.zone);
+            let known = zone_use.zone.parse::<Tz>().is_ok();
+            let found = issue("unknown-time-zone", String::new()).at(zone_use.line)
+                .in_component(&zone_use.component, zone_use.uid.as_deref());
+            if known {
+                if !defined {
+                    report.warning(IcalIssue {
+                        code: "missing-vtimezone".to_string(),
+                        message: format!("TZID {} has no VTIMEZONE; clients without a time zone database can't read it", zone_use.zone),
+                        ..found
+                    });
+                }
+            } else if outlook::windows_time_zone(&zone_use.zone).is_some() {
+                let message = if self.microsoft {
+                    format!("TZID {} is a Windows time zone; it is read as the matching IANA zone because Microsoft wrote the file", zone_use.zone)
+                } else {
+                    format!("TZID {} is a Windows time zone; import with ?compat=outlook to read it", zone_use.zone)
+                };
+                report.warning(IcalIssue { code: "windows-time-zone".to_string(), message, ..found });
+            } else if defined {
+                report.warning(IcalIssue {
+                    message: format!("TZID {} is not an IANA time zone; this server reads its times as UTC", zone_use.zone),
+                    ..found
+                });
+            } else {
+                report.error(IcalIssue {
+                    message: format!("TZID {} is neither defined by a VTIMEZONE nor an IANA time zone", zone_use.zone),
+                    ..found
+                });
+            }
+        }
+    }
+
+    fn check_content(&mut self, component: &Component, report: &mut IcalReport) {
+        let uid = component.property("UID").map(|uid| uid.value.trim().to_string()).filter(|uid| !uid.is_empty());
+        let at = |code: &str, message: String, line: usize| issue(code, message).at(line).in_component(&component.kind, uid.as_deref());
+
+        match &uid {
+            None => report.error(at("missing-uid", format!("The {} has no UID", component.kind), component.line)),
+            Some(uid) if component.property("RECURRENCE-ID").is_none() => {
+                if let Some(first) = self.masters.insert(uid.clone(), component.line) {
+                    report.error(at("duplicate-uid", format!("UID {} is also used by the component on line {}", uid, first), component.line));
+                    self.masters.insert(uid.clone(), first);
+                }
+            }
+            Some(_) => {}
+        }
+        if component.property("DTSTAMP").is_none() {
+            report.warning(at("missing-dtstamp", format!("The {} has no DTSTAMP", component.kind), component.line));
+        }
+
+        for property in &component.properties {
+            if TIME_PROPERTIES.contains(&property.name.as_str()) {
+                if let Some(zone) = property.param("TZID") {
+                    self.zone_uses.push(ZoneUse {
+                        zone: zone.to_string(),
+                        line: property.line,
+                        component: component.kind.clone(),
+                        uid: uid.clone(),
+                    });
+                }
+                for value in property.value.split(',') {
+                    if parse_value(value, property.is_date()).is_none() {
+                        report.error(at("invalid-date", format!(
+                            "{} value {} is not a {}", property.name, value.trim(), if property.is_date() { "date" } else { "date-time" },
+                        ), property.line));
+                    }
+                }
+            }
+        }
+
+        if component.kind == "VEVENT" {
+            self.check_event(component, report, &at);
+        }
+    }
+
+    fn check_event(&self, event: &Component, report: &mut IcalReport, at: &dyn Fn(&str, String, usize) -> IcalIssue) {
+        if event.property("SUMMARY").is_none() {
+            report.error(at("missing-summary", "The event has no SUMMARY, which this server requires".to_string(), event.line));
+        }
+        let Some(start) = event.property("DTSTART") else {
+            report.error(at("missing-dtstart", "The event has no DTSTART".to_string(), event.line));
+            return;
+        };
+        let end = event.property("DTEND");
+        let duration = event.property("DURATION");
+
+        match (end, duration) {
+            (Some(_), Some(duration)) => {
+                report.error(at("dtend-and-duration", "The event has both DTEND and DURATION".to_string(), duration.line));
+            }
+            (None, Some(duration)) if !start.is_date() => {
+                report.error(at("unsupported-duration", "The event has DURATION instead of DTEND, which this server can't read".to_string(), duration.line));
+            }
+            (None, None) if !start.is_date() => {
+                report.error(at("missing-dtend", "The event has no DTEND, which this server requires for events with a time of day".to_string(), event.line));
+            }
+            _ => {}
+        }
+
+        if let Some(end) = end {
+            if end.is_date() != start.is_date() {
+                report.error(at("mixed-value-types", "DTSTART and DTEND must both be dates or both be date-times".to_string(), end.line));
+            } else if end.param("TZID") == start.param("TZID") {
+                let start_value = parse_value(&start.value, start.is_date());
+                let end_value = parse_value(&end.value, end.is_date());
+                if let (Some(start_value), Some(end_value)) = (start_value, end_value)
+                    && end_value < start_value
+                {
+                    report.error(at("end-before-start", "DTEND is before DTSTART".to_string(), end.line));
+                }
+            }
+        }
+
+        for rule in event.properties.iter().filter(|property| property.name == "RRULE") {
+            check_rule(rule, start, report, at);
+        }
+    }
+}
+
+/// Check an RRULE against RFC 5545 and the subset the server expands
+fn check_rule(rule: &Property, start: &Property, report: &mut IcalReport, at: &dyn Fn(&str, String, usize) -> IcalIssue) {
+    let invalid = |message: String| at("invalid-rrule", message, rule.line);
+    let errors = report.error_count;
+    let mut parts = HashMap::new();
+    for part in rule.value.split(';').filter(|part| !part.trim().is_empty()) {
+        let Some((key, value)) = part.split_once('=') else {
+            report.error(invalid(format!("RRULE part {} is not KEY=VALUE", part)));
+            return;
+        };
+        let key = key.trim().to_ascii_uppercase();
+        if !RULE_PARTS.contains(&key.as_str()) {
+            report.error(invalid(format!("RRULE part {} is not defined by RFC 5545", key)));
+        } else if parts.insert(key.clone(), value.trim().to_string()).is_some() {
+            report.error(invalid(format!("RRULE has {} more than once", key)));
+        }
+    }
+
+    match parts.get("FREQ").map(|freq| freq.to_ascii_uppercase()) {
+        None => report.error(invalid("RRULE has no FREQ".to_string())),
+        Some(freq) if !["SECONDLY", "MINUTELY", "HOURLY", "DAILY", "WEEKLY", "MONTHLY", "YEARLY"].contains(&freq.as_str()) => {
+            report.error(invalid(format!("RRULE FREQ {} is not a frequency", freq)));
+        }
+        Some(_) => {}
+    }
+    if parts.contains_key("COUNT") && parts.contains_key("UNTIL") {
+        report.error(invalid("RRULE can't have both COUNT and UNTIL".to_string()));
+    }
+    for key in ["COUNT", "INTERVAL"] {
+        if let Some(value) = parts.get(key)
+            && !value.parse::<u32>().is_ok_and(|n| n > 0)
+        {
+            report.error(invalid(format!("RRULE {} must be a positive number, not {}", key, value)));
+        }
+    }
+    if let Some(until) = parts.get("UNTIL") {
+        // RFC 5545, 3.3.10: UNTIL has the value type of DTSTART, and is UTC if DTSTART isn't floating
+        let until_is_date = until.len() == 8;
+        if parse_value(until, until_is_date).is_none() {
+            report.error(invalid(format!("RRULE UNTIL {} is not a date or date-time", until)));
+        } else if until_is_date != start.is_date() {
+            report.error(invalid("RRULE UNTIL must be a date exactly when DTSTART is".to_string()));
+        } else if !until_is_date && (start.param("TZID").is_some() || start.value.trim().ends_with('Z')) && !until.ends_with('Z') {
+            report.error(invalid("RRULE UNTIL must be in UTC (end in Z) when DTSTART has a time zone".to_string()));
+        }
+    }
+
+    if report.error_count > errors {
+        return;
+    }
+    let ignored: Vec<&str> = RULE_PARTS.iter().copied()
+        .filter(|part| part.starts_with("BY") || *part == "WKST")
+        .filter(|part| parts.contains_key(*part))
+        .collect();
+    if Rule::parse(&rule.value).is_none() {
+        report.warning(at("unsupported-rrule", "This server only repeats events daily, weekly, monthly or yearly".to_string(), rule.line));
+    } else if !ignored.is_empty() {
+        report.warning(at("unsupported-rrule", format!(
+            "This server ignores {} when it expands the rule; clients still see them", ignored.join(", "),
+        ), rule.line));
+    }
+}
+
+/// Split a logical line into name, parameters and value; parameter values may be
+/// quoted and contain `;`, `:` and `,`
+fn parse_property(line: &LogicalLine) -> Option<Property> {
+    let mut params = Vec::new();
+    let mut quoted = false;
+    let mut start = 0;
+    let mut name = None;
+    let text = &line.text;
+
+    for (index, c) in text.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            ';' | ':' if !quoted => {
+                let part = &text[start..index];
+                match name {
+                    None => name = Some(part.trim().to_ascii_uppercase()),
+                    Some(_) => {
+                        let (param, value) = part.split_once('=')?;
+                        params.push((param.trim().to_ascii_uppercase(), value.trim().trim_matches('"').to_string()));
+                    }
+                }
+                start = index + 1;
+                if c == ':' {
+                    let name = name.filter(|name| !name.is_empty())?;
+                    return Some(Property { line: line.number, name, params, value: text[start..].to_string() });
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// A DATE (`YYYYMMDD`) or DATE-TIME (`YYYYMMDDTHHMMSS`, optionally ending in `Z`) value
+fn parse_value(value: &str, is_date: bool) -> Option<NaiveDateTime> {
+    let value = value.trim();
+    if is_date {
+        return NaiveDate::parse_from_str(value, "%Y%m%d").ok()?.and_hms_opt(0, 0, 0);
+    }
+    let value = value.strip_suffix('Z').unwrap_or(value);
+    if value.len() != 15 {
+        return None;
+    }
+    NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S").ok()
+}
+
+/// Line, counting from 1, that the byte at `offset` is on
+fn line_number(data: &[u8], offset: usize) -> usize {
+    let before = &data[..offset];
+    let crlf = before.windows(2).filter(|pair| pair == b"\r\n").count();
+    before.iter().filter(|&&b| b == b'\n' || b == b'\r').count() - crlf + 1
+}
+
+fn issue(code: &str, message: impl Into<String>) -> IcalIssue {
+    IcalIssue { code: code.to_string(), message: message.into(), line: None, component: None, uid: None }
+}
+
+impl IcalIssue {
+    fn at(self, line: usize) -> Self {
+        Self { line: Some(line), ..self }
+    }
+
+    fn within(self, component: &Component) -> Self {
+        let uid = component.property("UID").map(|uid| uid.value.trim().to_string());
+        Self { component: Some(component.kind.clone()), uid, ..self }
+    }
+
+    fn in_component(self, kind: &str, uid: Option<&str>) -> Self {
+        Self { component: Some(kind.to_string()), uid: uid.map(str::to_string), ..self }
+    }
+}
@@ -0,0 +1,179 @@
+//! Streaming reader for iCalendar imports
+//!
+//! Uploads are split into their top-level components (VEVENT, VTODO, ...) as the body
+//! arrives, so memory use is bounded by the largest component rather than the file. The
+//! import handler stores the components in batches, each in its own transaction; a
+//! batch that was committed stays imported even if a later one fails.
+
+use crate::error::AppError;
+use crate::models::{NewEvent, NewJournal, NewTask};
+
+/// Components stored per transaction
+pub const IMPORT_BATCH_SIZE: usize = 500;
+
+/// Failed components reported individually in the summary; further ones are only counted
+pub const MAX_REPORTED_ERRORS: usize = 100;
+
+/// Top-level component of an iCalendar object with its folded lines joined
+#[derive(Debug, Clone)]
+pub struct IcalComponent {
+    pub kind: String,
+    /// Component lines; left empty once the component grows beyond the size limit
+    pub data: String,
+    /// Size of the component in bytes, counted in full even when `data` was dropped
+    pub size: usize,
+}
+
+/// Splits a VCALENDAR into its top-level components as chunks of it arrive. Nested
+/// components such as VALARM stay part of their parent.
+#[derive(Debug)]
+pub struct ComponentReader {
+    max_component_size: usize,
+    /// Physical line still being received
+    partial: Vec<u8>,
+    /// Logical line being unfolded; complete once a line that doesn't continue it arrives
+    logical: Option<Vec<u8>>,
+    depth: usize,
+    current: Option<IcalComponent>,
+    started: bool,
+    prod_id: Option<String>,
+}
+
+impl ComponentReader {
+    /// Reader keeping the contents of components up to `max_component_size` bytes
+    pub fn new(max_component_size: usize) -> Self {
+        Self {
+            max_component_size,
+            partial: Vec::new(),
+            logical: None,
+            depth: 0,
+            current: None,
+            started: false,
+            prod_id: None,
+        }
+    }
+
+    /// Feed the next chunk of the file, returning the components it completed
+    pub fn push(&mut self, chunk: &[u8]) -> Result<Vec<IcalComponent>, AppError> {
+        let mut components = Vec::new();
+        let mut rest = chunk;
+
+        // Lines may end in CRLF, LF or a bare CR; the empty lines this makes of CRLF
+        // are skipped
+        while let Some(end) = rest.iter().position(|&b| b == b'\n' || b == b'\r') {
+            self.partial.extend_from_slice(&rest[..end]);
+            rest = &rest[end + 1..];
+            let line = std::mem::take(&mut self.partial);
+            self.physical_line(line, &mut components)?;
+        }
+        self.partial.extend_from_slice(rest);
+        if self.partial.len() > self.max_component_size {
+            return Err(AppError::ValidationError("iCalendar line is too long".to_string()));
+        }
+
+        Ok(components)
+    }
+
+    /// PRODID of the calendar, once the reader has passed it
+    pub fn prod_id(&self) -> Option<&str> {
+        self.prod_id.as_deref()
+    }
+
+    /// End of the file: returns the components still pending
+    pub fn finish(mut self) -> Result<Vec<IcalComponent>, AppError> {
+        let mut components = Vec::new();
+        if !self.partial.is_empty() {
+            let line = std::mem::take(&mut self.partial);
+            self.physical_line(line, &mut components)?;
+        }
+        if let Some(line) = self.logical.take() {
+            self.logical_line(line, &mut components)?;
+        }
+        if !self.started {
+            return Err(AppError::ValidationError("Expected an iCalendar (VCALENDAR) file".to_string()));
+        }
+
+        Ok(components)
+    }
+
+    fn physical_line(&mut self, line: Vec<u8>, components: &mut Vec<IcalComponent>) -> Result<(), AppError> {
+        if line.is_empty() {
+            return Ok(());
+        }
+
+        if matches!(line.first(), Some(b' ' | b'\t'))
+            && let Some(logical) = self.logical.as_mut()
+        {
+            logical.extend_from_slice(&line[1..]);
+            if logical.len() > self.max_component_size {
+                return Err(AppError::ValidationError("iCalendar line is too long".to_string()));
+            }
+            return Ok(());
+        }
+
+        if let Some(previous) = self.logical.replace(line) {
+            self.logical_line(previous, components)?;
+        }
+        Ok(())
+    }
+
+    fn logical_line(&mut self, line: Vec<u8>, components: &mut Vec<IcalComponent>) -> Result<(), AppError> {
+        let line = String::from_utf8_lossy(&line);
+        let upper = line.trim().to_ascii_uppercase();
+
+        if let Some(kind) = upper.strip_prefix("BEGIN:") {
+            if !self.started {
+                if kind != "VCALENDAR" {
+                    return Err(AppError::ValidationError("Expected an iCalendar (VCALENDAR) file".to_string()));
+                }
+                self.started = true;
+            }
+            self.depth += 1;
+            if self.depth == 2 {
+                self.current = Some(IcalComponent { kind: kind.to_string(), data: String::new(), size: 0 });
+            }
+        }
+
+        if self.depth == 1
+            && let Some(prod_id) = upper.strip_prefix("PRODID:")
+        {
+            self.prod_id = Some(prod_id.to_string());
+        }
+
+        if let Some(component) = self.current.as_mut() {
+            component.size += line.len() + 1;
+            if component.size <= self.max_component_size {
+                component.data.push_str(&line);
+                component.data.push('\n');
+            } else {
+                component.data = String::new();
+            }
+        }
+
+        if upper.starts_with("END:") {
+            if self.depth == 2 {
+                components.extend(self.current.take());
+            }
+            self.depth = self.depth.saturating_sub(1);
+        }
+        Ok(())
+    }
+}
+
+/// Parsed components stored together in one transaction
+#[derive(Debug, Default)]
+pub struct ImportBatch {
+    pub events: Vec<NewEvent>,
+    pub tasks: Vec<NewTask>,
+    pub journals: Vec<NewJournal>,
+}
+
+impl ImportBatch {
+    pub fn len(&self) -> usize {
+        self.events.len() + self.tasks.len() + self.journals.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
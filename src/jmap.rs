@@ -0,0 +1,585 @@
+//! Experimental JMAP for Calendars endpoint, built with the `jmap` feature
+//!
+//! Serves the session resource and a subset of methods on top of the service layer:
+//! `Calendar/get`, `Calendar/changes`, `CalendarEvent/get`, `CalendarEvent/set` and
+//! `CalendarEvent/changes`. Events are JSCalendar objects as produced by
+//! [`crate::jscalendar`]. Result references between method calls are not supported.
+
+use std::collections::HashMap;
+use axum::{
+    extract::{Extension, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    body::Body,
+    Json,
+};
+use chrono::{DateTime, Duration, Utc};
+use serde::Deserialize;
+use serde_json::{json, Map, Value};
+use uuid::Uuid;
+use crate::authz::{self, Access};
+use crate::dav::get_user_id;
+use crate::error::AppError;
+use crate::jscalendar;
+use crate::middleware::BasicAuthCredentials;
+use crate::models::{Calendar, Event, NewEvent, PermissionLevel, UpdateEvent};
+use crate::services::CalendarService;
+
+const CAPABILITY_CORE: &str = "urn:ietf:params:jmap:core";
+const CAPABILITY_CALENDARS: &str = "urn:ietf:params:jmap:calendars";
+
+/// Method calls accepted in one request
+const MAX_CALLS_IN_REQUEST: usize = 16;
+
+/// Objects accepted in one `/get` by id or one `/set`
+const MAX_OBJECTS: usize = 500;
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct JmapRequest {
+    using: Vec<String>,
+    method_calls: Vec<(String, Value, String)>,
+}
+
+/// Error answered in place of a method response
+struct MethodError {
+    kind: &'static str,
+    description: Option<String>,
+}
+
+impl MethodError {
+    fn new(kind: &'static str) -> Self {
+        Self { kind, description: None }
+    }
+
+    fn invalid_arguments(description: impl Into<String>) -> Self {
+        Self { kind: "invalidArguments", description: Some(description.into()) }
+    }
+}
+
+impl From<AppError> for MethodError {
+    fn from(e: AppError) -> Self {
+        tracing::error!("JMAP method failed: {:?}", e);
+        Self::new("serverFail")
+    }
+}
+
+/// `SetError` for one object of a `/set` call
+fn set_error(e: AppError) -> Value {
+    let kind = match &e {
+        AppError::AuthenticationError(_) => "forbidden",
+        AppError::NotFoundError(_) => "notFound",
+        AppError::QuotaExceeded(_) => "overQuota",
+        AppError::ValidationError(_) | AppError::FieldValidationError { .. } | AppError::UuidError(_) => "invalidProperties",
+        _ => "serverFail",
+    };
+    json!({ "type": kind, "description": e.to_string() })
+}
+
+/// Request-level error as an RFC 7807 problem document
+fn request_error(kind: &str, detail: &str) -> Response {
+    Response::builder()
+        .status(StatusCode::BAD_REQUEST)
+        .header(header::CONTENT_TYPE, "application/problem+json")
+        .body(Body::from(json!({
+            "type": format!("urn:ietf:params:jmap:error:{}", kind),
+            "status": 400,
+            "detail": detail,
+        }).to_string()))
+        .unwrap()
+}
+
+/// `/.well-known/jmap`, redirecting to the session resource
+pub async fn well_known(State(service): State<CalendarService>) -> impl IntoResponse {
+    Response::builder()
+        .status(StatusCode::MOVED_PERMANENTLY)
+        .header(header::LOCATION, service.url("/jmap/session"))
+        .body(Body::from(""))
+        .unwrap()
+}
+
+/// The JMAP session resource of the authenticated user
+pub async fn session(
+    State(service): State<CalendarService>,
+    user_id_ext: Option<Extension<Uuid>>,
+    basic_auth: Option<Extension<BasicAuthCredentials>>,
+) -> Result<Json<Value>, AppError> {
+    let user_id = get_user_id(&service, user_id_ext.map(|ext| ext.0), basic_auth.map(|ext| ext.0)).await?;
+    let user = service.get_user_by_id(user_id).await?
+        .ok_or(AppError::AuthenticationError("User not found".to_string()))?;
+    let account_id = user_id.to_string();
+
+    Ok(Json(json!({
+        "capabilities": {
+            CAPABILITY_CORE: {
+                "maxSizeUpload": 0,
+                "maxConcurrentUpload": 0,
+                "maxSizeRequest": 10_000_000,
+                "maxConcurrentRequests": 4,
+                "maxCallsInRequest": MAX_CALLS_IN_REQUEST,
+                "maxObjectsInGet": MAX_OBJECTS,
+                "maxObjectsInSet": MAX_OBJECTS,
+                "collationAlgorithms": [],
+            },
+            CAPABILITY_CALENDARS: {},
+        },
+        "accounts": {
+            account_id.clone(): {
+                "name": user.email,
+                "isPersonal": true,
+                "isReadOnly": false,
+                "accountCapabilities": { CAPABILITY_CALENDARS: {} },
+            },
+        },
+        "primaryAccounts": { CAPABILITY_CALENDARS: account_id },
+        "username": user.email,
+        "apiUrl": service.url("/jmap/api"),
+        "downloadUrl": service.url("/jmap/download/{accountId}/{blobId}/{name}?type={type}"),
+        "uploadUrl": service.url("/jmap/upload/{accountId}/"),
+        "eventSourceUrl": service.url("/jmap/eventsource"),
+        "state": "0",
+    })))
+}
+
+/// Process a JMAP API request, answering its method calls in order
+pub async fn api(
+    State(service): State<CalendarService>,
+    user_id_ext: Option<Extension<Uuid>>,
+    basic_auth: Option<Extension<BasicAuthCredentials>>,
+    body: String,
+) -> Result<Response, AppError> {
+    let user_id = get_user_id(&service, user_id_ext.map(|ext| ext.0), basic_auth.map(|ext| ext.0)).await?;
+
+    let Ok(request) = serde_json::from_str::<JmapRequest>(&body) else {
+        return Ok(request_error("notRequest", "The request is not a valid JMAP request object"));
+    };
+    if let Some(unknown) = request.using.iter().find(|c| *c != CAPABILITY_CORE && *c != CAPABILITY_CALENDARS) {
+        return Ok(request_error("unknownCapability", &format!("Unsupported capability {}", unknown)));
+    }
+    if request.method_calls.len() > MAX_CALLS_IN_REQUEST {
+        return Ok(request_error("limit", "Too many method calls"));
+    }
+
+    let calendars_enabled = request.using.iter().any(|c| c == CAPABILITY_CALENDARS);
+    let mut responses = Vec::with_capacity(request.method_calls.len());
+
+    for (name, arguments, call_id) in request.method_calls {
+        let result = if !calendars_enabled {
+            Err(MethodError::new("unknownMethod"))
+        } else {
+            call(&service, user_id, &name, &arguments).await
+        };
+
+        responses.push(match result {
+            Ok(response) => json!([name, response, call_id]),
+            Err(e) => {
+                let mut error = json!({ "type": e.kind });
+                if let Some(description) = e.description {
+                    error["description"] = json!(description);
+                }
+                json!(["error", error, call_id])
+            }
+        });
+    }
+
+    Ok(Json(json!({
+        "methodResponses": responses,
+        "sessionState": "0",
+    })).into_response())
+}
+
+async fn call(service: &CalendarService, user_id: Uuid, name: &str, arguments: &Value) -> Result<Value, MethodError> {
+    let account_id = arguments.get("accountId").and_then(Value::as_str);
+    if account_id != Some(user_id.to_string().as_str()) {
+        return Err(MethodError::new("accountNotFound"));
+    }
+
+    match name {
+        "Calendar/get" => calendar_get(service, user_id, arguments).await,
+        "Calendar/changes" => calendar_changes(service, user_id, arguments).await,
+        "CalendarEvent/get" => event_get(service, user_id, arguments).await,
+        "CalendarEvent/changes" => event_changes(service, user_id, arguments).await,
+        "CalendarEvent/set" => event_set(service, user_id, arguments).await,
+        _ => Err(MethodError::new("unknownMethod")),
+    }
+}
+
+// States and changes
+
+/// Modification times of a record, for computing states and changes
+struct Record {
+    id: Uuid,
+    created: DateTime<Utc>,
+    updated: DateTime<Utc>,
+}
+
+/// State of a record collection: its latest modification and its size. Creations and
+/// updates since a state are recovered from the timestamps; deletions only show as a
+/// smaller count than expected and are answered with `cannotCalculateChanges`.
+fn state(records: &[Record]) -> String {
+    let latest = records.iter().map(|r| r.updated).max().map(|t| t.timestamp_millis()).unwrap_or(0);
+    format!("{}-{}", latest, records.len())
+}
+
+fn changes(account_id: Uuid, records: &[Record], arguments: &Value) -> Result<Value, MethodError> {
+    let since_state = arguments.get("sinceState").and_then(Value::as_str)
+        .ok_or_else(|| MethodError::invalid_arguments("sinceState is required"))?;
+    let (latest, count) = since_state.split_once('-')
+        .and_then(|(latest, count)| Some((latest.parse::<i64>().ok()?, count.parse::<usize>().ok()?)))
+        .ok_or_else(|| MethodError::new("cannotCalculateChanges"))?;
+
+    let is_newer = |time: DateTime<Utc>| time.timestamp_millis() > latest;
+    let created: Vec<String> = records.iter().filter(|r| is_newer(r.created)).map(|r| r.id.to_string()).collect();
+    let updated: Vec<String> = records.iter()
+        .filter(|r| !is_newer(r.created) && is_newer(r.updated))
+        .map(|r| r.id.to_string())
+        .collect();
+
+    if count + created.len() != records.len() {
+        return Err(MethodError::new("cannotCalculateChanges"));
+    }
+
+    Ok(json!({
+        "accountId": account_id.to_string(),
+        "oldState": since_state,
+        "newState": state(records),
+        "hasMoreChanges": false,
+        "created": created,
+        "updated": updated,
+        "destroyed": [],
+    }))
+}
+
+/// Ids requested by a `/get` call; `None` asks for all records
+fn requested_ids(arguments: &Value) -> Result<Option<Vec<String>>, MethodError> {
+    match arguments.get("ids") {
+        None | Some(Value::Null) => Ok(None),
+        Some(Value::Array(ids)) if ids.len() > MAX_OBJECTS => Err(MethodError::new("requestTooLarge")),
+        Some(Value::Array(ids)) => Ok(Some(ids.iter().filter_map(|id| id.as_str().map(str::to_string)).collect())),
+        Some(_) => Err(MethodError::invalid_arguments("ids must be an array or null")),
+    }
+}
+
+/// `/get` response over `objects` keyed by id, limited to the requested ids and properties
+fn get_response(account_id: Uuid, state: String, objects: Vec<(String, Value)>, arguments: &Value) -> Result<Value, MethodError> {
+    let ids = requested_ids(arguments)?;
+    let properties: Option<Vec<&str>> = arguments.get("properties")
+        .and_then(Value::as_array)
+        .map(|props| props.iter().filter_map(Value::as_str).collect());
+
+    let mut by_id: HashMap<String, Value> = objects.into_iter().collect();
+    let (list, not_found): (Vec<Value>, Vec<String>) = match ids {
+        Some(ids) => {
+            let mut list = Vec::new();
+            let mut not_found = Vec::new();
+            for id in ids {
+                match by_id.remove(&id) {
+                    Some(object) => list.push(object),
+                    None => not_found.push(id),
+                }
+            }
+            (list, not_found)
+        }
+        None => (by_id.into_values().collect(), Vec::new()),
+    };
+
+    let list: Vec<Value> = list.into_iter()
+        .map(|object| match (&properties, object) {
+            (Some(properties), Value::Object(map)) => Value::Object(
+                map.into_iter().filter(|(k, _)| k == "id" || properties.contains(&k.as_str())).collect(),
+            ),
+            (_, object) => object,
+        })
+        .collect();
+
+    Ok(json!({
+        "accountId": account_id.to_string(),
+        "state": state,
+        "list": list,
+        "notFound": not_found,
+    }))
+}
+
+// Calendars
+
+/// The user's own calendars followed by those shared with them
+async fn visible_calendars(service: &CalendarService, user_id: Uuid) -> Result<Vec<(Calendar, PermissionLevel)>, AppError> {
+    let mut calendars: Vec<(Calendar, PermissionLevel)> = service.get_calendars_by_user_id(user_id).await?
+        .into_iter()
+        .map(|calendar| {
+            let permission = calendar.owner_permission();
+            (calendar, permission)
+        })
+        .collect();
+    calendars.extend(service.get_calendars_shared_with_user(user_id).await?);
+    Ok(calendars)
+}
+
+fn calendar_records(calendars: &[(Calendar, PermissionLevel)]) -> Vec<Record> {
+    calendars.iter()
+        .map(|(c, _)| Record { id: c.id, created: c.created_at, updated: c.updated_at })
+        .collect()
+}
+
+fn calendar_object(calendar: &Calendar, permission: PermissionLevel) -> Value {
+    let may_write = permission >= PermissionLevel::Write;
+    let may_admin = permission == PermissionLevel::Admin;
+
+    json!({
+        "id": calendar.id.to_string(),
+        "name": calendar.name,
+        "description": calendar.description,
+        "color": calendar.color,
+        "sortOrder": calendar.sort_order,
+        "isSubscribed": true,
+        "isVisible": true,
+        "myRights": {
+            "mayReadFreeBusy": true,
+            "mayReadItems": true,
+            "mayWriteAll": may_write,
+            "mayWriteOwn": may_write,
+            "mayUpdatePrivate": may_write,
+            "mayRSVP": false,
+            "mayAdmin": may_admin,
+            "mayDelete": may_admin,
+        },
+    })
+}
+
+async fn calendar_get(service: &CalendarService, user_id: Uuid, arguments: &Value) -> Result<Value, MethodError> {
+    let calendars = visible_calendars(service, user_id).await?;
+    let objects = calendars.iter()
+        .map(|(calendar, permission)| (calendar.id.to_string(), calendar_object(calendar, *permission)))
+        .collect();
+
+    get_response(user_id, state(&calendar_records(&calendars)), objects, arguments)
+}
+
+async fn calendar_changes(service: &CalendarService, user_id: Uuid, arguments: &Value) -> Result<Value, MethodError> {
+    let calendars = visible_calendars(service, user_id).await?;
+    changes(user_id, &calendar_records(&calendars), arguments)
+}
+
+// Calendar events
+
+/// Series masters and single events of all visible calendars, each with its
+/// overridden instances
+async fn visible_events(service: &CalendarService, user_id: Uuid) -> Result<Vec<(Event, Vec<Event>)>, AppError> {
+    let mut result = Vec::new();
+    for (calendar, _) in visible_calendars(service, user_id).await? {
+        let events = service.get_events_by_calendar_id(calendar.id).await?;
+        for master in events.iter().filter(|e| !e.is_exception()) {
+            let exceptions = events.iter().filter(|e| e.parent_id == Some(master.id)).cloned().collect();
+            result.push((master.clone(), exceptions));
+        }
+    }
+    Ok(result)
+}
+
+/// An event's record; changing an overridden instance updates its series
+fn event_records(events: &[(Event, Vec<Event>)]) -> Vec<Record> {
+    events.iter()
+        .map(|(master, exceptions)| Record {
+            id: master.id,
+            created: master.created_at,
+            updated: exceptions.iter().map(|e| e.updated_at).fold(master.updated_at, DateTime::max),
+        })
+        .collect()
+}
+
+fn event_object(master: &Event, exceptions: &[Event]) -> Value {
+    let exceptions: Vec<&Event> = exceptions.iter().collect();
+    let mut object = serde_json::to_value(jscalendar::event(master, &exceptions)).unwrap_or_default();
+    object["id"] = json!(master.id.to_string());
+    object["calendarIds"] = json!({ master.calendar_id.to_string(): true });
+    object
+}
+
+async fn event_get(service: &CalendarService, user_id: Uuid, arguments: &Value) -> Result<Value, MethodError> {
+    let events = visible_events(service, user_id).await?;
+    let objects = events.iter()
+        .map(|(master, exceptions)| (master.id.to_string(), event_object(master, exceptions)))
+        .collect();
+
+    get_response(user_id, state(&event_records(&events)), objects, arguments)
+}
+
+async fn event_changes(service: &CalendarService, user_id: Uuid, arguments: &Value) -> Result<Value, MethodError> {
+    let events = visible_events(service, user_id).await?;
+    changes(user_id, &event_records(&events), arguments)
+}
+
+fn invalid(description: &str) -> AppError {
+    AppError::ValidationError(description.to_string())
+}
+
+/// RRULE value for the first JSCalendar recurrence rule of an object
+fn rrule(object: &Map<String, Value>) -> Result<Option<String>, AppError> {
+    let Some(rule) = object.get("recurrenceRules").and_then(Value::as_array).and_then(|rules| rules.first()) else {
+        return Ok(None);
+    };
+
+    let frequency = rule.get("frequency").and_then(Value::as_str)
+        .filter(|f| matches!(*f, "daily" | "weekly" | "monthly" | "yearly"))
+        .ok_or_else(|| invalid("recurrenceRules: unsupported frequency"))?;
+    let mut rrule = format!("FREQ={}", frequency.to_ascii_uppercase());
+    if let Some(interval) = rule.get("interval").and_then(Value::as_u64) {
+        rrule.push_str(&format!(";INTERVAL={}", interval));
+    }
+    if let Some(count) = rule.get("count").and_then(Value::as_u64) {
+        rrule.push_str(&format!(";COUNT={}", count));
+    }
+    if let Some(until) = rule.get("until").and_then(Value::as_str) {
+        let until = jscalendar::parse_local_date_time(until).ok_or_else(|| invalid("recurrenceRules: invalid until"))?;
+        rrule.push_str(&format!(";UNTIL={}", until.format("%Y%m%dT%H%M%SZ")));
+    }
+    Ok(Some(rrule))
+}
+
+fn start(object: &Map<String, Value>) -> Result<Option<DateTime<Utc>>, AppError> {
+    object.get("start").and_then(Value::as_str)
+        .map(|s| jscalendar::parse_local_date_time(s).ok_or_else(|| invalid("start is not a LocalDateTime")))
+        .transpose()
+}
+
+fn duration(object: &Map<String, Value>) -> Result<Option<Duration>, AppError> {
+    object.get("duration").and_then(Value::as_str)
+        .map(|d| jscalendar::parse_duration(d).ok_or_else(|| invalid("duration is not a valid duration")))
+        .transpose()
+}
+
+fn location(object: &Map<String, Value>) -> Option<String> {
+    object.get("locations").and_then(Value::as_object)
+        .and_then(|locations| locations.values().find_map(|l| l.get("name").and_then(Value::as_str)))
+        .map(str::to_string)
+}
+
+async fn create_event(service: &CalendarService, user_id: Uuid, object: &Value) -> Result<Event, AppError> {
+    let object = object.as_object().ok_or_else(|| invalid("Event must be an object"))?;
+    let calendar_id = object.get("calendarIds").and_then(Value::as_object)
+        .and_then(|ids| ids.iter().find(|(_, v)| v.as_bool() == Some(true)).map(|(id, _)| id.clone()))
+        .ok_or_else(|| invalid("calendarIds is required"))?;
+    let calendar = authz::authorize_calendar(service, user_id, Uuid::parse_str(&calendar_id)?, Access::Edit).await?;
+
+    let start_time = start(object)?.ok_or_else(|| invalid("start is required"))?;
+    let end_time = start_time + duration(object)?.unwrap_or_else(Duration::zero);
+
+    service.create_event(calendar.id, NewEvent {
+        title: object.get("title").and_then(Value::as_str).unwrap_or_default().to_string(),
+        description: object.get("description").and_then(Value::as_str).map(str::to_string),
+        location: location(object),
+        start_time,
+        end_time,
+        is_all_day: object.get("showWithoutTime").and_then(Value::as_bool).unwrap_or(false),
+        rrule: rrule(object)?,
+        geo_lat: None,
+        geo_lon: None,
+        alarm_minutes: None,
+    }).await
+}
+
+async fn update_event(service: &CalendarService, user_id: Uuid, id: &str, patch: &Value) -> Result<Event, AppError> {
+    let patch = patch.as_object().ok_or_else(|| invalid("Patch must be an object"))?;
+    let event = authz::authorize_event(service, user_id, Uuid::parse_str(id)?, Access::Edit).await?;
+    if event.is_exception() {
+        return Err(AppError::NotFoundError("Event not found".to_string()));
+    }
+
+    // A new start keeps the duration unless that changes as well
+    let start_time = start(patch)?;
+    let end_time = match (start_time, duration(patch)?) {
+        (start, Some(duration)) => Some(start.unwrap_or(event.start_time) + duration),
+        (Some(start), None) => Some(start + (event.end_time - event.start_time)),
+        (None, None) => None,
+    };
+
+    service.update_event(event.id, UpdateEvent {
+        title: patch.get("title").and_then(Value::as_str).map(str::to_string),
+        // Properties patched to null are cleared
+        description: patch.get("description").map(|d| d.as_str().map(str::to_string)),
+        location: patch.contains_key("locations").then(|| location(patch)),
+        start_time,
+        end_time,
+        is_all_day: patch.get("showWithoutTime").and_then(Value::as_bool),
+        rrule: rrule(patch)?,
+        geo_lat: None,
+        geo_lon: None,
+        alarm_minutes: None,
+    }).await
+}
+
+async fn destroy_event(service: &CalendarService, user_id: Uuid, id: &str) -> Result<(), AppError> {
+    let event = authz::authorize_event(service, user_id, Uuid::parse_str(id)?, Access::Edit).await?;
+    if event.is_exception() {
+        return Err(AppError::NotFoundError("Event not found".to_string()));
+    }
+    service.delete_event(event.id).await
+}
+
+async fn event_set(service: &CalendarService, user_id: Uuid, arguments: &Value) -> Result<Value, MethodError> {
+    let empty = Map::new();
+    let create = arguments.get("create").and_then(Value::as_object).unwrap_or(&empty);
+    let update = arguments.get("update").and_then(Value::as_object).unwrap_or(&empty);
+    let destroy: Vec<&str> = arguments.get("destroy").and_then(Value::as_array)
+        .map(|ids| ids.iter().filter_map(Value::as_str).collect())
+        .unwrap_or_default();
+    if create.len() + update.len() + destroy.len() > MAX_OBJECTS {
+        return Err(MethodError::new("requestTooLarge"));
+    }
+
+    let old_state = state(&event_records(&visible_events(service, user_id).await?));
+    if let Some(if_in_state) = arguments.get("ifInState").and_then(Value::as_str)
+        && if_in_state != old_state
+    {
+        return Err(MethodError::new("stateMismatch"));
+    }
+
+    let mut created = Map::new();
+    let mut not_created = Map::new();
+    for (creation_id, object) in create {
+        match create_event(service, user_id, object).await {
+            Ok(event) => {
+                created.insert(creation_id.clone(), json!({ "id": event.id.to_string() }));
+            }
+            Err(e) => {
+                not_created.insert(creation_id.clone(), set_error(e));
+            }
+        }
+    }
+
+    let mut updated = Map::new();
+    let mut not_updated = Map::new();
+    for (id, patch) in update {
+        match update_event(service, user_id, id, patch).await {
+            Ok(_) => {
+                updated.insert(id.clone(), Value::Null);
+            }
+            Err(e) => {
+                not_updated.insert(id.clone(), set_error(e));
+            }
+        }
+    }
+
+    let mut destroyed = Vec::new();
+    let mut not_destroyed = Map::new();
+    for id in destroy {
+        match destroy_event(service, user_id, id).await {
+            Ok(()) => destroyed.push(id.to_string()),
+            Err(e) => {
+                not_destroyed.insert(id.to_string(), set_error(e));
+            }
+        }
+    }
+
+    Ok(json!({
+        "accountId": user_id.to_string(),
+        "oldState": old_state,
+        "newState": state(&event_records(&visible_events(service, user_id).await?)),
+        "created": created,
+        "updated": updated,
+        "destroyed": destroyed,
+        "notCreated": not_created,
+        "notUpdated": not_updated,
+        "notDestroyed": not_destroyed,
+    }))
+}
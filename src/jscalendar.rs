@@ -0,0 +1,279 @@
+//! Export to JSCalendar (RFC 8984), the JSON calendar format JMAP for Calendars
+//! builds on
+//!
+//! A calendar becomes a `Group` whose entries are its events and tasks. Overridden
+//! instances of a series and EXDATEs become `recurrenceOverrides` of the series
+//! master, keyed by the instance's original start.
+
+use std::collections::BTreeMap;
+use chrono::{DateTime, Duration, Utc};
+use serde::Serialize;
+use serde_json::{json, Value};
+use crate::models::{Calendar, Event, Task};
+use crate::recurrence::Rule;
+
+/// Media type of JSCalendar documents
+pub const CONTENT_TYPE: &str = "application/jscalendar+json;type=group";
+
+const PROD_ID: &str = "-//My CalDAV Server//EN";
+
+/// Stored times are UTC; all-day events are exported as floating dates
+pub(crate) const TIME_ZONE: &str = "Etc/UTC";
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Group {
+    #[serde(rename = "@type")]
+    pub kind: &'static str,
+    pub uid: String,
+    pub prod_id: &'static str,
+    pub title: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub color: Option<String>,
+    pub updated: DateTime<Utc>,
+    pub entries: Vec<Entry>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(untagged)]
+pub enum Entry {
+    Event(JsEvent),
+    Task(JsTask),
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct JsEvent {
+    #[serde(rename = "@type")]
+    pub kind: &'static str,
+    pub uid: String,
+    pub title: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    pub start: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub time_zone: Option<&'static str>,
+    pub duration: String,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub show_without_time: bool,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub locations: BTreeMap<String, Value>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub recurrence_rules: Vec<Value>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub recurrence_overrides: BTreeMap<String, Value>,
+    pub created: DateTime<Utc>,
+    pub updated: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct JsTask {
+    #[serde(rename = "@type")]
+    pub kind: &'static str,
+    pub uid: String,
+    pub title: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub due: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub time_zone: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub progress: Option<&'static str>,
+    pub created: DateTime<Utc>,
+    pub updated: DateTime<Utc>,
+}
+
+/// JSCalendar `LocalDateTime`, e.g. `2030-01-01T09:00:00`
+pub(crate) fn local_date_time(time: DateTime<Utc>) -> String {
+    time.format("%Y-%m-%dT%H:%M:%S").to_string()
+}
+
+/// ISO 8601 duration as restricted by RFC 8984, e.g. `PT1H30M` or `P1D`
+pub(crate) fn format_duration(duration: Duration) -> String {
+    let total = duration.num_seconds().max(0);
+    let (days, rest) = (total / 86_400, total % 86_400);
+    let (hours, minutes, seconds) = (rest / 3600, rest % 3600 / 60, rest % 60);
+
+    let mut result = String::from("P");
+    if days > 0 {
+        result.push_str(&format!("{}D", days));
+    }
+    if rest > 0 || days == 0 {
+        result.push('T');
+        if hours > 0 {
+            result.push_str(&format!("{}H", hours));
+        }
+        if minutes > 0 {
+            result.push_str(&format!("{}M", minutes));
+        }
+        if seconds > 0 || rest == 0 {
+            result.push_str(&format!("{}S", seconds));
+        }
+    }
+    result
+}
+
+/// Parse a `LocalDateTime` (or a UTC date-time with a trailing `Z`) as UTC
+#[cfg(feature = "jmap")]
+pub(crate) fn parse_local_date_time(value: &str) -> Option<DateTime<Utc>> {
+    chrono::NaiveDateTime::parse_from_str(value.trim_end_matches('Z'), "%Y-%m-%dT%H:%M:%S").ok().map(|dt| dt.and_utc())
+}
+
+/// Parse a duration such as `PT1H30M` or `P1DT12H`; weeks are accepted as well
+pub(crate) fn parse_duration(value: &str) -> Option<Duration> {
+    let rest = value.strip_prefix('P')?;
+    let (date, time) = rest.split_once('T').unwrap_or((rest, ""));
+
+    let mut total = Duration::zero();
+    let mut number = String::new();
+    for (part, is_time) in [(date, false), (time, true)] {
+        for c in part.chars() {
+            if c.is_ascii_digit() {
+                number.push(c);
+                continue;
+            }
+            let n: i64 = number.parse().ok()?;
+            number.clear();
+            total += match (c, is_time) {
+                ('W', false) => Duration::weeks(n),
+                ('D', false) => Duration::days(n),
+                ('H', true) => Duration::hours(n),
+                ('M', true) => Duration::minutes(n),
+                ('S', true) => Duration::seconds(n),
+                _ => return None,
+            };
+        }
+    }
+
+    number.is_empty().then_some(total)
+}
+
+fn recurrence_rule(rule: &Rule) -> Value {
+    let mut value = json!({
+        "@type": "RecurrenceRule",
+        "frequency": rule.frequency(),
+    });
+    if rule.interval() != 1 {
+        value["interval"] = json!(rule.interval());
+    }
+    if let Some(count) = rule.count() {
+        value["count"] = json!(count);
+    }
+    if let Some(until) = rule.until() {
+        value["until"] = json!(local_date_time(until));
+    }
+    value
+}
+
+/// Patch an overridden instance applies to its series master
+fn override_patch(master: &Event, exception: &Event) -> Value {
+    let mut patch = serde_json::Map::new();
+    if exception.start_time != exception.recurrence_id.unwrap_or(master.start_time) {
+        patch.insert("start".to_string(), json!(local_date_time(exception.start_time)));
+    }
+    if exception.end_time - exception.start_time != master.end_time - master.start_time {
+        patch.insert("duration".to_string(), json!(format_duration(exception.end_time - exception.start_time)));
+    }
+    if exception.title != master.title {
+        patch.insert("title".to_string(), json!(exception.title));
+    }
+    if exception.description != master.description {
+        patch.insert("description".to_string(), json!(exception.description.clone().unwrap_or_default()));
+    }
+    Value::Object(patch)
+}
+
+/// A series master or single event, with `exceptions` as its overridden instances
+pub(crate) fn event(event: &Event, exceptions: &[&Event]) -> JsEvent {
+    let mut locations = BTreeMap::new();
+    if event.location.is_some() || event.geo_lat.is_some() {
+        let mut location = json!({ "@type": "Location" });
+        if let Some(name) = &event.location {
+            location["name"] = json!(name);
+        }
+        if let Some((lat, lon)) = event.geo_lat.zip(event.geo_lon) {
+            location["coordinates"] = json!(format!("geo:{},{}", lat, lon));
+        }
+        locations.insert("1".to_string(), location);
+    }
+
+    let recurrence_rules = event.rrule.as_deref()
+        .and_then(Rule::parse)
+        .map(|rule| vec![recurrence_rule(&rule)])
+        .unwrap_or_default();
+
+    let mut recurrence_overrides = BTreeMap::new();
+    for exdate in &event.exdates {
+        recurrence_overrides.insert(local_date_time(*exdate), json!({ "excluded": true }));
+    }
+    for exception in exceptions {
+        if let Some(recurrence_id) = exception.recurrence_id {
+            recurrence_overrides.insert(local_date_time(recurrence_id), override_patch(event, exception));
+        }
+    }
+
+    JsEvent {
+        kind: "Event",
+        uid: event.id.to_string(),
+        title: event.title.clone(),
+        description: event.description.clone(),
+        start: local_date_time(event.start_time),
+        time_zone: (!event.is_all_day).then_some(TIME_ZONE),
+        duration: format_duration(event.end_time - event.start_time),
+        show_without_time: event.is_all_day,
+        locations,
+        recurrence_rules,
+        recurrence_overrides,
+        created: event.created_at,
+        updated: event.updated_at,
+    }
+}
+
+fn task(task: &Task) -> JsTask {
+    let progress = match task.status.as_deref() {
+        Some("COMPLETED") => Some("completed"),
+        Some("IN-PROCESS") => Some("in-process"),
+        Some("CANCELLED") => Some("cancelled"),
+        Some("NEEDS-ACTION") => Some("needs-action"),
+        _ => None,
+    };
+
+    JsTask {
+        kind: "Task",
+        uid: task.uid.clone().unwrap_or_else(|| task.id.to_string()),
+        title: task.summary.clone().unwrap_or_default(),
+        description: task.description.clone(),
+        due: task.due.map(local_date_time),
+        time_zone: task.due.map(|_| TIME_ZONE),
+        progress,
+        created: task.created_at,
+        updated: task.updated_at,
+    }
+}
+
+/// JSCalendar group of a calendar with all its events and tasks
+pub fn group(calendar: &Calendar, events: &[Event], tasks: &[Task]) -> Group {
+    let mut entries: Vec<Entry> = events.iter()
+        .filter(|e| !e.is_exception())
+        .map(|master| {
+            let exceptions: Vec<&Event> = events.iter().filter(|e| e.parent_id == Some(master.id)).collect();
+            Entry::Event(event(master, &exceptions))
+        })
+        .collect();
+    entries.extend(tasks.iter().map(|t| Entry::Task(task(t))));
+
+    Group {
+        kind: "Group",
+        uid: calendar.id.to_string(),
+        prod_id: PROD_ID,
+        title: calendar.name.clone(),
+        description: calendar.description.clone(),
+        color: calendar.color.clone(),
+        updated: calendar.updated_at,
+        entries,
+    }
+}
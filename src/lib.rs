@@ -1,56 +1,187 @@
 use axum::{
-    routing::{get, post, put, delete, any},
+    routing::{get, post, put, patch, delete, any},
     Router,
-    middleware::from_fn,
+    middleware::{from_fn, from_fn_with_state},
+    extract::DefaultBodyLimit,
     Extension,
 };
+use sqlx::sqlite::SqlitePool;
 use std::net::SocketAddr;
+use std::sync::Arc;
 use tokio::net::TcpListener;
 use tracing::info;
-use tower_http::trace::TraceLayer;
+use tower_http::request_id::{MakeRequestUuid, PropagateRequestIdLayer, SetRequestIdLayer};
+use tower_http::trace::{DefaultOnResponse, TraceLayer};
 use tower_http::services::ServeDir;
 
+mod authz;
+mod backup;
+mod client_profile;
+mod clock;
+mod compression;
 mod config;
+mod cors;
+mod dav;
+mod dav_usage;
+mod edit_conflicts;
+mod etag;
 mod error;
+mod event_csv;
+mod event_history;
+mod flash;
+mod free_busy;
+mod geo;
 mod handlers;
+mod holidays;
+mod i18n;
+mod ical_check;
+mod import;
+#[cfg(feature = "jmap")]
+mod jmap;
+mod jscalendar;
+mod lockout;
+mod logging;
+mod mail;
+mod maintenance;
 mod models;
+mod oidc;
+mod onboarding;
+mod outlook;
+mod push;
+mod quota;
+mod rate_limit;
+mod recurrence;
+mod reminders;
 mod services;
+mod settings;
 mod middleware;
 mod state;
 mod database;
+mod storage;
+mod streaming;
+mod sync_log;
+mod devices;
+mod takeout;
+mod tls;
 mod ui;
+mod validation;
 
+pub use crate::clock::{Clock, MockClock, SystemClock};
 pub use crate::config::Config;
 pub use crate::error::AppError;
+pub use crate::i18n::{missing_keys, Language};
+pub use crate::mail::{Mail, Mailer, MemoryMailer, SmtpMailer};
 pub use crate::services::CalendarService;
+pub use crate::database::initialize_database;
 
 pub async fn run() -> Result<(), Box<dyn std::error::Error>> {
     // Initialize tracing
-    tracing_subscriber::fmt::init();
+    logging::init();
 
-    // Load configuration
-    let config = Config::from_env().unwrap_or_default();
+    // Load configuration, refusing to start on invalid settings
+    let mut config = Config::load()?;
     
     // Ensure data directory exists
     std::fs::create_dir_all("./data")?;
     
     // Create database connection pool
-    let pool = sqlx::sqlite::SqlitePool::connect(&config.database_url).await?;
+    let pool = database::connect(&config.database_url, &config.database_pool).await?;
     
     // Initialize database
     database::initialize_database(&pool).await?;
     
     info!("Database initialized successfully");
     
-    let service = services::CalendarService::new(pool);
-    let auth_config = middleware::AuthConfig::new(config.jwt_secret.clone());
+    // The BASE_URL environment variable takes precedence over the URL set during setup,
+    // which takes precedence over the first ACME domain
+    if config.base_url.is_empty() {
+        config.base_url = services::CalendarService::new(pool.clone()).get_settings().await?.base_url;
+    }
+    if config.base_url.is_empty() {
+        config.base_url = config.tls.as_ref().and_then(|tls| tls.base_url()).unwrap_or_default();
+    }
+    
+    let pruning_service = services::CalendarService::new(pool.clone()).with_retention(config.retention.clone());
+    maintenance::spawn_pruning(pruning_service, std::time::Duration::from_secs(config.prune_interval_secs));
+    let backup_service = services::CalendarService::new(pool.clone()).with_backups(config.backup.clone());
+    backup::spawn_backups(backup_service, std::time::Duration::from_secs(config.backup.interval_secs));
+    reminders::spawn_reminders(services::CalendarService::new(pool.clone()));
+
+    let app = build_app_with_config(pool, &config);
+
+    // Run server
+    let addr = SocketAddr::from(([0, 0, 0, 0], config.port));
+
+    if let Some(tls) = &config.tls {
+        info!("Listening on {} (HTTPS)", addr);
+        return tls::serve(addr, app, tls).await;
+    }
+
+    info!("Listening on {}", addr);
+    let listener = TcpListener::bind(addr).await?;
+    axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>()).await?;
+
+    Ok(())
+}
+
+/// Replace the configured database with `backup`, keeping the current one next to it.
+/// Run only while the server is stopped.
+pub fn restore_backup(backup: &std::path::Path) -> Result<std::path::PathBuf, Box<dyn std::error::Error>> {
+    let config = Config::load()?;
+    backup::restore(&config.database_url, backup)
+}
+
+/// Application router on an initialized database, with the default configuration.
+/// Used by the integration tests, which drive it without binding a socket.
+pub fn build_app(pool: SqlitePool) -> Router {
+    build_app_with_config(pool, &Config::default())
+}
+
+/// Application router with all routes and middleware, configured from `config`
+pub fn build_app_with_config(pool: SqlitePool, config: &Config) -> Router {
+    build_app_with_clock(pool, config, Arc::new(SystemClock))
+}
+
+/// Application router telling time by `clock`, so tests can control token expiry,
+/// upcoming events and everything else time-dependent
+pub fn build_app_with_clock(pool: SqlitePool, config: &Config, clock: Arc<dyn Clock>) -> Router {
+    build_app_with_mailer(pool, config, clock, Arc::new(SmtpMailer))
+}
+
+/// Application router sending notification mail through `mailer`, so tests can
+/// read what would have been sent
+pub fn build_app_with_mailer(pool: SqlitePool, config: &Config, clock: Arc<dyn Clock>, mailer: Arc<dyn Mailer>) -> Router {
+    let attachment_storage = storage::AttachmentStorage::new(&config.attachments_dir, config.max_attachment_size);
+    let service = services::CalendarService::new(pool)
+        .with_jwt_secret(config.jwt_secret.clone())
+        .with_attachment_storage(attachment_storage)
+        .with_onboarding(config.onboarding.clone())
+        .with_oidc(config.oidc.clone().map(oidc::OidcClient::new))
+        .with_base_url(config.base_url.clone())
+        .with_retention(config.retention.clone())
+        .with_settings_overrides(config.settings_overrides.clone())
+        .with_backups(config.backup.clone())
+        .with_quotas(config.quotas.clone())
+        .with_dav_usage(dav_usage::DavUsage::new(config.dav_usage_stats))
+        .with_lockout(config.lockout.clone())
+        .with_mailer(mailer)
+        .with_clock(clock.clone());
+    // Leave room for the multipart framing around the largest accepted file
+    let upload_limit = DefaultBodyLimit::max(config.max_attachment_size + 64 * 1024);
+    let takeout_limit = DefaultBodyLimit::max(takeout::MAX_ARCHIVE_SIZE + 64 * 1024);
+    let ics_limit = DefaultBodyLimit::max(config.quotas.max_ics_size);
+    let auth_config = middleware::AuthConfig::new(config.jwt_secret.clone()).with_clock(clock);
+    let cors = cors::cors_layer(&config.cors_origins);
+    let rate_limiter = rate_limit::RateLimiter::new(config.rate_limit_requests, config.rate_limit_window_secs);
     
     // Build the application with routes
-    let app = Router::new()
+    let router = Router::new()
         // Public routes (no authentication required)
         .route("/", get(handlers::root))
         .route("/health", get(handlers::health))
-        .route("/.well-known/caldav", get(handlers::caldav_discovery))
+        // First-run setup wizard (only available while no account exists)
+        .route("/setup", get(handlers::web::setup_page).post(handlers::web::setup_handler))
+        .route("/.well-known/caldav", any(dav::caldav_discovery))
         .route("/api/auth/login", post(handlers::auth::login))
         .route("/api/auth/register", post(handlers::auth::register))
         // User routes
@@ -59,75 +190,222 @@ pub async fn run() -> Result<(), Box<dyn std::error::Error>> {
         .route("/api/calendars/{id}", get(handlers::get_calendar_by_id))
         .route("/api/auth/calendars", get(handlers::auth::get_user_calendars).post(handlers::auth::create_calendar))
         .route("/api/auth/calendars/{id}", put(handlers::update_calendar).delete(handlers::delete_calendar))
+        .route("/api/auth/calendars/{id}/resync", post(handlers::resync_calendar))
+        .route("/api/auth/calendars/{id}/transfer", post(handlers::transfer_calendar))
         .route("/api/auth/calendars/{id}/events", get(handlers::auth::get_events))
+        .route("/api/auth/calendars/{id}/deleted-events", get(handlers::get_deleted_events))
+        .route("/api/auth/calendars/{id}/tasks", get(handlers::auth::get_tasks))
+        .route("/api/auth/calendars/{id}/journals", get(handlers::auth::get_journals))
+        .route("/api/auth/calendars/{id}/import", post(handlers::import_calendar_ics))
+        .route("/api/auth/calendars/{id}/events.csv", get(handlers::export_calendar_csv))
+        .route("/api/auth/calendars/{id}/import/csv", post(handlers::import_calendar_csv))
+        .route("/api/auth/calendars/{id}/import/csv/preview", post(handlers::preview_calendar_csv))
+        .route("/api/auth/import/google", post(handlers::import_google_takeout).layer(takeout_limit))
+        .route("/api/auth/ical/validate", post(handlers::validate_ical).layer(ics_limit))
         // Event routes
         .route("/api/events/{id}", get(handlers::get_event_by_id))
         .route("/api/auth/events", post(handlers::auth::create_event))
+        .route("/api/auth/events/conflicts", get(handlers::find_conflicts))
+        .route("/api/auth/events/batch", post(handlers::batch_events))
         .route("/api/auth/events/{id}", get(handlers::auth::get_event).put(handlers::update_event).delete(handlers::delete_event))
+        .route("/api/auth/events/{id}/occurrence", put(handlers::update_event_occurrence).delete(handlers::delete_event_occurrence))
+        .route("/api/auth/events/{id}/occurrence/detach", post(handlers::detach_event_occurrence))
+        .route("/api/auth/events/{id}/duplicate", post(handlers::duplicate_event))
+        .route("/api/auth/events/{id}/history", get(handlers::get_event_history))
+        .route("/api/auth/events/{id}/history/{version}/restore", post(handlers::restore_event_version))
+        .route("/api/auth/events/{id}/edit-conflicts", get(handlers::get_edit_conflicts))
+        .route("/api/auth/events/{id}/edit-conflicts/{conflict}/resolve", post(handlers::resolve_edit_conflict))
+        .route("/api/auth/sync/commit", post(handlers::commit_sync_batch))
+        // Attachment routes
+        .route("/api/auth/events/{id}/attachments", get(handlers::get_event_attachments).post(handlers::upload_event_attachment).layer(upload_limit))
+        .route("/api/auth/attachments/{id}", delete(handlers::delete_attachment))
+        .route("/dav/attachments/{id}", get(handlers::download_attachment))
         // Share routes
         .route("/api/auth/calendars/{id}/shares", get(handlers::get_calendar_shares).post(handlers::create_share))
         .route("/api/auth/shares/{id}", delete(handlers::delete_share))
+        .route("/api/auth/calendars/{id}/share-links", get(handlers::get_share_links).post(handlers::create_share_link))
+        .route("/api/auth/share-links/{id}", delete(handlers::revoke_share_link))
+        .route("/api/auth/share-links/redeem", post(handlers::redeem_share_link))
+        .route("/api/auth/groups", get(handlers::get_groups).post(handlers::create_group))
+        .route("/api/auth/groups/{id}", get(handlers::get_group).delete(handlers::delete_group))
+        .route("/api/auth/groups/{id}/members", get(handlers::get_group_members).post(handlers::add_group_member))
+        .route("/api/auth/groups/{id}/members/{user_id}", put(handlers::update_group_member).delete(handlers::remove_group_member))
+        .route("/api/auth/groups/{id}/calendars", post(handlers::create_group_calendar))
+        .route("/api/auth/delegations", get(handlers::get_delegations).post(handlers::create_delegation))
+        .route("/api/auth/delegations/{id}", delete(handlers::delete_delegation))
+        .route("/api/auth/notification-preferences", get(handlers::get_notification_preferences))
+        .route("/api/auth/notification-preferences/{calendar_id}", put(handlers::update_notification_preference))
+        .route("/api/auth/settings", get(handlers::get_user_settings).put(handlers::update_user_settings))
+        .route("/api/auth/calendar-preferences", get(handlers::get_calendar_preferences).put(handlers::reorder_calendars))
+        .route("/api/auth/calendar-preferences/{calendar_id}", put(handlers::update_calendar_preference))
+        .route("/share/{token}", get(handlers::web::open_share_link_handler))
+        // Feed token routes
+        .route("/api/auth/calendars/{id}/feeds", get(handlers::get_feed_tokens).post(handlers::create_feed_token))
+        .route("/api/auth/feeds/{id}", delete(handlers::revoke_feed_token))
+        .route("/api/auth/user-feeds", get(handlers::get_user_feed_tokens).post(handlers::create_user_feed_token))
+        .route("/api/auth/user-feeds/{id}", delete(handlers::revoke_user_feed_token))
+        .route("/feeds/{token}", get(handlers::get_feed))
+        .route("/feeds/{token}/all.ics", get(handlers::get_user_feed))
+        // Subscription routes
+        .route("/api/auth/subscriptions", get(handlers::get_subscriptions).post(handlers::create_subscription))
+        .route("/api/auth/subscriptions/{id}", patch(handlers::update_subscription).delete(handlers::delete_subscription))
+        // Holiday pack routes
+        .route("/api/auth/holiday-packs", get(handlers::get_holiday_packs))
+        .route("/api/auth/holiday-packs/{id}", put(handlers::enable_holiday_pack).delete(handlers::disable_holiday_pack))
+        // Contact routes
+        .route("/api/auth/contacts", get(handlers::get_contacts).post(handlers::create_contact))
+        .route("/api/auth/contacts/{id}", put(handlers::update_contact).delete(handlers::delete_contact))
         // Admin routes
         .route("/api/admin/users", get(handlers::auth::admin_get_all_users).post(handlers::auth::admin_create_user))
         .route("/api/admin/users/{id}", delete(handlers::auth::admin_delete_user))
         .route("/api/admin/users/{id}/role", post(handlers::auth::admin_update_user_role))
+        .route("/api/admin/users/{id}/impersonate", post(handlers::auth::admin_impersonate_user))
+        .route("/api/admin/users/{id}/purge", post(handlers::auth::admin_purge_user_data))
+        .route("/api/admin/audit-log", get(handlers::auth::admin_get_audit_log))
+        .route("/api/admin/maintenance/prune", post(handlers::auth::admin_prune_expired_data))
+        .route("/api/admin/dav-usage", get(handlers::auth::admin_get_dav_usage))
         // Public API routes (no authentication required)
         .route("/api/public/calendars", get(handlers::get_public_calendars))
         .route("/api/public/calendars/{id}", get(handlers::get_public_calendar_by_id))
         .route("/api/public/calendars/{id}/events", get(handlers::get_public_calendar_events))
+        .route("/api/public/events/nearby", get(handlers::search_public_events_nearby))
         // ICS Export
         .route("/api/calendars/{id}/export", get(handlers::export_calendar_ics))
         // Search
         .route("/api/auth/search/events", get(handlers::search_events))
+        // Insights
+        .route("/api/auth/forecast", get(handlers::get_workload_forecast))
         // QR Code generation
         .route("/api/qr/calendar/{id}", get(handlers::get_calendar_qr_code))
         .route("/api/qr/event/{id}", get(handlers::get_event_qr_code))
         // CalDAV routes (support both JWT and Basic Auth)
-        .route("/calendars", any(handlers::caldav_propfind))
-        .route("/calendars/", any(handlers::caldav_propfind))
-        .route("/calendars/{id}", any(handlers::caldav_get))
-        .route("/calendars/{id}/", any(handlers::caldav_get))
-        .route("/calendars/{id}/{event}", any(handlers::caldav_get))
-        // MKCOL for creating calendars via CalDAV
-        .route("/calendars/new", any(handlers::caldav_mkcol))
+        .route("/calendars", any(dav::dispatch))
+        .route("/calendars/", any(dav::dispatch))
+        .route("/calendars/{*path}", any(dav::dispatch))
+        // Group principals
+        .route("/principals/groups/{id}", any(dav::group_principal))
+        .route("/principals/groups/{id}/", any(dav::group_principal))
+        // Read-only anonymous CalDAV access to public calendars
+        .route("/public-dav", any(dav::public_dav_root))
+        .route("/public-dav/", any(dav::public_dav_root))
+        .route("/public-dav/{id}", any(dav::public_dav_calendar))
+        .route("/public-dav/{id}/", any(dav::public_dav_calendar))
+        .route("/public-dav/{id}/{event}", any(dav::public_dav_event))
+        // WebDAV-Push subscription management
+        .route("/dav/push/{id}", delete(dav::caldav_push_unregister))
         // Web UI routes - Authentication (form-based for SSR)
         .route("/web/login", get(handlers::web::login_page).post(handlers::web::login_handler))
+        .route("/web/login/oidc", get(handlers::web::oidc_login_handler))
+        .route("/web/login/oidc/callback", get(handlers::web::oidc_callback_handler))
         .route("/web/register", get(handlers::web::register_page).post(handlers::web::register_handler))
         .route("/web/logout", get(handlers::web::logout_handler))
+        .route("/web/theme", post(handlers::web::update_theme_handler))
         // Web UI routes - Dashboard
         .route("/web/dashboard", get(handlers::web::dashboard_page))
         // Web UI routes - Calendars
         .route("/web/calendars", get(handlers::web::calendars_page))
+        .route("/web/calendars/order", post(handlers::web::reorder_calendars_handler))
         .route("/web/calendars/new", get(handlers::web::new_calendar_page).post(handlers::web::create_calendar_handler))
         .route("/web/calendars/{id}", get(handlers::web::calendar_detail_page))
         .route("/web/calendars/{id}/edit", get(handlers::web::edit_calendar_page).post(handlers::web::update_calendar_handler))
         .route("/web/calendars/{id}/delete", post(handlers::web::delete_calendar_handler))
+        .route("/web/calendars/{id}/default", post(handlers::web::set_default_calendar_handler))
+        .route("/web/calendars/{id}/visibility", post(handlers::web::update_calendar_visibility_handler))
+        .route("/web/calendars/{id}/resync", post(handlers::web::resync_calendar_handler))
+        .route("/web/calendars/{id}/deleted", get(handlers::web::deleted_events_page))
+        .route("/web/calendars/{id}/transfer", post(handlers::web::transfer_calendar_handler))
+        .route("/web/calendars/{id}/journal", get(handlers::web::journal_page).post(handlers::web::create_journal_handler))
+        .route("/web/calendars/{id}/journal/{journal_id}/delete", post(handlers::web::delete_journal_handler))
+        .route("/web/groups", get(handlers::web::groups_page).post(handlers::web::create_group_handler))
+        .route("/web/groups/{id}", get(handlers::web::group_detail_page))
+        .route("/web/groups/{id}/delete", post(handlers::web::delete_group_handler))
+        .route("/web/groups/{id}/members", post(handlers::web::add_group_member_handler))
+        .route("/web/groups/{id}/members/{user_id}/role", post(handlers::web::update_group_member_handler))
+        .route("/web/groups/{id}/members/{user_id}/remove", post(handlers::web::remove_group_member_handler))
+        .route("/web/groups/{id}/calendars", post(handlers::web::create_group_calendar_handler))
         // Web UI routes - Events
         .route("/web/events", get(handlers::web::events_page))
+        .route("/web/schedule", get(handlers::web::schedule_page))
         .route("/web/events/new", get(handlers::web::new_event_page).post(handlers::web::create_event_handler))
+        .route("/web/events/quick", get(handlers::web::event_dialog).post(handlers::web::create_event_from_dialog))
         .route("/web/events/{id}/edit", get(handlers::web::edit_event_page).post(handlers::web::update_event_handler))
         .route("/web/events/{id}/delete", post(handlers::web::delete_event_handler))
+        .route("/web/events/{id}/detach", post(handlers::web::detach_occurrence_handler))
+        .route("/web/events/{id}/duplicate", post(handlers::web::duplicate_event_handler))
+        .route("/web/events/{id}/history", get(handlers::web::event_history_page))
+        .route("/web/events/{id}/history/{version}/restore", post(handlers::web::restore_event_version_handler))
+        .route("/web/events/{id}/edit-conflicts", get(handlers::web::edit_conflicts_page))
+        .route("/web/events/{id}/edit-conflicts/{conflict}/resolve", post(handlers::web::resolve_edit_conflict_handler))
+        .route("/web/events/{id}/attachments", post(handlers::web::upload_attachment_handler).layer(upload_limit))
+        .route("/web/attachments/{id}/delete", post(handlers::web::delete_attachment_handler))
+        // Web UI routes - Contacts
+        .route("/web/contacts", get(handlers::web::contacts_page).post(handlers::web::create_contact_handler))
+        .route("/web/contacts/{id}/delete", post(handlers::web::delete_contact_handler))
+        .route("/web/import/google", get(handlers::web::import_google_page).post(handlers::web::import_google_handler).layer(takeout_limit))
+        // Web UI routes - Insights
+        .route("/web/insights", get(handlers::web::insights_page))
+        .route("/web/settings", get(handlers::web::settings_page))
+        .route("/web/settings/connection.mobileconfig", get(handlers::web::download_mobileconfig_handler))
+        .route("/web/settings/connection.json", get(handlers::web::download_connection_json_handler))
+        .route("/web/settings/sync-log", get(handlers::web::sync_log_page).post(handlers::web::update_sync_log_handler))
+        .route("/web/settings/sync-log/clear", post(handlers::web::clear_sync_log_handler))
+        .route("/web/settings/devices", get(handlers::web::devices_page))
+        .route("/web/settings/devices/{id}/revoke", post(handlers::web::revoke_device_handler))
+        .route("/web/settings/devices/{id}/remove", post(handlers::web::remove_device_handler))
+        .route("/web/settings/identities/link", post(handlers::web::link_identity_handler))
+        .route("/web/settings/identities/{id}/unlink", post(handlers::web::unlink_identity_handler))
+        .route("/web/settings/delegations", post(handlers::web::create_delegation_handler))
+        .route("/web/settings/language", post(handlers::web::update_language_handler))
+        .route("/web/settings/time", post(handlers::web::update_time_settings_handler))
+        .route("/web/settings/week", post(handlers::web::update_week_settings_handler))
+        .route("/web/settings/notifications/{calendar_id}", post(handlers::web::update_notification_preference_handler))
+        .route("/web/settings/holidays/{id}", post(handlers::web::update_holiday_pack_handler))
+        .route("/web/settings/delegations/{id}/delete", post(handlers::web::delete_delegation_handler))
         // Web UI routes - Shares
         .route("/web/calendars/{id}/shares", post(handlers::web::create_share_handler))
+        .route("/web/calendars/{id}/fragments/shares", get(handlers::web::share_list_fragment))
+        .route("/web/calendars/{id}/fragments/events", get(handlers::web::event_list_fragment))
         .route("/web/shares/{id}/delete", post(handlers::web::delete_share_handler))
+        .route("/web/calendars/{id}/share-links", post(handlers::web::create_share_link_handler))
+        .route("/web/share-links/continue", get(handlers::web::continue_share_link_handler))
+        .route("/web/share-links/{id}/revoke", post(handlers::web::revoke_share_link_handler))
         // Web UI routes - Admin
         .route("/web/admin", get(handlers::web::admin_page))
+        .route("/web/admin/backups", get(handlers::web::admin_backups_page).post(handlers::web::create_backup_handler))
+        .route("/web/admin/backups/{name}", get(handlers::web::download_backup_handler))
         .route("/web/admin/users/{id}/role", post(handlers::web::update_user_role_handler))
+        .route("/web/admin/users/{id}/impersonate", post(handlers::web::impersonate_user_handler))
+        .route("/web/admin/users/{id}/purge", post(handlers::web::purge_user_data_handler))
+        .route("/web/impersonation/stop", post(handlers::web::stop_impersonation_handler))
         // Static files
-        .nest_service("/static", ServeDir::new("static"))
-        .with_state(service)
-        .layer(TraceLayer::new_for_http())
-        .layer(from_fn(middleware::cors_middleware))
-        .layer(from_fn(middleware::logging_middleware))
-        .layer(from_fn(middleware::auth_middleware))
-        .layer(Extension(auth_config));
+        .nest_service("/static", ServeDir::new("static"));
 
-    // Run server
-    let addr = SocketAddr::from(([0, 0, 0, 0], config.port));
-    info!("Listening on {}", addr);
-    
-    let listener = TcpListener::bind(addr).await?;
-    axum::serve(listener, app).await?;
+    // Experimental JMAP for Calendars endpoint
+    #[cfg(feature = "jmap")]
+    let router = router
+        .route("/.well-known/jmap", get(jmap::well_known))
+        .route("/jmap/session", get(jmap::session))
+        .route("/jmap/api", post(jmap::api));
 
-    Ok(())
+    router
+        .with_state(service.clone())
+        .layer(from_fn(middleware::error_page_middleware))
+        // Outside the error pages, so they are rendered with the user's settings too
+        .layer(from_fn_with_state(service.clone(), middleware::user_settings_middleware))
+        .layer(from_fn_with_state(service.clone(), middleware::flash_middleware))
+        .layer(from_fn_with_state(service, middleware::impersonation_middleware))
+        .layer(from_fn_with_state(rate_limiter, middleware::rate_limit_middleware))
+        .layer(from_fn(middleware::auth_middleware))
+        .layer(Extension(auth_config))
+        // Outside authentication, so preflights are answered without credentials
+        .layer(from_fn_with_state(cors, middleware::cors_middleware))
+        .layer(compression::compression_layer(config.compression))
+        // Outermost, so the request span covers all middleware
+        .layer(
+            TraceLayer::new_for_http()
+                .make_span_with(logging::request_span)
+                .on_response(DefaultOnResponse::new().level(tracing::Level::INFO)),
+        )
+        .layer(PropagateRequestIdLayer::new(logging::REQUEST_ID_HEADER))
+        .layer(SetRequestIdLayer::new(logging::REQUEST_ID_HEADER, MakeRequestUuid))
 }
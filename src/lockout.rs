@@ -0,0 +1,49 @@
+//! Locking accounts after repeated failed logins
+//!
+//! Every password check — web and API sign-in as well as CalDAV Basic auth — goes
+//! through `CalendarService::authenticate`, which records the attempt. After
+//! `max_failed_logins` consecutive failures the account is locked for
+//! `lockout_minutes`, and its owner is told by mail.
+
+use serde::{Deserialize, Serialize};
+
+/// Default consecutive failed logins that lock an account
+pub const DEFAULT_MAX_FAILED_LOGINS: u32 = 5;
+
+/// Default minutes an account stays locked
+pub const DEFAULT_LOCKOUT_MINUTES: u32 = 15;
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct LockoutPolicy {
+    /// Consecutive failed logins that lock the account; 0 disables locking
+    pub max_failed_logins: u32,
+    pub lockout_minutes: u32,
+}
+
+impl Default for LockoutPolicy {
+    fn default() -> Self {
+        Self {
+            max_failed_logins: DEFAULT_MAX_FAILED_LOGINS,
+            lockout_minutes: DEFAULT_LOCKOUT_MINUTES,
+        }
+    }
+}
+
+/// Where a password was checked
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LoginSource {
+    Web,
+    Api,
+    /// CalDAV Basic auth, checked on every request; only failures are recorded
+    Dav,
+}
+
+impl LoginSource {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LoginSource::Web => "web",
+            LoginSource::Api => "api",
+            LoginSource::Dav => "dav",
+        }
+    }
+}
@@ -0,0 +1,55 @@
+//! Log output and per-request tracing spans
+//!
+//! Every request runs in a `request` span carrying its `x-request-id` (taken from the
+//! client or generated), so everything logged by middleware and handlers while serving
+//! it can be correlated. The ID is echoed in the response headers.
+
+use axum::{extract::Request, http::HeaderName};
+use tracing::Span;
+use tracing_subscriber::EnvFilter;
+
+/// Header carrying the request ID
+pub const REQUEST_ID_HEADER: HeaderName = HeaderName::from_static("x-request-id");
+
+/// Level used when neither `RUST_LOG` nor `LOG_LEVEL` is set
+const DEFAULT_LOG_LEVEL: &str = "info";
+
+/// Install the global subscriber. The filter comes from `RUST_LOG` or else `LOG_LEVEL`
+/// (a level or full filter directives such as `info,sqlx=warn`); `LOG_FORMAT=json`
+/// switches from human-readable lines to one JSON object per event.
+pub fn init() {
+    let directives = std::env::var("RUST_LOG")
+        .or_else(|_| std::env::var("LOG_LEVEL"))
+        .unwrap_or_else(|_| DEFAULT_LOG_LEVEL.to_string());
+    let filter = EnvFilter::try_new(&directives).unwrap_or_else(|e| {
+        eprintln!("Invalid log filter {:?} ({}), using {}", directives, e, DEFAULT_LOG_LEVEL);
+        EnvFilter::new(DEFAULT_LOG_LEVEL)
+    });
+
+    let builder = tracing_subscriber::fmt().with_env_filter(filter);
+    match std::env::var("LOG_FORMAT").as_deref() {
+        Ok("json") => builder.json().with_current_span(true).with_span_list(false).init(),
+        _ => builder.init(),
+    }
+}
+
+/// Span for one request. `user_id` is filled in once the request is authenticated.
+pub fn request_span(req: &Request) -> Span {
+    let request_id = req.headers()
+        .get(&REQUEST_ID_HEADER)
+        .and_then(|id| id.to_str().ok())
+        .unwrap_or_default();
+
+    tracing::info_span!(
+        "request",
+        request_id = %request_id,
+        method = %req.method(),
+        path = %req.uri().path(),
+        user_id = tracing::field::Empty,
+    )
+}
+
+/// Attach the authenticated user to the current request span
+pub fn record_user(user_id: uuid::Uuid) {
+    Span::current().record("user_id", tracing::field::display(user_id));
+}
@@ -0,0 +1,87 @@
+//! Outgoing mail through the SMTP server from the instance settings
+//!
+//! Notifications are best effort: without a configured mail server, or when delivery
+//! fails, the message is logged and dropped. Tests swap in a [`MemoryMailer`] to see
+//! what would have been sent.
+
+use std::fmt::Debug;
+use std::sync::{Arc, Mutex};
+
+use async_trait::async_trait;
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+
+use crate::settings::SmtpSettings;
+
+/// Port on which SMTP servers expect TLS from the start instead of STARTTLS
+const IMPLICIT_TLS_PORT: u16 = 465;
+
+/// A plain-text mail to one recipient
+#[derive(Debug, Clone, PartialEq)]
+pub struct Mail {
+    pub to: String,
+    pub subject: String,
+    pub body: String,
+}
+
+#[async_trait]
+pub trait Mailer: Debug + Send + Sync {
+    /// Deliver `mail` through `smtp`; `None` means no mail server is configured
+    async fn send(&self, smtp: Option<&SmtpSettings>, mail: Mail) -> Result<(), String>;
+}
+
+/// Delivers mail through the configured SMTP server
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SmtpMailer;
+
+#[async_trait]
+impl Mailer for SmtpMailer {
+    async fn send(&self, smtp: Option<&SmtpSettings>, mail: Mail) -> Result<(), String> {
+        let smtp = smtp.ok_or("no mail server is configured")?;
+
+        let message = Message::builder()
+            .from(smtp.from_address.parse().map_err(|e| format!("invalid sender {:?}: {}", smtp.from_address, e))?)
+            .to(mail.to.parse().map_err(|e| format!("invalid recipient {:?}: {}", mail.to, e))?)
+            .subject(mail.subject)
+            .body(mail.body)
+            .map_err(|e| e.to_string())?;
+
+        let builder = if smtp.port == IMPLICIT_TLS_PORT {
+            AsyncSmtpTransport::<Tokio1Executor>::relay(&smtp.host)
+        } else {
+            AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&smtp.host)
+        };
+        let mut builder = builder.map_err(|e| e.to_string())?.port(smtp.port);
+        if let (Some(username), Some(password)) = (&smtp.username, &smtp.password) {
+            builder = builder.credentials(Credentials::new(username.clone(), password.clone()));
+        }
+
+        builder.build().send(message).await.map_err(|e| e.to_string())?;
+        Ok(())
+    }
+}
+
+/// Keeps mail in memory instead of sending it. Clones share the same outbox.
+#[derive(Debug, Clone, Default)]
+pub struct MemoryMailer {
+    sent: Arc<Mutex<Vec<Mail>>>,
+}
+
+impl MemoryMailer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Mail "sent" so far, oldest first
+    pub fn sent(&self) -> Vec<Mail> {
+        self.sent.lock().unwrap().clone()
+    }
+}
+
+#[async_trait]
+impl Mailer for MemoryMailer {
+    async fn send(&self, _smtp: Option<&SmtpSettings>, mail: Mail) -> Result<(), String> {
+        self.sent.lock().unwrap().push(mail);
+        Ok(())
+    }
+}
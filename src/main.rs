@@ -1,9 +1,22 @@
-use my_cal_dav_server::run;
+use my_cal_dav_server::{restore_backup, run};
 
 #[tokio::main]
 async fn main() {
-    if let Err(err) = run().await {
+    let args: Vec<String> = std::env::args().collect();
+
+    let result = match args.get(1).map(String::as_str) {
+        // Replace the configured database with a backup; the server must be stopped
+        Some("restore") => match args.get(2) {
+            Some(backup) => restore_backup(std::path::Path::new(backup)).map(|database| {
+                println!("Restored {} to {}", backup, database.display());
+            }),
+            None => Err("Usage: my_cal_dav_server restore <backup-file>".into()),
+        },
+        _ => run().await,
+    };
+
+    if let Err(err) = result {
         eprintln!("Error: {}", err);
         std::process::exit(1);
     }
-}
\ No newline at end of file
+}
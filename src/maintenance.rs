@@ -0,0 +1,132 @@
+//! Scheduled pruning of records that have outlived their use, so the database
+//! doesn't grow without bound, and of data left behind by deleted records
+
+use std::time::Duration;
+use serde::{Deserialize, Serialize};
+use crate::event_history::DEFAULT_EVENT_VERSIONS;
+use crate::services::CalendarService;
+
+/// Default time between pruning runs in seconds
+pub const DEFAULT_PRUNE_INTERVAL_SECS: u64 = 3600;
+
+/// Default days to keep sign-in attempts
+pub const DEFAULT_LOGIN_ATTEMPT_DAYS: u32 = 90;
+
+/// How long records are kept before they are pruned
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct RetentionPolicy {
+    /// Days to keep audit log entries; `None` keeps them forever
+    pub audit_log_days: Option<u32>,
+    /// Earlier versions kept per event, the oldest going first; 0 turns event history off
+    pub event_versions: usize,
+    /// Days to keep earlier versions of events; `None` keeps them until they are
+    /// pushed out by newer ones
+    pub event_version_days: Option<u32>,
+    /// Days to keep sign-in attempts, which only feed the account's login history
+    pub login_attempt_days: u32,
+}
+
+impl Default for RetentionPolicy {
+    fn default() -> Self {
+        Self {
+            audit_log_days: None,
+            event_versions: DEFAULT_EVENT_VERSIONS,
+            event_version_days: None,
+            login_attempt_days: DEFAULT_LOGIN_ATTEMPT_DAYS,
+        }
+    }
+}
+
+/// Age below which an attachment file without a row is left alone, as its upload may
+/// still be in progress
+pub const ORPHANED_FILE_GRACE: Duration = Duration::from_secs(3600);
+
+/// Columns holding the `id` of a row in another table, as (table, column, referenced
+/// table), parents first. The schema deletes rows with their parent; rows written while
+/// foreign keys weren't enforced can still point at nothing and are pruned. The audit
+/// log keeps naming deleted users on purpose, and event versions outlive their event.
+pub const REFERENCES: [(&str, &str, &str); 35] = [
+    ("calendars", "user_id", "users"),
+    ("calendars", "group_id", "user_groups"),
+    ("events", "calendar_id", "calendars"),
+    ("events", "parent_id", "events"),
+    ("attachments", "event_id", "events"),
+    ("edit_conflicts", "event_id", "events"),
+    ("event_versions", "calendar_id", "calendars"),
+    ("tasks", "calendar_id", "calendars"),
+    ("journals", "calendar_id", "calendars"),
+    ("shares", "calendar_id", "calendars"),
+    ("shares", "user_id", "users"),
+    ("shares", "shared_with_user_id", "users"),
+    ("share_links", "calendar_id", "calendars"),
+    ("share_links", "created_by", "users"),
+    ("feed_tokens", "calendar_id", "calendars"),
+    ("feed_tokens", "created_by", "users"),
+    ("feed_token_clients", "token_id", "feed_tokens"),
+    ("user_feed_tokens", "user_id", "users"),
+    ("push_subscriptions", "calendar_id", "calendars"),
+    ("push_subscriptions", "user_id", "users"),
+    ("notification_preferences", "calendar_id", "calendars"),
+    ("notification_preferences", "user_id", "users"),
+    ("calendar_preferences", "calendar_id", "calendars"),
+    ("calendar_preferences", "user_id", "users"),
+    ("group_members", "group_id", "user_groups"),
+    ("group_members", "user_id", "users"),
+    ("delegations", "owner_id", "users"),
+    ("delegations", "delegate_id", "users"),
+    ("user_identities", "user_id", "users"),
+    ("calendar_subscriptions", "user_id", "users"),
+    ("contacts", "user_id", "users"),
+    ("login_attempts", "user_id", "users"),
+    ("user_settings", "user_id", "users"),
+    ("sync_log", "user_id", "users"),
+    ("devices", "user_id", "users"),
+];
+
+/// Rows removed by one pruning run, per kind of record
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct PruneReport {
+    pub expired_push_subscriptions: u64,
+    pub audit_log_entries: u64,
+    pub event_versions: u64,
+    pub login_attempts: u64,
+    /// Rows pointing at a deleted row, and groups without members
+    pub orphaned_rows: u64,
+    /// Attachment files whose attachment no longer exists
+    pub orphaned_attachment_files: u64,
+}
+
+impl PruneReport {
+    pub fn total(&self) -> u64 {
+        self.expired_push_subscriptions + self.audit_log_entries + self.event_versions + self.login_attempts + self.orphaned_rows + self.orphaned_attachment_files
+    }
+}
+
+/// Prune every `interval` for as long as the server runs, starting right away.
+/// A zero interval disables scheduled pruning.
+pub fn spawn_pruning(service: CalendarService, interval: Duration) {
+    if interval.is_zero() {
+        tracing::info!("Scheduled pruning is disabled");
+        return;
+    }
+
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            match service.prune_expired_data(service.now()).await {
+                Ok(report) => tracing::info!(
+                    expired_push_subscriptions = report.expired_push_subscriptions,
+                    audit_log_entries = report.audit_log_entries,
+                    event_versions = report.event_versions,
+                    login_attempts = report.login_attempts,
+                    orphaned_rows = report.orphaned_rows,
+                    orphaned_attachment_files = report.orphaned_attachment_files,
+                    total = report.total(),
+                    "Pruned expired data"
+                ),
+                Err(e) => tracing::error!("Pruning expired data failed: {:?}", e),
+            }
+        }
+    });
+}
@@ -1,16 +1,25 @@
 use axum::{
-    extract::Request,
-    http::{header, StatusCode},
+    extract::{ConnectInfo, Request, State},
+    http::{header, HeaderValue, Method, StatusCode},
     middleware::Next,
     response::{IntoResponse, Response},
     Extension,
 };
 use jsonwebtoken::{decode, DecodingKey, Validation};
 use serde::{Deserialize, Serialize};
-use tracing::info;
+use tracing::{info, warn};
 use uuid::Uuid;
 use base64::{engine::general_purpose::STANDARD as BASE64_STANDARD, Engine};
+use std::sync::Arc;
+use crate::clock::{Clock, SystemClock};
+use crate::flash::{with_flash, FLASH_COOKIE};
+use crate::i18n::{translate, Language};
 use crate::models::UserRole;
+use crate::services::CalendarService;
+use crate::settings::{Theme, UserSettings, THEME_COOKIE};
+use crate::rate_limit::{RateLimitStatus, RateLimiter};
+use tower::{Layer, ServiceExt};
+use tower_http::cors::CorsLayer;
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct Claims {
@@ -18,16 +27,25 @@ pub struct Claims {
     pub exp: usize,   // Expiration time
     pub iat: usize,   // Issued at
     pub role: Option<String>,  // User role (admin/user)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub impersonator: Option<String>,  // Admin user id for impersonation tokens
 }
 
 #[derive(Clone)]
 pub struct AuthConfig {
     pub jwt_secret: String,
+    /// Clock token expiry is checked against
+    pub clock: Arc<dyn Clock>,
 }
 
 impl AuthConfig {
     pub fn new(jwt_secret: String) -> Self {
-        Self { jwt_secret }
+        Self { jwt_secret, clock: Arc::new(SystemClock) }
+    }
+
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
     }
 }
 
@@ -45,6 +63,12 @@ impl UserRoleExt {
     }
 }
 
+/// Present on requests made with an admin impersonation token
+#[derive(Debug, Clone, Copy)]
+pub struct Impersonation {
+    pub admin_id: Uuid,
+}
+
 /// Result of parsing Basic Auth credentials
 #[derive(Debug, Clone)]
 pub struct BasicAuthCredentials {
@@ -76,7 +100,6 @@ fn parse_basic_auth(header_value: &str) -> Option<BasicAuthCredentials> {
 /// Check if the path is a CalDAV endpoint that should support Basic Auth
 fn is_caldav_endpoint(path: &str) -> bool {
     path.starts_with("/calendars") 
-        || path == "/.well-known/caldav"
         || path.starts_with("/dav")
         || path.starts_with("/principals")
 }
@@ -98,10 +121,14 @@ pub async fn auth_middleware(
         || path == "/";
     
     // Check if this is a web route that requires authentication
-    let is_web_route = path.starts_with("/web/") && !path.starts_with("/web/login") && !path.starts_with("/web/register");
+    let is_web_route = path.starts_with("/web/")
+        && !path.starts_with("/web/login")
+        && !path.starts_with("/web/register")
+        && path != "/web/theme";
     
     // Check if this is an API route that requires authentication
-    let is_api_route = path.starts_with("/api/auth/") && !path.starts_with("/api/auth/login") && !path.starts_with("/api/auth/register");
+    let is_api_route = (path.starts_with("/api/auth/") && !path.starts_with("/api/auth/login") && !path.starts_with("/api/auth/register"))
+        || path.starts_with("/api/users/");
     
     // Check if this is a CalDAV route
     let is_caldav = is_caldav_endpoint(path);
@@ -111,11 +138,7 @@ pub async fn auth_middleware(
     // Try to get token from Authorization header or Cookie
     let token = if let Some(auth_header) = req.headers().get(header::AUTHORIZATION) {
         let auth_str = auth_header.to_str().unwrap_or_default();
-        if auth_str.starts_with("Bearer ") {
-            Some(auth_str["Bearer ".len()..].to_string())
-        } else {
-            None
-        }
+        auth_str.strip_prefix("Bearer ").map(str::to_string)
     } else if let Some(cookie_header) = req.headers().get(header::COOKIE) {
         // Parse cookie for auth_token
         let cookie_str = cookie_header.to_str().unwrap_or_default();
@@ -126,13 +149,18 @@ pub async fn auth_middleware(
     
     // Try to authenticate with token
     if let Some(token) = token {
-        let validation = Validation::new(jsonwebtoken::Algorithm::HS256);
+        // Expiry is checked against the configured clock below
+        let mut validation = Validation::new(jsonwebtoken::Algorithm::HS256);
+        validation.validate_exp = false;
         
         match decode::<Claims>(
             &token,
             &DecodingKey::from_secret(auth_config.jwt_secret.as_bytes()),
             &validation
         ) {
+            Ok(decoded) if decoded.claims.exp <= auth_config.clock.now().timestamp() as usize => {
+                info!("Token validation failed: token expired");
+            }
             Ok(decoded) => {
                 // Parse user_id from claims
                 if let Ok(user_id) = Uuid::parse_str(&decoded.claims.sub) {
@@ -142,9 +170,13 @@ pub async fn auth_middleware(
                         "admin" => UserRole::Admin,
                         _ => UserRole::User,
                     };
+                    crate::logging::record_user(user_id);
                     req.extensions_mut().insert(user_id);
                     req.extensions_mut().insert(OptionalUser(Some(user_id)));
                     req.extensions_mut().insert(UserRoleExt(role));
+                    if let Some(admin_id) = decoded.claims.impersonator.as_deref().and_then(|id| Uuid::parse_str(id).ok()) {
+                        req.extensions_mut().insert(Impersonation { admin_id });
+                    }
                     return next.run(req).await;
                 }
             }
@@ -155,13 +187,13 @@ pub async fn auth_middleware(
     }
     
     // Try Basic Auth (primarily for CalDAV endpoints)
-    if let Some(auth_header) = req.headers().get(header::AUTHORIZATION) {
-        if let Some(credentials) = parse_basic_auth(auth_header.to_str().unwrap_or_default()) {
-            // Store credentials in request extensions for handlers to use
-            req.extensions_mut().insert(credentials);
-            req.extensions_mut().insert(OptionalUser(None));
-            return next.run(req).await;
-        }
+    if let Some(auth_header) = req.headers().get(header::AUTHORIZATION)
+        && let Some(credentials) = parse_basic_auth(auth_header.to_str().unwrap_or_default())
+    {
+        // Store credentials in request extensions for handlers to use
+        req.extensions_mut().insert(credentials);
+        req.extensions_mut().insert(OptionalUser(None));
+        return next.run(req).await;
     }
     
     // Add OptionalUser(None) for unauthenticated requests
@@ -196,41 +228,283 @@ pub async fn auth_middleware(
 
 /// Parse auth_token from cookie string
 fn parse_auth_cookie(cookie_str: &str) -> Option<String> {
+    parse_cookie(cookie_str, "auth_token")
+}
+
+/// Parse a named cookie from a Cookie header value
+pub fn parse_cookie(cookie_str: &str, name: &str) -> Option<String> {
     for cookie in cookie_str.split(';') {
-        let cookie = cookie.trim();
-        if cookie.starts_with("auth_token=") {
-            return Some(cookie["auth_token=".len()..].to_string());
+        if let Some((key, value)) = cookie.trim().split_once('=')
+            && key == name
+        {
+            return Some(value.to_string());
         }
     }
     None
 }
 
-/// Middleware to add CORS headers to responses
-pub async fn cors_middleware(req: Request, next: Next) -> Response {
+/// Apply the CORS policy to cross-origin API and CalDAV requests. It runs before
+/// authentication so preflights are answered without credentials.
+pub async fn cors_middleware(
+    State(cors): State<Option<CorsLayer>>,
+    req: Request,
+    next: Next,
+) -> Response {
+    match cors {
+        Some(cors) if crate::cors::applies_to(&req) => match cors.layer(next).oneshot(req).await {
+            Ok(response) => response,
+            Err(never) => match never {},
+        },
+        _ => next.run(req).await,
+    }
+}
+
+/// Paths where an impersonation token may change data: events with their attachments,
+/// history and conflict copies, and ending the impersonation
+const IMPERSONATION_WRITABLE_PATHS: &[&str] = &[
+    "/api/auth/events",
+    "/api/auth/attachments/",
+    "/api/auth/sync/commit",
+    "/web/events/",
+    "/web/attachments/",
+    "/web/impersonation/stop",
+];
+
+/// Whether an impersonation token may make a request. The admin can look around and
+/// edit events for the user, but not hand out access (shares, share links,
+/// delegations, feed tokens), give calendars away, or use admin functions.
+fn is_allowed_while_impersonating(method: &Method, path: &str) -> bool {
+    if path.starts_with("/api/admin") || path.starts_with("/web/admin") {
+        return false;
+    }
+    // Opening a share link redeems it for the user
+    if path.starts_with("/share/") || path.starts_with("/web/share-links/") {
+        return false;
+    }
+    if matches!(method.as_str(), "GET" | "HEAD" | "OPTIONS" | "PROPFIND" | "REPORT") {
+        return true;
+    }
+    // CalDAV clients edit events object by object
+    let dav_object = path.starts_with("/calendars/") && path.ends_with(".ics");
+    (dav_object && matches!(method.as_str(), "PUT" | "DELETE"))
+        || IMPERSONATION_WRITABLE_PATHS.iter().any(|prefix| path.starts_with(prefix))
+}
+
+/// Audit every request made with an impersonation token and flag HTML pages with a banner
+pub async fn impersonation_middleware(
+    State(service): State<CalendarService>,
+    req: Request,
+    next: Next,
+) -> Response {
+    let Some(impersonation) = req.extensions().get::<Impersonation>().copied() else {
+        return next.run(req).await;
+    };
+    let user_id = req.extensions().get::<Uuid>().copied();
+    let path = req.uri().path().to_string();
+    let method = req.method().clone();
+    // The banner is read by the admin, whose browser may prefer another language
+    let language = req.headers()
+        .get(header::ACCEPT_LANGUAGE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(Language::negotiate)
+        .unwrap_or_default();
+
+    if let Err(e) = service
+        .record_audit(impersonation.admin_id, user_id, "impersonated_request", Some(&format!("{} {}", method, path)))
+        .await
+    {
+        warn!("Failed to record impersonation audit entry: {}", e);
+        return (StatusCode::INTERNAL_SERVER_ERROR, "Audit logging failed").into_response();
+    }
+
+    if !is_allowed_while_impersonating(&method, &path) {
+        return (StatusCode::FORBIDDEN, "Not available while impersonating").into_response();
+    }
+
     let response = next.run(req).await;
-    
+
+    let is_html = response.headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.starts_with("text/html"));
+    if !is_html {
+        return response;
+    }
+
     let (mut parts, body) = response.into_parts();
-    
-    parts.headers.insert(
-        header::ACCESS_CONTROL_ALLOW_ORIGIN,
-        "*".parse().unwrap(),
-    );
-    parts.headers.insert(
-        header::ACCESS_CONTROL_ALLOW_METHODS,
-        "GET, POST, PUT, DELETE, OPTIONS, PROPFIND, REPORT, MKCOL".parse().unwrap(),
-    );
-    parts.headers.insert(
-        header::ACCESS_CONTROL_ALLOW_HEADERS,
-        "Authorization, Content-Type, Accept, Depth, Prefer".parse().unwrap(),
-    );
-    
-    Response::from_parts(parts, body)
+    let Ok(bytes) = axum::body::to_bytes(body, usize::MAX).await else {
+        return (StatusCode::INTERNAL_SERVER_ERROR, "Failed to read response").into_response();
+    };
+    let html = String::from_utf8_lossy(&bytes).replacen("<body>", &format!("<body>{}", impersonation_banner(language)), 1);
+    parts.headers.remove(header::CONTENT_LENGTH);
+
+    Response::from_parts(parts, axum::body::Body::from(html))
 }
 
-/// Middleware for logging requests
-pub async fn logging_middleware(req: Request, next: Next) -> Response {
-    info!("Incoming request: {} {}", req.method(), req.uri().path());
-    let response = next.run(req).await;
-    info!("Response status: {}", response.status());
+fn impersonation_banner(language: Language) -> String {
+    format!(
+        r#"<div class="impersonation-banner">{} <form method="post" action="/web/impersonation/stop" class="inline-form"><button type="submit" class="btn btn-sm btn-danger">{}</button></form></div>"#,
+        translate(language, "impersonation.banner"),
+        translate(language, "impersonation.stop"),
+    )
+}
+
+/// Render web UI pages with the signed-in user's settings, or for signed-out visitors
+/// with the theme remembered in their cookie. Without a chosen language the page is
+/// rendered in the one negotiated from `Accept-Language`.
+pub async fn user_settings_middleware(
+    State(service): State<CalendarService>,
+    req: Request,
+    next: Next,
+) -> Response {
+    if !req.uri().path().starts_with("/web/") && req.uri().path() != "/setup" {
+        return next.run(req).await;
+    }
+
+    let mut settings = match req.extensions().get::<Uuid>() {
+        Some(user_id) => service.get_user_settings(*user_id).await.unwrap_or_else(|e| {
+            warn!("Failed to load user settings: {}", e);
+            UserSettings::default()
+        }),
+        None => UserSettings {
+            theme: req.headers()
+                .get(header::COOKIE)
+                .and_then(|cookie| parse_cookie(cookie.to_str().unwrap_or_default(), THEME_COOKIE))
+                .map(|theme| Theme::from_str(&theme))
+                .unwrap_or_default(),
+            ..UserSettings::default()
+        },
+    };
+    // Pages are rendered in the chosen language, else the one the browser prefers
+    settings.language = settings.language.or_else(|| {
+        req.headers()
+            .get(header::ACCEPT_LANGUAGE)
+            .and_then(|value| value.to_str().ok())
+            .and_then(Language::negotiate)
+    }).or(Some(Language::default()));
+
+    crate::ui::with_user_settings(settings, next.run(req)).await
+}
+
+/// Offer the flash from a valid flash cookie to the web UI page being rendered, and
+/// clear the cookie once a page has shown it
+pub async fn flash_middleware(
+    State(service): State<CalendarService>,
+    req: Request,
+    next: Next,
+) -> Response {
+    if !req.uri().path().starts_with("/web/") && req.uri().path() != "/setup" {
+        return next.run(req).await;
+    }
+
+    let flash = req.headers()
+        .get(header::COOKIE)
+        .and_then(|cookie| parse_cookie(cookie.to_str().unwrap_or_default(), FLASH_COOKIE))
+        .and_then(|token| service.verify_flash(&token));
+    let (mut response, taken) = with_flash(flash, next.run(req)).await;
+
+    // A response that leaves a new flash keeps its own cookie
+    let sets_flash = response.headers()
+        .get_all(header::SET_COOKIE)
+        .iter()
+        .any(|value| value.to_str().is_ok_and(|value| value.starts_with(&format!("{}=", FLASH_COOKIE))));
+    if taken && !sets_flash {
+        let clear = format!("{}=; Path=/; HttpOnly; SameSite=Lax; Max-Age=0", FLASH_COOKIE);
+        if let Ok(value) = HeaderValue::from_str(&clear) {
+            response.headers_mut().append(header::SET_COOKIE, value);
+        }
+    }
     response
 }
+
+/// Whether a request hits an endpoint expensive enough to be rate limited
+/// (ICS export and feeds, REPORT queries and search)
+fn is_rate_limited(method: &str, path: &str) -> bool {
+    method == "REPORT"
+        || path.ends_with("/export")
+        || path.ends_with("/events.csv")
+        || path.starts_with("/feeds/")
+        || path.starts_with("/api/auth/search")
+        || path == "/api/public/events/nearby"
+}
+
+/// Identify the client a request is counted against: the user of a verified token or,
+/// for everyone else, the peer address. Basic Auth credentials are only checked by the
+/// handler, so the account they name could be anyone's.
+fn rate_limit_key(req: &Request) -> String {
+    if let Some(user_id) = req.extensions().get::<Uuid>() {
+        return format!("user:{}", user_id);
+    }
+    match req.extensions().get::<ConnectInfo<std::net::SocketAddr>>() {
+        Some(ConnectInfo(addr)) => format!("ip:{}", addr.ip()),
+        None => "anonymous".to_string(),
+    }
+}
+
+fn apply_rate_limit_headers(response: &mut Response, status: &RateLimitStatus) {
+    let headers = response.headers_mut();
+    headers.insert("X-RateLimit-Limit", status.limit.into());
+    headers.insert("X-RateLimit-Remaining", status.remaining.into());
+    headers.insert("X-RateLimit-Reset", status.reset.into());
+}
+
+/// Count expensive requests per client, advertising the remaining budget in
+/// `X-RateLimit-*` headers and answering 429 with `Retry-After` once it is used up
+pub async fn rate_limit_middleware(
+    State(limiter): State<RateLimiter>,
+    req: Request,
+    next: Next,
+) -> Response {
+    if !is_rate_limited(req.method().as_str(), req.uri().path()) {
+        return next.run(req).await;
+    }
+
+    let status = limiter.check(&rate_limit_key(&req));
+
+    let mut response = if status.allowed {
+        next.run(req).await
+    } else {
+        warn!("Rate limit exceeded for {} {}", req.method(), req.uri().path());
+        let body = serde_json::json!({
+            "code": "rate_limited",
+            "message": "Rate limit exceeded",
+            "status": StatusCode::TOO_MANY_REQUESTS.as_u16(),
+            "retry_after": status.retry_after,
+        });
+        let mut response = (StatusCode::TOO_MANY_REQUESTS, axum::Json(body)).into_response();
+        response.headers_mut().insert(header::RETRY_AFTER, status.retry_after.into());
+        response
+    };
+
+    apply_rate_limit_headers(&mut response, &status);
+    response
+}
+
+/// Replace the JSON error bodies of web UI routes (and their bare 404s) with a styled
+/// HTML error page quoting the request ID. Scripts asking for JSON, like the event
+/// dialog, get the error as it is.
+pub async fn error_page_middleware(req: Request, next: Next) -> Response {
+    let wants_json = req.headers()
+        .get(header::ACCEPT)
+        .and_then(|accept| accept.to_str().ok())
+        .is_some_and(|accept| accept.contains("application/json"));
+    if !req.uri().path().starts_with("/web/") || wants_json {
+        return next.run(req).await;
+    }
+    let request_id = req.headers()
+        .get(&crate::logging::REQUEST_ID_HEADER)
+        .and_then(|id| id.to_str().ok())
+        .map(str::to_string);
+
+    let response = next.run(req).await;
+    let status = response.status();
+    let replaceable = match response.headers().get(header::CONTENT_TYPE) {
+        Some(content_type) => content_type.as_bytes().starts_with(b"application/json"),
+        None => true,
+    };
+    if !(status.is_client_error() || status.is_server_error()) || !replaceable {
+        return response;
+    }
+
+    crate::handlers::web::error_page(status, request_id)
+}
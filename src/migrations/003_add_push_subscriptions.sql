@@ -0,0 +1,16 @@
+-- Add change tag to calendars so clients can detect collection changes
+ALTER TABLE calendars ADD COLUMN ctag INTEGER NOT NULL DEFAULT 0;
+
+-- Create WebDAV-Push subscriptions table
+CREATE TABLE IF NOT EXISTS push_subscriptions (
+    id TEXT PRIMARY KEY,
+    calendar_id TEXT NOT NULL,
+    user_id TEXT NOT NULL,
+    push_resource TEXT NOT NULL,
+    expires_at TEXT NOT NULL,
+    created_at TEXT NOT NULL,
+    FOREIGN KEY (calendar_id) REFERENCES calendars (id) ON DELETE CASCADE,
+    FOREIGN KEY (user_id) REFERENCES users (id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_push_subscriptions_calendar_id ON push_subscriptions (calendar_id);
@@ -0,0 +1,9 @@
+-- Add recurrence support to events
+ALTER TABLE events ADD COLUMN rrule TEXT;
+ALTER TABLE events ADD COLUMN exdates TEXT;
+
+-- Overridden instances of a recurring event reference their series master
+ALTER TABLE events ADD COLUMN parent_id TEXT REFERENCES events (id) ON DELETE CASCADE;
+ALTER TABLE events ADD COLUMN recurrence_id TEXT;
+
+CREATE INDEX IF NOT EXISTS idx_events_parent_id ON events (parent_id);
@@ -0,0 +1,5 @@
+-- Add GEO coordinates (WGS84 degrees) to events
+ALTER TABLE events ADD COLUMN geo_lat REAL;
+ALTER TABLE events ADD COLUMN geo_lon REAL;
+
+CREATE INDEX IF NOT EXISTS idx_events_geo ON events (geo_lat, geo_lon);
@@ -0,0 +1,2 @@
+-- Add calendar ordering (exposed to CalDAV clients as calendar-order)
+ALTER TABLE calendars ADD COLUMN sort_order INTEGER NOT NULL DEFAULT 0;
@@ -0,0 +1,29 @@
+-- Create stores for imported VTODO and VJOURNAL components
+CREATE TABLE IF NOT EXISTS tasks (
+    id TEXT PRIMARY KEY,
+    calendar_id TEXT NOT NULL,
+    uid TEXT,
+    summary TEXT,
+    description TEXT,
+    due TEXT,
+    status TEXT,
+    created_at TEXT NOT NULL,
+    updated_at TEXT NOT NULL,
+    FOREIGN KEY (calendar_id) REFERENCES calendars (id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_tasks_calendar_id ON tasks (calendar_id);
+
+CREATE TABLE IF NOT EXISTS journals (
+    id TEXT PRIMARY KEY,
+    calendar_id TEXT NOT NULL,
+    uid TEXT,
+    summary TEXT,
+    description TEXT,
+    dtstart TEXT,
+    created_at TEXT NOT NULL,
+    updated_at TEXT NOT NULL,
+    FOREIGN KEY (calendar_id) REFERENCES calendars (id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_journals_calendar_id ON journals (calendar_id);
@@ -0,0 +1,6 @@
+-- Instance settings written by the first-run setup wizard (key/value pairs)
+CREATE TABLE IF NOT EXISTS settings (
+    key TEXT PRIMARY KEY,
+    value TEXT NOT NULL,
+    updated_at TEXT NOT NULL
+);
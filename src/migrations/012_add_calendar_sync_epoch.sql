@@ -0,0 +1,3 @@
+-- Add a re-sync counter to calendars; bumping it changes every object's ETag so
+-- clients download the whole calendar again
+ALTER TABLE calendars ADD COLUMN sync_epoch INTEGER NOT NULL DEFAULT 0;
@@ -0,0 +1,26 @@
+-- Links to a calendar's read-only iCalendar feed; each one can be revoked on its own
+CREATE TABLE IF NOT EXISTS feed_tokens (
+    id TEXT PRIMARY KEY,
+    calendar_id TEXT NOT NULL,
+    created_by TEXT NOT NULL,
+    label TEXT NOT NULL,
+    created_at TEXT NOT NULL,
+    last_fetched_at TEXT,
+    fetch_count INTEGER NOT NULL DEFAULT 0,
+    revoked_at TEXT,
+    FOREIGN KEY (calendar_id) REFERENCES calendars (id) ON DELETE CASCADE,
+    FOREIGN KEY (created_by) REFERENCES users (id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_feed_tokens_calendar_id ON feed_tokens (calendar_id);
+
+-- Addresses a feed was fetched from
+CREATE TABLE IF NOT EXISTS feed_token_clients (
+    token_id TEXT NOT NULL,
+    ip TEXT NOT NULL,
+    first_fetched_at TEXT NOT NULL,
+    last_fetched_at TEXT NOT NULL,
+    fetch_count INTEGER NOT NULL DEFAULT 1,
+    PRIMARY KEY (token_id, ip),
+    FOREIGN KEY (token_id) REFERENCES feed_tokens (id) ON DELETE CASCADE
+);
@@ -0,0 +1,15 @@
+-- Links to one read-only feed merging several of a user's calendars
+CREATE TABLE IF NOT EXISTS user_feed_tokens (
+    id TEXT PRIMARY KEY,
+    user_id TEXT NOT NULL,
+    label TEXT NOT NULL,
+    -- Comma-separated calendar IDs; NULL includes every calendar the user owns
+    calendar_ids TEXT,
+    created_at TEXT NOT NULL,
+    last_fetched_at TEXT,
+    fetch_count INTEGER NOT NULL DEFAULT 0,
+    revoked_at TEXT,
+    FOREIGN KEY (user_id) REFERENCES users (id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_user_feed_tokens_user_id ON user_feed_tokens (user_id);
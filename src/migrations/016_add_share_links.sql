@@ -0,0 +1,16 @@
+-- Links that grant access to a calendar to whoever redeems them
+CREATE TABLE IF NOT EXISTS share_links (
+    id TEXT PRIMARY KEY,
+    calendar_id TEXT NOT NULL,
+    created_by TEXT NOT NULL,
+    permission_level TEXT NOT NULL,
+    -- NULL links never expire
+    expires_at TEXT,
+    created_at TEXT NOT NULL,
+    redeemed_count INTEGER NOT NULL DEFAULT 0,
+    revoked_at TEXT,
+    FOREIGN KEY (calendar_id) REFERENCES calendars (id) ON DELETE CASCADE,
+    FOREIGN KEY (created_by) REFERENCES users (id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_share_links_calendar_id ON share_links (calendar_id);
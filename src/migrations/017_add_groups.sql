@@ -0,0 +1,24 @@
+-- Groups of users owning calendars together, e.g. a family or a small team
+CREATE TABLE IF NOT EXISTS user_groups (
+    id TEXT PRIMARY KEY,
+    name TEXT NOT NULL,
+    created_by TEXT NOT NULL,
+    created_at TEXT NOT NULL,
+    updated_at TEXT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS group_members (
+    group_id TEXT NOT NULL,
+    user_id TEXT NOT NULL,
+    -- 'admin' manages the group and its calendars, 'member' edits events
+    role TEXT NOT NULL DEFAULT 'member',
+    created_at TEXT NOT NULL,
+    PRIMARY KEY (group_id, user_id),
+    FOREIGN KEY (group_id) REFERENCES user_groups (id) ON DELETE CASCADE,
+    FOREIGN KEY (user_id) REFERENCES users (id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_group_members_user_id ON group_members (user_id);
+
+-- Calendars owned by a group; NULL for personal calendars
+ALTER TABLE calendars ADD COLUMN group_id TEXT REFERENCES user_groups (id);
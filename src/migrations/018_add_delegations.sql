@@ -0,0 +1,14 @@
+-- Users acting on behalf of another user's calendars
+CREATE TABLE IF NOT EXISTS delegations (
+    id TEXT PRIMARY KEY,
+    owner_id TEXT NOT NULL,
+    delegate_id TEXT NOT NULL,
+    -- 'read' or 'write', applied to every calendar the owner has
+    permission_level TEXT NOT NULL,
+    created_at TEXT NOT NULL,
+    UNIQUE (owner_id, delegate_id),
+    FOREIGN KEY (owner_id) REFERENCES users (id) ON DELETE CASCADE,
+    FOREIGN KEY (delegate_id) REFERENCES users (id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_delegations_delegate_id ON delegations (delegate_id);
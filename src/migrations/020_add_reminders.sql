@@ -0,0 +1,16 @@
+-- How each user wants to be reminded of events in a calendar they can see
+CREATE TABLE IF NOT EXISTS notification_preferences (
+    user_id TEXT NOT NULL,
+    calendar_id TEXT NOT NULL,
+    -- 'email' or 'none'
+    channel TEXT NOT NULL,
+    updated_at TEXT NOT NULL,
+    PRIMARY KEY (user_id, calendar_id),
+    FOREIGN KEY (user_id) REFERENCES users (id) ON DELETE CASCADE,
+    FOREIGN KEY (calendar_id) REFERENCES calendars (id) ON DELETE CASCADE
+);
+
+-- Minutes before the start an event's reminder is due
+ALTER TABLE events ADD COLUMN alarm_minutes INTEGER;
+-- Reminder given to events created in the calendar without one of their own
+ALTER TABLE calendars ADD COLUMN default_alarm_minutes INTEGER;
@@ -0,0 +1,18 @@
+-- People whose birthdays and anniversaries fill the owner's Birthdays calendar
+CREATE TABLE IF NOT EXISTS contacts (
+    id TEXT PRIMARY KEY,
+    user_id TEXT NOT NULL,
+    name TEXT NOT NULL,
+    email TEXT,
+    -- Dates as YYYY-MM-DD
+    birthday TEXT,
+    anniversary TEXT,
+    created_at TEXT NOT NULL,
+    updated_at TEXT NOT NULL,
+    FOREIGN KEY (user_id) REFERENCES users (id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_contacts_user_id ON contacts (user_id);
+
+-- The calendar generated from a user's contacts, which nobody can change directly
+ALTER TABLE calendars ADD COLUMN is_birthdays INTEGER NOT NULL DEFAULT 0;
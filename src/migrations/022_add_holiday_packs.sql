@@ -0,0 +1,4 @@
+-- Built-in holiday pack a calendar is generated from; NULL for ordinary calendars
+ALTER TABLE calendars ADD COLUMN holiday_pack TEXT;
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_calendars_holiday_pack ON calendars (user_id, holiday_pack) WHERE holiday_pack IS NOT NULL;
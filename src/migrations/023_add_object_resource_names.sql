@@ -0,0 +1,9 @@
+-- iCalendar UID and CalDAV resource name a client chose when it created an object;
+-- NULL for objects created elsewhere, which use their id for both
+ALTER TABLE events ADD COLUMN uid TEXT;
+ALTER TABLE events ADD COLUMN resource_name TEXT;
+ALTER TABLE journals ADD COLUMN resource_name TEXT;
+
+CREATE INDEX IF NOT EXISTS idx_events_calendar_uid ON events (calendar_id, uid);
+CREATE INDEX IF NOT EXISTS idx_events_calendar_resource_name ON events (calendar_id, resource_name);
+CREATE INDEX IF NOT EXISTS idx_journals_calendar_resource_name ON journals (calendar_id, resource_name);
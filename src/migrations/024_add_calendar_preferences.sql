@@ -0,0 +1,15 @@
+-- How each user arranges the calendars they can see
+CREATE TABLE IF NOT EXISTS calendar_preferences (
+    user_id TEXT NOT NULL,
+    calendar_id TEXT NOT NULL,
+    -- Position in the user's calendar list; NULL keeps the calendar's own order
+    sort_order INTEGER,
+    -- Hidden calendars are left out of the dashboard
+    hidden INTEGER NOT NULL DEFAULT 0,
+    -- Color the user sees instead of the calendar's own
+    color TEXT,
+    updated_at TEXT NOT NULL,
+    PRIMARY KEY (user_id, calendar_id),
+    FOREIGN KEY (user_id) REFERENCES users (id) ON DELETE CASCADE,
+    FOREIGN KEY (calendar_id) REFERENCES calendars (id) ON DELETE CASCADE
+);
@@ -0,0 +1,12 @@
+-- The calendar new events go to when none is chosen, one per user
+ALTER TABLE calendars ADD COLUMN is_default INTEGER NOT NULL DEFAULT 0;
+
+-- Existing users start with their first personal calendar as the default
+UPDATE calendars SET is_default = 1 WHERE id IN (
+    SELECT (
+        SELECT c.id FROM calendars c
+        WHERE c.user_id = owners.user_id AND c.group_id IS NULL AND c.is_birthdays = 0 AND c.holiday_pack IS NULL
+        ORDER BY c.created_at LIMIT 1
+    )
+    FROM (SELECT DISTINCT user_id FROM calendars) owners
+);
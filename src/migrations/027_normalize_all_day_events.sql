@@ -0,0 +1,9 @@
+-- All-day events cover whole days: they start at midnight UTC of their first day and end
+-- at midnight after their last, like DTSTART and DTEND of a VALUE=DATE event
+UPDATE events SET
+    end_time = max(
+        CASE WHEN time(end_time) = '00:00:00' THEN date(end_time) ELSE date(end_time, '+1 day') END,
+        date(start_time, '+1 day')
+    ) || 'T00:00:00+00:00',
+    start_time = date(start_time) || 'T00:00:00+00:00'
+WHERE is_all_day = 1;
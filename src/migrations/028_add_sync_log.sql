@@ -0,0 +1,15 @@
+-- CalDAV requests of users who turned on the sync log, newest kept per user
+CREATE TABLE IF NOT EXISTS sync_log (
+    id TEXT PRIMARY KEY,
+    user_id TEXT NOT NULL,
+    method TEXT NOT NULL,
+    path TEXT NOT NULL,
+    status INTEGER NOT NULL,
+    user_agent TEXT,
+    duration_ms INTEGER NOT NULL,
+    error TEXT,
+    created_at TEXT NOT NULL,
+    FOREIGN KEY (user_id) REFERENCES users (id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_sync_log_user_created ON sync_log (user_id, created_at);
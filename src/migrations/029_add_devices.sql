@@ -0,0 +1,14 @@
+-- CalDAV clients seen per user, told apart by user agent and the credential they use
+CREATE TABLE IF NOT EXISTS devices (
+    id TEXT PRIMARY KEY,
+    user_id TEXT NOT NULL,
+    user_agent TEXT NOT NULL,
+    credential TEXT NOT NULL,
+    ip TEXT,
+    first_seen_at TEXT NOT NULL,
+    last_seen_at TEXT NOT NULL,
+    request_count INTEGER NOT NULL DEFAULT 1,
+    revoked_at TEXT,
+    UNIQUE (user_id, user_agent, credential),
+    FOREIGN KEY (user_id) REFERENCES users (id) ON DELETE CASCADE
+);
@@ -0,0 +1,29 @@
+-- Shares with a user go when that user is deleted. SQLite can't add a foreign key to an
+-- existing table, so the table is rebuilt; shares already left behind are dropped.
+DELETE FROM shares
+WHERE calendar_id NOT IN (SELECT id FROM calendars)
+   OR user_id NOT IN (SELECT id FROM users)
+   OR shared_with_user_id NOT IN (SELECT id FROM users);
+
+CREATE TABLE shares_new (
+    id TEXT PRIMARY KEY,
+    calendar_id TEXT NOT NULL,
+    user_id TEXT NOT NULL,
+    shared_with_user_id TEXT,
+    shared_with_email TEXT,
+    permission_level TEXT NOT NULL,
+    created_at TEXT NOT NULL,
+    FOREIGN KEY (calendar_id) REFERENCES calendars (id) ON DELETE CASCADE,
+    FOREIGN KEY (user_id) REFERENCES users (id) ON DELETE CASCADE,
+    FOREIGN KEY (shared_with_user_id) REFERENCES users (id) ON DELETE CASCADE
+);
+
+INSERT INTO shares_new (id, calendar_id, user_id, shared_with_user_id, shared_with_email, permission_level, created_at)
+SELECT id, calendar_id, user_id, shared_with_user_id, shared_with_email, permission_level, created_at FROM shares;
+
+DROP TABLE shares;
+ALTER TABLE shares_new RENAME TO shares;
+
+CREATE INDEX IF NOT EXISTS idx_shares_calendar_id ON shares (calendar_id);
+CREATE INDEX IF NOT EXISTS idx_shares_user_id ON shares (user_id);
+CREATE INDEX IF NOT EXISTS idx_shares_shared_with_user_id ON shares (shared_with_user_id);
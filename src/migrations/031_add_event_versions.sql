@@ -0,0 +1,31 @@
+-- Earlier states of events, stored before each change and deletion so they can be restored.
+-- event_id has no foreign key: versions of deleted events are what makes them restorable.
+CREATE TABLE IF NOT EXISTS event_versions (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    event_id TEXT NOT NULL,
+    calendar_id TEXT NOT NULL,
+    action TEXT NOT NULL,
+    title TEXT NOT NULL,
+    description TEXT,
+    location TEXT,
+    geo_lat REAL,
+    geo_lon REAL,
+    start_time TEXT NOT NULL,
+    end_time TEXT NOT NULL,
+    is_all_day INTEGER NOT NULL DEFAULT 0,
+    rrule TEXT,
+    exdates TEXT,
+    parent_id TEXT,
+    recurrence_id TEXT,
+    alarm_minutes INTEGER,
+    uid TEXT,
+    resource_name TEXT,
+    created_at TEXT NOT NULL,
+    updated_at TEXT NOT NULL,
+    recorded_at TEXT NOT NULL,
+    FOREIGN KEY (calendar_id) REFERENCES calendars (id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_event_versions_event_id ON event_versions (event_id);
+CREATE INDEX IF NOT EXISTS idx_event_versions_calendar_action ON event_versions (calendar_id, action);
+CREATE INDEX IF NOT EXISTS idx_event_versions_recorded_at ON event_versions (recorded_at);
@@ -0,0 +1,25 @@
+-- Highest SEQUENCE clients have written an event with. A write with a lower one is
+-- based on an older revision
+ALTER TABLE events ADD COLUMN sequence INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE event_versions ADD COLUMN sequence INTEGER NOT NULL DEFAULT 0;
+
+-- Changes refused because the event changed in the meantime, kept until a user resolves them
+CREATE TABLE IF NOT EXISTS edit_conflicts (
+    id TEXT PRIMARY KEY,
+    event_id TEXT NOT NULL,
+    client TEXT,
+    title TEXT NOT NULL,
+    description TEXT,
+    location TEXT,
+    geo_lat REAL,
+    geo_lon REAL,
+    start_time TEXT NOT NULL,
+    end_time TEXT NOT NULL,
+    is_all_day INTEGER NOT NULL DEFAULT 0,
+    rrule TEXT,
+    alarm_minutes INTEGER,
+    created_at TEXT NOT NULL,
+    FOREIGN KEY (event_id) REFERENCES events (id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_edit_conflicts_event_id ON edit_conflicts (event_id);
@@ -1,5 +1,5 @@
-use chrono::{DateTime, Utc};
-use serde::{Deserialize, Serialize};
+use chrono::{DateTime, Duration, NaiveDate, NaiveTime, Utc};
+use serde::{Deserialize, Deserializer, Serialize};
 use sqlx::{FromRow, Row};
 use uuid::Uuid;
 use std::fmt;
@@ -58,6 +58,8 @@ pub struct User {
     pub id: Uuid,
     pub name: String,
     pub email: String,
+    /// Never serialized; JSON responses use [`PublicUser`]
+    #[serde(skip_serializing)]
     pub password_hash: String,
     pub role: UserRole,
     pub created_at: DateTime<Utc>,
@@ -94,10 +96,38 @@ pub struct Calendar {
     pub description: Option<String>,
     pub color: Option<String>,
     pub is_public: bool,
+    pub ctag: i64,
+    /// Position among the owner's calendars (CalDAV `calendar-order`)
+    pub sort_order: i64,
+    /// Incremented by a forced re-sync; part of every object's ETag
+    pub sync_epoch: i64,
+    /// Group owning the calendar together; its members get access by their role
+    pub group_id: Option<Uuid>,
+    /// Reminder given to new events that come without one, in minutes before the start
+    pub default_alarm_minutes: Option<i64>,
+    /// Generated from the owner's contacts and read-only, even to the owner
+    pub is_birthdays: bool,
+    /// Holiday pack the calendar was generated from; such calendars are read-only too
+    pub holiday_pack: Option<String>,
+    /// Where the owner's new events go when no calendar is chosen; one per user
+    pub is_default: bool,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
 
+impl Calendar {
+    /// Whether the server generates the calendar's events, from contacts or a
+    /// holiday pack, so nobody can change them
+    pub fn is_generated(&self) -> bool {
+        self.is_birthdays || self.holiday_pack.is_some()
+    }
+
+    /// Permission the owner holds on the calendar
+    pub fn owner_permission(&self) -> PermissionLevel {
+        if self.is_generated() { PermissionLevel::Read } else { PermissionLevel::Admin }
+    }
+}
+
 impl FromRow<'_, sqlx::sqlite::SqliteRow> for Calendar {
     fn from_row(row: &'_ sqlx::sqlite::SqliteRow) -> Result<Self, sqlx::Error> {
         let id_str: String = row.try_get("id")?;
@@ -112,6 +142,12 @@ impl FromRow<'_, sqlx::sqlite::SqliteRow> for Calendar {
             source: Box::new(e),
         })?;
         
+        let group_id_str: Option<String> = row.try_get("group_id")?;
+        let group_id = group_id_str.as_deref().map(parse_uuid).transpose().map_err(|e| sqlx::Error::ColumnDecode {
+            index: "group_id".to_string(),
+            source: Box::new(e),
+        })?;
+        
         Ok(Calendar {
             id,
             user_id,
@@ -119,6 +155,14 @@ impl FromRow<'_, sqlx::sqlite::SqliteRow> for Calendar {
             description: row.try_get("description")?,
             color: row.try_get("color")?,
             is_public: row.try_get::<i32, _>("is_public")? != 0,
+            ctag: row.try_get("ctag")?,
+            sort_order: row.try_get("sort_order")?,
+            sync_epoch: row.try_get("sync_epoch")?,
+            group_id,
+            default_alarm_minutes: row.try_get("default_alarm_minutes")?,
+            is_birthdays: row.try_get::<i32, _>("is_birthdays")? != 0,
+            holiday_pack: row.try_get("holiday_pack")?,
+            is_default: row.try_get::<i32, _>("is_default")? != 0,
             created_at: row.try_get("created_at")?,
             updated_at: row.try_get("updated_at")?,
         })
@@ -132,13 +176,63 @@ pub struct Event {
     pub title: String,
     pub description: Option<String>,
     pub location: Option<String>,
+    /// Latitude of the event's GEO position
+    pub geo_lat: Option<f64>,
+    /// Longitude of the event's GEO position
+    pub geo_lon: Option<f64>,
     pub start_time: DateTime<Utc>,
     pub end_time: DateTime<Utc>,
     pub is_all_day: bool,
+    /// Recurrence rule (RRULE value) of a series master
+    pub rrule: Option<String>,
+    /// Instances excluded from the series (EXDATE values)
+    pub exdates: Vec<DateTime<Utc>>,
+    /// Series master of an overridden instance
+    pub parent_id: Option<Uuid>,
+    /// Original start of the instance this event overrides (RECURRENCE-ID)
+    pub recurrence_id: Option<DateTime<Utc>>,
+    /// Reminder (VALARM) in minutes before the start
+    pub alarm_minutes: Option<i64>,
+    /// Highest revision (SEQUENCE) a client has written the event with
+    pub sequence: i64,
+    /// iCalendar UID given by the client that created the event
+    pub uid: Option<String>,
+    /// Name of the CalDAV object resource the event was uploaded as
+    pub resource_name: Option<String>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
 
+impl Event {
+    pub fn is_recurring(&self) -> bool {
+        self.rrule.is_some()
+    }
+
+    pub fn is_exception(&self) -> bool {
+        self.parent_id.is_some()
+    }
+
+    /// When the calendar object resource of this series master or standalone event last
+    /// changed, going by it and its overridden instances among `events`
+    pub fn object_modified(&self, events: &[Event]) -> DateTime<Utc> {
+        events.iter()
+            .filter(|event| event.parent_id == Some(self.id))
+            .map(|event| event.updated_at)
+            .fold(self.updated_at, DateTime::max)
+    }
+
+    /// iCalendar UID of a series master or standalone event; events created outside
+    /// CalDAV use their id
+    pub fn ical_uid(&self) -> String {
+        self.uid.clone().unwrap_or_else(|| self.id.to_string())
+    }
+
+    /// Name of the event's CalDAV object resource, `{id}.ics` unless a client chose one
+    pub fn object_name(&self) -> String {
+        self.resource_name.clone().unwrap_or_else(|| format!("{}.ics", self.id))
+    }
+}
+
 impl FromRow<'_, sqlx::sqlite::SqliteRow> for Event {
     fn from_row(row: &'_ sqlx::sqlite::SqliteRow) -> Result<Self, sqlx::Error> {
         let id_str: String = row.try_get("id")?;
@@ -146,28 +240,145 @@ impl FromRow<'_, sqlx::sqlite::SqliteRow> for Event {
             index: "id".to_string(),
             source: Box::new(e),
         })?;
-        
+
         let calendar_id_str: String = row.try_get("calendar_id")?;
         let calendar_id = parse_uuid(&calendar_id_str).map_err(|e| sqlx::Error::ColumnDecode {
             index: "calendar_id".to_string(),
             source: Box::new(e),
         })?;
-        
+
+        let parent_id: Option<String> = row.try_get("parent_id")?;
+        let parent_id = parent_id
+            .as_ref()
+            .map(|s| parse_uuid(s))
+            .transpose()
+            .map_err(|e| sqlx::Error::ColumnDecode {
+                index: "parent_id".to_string(),
+                source: Box::new(e),
+            })?;
+
+        let exdates: Option<String> = row.try_get("exdates")?;
+        let exdates = exdates
+            .map(|s| parse_ical_date_list(&s))
+            .unwrap_or_default();
+
         Ok(Event {
             id,
             calendar_id,
             title: row.try_get("title")?,
             description: row.try_get("description")?,
             location: row.try_get("location")?,
+            geo_lat: row.try_get("geo_lat")?,
+            geo_lon: row.try_get("geo_lon")?,
             start_time: row.try_get("start_time")?,
             end_time: row.try_get("end_time")?,
             is_all_day: row.try_get::<i32, _>("is_all_day")? != 0,
+            rrule: row.try_get("rrule")?,
+            exdates,
+            parent_id,
+            recurrence_id: row.try_get("recurrence_id")?,
+            alarm_minutes: row.try_get("alarm_minutes")?,
+            sequence: row.try_get("sequence")?,
+            uid: row.try_get("uid")?,
+            resource_name: row.try_get("resource_name")?,
             created_at: row.try_get("created_at")?,
             updated_at: row.try_get("updated_at")?,
         })
     }
 }
 
+/// Event in a list response, together with its next upcoming instance
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ListedEvent {
+    #[serde(flatten)]
+    pub event: Event,
+    /// Start of the next instance that has not ended yet, computed from the RRULE for
+    /// series masters; `None` once the event (or the whole series) is over
+    pub next_occurrence: Option<DateTime<Utc>>,
+}
+
+/// Event as stored by a create or update, with the events it overlaps if the client
+/// asked for a conflict check
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct CheckedEvent {
+    #[serde(flatten)]
+    pub event: Event,
+    /// Instances of other events in the same calendar overlapping this one, with their
+    /// start and end; left out unless `check_conflicts` was set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub conflicts: Option<Vec<Event>>,
+}
+
+/// Compact view of an event embedded in calendar listings. For recurring events the
+/// times are those of the next upcoming instance rather than the series start.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct EventSummary {
+    pub id: Uuid,
+    pub title: String,
+    pub location: Option<String>,
+    pub start_time: DateTime<Utc>,
+    pub end_time: DateTime<Utc>,
+    pub is_all_day: bool,
+    pub is_recurring: bool,
+}
+
+/// Calendar together with summaries of its upcoming events
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct CalendarWithEvents {
+    #[serde(flatten)]
+    pub calendar: Calendar,
+    pub upcoming_events: Vec<EventSummary>,
+}
+
+/// Event found by a location search, with its distance from the search point
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct NearbyEvent {
+    #[serde(flatten)]
+    pub event: Event,
+    pub distance_km: f64,
+}
+
+/// Committed time in one week of a workload forecast
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ForecastWeek {
+    /// Monday the week starts on (UTC)
+    pub week_start: chrono::NaiveDate,
+    /// Sum of timed event durations falling into the week
+    pub hours: f64,
+    /// Number of event instances starting in the week
+    pub event_count: usize,
+}
+
+/// Format a list of instants as a comma-separated iCalendar UTC date-time list
+pub fn format_ical_date_list(dates: &[DateTime<Utc>]) -> String {
+    dates
+        .iter()
+        .map(|d| d.format("%Y%m%dT%H%M%SZ").to_string())
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// Start and end of an all-day event as stored: midnight UTC of its first day and of the
+/// day after its last, like DTSTART and the exclusive DTEND of a `VALUE=DATE` event. An end
+/// within a day counts that day; one at or before the start leaves a single day.
+pub fn all_day_span(start: DateTime<Utc>, end: DateTime<Utc>) -> (DateTime<Utc>, DateTime<Utc>) {
+    let midnight = |time: DateTime<Utc>| time.date_naive().and_time(NaiveTime::MIN).and_utc();
+    let start = midnight(start);
+    let end = if midnight(end) == end { end } else { midnight(end) + Duration::days(1) };
+    (start, end.max(start + Duration::days(1)))
+}
+
+/// Parse a comma-separated iCalendar UTC date-time list, skipping invalid entries
+pub fn parse_ical_date_list(value: &str) -> Vec<DateTime<Utc>> {
+    value
+        .split(',')
+        .filter_map(|s| {
+            chrono::NaiveDateTime::parse_from_str(s.trim().trim_end_matches('Z'), "%Y%m%dT%H%M%S").ok()
+        })
+        .map(|dt| dt.and_utc())
+        .collect()
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct Share {
     pub id: Uuid,
@@ -221,131 +432,1411 @@ impl FromRow<'_, sqlx::sqlite::SqliteRow> for Share {
     }
 }
 
+/// WebDAV-Push subscription registered by a client for a calendar collection
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
-pub enum PermissionLevel {
-    Read,
-    Write,
-    Admin,
+pub struct PushSubscription {
+    pub id: Uuid,
+    pub calendar_id: Uuid,
+    pub user_id: Uuid,
+    pub push_resource: String,
+    pub expires_at: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
 }
 
-#[allow(dead_code)]
-impl PermissionLevel {
-    pub fn as_str(&self) -> &'static str {
-        match self {
-            PermissionLevel::Read => "read",
-            PermissionLevel::Write => "write",
-            PermissionLevel::Admin => "admin",
-        }
-    }
-    
-    pub fn from_str(s: &str) -> Option<Self> {
-        match s {
-            "read" => Some(PermissionLevel::Read),
-            "write" => Some(PermissionLevel::Write),
-            "admin" => Some(PermissionLevel::Admin),
-            _ => None,
-        }
+impl FromRow<'_, sqlx::sqlite::SqliteRow> for PushSubscription {
+    fn from_row(row: &'_ sqlx::sqlite::SqliteRow) -> Result<Self, sqlx::Error> {
+        let id_str: String = row.try_get("id")?;
+        let id = parse_uuid(&id_str).map_err(|e| sqlx::Error::ColumnDecode {
+            index: "id".to_string(),
+            source: Box::new(e),
+        })?;
+        
+        let calendar_id_str: String = row.try_get("calendar_id")?;
+        let calendar_id = parse_uuid(&calendar_id_str).map_err(|e| sqlx::Error::ColumnDecode {
+            index: "calendar_id".to_string(),
+            source: Box::new(e),
+        })?;
+        
+        let user_id_str: String = row.try_get("user_id")?;
+        let user_id = parse_uuid(&user_id_str).map_err(|e| sqlx::Error::ColumnDecode {
+            index: "user_id".to_string(),
+            source: Box::new(e),
+        })?;
+        
+        Ok(PushSubscription {
+            id,
+            calendar_id,
+            user_id,
+            push_resource: row.try_get("push_resource")?,
+            expires_at: row.try_get("expires_at")?,
+            created_at: row.try_get("created_at")?,
+        })
     }
 }
 
-// Request/Response DTOs
+/// Person whose birthday and anniversary appear in the owner's Birthdays calendar
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Contact {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub name: String,
+    pub email: Option<String>,
+    pub birthday: Option<NaiveDate>,
+    pub anniversary: Option<NaiveDate>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct NewUser {
+impl FromRow<'_, sqlx::sqlite::SqliteRow> for Contact {
+    fn from_row(row: &'_ sqlx::sqlite::SqliteRow) -> Result<Self, sqlx::Error> {
+        let id_str: String = row.try_get("id")?;
+        let id = parse_uuid(&id_str).map_err(|e| sqlx::Error::ColumnDecode {
+            index: "id".to_string(),
+            source: Box::new(e),
+        })?;
+        
+        let user_id_str: String = row.try_get("user_id")?;
+        let user_id = parse_uuid(&user_id_str).map_err(|e| sqlx::Error::ColumnDecode {
+            index: "user_id".to_string(),
+            source: Box::new(e),
+        })?;
+        
+        Ok(Contact {
+            id,
+            user_id,
+            name: row.try_get("name")?,
+            email: row.try_get("email")?,
+            birthday: row.try_get("birthday")?,
+            anniversary: row.try_get("anniversary")?,
+            created_at: row.try_get("created_at")?,
+            updated_at: row.try_get("updated_at")?,
+        })
+    }
+}
+
+/// A built-in holiday pack and whether the user enabled it
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct HolidayPackStatus {
+    pub id: String,
     pub name: String,
-    pub email: String,
-    pub password: String,
+    /// The user's calendar of the pack's holidays, if enabled
+    pub calendar_id: Option<Uuid>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct NewCalendar {
+/// External iCalendar feed (e.g. a holiday or sports calendar) a user subscribes to
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct CalendarSubscription {
+    pub id: Uuid,
+    pub user_id: Uuid,
     pub name: String,
-    pub description: Option<String>,
+    /// Feed URL; `webcal://` links are stored as `https://`
+    pub url: String,
     pub color: Option<String>,
-    pub is_public: bool,
+    /// Whether clients show the feed's events
+    pub is_visible: bool,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, Default)]
-pub struct UpdateCalendar {
-    pub name: Option<String>,
-    pub description: Option<String>,
-    pub color: Option<String>,
-    pub is_public: Option<bool>,
+impl FromRow<'_, sqlx::sqlite::SqliteRow> for CalendarSubscription {
+    fn from_row(row: &'_ sqlx::sqlite::SqliteRow) -> Result<Self, sqlx::Error> {
+        let id_str: String = row.try_get("id")?;
+        let id = parse_uuid(&id_str).map_err(|e| sqlx::Error::ColumnDecode {
+            index: "id".to_string(),
+            source: Box::new(e),
+        })?;
+        
+        let user_id_str: String = row.try_get("user_id")?;
+        let user_id = parse_uuid(&user_id_str).map_err(|e| sqlx::Error::ColumnDecode {
+            index: "user_id".to_string(),
+            source: Box::new(e),
+        })?;
+        
+        Ok(CalendarSubscription {
+            id,
+            user_id,
+            name: row.try_get("name")?,
+            url: row.try_get("url")?,
+            color: row.try_get("color")?,
+            is_visible: row.try_get("is_visible")?,
+            created_at: row.try_get("created_at")?,
+            updated_at: row.try_get("updated_at")?,
+        })
+    }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct NewEvent {
-    pub title: String,
-    pub description: Option<String>,
-    pub location: Option<String>,
-    pub start_time: DateTime<Utc>,
-    pub end_time: DateTime<Utc>,
-    pub is_all_day: bool,
+/// Revocable link to a calendar's read-only iCalendar feed
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct FeedToken {
+    pub id: Uuid,
+    pub calendar_id: Uuid,
+    pub created_by: Uuid,
+    /// Who or what the link was given to, e.g. "Family" or "Team wiki"
+    pub label: String,
+    pub created_at: DateTime<Utc>,
+    pub last_fetched_at: Option<DateTime<Utc>>,
+    pub fetch_count: i64,
+    pub revoked_at: Option<DateTime<Utc>>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, Default)]
-pub struct UpdateEvent {
-    pub title: Option<String>,
-    pub description: Option<String>,
-    pub location: Option<String>,
-    pub start_time: Option<DateTime<Utc>>,
-    pub end_time: Option<DateTime<Utc>>,
-    pub is_all_day: Option<bool>,
+impl FromRow<'_, sqlx::sqlite::SqliteRow> for FeedToken {
+    fn from_row(row: &'_ sqlx::sqlite::SqliteRow) -> Result<Self, sqlx::Error> {
+        let id_str: String = row.try_get("id")?;
+        let id = parse_uuid(&id_str).map_err(|e| sqlx::Error::ColumnDecode {
+            index: "id".to_string(),
+            source: Box::new(e),
+        })?;
+        
+        let calendar_id_str: String = row.try_get("calendar_id")?;
+        let calendar_id = parse_uuid(&calendar_id_str).map_err(|e| sqlx::Error::ColumnDecode {
+            index: "calendar_id".to_string(),
+            source: Box::new(e),
+        })?;
+        
+        let created_by_str: String = row.try_get("created_by")?;
+        let created_by = parse_uuid(&created_by_str).map_err(|e| sqlx::Error::ColumnDecode {
+            index: "created_by".to_string(),
+            source: Box::new(e),
+        })?;
+        
+        Ok(FeedToken {
+            id,
+            calendar_id,
+            created_by,
+            label: row.try_get("label")?,
+            created_at: row.try_get("created_at")?,
+            last_fetched_at: row.try_get("last_fetched_at")?,
+            fetch_count: row.try_get("fetch_count")?,
+            revoked_at: row.try_get("revoked_at")?,
+        })
+    }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct NewShare {
-    pub shared_with_email: String,
-    pub permission: String,
+/// Address a feed was fetched from
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct FeedTokenClient {
+    pub ip: String,
+    pub first_fetched_at: DateTime<Utc>,
+    pub last_fetched_at: DateTime<Utc>,
+    pub fetch_count: i64,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct UpdateShare {
-    pub permission_level: PermissionLevel,
+impl FromRow<'_, sqlx::sqlite::SqliteRow> for FeedTokenClient {
+    fn from_row(row: &'_ sqlx::sqlite::SqliteRow) -> Result<Self, sqlx::Error> {
+        Ok(FeedTokenClient {
+            ip: row.try_get("ip")?,
+            first_fetched_at: row.try_get("first_fetched_at")?,
+            last_fetched_at: row.try_get("last_fetched_at")?,
+            fetch_count: row.try_get("fetch_count")?,
+        })
+    }
 }
 
-// iCalendar export structures
+/// Feed token with its URL and the addresses it was fetched from, as shown to the owner
+#[derive(Debug, Clone, Serialize)]
+pub struct FeedTokenDetails {
+    #[serde(flatten)]
+    pub token: FeedToken,
+    pub url: String,
+    pub clients: Vec<FeedTokenClient>,
+}
 
-#[derive(Debug, Clone)]
-pub struct ICalendarEvent {
-    pub uid: String,
-    pub summary: String,
-    pub description: Option<String>,
-    pub location: Option<String>,
-    pub dtstart: DateTime<Utc>,
-    pub dtend: DateTime<Utc>,
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NewFeedToken {
+    pub label: String,
 }
 
-impl ICalendarEvent {
-    pub fn to_ical_string(&self) -> String {
-        format!(
-            "BEGIN:VEVENT\r\n\
-             UID:{}\r\n\
-             SUMMARY:{}\r\n\
-             DESCRIPTION:{}\r\n\
-             LOCATION:{}\r\n\
-             DTSTART:{}\r\n\
-             DTEND:{}\r\n\
-             END:VEVENT\r\n",
-            self.uid,
-            escape_ical_text(&self.summary),
-            self.description.as_ref().map(|d| escape_ical_text(d)).unwrap_or_default(),
+/// Revocable link to a single read-only feed merging several of a user's calendars
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct UserFeedToken {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub label: String,
+    /// Calendars merged into the feed; `None` includes every calendar the user owns
+    pub calendar_ids: Option<Vec<Uuid>>,
+    pub created_at: DateTime<Utc>,
+    pub last_fetched_at: Option<DateTime<Utc>>,
+    pub fetch_count: i64,
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
+impl FromRow<'_, sqlx::sqlite::SqliteRow> for UserFeedToken {
+    fn from_row(row: &'_ sqlx::sqlite::SqliteRow) -> Result<Self, sqlx::Error> {
+        let id_str: String = row.try_get("id")?;
+        let id = parse_uuid(&id_str).map_err(|e| sqlx::Error::ColumnDecode {
+            index: "id".to_string(),
+            source: Box::new(e),
+        })?;
+        
+        let user_id_str: String = row.try_get("user_id")?;
+        let user_id = parse_uuid(&user_id_str).map_err(|e| sqlx::Error::ColumnDecode {
+            index: "user_id".to_string(),
+            source: Box::new(e),
+        })?;
+
+        let calendar_ids_str: Option<String> = row.try_get("calendar_ids")?;
+        let calendar_ids = calendar_ids_str
+            .map(|ids| ids.split(',').filter(|id| !id.is_empty()).map(parse_uuid).collect::<Result<Vec<_>, _>>())
+            .transpose()
+            .map_err(|e| sqlx::Error::ColumnDecode {
+                index: "calendar_ids".to_string(),
+                source: Box::new(e),
+            })?;
+        
+        Ok(UserFeedToken {
+            id,
+            user_id,
+            label: row.try_get("label")?,
+            calendar_ids,
+            created_at: row.try_get("created_at")?,
+            last_fetched_at: row.try_get("last_fetched_at")?,
+            fetch_count: row.try_get("fetch_count")?,
+            revoked_at: row.try_get("revoked_at")?,
+        })
+    }
+}
+
+/// User feed token with its URL, as shown to the owner
+#[derive(Debug, Clone, Serialize)]
+pub struct UserFeedTokenDetails {
+    #[serde(flatten)]
+    pub token: UserFeedToken,
+    pub url: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NewUserFeedToken {
+    pub label: String,
+    /// Calendars to merge; leave out to include all of them, now and in future
+    #[serde(default)]
+    pub calendar_ids: Option<Vec<Uuid>>,
+}
+
+/// File attached to an event
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Attachment {
+    pub id: Uuid,
+    pub event_id: Uuid,
+    pub filename: String,
+    pub content_type: String,
+    pub size: i64,
+    pub created_at: DateTime<Utc>,
+}
+
+impl Attachment {
+    /// Download location, used as the ATTACH value in iCalendar output
+    pub fn url(&self) -> String {
+        format!("/dav/attachments/{}", self.id)
+    }
+}
+
+impl FromRow<'_, sqlx::sqlite::SqliteRow> for Attachment {
+    fn from_row(row: &'_ sqlx::sqlite::SqliteRow) -> Result<Self, sqlx::Error> {
+        let id_str: String = row.try_get("id")?;
+        let id = parse_uuid(&id_str).map_err(|e| sqlx::Error::ColumnDecode {
+            index: "id".to_string(),
+            source: Box::new(e),
+        })?;
+        
+        let event_id_str: String = row.try_get("event_id")?;
+        let event_id = parse_uuid(&event_id_str).map_err(|e| sqlx::Error::ColumnDecode {
+            index: "event_id".to_string(),
+            source: Box::new(e),
+        })?;
+        
+        Ok(Attachment {
+            id,
+            event_id,
+            filename: row.try_get("filename")?,
+            content_type: row.try_get("content_type")?,
+            size: row.try_get("size")?,
+            created_at: row.try_get("created_at")?,
+        })
+    }
+}
+
+/// Entry of the audit log
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct AuditLogEntry {
+    pub id: Uuid,
+    /// User who performed the action (the admin, when impersonating)
+    pub actor_id: Uuid,
+    /// User being impersonated while the action was performed
+    pub impersonated_user_id: Option<Uuid>,
+    pub action: String,
+    pub details: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl FromRow<'_, sqlx::sqlite::SqliteRow> for AuditLogEntry {
+    fn from_row(row: &'_ sqlx::sqlite::SqliteRow) -> Result<Self, sqlx::Error> {
+        let id_str: String = row.try_get("id")?;
+        let id = parse_uuid(&id_str).map_err(|e| sqlx::Error::ColumnDecode {
+            index: "id".to_string(),
+            source: Box::new(e),
+        })?;
+        
+        let actor_id_str: String = row.try_get("actor_id")?;
+        let actor_id = parse_uuid(&actor_id_str).map_err(|e| sqlx::Error::ColumnDecode {
+            index: "actor_id".to_string(),
+            source: Box::new(e),
+        })?;
+        
+        let impersonated_user_id: Option<String> = row.try_get("impersonated_user_id")?;
+        let impersonated_user_id = impersonated_user_id
+            .as_ref()
+            .map(|s| parse_uuid(s))
+            .transpose()
+            .map_err(|e| sqlx::Error::ColumnDecode {
+                index: "impersonated_user_id".to_string(),
+                source: Box::new(e),
+            })?;
+        
+        Ok(AuditLogEntry {
+            id,
+            actor_id,
+            impersonated_user_id,
+            action: row.try_get("action")?,
+            details: row.try_get("details")?,
+            created_at: row.try_get("created_at")?,
+        })
+    }
+}
+
+/// External (OIDC) identity linked to a local account
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct UserIdentity {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub issuer: String,
+    pub subject: String,
+    pub email: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl FromRow<'_, sqlx::sqlite::SqliteRow> for UserIdentity {
+    fn from_row(row: &'_ sqlx::sqlite::SqliteRow) -> Result<Self, sqlx::Error> {
+        let id_str: String = row.try_get("id")?;
+        let id = parse_uuid(&id_str).map_err(|e| sqlx::Error::ColumnDecode {
+            index: "id".to_string(),
+            source: Box::new(e),
+        })?;
+        
+        let user_id_str: String = row.try_get("user_id")?;
+        let user_id = parse_uuid(&user_id_str).map_err(|e| sqlx::Error::ColumnDecode {
+            index: "user_id".to_string(),
+            source: Box::new(e),
+        })?;
+        
+        Ok(UserIdentity {
+            id,
+            user_id,
+            issuer: row.try_get("issuer")?,
+            subject: row.try_get("subject")?,
+            email: row.try_get("email")?,
+            created_at: row.try_get("created_at")?,
+        })
+    }
+}
+
+/// To-do (VTODO) stored in a calendar
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Task {
+    pub id: Uuid,
+    pub calendar_id: Uuid,
+    pub uid: Option<String>,
+    pub summary: Option<String>,
+    pub description: Option<String>,
+    pub due: Option<DateTime<Utc>>,
+    /// STATUS value, e.g. NEEDS-ACTION or COMPLETED
+    pub status: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl FromRow<'_, sqlx::sqlite::SqliteRow> for Task {
+    fn from_row(row: &'_ sqlx::sqlite::SqliteRow) -> Result<Self, sqlx::Error> {
+        let id_str: String = row.try_get("id")?;
+        let id = parse_uuid(&id_str).map_err(|e| sqlx::Error::ColumnDecode {
+            index: "id".to_string(),
+            source: Box::new(e),
+        })?;
+        
+        let calendar_id_str: String = row.try_get("calendar_id")?;
+        let calendar_id = parse_uuid(&calendar_id_str).map_err(|e| sqlx::Error::ColumnDecode {
+            index: "calendar_id".to_string(),
+            source: Box::new(e),
+        })?;
+        
+        Ok(Task {
+            id,
+            calendar_id,
+            uid: row.try_get("uid")?,
+            summary: row.try_get("summary")?,
+            description: row.try_get("description")?,
+            due: row.try_get("due")?,
+            status: row.try_get("status")?,
+            created_at: row.try_get("created_at")?,
+            updated_at: row.try_get("updated_at")?,
+        })
+    }
+}
+
+/// Journal entry (VJOURNAL) stored in a calendar
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Journal {
+    pub id: Uuid,
+    pub calendar_id: Uuid,
+    pub uid: Option<String>,
+    pub summary: Option<String>,
+    pub description: Option<String>,
+    pub dtstart: Option<DateTime<Utc>>,
+    /// Name of the CalDAV object resource the entry was uploaded as
+    pub resource_name: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl FromRow<'_, sqlx::sqlite::SqliteRow> for Journal {
+    fn from_row(row: &'_ sqlx::sqlite::SqliteRow) -> Result<Self, sqlx::Error> {
+        let id_str: String = row.try_get("id")?;
+        let id = parse_uuid(&id_str).map_err(|e| sqlx::Error::ColumnDecode {
+            index: "id".to_string(),
+            source: Box::new(e),
+        })?;
+        
+        let calendar_id_str: String = row.try_get("calendar_id")?;
+        let calendar_id = parse_uuid(&calendar_id_str).map_err(|e| sqlx::Error::ColumnDecode {
+            index: "calendar_id".to_string(),
+            source: Box::new(e),
+        })?;
+        
+        Ok(Journal {
+            id,
+            calendar_id,
+            uid: row.try_get("uid")?,
+            summary: row.try_get("summary")?,
+            description: row.try_get("description")?,
+            dtstart: row.try_get("dtstart")?,
+            resource_name: row.try_get("resource_name")?,
+            created_at: row.try_get("created_at")?,
+            updated_at: row.try_get("updated_at")?,
+        })
+    }
+}
+
+impl Journal {
+    /// Name of the entry's CalDAV object resource, `{id}.ics` unless a client chose one
+    pub fn object_name(&self) -> String {
+        self.resource_name.clone().unwrap_or_else(|| format!("{}.ics", self.id))
+    }
+
+    /// The entry as a VJOURNAL component. Entries without a UID of their own are
+    /// identified by their id.
+    pub fn to_ical_string(&self) -> String {
+        let mut ical = format!(
+            "BEGIN:VJOURNAL\r\n\
+             UID:{}\r\n\
+             DTSTAMP:{}\r\n",
+            self.uid.clone().unwrap_or_else(|| self.id.to_string()),
+            self.updated_at.format("%Y%m%dT%H%M%SZ"),
+        );
+
+        if let Some(dtstart) = self.dtstart {
+            ical.push_str(&format!("DTSTART:{}\r\n", dtstart.format("%Y%m%dT%H%M%SZ")));
+        }
+        if let Some(summary) = &self.summary {
+            ical.push_str(&format!("SUMMARY:{}\r\n", escape_ical_text(summary)));
+        }
+        if let Some(description) = &self.description {
+            ical.push_str(&format!("DESCRIPTION:{}\r\n", escape_ical_text(description)));
+        }
+
+        ical.push_str("END:VJOURNAL\r\n");
+        ical
+    }
+}
+
+/// Group of users owning calendars together
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Group {
+    pub id: Uuid,
+    pub name: String,
+    pub created_by: Uuid,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl FromRow<'_, sqlx::sqlite::SqliteRow> for Group {
+    fn from_row(row: &'_ sqlx::sqlite::SqliteRow) -> Result<Self, sqlx::Error> {
+        let id_str: String = row.try_get("id")?;
+        let id = parse_uuid(&id_str).map_err(|e| sqlx::Error::ColumnDecode {
+            index: "id".to_string(),
+            source: Box::new(e),
+        })?;
+        
+        let created_by_str: String = row.try_get("created_by")?;
+        let created_by = parse_uuid(&created_by_str).map_err(|e| sqlx::Error::ColumnDecode {
+            index: "created_by".to_string(),
+            source: Box::new(e),
+        })?;
+        
+        Ok(Group {
+            id,
+            name: row.try_get("name")?,
+            created_by,
+            created_at: row.try_get("created_at")?,
+            updated_at: row.try_get("updated_at")?,
+        })
+    }
+}
+
+/// Role of a user in a group
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum GroupRole {
+    /// Edits events in the group's calendars
+    #[default]
+    Member,
+    /// Also manages the group, its members and its calendars
+    Admin,
+}
+
+impl GroupRole {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            GroupRole::Member => "member",
+            GroupRole::Admin => "admin",
+        }
+    }
+    
+    pub fn from_str(s: &str) -> Self {
+        match s {
+            "admin" => GroupRole::Admin,
+            _ => GroupRole::Member,
+        }
+    }
+    
+    /// Access the role grants on the group's calendars
+    pub fn permission(&self) -> PermissionLevel {
+        match self {
+            GroupRole::Member => PermissionLevel::Write,
+            GroupRole::Admin => PermissionLevel::Admin,
+        }
+    }
+}
+
+/// Member of a group, with their account details
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct GroupMember {
+    pub group_id: Uuid,
+    pub user_id: Uuid,
+    pub name: String,
+    pub email: String,
+    pub role: GroupRole,
+    pub created_at: DateTime<Utc>,
+}
+
+impl FromRow<'_, sqlx::sqlite::SqliteRow> for GroupMember {
+    fn from_row(row: &'_ sqlx::sqlite::SqliteRow) -> Result<Self, sqlx::Error> {
+        let group_id_str: String = row.try_get("group_id")?;
+        let group_id = parse_uuid(&group_id_str).map_err(|e| sqlx::Error::ColumnDecode {
+            index: "group_id".to_string(),
+            source: Box::new(e),
+        })?;
+        
+        let user_id_str: String = row.try_get("user_id")?;
+        let user_id = parse_uuid(&user_id_str).map_err(|e| sqlx::Error::ColumnDecode {
+            index: "user_id".to_string(),
+            source: Box::new(e),
+        })?;
+        
+        let role: String = row.try_get("role")?;
+        
+        Ok(GroupMember {
+            group_id,
+            user_id,
+            name: row.try_get("name")?,
+            email: row.try_get("email")?,
+            role: GroupRole::from_str(&role),
+            created_at: row.try_get("created_at")?,
+        })
+    }
+}
+
+/// A group as seen by one of its members
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct UserGroup {
+    #[serde(flatten)]
+    pub group: Group,
+    pub role: GroupRole,
+}
+
+/// A group with its members and calendars
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct GroupDetails {
+    #[serde(flatten)]
+    pub group: Group,
+    pub role: GroupRole,
+    pub members: Vec<GroupMember>,
+    pub calendars: Vec<Calendar>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NewGroup {
+    pub name: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NewGroupMember {
+    pub email: String,
+    #[serde(default)]
+    pub role: GroupRole,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UpdateGroupMember {
+    pub role: GroupRole,
+}
+
+/// Share permission, ordered from least to most privileged
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
+pub enum PermissionLevel {
+    Read,
+    Write,
+    Admin,
+}
+
+#[allow(dead_code)]
+impl PermissionLevel {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PermissionLevel::Read => "read",
+            PermissionLevel::Write => "write",
+            PermissionLevel::Admin => "admin",
+        }
+    }
+    
+    pub fn from_str(s: &str) -> Option<Self> {
+        match s {
+            "read" => Some(PermissionLevel::Read),
+            "write" => Some(PermissionLevel::Write),
+            "admin" => Some(PermissionLevel::Admin),
+            _ => None,
+        }
+    }
+}
+
+/// Recent sign-in history of an account
+#[derive(Debug, Clone, Serialize, Default, PartialEq)]
+pub struct LoginActivity {
+    pub last_login_at: Option<DateTime<Utc>>,
+    pub last_failed_login_at: Option<DateTime<Utc>>,
+    /// Set while the account is locked after too many failed logins
+    pub locked_until: Option<DateTime<Utc>>,
+}
+
+// Request/Response DTOs
+
+/// A user as returned by the API, without credentials
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PublicUser {
+    pub id: Uuid,
+    pub email: String,
+    pub name: String,
+    pub role: UserRole,
+}
+
+impl From<User> for PublicUser {
+    fn from(user: User) -> Self {
+        Self {
+            id: user.id,
+            email: user.email,
+            name: user.name,
+            role: user.role,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NewUser {
+    pub name: String,
+    pub email: String,
+    pub password: String,
+    /// IANA time zone detected by the client, e.g. `Europe/Berlin`; times are shown in UTC
+    /// when it is missing or unknown
+    #[serde(default)]
+    pub timezone: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NewCalendar {
+    pub name: String,
+    pub description: Option<String>,
+    pub color: Option<String>,
+    pub is_public: bool,
+    /// Reminder for new events without one, in minutes before the start
+    #[serde(default)]
+    pub default_alarm_minutes: Option<i64>,
+}
+
+/// Deserialize an optional field that can also be cleared: a missing field becomes
+/// `None` (leave unchanged), `null` becomes `Some(None)` (clear) and a value `Some(Some(_))`.
+/// Use together with `#[serde(default)]`.
+pub fn double_option<'de, T, D>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    T: Deserialize<'de>,
+    D: Deserializer<'de>,
+{
+    Option::<T>::deserialize(deserializer).map(Some)
+}
+
+/// Hand a calendar over to another existing account
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TransferCalendar {
+    pub new_owner_email: String,
+    /// The calendar's name, typed again to confirm the transfer
+    pub confirm_name: String,
+    /// Keep full access to the calendar through a share after handing it over
+    #[serde(default)]
+    pub keep_access: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct UpdateCalendar {
+    pub name: Option<String>,
+    /// `null` removes the description
+    #[serde(default, deserialize_with = "double_option", skip_serializing_if = "Option::is_none")]
+    pub description: Option<Option<String>>,
+    /// `null` removes the color
+    #[serde(default, deserialize_with = "double_option", skip_serializing_if = "Option::is_none")]
+    pub color: Option<Option<String>>,
+    pub is_public: Option<bool>,
+    pub sort_order: Option<i64>,
+    /// `null` stops giving new events a reminder
+    #[serde(default, deserialize_with = "double_option", skip_serializing_if = "Option::is_none")]
+    pub default_alarm_minutes: Option<Option<i64>>,
+    /// `true` makes this the owner's default calendar in place of the current one
+    pub is_default: Option<bool>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NewEvent {
+    pub title: String,
+    pub description: Option<String>,
+    pub location: Option<String>,
+    pub start_time: DateTime<Utc>,
+    pub end_time: DateTime<Utc>,
+    pub is_all_day: bool,
+    pub rrule: Option<String>,
+    pub geo_lat: Option<f64>,
+    pub geo_lon: Option<f64>,
+    /// Reminder in minutes before the start; without one the calendar's default applies
+    #[serde(default)]
+    pub alarm_minutes: Option<i64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct UpdateEvent {
+    pub title: Option<String>,
+    /// `null` removes the description
+    #[serde(default, deserialize_with = "double_option", skip_serializing_if = "Option::is_none")]
+    pub description: Option<Option<String>>,
+    /// `null` removes the location
+    #[serde(default, deserialize_with = "double_option", skip_serializing_if = "Option::is_none")]
+    pub location: Option<Option<String>>,
+    pub start_time: Option<DateTime<Utc>>,
+    pub end_time: Option<DateTime<Utc>>,
+    pub is_all_day: Option<bool>,
+    pub rrule: Option<String>,
+    /// Latitude and longitude are only applied when both are given; both `null`
+    /// removes the position
+    #[serde(default, deserialize_with = "double_option", skip_serializing_if = "Option::is_none")]
+    pub geo_lat: Option<Option<f64>>,
+    #[serde(default, deserialize_with = "double_option", skip_serializing_if = "Option::is_none")]
+    pub geo_lon: Option<Option<f64>>,
+    /// `null` removes the reminder
+    #[serde(default, deserialize_with = "double_option", skip_serializing_if = "Option::is_none")]
+    pub alarm_minutes: Option<Option<i64>>,
+}
+
+impl UpdateEvent {
+    /// New position if the update sets or removes one: `Some(None)` removes it.
+    /// A coordinate given without the other, or next to a `null`, is ignored.
+    pub fn geo(&self) -> Option<Option<(f64, f64)>> {
+        match (self.geo_lat, self.geo_lon) {
+            (Some(Some(lat)), Some(Some(lon))) => Some(Some((lat, lon))),
+            (Some(None), Some(None)) => Some(None),
+            _ => None,
+        }
+    }
+}
+
+/// Changes to a single occurrence of a recurring event
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UpdateOccurrence {
+    /// Original start of the occurrence being overridden
+    pub recurrence_id: DateTime<Utc>,
+    #[serde(flatten)]
+    pub changes: UpdateEvent,
+}
+
+/// Which part of a recurring series an edit or deletion applies to
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum RecurrenceScope {
+    /// Only the selected occurrence
+    This,
+    /// The whole series
+    #[default]
+    All,
+}
+
+/// Event change recorded by an offline client
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "lowercase")]
+pub enum SyncOperation {
+    Create {
+        calendar_id: Uuid,
+        event: NewEvent,
+    },
+    /// Applied only if the event is still at `base_version` (its `updated_at`)
+    Update {
+        event_id: Uuid,
+        base_version: DateTime<Utc>,
+        changes: UpdateEvent,
+    },
+    Delete {
+        event_id: Uuid,
+        base_version: DateTime<Utc>,
+    },
+}
+
+/// One queued offline mutation
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SyncMutation {
+    /// Client-chosen id echoed in the result
+    pub client_id: String,
+    #[serde(flatten)]
+    pub operation: SyncOperation,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SyncCommitRequest {
+    pub mutations: Vec<SyncMutation>,
+}
+
+/// Outcome of a single mutation in a sync or event batch
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum SyncStatus {
+    Applied,
+    /// The event changed on the server since `base_version`; nothing was applied
+    Conflict,
+    NotFound,
+    Forbidden,
+    /// Not applied because another operation of an atomic batch failed
+    Skipped,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SyncResult {
+    pub client_id: String,
+    pub status: SyncStatus,
+    /// Server state after the mutation, or the current server version on conflict
+    pub event: Option<Event>,
+}
+
+/// One operation of an event batch. `base_version` is optional here: without it
+/// updates and deletes apply whatever the event's current version.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "lowercase")]
+pub enum BatchOperation {
+    Create {
+        calendar_id: Uuid,
+        event: NewEvent,
+    },
+    Update {
+        event_id: Uuid,
+        #[serde(default)]
+        base_version: Option<DateTime<Utc>>,
+        changes: UpdateEvent,
+    },
+    Delete {
+        event_id: Uuid,
+        #[serde(default)]
+        base_version: Option<DateTime<Utc>>,
+    },
+}
+
+impl From<SyncOperation> for BatchOperation {
+    fn from(operation: SyncOperation) -> Self {
+        match operation {
+            SyncOperation::Create { calendar_id, event } => BatchOperation::Create { calendar_id, event },
+            SyncOperation::Update { event_id, base_version, changes } => {
+                BatchOperation::Update { event_id, base_version: Some(base_version), changes }
+            }
+            SyncOperation::Delete { event_id, base_version } => {
+                BatchOperation::Delete { event_id, base_version: Some(base_version) }
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EventBatchRequest {
+    pub operations: Vec<BatchOperation>,
+    /// Apply all operations or none of them
+    #[serde(default)]
+    pub atomic: bool,
+}
+
+/// Outcome of one operation of an event batch, in request order
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BatchResult {
+    pub index: usize,
+    pub status: SyncStatus,
+    pub event: Option<Event>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NewTask {
+    pub uid: Option<String>,
+    pub summary: Option<String>,
+    pub description: Option<String>,
+    pub due: Option<DateTime<Utc>>,
+    pub status: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NewJournal {
+    pub uid: Option<String>,
+    pub summary: Option<String>,
+    pub description: Option<String>,
+    pub dtstart: Option<DateTime<Utc>>,
+}
+
+/// Outcome of importing one component type from an iCalendar file
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ComponentImportCount {
+    pub imported: usize,
+    pub failed: usize,
+}
+
+/// Per-type result of an ICS import
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ImportSummary {
+    pub events: ComponentImportCount,
+    pub tasks: ComponentImportCount,
+    pub journals: ComponentImportCount,
+    /// Unsupported component types that were skipped, with their counts
+    pub skipped: std::collections::BTreeMap<String, usize>,
+    /// Reasons for failed components
+    pub errors: Vec<String>,
+    /// Outlook quirks rewritten before parsing, by id (see `outlook::QUIRKS`)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub quirks: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NewShare {
+    pub shared_with_email: String,
+    pub permission: String,
+}
+
+/// Right of a delegate to act on all of an owner's calendars
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Delegation {
+    pub id: Uuid,
+    pub owner_id: Uuid,
+    pub owner_name: String,
+    pub owner_email: String,
+    pub delegate_id: Uuid,
+    pub delegate_name: String,
+    pub delegate_email: String,
+    /// `read` or `write`
+    pub permission_level: String,
+    pub created_at: DateTime<Utc>,
+}
+
+impl FromRow<'_, sqlx::sqlite::SqliteRow> for Delegation {
+    fn from_row(row: &'_ sqlx::sqlite::SqliteRow) -> Result<Self, sqlx::Error> {
+        let id_str: String = row.try_get("id")?;
+        let id = parse_uuid(&id_str).map_err(|e| sqlx::Error::ColumnDecode {
+            index: "id".to_string(),
+            source: Box::new(e),
+        })?;
+        
+        let owner_id_str: String = row.try_get("owner_id")?;
+        let owner_id = parse_uuid(&owner_id_str).map_err(|e| sqlx::Error::ColumnDecode {
+            index: "owner_id".to_string(),
+            source: Box::new(e),
+        })?;
+        
+        let delegate_id_str: String = row.try_get("delegate_id")?;
+        let delegate_id = parse_uuid(&delegate_id_str).map_err(|e| sqlx::Error::ColumnDecode {
+            index: "delegate_id".to_string(),
+            source: Box::new(e),
+        })?;
+        
+        Ok(Delegation {
+            id,
+            owner_id,
+            owner_name: row.try_get("owner_name")?,
+            owner_email: row.try_get("owner_email")?,
+            delegate_id,
+            delegate_name: row.try_get("delegate_name")?,
+            delegate_email: row.try_get("delegate_email")?,
+            permission_level: row.try_get("permission_level")?,
+            created_at: row.try_get("created_at")?,
+        })
+    }
+}
+
+/// How a user is told about upcoming events of a calendar
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum NotificationChannel {
+    #[default]
+    None,
+    /// Reminders are mailed when an event's alarm goes off
+    Email,
+}
+
+impl NotificationChannel {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            NotificationChannel::None => "none",
+            NotificationChannel::Email => "email",
+        }
+    }
+
+    pub fn from_str(s: &str) -> Self {
+        match s {
+            "email" => NotificationChannel::Email,
+            _ => NotificationChannel::None,
+        }
+    }
+}
+
+/// A user's notification channel for one of the calendars they can see
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct NotificationPreference {
+    pub calendar_id: Uuid,
+    pub calendar_name: String,
+    pub channel: NotificationChannel,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UpdateNotificationPreference {
+    pub channel: NotificationChannel,
+}
+
+/// How a user arranges one of the calendars they own or that is shared with them
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct CalendarPreference {
+    pub calendar_id: Uuid,
+    pub calendar_name: String,
+    /// Position in the user's calendar list, the calendar's own unless they moved it
+    pub sort_order: i64,
+    pub hidden: bool,
+    /// Color the user picked instead of the calendar's own
+    pub color: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct UpdateCalendarPreference {
+    pub sort_order: Option<i64>,
+    pub hidden: Option<bool>,
+    /// `null` goes back to the calendar's own color
+    #[serde(default, deserialize_with = "double_option", skip_serializing_if = "Option::is_none")]
+    pub color: Option<Option<String>>,
+}
+
+/// New order of the user's calendars, first to last
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CalendarOrder {
+    pub calendar_ids: Vec<Uuid>,
+}
+
+/// What a user changed about one calendar; unset fields keep the calendar's own
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CalendarOverride {
+    pub sort_order: Option<i64>,
+    pub hidden: bool,
+    pub color: Option<String>,
+}
+
+/// A user's stored calendar preferences, laid over the calendars they see
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CalendarLayout {
+    pub overrides: std::collections::HashMap<Uuid, CalendarOverride>,
+}
+
+impl CalendarLayout {
+    /// The calendar with the user's order and color in place of its own
+    pub fn apply(&self, mut calendar: Calendar) -> Calendar {
+        if let Some(preference) = self.overrides.get(&calendar.id) {
+            calendar.sort_order = preference.sort_order.unwrap_or(calendar.sort_order);
+            if preference.color.is_some() {
+                calendar.color = preference.color.clone();
+            }
+        }
+        calendar
+    }
+
+    /// Calendars with the user's preferences applied, in the user's order
+    pub fn arrange(&self, calendars: Vec<Calendar>) -> Vec<Calendar> {
+        let mut calendars: Vec<Calendar> = calendars.into_iter().map(|c| self.apply(c)).collect();
+        calendars.sort_by_key(|c| c.sort_order);
+        calendars
+    }
+
+    pub fn is_hidden(&self, calendar_id: Uuid) -> bool {
+        self.overrides.get(&calendar_id).is_some_and(|preference| preference.hidden)
+    }
+}
+
+/// Delegations a user has granted and received
+#[derive(Debug, Clone, Serialize, Default, PartialEq)]
+pub struct Delegations {
+    pub granted: Vec<Delegation>,
+    pub received: Vec<Delegation>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NewDelegation {
+    pub delegate_email: String,
+    /// `read` or `write`
+    pub permission: String,
+}
+
+/// Link granting access to a calendar to whoever redeems it, account or not yet
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ShareLink {
+    pub id: Uuid,
+    pub calendar_id: Uuid,
+    pub created_by: Uuid,
+    /// `read` or `write`
+    pub permission_level: String,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    pub redeemed_count: i64,
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
+impl ShareLink {
+    /// Whether the link can still be redeemed at `now`
+    pub fn is_active(&self, now: DateTime<Utc>) -> bool {
+        self.revoked_at.is_none() && self.expires_at.is_none_or(|expires_at| expires_at > now)
+    }
+}
+
+impl FromRow<'_, sqlx::sqlite::SqliteRow> for ShareLink {
+    fn from_row(row: &'_ sqlx::sqlite::SqliteRow) -> Result<Self, sqlx::Error> {
+        let id_str: String = row.try_get("id")?;
+        let id = parse_uuid(&id_str).map_err(|e| sqlx::Error::ColumnDecode {
+            index: "id".to_string(),
+            source: Box::new(e),
+        })?;
+        
+        let calendar_id_str: String = row.try_get("calendar_id")?;
+        let calendar_id = parse_uuid(&calendar_id_str).map_err(|e| sqlx::Error::ColumnDecode {
+            index: "calendar_id".to_string(),
+            source: Box::new(e),
+        })?;
+        
+        let created_by_str: String = row.try_get("created_by")?;
+        let created_by = parse_uuid(&created_by_str).map_err(|e| sqlx::Error::ColumnDecode {
+            index: "created_by".to_string(),
+            source: Box::new(e),
+        })?;
+        
+        Ok(ShareLink {
+            id,
+            calendar_id,
+            created_by,
+            permission_level: row.try_get("permission_level")?,
+            expires_at: row.try_get("expires_at")?,
+            created_at: row.try_get("created_at")?,
+            redeemed_count: row.try_get("redeemed_count")?,
+            revoked_at: row.try_get("revoked_at")?,
+        })
+    }
+}
+
+/// Share link with its URL, as shown to the calendar's managers
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct ShareLinkDetails {
+    #[serde(flatten)]
+    pub link: ShareLink,
+    pub url: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NewShareLink {
+    /// `read` or `write`; links can't grant full access
+    pub permission: String,
+    /// Days until the link stops working; leave out for a link that lasts until revoked
+    #[serde(default)]
+    pub expires_in_days: Option<u32>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NewContact {
+    pub name: String,
+    #[serde(default)]
+    pub email: Option<String>,
+    #[serde(default)]
+    pub birthday: Option<NaiveDate>,
+    #[serde(default)]
+    pub anniversary: Option<NaiveDate>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct UpdateContact {
+    pub name: Option<String>,
+    /// `null` removes the email
+    #[serde(default, deserialize_with = "double_option", skip_serializing_if = "Option::is_none")]
+    pub email: Option<Option<String>>,
+    /// `null` removes the birthday
+    #[serde(default, deserialize_with = "double_option", skip_serializing_if = "Option::is_none")]
+    pub birthday: Option<Option<NaiveDate>>,
+    /// `null` removes the anniversary
+    #[serde(default, deserialize_with = "double_option", skip_serializing_if = "Option::is_none")]
+    pub anniversary: Option<Option<NaiveDate>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NewCalendarSubscription {
+    pub name: String,
+    pub url: String,
+    pub color: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct UpdateCalendarSubscription {
+    pub name: Option<String>,
+    /// `null` removes the color
+    #[serde(default, deserialize_with = "double_option", skip_serializing_if = "Option::is_none")]
+    pub color: Option<Option<String>>,
+    pub is_visible: Option<bool>,
+}
+
+#[allow(dead_code)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UpdateShare {
+    pub permission_level: PermissionLevel,
+}
+
+// iCalendar export structures
+
+#[derive(Debug, Clone)]
+pub struct ICalendarEvent {
+    pub uid: String,
+    pub summary: String,
+    pub description: Option<String>,
+    pub location: Option<String>,
+    pub geo: Option<(f64, f64)>,
+    pub dtstart: DateTime<Utc>,
+    pub dtend: DateTime<Utc>,
+    /// Whether the dates are written as whole days (`VALUE=DATE`)
+    pub is_all_day: bool,
+    pub rrule: Option<String>,
+    pub exdates: Vec<DateTime<Utc>>,
+    pub recurrence_id: Option<DateTime<Utc>>,
+    /// Minutes before the start of a display reminder (VALARM)
+    pub alarm_minutes: Option<i64>,
+    /// Revision of the event; 0, the iCalendar default, isn't written
+    pub sequence: i64,
+    pub attachments: Vec<Attachment>,
+    /// Public server URL prefixed to attachment links
+    pub base_url: String,
+    /// Display color (RFC 7986 COLOR), set where events of several calendars are merged
+    pub color: Option<String>,
+}
+
+impl ICalendarEvent {
+    pub fn to_ical_string(&self) -> String {
+        let format_time = |time: &DateTime<Utc>| if self.is_all_day {
+            format!(";VALUE=DATE:{}", time.format("%Y%m%d"))
+        } else {
+            format!(":{}", time.format("%Y%m%dT%H%M%SZ"))
+        };
+        let mut ical = format!(
+            "BEGIN:VEVENT\r\n\
+             UID:{}\r\n\
+             SUMMARY:{}\r\n\
+             DESCRIPTION:{}\r\n\
+             LOCATION:{}\r\n\
+             DTSTART{}\r\n\
+             DTEND{}\r\n",
+            self.uid,
+            escape_ical_text(&self.summary),
+            self.description.as_ref().map(|d| escape_ical_text(d)).unwrap_or_default(),
             self.location.as_ref().map(|l| escape_ical_text(l)).unwrap_or_default(),
-            self.dtstart.format("%Y%m%dT%H%M%SZ"),
-            self.dtend.format("%Y%m%dT%H%M%SZ")
-        )
+            format_time(&self.dtstart),
+            format_time(&self.dtend)
+        );
+
+        if self.sequence > 0 {
+            ical.push_str(&format!("SEQUENCE:{}\r\n", self.sequence));
+        }
+        if let Some((lat, lon)) = self.geo {
+            ical.push_str(&format!("GEO:{};{}\r\n", lat, lon));
+        }
+        if let Some(rrule) = &self.rrule {
+            ical.push_str(&format!("RRULE:{}\r\n", rrule));
+        }
+        if !self.exdates.is_empty() && self.is_all_day {
+            let dates: Vec<_> = self.exdates.iter().map(|date| date.format("%Y%m%d").to_string()).collect();
+            ical.push_str(&format!("EXDATE;VALUE=DATE:{}\r\n", dates.join(",")));
+        } else if !self.exdates.is_empty() {
+            ical.push_str(&format!("EXDATE:{}\r\n", format_ical_date_list(&self.exdates)));
+        }
+        if let Some(recurrence_id) = &self.recurrence_id {
+            ical.push_str(&format!("RECURRENCE-ID{}\r\n", format_time(recurrence_id)));
+        }
+        for attachment in &self.attachments {
+            ical.push_str(&format!("ATTACH;FMTTYPE={}:{}{}\r\n", attachment.content_type, self.base_url, attachment.url()));
+        }
+        if let Some(color) = &self.color {
+            ical.push_str(&format!("COLOR:{}\r\n", color));
+        }
+        if let Some(minutes) = self.alarm_minutes {
+            ical.push_str(&format!(
+                "BEGIN:VALARM\r\nACTION:DISPLAY\r\nDESCRIPTION:{}\r\nTRIGGER:-PT{}M\r\nEND:VALARM\r\n",
+                escape_ical_text(&self.summary),
+                minutes,
+            ));
+        }
+
+        ical.push_str("END:VEVENT\r\n");
+        ical
     }
 }
 
 impl From<&Event> for ICalendarEvent {
     fn from(event: &Event) -> Self {
         Self {
-            uid: event.id.to_string(),
+            // Overridden instances share the UID of their series master, which
+            // `CalendarService::ical_event` fills in when the master has its own
+            uid: event.uid.clone().unwrap_or_else(|| event.parent_id.unwrap_or(event.id).to_string()),
             summary: event.title.clone(),
             description: event.description.clone(),
             location: event.location.clone(),
+            geo: event.geo_lat.zip(event.geo_lon),
             dtstart: event.start_time,
             dtend: event.end_time,
+            is_all_day: event.is_all_day,
+            rrule: event.rrule.clone(),
+            exdates: event.exdates.clone(),
+            recurrence_id: event.recurrence_id,
+            alarm_minutes: event.alarm_minutes,
+            sequence: event.sequence,
+            attachments: Vec::new(),
+            base_url: String::new(),
+            color: None,
         }
     }
 }
@@ -0,0 +1,148 @@
+//! Minimal OpenID Connect client (authorization code flow) for signing in with,
+//! and linking, external identities
+
+use jsonwebtoken::{decode, decode_header, jwk::JwkSet, DecodingKey, Validation};
+use serde::{Deserialize, Serialize};
+
+use crate::error::AppError;
+
+/// Provider settings for OIDC sign-in
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct OidcConfig {
+    pub issuer: String,
+    pub client_id: String,
+    pub client_secret: String,
+    pub redirect_url: String,
+}
+
+impl OidcConfig {
+    /// Read provider settings from `OIDC_*` variables; OIDC stays disabled unless
+    /// issuer, client id and redirect URL are all set
+    pub fn from_env() -> Option<Self> {
+        Some(Self {
+            issuer: std::env::var("OIDC_ISSUER").ok()?,
+            client_id: std::env::var("OIDC_CLIENT_ID").ok()?,
+            client_secret: std::env::var("OIDC_CLIENT_SECRET").unwrap_or_default(),
+            redirect_url: std::env::var("OIDC_REDIRECT_URL").ok()?,
+        })
+    }
+}
+
+/// Subset of the provider's discovery document we rely on
+#[derive(Debug, Deserialize)]
+struct ProviderMetadata {
+    authorization_endpoint: String,
+    token_endpoint: String,
+    jwks_uri: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct TokenResponse {
+    id_token: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct IdTokenClaims {
+    iss: String,
+    sub: String,
+    email: Option<String>,
+    nonce: Option<String>,
+}
+
+/// External identity asserted by the provider's ID token
+#[derive(Debug, Clone)]
+pub struct ExternalIdentity {
+    pub issuer: String,
+    pub subject: String,
+    pub email: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct OidcClient {
+    config: OidcConfig,
+    http_client: reqwest::Client,
+}
+
+impl OidcClient {
+    pub fn new(config: OidcConfig) -> Self {
+        Self {
+            config,
+            http_client: reqwest::Client::new(),
+        }
+    }
+
+    async fn get_json<T: serde::de::DeserializeOwned>(&self, url: &str) -> Result<T, AppError> {
+        let body = self.http_client.get(url).send().await
+            .and_then(|r| r.error_for_status())
+            .map_err(|e| AppError::InternalServerError(format!("OIDC request failed: {}", e)))?
+            .text().await
+            .map_err(|e| AppError::InternalServerError(format!("OIDC request failed: {}", e)))?;
+        serde_json::from_str(&body)
+            .map_err(|e| AppError::InternalServerError(format!("Invalid OIDC response: {}", e)))
+    }
+
+    async fn metadata(&self) -> Result<ProviderMetadata, AppError> {
+        self.get_json(&format!("{}/.well-known/openid-configuration", self.config.issuer.trim_end_matches('/'))).await
+    }
+
+    /// URL to send the browser to for authentication at the provider
+    pub async fn authorization_url(&self, state: &str, nonce: &str) -> Result<String, AppError> {
+        let metadata = self.metadata().await?;
+        let url = reqwest::Url::parse_with_params(&metadata.authorization_endpoint, &[
+            ("response_type", "code"),
+            ("client_id", self.config.client_id.as_str()),
+            ("redirect_uri", self.config.redirect_url.as_str()),
+            ("scope", "openid email profile"),
+            ("state", state),
+            ("nonce", nonce),
+        ])
+        .map_err(|e| AppError::InternalServerError(format!("Invalid authorization endpoint: {}", e)))?;
+        Ok(url.to_string())
+    }
+
+    /// Exchange an authorization code and verify the returned ID token against the
+    /// provider's signing keys, issuer, audience and the expected nonce
+    pub async fn exchange_code(&self, code: &str, nonce: &str) -> Result<ExternalIdentity, AppError> {
+        let metadata = self.metadata().await?;
+
+        let body = self.http_client
+            .post(&metadata.token_endpoint)
+            .form(&[
+                ("grant_type", "authorization_code"),
+                ("code", code),
+                ("redirect_uri", self.config.redirect_url.as_str()),
+                ("client_id", self.config.client_id.as_str()),
+                ("client_secret", self.config.client_secret.as_str()),
+            ])
+            .send().await
+            .and_then(|r| r.error_for_status())
+            .map_err(|e| AppError::AuthenticationError(format!("OIDC code exchange failed: {}", e)))?
+            .text().await
+            .map_err(|e| AppError::AuthenticationError(format!("OIDC code exchange failed: {}", e)))?;
+        let token: TokenResponse = serde_json::from_str(&body)
+            .map_err(|e| AppError::AuthenticationError(format!("Invalid token response: {}", e)))?;
+
+        let header = decode_header(&token.id_token)?;
+        let jwks: JwkSet = self.get_json(&metadata.jwks_uri).await?;
+        let jwk = match header.kid.as_deref() {
+            Some(kid) => jwks.find(kid),
+            None => jwks.keys.first(),
+        }
+        .ok_or_else(|| AppError::AuthenticationError("Unknown ID token signing key".to_string()))?;
+
+        let mut validation = Validation::new(header.alg);
+        validation.set_audience(&[&self.config.client_id]);
+        validation.set_issuer(&[&self.config.issuer]);
+
+        let claims = decode::<IdTokenClaims>(&token.id_token, &DecodingKey::from_jwk(jwk)?, &validation)?.claims;
+        if claims.nonce.as_deref() != Some(nonce) {
+            return Err(AppError::AuthenticationError("ID token nonce mismatch".to_string()));
+        }
+
+        Ok(ExternalIdentity {
+            issuer: claims.iss,
+            subject: claims.sub,
+            email: claims.email,
+        })
+    }
+}
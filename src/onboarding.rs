@@ -0,0 +1,61 @@
+//! Calendars and starter content provisioned for newly registered users
+
+use serde::{Deserialize, Serialize};
+
+/// Default set of calendars created on registration
+pub const DEFAULT_CALENDARS: &str = "Personal:#3B82F6,Work:#10B981";
+
+/// Title of the starter event placed in the first default calendar
+pub const STARTER_EVENT_TITLE: &str = "Welcome to My CalDAV Server";
+
+/// Description of the starter event, doubling as a short tour
+pub const STARTER_EVENT_DESCRIPTION: &str = "A quick tour to get you started:\n\
+- Create events from the dashboard or the Events page.\n\
+- Share calendars with other users from the calendar's page.\n\
+- Connect phones and desktop clients via CalDAV at /calendars/ with your email and password.\n\
+- Feel free to delete this event once you're done.";
+
+/// Calendar created automatically for new users
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DefaultCalendar {
+    pub name: String,
+    pub color: Option<String>,
+}
+
+/// Instance setting describing what new accounts start with
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Onboarding {
+    pub calendars: Vec<DefaultCalendar>,
+    /// Whether to add a welcome event to the first default calendar
+    pub starter_event: bool,
+}
+
+impl Default for Onboarding {
+    fn default() -> Self {
+        Self {
+            calendars: parse_default_calendars(DEFAULT_CALENDARS),
+            starter_event: true,
+        }
+    }
+}
+
+/// Parse a comma-separated list of `Name[:#color]` entries. An empty string
+/// yields no default calendars.
+pub fn parse_default_calendars(value: &str) -> Vec<DefaultCalendar> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| match entry.split_once(':') {
+            Some((name, color)) => DefaultCalendar {
+                name: name.trim().to_string(),
+                color: Some(color.trim().to_string()).filter(|c| !c.is_empty()),
+            },
+            None => DefaultCalendar {
+                name: entry.to_string(),
+                color: None,
+            },
+        })
+        .filter(|calendar| !calendar.name.is_empty())
+        .collect()
+}
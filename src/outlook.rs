@@ -0,0 +1,240 @@
+//! Compatibility with the iCalendar files Outlook and Exchange write
+//!
+//! Outlook names time zones by their Windows IDs, marks all-day events with its own
+//! `X-MICROSOFT-CDO-ALLDAYEVENT` property and tags titles with a `LANGUAGE` parameter,
+//! none of which the event parser understands.
+//! Files whose PRODID names Microsoft, and imports asking for `?compat=outlook`, have
+//! their events rewritten to standard iCalendar before they are parsed; [`QUIRKS`] lists
+//! what is rewritten. Line endings other than CRLF are accepted from every file.
+
+use chrono_tz::Tz;
+
+/// Outlook quirk handled by the compatibility mode
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Quirk {
+    /// Name reported in import summaries
+    pub id: &'static str,
+    pub description: &'static str,
+}
+
+/// Quirks rewritten in compatibility mode; other `X-MICROSOFT-CDO-*` properties are ignored
+/// like any unknown property
+pub const QUIRKS: [Quirk; 3] = [
+    Quirk {
+        id: "windows-time-zones",
+        description: "TZIDs such as \"W. Europe Standard Time\" are read as the IANA zone Windows maps them to",
+    },
+    Quirk {
+        id: "cdo-all-day",
+        description: "Events with X-MICROSOFT-CDO-ALLDAYEVENT:TRUE and times at midnight are all-day events",
+    },
+    Quirk {
+        id: "language-parameters",
+        description: "SUMMARY, DESCRIPTION and LOCATION tagged with a LANGUAGE are read like untagged ones",
+    },
+];
+
+const WINDOWS_TIME_ZONES: Quirk = QUIRKS[0];
+const CDO_ALL_DAY: Quirk = QUIRKS[1];
+const LANGUAGE_PARAMETERS: Quirk = QUIRKS[2];
+
+/// Windows time zone IDs and the IANA zones they stand for, from the CLDR's
+/// `windowsZones.xml` (territory `001`)
+const WINDOWS_ZONES: &[(&str, &str)] = &[
+    ("Dateline Standard Time", "Etc/GMT+12"),
+    ("UTC-11", "Etc/GMT+11"),
+    ("Aleutian Standard Time", "America/Adak"),
+    ("Hawaiian Standard Time", "Pacific/Honolulu"),
+    ("Alaskan Standard Time", "America/Anchorage"),
+    ("Pacific Standard Time (Mexico)", "America/Tijuana"),
+    ("Pacific Standard Time", "America/Los_Angeles"),
+    ("US Mountain Standard Time", "America/Phoenix"),
+    ("Mountain Standard Time (Mexico)", "America/Mazatlan"),
+    ("Mountain Standard Time", "America/Denver"),
+    ("Central America Standard Time", "America/Guatemala"),
+    ("Central Standard Time", "America/Chicago"),
+    ("Central Standard Time (Mexico)", "America/Mexico_City"),
+    ("Canada Central Standard Time", "America/Regina"),
+    ("SA Pacific Standard Time", "America/Bogota"),
+    ("Eastern Standard Time (Mexico)", "America/Cancun"),
+    ("Eastern Standard Time", "America/New_York"),
+    ("US Eastern Standard Time", "America/Indiana/Indianapolis"),
+    ("Venezuela Standard Time", "America/Caracas"),
+    ("Atlantic Standard Time", "America/Halifax"),
+    ("SA Western Standard Time", "America/La_Paz"),
+    ("Central Brazilian Standard Time", "America/Cuiaba"),
+    ("Pacific SA Standard Time", "America/Santiago"),
+    ("Newfoundland Standard Time", "America/St_Johns"),
+    ("E. South America Standard Time", "America/Sao_Paulo"),
+    ("Argentina Standard Time", "America/Argentina/Buenos_Aires"),
+    ("SA Eastern Standard Time", "America/Cayenne"),
+    ("Greenland Standard Time", "America/Nuuk"),
+    ("Montevideo Standard Time", "America/Montevideo"),
+    ("UTC-02", "Etc/GMT+2"),
+    ("Azores Standard Time", "Atlantic/Azores"),
+    ("Cape Verde Standard Time", "Atlantic/Cape_Verde"),
+    ("UTC", "Etc/UTC"),
+    ("GMT Standard Time", "Europe/London"),
+    ("Greenwich Standard Time", "Atlantic/Reykjavik"),
+    ("Morocco Standard Time", "Africa/Casablanca"),
+    ("W. Europe Standard Time", "Europe/Berlin"),
+    ("Central Europe Standard Time", "Europe/Budapest"),
+    ("Romance Standard Time", "Europe/Paris"),
+    ("Central European Standard Time", "Europe/Warsaw"),
+    ("W. Central Africa Standard Time", "Africa/Lagos"),
+    ("GTB Standard Time", "Europe/Bucharest"),
+    ("Middle East Standard Time", "Asia/Beirut"),
+    ("Egypt Standard Time", "Africa/Cairo"),
+    ("E. Europe Standard Time", "Europe/Chisinau"),
+    ("FLE Standard Time", "Europe/Kiev"),
+    ("Israel Standard Time", "Asia/Jerusalem"),
+    ("South Africa Standard Time", "Africa/Johannesburg"),
+    ("Kaliningrad Standard Time", "Europe/Kaliningrad"),
+    ("Jordan Standard Time", "Asia/Amman"),
+    ("Arabic Standard Time", "Asia/Baghdad"),
+    ("Turkey Standard Time", "Europe/Istanbul"),
+    ("Arab Standard Time", "Asia/Riyadh"),
+    ("Belarus Standard Time", "Europe/Minsk"),
+    ("Russian Standard Time", "Europe/Moscow"),
+    ("E. Africa Standard Time", "Africa/Nairobi"),
+    ("Iran Standard Time", "Asia/Tehran"),
+    ("Arabian Standard Time", "Asia/Dubai"),
+    ("Azerbaijan Standard Time", "Asia/Baku"),
+    ("Georgian Standard Time", "Asia/Tbilisi"),
+    ("Caucasus Standard Time", "Asia/Yerevan"),
+    ("Afghanistan Standard Time", "Asia/Kabul"),
+    ("West Asia Standard Time", "Asia/Tashkent"),
+    ("Pakistan Standard Time", "Asia/Karachi"),
+    ("India Standard Time", "Asia/Kolkata"),
+    ("Sri Lanka Standard Time", "Asia/Colombo"),
+    ("Nepal Standard Time", "Asia/Kathmandu"),
+    ("Central Asia Standard Time", "Asia/Almaty"),
+    ("Bangladesh Standard Time", "Asia/Dhaka"),
+    ("Myanmar Standard Time", "Asia/Yangon"),
+    ("SE Asia Standard Time", "Asia/Bangkok"),
+    ("N. Central Asia Standard Time", "Asia/Novosibirsk"),
+    ("China Standard Time", "Asia/Shanghai"),
+    ("North Asia Standard Time", "Asia/Krasnoyarsk"),
+    ("Singapore Standard Time", "Asia/Singapore"),
+    ("W. Australia Standard Time", "Australia/Perth"),
+    ("Taipei Standard Time", "Asia/Taipei"),
+    ("Tokyo Standard Time", "Asia/Tokyo"),
+    ("Korea Standard Time", "Asia/Seoul"),
+    ("Cen. Australia Standard Time", "Australia/Adelaide"),
+    ("AUS Central Standard Time", "Australia/Darwin"),
+    ("E. Australia Standard Time", "Australia/Brisbane"),
+    ("AUS Eastern Standard Time", "Australia/Sydney"),
+    ("West Pacific Standard Time", "Pacific/Port_Moresby"),
+    ("Tasmania Standard Time", "Australia/Hobart"),
+    ("Vladivostok Standard Time", "Asia/Vladivostok"),
+    ("New Zealand Standard Time", "Pacific/Auckland"),
+    ("Fiji Standard Time", "Pacific/Fiji"),
+    ("Tonga Standard Time", "Pacific/Tongatapu"),
+    ("Samoa Standard Time", "Pacific/Apia"),
+    ("Line Islands Standard Time", "Pacific/Kiritimati"),
+];
+
+/// Properties whose TZID parameter names the zone of their value
+const TIME_PROPERTIES: [&str; 5] = ["DTSTART", "DTEND", "RECURRENCE-ID", "EXDATE", "DUE"];
+
+/// Text properties Outlook tags with the language they are written in
+const TEXT_PROPERTIES: [&str; 3] = ["SUMMARY", "DESCRIPTION", "LOCATION"];
+
+/// Whether a PRODID, such as `-//Microsoft Corporation//Outlook 16.0 MIMEDIR//EN`,
+/// names a Microsoft product
+pub fn is_microsoft(prod_id: &str) -> bool {
+    prod_id.to_ascii_lowercase().contains("microsoft")
+}
+
+/// Whether an iCalendar object was written by Outlook or Exchange, going by its PRODID
+pub fn written_by_outlook(data: &str) -> bool {
+    data.lines()
+        .find_map(|line| line.strip_prefix("PRODID:"))
+        .is_some_and(is_microsoft)
+}
+
+/// The IANA zone of a Windows time zone ID
+pub fn windows_time_zone(name: &str) -> Option<Tz> {
+    WINDOWS_ZONES.iter()
+        .find(|(windows, _)| windows.eq_ignore_ascii_case(name.trim()))
+        .and_then(|(_, iana)| iana.parse().ok())
+}
+
+/// Rewrite the Outlook quirks of an iCalendar object or component to standard
+/// iCalendar, returning it with the quirks that were found in it
+pub fn normalize(data: &str) -> (String, Vec<Quirk>) {
+    let mut found = Vec::new();
+    let all_day = data.lines().any(|line| {
+        line.trim().eq_ignore_ascii_case("X-MICROSOFT-CDO-ALLDAYEVENT:TRUE")
+    });
+    let mut in_time_zone = false;
+    let mut normalized = String::with_capacity(data.len());
+
+    for line in data.lines() {
+        let upper = line.to_ascii_uppercase();
+        if upper.starts_with("BEGIN:VTIMEZONE") {
+            in_time_zone = true;
+        } else if upper.starts_with("END:VTIMEZONE") {
+            in_time_zone = false;
+        }
+
+        let name = upper.split([';', ':']).next().unwrap_or_default();
+        let rewritten = match line.rsplit_once(':') {
+            Some((params, value)) if !in_time_zone && TIME_PROPERTIES.contains(&name) => {
+                rewrite_time(name, params, value, all_day, &mut found)
+            }
+            _ if TEXT_PROPERTIES.contains(&name) && upper[name.len()..].starts_with(";LANGUAGE=") => {
+                // Text values may contain colons, so the value starts after the first one
+                line.split_once(':').map(|(_, value)| {
+                    add(&mut found, LANGUAGE_PARAMETERS);
+                    format!("{}:{}", name, value)
+                })
+            }
+            _ => None,
+        };
+        normalized.push_str(rewritten.as_deref().unwrap_or(line));
+        normalized.push('\n');
+    }
+
+    (normalized, found)
+}
+
+/// A time property with a Windows TZID replaced, or turned into a date if the event is
+/// all-day; `None` if it needs neither
+fn rewrite_time(name: &str, params: &str, value: &str, all_day: bool, found: &mut Vec<Quirk>) -> Option<String> {
+    let values: Vec<&str> = value.split(',').map(str::trim).collect();
+    let midnight = values.iter().all(|value| value.is_ascii() && value.len() >= 15 && value[8..].starts_with("T000000"));
+    if all_day && midnight && name != "DUE" {
+        add(found, CDO_ALL_DAY);
+        let dates: Vec<&str> = values.iter().map(|value| &value[..8]).collect();
+        return Some(format!("{};VALUE=DATE:{}", name, dates.join(",")));
+    }
+
+    let mut changed = false;
+    let params: Vec<String> = params.split(';').map(|param| {
+        match param.strip_prefix("TZID=") {
+            Some(zone) => {
+                let zone = zone.trim_matches('"');
+                match (zone.parse::<Tz>(), windows_time_zone(zone)) {
+                    (Err(_), Some(iana)) => {
+                        changed = true;
+                        format!("TZID={}", iana.name())
+                    }
+                    _ => param.to_string(),
+                }
+            }
+            None => param.to_string(),
+        }
+    }).collect();
+
+    changed.then(|| {
+        add(found, WINDOWS_TIME_ZONES);
+        format!("{}:{}", params.join(";"), value)
+    })
+}
+
+fn add(found: &mut Vec<Quirk>, quirk: Quirk) {
+    if !found.contains(&quirk) {
+        found.push(quirk);
+    }
+}
@@ -0,0 +1,81 @@
+//! Checks on WebDAV-Push delivery targets
+//!
+//! Any user who can read a calendar may register a push resource, which the server
+//! then POSTs to whenever the calendar changes. Targets must therefore be public
+//! HTTPS endpoints: the host is resolved and refused when any of its addresses is
+//! loopback, private, link-local or otherwise not routable on the internet. The check
+//! runs at registration and again before every delivery, since DNS answers change.
+
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::time::Duration;
+
+use reqwest::Url;
+
+use crate::error::AppError;
+
+/// Push subscriptions a user may hold across all calendars
+pub const MAX_PUSH_SUBSCRIPTIONS_PER_USER: usize = 100;
+
+/// How long a push service may take to accept a message
+pub const PUSH_DELIVERY_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Parse a push resource and make sure it points at a public HTTPS host
+pub async fn check_push_resource(value: &str) -> Result<Url, AppError> {
+    let invalid = |reason: &str| AppError::ValidationError(format!("Invalid push-resource: {}", reason));
+
+    let url = Url::parse(value).map_err(|_| invalid("not a URL"))?;
+    if url.scheme() != "https" {
+        return Err(invalid("must use https"));
+    }
+    let host = url.host_str().ok_or_else(|| invalid("missing host"))?;
+    let port = url.port_or_known_default().unwrap_or(443);
+
+    // IPv6 literals come bracketed, which the resolver doesn't accept
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    let addresses: Vec<IpAddr> = tokio::net::lookup_host((host, port)).await
+        .map_err(|_| invalid("host doesn't resolve"))?
+        .map(|address| address.ip())
+        .collect();
+    if addresses.is_empty() || !addresses.iter().all(|ip| is_public(*ip)) {
+        return Err(invalid("host must be reachable on the public internet"));
+    }
+
+    Ok(url)
+}
+
+/// Whether an address is routable on the public internet
+fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_public_v4(ip),
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_public_v4(ip),
+            None => is_public_v6(ip),
+        },
+    }
+}
+
+fn is_public_v4(ip: Ipv4Addr) -> bool {
+    let [a, b, ..] = ip.octets();
+    !(ip.is_loopback()
+        || ip.is_private()
+        || ip.is_link_local()
+        || ip.is_unspecified()
+        || ip.is_broadcast()
+        || ip.is_multicast()
+        || ip.is_documentation()
+        // Carrier-grade NAT, 100.64.0.0/10
+        || (a == 100 && (64..128).contains(&b))
+        // "This network", 0.0.0.0/8
+        || a == 0)
+}
+
+fn is_public_v6(ip: Ipv6Addr) -> bool {
+    let first = ip.segments()[0];
+    !(ip.is_loopback()
+        || ip.is_unspecified()
+        || ip.is_multicast()
+        // Unique local, fc00::/7
+        || (first & 0xfe00) == 0xfc00
+        // Link-local, fe80::/10
+        || (first & 0xffc0) == 0xfe80)
+}
@@ -0,0 +1,48 @@
+//! Per-user and per-calendar storage limits
+//!
+//! Limits are checked by `CalendarService` wherever calendars and events are created,
+//! so the REST API, the web UI, CalDAV and JMAP all enforce them the same way.
+//! Exceeding a count limit is reported as 507 Insufficient Storage.
+
+use serde::{Deserialize, Serialize};
+
+/// Default largest iCalendar object accepted by CalDAV PUT or as an imported component (10 MiB)
+pub const DEFAULT_MAX_ICS_SIZE: usize = 10 * 1024 * 1024;
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Quotas {
+    /// Calendars a user may own; `None` is unlimited
+    pub max_calendars_per_user: Option<usize>,
+    /// Events (series count once) a calendar may hold; `None` is unlimited
+    pub max_events_per_calendar: Option<usize>,
+    /// Largest iCalendar object in bytes accepted by CalDAV PUT and per imported component
+    pub max_ics_size: usize,
+}
+
+impl Default for Quotas {
+    fn default() -> Self {
+        Self {
+            max_calendars_per_user: None,
+            max_events_per_calendar: None,
+            max_ics_size: DEFAULT_MAX_ICS_SIZE,
+        }
+    }
+}
+
+/// Usage of one limit
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct QuotaUsage {
+    pub label: String,
+    pub used: usize,
+    pub limit: Option<usize>,
+}
+
+impl QuotaUsage {
+    /// Share of the limit in use, in percent (0 when unlimited)
+    pub fn percent(&self) -> usize {
+        match self.limit {
+            Some(limit) if limit > 0 => (self.used * 100 / limit).min(100),
+            _ => 0,
+        }
+    }
+}
@@ -0,0 +1,88 @@
+//! Soft per-client rate limits for expensive endpoints
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// Default number of expensive requests a client may make per window
+pub const DEFAULT_RATE_LIMIT_REQUESTS: u32 = 60;
+
+/// Default length of a rate limit window in seconds
+pub const DEFAULT_RATE_LIMIT_WINDOW_SECS: u64 = 60;
+
+/// Number of tracked clients above which expired windows are pruned
+const PRUNE_THRESHOLD: usize = 10_000;
+
+/// Outcome of counting a request against a client's window
+#[derive(Debug, Clone, Copy)]
+pub struct RateLimitStatus {
+    pub limit: u32,
+    pub remaining: u32,
+    /// Unix timestamp (seconds) at which the current window resets
+    pub reset: u64,
+    /// Seconds until the window resets
+    pub retry_after: u64,
+    pub allowed: bool,
+}
+
+#[derive(Debug)]
+struct Window {
+    started: Instant,
+    count: u32,
+}
+
+/// Fixed-window request counter keyed by client
+#[derive(Debug, Clone)]
+pub struct RateLimiter {
+    limit: u32,
+    window: Duration,
+    windows: Arc<Mutex<HashMap<String, Window>>>,
+}
+
+impl Default for RateLimiter {
+    fn default() -> Self {
+        Self::new(DEFAULT_RATE_LIMIT_REQUESTS, DEFAULT_RATE_LIMIT_WINDOW_SECS)
+    }
+}
+
+impl RateLimiter {
+    pub fn new(limit: u32, window_secs: u64) -> Self {
+        Self {
+            limit,
+            window: Duration::from_secs(window_secs.max(1)),
+            windows: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Count a request for `key` and report the client's remaining budget
+    pub fn check(&self, key: &str) -> RateLimitStatus {
+        let now = Instant::now();
+        let mut windows = self.windows.lock().unwrap_or_else(|e| e.into_inner());
+
+        if windows.len() > PRUNE_THRESHOLD {
+            windows.retain(|_, w| now.duration_since(w.started) < self.window);
+        }
+
+        let window = windows.entry(key.to_string()).or_insert(Window { started: now, count: 0 });
+        if now.duration_since(window.started) >= self.window {
+            window.started = now;
+            window.count = 0;
+        }
+
+        let allowed = window.count < self.limit;
+        if allowed {
+            window.count += 1;
+        }
+
+        let retry_after = self.window.saturating_sub(now.duration_since(window.started)).as_secs().max(1);
+        let unix_now = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+
+        RateLimitStatus {
+            limit: self.limit,
+            remaining: self.limit - window.count,
+            reset: unix_now + retry_after,
+            retry_after,
+            allowed,
+        }
+    }
+}
@@ -0,0 +1,253 @@
+//! Expansion of recurring events into concrete instances
+//!
+//! Supports the RRULE subset the server creates itself: FREQ (DAILY, WEEKLY,
+//! MONTHLY, YEARLY) with optional INTERVAL, COUNT and UNTIL. Other rule parts
+//! are ignored.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use chrono::{DateTime, Duration, Months, NaiveDateTime, Utc};
+use uuid::Uuid;
+
+use crate::models::Event;
+
+/// Upper bound on generated instances, guarding against unbounded rules
+const MAX_INSTANCES: usize = 10_000;
+
+/// Number of cached expansions above which the cache is emptied
+const CACHE_CAPACITY: usize = 4_096;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Frequency {
+    Daily,
+    Weekly,
+    Monthly,
+    Yearly,
+}
+
+/// Parsed recurrence rule
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rule {
+    freq: Frequency,
+    interval: u32,
+    count: Option<usize>,
+    until: Option<DateTime<Utc>>,
+}
+
+impl Rule {
+    /// Parse an RRULE value such as `FREQ=WEEKLY;INTERVAL=2;COUNT=10`
+    pub fn parse(value: &str) -> Option<Self> {
+        let mut freq = None;
+        let mut interval = 1;
+        let mut count = None;
+        let mut until = None;
+
+        for part in value.split(';') {
+            let (key, val) = part.split_once('=')?;
+            match key.trim().to_ascii_uppercase().as_str() {
+                "FREQ" => {
+                    freq = Some(match val.trim().to_ascii_uppercase().as_str() {
+                        "DAILY" => Frequency::Daily,
+                        "WEEKLY" => Frequency::Weekly,
+                        "MONTHLY" => Frequency::Monthly,
+                        "YEARLY" => Frequency::Yearly,
+                        _ => return None,
+                    });
+                }
+                "INTERVAL" => interval = val.trim().parse().ok().filter(|i| *i > 0)?,
+                "COUNT" => count = val.trim().parse().ok(),
+                "UNTIL" => until = parse_until(val.trim()),
+                _ => {}
+            }
+        }
+
+        Some(Self {
+            freq: freq?,
+            interval,
+            count,
+            until,
+        })
+    }
+
+    /// Lowercase frequency name, as used by JSCalendar (RFC 8984)
+    pub fn frequency(&self) -> &'static str {
+        match self.freq {
+            Frequency::Daily => "daily",
+            Frequency::Weekly => "weekly",
+            Frequency::Monthly => "monthly",
+            Frequency::Yearly => "yearly",
+        }
+    }
+
+    pub fn interval(&self) -> u32 {
+        self.interval
+    }
+
+    pub fn count(&self) -> Option<usize> {
+        self.count
+    }
+
+    pub fn until(&self) -> Option<DateTime<Utc>> {
+        self.until
+    }
+
+    /// Start of the n-th instance (0 being the series start)
+    fn nth_start(&self, start: DateTime<Utc>, n: u32) -> Option<DateTime<Utc>> {
+        let steps = n.checked_mul(self.interval)?;
+        match self.freq {
+            Frequency::Daily => start.checked_add_signed(Duration::try_days(steps as i64)?),
+            Frequency::Weekly => start.checked_add_signed(Duration::try_weeks(steps as i64)?),
+            Frequency::Monthly => start.checked_add_months(Months::new(steps)),
+            Frequency::Yearly => start.checked_add_months(Months::new(steps.checked_mul(12)?)),
+        }
+    }
+}
+
+fn parse_until(value: &str) -> Option<DateTime<Utc>> {
+    let value = value.trim_end_matches('Z');
+    if value.len() == 8 {
+        // DATE form: the whole day is included
+        return chrono::NaiveDate::parse_from_str(value, "%Y%m%d").ok()
+            .and_then(|d| d.and_hms_opt(23, 59, 59))
+            .map(|dt| dt.and_utc());
+    }
+    NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S").ok().map(|dt| dt.and_utc())
+}
+
+/// Instances (start, end) of an event overlapping `[window_start, window_end)`.
+///
+/// Non-recurring events yield at most their own span. For series masters,
+/// excluded dates (EXDATE) and instances listed in `overridden` (the
+/// RECURRENCE-IDs of exception events, which are expanded separately) are skipped.
+pub fn instances(
+    event: &Event,
+    window_start: DateTime<Utc>,
+    window_end: DateTime<Utc>,
+    overridden: &[DateTime<Utc>],
+) -> Vec<(DateTime<Utc>, DateTime<Utc>)> {
+    let duration = event.end_time - event.start_time;
+    let overlaps = |start: DateTime<Utc>| start < window_end && start + duration > window_start;
+
+    let Some(rule) = event.rrule.as_deref().and_then(Rule::parse) else {
+        return if overlaps(event.start_time) {
+            vec![(event.start_time, event.end_time)]
+        } else {
+            Vec::new()
+        };
+    };
+
+    let mut result = Vec::new();
+    for n in 0..MAX_INSTANCES as u32 {
+        if rule.count.is_some_and(|count| n as usize >= count) {
+            break;
+        }
+        let Some(start) = rule.nth_start(event.start_time, n) else {
+            break;
+        };
+        if start >= window_end || rule.until.is_some_and(|until| start > until) {
+            break;
+        }
+        if overlaps(start) && !event.exdates.contains(&start) && !overridden.contains(&start) {
+            result.push((start, start + duration));
+        }
+    }
+
+    result
+}
+
+/// First instance (start, end) of an event that has not ended by `now`.
+///
+/// Non-recurring events yield their own span while it lasts; series masters yield
+/// the next instance that is neither excluded nor listed in `overridden`.
+pub fn next_occurrence(
+    event: &Event,
+    now: DateTime<Utc>,
+    overridden: &[DateTime<Utc>],
+) -> Option<(DateTime<Utc>, DateTime<Utc>)> {
+    let duration = event.end_time - event.start_time;
+
+    let Some(rule) = event.rrule.as_deref().and_then(Rule::parse) else {
+        return (event.end_time > now).then_some((event.start_time, event.end_time));
+    };
+
+    for n in 0..MAX_INSTANCES as u32 {
+        if rule.count.is_some_and(|count| n as usize >= count) {
+            return None;
+        }
+        let start = rule.nth_start(event.start_time, n)?;
+        if rule.until.is_some_and(|until| start > until) {
+            return None;
+        }
+        if start + duration > now && !event.exdates.contains(&start) && !overridden.contains(&start) {
+            return Some((start, start + duration));
+        }
+    }
+
+    None
+}
+
+/// Next occurrence of every event in `events` that has not ended by `now`, sorted by
+/// start. Overridden instances of a series are reported through their exception event,
+/// which must be part of `events` as well.
+pub fn upcoming(events: &[Event], now: DateTime<Utc>) -> Vec<(&Event, DateTime<Utc>, DateTime<Utc>)> {
+    let mut result: Vec<_> = events
+        .iter()
+        .filter_map(|event| {
+            let overridden: Vec<_> = events
+                .iter()
+                .filter(|e| e.parent_id == Some(event.id))
+                .filter_map(|e| e.recurrence_id)
+                .collect();
+            let (start, end) = next_occurrence(event, now, &overridden)?;
+            Some((event, start, end))
+        })
+        .collect();
+    result.sort_by_key(|(_, start, _)| *start);
+
+    result
+}
+
+/// Event version and window an expansion was computed for
+type CacheKey = (Uuid, DateTime<Utc>, DateTime<Utc>, DateTime<Utc>);
+
+/// Start and end of each instance
+type Instances = Vec<(DateTime<Utc>, DateTime<Utc>)>;
+
+/// Memoised instances of series masters, so repeated checks of the same window
+/// don't walk long-running rules again. Entries are keyed by the event's
+/// `updated_at`, so an edited series is expanded afresh.
+#[derive(Debug, Clone, Default)]
+pub struct ExpansionCache {
+    entries: Arc<Mutex<HashMap<CacheKey, Instances>>>,
+}
+
+impl ExpansionCache {
+    /// Same as [`instances`], served from the cache for recurring events
+    pub fn instances(
+        &self,
+        event: &Event,
+        window_start: DateTime<Utc>,
+        window_end: DateTime<Utc>,
+        overridden: &[DateTime<Utc>],
+    ) -> Vec<(DateTime<Utc>, DateTime<Utc>)> {
+        if event.rrule.is_none() {
+            return instances(event, window_start, window_end, overridden);
+        }
+
+        // Overrides live on other rows, so they are applied after the lookup
+        let key = (event.id, event.updated_at, window_start, window_end);
+        let cached = self.entries.lock().unwrap_or_else(|e| e.into_inner()).get(&key).cloned();
+        let all = cached.unwrap_or_else(|| {
+            let all = instances(event, window_start, window_end, &[]);
+            let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+            if entries.len() >= CACHE_CAPACITY {
+                entries.clear();
+            }
+            entries.insert(key, all.clone());
+            all
+        });
+
+        all.into_iter().filter(|(start, _)| !overridden.contains(start)).collect()
+    }
+}
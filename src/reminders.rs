@@ -0,0 +1,46 @@
+//! Event reminders
+//!
+//! Events carry an optional alarm, in minutes before their start; calendars can
+//! set a default alarm for events created without one. Users who chose email
+//! notifications for a calendar are mailed when an alarm goes off.
+
+use std::time::Duration;
+use crate::error::AppError;
+use crate::services::CalendarService;
+
+/// Time between checks for due reminders
+pub const REMINDER_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Longest supported alarm: four weeks before the start
+pub const MAX_ALARM_MINUTES: i64 = 4 * 7 * 24 * 60;
+
+/// Check that an alarm lies between the start and `MAX_ALARM_MINUTES` before it
+pub fn validate_alarm_minutes(field: &str, minutes: Option<i64>) -> Result<(), AppError> {
+    match minutes {
+        Some(minutes) if !(0..=MAX_ALARM_MINUTES).contains(&minutes) => Err(AppError::invalid_field(
+            field,
+            format!("must be between 0 and {} minutes", MAX_ALARM_MINUTES),
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Mail due reminders every `REMINDER_INTERVAL` for as long as the server runs.
+/// Each run covers the alarms that went off since the previous one, so reminders
+/// due while the server was down are not sent.
+pub fn spawn_reminders(service: CalendarService) {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(REMINDER_INTERVAL);
+        let mut since = service.now();
+        loop {
+            ticker.tick().await;
+            let until = service.now();
+            match service.send_due_reminders(since, until).await {
+                Ok(0) => {}
+                Ok(sent) => tracing::info!(sent, "Sent event reminders"),
+                Err(e) => tracing::error!("Sending event reminders failed: {:?}", e),
+            }
+            since = until;
+        }
+    });
+}
@@ -1,8 +1,38 @@
+use std::sync::Arc;
 use sqlx::sqlite::SqlitePool;
-use chrono::Utc;
+use sqlx::{FromRow, QueryBuilder, Row, Sqlite, Transaction};
+use chrono::{Datelike, Utc};
 use uuid::Uuid;
 use crate::models::*;
 use crate::error::AppError;
+use crate::flash::{Flash, FlashKind, FLASH_COOKIE_SECONDS};
+use crate::oidc::{ExternalIdentity, OidcClient};
+use crate::settings::{InstanceSettings, RegistrationPolicy, SettingsOverrides, UpdateUserSettings, UserSettings};
+use crate::onboarding::{Onboarding, STARTER_EVENT_DESCRIPTION, STARTER_EVENT_TITLE};
+use crate::storage::AttachmentStorage;
+use crate::geo;
+use crate::push;
+use crate::i18n::{translate, translate_with, Language};
+use crate::holidays;
+use crate::jscalendar;
+use crate::validation;
+use crate::maintenance::{self, PruneReport, RetentionPolicy};
+use crate::edit_conflicts::{ConflictSide, EditConflict};
+use crate::event_history::{self, EventVersion, VersionAction};
+use crate::recurrence;
+use crate::reminders;
+use crate::clock::{Clock, SystemClock};
+use crate::backup::{self, BackupConfig, BackupInfo};
+use crate::quota::{QuotaUsage, Quotas};
+use crate::import::ImportBatch;
+use crate::dav_usage::DavUsage;
+use crate::etag::IcsCache;
+use crate::sync_log::{self, NewSyncLogEntry, SyncLogEntry};
+use crate::devices::{Device, DeviceCredential};
+use crate::free_busy::FreeBusy;
+use crate::streaming::{self, Chunks};
+use crate::lockout::{LockoutPolicy, LoginSource};
+use crate::mail::{Mail, Mailer, SmtpMailer};
 use bcrypt::{hash, DEFAULT_COST};
 use jsonwebtoken::{encode, Header, EncodingKey};
 
@@ -14,21 +44,320 @@ fn escape_ical_text(text: &str) -> String {
         .replace('\n', "\\n")
 }
 
+/// Check the times of an event and bring those of an all-day event to whole days, as
+/// they are stored
+fn stored_times(start: chrono::DateTime<Utc>, end: chrono::DateTime<Utc>, is_all_day: bool) -> Result<(chrono::DateTime<Utc>, chrono::DateTime<Utc>), AppError> {
+    validation::event_times(start, end)?;
+    Ok(if is_all_day { all_day_span(start, end) } else { (start, end) })
+}
+
+/// Name and color of the calendar generated from a user's contacts
+const BIRTHDAYS_CALENDAR_NAME: &str = "Birthdays";
+const BIRTHDAYS_CALENDAR_COLOR: &str = "#EC4899";
+
+/// Color of holiday calendars
+const HOLIDAYS_CALENDAR_COLOR: &str = "#10B981";
+
+/// All-day event of a calendar the server generates
+struct GeneratedEvent {
+    id: Uuid,
+    title: String,
+    date: chrono::NaiveDate,
+    /// Whether the event recurs every year on the same date
+    yearly: bool,
+    created_at: chrono::DateTime<Utc>,
+    updated_at: chrono::DateTime<Utc>,
+}
+
+/// Columns of an event row, in the order `Event` is read from
+const EVENT_COLUMNS: &str = "id, calendar_id, title, description, location, geo_lat, geo_lon, start_time, end_time, is_all_day, rrule, exdates, parent_id, recurrence_id, alarm_minutes, sequence, uid, resource_name, created_at, updated_at";
+
+/// Event versions with the columns `EventVersion` is read from
+const EVENT_VERSION_SELECT: &str = "SELECT id AS version_id, action, recorded_at, event_id AS id, calendar_id, title, description, location, \
+     geo_lat, geo_lon, start_time, end_time, is_all_day, rrule, exdates, parent_id, recurrence_id, alarm_minutes, sequence, uid, resource_name, \
+     created_at, updated_at FROM event_versions";
+
+/// Delegations joined with the names and emails of both users
+const DELEGATION_SELECT: &str = "SELECT d.id, d.owner_id, owner.name AS owner_name, owner.email AS owner_email, \
+     d.delegate_id, delegate.name AS delegate_name, delegate.email AS delegate_email, d.permission_level, d.created_at \
+     FROM delegations d JOIN users owner ON owner.id = d.owner_id JOIN users delegate ON delegate.id = d.delegate_id";
+
+/// Lifetime of the signed state cookie that ties an OIDC callback to its request
+const OIDC_STATE_MINUTES: i64 = 10;
+
+/// Signed contents of the OIDC state cookie
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+pub struct OidcState {
+    pub state: String,
+    pub nonce: String,
+    /// Set when the flow links an identity to this (re-authenticated) user instead of signing in
+    pub link_user: Option<Uuid>,
+    exp: usize,
+}
+
+/// Signed contents of a feed token
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+struct FeedClaims {
+    /// Feed token ID
+    sub: String,
+    /// Calendar the feed shows
+    cal: String,
+}
+
+/// Signed contents of a user feed token
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+struct UserFeedClaims {
+    /// User feed token ID
+    sub: String,
+    /// User whose calendars the feed merges
+    usr: String,
+}
+
+/// Signed contents of the flash cookie
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+struct FlashClaims {
+    kind: FlashKind,
+    msg: String,
+    exp: usize,
+}
+
+/// Claims of a share link token
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+struct ShareLinkClaims {
+    /// Share link ID
+    sub: String,
+    /// Calendar the link grants access to
+    cal: String,
+}
+
+/// Longest lifetime that can be chosen for an expiring share link
+pub const MAX_SHARE_LINK_DAYS: u32 = 365;
+
+/// Lifetime of admin impersonation tokens
+const IMPERSONATION_TOKEN_MINUTES: i64 = 60;
+
+/// A user's rows deleted when an admin purges their data, as (table, column naming the
+/// user); their personal calendars go too
+const PURGED_USER_DATA: [(&str, &str); 14] = [
+    ("shares", "user_id"),
+    ("shares", "shared_with_user_id"),
+    ("share_links", "created_by"),
+    ("feed_tokens", "created_by"),
+    ("user_feed_tokens", "user_id"),
+    ("push_subscriptions", "user_id"),
+    ("calendar_subscriptions", "user_id"),
+    ("calendar_preferences", "user_id"),
+    ("notification_preferences", "user_id"),
+    ("delegations", "owner_id"),
+    ("delegations", "delegate_id"),
+    ("contacts", "user_id"),
+    ("devices", "user_id"),
+    ("sync_log", "user_id"),
+];
+
 #[derive(Clone)]
 pub struct CalendarService {
     pool: SqlitePool,
     jwt_secret: String,
+    http_client: reqwest::Client,
+    attachments: AttachmentStorage,
+    onboarding: Onboarding,
+    oidc: Option<OidcClient>,
+    base_url: String,
+    retention: RetentionPolicy,
+    settings_overrides: SettingsOverrides,
+    clock: Arc<dyn Clock>,
+    backups: BackupConfig,
+    quotas: Quotas,
+    expansions: recurrence::ExpansionCache,
+    ics_cache: IcsCache,
+    dav_usage: DavUsage,
+    mailer: Arc<dyn Mailer>,
+    lockout: LockoutPolicy,
 }
 
 impl CalendarService {
     pub fn new(pool: SqlitePool) -> Self {
-        CalendarService { 
+        CalendarService {
             pool,
             jwt_secret: std::env::var("JWT_SECRET")
                 .unwrap_or_else(|_| "your-secret-key-change-in-production".to_string()),
+            // Push targets are user-supplied, so don't wait on them long or follow redirects
+            http_client: reqwest::Client::builder()
+                .timeout(crate::push::PUSH_DELIVERY_TIMEOUT)
+                .redirect(reqwest::redirect::Policy::none())
+                .build()
+                .expect("failed to build HTTP client"),
+            attachments: AttachmentStorage::default(),
+            onboarding: Onboarding::default(),
+            oidc: None,
+            base_url: String::new(),
+            retention: RetentionPolicy::default(),
+            settings_overrides: SettingsOverrides::default(),
+            clock: Arc::new(SystemClock),
+            backups: BackupConfig::default(),
+            quotas: Quotas::default(),
+            expansions: recurrence::ExpansionCache::default(),
+            ics_cache: IcsCache::default(),
+            dav_usage: DavUsage::default(),
+            mailer: Arc::new(SmtpMailer),
+            lockout: LockoutPolicy::default(),
+        }
+    }
+
+    /// Sign tokens with the given secret instead of the one from `JWT_SECRET`
+    pub fn with_jwt_secret(mut self, jwt_secret: impl Into<String>) -> Self {
+        self.jwt_secret = jwt_secret.into();
+        self
+    }
+
+    /// Use the given storage for event attachment contents
+    pub fn with_attachment_storage(mut self, attachments: AttachmentStorage) -> Self {
+        self.attachments = attachments;
+        self
+    }
+
+    /// Use the given calendars and starter content for newly registered users
+    pub fn with_onboarding(mut self, onboarding: Onboarding) -> Self {
+        self.onboarding = onboarding;
+        self
+    }
+
+    /// Enable sign-in and account linking with an OIDC provider
+    pub fn with_oidc(mut self, oidc: Option<OidcClient>) -> Self {
+        self.oidc = oidc;
+        self
+    }
+
+    /// Keep prunable records for as long as `retention` says
+    pub fn with_retention(mut self, retention: RetentionPolicy) -> Self {
+        self.retention = retention;
+        self
+    }
+
+    /// Pin instance settings from the configuration over those stored by the setup wizard
+    pub fn with_settings_overrides(mut self, overrides: SettingsOverrides) -> Self {
+        self.settings_overrides = overrides;
+        self
+    }
+
+    /// Write backups where and as many as `backups` says
+    pub fn with_backups(mut self, backups: BackupConfig) -> Self {
+        self.backups = backups;
+        self
+    }
+
+    /// Enforce the given storage limits
+    pub fn with_quotas(mut self, quotas: Quotas) -> Self {
+        self.quotas = quotas;
+        self
+    }
+
+    pub fn quotas(&self) -> &Quotas {
+        &self.quotas
+    }
+
+    /// Count requests for unsupported DAV features in `dav_usage`
+    pub fn with_dav_usage(mut self, dav_usage: DavUsage) -> Self {
+        self.dav_usage = dav_usage;
+        self
+    }
+
+    pub fn dav_usage(&self) -> &DavUsage {
+        &self.dav_usage
+    }
+
+    /// Deliver notifications through `mailer` instead of the configured SMTP server
+    pub fn with_mailer(mut self, mailer: Arc<dyn Mailer>) -> Self {
+        self.mailer = mailer;
+        self
+    }
+
+    pub fn with_lockout(mut self, lockout: LockoutPolicy) -> Self {
+        self.lockout = lockout;
+        self
+    }
+
+    /// Settings mails to a user are written with: their language, else English, and
+    /// their time zone
+    async fn mail_settings(&self, user_id: Uuid) -> (Language, UserSettings) {
+        match self.get_user_settings(user_id).await {
+            Ok(settings) => (settings.language.unwrap_or_default(), settings),
+            Err(e) => {
+                tracing::warn!(error = %e, "Reading mail settings of the recipient failed");
+                (Language::default(), UserSettings::default())
+            }
+        }
+    }
+
+    /// Send a notification mail through the instance's mail server. Failures are
+    /// logged, not returned, so a broken mail setup never fails the request.
+    pub async fn send_mail(&self, to: &str, subject: &str, body: String) {
+        let smtp = match self.get_settings().await {
+            Ok(settings) => settings.smtp,
+            Err(e) => {
+                tracing::warn!(error = %e, "Reading mail settings failed");
+                None
+            }
+        };
+        let mail = Mail { to: to.to_string(), subject: subject.to_string(), body };
+        if let Err(e) = self.mailer.send(smtp.as_ref(), mail).await {
+            tracing::warn!(to, subject, error = %e, "Mail not sent");
+        }
+    }
+
+    /// Take the current time from `clock` instead of the system clock
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Current time according to the service's clock
+    pub fn now(&self) -> chrono::DateTime<Utc> {
+        self.clock.now()
+    }
+
+    pub fn clock(&self) -> Arc<dyn Clock> {
+        self.clock.clone()
+    }
+
+    pub fn oidc(&self) -> Option<&OidcClient> {
+        self.oidc.as_ref()
+    }
+
+    /// Public URL the server is reachable at (e.g. `https://example.com/caldav` behind a
+    /// reverse proxy). Empty keeps generated links relative to the server root.
+    pub fn with_base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.base_url = base_url.into().trim_end_matches('/').to_string();
+        self
+    }
+
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    /// Path prefix of the public URL, e.g. `/caldav` for `https://example.com/caldav`
+    pub fn base_path(&self) -> &str {
+        match self.base_url.split_once("://") {
+            Some((_, rest)) => rest.find('/').map(|i| &rest[i..]).unwrap_or(""),
+            None => &self.base_url,
         }
     }
 
+    /// Full URL of a server path, for Location headers and links shown to users
+    pub fn url(&self, path: &str) -> String {
+        format!("{}{}", self.base_url, path)
+    }
+
+    /// DAV href of a server path, including the public path prefix
+    pub fn href(&self, path: &str) -> String {
+        format!("{}{}", self.base_path(), path)
+    }
+
+    pub fn max_attachment_size(&self) -> usize {
+        self.attachments.max_size()
+    }
+
     pub fn get_jwt_secret(&self) -> String {
         self.jwt_secret.clone()
     }
@@ -36,9 +365,10 @@ impl CalendarService {
     pub fn generate_jwt(&self, user_id: Uuid, role: &UserRole) -> Result<String, AppError> {
         let claims = crate::middleware::Claims {
             sub: user_id.to_string(),
-            exp: (Utc::now() + chrono::Duration::hours(24)).timestamp() as usize,
-            iat: Utc::now().timestamp() as usize,
+            exp: (self.now() + chrono::Duration::hours(24)).timestamp() as usize,
+            iat: self.now().timestamp() as usize,
             role: Some(role.as_str().to_string()),
+            impersonator: None,
         };
         
         encode(
@@ -48,494 +378,4860 @@ impl CalendarService {
         ).map_err(|e| AppError::InternalServerError(format!("JWT encoding error: {}", e)))
     }
 
-    // User operations
-    pub async fn get_user_by_id(&self, id: Uuid) -> Result<Option<User>, AppError> {
-        let user = sqlx::query_as::<_, User>(
-            "SELECT id, name, email, password_hash, role, created_at, updated_at FROM users WHERE id = ?"
-        )
-        .bind(id.to_string())
-        .fetch_optional(&self.pool)
-        .await
-        .map_err(|e| {
-            tracing::error!("Database error in get_user_by_id: {:?}", e);
-            AppError::DatabaseError(e)
-        })?;
-
-        Ok(user)
+    /// Issue a short-lived token acting as `user` on behalf of an admin. The token carries
+    /// the admin's id so every request made with it can be audited.
+    pub fn generate_impersonation_jwt(&self, admin_id: Uuid, user: &User) -> Result<(String, chrono::DateTime<Utc>), AppError> {
+        let expires_at = self.now() + chrono::Duration::minutes(IMPERSONATION_TOKEN_MINUTES);
+        let claims = crate::middleware::Claims {
+            sub: user.id.to_string(),
+            exp: expires_at.timestamp() as usize,
+            iat: self.now().timestamp() as usize,
+            role: Some(user.role.as_str().to_string()),
+            impersonator: Some(admin_id.to_string()),
+        };
+        
+        let token = encode(
+            &Header::default(),
+            &claims,
+            &EncodingKey::from_secret(self.jwt_secret.as_bytes()),
+        ).map_err(|e| AppError::InternalServerError(format!("JWT encoding error: {}", e)))?;
+        
+        Ok((token, expires_at))
     }
 
-    pub async fn get_user_by_email(&self, email: &str) -> Result<Option<User>, AppError> {
-        tracing::info!("Fetching user by email: {}", email);
-        let user = sqlx::query_as::<_, User>(
-            "SELECT id, name, email, password_hash, role, created_at, updated_at FROM users WHERE email = ?"
-        )
-        .bind(email)
-        .fetch_optional(&self.pool)
-        .await
-        .map_err(|e| {
-            tracing::error!("Database error in get_user_by_email: {:?}", e);
-            AppError::DatabaseError(e)
-        })?;
+    /// Start impersonating a user as an admin, returning the impersonation token, its
+    /// expiry and the impersonated user. Admin accounts can't be impersonated.
+    pub async fn start_impersonation(&self, admin_id: Uuid, user_id: Uuid) -> Result<(String, chrono::DateTime<Utc>, User), AppError> {
+        if admin_id == user_id {
+            return Err(AppError::ValidationError("Cannot impersonate yourself".to_string()));
+        }
 
-        Ok(user)
-    }
+        let user = self.get_user_by_id(user_id).await?
+            .ok_or(AppError::NotFoundError("User not found".to_string()))?;
 
-    pub async fn create_user(&self, new_user: NewUser) -> Result<User, AppError> {
-        let password_hash = hash(&new_user.password, DEFAULT_COST)?;
-        let now = Utc::now();
-        let id = Uuid::new_v4();
-        let role = UserRole::default().as_str();
-        
-        sqlx::query(
-            "INSERT INTO users (id, name, email, password_hash, role, created_at, updated_at) VALUES (?, ?, ?, ?, ?, ?, ?)"
-        )
-        .bind(id.to_string())
-        .bind(&new_user.name)
-        .bind(&new_user.email)
-        .bind(&password_hash)
-        .bind(role)
-        .bind(now)
-        .bind(now)
-        .execute(&self.pool)
-        .await?;
+        if user.role.is_admin() {
+            return Err(AppError::ValidationError("Admin accounts cannot be impersonated".to_string()));
+        }
 
-        // Fetch the user back
-        let user = self.get_user_by_id(id).await?
-            .ok_or_else(|| AppError::InternalServerError("Failed to fetch created user".to_string()))?;
+        let (token, expires_at) = self.generate_impersonation_jwt(admin_id, &user)?;
+        self.record_audit(admin_id, Some(user.id), "impersonation_started", Some(&user.email)).await?;
 
-        Ok(user)
+        Ok((token, expires_at, user))
     }
-    
-    /// Create a user with a specific role (admin only)
-    pub async fn create_user_with_role(&self, new_user: NewUser, role: UserRole) -> Result<User, AppError> {
-        let password_hash = hash(&new_user.password, DEFAULT_COST)?;
-        let now = Utc::now();
-        let id = Uuid::new_v4();
-        
+
+    // Audit log operations
+
+    pub async fn record_audit(&self, actor_id: Uuid, impersonated_user_id: Option<Uuid>, action: &str, details: Option<&str>) -> Result<(), AppError> {
         sqlx::query(
-            "INSERT INTO users (id, name, email, password_hash, role, created_at, updated_at) VALUES (?, ?, ?, ?, ?, ?, ?)"
+            "INSERT INTO audit_log (id, actor_id, impersonated_user_id, action, details, created_at) VALUES (?, ?, ?, ?, ?, ?)"
         )
-        .bind(id.to_string())
-        .bind(&new_user.name)
-        .bind(&new_user.email)
-        .bind(&password_hash)
-        .bind(role.as_str())
-        .bind(now)
-        .bind(now)
+        .bind(Uuid::new_v4().to_string())
+        .bind(actor_id.to_string())
+        .bind(impersonated_user_id.map(|id| id.to_string()))
+        .bind(action)
+        .bind(details)
+        .bind(self.now())
         .execute(&self.pool)
         .await?;
 
-        // Fetch the user back
-        let user = self.get_user_by_id(id).await?
-            .ok_or_else(|| AppError::InternalServerError("Failed to fetch created user".to_string()))?;
-
-        Ok(user)
+        Ok(())
     }
-    
-    /// Get all users (admin only)
-    pub async fn get_all_users(&self) -> Result<Vec<User>, AppError> {
-        let users = sqlx::query_as::<_, User>(
-            "SELECT id, name, email, password_hash, role, created_at, updated_at FROM users"
+
+    /// Most recent audit log entries, newest first
+    pub async fn get_audit_log(&self, limit: i64) -> Result<Vec<AuditLogEntry>, AppError> {
+        let entries = sqlx::query_as::<_, AuditLogEntry>(
+            "SELECT id, actor_id, impersonated_user_id, action, details, created_at FROM audit_log ORDER BY created_at DESC LIMIT ?"
         )
+        .bind(limit)
         .fetch_all(&self.pool)
-        .await
-        .map_err(|e| {
-            tracing::error!("Database error in get_all_users: {:?}", e);
-            AppError::DatabaseError(e)
-        })?;
+        .await?;
 
-        Ok(users)
+        Ok(entries)
     }
-    
-    /// Update user role (admin only)
-    pub async fn update_user_role(&self, id: Uuid, role: UserRole) -> Result<User, AppError> {
-        let now = Utc::now();
-        
-        sqlx::query("UPDATE users SET role = ?, updated_at = ? WHERE id = ?")
-            .bind(role.as_str())
-            .bind(now)
-            .bind(id.to_string())
+
+    /// Remove audit log entries recorded before `cutoff`
+    pub async fn delete_audit_log_before(&self, cutoff: chrono::DateTime<Utc>) -> Result<u64, AppError> {
+        let result = sqlx::query("DELETE FROM audit_log WHERE created_at < ?")
+            .bind(cutoff)
             .execute(&self.pool)
             .await?;
-        
-        self.get_user_by_id(id).await?.ok_or(AppError::NotFoundError("User not found".to_string()))
+
+        Ok(result.rows_affected())
     }
 
-    pub async fn update_user(&self, id: Uuid, email: Option<String>, password: Option<String>) -> Result<User, AppError> {
-        let now = Utc::now();
-        
-        if let Some(new_email) = email {
-            sqlx::query("UPDATE users SET email = ?, updated_at = ? WHERE id = ?")
-                .bind(new_email)
-                .bind(now)
-                .bind(id.to_string())
-                .execute(&self.pool)
-                .await?;
+    /// Remove sign-in attempts recorded before `cutoff`
+    pub async fn delete_login_attempts_before(&self, cutoff: chrono::DateTime<Utc>) -> Result<u64, AppError> {
+        let result = sqlx::query("DELETE FROM login_attempts WHERE created_at < ?")
+            .bind(cutoff)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    /// Start an OIDC flow, returning the signed state cookie value and the state and
+    /// nonce to send to the provider
+    pub fn create_oidc_state(&self, link_user: Option<Uuid>) -> Result<(String, OidcState), AppError> {
+        let state = OidcState {
+            state: Uuid::new_v4().to_string(),
+            nonce: Uuid::new_v4().to_string(),
+            link_user,
+            exp: (self.now() + chrono::Duration::minutes(OIDC_STATE_MINUTES)).timestamp() as usize,
+        };
+        let token = encode(
+            &Header::default(),
+            &state,
+            &EncodingKey::from_secret(self.jwt_secret.as_bytes()),
+        )?;
+        Ok((token, state))
+    }
+
+    /// Token validation with expiry left out, so it can be checked against the service's clock
+    fn validation_without_exp() -> jsonwebtoken::Validation {
+        let mut validation = jsonwebtoken::Validation::default();
+        validation.validate_exp = false;
+        validation.required_spec_claims.clear();
+        validation
+    }
+
+    /// Verify a state cookie and check it belongs to the callback's `state` parameter
+    pub fn verify_oidc_state(&self, token: &str, state: &str) -> Result<OidcState, AppError> {
+        let decoded = jsonwebtoken::decode::<OidcState>(
+            token,
+            &jsonwebtoken::DecodingKey::from_secret(self.jwt_secret.as_bytes()),
+            &Self::validation_without_exp(),
+        )?;
+        if decoded.claims.exp <= self.now().timestamp() as usize {
+            return Err(AppError::AuthenticationError("OIDC state expired".to_string()));
         }
-        
-        if let Some(new_password) = password {
-            let password_hash = hash(new_password, DEFAULT_COST)?;
-            sqlx::query("UPDATE users SET password_hash = ?, updated_at = ? WHERE id = ?")
-                .bind(password_hash)
-                .bind(now)
-                .bind(id.to_string())
-                .execute(&self.pool)
-                .await?;
+        if decoded.claims.state != state {
+            return Err(AppError::AuthenticationError("OIDC state mismatch".to_string()));
         }
-        
-        self.get_user_by_id(id).await?.ok_or(AppError::NotFoundError("User not found".to_string()))
+        Ok(decoded.claims)
     }
 
-    pub async fn delete_user(&self, id: Uuid) -> Result<(), AppError> {
-        sqlx::query("DELETE FROM users WHERE id = ?")
-            .bind(id.to_string())
-            .execute(&self.pool)
-            .await?;
-        Ok(())
+    /// Signed value of the flash cookie carrying `flash` to the next page
+    pub fn sign_flash(&self, flash: &Flash) -> Result<String, AppError> {
+        let claims = FlashClaims {
+            kind: flash.kind,
+            msg: flash.message.clone(),
+            exp: (self.now() + chrono::Duration::seconds(FLASH_COOKIE_SECONDS.into())).timestamp() as usize,
+        };
+        Ok(encode(&Header::default(), &claims, &EncodingKey::from_secret(self.jwt_secret.as_bytes()))?)
     }
 
-    // Calendar operations
-    pub async fn get_calendars_by_user_id(&self, user_id: Uuid) -> Result<Vec<Calendar>, AppError> {
-        let calendars = sqlx::query_as::<_, Calendar>(
-            "SELECT id, user_id, name, description, color, is_public, created_at, updated_at FROM calendars WHERE user_id = ?"
+    /// The flash in a flash cookie, unless the cookie was not signed by us or has expired
+    pub fn verify_flash(&self, token: &str) -> Option<Flash> {
+        let claims = jsonwebtoken::decode::<FlashClaims>(
+            token,
+            &jsonwebtoken::DecodingKey::from_secret(self.jwt_secret.as_bytes()),
+            &Self::validation_without_exp(),
+        )
+        .ok()?
+        .claims;
+        (claims.exp > self.now().timestamp() as usize).then_some(Flash { kind: claims.kind, message: claims.msg })
+    }
+
+    /// Signed value of a feed token, as it appears in the feed URL. It only identifies
+    /// the token; whether it's still valid is looked up on every fetch.
+    fn feed_token_value(&self, token: &FeedToken) -> Result<String, AppError> {
+        let claims = FeedClaims {
+            sub: token.id.to_string(),
+            cal: token.calendar_id.to_string(),
+        };
+        Ok(encode(&Header::default(), &claims, &EncodingKey::from_secret(self.jwt_secret.as_bytes()))?)
+    }
+
+    /// Signed value of a user feed token, as it appears in the feed URL
+    fn user_feed_token_value(&self, token: &UserFeedToken) -> Result<String, AppError> {
+        let claims = UserFeedClaims {
+            sub: token.id.to_string(),
+            usr: token.user_id.to_string(),
+        };
+        Ok(encode(&Header::default(), &claims, &EncodingKey::from_secret(self.jwt_secret.as_bytes()))?)
+    }
+
+    // External identity operations
+    pub async fn get_user_identities(&self, user_id: Uuid) -> Result<Vec<UserIdentity>, AppError> {
+        let identities = sqlx::query_as::<_, UserIdentity>(
+            "SELECT id, user_id, issuer, subject, email, created_at FROM user_identities WHERE user_id = ? ORDER BY created_at"
         )
         .bind(user_id.to_string())
         .fetch_all(&self.pool)
         .await?;
 
-        Ok(calendars)
+        Ok(identities)
     }
 
-    pub async fn get_calendar_by_id(&self, id: Uuid) -> Result<Option<Calendar>, AppError> {
-        let calendar = sqlx::query_as::<_, Calendar>(
-            "SELECT id, user_id, name, description, color, is_public, created_at, updated_at FROM calendars WHERE id = ?"
+    /// Find the local account an external identity is linked to
+    pub async fn get_user_by_identity(&self, issuer: &str, subject: &str) -> Result<Option<User>, AppError> {
+        let user_id: Option<(String,)> = sqlx::query_as(
+            "SELECT user_id FROM user_identities WHERE issuer = ? AND subject = ?"
         )
-        .bind(id.to_string())
+        .bind(issuer)
+        .bind(subject)
         .fetch_optional(&self.pool)
         .await?;
 
-        Ok(calendar)
+        match user_id {
+            Some((user_id,)) => self.get_user_by_id(Uuid::parse_str(&user_id)?).await,
+            None => Ok(None),
+        }
     }
-    
-    /// Get all public calendars
-    pub async fn get_public_calendars(&self) -> Result<Vec<Calendar>, AppError> {
-        let calendars = sqlx::query_as::<_, Calendar>(
-            "SELECT id, user_id, name, description, color, is_public, created_at, updated_at FROM calendars WHERE is_public = 1"
+
+    /// Link an external identity to a user. An identity can belong to one account only.
+    pub async fn link_identity(&self, user_id: Uuid, identity: &ExternalIdentity) -> Result<UserIdentity, AppError> {
+        if let Some(owner) = self.get_user_by_identity(&identity.issuer, &identity.subject).await? {
+            let message = if owner.id == user_id {
+                "This identity is already linked to your account"
+            } else {
+                "This identity is already linked to another account"
+            };
+            return Err(AppError::ValidationError(message.to_string()));
+        }
+
+        let id = Uuid::new_v4();
+        sqlx::query(
+            "INSERT INTO user_identities (id, user_id, issuer, subject, email, created_at) VALUES (?, ?, ?, ?, ?, ?)"
         )
-        .fetch_all(&self.pool)
+        .bind(id.to_string())
+        .bind(user_id.to_string())
+        .bind(&identity.issuer)
+        .bind(&identity.subject)
+        .bind(&identity.email)
+        .bind(self.now())
+        .execute(&self.pool)
         .await?;
 
-        Ok(calendars)
+        let identity = sqlx::query_as::<_, UserIdentity>(
+            "SELECT id, user_id, issuer, subject, email, created_at FROM user_identities WHERE id = ?"
+        )
+        .bind(id.to_string())
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(identity)
     }
-    
-    /// Export calendar as ICS format
-    pub async fn export_calendar_ics(&self, calendar_id: Uuid) -> Result<String, AppError> {
-        let calendar = self.get_calendar_by_id(calendar_id).await?
+
+    /// Unlink one of the user's external identities
+    pub async fn unlink_identity(&self, user_id: Uuid, identity_id: Uuid) -> Result<(), AppError> {
+        let result = sqlx::query("DELETE FROM user_identities WHERE id = ? AND user_id = ?")
+            .bind(identity_id.to_string())
+            .bind(user_id.to_string())
+            .execute(&self.pool)
+            .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(AppError::NotFoundError("Identity not found".to_string()));
+        }
+
+        Ok(())
+    }
+
+    /// Check a user's current password before sensitive account changes
+    pub async fn verify_password(&self, user: &User, password: &str) -> Result<bool, AppError> {
+        Ok(bcrypt::verify(password, &user.password_hash)?)
+    }
+
+    /// Check a sign-in and record the attempt, locking the account after too many
+    /// consecutive failures. Locked accounts are refused even with the right password.
+    pub async fn authenticate(&self, email: &str, password: &str, source: LoginSource) -> Result<User, AppError> {
+        let user = self.get_user_by_email(email).await?
+            .ok_or_else(|| AppError::AuthenticationError("Invalid credentials".to_string()))?;
+
+        let row = sqlx::query("SELECT failed_login_count, locked_until FROM users WHERE id = ?")
+            .bind(user.id.to_string())
+            .fetch_one(&self.pool)
+            .await?;
+        let failed_logins: i64 = row.try_get("failed_login_count")?;
+        let locked_until: Option<chrono::DateTime<Utc>> = row.try_get("locked_until")?;
+        if locked_until.is_some_and(|until| until > self.now()) {
+            return Err(AppError::AuthenticationError("Account is locked after too many failed logins; try again later".to_string()));
+        }
+
+        if !bcrypt::verify(password, &user.password_hash)? {
+            self.record_failed_login(&user, source).await?;
+            return Err(AppError::AuthenticationError("Invalid credentials".to_string()));
+        }
+
+        // CalDAV clients authenticate every request, which would drown the history
+        if source != LoginSource::Dav {
+            self.record_login_attempt(user.id, true, source).await?;
+        }
+        if failed_logins > 0 || locked_until.is_some() {
+            sqlx::query("UPDATE users SET failed_login_count = 0, locked_until = NULL WHERE id = ?")
+                .bind(user.id.to_string())
+                .execute(&self.pool)
+                .await?;
+        }
+
+        Ok(user)
+    }
+
+    async fn record_login_attempt(&self, user_id: Uuid, succeeded: bool, source: LoginSource) -> Result<(), AppError> {
+        sqlx::query("INSERT INTO login_attempts (id, user_id, succeeded, source, created_at) VALUES (?, ?, ?, ?, ?)")
+            .bind(Uuid::new_v4().to_string())
+            .bind(user_id.to_string())
+            .bind(succeeded)
+            .bind(source.as_str())
+            .bind(self.now())
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Count a failed login, locking the account and telling its owner once the limit is reached
+    async fn record_failed_login(&self, user: &User, source: LoginSource) -> Result<(), AppError> {
+        self.record_login_attempt(user.id, false, source).await?;
+
+        // Counted in place, so concurrent failures can't overwrite each other's count
+        let failed_logins: i64 = sqlx::query_scalar(
+            "UPDATE users SET failed_login_count = failed_login_count + 1 WHERE id = ? RETURNING failed_login_count"
+        )
+        .bind(user.id.to_string())
+        .fetch_one(&self.pool)
+        .await?;
+
+        let limit = i64::from(self.lockout.max_failed_logins);
+        if limit == 0 || failed_logins < limit {
+            return Ok(());
+        }
+
+        let locked_until = self.now() + chrono::Duration::minutes(self.lockout.lockout_minutes.into());
+        sqlx::query("UPDATE users SET failed_login_count = 0, locked_until = ? WHERE id = ?")
+            .bind(locked_until)
+            .bind(user.id.to_string())
+            .execute(&self.pool)
+            .await?;
+        self.record_audit(user.id, None, "account_locked", Some(&format!("{} failed logins via {}", failed_logins, source.as_str()))).await?;
+        tracing::warn!(user_id = %user.id, failed_logins, "Account locked");
+
+        let instance_name = self.get_settings().await.map(|s| s.instance_name).unwrap_or_default();
+        let (language, settings) = self.mail_settings(user.id).await;
+        self.send_mail(
+            &user.email,
+            &translate(language, "mail.lockout.subject"),
+            translate_with(language, "mail.lockout.body", &[
+                ("name", &user.name),
+                ("instance", &instance_name),
+                ("attempts", &failed_logins.to_string()),
+                ("until", &settings.format_timestamp(locked_until)),
+            ]),
+        ).await;
+
+        Ok(())
+    }
+
+    /// Last successful and failed sign-ins of a user, and whether the account is locked
+    pub async fn get_login_activity(&self, user_id: Uuid) -> Result<LoginActivity, AppError> {
+        let last_attempt = |succeeded: bool| {
+            sqlx::query_scalar::<_, chrono::DateTime<Utc>>(
+                "SELECT created_at FROM login_attempts WHERE user_id = ? AND succeeded = ? ORDER BY created_at DESC LIMIT 1"
+            )
+            .bind(user_id.to_string())
+            .bind(succeeded)
+            .fetch_optional(&self.pool)
+        };
+        let last_login_at = last_attempt(true).await?;
+        let last_failed_login_at = last_attempt(false).await?;
+
+        let locked_until: Option<chrono::DateTime<Utc>> = sqlx::query_scalar("SELECT locked_until FROM users WHERE id = ?")
+            .bind(user_id.to_string())
+            .fetch_one(&self.pool)
+            .await?;
+
+        Ok(LoginActivity {
+            last_login_at,
+            last_failed_login_at,
+            locked_until: locked_until.filter(|until| *until > self.now()),
+        })
+    }
+
+    // Reminders
+
+    /// How the user is notified of reminders in each calendar they own or that is
+    /// shared with them; calendars without a stored preference use no notifications
+    pub async fn get_notification_preferences(&self, user_id: Uuid) -> Result<Vec<NotificationPreference>, AppError> {
+        let mut calendars = self.get_calendars_by_user_id(user_id).await?;
+        calendars.extend(self.get_calendars_shared_with_user(user_id).await?.into_iter().map(|(calendar, _)| calendar));
+
+        let rows = sqlx::query("SELECT calendar_id, channel FROM notification_preferences WHERE user_id = ?")
+            .bind(user_id.to_string())
+            .fetch_all(&self.pool)
+            .await?;
+        let mut channels = std::collections::HashMap::new();
+        for row in &rows {
+            channels.insert(row.try_get::<String, _>("calendar_id")?, NotificationChannel::from_str(&row.try_get::<String, _>("channel")?));
+        }
+
+        Ok(calendars.into_iter()
+            .map(|calendar| NotificationPreference {
+                channel: channels.get(&calendar.id.to_string()).copied().unwrap_or_default(),
+                calendar_id: calendar.id,
+                calendar_name: calendar.name,
+            })
+            .collect())
+    }
+
+    /// Store how the user is notified of reminders in a calendar. Access must be
+    /// checked by the caller.
+    pub async fn set_notification_preference(&self, user_id: Uuid, calendar_id: Uuid, channel: NotificationChannel) -> Result<(), AppError> {
+        sqlx::query(
+            "INSERT INTO notification_preferences (user_id, calendar_id, channel, updated_at) VALUES (?, ?, ?, ?) \
+             ON CONFLICT (user_id, calendar_id) DO UPDATE SET channel = excluded.channel, updated_at = excluded.updated_at"
+        )
+        .bind(user_id.to_string())
+        .bind(calendar_id.to_string())
+        .bind(channel.as_str())
+        .bind(self.now())
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    // User settings
+
+    pub async fn get_user_settings(&self, user_id: Uuid) -> Result<UserSettings, AppError> {
+        let pairs: Vec<(String, String)> = sqlx::query_as("SELECT key, value FROM user_settings WHERE user_id = ?")
+            .bind(user_id.to_string())
+            .fetch_all(&self.pool)
+            .await?;
+        Ok(UserSettings::from_pairs(pairs))
+    }
+
+    /// Change the user's settings; fields left out are kept
+    pub async fn update_user_settings(&self, user_id: Uuid, update: UpdateUserSettings) -> Result<UserSettings, AppError> {
+        if let Some(hours) = &update.working_hours
+            && hours.start >= hours.end
+        {
+            return Err(AppError::invalid_field("working_hours", "Working hours must end after they start"));
+        }
+
+        let clear_sync_log = update.sync_log == Some(false);
+        let mut settings = self.get_user_settings(user_id).await?;
+        settings.apply(update);
+
+        let now = self.now();
+        let mut tx = self.pool.begin().await?;
+        for (key, value) in settings.to_pairs() {
+            sqlx::query(
+                "INSERT INTO user_settings (user_id, key, value, updated_at) VALUES (?, ?, ?, ?) \
+                 ON CONFLICT (user_id, key) DO UPDATE SET value = excluded.value, updated_at = excluded.updated_at"
+            )
+            .bind(user_id.to_string())
+            .bind(key)
+            .bind(value)
+            .bind(now)
+            .execute(&mut *tx)
+            .await?;
+        }
+        tx.commit().await?;
+
+        if clear_sync_log {
+            self.clear_sync_log(user_id).await?;
+        }
+        Ok(settings)
+    }
+
+    // Sync log
+
+    /// Log a CalDAV request of the user, deleting their oldest entries beyond
+    /// [`sync_log::MAX_ENTRIES_PER_USER`]
+    pub async fn record_sync_request(&self, user_id: Uuid, entry: NewSyncLogEntry) -> Result<(), AppError> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query(
+            "INSERT INTO sync_log (id, user_id, method, path, status, user_agent, duration_ms, error, created_at) \
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)"
+        )
+        .bind(Uuid::new_v4().to_string())
+        .bind(user_id.to_string())
+        .bind(&entry.method)
+        .bind(&entry.path)
+        .bind(entry.status as i64)
+        .bind(&entry.user_agent)
+        .bind(entry.duration_ms)
+        .bind(&entry.error)
+        .bind(self.now())
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query(
+            "DELETE FROM sync_log WHERE user_id = ? AND rowid NOT IN \
+             (SELECT rowid FROM sync_log WHERE user_id = ? ORDER BY created_at DESC, rowid DESC LIMIT ?)"
+        )
+        .bind(user_id.to_string())
+        .bind(user_id.to_string())
+        .bind(sync_log::MAX_ENTRIES_PER_USER as i64)
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    /// The user's logged CalDAV requests, newest first
+    pub async fn get_sync_log(&self, user_id: Uuid) -> Result<Vec<SyncLogEntry>, AppError> {
+        let entries = sqlx::query_as::<_, SyncLogEntry>(
+            "SELECT id, method, path, status, user_agent, duration_ms, error, created_at FROM sync_log \
+             WHERE user_id = ? ORDER BY created_at DESC, rowid DESC"
+        )
+        .bind(user_id.to_string())
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(entries)
+    }
+
+    pub async fn clear_sync_log(&self, user_id: Uuid) -> Result<(), AppError> {
+        sqlx::query("DELETE FROM sync_log WHERE user_id = ?")
+            .bind(user_id.to_string())
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    // Devices
+
+    /// Register a CalDAV request of a device, returning `false` without recording it
+    /// if the user revoked the device
+    pub async fn record_device_request(
+        &self,
+        user_id: Uuid,
+        user_agent: &str,
+        credential: DeviceCredential,
+        ip: Option<&str>,
+    ) -> Result<bool, AppError> {
+        let revoked: Option<Option<chrono::DateTime<Utc>>> = sqlx::query_scalar(
+            "SELECT revoked_at FROM devices WHERE user_id = ? AND user_agent = ? AND credential = ?"
+        )
+        .bind(user_id.to_string())
+        .bind(user_agent)
+        .bind(credential.as_str())
+        .fetch_optional(&self.pool)
+        .await?;
+        if revoked.flatten().is_some() {
+            return Ok(false);
+        }
+
+        let now = self.now();
+        sqlx::query(
+            "INSERT INTO devices (id, user_id, user_agent, credential, ip, first_seen_at, last_seen_at) VALUES (?, ?, ?, ?, ?, ?, ?) \
+             ON CONFLICT (user_id, user_agent, credential) DO UPDATE SET \
+             ip = excluded.ip, last_seen_at = excluded.last_seen_at, request_count = request_count + 1"
+        )
+        .bind(Uuid::new_v4().to_string())
+        .bind(user_id.to_string())
+        .bind(user_agent)
+        .bind(credential.as_str())
+        .bind(ip)
+        .bind(now)
+        .bind(now)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(true)
+    }
+
+    /// The user's devices, most recently synced first
+    pub async fn get_devices(&self, user_id: Uuid) -> Result<Vec<Device>, AppError> {
+        let devices = sqlx::query_as::<_, Device>(
+            "SELECT id, user_agent, credential, ip, first_seen_at, last_seen_at, request_count, revoked_at FROM devices \
+             WHERE user_id = ? ORDER BY last_seen_at DESC"
+        )
+        .bind(user_id.to_string())
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(devices)
+    }
+
+    pub async fn get_device(&self, user_id: Uuid, id: Uuid) -> Result<Device, AppError> {
+        sqlx::query_as::<_, Device>(
+            "SELECT id, user_agent, credential, ip, first_seen_at, last_seen_at, request_count, revoked_at FROM devices \
+             WHERE id = ? AND user_id = ?"
+        )
+        .bind(id.to_string())
+        .bind(user_id.to_string())
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or_else(|| AppError::NotFoundError("Device not found".to_string()))
+    }
+
+    /// Refuse the device's CalDAV requests from now on
+    pub async fn revoke_device(&self, user_id: Uuid, id: Uuid) -> Result<(), AppError> {
+        sqlx::query("UPDATE devices SET revoked_at = ? WHERE id = ? AND user_id = ? AND revoked_at IS NULL")
+            .bind(self.now())
+            .bind(id.to_string())
+            .bind(user_id.to_string())
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Forget a device; a revoked device may sync again and is then registered anew
+    pub async fn remove_device(&self, user_id: Uuid, id: Uuid) -> Result<(), AppError> {
+        let result = sqlx::query("DELETE FROM devices WHERE id = ? AND user_id = ?")
+            .bind(id.to_string())
+            .bind(user_id.to_string())
+            .execute(&self.pool)
+            .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(AppError::NotFoundError("Device not found".to_string()));
+        }
+
+        Ok(())
+    }
+
+    // Calendar preferences
+
+    /// The order, visibility and color overrides the user stored for their calendars
+    pub async fn get_calendar_layout(&self, user_id: Uuid) -> Result<CalendarLayout, AppError> {
+        let rows = sqlx::query("SELECT calendar_id, sort_order, hidden, color FROM calendar_preferences WHERE user_id = ?")
+            .bind(user_id.to_string())
+            .fetch_all(&self.pool)
+            .await?;
+
+        let mut layout = CalendarLayout::default();
+        for row in &rows {
+            layout.overrides.insert(Uuid::parse_str(&row.try_get::<String, _>("calendar_id")?)?, CalendarOverride {
+                sort_order: row.try_get("sort_order")?,
+                hidden: row.try_get("hidden")?,
+                color: row.try_get("color")?,
+            });
+        }
+        Ok(layout)
+    }
+
+    /// How the user arranges each calendar they own or that is shared with them, in
+    /// the user's order
+    pub async fn get_calendar_preferences(&self, user_id: Uuid) -> Result<Vec<CalendarPreference>, AppError> {
+        let layout = self.get_calendar_layout(user_id).await?;
+        let mut calendars = self.get_calendars_by_user_id(user_id).await?;
+        calendars.extend(self.get_calendars_shared_with_user(user_id).await?.into_iter().map(|(calendar, _)| calendar));
+
+        Ok(layout.arrange(calendars).into_iter()
+            .map(|calendar| {
+                let preference = layout.overrides.get(&calendar.id);
+                CalendarPreference {
+                    hidden: preference.is_some_and(|p| p.hidden),
+                    color: preference.and_then(|p| p.color.clone()),
+                    sort_order: calendar.sort_order,
+                    calendar_id: calendar.id,
+                    calendar_name: calendar.name,
+                }
+            })
+            .collect())
+    }
+
+    /// Change how the user arranges a calendar; fields left out are kept. Access must
+    /// be checked by the caller.
+    pub async fn update_calendar_preference(&self, user_id: Uuid, calendar_id: Uuid, update: UpdateCalendarPreference) -> Result<(), AppError> {
+        if let Some(Some(color)) = &update.color
+            && !validation::is_hex_color(color)
+        {
+            return Err(AppError::invalid_field("color", "Color must look like #RRGGBB"));
+        }
+
+        let current = self.get_calendar_layout(user_id).await?.overrides.remove(&calendar_id).unwrap_or_default();
+        sqlx::query(
+            "INSERT INTO calendar_preferences (user_id, calendar_id, sort_order, hidden, color, updated_at) VALUES (?, ?, ?, ?, ?, ?) \
+             ON CONFLICT (user_id, calendar_id) DO UPDATE SET sort_order = excluded.sort_order, hidden = excluded.hidden, \
+             color = excluded.color, updated_at = excluded.updated_at"
+        )
+        .bind(user_id.to_string())
+        .bind(calendar_id.to_string())
+        .bind(update.sort_order.or(current.sort_order))
+        .bind(update.hidden.unwrap_or(current.hidden))
+        .bind(update.color.unwrap_or(current.color))
+        .bind(self.now())
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Put the user's calendars in the given order. Calendars left out follow those
+    /// listed, in their current order.
+    pub async fn reorder_calendars(&self, user_id: Uuid, calendar_ids: &[Uuid]) -> Result<(), AppError> {
+        let current: Vec<Uuid> = self.get_calendar_preferences(user_id).await?
+            .into_iter()
+            .map(|preference| preference.calendar_id)
+            .collect();
+        if calendar_ids.iter().any(|id| !current.contains(id)) {
+            return Err(AppError::NotFoundError("Calendar not found".to_string()));
+        }
+
+        let rest = current.iter().copied().filter(|id| !calendar_ids.contains(id));
+        let now = self.now();
+        let mut tx = self.pool.begin().await?;
+        for (position, calendar_id) in calendar_ids.iter().copied().chain(rest).enumerate() {
+            sqlx::query(
+                "INSERT INTO calendar_preferences (user_id, calendar_id, sort_order, updated_at) VALUES (?, ?, ?, ?) \
+                 ON CONFLICT (user_id, calendar_id) DO UPDATE SET sort_order = excluded.sort_order, updated_at = excluded.updated_at"
+            )
+            .bind(user_id.to_string())
+            .bind(calendar_id.to_string())
+            .bind(position as i64)
+            .bind(now)
+            .execute(&mut *tx)
+            .await?;
+        }
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    /// Mail the reminders that go off in `[since, until)` to users who chose email
+    /// notifications for the event's calendar, returning how many were sent.
+    /// Calendars the user can no longer see are skipped.
+    pub async fn send_due_reminders(&self, since: chrono::DateTime<Utc>, until: chrono::DateTime<Utc>) -> Result<usize, AppError> {
+        let rows = sqlx::query(
+            "SELECT np.user_id, u.email, np.calendar_id FROM notification_preferences np JOIN users u ON u.id = np.user_id \
+             WHERE np.channel = ? ORDER BY u.email"
+        )
+        .bind(NotificationChannel::Email.as_str())
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut sent = 0;
+        for row in &rows {
+            let user_id = Uuid::parse_str(&row.try_get::<String, _>("user_id")?)
+                .map_err(|e| AppError::InternalServerError(e.to_string()))?;
+            let email: String = row.try_get("email")?;
+            let calendar_id: String = row.try_get("calendar_id")?;
+
+            let visible = self.get_notification_preferences(user_id).await?;
+            let Some(preference) = visible.iter().find(|p| p.calendar_id.to_string() == calendar_id) else {
+                continue;
+            };
+            let (language, settings) = self.mail_settings(user_id).await;
+
+            let events = self.get_events_by_calendar_id(preference.calendar_id).await?;
+            for event in &events {
+                let Some(minutes) = event.alarm_minutes else {
+                    continue;
+                };
+                let overridden: Vec<_> = events
+                    .iter()
+                    .filter(|e| e.parent_id == Some(event.id))
+                    .filter_map(|e| e.recurrence_id)
+                    .collect();
+
+                // Instances whose alarm time falls in the window
+                let lead = chrono::Duration::minutes(minutes);
+                let (window_start, window_end) = (since + lead, until + lead);
+                for (start, _) in recurrence::instances(event, window_start, window_end, &overridden) {
+                    if start < window_start {
+                        continue;
+                    }
+                    let body = translate_with(language, "mail.reminder.body", &[
+                        ("title", &event.title),
+                        ("calendar", &preference.calendar_name),
+                        ("start", &settings.format_timestamp(start)),
+                    ]);
+                    let subject = translate_with(language, "mail.reminder.subject", &[("title", &event.title)]);
+                    self.send_mail(&email, &subject, body).await;
+                    sent += 1;
+                }
+            }
+        }
+
+        Ok(sent)
+    }
+
+    // Maintenance
+
+    /// Prune records that are past their expiry or retention period as of `now`
+    pub async fn prune_expired_data(&self, now: chrono::DateTime<Utc>) -> Result<PruneReport, AppError> {
+        let mut report = PruneReport {
+            expired_push_subscriptions: self.delete_expired_push_subscriptions(now).await?,
+            ..PruneReport::default()
+        };
+
+        if let Some(days) = self.retention.audit_log_days {
+            report.audit_log_entries = self.delete_audit_log_before(now - chrono::Duration::days(days.into())).await?;
+        }
+        if let Some(days) = self.retention.event_version_days {
+            report.event_versions = self.delete_event_versions_before(now - chrono::Duration::days(days.into())).await?;
+        }
+        report.login_attempts = self.delete_login_attempts_before(now - chrono::Duration::days(self.retention.login_attempt_days.into())).await?;
+        report.orphaned_rows = self.delete_orphaned_rows().await?;
+        report.orphaned_attachment_files = self.delete_orphaned_attachment_files().await?;
+
+        Ok(report)
+    }
+
+    /// Delete rows pointing at a row that no longer exists, then groups left without
+    /// members together with their calendars
+    async fn delete_orphaned_rows(&self) -> Result<u64, AppError> {
+        let mut deleted = 0;
+        for (table, column, parent) in maintenance::REFERENCES {
+            deleted += sqlx::query(&format!(
+                "DELETE FROM {table} WHERE {column} IS NOT NULL AND {column} NOT IN (SELECT id FROM {parent})"
+            ))
+            .execute(&self.pool)
+            .await?
+            .rows_affected();
+        }
+
+        let empty_groups: Vec<String> = sqlx::query_scalar("SELECT id FROM user_groups WHERE id NOT IN (SELECT group_id FROM group_members)")
+            .fetch_all(&self.pool)
+            .await?;
+        for group_id in empty_groups {
+            let group_id = Uuid::parse_str(&group_id)?;
+            deleted += self.get_group_calendars(group_id).await?.len() as u64 + 1;
+            self.delete_group(group_id).await?;
+        }
+
+        Ok(deleted)
+    }
+
+    /// Delete attachment files without an attachment, once they are older than an
+    /// upload can take
+    async fn delete_orphaned_attachment_files(&self) -> Result<u64, AppError> {
+        let attachments: std::collections::HashSet<String> = sqlx::query_scalar("SELECT id FROM attachments")
+            .fetch_all(&self.pool)
+            .await?
+            .into_iter()
+            .collect();
+
+        let cutoff = std::time::SystemTime::now() - maintenance::ORPHANED_FILE_GRACE;
+        let mut deleted = 0;
+        for id in self.attachments.stored_before(cutoff).await? {
+            if !attachments.contains(&id.to_string()) {
+                self.attachments.remove(id).await?;
+                deleted += 1;
+            }
+        }
+        Ok(deleted)
+    }
+
+    // Backups
+
+    /// Snapshot the database into the backup directory, then delete the backups beyond
+    /// the configured number, oldest first
+    pub async fn create_backup(&self) -> Result<BackupInfo, AppError> {
+        let io_error = |e: std::io::Error| AppError::InternalServerError(format!("Backup failed: {}", e));
+        std::fs::create_dir_all(&self.backups.dir).map_err(io_error)?;
+
+        let name = backup::file_name(self.now());
+        let path = std::path::Path::new(&self.backups.dir).join(&name);
+        sqlx::query("VACUUM INTO ?")
+            .bind(path.to_string_lossy().into_owned())
+            .execute(&self.pool)
+            .await?;
+
+        let backups = self.list_backups()?;
+        for old in backups.iter().skip(self.backups.keep.max(1)) {
+            std::fs::remove_file(std::path::Path::new(&self.backups.dir).join(&old.name)).map_err(io_error)?;
+        }
+
+        backups.into_iter()
+            .find(|b| b.name == name)
+            .ok_or_else(|| AppError::InternalServerError("Backup file missing after backup".to_string()))
+    }
+
+    /// Backups in the backup directory, newest first
+    pub fn list_backups(&self) -> Result<Vec<BackupInfo>, AppError> {
+        let io_error = |e: std::io::Error| AppError::InternalServerError(format!("Listing backups failed: {}", e));
+        let entries = match std::fs::read_dir(&self.backups.dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(io_error(e)),
+        };
+
+        let mut backups = Vec::new();
+        for entry in entries {
+            let entry = entry.map_err(io_error)?;
+            let name = entry.file_name().to_string_lossy().into_owned();
+            if !backup::is_backup_name(&name) {
+                continue;
+            }
+            let Ok(created_at) = chrono::NaiveDateTime::parse_from_str(&name, "calendar-%Y%m%d-%H%M%S.db") else {
+                continue;
+            };
+            backups.push(BackupInfo {
+                size: entry.metadata().map_err(io_error)?.len(),
+                created_at: created_at.and_utc(),
+                name,
+            });
+        }
+        backups.sort_by(|a, b| b.name.cmp(&a.name));
+
+        Ok(backups)
+    }
+
+    pub fn backup_dir(&self) -> &str {
+        &self.backups.dir
+    }
+
+    /// Location of the backup called `name`, if there is one
+    pub fn backup_path(&self, name: &str) -> Option<std::path::PathBuf> {
+        let path = std::path::Path::new(&self.backups.dir).join(name);
+        (backup::is_backup_name(name) && path.is_file()).then_some(path)
+    }
+
+    // Instance settings and first-run setup
+
+    /// The setup wizard is offered until the first account exists
+    pub async fn needs_setup(&self) -> Result<bool, AppError> {
+        let (count,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM users")
+            .fetch_one(&self.pool)
+            .await?;
+
+        Ok(count == 0)
+    }
+
+    pub async fn get_settings(&self) -> Result<InstanceSettings, AppError> {
+        let pairs: Vec<(String, String)> = sqlx::query_as("SELECT key, value FROM settings")
+            .fetch_all(&self.pool)
+            .await?;
+
+        let mut settings = InstanceSettings::from_pairs(pairs);
+        self.settings_overrides.apply(&mut settings);
+        Ok(settings)
+    }
+
+    /// Whether self-service registration is allowed. It stays closed until setup has
+    /// created the admin account, so the first account can't be taken over.
+    pub async fn registration_open(&self) -> Result<bool, AppError> {
+        if self.needs_setup().await? {
+            return Ok(false);
+        }
+        Ok(self.get_settings().await?.registration == RegistrationPolicy::Open)
+    }
+
+    /// Finish the first-run setup: store the instance settings and create the first
+    /// admin account. Both happen in one transaction, and the account is only inserted
+    /// while the users table is empty, so the wizard can't be replayed later.
+    pub async fn complete_setup(&self, admin: NewUser, settings: &InstanceSettings) -> Result<User, AppError> {
+        let password_hash = hash(&admin.password, DEFAULT_COST)?;
+        let now = self.now();
+        let id = Uuid::new_v4();
+
+        let mut tx = self.pool.begin().await?;
+
+        for (key, value) in settings.to_pairs() {
+            sqlx::query(
+                "INSERT INTO settings (key, value, updated_at) VALUES (?, ?, ?) \
+                 ON CONFLICT(key) DO UPDATE SET value = excluded.value, updated_at = excluded.updated_at"
+            )
+            .bind(key)
+            .bind(value)
+            .bind(now)
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        let inserted = sqlx::query(
+            "INSERT INTO users (id, name, email, password_hash, role, created_at, updated_at) \
+             SELECT ?, ?, ?, ?, ?, ?, ? WHERE NOT EXISTS (SELECT 1 FROM users)"
+        )
+        .bind(id.to_string())
+        .bind(&admin.name)
+        .bind(&admin.email)
+        .bind(&password_hash)
+        .bind(UserRole::Admin.as_str())
+        .bind(now)
+        .bind(now)
+        .execute(&mut *tx)
+        .await?;
+
+        if inserted.rows_affected() == 0 {
+            return Err(AppError::ValidationError("Setup has already been completed".to_string()));
+        }
+        tx.commit().await?;
+
+        let user = self.get_user_by_id(id).await?
+            .ok_or_else(|| AppError::InternalServerError("Failed to fetch created user".to_string()))?;
+
+        self.provision_new_user(user.id, admin.timezone.as_deref()).await?;
+
+        Ok(user)
+    }
+
+    // User operations
+    pub async fn get_user_by_id(&self, id: Uuid) -> Result<Option<User>, AppError> {
+        let user = sqlx::query_as::<_, User>(
+            "SELECT id, name, email, password_hash, role, created_at, updated_at FROM users WHERE id = ?"
+        )
+        .bind(id.to_string())
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| {
+            tracing::error!("Database error in get_user_by_id: {:?}", e);
+            AppError::DatabaseError(e)
+        })?;
+
+        Ok(user)
+    }
+
+    pub async fn get_user_by_email(&self, email: &str) -> Result<Option<User>, AppError> {
+        tracing::info!("Fetching user by email: {}", email);
+        let user = sqlx::query_as::<_, User>(
+            "SELECT id, name, email, password_hash, role, created_at, updated_at FROM users WHERE email = ?"
+        )
+        .bind(email)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| {
+            tracing::error!("Database error in get_user_by_email: {:?}", e);
+            AppError::DatabaseError(e)
+        })?;
+
+        Ok(user)
+    }
+
+    pub async fn create_user(&self, new_user: NewUser) -> Result<User, AppError> {
+        let password_hash = hash(&new_user.password, DEFAULT_COST)?;
+        let now = self.now();
+        let id = Uuid::new_v4();
+        let role = UserRole::default().as_str();
+        
+        sqlx::query(
+            "INSERT INTO users (id, name, email, password_hash, role, created_at, updated_at) VALUES (?, ?, ?, ?, ?, ?, ?)"
+        )
+        .bind(id.to_string())
+        .bind(&new_user.name)
+        .bind(&new_user.email)
+        .bind(&password_hash)
+        .bind(role)
+        .bind(now)
+        .bind(now)
+        .execute(&self.pool)
+        .await?;
+
+        // Fetch the user back
+        let user = self.get_user_by_id(id).await?
+            .ok_or_else(|| AppError::InternalServerError("Failed to fetch created user".to_string()))?;
+
+        self.provision_new_user(user.id, new_user.timezone.as_deref()).await?;
+
+        Ok(user)
+    }
+    
+    /// Create a user with a specific role (admin only)
+    pub async fn create_user_with_role(&self, new_user: NewUser, role: UserRole) -> Result<User, AppError> {
+        let password_hash = hash(&new_user.password, DEFAULT_COST)?;
+        let now = self.now();
+        let id = Uuid::new_v4();
+        
+        sqlx::query(
+            "INSERT INTO users (id, name, email, password_hash, role, created_at, updated_at) VALUES (?, ?, ?, ?, ?, ?, ?)"
+        )
+        .bind(id.to_string())
+        .bind(&new_user.name)
+        .bind(&new_user.email)
+        .bind(&password_hash)
+        .bind(role.as_str())
+        .bind(now)
+        .bind(now)
+        .execute(&self.pool)
+        .await?;
+
+        // Fetch the user back
+        let user = self.get_user_by_id(id).await?
+            .ok_or_else(|| AppError::InternalServerError("Failed to fetch created user".to_string()))?;
+
+        self.provision_new_user(user.id, new_user.timezone.as_deref()).await?;
+
+        Ok(user)
+    }
+    
+    /// Create the configured default calendars and starter event for a new account, and
+    /// show it times in the time zone detected at registration
+    async fn provision_new_user(&self, user_id: Uuid, timezone: Option<&str>) -> Result<(), AppError> {
+        if let Some(timezone) = timezone.and_then(|tz| tz.parse::<chrono_tz::Tz>().ok()) {
+            self.update_user_settings(user_id, UpdateUserSettings { timezone: Some(timezone), ..Default::default() }).await?;
+        }
+
+        let mut first_calendar = None;
+
+        for default_calendar in &self.onboarding.calendars {
+            let calendar = self.create_calendar(user_id, NewCalendar {
+                name: default_calendar.name.clone(),
+                description: None,
+                color: default_calendar.color.clone(),
+                is_public: false,
+                default_alarm_minutes: None,
+            }).await?;
+            first_calendar.get_or_insert(calendar.id);
+        }
+
+        if let (Some(calendar_id), true) = (first_calendar, self.onboarding.starter_event) {
+            // Tomorrow at 09:00 UTC, so the event shows up under upcoming events
+            let start_time = (self.now() + chrono::Duration::days(1))
+                .date_naive()
+                .and_hms_opt(9, 0, 0)
+                .unwrap_or_default()
+                .and_utc();
+
+            self.create_event(calendar_id, NewEvent {
+                title: STARTER_EVENT_TITLE.to_string(),
+                description: Some(STARTER_EVENT_DESCRIPTION.to_string()),
+                location: None,
+                start_time,
+                end_time: start_time + chrono::Duration::minutes(30),
+                is_all_day: false,
+                rrule: None,
+                geo_lat: None,
+                geo_lon: None,
+                alarm_minutes: None,
+            }).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Get all users (admin only)
+    pub async fn get_all_users(&self) -> Result<Vec<User>, AppError> {
+        let users = sqlx::query_as::<_, User>(
+            "SELECT id, name, email, password_hash, role, created_at, updated_at FROM users"
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| {
+            tracing::error!("Database error in get_all_users: {:?}", e);
+            AppError::DatabaseError(e)
+        })?;
+
+        Ok(users)
+    }
+    
+    /// Update user role (admin only)
+    pub async fn update_user_role(&self, id: Uuid, role: UserRole) -> Result<User, AppError> {
+        let now = self.now();
+        
+        sqlx::query("UPDATE users SET role = ?, updated_at = ? WHERE id = ?")
+            .bind(role.as_str())
+            .bind(now)
+            .bind(id.to_string())
+            .execute(&self.pool)
+            .await?;
+        
+        self.get_user_by_id(id).await?.ok_or(AppError::NotFoundError("User not found".to_string()))
+    }
+
+    pub async fn update_user(&self, id: Uuid, email: Option<String>, password: Option<String>) -> Result<User, AppError> {
+        let mut query = QueryBuilder::<Sqlite>::new("UPDATE users SET updated_at = ");
+        query.push_bind(self.now());
+
+        if let Some(new_email) = email {
+            query.push(", email = ").push_bind(new_email);
+        }
+
+        if let Some(new_password) = password {
+            let password_hash = hash(new_password, DEFAULT_COST)?;
+            query.push(", password_hash = ").push_bind(password_hash);
+        }
+
+        query.push(" WHERE id = ").push_bind(id.to_string());
+        query.build().execute(&self.pool).await?;
+
+        self.get_user_by_id(id).await?.ok_or(AppError::NotFoundError("User not found".to_string()))
+    }
+
+    /// Delete a user in one transaction. Everything that belongs to them, their
+    /// calendars with all they hold included, is deleted with them by the schema's
+    /// cascades. Group calendars they are the owner of record for pass to another member,
+    /// who becomes an admin if no other is left; groups they were the only member of are
+    /// deleted with their calendars. Shares offered to their email address go too, so a
+    /// new account with that address doesn't inherit them.
+    pub async fn delete_user(&self, id: Uuid) -> Result<(), AppError> {
+        let user = self.get_user_by_id(id).await?
+            .ok_or(AppError::NotFoundError("User not found".to_string()))?;
+        let mut tx = self.pool.begin().await?;
+
+        let groups: Vec<String> = sqlx::query_scalar("SELECT group_id FROM group_members WHERE user_id = ?")
+            .bind(id.to_string())
+            .fetch_all(&mut *tx)
+            .await?;
+        let mut handed_over = Vec::new();
+        let mut attachment_ids = Vec::new();
+        for group_id in groups {
+            let successor: Option<(String, String)> = sqlx::query_as(
+                "SELECT user_id, role FROM group_members WHERE group_id = ? AND user_id != ? ORDER BY role = 'admin' DESC, created_at LIMIT 1"
+            )
+            .bind(&group_id)
+            .bind(id.to_string())
+            .fetch_optional(&mut *tx)
+            .await?;
+
+            match successor {
+                Some((successor, role)) => {
+                    if role != GroupRole::Admin.as_str() {
+                        sqlx::query("UPDATE group_members SET role = ? WHERE group_id = ? AND user_id = ?")
+                            .bind(GroupRole::Admin.as_str())
+                            .bind(&group_id)
+                            .bind(&successor)
+                            .execute(&mut *tx)
+                            .await?;
+                    }
+                    sqlx::query("UPDATE calendars SET user_id = ?, updated_at = ? WHERE group_id = ? AND user_id = ?")
+                        .bind(&successor)
+                        .bind(self.now())
+                        .bind(&group_id)
+                        .bind(id.to_string())
+                        .execute(&mut *tx)
+                        .await?;
+                    handed_over.push(Uuid::parse_str(&group_id)?);
+                }
+                None => {
+                    attachment_ids.extend(Self::calendar_attachment_ids(&mut tx, "c.group_id = ?", &group_id).await?);
+                    sqlx::query("DELETE FROM calendars WHERE group_id = ?")
+                        .bind(&group_id)
+                        .execute(&mut *tx)
+                        .await?;
+                    sqlx::query("DELETE FROM user_groups WHERE id = ?")
+                        .bind(&group_id)
+                        .execute(&mut *tx)
+                        .await?;
+                }
+            }
+        }
+
+        attachment_ids.extend(Self::calendar_attachment_ids(&mut tx, "c.user_id = ?", &id.to_string()).await?);
+        sqlx::query("DELETE FROM shares WHERE shared_with_email = ?")
+            .bind(&user.email)
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query("DELETE FROM users WHERE id = ?")
+            .bind(id.to_string())
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+
+        for group_id in handed_over {
+            self.bump_group_calendars(group_id).await?;
+        }
+        self.remove_attachment_files(&attachment_ids).await?;
+        Ok(())
+    }
+
+    /// Delete everything a user has stored, shared or been given, keeping the account
+    /// with its sign-in methods, settings and group memberships. Group calendars stay
+    /// with their group.
+    pub async fn purge_user_data(&self, id: Uuid) -> Result<(), AppError> {
+        let user = self.get_user_by_id(id).await?
+            .ok_or(AppError::NotFoundError("User not found".to_string()))?;
+        let mut tx = self.pool.begin().await?;
+
+        let attachment_ids = Self::calendar_attachment_ids(&mut tx, "c.user_id = ? AND c.group_id IS NULL", &id.to_string()).await?;
+        sqlx::query("DELETE FROM calendars WHERE user_id = ? AND group_id IS NULL")
+            .bind(id.to_string())
+            .execute(&mut *tx)
+            .await?;
+        for (table, column) in PURGED_USER_DATA {
+            sqlx::query(&format!("DELETE FROM {} WHERE {} = ?", table, column))
+                .bind(id.to_string())
+                .execute(&mut *tx)
+                .await?;
+        }
+        sqlx::query("DELETE FROM shares WHERE shared_with_email = ?")
+            .bind(&user.email)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+
+        self.remove_attachment_files(&attachment_ids).await?;
+        Ok(())
+    }
+
+    /// Ids of the attachments in the calendars `c` matching `condition`, which takes
+    /// `value` as its one parameter
+    async fn calendar_attachment_ids(tx: &mut Transaction<'_, Sqlite>, condition: &str, value: &str) -> Result<Vec<String>, AppError> {
+        let ids = sqlx::query_scalar(&format!(
+            "SELECT a.id FROM attachments a JOIN events e ON e.id = a.event_id JOIN calendars c ON c.id = e.calendar_id WHERE {}",
+            condition
+        ))
+        .bind(value)
+        .fetch_all(&mut **tx)
+        .await?;
+
+        Ok(ids)
+    }
+
+    // Calendar operations
+    pub async fn get_calendars_by_user_id(&self, user_id: Uuid) -> Result<Vec<Calendar>, AppError> {
+        let calendars = sqlx::query_as::<_, Calendar>(
+            "SELECT id, user_id, name, description, color, is_public, ctag, sort_order, sync_epoch, group_id, default_alarm_minutes, is_birthdays, holiday_pack, is_default, created_at, updated_at FROM calendars WHERE user_id = ? ORDER BY sort_order, created_at"
+        )
+        .bind(user_id.to_string())
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(calendars)
+    }
+
+    /// Get a user's calendars with up to `limit` upcoming events each, loaded in a single query
+    pub async fn get_calendars_with_upcoming_events(&self, user_id: Uuid, limit: usize) -> Result<Vec<CalendarWithEvents>, AppError> {
+        let now = self.now();
+        let calendars = self.get_calendars_by_user_id(user_id).await?;
+
+        // Series masters are loaded regardless of their first instance, which may lie
+        // years back; the next occurrence is worked out from the RRULE below
+        let events = sqlx::query_as::<_, Event>(
+            "SELECT e.id, e.calendar_id, e.title, e.description, e.location, e.geo_lat, e.geo_lon, e.start_time, e.end_time, e.is_all_day, e.rrule, e.exdates, e.parent_id, e.recurrence_id, e.alarm_minutes, e.sequence, e.uid, e.resource_name, e.created_at, e.updated_at \
+             FROM events e \
+             JOIN calendars c ON c.id = e.calendar_id \
+             WHERE c.user_id = ? AND (e.end_time >= ? OR e.rrule IS NOT NULL OR e.parent_id IS NOT NULL)"
+        )
+        .bind(user_id.to_string())
+        .bind(now)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let upcoming = recurrence::upcoming(&events, now);
+
+        let calendars = calendars
+            .into_iter()
+            .map(|calendar| {
+                let upcoming_events = upcoming
+                    .iter()
+                    .filter(|(event, _, _)| event.calendar_id == calendar.id)
+                    .take(limit)
+                    .map(|(event, start, end)| EventSummary {
+                        id: event.id,
+                        title: event.title.clone(),
+                        location: event.location.clone(),
+                        start_time: *start,
+                        end_time: *end,
+                        is_all_day: event.is_all_day,
+                        is_recurring: event.is_recurring() || event.is_exception(),
+                    })
+                    .collect();
+                CalendarWithEvents { calendar, upcoming_events }
+            })
+            .collect();
+
+        Ok(calendars)
+    }
+
+    /// Up to `limit` instances of events in the user's calendars starting within `range`
+    /// from now, in a single query. Recurring events appear at their next occurrence.
+    pub async fn get_upcoming_events(&self, user_id: Uuid, range: chrono::Duration, limit: usize) -> Result<Vec<Event>, AppError> {
+        let now = self.now();
+        let until = now + range;
+
+        let events = sqlx::query_as::<_, Event>(
+            "SELECT e.id, e.calendar_id, e.title, e.description, e.location, e.geo_lat, e.geo_lon, e.start_time, e.end_time, e.is_all_day, e.rrule, e.exdates, e.parent_id, e.recurrence_id, e.alarm_minutes, e.sequence, e.uid, e.resource_name, e.created_at, e.updated_at \
+             FROM events e \
+             JOIN calendars c ON c.id = e.calendar_id \
+             WHERE c.user_id = ? AND ((e.end_time >= ? AND e.start_time <= ?) OR e.rrule IS NOT NULL OR e.parent_id IS NOT NULL)"
+        )
+        .bind(user_id.to_string())
+        .bind(now)
+        .bind(until)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(recurrence::upcoming(&events, now)
+            .into_iter()
+            .filter(|(_, start, _)| *start <= until)
+            .take(limit)
+            .map(|(event, start, end)| Event { start_time: start, end_time: end, ..event.clone() })
+            .collect())
+    }
+
+    /// Instances of events in the user's calendars that overlap `[start, end)`, with their
+    /// start and end set to the overlapping instance. Recurring series are expanded within
+    /// the window, so a weekly meeting conflicts with a one-off booked over any of its
+    /// occurrences. All-day events don't block time. `exclude` leaves out an event and its
+    /// overridden instances, e.g. the one being edited.
+    pub async fn find_conflicts(
+        &self,
+        user_id: Uuid,
+        start: chrono::DateTime<Utc>,
+        end: chrono::DateTime<Utc>,
+        exclude: Option<Uuid>,
+    ) -> Result<Vec<Event>, AppError> {
+        let events = sqlx::query_as::<_, Event>(
+            "SELECT e.id, e.calendar_id, e.title, e.description, e.location, e.geo_lat, e.geo_lon, e.start_time, e.end_time, e.is_all_day, e.rrule, e.exdates, e.parent_id, e.recurrence_id, e.alarm_minutes, e.sequence, e.uid, e.resource_name, e.created_at, e.updated_at \
+             FROM events e \
+             JOIN calendars c ON c.id = e.calendar_id \
+             WHERE c.user_id = ? AND e.is_all_day = 0 AND ((e.end_time > ? AND e.start_time < ?) OR e.rrule IS NOT NULL OR e.parent_id IS NOT NULL)"
+        )
+        .bind(user_id.to_string())
+        .bind(start)
+        .bind(end)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let excluded = |event: &Event| exclude.is_some_and(|id| event.id == id || event.parent_id == Some(id));
+        let mut conflicts: Vec<Event> = events
+            .iter()
+            .filter(|event| !excluded(event))
+            .flat_map(|event| {
+                let overridden: Vec<_> = events
+                    .iter()
+                    .filter(|e| e.parent_id == Some(event.id))
+                    .filter_map(|e| e.recurrence_id)
+                    .collect();
+                self.expansions
+                    .instances(event, start, end, &overridden)
+                    .into_iter()
+                    .map(|(start_time, end_time)| Event { start_time, end_time, ..event.clone() })
+            })
+            .collect();
+        conflicts.sort_by_key(|event| event.start_time);
+
+        Ok(conflicts)
+    }
+
+    /// Instances of other events in an event's calendar that overlap it, for warning
+    /// about double bookings. As in [`Self::find_conflicts`], all-day events don't block
+    /// time; a series is checked at its first occurrence.
+    pub async fn find_event_conflicts(&self, event: &Event) -> Result<Vec<Event>, AppError> {
+        if event.is_all_day || event.end_time <= event.start_time {
+            return Ok(Vec::new());
+        }
+        let series = event.parent_id.unwrap_or(event.id);
+
+        Ok(self.get_event_instances(event.calendar_id, event.start_time, event.end_time).await?
+            .into_iter()
+            .filter(|other| !other.is_all_day && other.id != series && other.parent_id != Some(series))
+            .collect())
+    }
+
+    /// Instances of the events in a calendar that overlap `[start, end)`, with their start
+    /// and end set to the instance, sorted by start. Overridden instances of a series are
+    /// reported through their exception event.
+    pub async fn get_event_instances(
+        &self,
+        calendar_id: Uuid,
+        start: chrono::DateTime<Utc>,
+        end: chrono::DateTime<Utc>,
+    ) -> Result<Vec<Event>, AppError> {
+        let events = self.get_events_by_calendar_id(calendar_id).await?;
+
+        let mut instances: Vec<Event> = events
+            .iter()
+            .flat_map(|event| {
+                let overridden: Vec<_> = events
+                    .iter()
+                    .filter(|e| e.parent_id == Some(event.id))
+                    .filter_map(|e| e.recurrence_id)
+                    .collect();
+                self.expansions
+                    .instances(event, start, end, &overridden)
+                    .into_iter()
+                    .map(|(start_time, end_time)| Event { start_time, end_time, ..event.clone() })
+            })
+            .collect();
+        instances.sort_by_key(|event| event.start_time);
+
+        Ok(instances)
+    }
+
+    /// Free-busy of a calendar within `[start, end)`. The time outside the owner's working
+    /// hours is marked unavailable if they chose to share it.
+    pub async fn get_free_busy(
+        &self,
+        calendar: &Calendar,
+        start: chrono::DateTime<Utc>,
+        end: chrono::DateTime<Utc>,
+    ) -> Result<FreeBusy, AppError> {
+        let free_busy = FreeBusy::new(start, end, &self.get_event_instances(calendar.id, start, end).await?);
+
+        let owner = self.get_user_settings(calendar.user_id).await?;
+        Ok(if owner.working_hours.free_busy {
+            free_busy.with_working_hours(&owner.working_hours, owner.timezone)
+        } else {
+            free_busy
+        })
+    }
+
+    /// Number of events in each of the user's calendars, counting a recurring series
+    /// once; calendars without events are left out
+    pub async fn count_events_per_calendar(&self, user_id: Uuid) -> Result<std::collections::HashMap<Uuid, usize>, AppError> {
+        let rows: Vec<(String, i64)> = sqlx::query_as(
+            "SELECT e.calendar_id, COUNT(*) FROM events e \
+             JOIN calendars c ON c.id = e.calendar_id \
+             WHERE c.user_id = ? AND e.parent_id IS NULL \
+             GROUP BY e.calendar_id"
+        )
+        .bind(user_id.to_string())
+        .fetch_all(&self.pool)
+        .await?;
+
+        rows.into_iter()
+            .map(|(calendar_id, count)| Ok((Uuid::parse_str(&calendar_id)?, count as usize)))
+            .collect()
+    }
+
+    /// Number of shares on the calendars the user owns
+    pub async fn count_shares_for_user(&self, user_id: Uuid) -> Result<usize, AppError> {
+        let count: i64 = sqlx::query_scalar(
+            "SELECT COUNT(*) FROM shares s JOIN calendars c ON c.id = s.calendar_id WHERE c.user_id = ?"
+        )
+        .bind(user_id.to_string())
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(count as usize)
+    }
+
+    pub async fn get_calendar_by_id(&self, id: Uuid) -> Result<Option<Calendar>, AppError> {
+        let calendar = sqlx::query_as::<_, Calendar>(
+            "SELECT id, user_id, name, description, color, is_public, ctag, sort_order, sync_epoch, group_id, default_alarm_minutes, is_birthdays, holiday_pack, is_default, created_at, updated_at FROM calendars WHERE id = ?"
+        )
+        .bind(id.to_string())
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(calendar)
+    }
+    
+    /// Get all public calendars
+    pub async fn get_public_calendars(&self) -> Result<Vec<Calendar>, AppError> {
+        let calendars = sqlx::query_as::<_, Calendar>(
+            "SELECT id, user_id, name, description, color, is_public, ctag, sort_order, sync_epoch, group_id, default_alarm_minutes, is_birthdays, holiday_pack, is_default, created_at, updated_at FROM calendars WHERE is_public = 1"
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(calendars)
+    }
+    
+    /// Export calendar as ICS format
+    /// Write a calendar as one iCalendar file, a page of events per chunk; journal
+    /// entries are only included with `journals`
+    pub async fn write_calendar_ics(&self, calendar: &Calendar, journals: bool, chunks: &Chunks) -> Result<(), AppError> {
+        chunks.send(format!(
+            "BEGIN:VCALENDAR\r\n\
+             VERSION:2.0\r\n\
+             PRODID:-//My CalDAV Server//EN\r\n\
+             CALSCALE:GREGORIAN\r\n\
+             X-WR-CALNAME:{}\r\n",
+            escape_ical_text(&calendar.name)
+        )).await?;
+
+        let mut after = None;
+        loop {
+            let events = self.get_events_page(calendar.id, after).await?;
+            let Some(last) = events.last() else { break };
+            after = Some(last.id);
+
+            let mut chunk = String::new();
+            for event in &events {
+                chunk.push_str(&self.event_ics(event).await?);
+            }
+            chunks.send(chunk).await?;
+        }
+
+        if journals {
+            let mut chunk = String::new();
+            for journal in self.get_journals_by_calendar_id(calendar.id).await? {
+                chunk.push_str(&journal.to_ical_string());
+            }
+            chunks.send(chunk).await?;
+        }
+
+        chunks.send("END:VCALENDAR\r\n".to_string()).await
+    }
+
+    /// Export a calendar with its events and tasks as a JSCalendar group
+    pub async fn export_calendar_jscalendar(&self, calendar_id: Uuid) -> Result<jscalendar::Group, AppError> {
+        let calendar = self.get_calendar_by_id(calendar_id).await?
+            .ok_or(AppError::NotFoundError("Calendar not found".to_string()))?;
+
+        let events = self.get_events_by_calendar_id(calendar_id).await?;
+        let tasks = self.get_tasks_by_calendar_id(calendar_id).await?;
+
+        Ok(jscalendar::group(&calendar, &events, &tasks))
+    }
+
+    /// Build the iCalendar representation of an event, including its attachments
+    pub async fn ical_event(&self, event: &Event) -> Result<ICalendarEvent, AppError> {
+        let mut ical_event = ICalendarEvent::from(event);
+        if let Some(parent_id) = event.parent_id
+            && let Some(master) = self.get_event_by_id(parent_id).await?
+        {
+            ical_event.uid = master.ical_uid();
+        }
+        ical_event.attachments = self.get_attachments_by_event_id(event.id).await?;
+        ical_event.base_url = self.base_url.clone();
+        Ok(ical_event)
+    }
+
+    /// The VEVENT of an event, rendered once per version of the event
+    pub async fn event_ics(&self, event: &Event) -> Result<Arc<str>, AppError> {
+        if let Some(ics) = self.ics_cache.get(event.id, event.updated_at) {
+            return Ok(ics);
+        }
+        let ics: Arc<str> = self.ical_event(event).await?.to_ical_string().into();
+        self.ics_cache.insert(event.id, event.updated_at, ics.clone());
+        Ok(ics)
+    }
+
+    /// Render a calendar object resource (a series master and its overridden instances)
+    pub async fn calendar_object_ics(&self, events: &[Event]) -> Result<String, AppError> {
+        let mut ical_content = String::from(
+            "BEGIN:VCALENDAR\r\n\
+             VERSION:2.0\r\n\
+             PRODID:-//My CalDAV Server//EN\r\n"
+        );
+
+        for event in events {
+            ical_content.push_str(&self.event_ics(event).await?);
+        }
+
+        ical_content.push_str("END:VCALENDAR\r\n");
+        Ok(ical_content)
+    }
+    
+    /// Search events by title or description
+    pub async fn search_events(&self, user_id: Uuid, query: &str) -> Result<Vec<Event>, AppError> {
+        let calendars = self.get_calendars_by_user_id(user_id).await?;
+        let mut results = Vec::new();
+        
+        for calendar in calendars {
+            let events = sqlx::query_as::<_, Event>(
+                "SELECT id, calendar_id, title, description, location, geo_lat, geo_lon, start_time, end_time, is_all_day, rrule, exdates, parent_id, recurrence_id, alarm_minutes, sequence, uid, resource_name, created_at, updated_at 
+                 FROM events 
+                 WHERE calendar_id = ? AND (title LIKE ? OR description LIKE ?)"
+            )
+            .bind(calendar.id.to_string())
+            .bind(format!("%{}%", query))
+            .bind(format!("%{}%", query))
+            .fetch_all(&self.pool)
+            .await?;
+            
+            results.extend(events);
+        }
+        
+        Ok(results)
+    }
+
+    /// Find events of public calendars within `radius_km` of a point that overlap a time range,
+    /// nearest first. Recurring series appear once per instance in the range.
+    pub async fn search_public_events_near(
+        &self,
+        lat: f64,
+        lon: f64,
+        radius_km: f64,
+        start: chrono::DateTime<Utc>,
+        end: chrono::DateTime<Utc>,
+    ) -> Result<Vec<NearbyEvent>, AppError> {
+        let bounds = geo::BoundingBox::around(lat, lon, radius_km);
+        let [first, second] = bounds.lon_ranges();
+
+        let events = sqlx::query_as::<_, Event>(
+            "SELECT e.id, e.calendar_id, e.title, e.description, e.location, e.geo_lat, e.geo_lon, e.start_time, e.end_time, e.is_all_day, e.rrule, e.exdates, e.parent_id, e.recurrence_id, e.alarm_minutes, e.sequence, e.uid, e.resource_name, e.created_at, e.updated_at \
+             FROM events e JOIN calendars c ON c.id = e.calendar_id \
+             WHERE c.is_public = 1 \
+               AND e.geo_lat BETWEEN ? AND ? \
+               AND (e.geo_lon BETWEEN ? AND ? OR e.geo_lon BETWEEN ? AND ?) \
+               AND ((e.end_time >= ? AND e.start_time <= ?) OR e.rrule IS NOT NULL)"
+        )
+        .bind(bounds.min_lat)
+        .bind(bounds.max_lat)
+        .bind(first.0)
+        .bind(first.1)
+        .bind(second.0)
+        .bind(second.1)
+        .bind(start)
+        .bind(end)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut results = Vec::new();
+        for event in events {
+            let (Some(geo_lat), Some(geo_lon)) = (event.geo_lat, event.geo_lon) else {
+                continue;
+            };
+            let distance_km = geo::distance_km(lat, lon, geo_lat, geo_lon);
+            if distance_km > radius_km {
+                continue;
+            }
+            if !event.is_recurring() {
+                results.push(NearbyEvent { event, distance_km });
+                continue;
+            }
+
+            // A series may have started long before the window; list each of its instances
+            // inside it, leaving overridden ones to their exception events
+            let overridden: Vec<_> = self.get_event_exceptions(event.id).await?
+                .into_iter()
+                .filter_map(|e| e.recurrence_id)
+                .collect();
+            for (instance_start, instance_end) in recurrence::instances(&event, start, end, &overridden) {
+                results.push(NearbyEvent {
+                    event: Event { start_time: instance_start, end_time: instance_end, ..event.clone() },
+                    distance_km,
+                });
+            }
+        }
+        results.sort_by(|a, b| a.distance_km.total_cmp(&b.distance_km));
+
+        Ok(results)
+    }
+
+    /// Per-week committed hours across calendars for the next `weeks` weeks, starting with
+    /// the current week. Recurring events are expanded; all-day events don't count.
+    pub async fn get_workload_forecast(&self, calendar_ids: &[Uuid], weeks: u32) -> Result<Vec<ForecastWeek>, AppError> {
+        let today = self.now().date_naive();
+        let first_monday = today - chrono::Duration::days(today.weekday().num_days_from_monday() as i64);
+        let window_start = first_monday.and_time(chrono::NaiveTime::MIN).and_utc();
+        let window_end = window_start + chrono::Duration::weeks(weeks as i64);
+
+        let mut forecast: Vec<ForecastWeek> = (0..weeks)
+            .map(|w| ForecastWeek {
+                week_start: first_monday + chrono::Duration::weeks(w as i64),
+                hours: 0.0,
+                event_count: 0,
+            })
+            .collect();
+
+        for calendar_id in calendar_ids {
+            let events = self.get_events_by_calendar_id(*calendar_id).await?;
+
+            for event in events.iter().filter(|e| !e.is_all_day) {
+                let overridden: Vec<_> = events
+                    .iter()
+                    .filter(|e| e.parent_id == Some(event.id))
+                    .filter_map(|e| e.recurrence_id)
+                    .collect();
+
+                for (start, end) in recurrence::instances(event, window_start, window_end, &overridden) {
+                    // Instances spanning midnight on Sunday count towards both weeks
+                    for (i, week) in forecast.iter_mut().enumerate() {
+                        let week_start = window_start + chrono::Duration::weeks(i as i64);
+                        let week_end = week_start + chrono::Duration::weeks(1);
+                        let overlap = end.min(week_end) - start.max(week_start);
+
+                        if overlap > chrono::Duration::zero() {
+                            week.hours += overlap.num_minutes() as f64 / 60.0;
+                        }
+                        if start >= week_start && start < week_end {
+                            week.event_count += 1;
+                        }
+                    }
+                }
+            }
+        }
+
+        for week in &mut forecast {
+            week.hours = (week.hours * 100.0).round() / 100.0;
+        }
+
+        Ok(forecast)
+    }
+
+    // Quotas
+
+    async fn count_calendars(&self, user_id: Uuid) -> Result<usize, AppError> {
+        let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM calendars WHERE user_id = ? AND is_birthdays = 0 AND holiday_pack IS NULL")
+            .bind(user_id.to_string())
+            .fetch_one(&self.pool)
+            .await?;
+        Ok(count as usize)
+    }
+
+    /// Events of a calendar; a recurring series with its overridden instances counts once
+    async fn count_events(&self, calendar_id: Uuid) -> Result<usize, AppError> {
+        let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM events WHERE calendar_id = ? AND parent_id IS NULL")
+            .bind(calendar_id.to_string())
+            .fetch_one(&self.pool)
+            .await?;
+        Ok(count as usize)
+    }
+
+    /// Fail unless the user may own another calendar
+    async fn check_calendar_quota(&self, user_id: Uuid) -> Result<(), AppError> {
+        match self.quotas.max_calendars_per_user {
+            Some(limit) if !self.can_create_calendar(user_id).await? => {
+                Err(AppError::QuotaExceeded(format!("A user can own at most {} calendars", limit)))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Whether the user may own another calendar
+    pub async fn can_create_calendar(&self, user_id: Uuid) -> Result<bool, AppError> {
+        match self.quotas.max_calendars_per_user {
+            Some(limit) => Ok(self.count_calendars(user_id).await? < limit),
+            None => Ok(true),
+        }
+    }
+
+    /// Fail unless `additional` more events fit into the calendar
+    pub async fn check_event_quota(&self, calendar_id: Uuid, additional: usize) -> Result<(), AppError> {
+        if let Some(limit) = self.quotas.max_events_per_calendar
+            && self.count_events(calendar_id).await? + additional > limit
+        {
+            return Err(AppError::QuotaExceeded(format!("A calendar can hold at most {} events", limit)));
+        }
+        Ok(())
+    }
+
+    /// Fail if an iCalendar payload of `size` bytes is larger than allowed
+    pub fn check_ics_size(&self, size: usize) -> Result<(), AppError> {
+        if size > self.quotas.max_ics_size {
+            return Err(AppError::ValidationError(format!(
+                "iCalendar data is {} bytes, the limit is {} bytes",
+                size, self.quotas.max_ics_size
+            )));
+        }
+        Ok(())
+    }
+
+    /// Usage of the user's calendar allowance and of each owned calendar's event allowance
+    pub async fn get_quota_usage(&self, user_id: Uuid) -> Result<Vec<QuotaUsage>, AppError> {
+        let calendars = self.get_calendars_by_user_id(user_id).await?;
+        let event_counts = self.count_events_per_calendar(user_id).await?;
+        let mut usage = vec![QuotaUsage {
+            label: "Calendars".to_string(),
+            used: calendars.len(),
+            limit: self.quotas.max_calendars_per_user,
+        }];
+        for calendar in calendars {
+            usage.push(QuotaUsage {
+                used: event_counts.get(&calendar.id).copied().unwrap_or(0),
+                label: calendar.name,
+                limit: self.quotas.max_events_per_calendar,
+            });
+        }
+        Ok(usage)
+    }
+
+    pub async fn create_calendar(&self, user_id: Uuid, new_calendar: NewCalendar) -> Result<Calendar, AppError> {
+        self.check_calendar_quota(user_id).await?;
+        reminders::validate_alarm_minutes("default_alarm_minutes", new_calendar.default_alarm_minutes)?;
+
+        let now = self.now();
+        let id = Uuid::new_v4();
+        
+        sqlx::query(
+            "INSERT INTO calendars (id, user_id, name, description, color, is_public, default_alarm_minutes, created_at, updated_at) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)"
+        )
+        .bind(id.to_string())
+        .bind(user_id.to_string())
+        .bind(&new_calendar.name)
+        .bind(&new_calendar.description)
+        .bind(&new_calendar.color)
+        .bind(new_calendar.is_public)
+        .bind(new_calendar.default_alarm_minutes)
+        .bind(now)
+        .bind(now)
+        .execute(&self.pool)
+        .await?;
+        self.ensure_default_calendar(user_id).await?;
+
+        // Fetch the calendar back
+        let calendar = self.get_calendar_by_id(id).await?
+            .ok_or_else(|| AppError::InternalServerError("Failed to fetch created calendar".to_string()))?;
+
+        Ok(calendar)
+    }
+
+    pub async fn update_calendar(&self, id: Uuid, updates: UpdateCalendar) -> Result<Calendar, AppError> {
+        let current = self.get_calendar_by_id(id).await?
+            .ok_or(AppError::NotFoundError("Calendar not found".to_string()))?;
+        reminders::validate_alarm_minutes("default_alarm_minutes", updates.default_alarm_minutes.flatten())?;
+
+        // Collection properties are part of what clients cache, so real changes bump the ctag
+        let properties_changed = updates.name.as_ref().is_some_and(|name| *name != current.name)
+            || updates.description.as_ref().is_some_and(|description| *description != current.description)
+            || updates.color.as_ref().is_some_and(|color| *color != current.color)
+            || updates.is_public.is_some_and(|is_public| is_public != current.is_public)
+            || updates.sort_order.is_some_and(|sort_order| sort_order != current.sort_order);
+
+        let mut query = QueryBuilder::<Sqlite>::new("UPDATE calendars SET updated_at = ");
+        query.push_bind(self.now());
+        if let Some(name) = updates.name {
+            query.push(", name = ").push_bind(name);
+        }
+        if let Some(description) = updates.description {
+            query.push(", description = ").push_bind(description);
+        }
+        if let Some(color) = updates.color {
+            query.push(", color = ").push_bind(color);
+        }
+        if let Some(is_public) = updates.is_public {
+            query.push(", is_public = ").push_bind(is_public);
+        }
+        if let Some(sort_order) = updates.sort_order {
+            query.push(", sort_order = ").push_bind(sort_order);
+        }
+        if let Some(default_alarm_minutes) = updates.default_alarm_minutes {
+            query.push(", default_alarm_minutes = ").push_bind(default_alarm_minutes);
+        }
+        query.push(" WHERE id = ").push_bind(id.to_string());
+        query.build().execute(&self.pool).await?;
+
+        match updates.is_default {
+            Some(true) => self.set_default_calendar(current.user_id, id).await?,
+            Some(false) if current.is_default => {
+                return Err(AppError::invalid_field("is_default", "Make another calendar the default instead"));
+            }
+            _ => {}
+        }
+
+        if properties_changed {
+            self.bump_ctag(id).await?;
+        }
+        
+        self.get_calendar_by_id(id).await?.ok_or(AppError::NotFoundError("Calendar not found".to_string()))
+    }
+
+    pub async fn delete_calendar(&self, id: Uuid) -> Result<(), AppError> {
+        let owner_id = self.get_calendar_by_id(id).await?.map(|calendar| calendar.user_id);
+        let mut tx = self.pool.begin().await?;
+
+        // Attachment files outlive their rows, so they are looked up before the delete
+        let attachment_ids = Self::calendar_attachment_ids(&mut tx, "c.id = ?", &id.to_string()).await?;
+
+        // Events, attachments, tasks, journal entries, shares and links go with it
+        sqlx::query("DELETE FROM calendars WHERE id = ?")
+            .bind(id.to_string())
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+
+        // The next personal calendar takes over from a deleted default
+        if let Some(owner_id) = owner_id {
+            self.ensure_default_calendar(owner_id).await?;
+        }
+
+        // Files are only removed once their rows are gone for good
+        self.remove_attachment_files(&attachment_ids).await?;
+        Ok(())
+    }
+
+    /// The calendar the user's new events go to when they don't choose one
+    pub async fn get_default_calendar(&self, user_id: Uuid) -> Result<Option<Calendar>, AppError> {
+        Ok(self.get_calendars_by_user_id(user_id).await?.into_iter().find(|c| c.is_default))
+    }
+
+    /// Make one of the user's personal calendars their default
+    pub async fn set_default_calendar(&self, user_id: Uuid, calendar_id: Uuid) -> Result<(), AppError> {
+        let calendar = self.get_calendar_by_id(calendar_id).await?
+            .filter(|c| c.user_id == user_id)
+            .ok_or(AppError::NotFoundError("Calendar not found".to_string()))?;
+        if calendar.group_id.is_some() || calendar.is_generated() {
+            return Err(AppError::invalid_field("is_default", "Only your own calendars that take events can be the default"));
+        }
+
+        sqlx::query("UPDATE calendars SET is_default = (id = ?) WHERE user_id = ?")
+            .bind(calendar_id.to_string())
+            .bind(user_id.to_string())
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Make the user's first personal calendar their default if they have none, as
+    /// after creating their first calendar or deleting the default
+    async fn ensure_default_calendar(&self, user_id: Uuid) -> Result<(), AppError> {
+        sqlx::query(
+            "UPDATE calendars SET is_default = 1 WHERE id = ( \
+                 SELECT id FROM calendars WHERE user_id = ? AND group_id IS NULL AND is_birthdays = 0 AND holiday_pack IS NULL \
+                 ORDER BY created_at LIMIT 1 \
+             ) AND NOT EXISTS (SELECT 1 FROM calendars WHERE user_id = ? AND is_default = 1)"
+        )
+        .bind(user_id.to_string())
+        .bind(user_id.to_string())
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    // Event operations
+    pub async fn get_event_by_id(&self, id: Uuid) -> Result<Option<Event>, AppError> {
+        let event = sqlx::query_as::<_, Event>(
+            "SELECT id, calendar_id, title, description, location, geo_lat, geo_lon, start_time, end_time, is_all_day, rrule, exdates, parent_id, recurrence_id, alarm_minutes, sequence, uid, resource_name, created_at, updated_at FROM events WHERE id = ?"
+        )
+        .bind(id.to_string())
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(event)
+    }
+
+    /// Series master or standalone event stored under a CalDAV resource name
+    pub async fn get_event_by_resource_name(&self, calendar_id: Uuid, name: &str) -> Result<Option<Event>, AppError> {
+        let event = sqlx::query_as::<_, Event>(
+            "SELECT id, calendar_id, title, description, location, geo_lat, geo_lon, start_time, end_time, is_all_day, rrule, exdates, parent_id, recurrence_id, alarm_minutes, sequence, uid, resource_name, created_at, updated_at FROM events WHERE calendar_id = ? AND parent_id IS NULL AND COALESCE(resource_name, id || '.ics') = ?"
+        )
+        .bind(calendar_id.to_string())
+        .bind(name)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(event)
+    }
+
+    /// Series master or standalone event of a calendar with the given iCalendar UID
+    pub async fn get_event_by_uid(&self, calendar_id: Uuid, uid: &str) -> Result<Option<Event>, AppError> {
+        let event = sqlx::query_as::<_, Event>(
+            "SELECT id, calendar_id, title, description, location, geo_lat, geo_lon, start_time, end_time, is_all_day, rrule, exdates, parent_id, recurrence_id, alarm_minutes, sequence, uid, resource_name, created_at, updated_at FROM events WHERE calendar_id = ? AND parent_id IS NULL AND COALESCE(uid, id) = ?"
+        )
+        .bind(calendar_id.to_string())
+        .bind(uid)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(event)
+    }
+
+    /// Store an event under another CalDAV resource name, e.g. the destination of a
+    /// COPY or MOVE
+    pub async fn rename_event_resource(&self, id: Uuid, resource_name: &str) -> Result<Event, AppError> {
+        sqlx::query("UPDATE events SET resource_name = ? WHERE id = ?")
+            .bind(resource_name)
+            .bind(id.to_string())
+            .execute(&self.pool)
+            .await?;
+
+        self.get_event_by_id(id).await?
+            .ok_or(AppError::NotFoundError("Event not found".to_string()))
+    }
+
+    pub async fn get_events_by_calendar_id(&self, calendar_id: Uuid) -> Result<Vec<Event>, AppError> {
+        let events = sqlx::query_as::<_, Event>(
+            "SELECT id, calendar_id, title, description, location, geo_lat, geo_lon, start_time, end_time, is_all_day, rrule, exdates, parent_id, recurrence_id, alarm_minutes, sequence, uid, resource_name, created_at, updated_at FROM events WHERE calendar_id = ?"
+        )
+        .bind(calendar_id.to_string())
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(events)
+    }
+
+    /// One page of a calendar's events, series masters and overridden instances alike,
+    /// in id order; `after` is the last event of the previous page
+    pub async fn get_events_page(&self, calendar_id: Uuid, after: Option<Uuid>) -> Result<Vec<Event>, AppError> {
+        let events = sqlx::query_as::<_, Event>(
+            "SELECT id, calendar_id, title, description, location, geo_lat, geo_lon, start_time, end_time, is_all_day, rrule, exdates, parent_id, recurrence_id, alarm_minutes, sequence, uid, resource_name, created_at, updated_at FROM events WHERE calendar_id = ? AND id > ? ORDER BY id LIMIT ?"
+        )
+        .bind(calendar_id.to_string())
+        .bind(after.map(|id| id.to_string()).unwrap_or_default())
+        .bind(streaming::PAGE_SIZE)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(events)
+    }
+
+    /// Events of a calendar with the start of their next upcoming instance
+    pub async fn get_listed_events_by_calendar_id(&self, calendar_id: Uuid) -> Result<Vec<ListedEvent>, AppError> {
+        let events = self.get_events_by_calendar_id(calendar_id).await?;
+        let now = self.now();
+
+        let next: std::collections::HashMap<Uuid, _> = recurrence::upcoming(&events, now)
+            .into_iter()
+            .map(|(event, start, _)| (event.id, start))
+            .collect();
+
+        Ok(events
+            .into_iter()
+            .map(|event| ListedEvent {
+                next_occurrence: next.get(&event.id).copied(),
+                event,
+            })
+            .collect())
+    }
+
+    /// Create an event; one without a reminder gets the calendar's default reminder
+    pub async fn create_event(&self, calendar_id: Uuid, new_event: NewEvent) -> Result<Event, AppError> {
+        self.insert_event(calendar_id, new_event, None, None).await
+    }
+
+    /// Create an event brought over from another calendar service, keeping its UID
+    pub async fn create_event_with_uid(&self, calendar_id: Uuid, new_event: NewEvent, uid: &str) -> Result<Event, AppError> {
+        self.insert_event(calendar_id, new_event, Some(uid), None).await
+    }
+
+    /// Create an event uploaded over CalDAV, keeping the UID and the resource name
+    /// the client chose. Callers check that neither is taken in the calendar.
+    pub async fn create_event_resource(&self, calendar_id: Uuid, new_event: NewEvent, uid: Option<&str>, resource_name: &str) -> Result<Event, AppError> {
+        self.insert_event(calendar_id, new_event, uid, Some(resource_name)).await
+    }
+
+    async fn insert_event(&self, calendar_id: Uuid, mut new_event: NewEvent, uid: Option<&str>, resource_name: Option<&str>) -> Result<Event, AppError> {
+        (new_event.start_time, new_event.end_time) = stored_times(new_event.start_time, new_event.end_time, new_event.is_all_day)?;
+        self.check_event_quota(calendar_id, 1).await?;
+        reminders::validate_alarm_minutes("alarm_minutes", new_event.alarm_minutes)?;
+
+        let now = self.now();
+        let id = Uuid::new_v4();
+        
+        sqlx::query(
+            "INSERT INTO events (id, calendar_id, title, description, location, geo_lat, geo_lon, start_time, end_time, is_all_day, rrule, alarm_minutes, uid, resource_name, created_at, updated_at) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, COALESCE(?, (SELECT default_alarm_minutes FROM calendars WHERE id = ?)), ?, ?, ?, ?)"
+        )
+        .bind(id.to_string())
+        .bind(calendar_id.to_string())
+        .bind(&new_event.title)
+        .bind(&new_event.description)
+        .bind(&new_event.location)
+        .bind(new_event.geo_lat)
+        .bind(new_event.geo_lon)
+        .bind(new_event.start_time)
+        .bind(new_event.end_time)
+        .bind(new_event.is_all_day)
+        .bind(&new_event.rrule)
+        .bind(new_event.alarm_minutes)
+        .bind(calendar_id.to_string())
+        .bind(uid)
+        .bind(resource_name)
+        .bind(now)
+        .bind(now)
+        .execute(&self.pool)
+        .await?;
+
+        // Fetch the event back
+        let event = self.get_event_by_id(id).await?
+            .ok_or_else(|| AppError::InternalServerError("Failed to fetch created event".to_string()))?;
+
+        self.bump_ctag(calendar_id).await?;
+
+        Ok(event)
+    }
+
+    /// Change an event, keeping the state it leaves as a version if anything changed
+    pub async fn update_event(&self, id: Uuid, mut updates: UpdateEvent) -> Result<Event, AppError> {
+        reminders::validate_alarm_minutes("alarm_minutes", updates.alarm_minutes.flatten())?;
+        let mut tx = self.pool.begin().await?;
+        let current = sqlx::query_as::<_, Event>(&format!("SELECT {} FROM events WHERE id = ?", EVENT_COLUMNS))
+            .bind(id.to_string())
+            .fetch_optional(&mut *tx)
+            .await?
+            .ok_or(AppError::NotFoundError("Event not found".to_string()))?;
+        // Moving one end is checked against where the other one stays
+        if updates.start_time.is_some() || updates.end_time.is_some() || updates.is_all_day.is_some() {
+            let (start_time, end_time) = stored_times(
+                updates.start_time.unwrap_or(current.start_time),
+                updates.end_time.unwrap_or(current.end_time),
+                updates.is_all_day.unwrap_or(current.is_all_day),
+            )?;
+            updates.start_time = Some(start_time);
+            updates.end_time = Some(end_time);
+        }
+        let mut query = QueryBuilder::<Sqlite>::new("UPDATE events SET updated_at = ");
+        query.push_bind(self.now());
+
+        if let Some(geo) = updates.geo() {
+            query.push(", geo_lat = ").push_bind(geo.map(|(lat, _)| lat));
+            query.push(", geo_lon = ").push_bind(geo.map(|(_, lon)| lon));
+        }
+        if let Some(title) = updates.title {
+            query.push(", title = ").push_bind(title);
+        }
+        if let Some(description) = updates.description {
+            query.push(", description = ").push_bind(description);
+        }
+        if let Some(location) = updates.location {
+            query.push(", location = ").push_bind(location);
+        }
+        if let Some(start_time) = updates.start_time {
+            query.push(", start_time = ").push_bind(start_time);
+        }
+        if let Some(end_time) = updates.end_time {
+            query.push(", end_time = ").push_bind(end_time);
+        }
+        if let Some(is_all_day) = updates.is_all_day {
+            query.push(", is_all_day = ").push_bind(is_all_day);
+        }
+        if let Some(rrule) = updates.rrule {
+            // An empty rule turns the series back into a single event
+            let rrule = Some(rrule).filter(|r| !r.trim().is_empty());
+            query.push(", rrule = ").push_bind(rrule);
+        }
+        if let Some(alarm_minutes) = updates.alarm_minutes {
+            query.push(", alarm_minutes = ").push_bind(alarm_minutes);
+        }
+
+        query.push(" WHERE id = ").push_bind(id.to_string());
+        query.build().execute(&mut *tx).await?;
+
+        let event = sqlx::query_as::<_, Event>(&format!("SELECT {} FROM events WHERE id = ?", EVENT_COLUMNS))
+            .bind(id.to_string())
+            .fetch_one(&mut *tx)
+            .await?;
+        // Clients often put back an event unchanged, which isn't worth a version
+        if !event_history::changed_fields(&current, &event).is_empty() {
+            self.record_event_versions(&mut tx, std::slice::from_ref(&current), VersionAction::Updated).await?;
+        }
+        tx.commit().await?;
+        self.bump_ctag(event.calendar_id).await?;
+
+        Ok(event)
+    }
+
+    /// Delete an event, keeping its last state, and that of its overridden instances,
+    /// as versions it can be restored from
+    pub async fn delete_event(&self, id: Uuid) -> Result<(), AppError> {
+        let event = self.get_event_by_id(id).await?;
+        let mut tx = self.pool.begin().await?;
+        self.record_deleted_event(&mut tx, id).await?;
+
+        let attachment_ids: Vec<String> = sqlx::query_scalar(
+            "SELECT a.id FROM attachments a JOIN events e ON e.id = a.event_id WHERE e.id = ? OR e.parent_id = ?"
+        )
+        .bind(id.to_string())
+        .bind(id.to_string())
+        .fetch_all(&mut *tx)
+        .await?;
+        Self::delete_attachment_rows(&mut tx, &attachment_ids).await?;
+
+        // Deleting a series master also removes its overridden instances
+        sqlx::query("DELETE FROM events WHERE id = ? OR parent_id = ?")
+            .bind(id.to_string())
+            .bind(id.to_string())
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+        self.remove_attachment_files(&attachment_ids).await?;
+
+        if let Some(event) = event {
+            // An overridden instance is part of its series master's resource
+            if let Some(parent_id) = event.parent_id {
+                self.touch_event(parent_id).await?;
+            }
+            self.bump_ctag(event.calendar_id).await?;
+        }
+        Ok(())
+    }
+
+    /// Apply event operations in one transaction, returning the status and resulting
+    /// event of each in order. Operations whose `base_version` no longer matches are
+    /// reported as conflicts and skipped. If `atomic` is set, any operation that isn't
+    /// applied rolls back the others, which are then reported as skipped; a database
+    /// error always rolls back the whole batch. Access must be checked by the caller.
+    pub async fn apply_event_batch(&self, operations: Vec<BatchOperation>, atomic: bool) -> Result<Vec<(SyncStatus, Option<Event>)>, AppError> {
+        // A batch that would overfill a calendar is rejected as a whole
+        let mut creates: std::collections::HashMap<Uuid, usize> = std::collections::HashMap::new();
+        for operation in &operations {
+            match operation {
+                BatchOperation::Create { calendar_id, event } => {
+                    validation::event_times(event.start_time, event.end_time)?;
+                    reminders::validate_alarm_minutes("alarm_minutes", event.alarm_minutes)?;
+                    *creates.entry(*calendar_id).or_default() += 1;
+                }
+                BatchOperation::Update { changes, .. } => {
+                    reminders::validate_alarm_minutes("alarm_minutes", changes.alarm_minutes.flatten())?;
+                }
+                BatchOperation::Delete { .. } => {}
+            }
+        }
+        for (calendar_id, count) in creates {
+            self.check_event_quota(calendar_id, count).await?;
+        }
+
+        let mut tx = self.pool.begin().await?;
+        let mut results = Vec::with_capacity(operations.len());
+        let mut changed_calendars = Vec::new();
+        let mut removed_attachments = Vec::new();
+
+        for operation in operations {
+            let now = self.now();
+            let outcome = match operation {
+                BatchOperation::Create { calendar_id, mut event } => {
+                    (event.start_time, event.end_time) = stored_times(event.start_time, event.end_time, event.is_all_day)?;
+                    let id = Uuid::new_v4();
+                    sqlx::query(
+                        "INSERT INTO events (id, calendar_id, title, description, location, geo_lat, geo_lon, start_time, end_time, is_all_day, rrule, alarm_minutes, created_at, updated_at) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, COALESCE(?, (SELECT default_alarm_minutes FROM calendars WHERE id = ?)), ?, ?)"
+                    )
+                    .bind(id.to_string())
+                    .bind(calendar_id.to_string())
+                    .bind(&event.title)
+                    .bind(&event.description)
+                    .bind(&event.location)
+                    .bind(event.geo_lat)
+                    .bind(event.geo_lon)
+                    .bind(event.start_time)
+                    .bind(event.end_time)
+                    .bind(event.is_all_day)
+                    .bind(&event.rrule)
+                    .bind(event.alarm_minutes)
+                    .bind(calendar_id.to_string())
+                    .bind(now)
+                    .bind(now)
+                    .execute(&mut *tx)
+                    .await?;
+
+                    let created = sqlx::query_as::<_, Event>(&format!("SELECT {} FROM events WHERE id = ?", EVENT_COLUMNS))
+                        .bind(id.to_string())
+                        .fetch_one(&mut *tx)
+                        .await?;
+                    changed_calendars.push(calendar_id);
+                    (SyncStatus::Applied, Some(created))
+                }
+                BatchOperation::Update { event_id, base_version, changes } => {
+                    let current = sqlx::query_as::<_, Event>(&format!("SELECT {} FROM events WHERE id = ?", EVENT_COLUMNS))
+                        .bind(event_id.to_string())
+                        .fetch_optional(&mut *tx)
+                        .await?;
+
+                    match current {
+                        None => (SyncStatus::NotFound, None),
+                        Some(current) if base_version.is_some_and(|v| v != current.updated_at) => (SyncStatus::Conflict, Some(current)),
+                        Some(current) => {
+                            // Same semantics as update_event: coordinates only as a pair,
+                            // an empty rule ends the series
+                            let (geo_lat, geo_lon) = match changes.geo() {
+                                Some(geo) => (geo.map(|(lat, _)| lat), geo.map(|(_, lon)| lon)),
+                                None => (current.geo_lat, current.geo_lon),
+                            };
+                            let rrule = match changes.rrule {
+                                Some(rrule) => Some(rrule).filter(|r| !r.trim().is_empty()),
+                                None => current.rrule.clone(),
+                            };
+                            let is_all_day = changes.is_all_day.unwrap_or(current.is_all_day);
+                            // Fails the whole batch, like the checks before it
+                            let (start_time, end_time) = stored_times(
+                                changes.start_time.unwrap_or(current.start_time),
+                                changes.end_time.unwrap_or(current.end_time),
+                                is_all_day,
+                            )?;
+
+                            sqlx::query(
+                                "UPDATE events SET title = ?, description = ?, location = ?, geo_lat = ?, geo_lon = ?, start_time = ?, end_time = ?, is_all_day = ?, rrule = ?, alarm_minutes = ?, updated_at = ? WHERE id = ?"
+                            )
+                            .bind(changes.title.unwrap_or_else(|| current.title.clone()))
+                            .bind(changes.description.unwrap_or_else(|| current.description.clone()))
+                            .bind(changes.location.unwrap_or_else(|| current.location.clone()))
+                            .bind(geo_lat)
+                            .bind(geo_lon)
+                            .bind(start_time)
+                            .bind(end_time)
+                            .bind(is_all_day)
+                            .bind(rrule)
+                            .bind(changes.alarm_minutes.unwrap_or(current.alarm_minutes))
+                            .bind(now)
+                            .bind(event_id.to_string())
+                            .execute(&mut *tx)
+                            .await?;
+
+                            let updated = sqlx::query_as::<_, Event>(&format!("SELECT {} FROM events WHERE id = ?", EVENT_COLUMNS))
+                                .bind(event_id.to_string())
+                                .fetch_one(&mut *tx)
+                                .await?;
+                            if !event_history::changed_fields(&current, &updated).is_empty() {
+                                self.record_event_versions(&mut tx, std::slice::from_ref(&current), VersionAction::Updated).await?;
+                            }
+                            changed_calendars.push(current.calendar_id);
+                            (SyncStatus::Applied, Some(updated))
+                        }
+                    }
+                }
+                BatchOperation::Delete { event_id, base_version } => {
+                    let current = sqlx::query_as::<_, Event>(&format!("SELECT {} FROM events WHERE id = ?", EVENT_COLUMNS))
+                        .bind(event_id.to_string())
+                        .fetch_optional(&mut *tx)
+                        .await?;
+
+                    match current {
+                        None => (SyncStatus::NotFound, None),
+                        Some(current) if base_version.is_some_and(|v| v != current.updated_at) => (SyncStatus::Conflict, Some(current)),
+                        Some(current) => {
+                            self.record_deleted_event(&mut tx, event_id).await?;
+                            let attachment_ids: Vec<String> = sqlx::query_scalar(
+                                "SELECT a.id FROM attachments a JOIN events e ON e.id = a.event_id WHERE e.id = ? OR e.parent_id = ?"
+                            )
+                            .bind(event_id.to_string())
+                            .bind(event_id.to_string())
+                            .fetch_all(&mut *tx)
+                            .await?;
+                            Self::delete_attachment_rows(&mut tx, &attachment_ids).await?;
+                            removed_attachments.extend(attachment_ids);
+
+                            // Deleting a series master also removes its overridden instances
+                            sqlx::query("DELETE FROM events WHERE id = ? OR parent_id = ?")
+                                .bind(event_id.to_string())
+                                .bind(event_id.to_string())
+                                .execute(&mut *tx)
+                                .await?;
+                            changed_calendars.push(current.calendar_id);
+                            (SyncStatus::Applied, None)
+                        }
+                    }
+                }
+            };
+
+            results.push(outcome);
+        }
+
+        if atomic && results.iter().any(|(status, _)| *status != SyncStatus::Applied) {
+            tx.rollback().await?;
+            return Ok(results.into_iter()
+                .map(|(status, event)| match status {
+                    SyncStatus::Applied => (SyncStatus::Skipped, None),
+                    _ => (status, event),
+                })
+                .collect());
+        }
+
+        tx.commit().await?;
+
+        // Files are only removed once their rows are gone for good
+        self.remove_attachment_files(&removed_attachments).await?;
+        changed_calendars.sort();
+        changed_calendars.dedup();
+        for calendar_id in changed_calendars {
+            self.bump_ctag(calendar_id).await?;
+        }
+
+        Ok(results)
+    }
+
+    // Event history
+
+    /// Store the current state of `events` as their newest versions, dropping the
+    /// oldest ones beyond the number kept per event
+    async fn record_event_versions(&self, tx: &mut Transaction<'_, Sqlite>, events: &[Event], action: VersionAction) -> Result<(), AppError> {
+        let keep = self.retention.event_versions;
+        if keep == 0 {
+            return Ok(());
+        }
+
+        let now = self.now();
+        for event in events {
+            sqlx::query(
+                "INSERT INTO event_versions (event_id, calendar_id, action, title, description, location, geo_lat, geo_lon, start_time, end_time, is_all_day, rrule, exdates, parent_id, recurrence_id, alarm_minutes, sequence, uid, resource_name, created_at, updated_at, recorded_at) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)"
+            )
+            .bind(event.id.to_string())
+            .bind(event.calendar_id.to_string())
+            .bind(action.as_str())
+            .bind(&event.title)
+            .bind(&event.description)
+            .bind(&event.location)
+            .bind(event.geo_lat)
+            .bind(event.geo_lon)
+            .bind(event.start_time)
+            .bind(event.end_time)
+            .bind(event.is_all_day)
+            .bind(&event.rrule)
+            .bind(Some(format_ical_date_list(&event.exdates)).filter(|e| !e.is_empty()))
+            .bind(event.parent_id.map(|id| id.to_string()))
+            .bind(event.recurrence_id)
+            .bind(event.alarm_minutes)
+            .bind(event.sequence)
+            .bind(&event.uid)
+            .bind(&event.resource_name)
+            .bind(event.created_at)
+            .bind(event.updated_at)
+            .bind(now)
+            .execute(&mut **tx)
+            .await?;
+
+            sqlx::query("DELETE FROM event_versions WHERE event_id = ? AND id NOT IN (SELECT id FROM event_versions WHERE event_id = ? ORDER BY id DESC LIMIT ?)")
+                .bind(event.id.to_string())
+                .bind(event.id.to_string())
+                .bind(keep as i64)
+                .execute(&mut **tx)
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Store the last state of an event and of its overridden instances before they
+    /// are deleted
+    async fn record_deleted_event(&self, tx: &mut Transaction<'_, Sqlite>, id: Uuid) -> Result<(), AppError> {
+        let events = sqlx::query_as::<_, Event>(&format!("SELECT {} FROM events WHERE id = ? OR parent_id = ?", EVENT_COLUMNS))
+            .bind(id.to_string())
+            .bind(id.to_string())
+            .fetch_all(&mut **tx)
+            .await?;
+        self.record_event_versions(tx, &events, VersionAction::Deleted).await
+    }
+
+    /// Earlier versions of an event, newest first
+    pub async fn get_event_versions(&self, event_id: Uuid) -> Result<Vec<EventVersion>, AppError> {
+        let versions = sqlx::query_as::<_, EventVersion>(&format!("{} WHERE event_id = ? ORDER BY version_id DESC", EVENT_VERSION_SELECT))
+            .bind(event_id.to_string())
+            .fetch_all(&self.pool)
+            .await?;
+        Ok(versions)
+    }
+
+    /// Deleted events of a calendar that can still be restored, as their last version,
+    /// most recently deleted first. Overridden instances come back with their series.
+    pub async fn get_deleted_events(&self, calendar_id: Uuid) -> Result<Vec<EventVersion>, AppError> {
+        let versions = sqlx::query_as::<_, EventVersion>(&format!(
+            "{} v WHERE calendar_id = ? AND action = 'deleted' AND parent_id IS NULL \
+             AND event_id NOT IN (SELECT id FROM events) \
+             AND v.id = (SELECT MAX(id) FROM event_versions WHERE event_id = v.event_id) ORDER BY version_id DESC",
+            EVENT_VERSION_SELECT
+        ))
+        .bind(calendar_id.to_string())
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(versions)
+    }
+
+    /// Calendar of an event, or of the newest version of a deleted one
+    pub async fn get_event_history_calendar_id(&self, event_id: Uuid) -> Result<Option<Uuid>, AppError> {
+        if let Some(event) = self.get_event_by_id(event_id).await? {
+            return Ok(Some(event.calendar_id));
+        }
+
+        let calendar_id: Option<String> = sqlx::query_scalar("SELECT calendar_id FROM event_versions WHERE event_id = ? ORDER BY id DESC LIMIT 1")
+            .bind(event_id.to_string())
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(calendar_id.map(|id| Uuid::parse_str(&id)).transpose()?)
+    }
+
+    /// Bring an event back to an earlier version. An event that still exists is
+    /// changed, keeping the state it leaves as a version; a deleted one is created
+    /// again with its UID and resource name, together with the overridden instances
+    /// deleted with it. Attachments aren't versioned and stay as they are.
+    pub async fn restore_event_version(&self, event_id: Uuid, version_id: i64) -> Result<Event, AppError> {
+        let version = sqlx::query_as::<_, EventVersion>(&format!("{} WHERE event_id = ? AND version_id = ?", EVENT_VERSION_SELECT))
+            .bind(event_id.to_string())
+            .bind(version_id)
+            .fetch_optional(&self.pool)
+            .await?
+            .ok_or(AppError::NotFoundError("Version not found".to_string()))?;
+        let restored = version.event;
+        let current = self.get_event_by_id(event_id).await?;
+        if current.is_none() {
+            self.check_event_quota(restored.calendar_id, 1).await?;
+        }
+
+        let now = self.now();
+        let mut tx = self.pool.begin().await?;
+        let calendar_id = match current {
+            Some(current) => {
+                sqlx::query(
+                    "UPDATE events SET title = ?, description = ?, location = ?, geo_lat = ?, geo_lon = ?, start_time = ?, end_time = ?, is_all_day = ?, rrule = ?, exdates = ?, alarm_minutes = ?, updated_at = ? WHERE id = ?"
+                )
+                .bind(&restored.title)
+                .bind(&restored.description)
+                .bind(&restored.location)
+                .bind(restored.geo_lat)
+                .bind(restored.geo_lon)
+                .bind(restored.start_time)
+                .bind(restored.end_time)
+                .bind(restored.is_all_day)
+                .bind(&restored.rrule)
+                .bind(Some(format_ical_date_list(&restored.exdates)).filter(|e| !e.is_empty()))
+                .bind(restored.alarm_minutes)
+                .bind(now)
+                .bind(event_id.to_string())
+                .execute(&mut *tx)
+                .await?;
+
+                if !event_history::changed_fields(&current, &restored).is_empty() {
+                    self.record_event_versions(&mut tx, std::slice::from_ref(&current), VersionAction::Updated).await?;
+                }
+                current.calendar_id
+            }
+            None => {
+                if let Some(parent_id) = restored.parent_id {
+                    let parent: Option<String> = sqlx::query_scalar("SELECT id FROM events WHERE id = ?")
+                        .bind(parent_id.to_string())
+                        .fetch_optional(&mut *tx)
+                        .await?;
+                    if parent.is_none() {
+                        return Err(AppError::ValidationError("Restore the series this instance belongs to first".to_string()));
+                    }
+                }
+                let taken: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM events WHERE calendar_id = ? AND (uid = ? OR resource_name = ?)")
+                    .bind(restored.calendar_id.to_string())
+                    .bind(&restored.uid)
+                    .bind(&restored.resource_name)
+                    .fetch_one(&mut *tx)
+                    .await?;
+                if taken > 0 {
+                    return Err(AppError::ValidationError("Another event in the calendar has taken this event's UID".to_string()));
+                }
+
+                // Instances overridden when the series was deleted were stored along with it
+                let overrides = sqlx::query_as::<_, EventVersion>(&format!(
+                    "{} WHERE parent_id = ? AND action = 'deleted' AND event_id NOT IN (SELECT id FROM events) \
+                     AND recorded_at = (SELECT MAX(recorded_at) FROM event_versions WHERE event_id = ? AND action = 'deleted')",
+                    EVENT_VERSION_SELECT
+                ))
+                .bind(event_id.to_string())
+                .bind(event_id.to_string())
+                .fetch_all(&mut *tx)
+                .await?;
+
+                Self::insert_restored_event(&mut tx, &restored, now).await?;
+                for instance in overrides {
+                    Self::insert_restored_event(&mut tx, &instance.event, now).await?;
+                }
+                restored.calendar_id
+            }
+        };
+        tx.commit().await?;
+
+        self.bump_ctag(calendar_id).await?;
+        self.get_event_by_id(event_id).await?
+            .ok_or_else(|| AppError::InternalServerError("Failed to fetch restored event".to_string()))
+    }
+
+    /// Insert a deleted event again as it was stored in a version
+    async fn insert_restored_event(tx: &mut Transaction<'_, Sqlite>, event: &Event, now: chrono::DateTime<Utc>) -> Result<(), AppError> {
+        sqlx::query(&format!("INSERT INTO events ({}) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)", EVENT_COLUMNS))
+            .bind(event.id.to_string())
+            .bind(event.calendar_id.to_string())
+            .bind(&event.title)
+            .bind(&event.description)
+            .bind(&event.location)
+            .bind(event.geo_lat)
+            .bind(event.geo_lon)
+            .bind(event.start_time)
+            .bind(event.end_time)
+            .bind(event.is_all_day)
+            .bind(&event.rrule)
+            .bind(Some(format_ical_date_list(&event.exdates)).filter(|e| !e.is_empty()))
+            .bind(event.parent_id.map(|id| id.to_string()))
+            .bind(event.recurrence_id)
+            .bind(event.alarm_minutes)
+            .bind(event.sequence)
+            .bind(&event.uid)
+            .bind(&event.resource_name)
+            .bind(event.created_at)
+            .bind(now)
+            .execute(&mut **tx)
+            .await?;
+        Ok(())
+    }
+
+    /// Delete event versions stored before `cutoff`, returning how many were removed
+    pub async fn delete_event_versions_before(&self, cutoff: chrono::DateTime<Utc>) -> Result<u64, AppError> {
+        let result = sqlx::query("DELETE FROM event_versions WHERE recorded_at < ?")
+            .bind(cutoff)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected())
+    }
+
+    // Event conflict operations
+
+    /// Raise the SEQUENCE of an event to one a client wrote it with; it never goes down
+    pub async fn raise_event_sequence(&self, id: Uuid, sequence: i64) -> Result<Event, AppError> {
+        sqlx::query("UPDATE events SET sequence = MAX(sequence, ?) WHERE id = ?")
+            .bind(sequence)
+            .bind(id.to_string())
+            .execute(&self.pool)
+            .await?;
+        self.get_event_by_id(id).await?
+            .ok_or(AppError::NotFoundError("Event not found".to_string()))
+    }
+
+    /// Keep a change that lost against a newer state of the event as a conflict copy.
+    /// A client sending the same change again doesn't add another copy.
+    pub async fn store_edit_conflict(&self, event_id: Uuid, client: Option<&str>, version: NewEvent) -> Result<EditConflict, AppError> {
+        if let Some(conflict) = self.get_edit_conflicts(event_id).await?.into_iter().find(|c| c.version == version) {
+            return Ok(conflict);
+        }
+
+        let conflict = EditConflict {
+            id: Uuid::new_v4(),
+            event_id,
+            client: EditConflict::client_name(client),
+            version,
+            created_at: self.now(),
+        };
+        sqlx::query(
+            "INSERT INTO edit_conflicts (id, event_id, client, title, description, location, geo_lat, geo_lon, start_time, end_time, is_all_day, rrule, alarm_minutes, created_at) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)"
+        )
+        .bind(conflict.id.to_string())
+        .bind(event_id.to_string())
+        .bind(&conflict.client)
+        .bind(&conflict.version.title)
+        .bind(&conflict.version.description)
+        .bind(&conflict.version.location)
+        .bind(conflict.version.geo_lat)
+        .bind(conflict.version.geo_lon)
+        .bind(conflict.version.start_time)
+        .bind(conflict.version.end_time)
+        .bind(conflict.version.is_all_day)
+        .bind(&conflict.version.rrule)
+        .bind(conflict.version.alarm_minutes)
+        .bind(conflict.created_at)
+        .execute(&self.pool)
+        .await?;
+
+        tracing::info!("Kept conflicting change of event {} as conflict {}", event_id, conflict.id);
+        Ok(conflict)
+    }
+
+    /// Conflict copies of an event, oldest first
+    pub async fn get_edit_conflicts(&self, event_id: Uuid) -> Result<Vec<EditConflict>, AppError> {
+        let conflicts = sqlx::query_as::<_, EditConflict>(
+            "SELECT id, event_id, client, title, description, location, geo_lat, geo_lon, start_time, end_time, is_all_day, rrule, alarm_minutes, created_at FROM edit_conflicts WHERE event_id = ? ORDER BY created_at"
+        )
+        .bind(event_id.to_string())
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(conflicts)
+    }
+
+    /// Events in the user's calendars that have conflict copies waiting to be resolved
+    pub async fn get_events_with_edit_conflicts(&self, user_id: Uuid) -> Result<Vec<Event>, AppError> {
+        let events = sqlx::query_as::<_, Event>(&format!(
+            "SELECT {} FROM events WHERE id IN (\
+                SELECT ec.event_id FROM edit_conflicts ec \
+                JOIN events e ON e.id = ec.event_id \
+                JOIN calendars c ON c.id = e.calendar_id \
+                WHERE c.user_id = ?\
+             ) ORDER BY start_time",
+            EVENT_COLUMNS
+        ))
+        .bind(user_id.to_string())
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(events)
+    }
+
+    /// Resolve a conflict by keeping the stored event or by applying the conflict copy
+    /// to it, which is recorded in the event's history; either way the copy is discarded
+    pub async fn resolve_edit_conflict(&self, event_id: Uuid, conflict_id: Uuid, keep: ConflictSide) -> Result<Event, AppError> {
+        let conflict = self.get_edit_conflicts(event_id).await?
+            .into_iter()
+            .find(|c| c.id == conflict_id)
+            .ok_or(AppError::NotFoundError("Conflict not found".to_string()))?;
+
+        let event = match keep {
+            ConflictSide::Current => self.get_event_by_id(event_id).await?
+                .ok_or(AppError::NotFoundError("Event not found".to_string()))?,
+            ConflictSide::Conflict => self.update_event(event_id, conflict.update()).await?,
+        };
+        sqlx::query("DELETE FROM edit_conflicts WHERE id = ?")
+            .bind(conflict_id.to_string())
+            .execute(&self.pool)
+            .await?;
+
+        Ok(event)
+    }
+
+    // Attachment operations
+
+    pub async fn get_attachments_by_event_id(&self, event_id: Uuid) -> Result<Vec<Attachment>, AppError> {
+        let attachments = sqlx::query_as::<_, Attachment>(
+            "SELECT id, event_id, filename, content_type, size, created_at FROM attachments WHERE event_id = ? ORDER BY created_at"
+        )
+        .bind(event_id.to_string())
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(attachments)
+    }
+
+    pub async fn get_attachment_by_id(&self, id: Uuid) -> Result<Option<Attachment>, AppError> {
+        let attachment = sqlx::query_as::<_, Attachment>(
+            "SELECT id, event_id, filename, content_type, size, created_at FROM attachments WHERE id = ?"
+        )
+        .bind(id.to_string())
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(attachment)
+    }
+
+    /// Store a file and attach it to an event
+    pub async fn create_attachment(&self, event_id: Uuid, filename: &str, content_type: &str, data: &[u8]) -> Result<Attachment, AppError> {
+        let event = self.get_event_by_id(event_id).await?
+            .ok_or(AppError::NotFoundError("Event not found".to_string()))?;
+
+        let now = self.now();
+        let id = Uuid::new_v4();
+
+        self.attachments.save(id, data).await?;
+
+        sqlx::query(
+            "INSERT INTO attachments (id, event_id, filename, content_type, size, created_at) VALUES (?, ?, ?, ?, ?, ?)"
+        )
+        .bind(id.to_string())
+        .bind(event_id.to_string())
+        .bind(filename)
+        .bind(content_type)
+        .bind(data.len() as i64)
+        .bind(now)
+        .execute(&self.pool)
+        .await?;
+
+        let attachment = self.get_attachment_by_id(id).await?
+            .ok_or_else(|| AppError::InternalServerError("Failed to fetch created attachment".to_string()))?;
+
+        self.touch_event(event_id).await?;
+        self.bump_ctag(event.calendar_id).await?;
+
+        Ok(attachment)
+    }
+
+    /// Read the stored contents of an attachment
+    pub async fn read_attachment(&self, attachment: &Attachment) -> Result<Vec<u8>, AppError> {
+        self.attachments.load(attachment.id).await
+    }
+
+    pub async fn delete_attachment(&self, id: Uuid) -> Result<(), AppError> {
+        let attachment = self.get_attachment_by_id(id).await?
+            .ok_or(AppError::NotFoundError("Attachment not found".to_string()))?;
+        let event = self.get_event_by_id(attachment.event_id).await?;
+
+        sqlx::query("DELETE FROM attachments WHERE id = ?")
+            .bind(id.to_string())
+            .execute(&self.pool)
+            .await?;
+        self.attachments.remove(id).await?;
+
+        if let Some(event) = event {
+            self.touch_event(event.id).await?;
+            self.bump_ctag(event.calendar_id).await?;
+        }
+        Ok(())
+    }
+
+    /// Mark an event as changed when something exported with it, like its attachments,
+    /// changes, so it gets a new ETag
+    async fn touch_event(&self, event_id: Uuid) -> Result<(), AppError> {
+        sqlx::query("UPDATE events SET updated_at = ? WHERE id = ?")
+            .bind(self.now())
+            .bind(event_id.to_string())
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Delete attachment rows as part of a transaction. Their files are left in place
+    /// until the transaction has committed, see `remove_attachment_files`.
+    async fn delete_attachment_rows(tx: &mut Transaction<'_, Sqlite>, ids: &[String]) -> Result<(), AppError> {
+        for id in ids {
+            sqlx::query("DELETE FROM attachments WHERE id = ?")
+                .bind(id)
+                .execute(&mut **tx)
+                .await?;
+        }
+        Ok(())
+    }
+
+    async fn remove_attachment_files(&self, ids: &[String]) -> Result<(), AppError> {
+        for id in ids {
+            self.attachments.remove(Uuid::parse_str(id)?).await?;
+        }
+        Ok(())
+    }
+
+    // Recurrence exception operations
+
+    /// Get the overridden instances of a recurring event
+    pub async fn get_event_exceptions(&self, master_id: Uuid) -> Result<Vec<Event>, AppError> {
+        let events = sqlx::query_as::<_, Event>(
+            "SELECT id, calendar_id, title, description, location, geo_lat, geo_lon, start_time, end_time, is_all_day, rrule, exdates, parent_id, recurrence_id, alarm_minutes, sequence, uid, resource_name, created_at, updated_at FROM events WHERE parent_id = ? ORDER BY recurrence_id"
+        )
+        .bind(master_id.to_string())
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(events)
+    }
+
+    /// Get all components of the calendar object resource an event belongs to:
+    /// the series master first, followed by its overridden instances
+    pub async fn get_calendar_object_events(&self, event: &Event) -> Result<Vec<Event>, AppError> {
+        let master = match event.parent_id {
+            Some(parent_id) => self.get_event_by_id(parent_id).await?
+                .ok_or(AppError::NotFoundError("Event not found".to_string()))?,
+            None => event.clone(),
+        };
+
+        let mut events = vec![master.clone()];
+        if master.is_recurring() {
+            events.extend(self.get_event_exceptions(master.id).await?);
+        }
+
+        Ok(events)
+    }
+
+    /// Override a single occurrence of a recurring event, creating or updating
+    /// the exception identified by its RECURRENCE-ID
+    pub async fn update_event_occurrence(&self, master_id: Uuid, occurrence: UpdateOccurrence) -> Result<Event, AppError> {
+        let master = self.get_event_by_id(master_id).await?
+            .ok_or(AppError::NotFoundError("Event not found".to_string()))?;
+
+        if !master.is_recurring() {
+            return Err(AppError::ValidationError("Event is not recurring".to_string()));
+        }
+
+        let existing = sqlx::query_as::<_, Event>(
+            "SELECT id, calendar_id, title, description, location, geo_lat, geo_lon, start_time, end_time, is_all_day, rrule, exdates, parent_id, recurrence_id, alarm_minutes, sequence, uid, resource_name, created_at, updated_at FROM events WHERE parent_id = ? AND recurrence_id = ?"
+        )
+        .bind(master_id.to_string())
+        .bind(occurrence.recurrence_id)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        // Recurrence rules can't be changed per instance
+        let changes = UpdateEvent { rrule: None, ..occurrence.changes };
+        reminders::validate_alarm_minutes("alarm_minutes", changes.alarm_minutes.flatten())?;
+
+        if let Some(existing) = existing {
+            return self.update_event(existing.id, changes).await;
+        }
+
+        let now = self.now();
+        let id = Uuid::new_v4();
+        let duration = master.end_time - master.start_time;
+        let start_time = changes.start_time.unwrap_or(occurrence.recurrence_id);
+        let geo = changes.geo().unwrap_or(master.geo_lat.zip(master.geo_lon));
+        let is_all_day = changes.is_all_day.unwrap_or(master.is_all_day);
+        let (start_time, end_time) = stored_times(start_time, changes.end_time.unwrap_or(start_time + duration), is_all_day)?;
+
+        sqlx::query(
+            "INSERT INTO events (id, calendar_id, title, description, location, geo_lat, geo_lon, start_time, end_time, is_all_day, parent_id, recurrence_id, alarm_minutes, created_at, updated_at) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)"
+        )
+        .bind(id.to_string())
+        .bind(master.calendar_id.to_string())
+        .bind(changes.title.unwrap_or(master.title))
+        .bind(changes.description.unwrap_or(master.description))
+        .bind(changes.location.unwrap_or(master.location))
+        .bind(geo.map(|(lat, _)| lat))
+        .bind(geo.map(|(_, lon)| lon))
+        .bind(start_time)
+        .bind(end_time)
+        .bind(is_all_day)
+        .bind(master_id.to_string())
+        .bind(occurrence.recurrence_id)
+        .bind(changes.alarm_minutes.unwrap_or(master.alarm_minutes))
+        .bind(now)
+        .bind(now)
+        .execute(&self.pool)
+        .await?;
+
+        let event = self.get_event_by_id(id).await?
+            .ok_or_else(|| AppError::InternalServerError("Failed to fetch created occurrence".to_string()))?;
+
+        self.bump_ctag(master.calendar_id).await?;
+
+        Ok(event)
+    }
+
+    /// Remove a single occurrence from a recurring event by adding an EXDATE to
+    /// the series master and dropping any override of that occurrence
+    pub async fn delete_event_occurrence(&self, master_id: Uuid, recurrence_id: chrono::DateTime<Utc>) -> Result<(), AppError> {
+        let master = self.get_event_by_id(master_id).await?
+            .ok_or(AppError::NotFoundError("Event not found".to_string()))?;
+
+        if !master.is_recurring() {
+            return Err(AppError::ValidationError("Event is not recurring".to_string()));
+        }
+
+        let mut tx = self.pool.begin().await?;
+        self.exclude_occurrence(&mut tx, &master, recurrence_id).await?;
+        tx.commit().await?;
+
+        self.bump_ctag(master.calendar_id).await?;
+
+        Ok(())
+    }
+
+    /// Add an EXDATE for an occurrence to its series master and drop any override of it
+    async fn exclude_occurrence(&self, tx: &mut Transaction<'_, Sqlite>, master: &Event, recurrence_id: chrono::DateTime<Utc>) -> Result<(), AppError> {
+        let mut exdates = master.exdates.clone();
+        if !exdates.contains(&recurrence_id) {
+            exdates.push(recurrence_id);
+            exdates.sort();
+            self.record_event_versions(tx, std::slice::from_ref(master), VersionAction::Updated).await?;
+        }
+        let overrides = sqlx::query_as::<_, Event>(&format!("SELECT {} FROM events WHERE parent_id = ? AND recurrence_id = ?", EVENT_COLUMNS))
+            .bind(master.id.to_string())
+            .bind(recurrence_id)
+            .fetch_all(&mut **tx)
+            .await?;
+        self.record_event_versions(tx, &overrides, VersionAction::Deleted).await?;
+
+        sqlx::query("UPDATE events SET exdates = ?, updated_at = ? WHERE id = ?")
+            .bind(format_ical_date_list(&exdates))
+            .bind(self.now())
+            .bind(master.id.to_string())
+            .execute(&mut **tx)
+            .await?;
+
+        sqlx::query("DELETE FROM events WHERE parent_id = ? AND recurrence_id = ?")
+            .bind(master.id.to_string())
+            .bind(recurrence_id)
+            .execute(&mut **tx)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Turn a single occurrence of a recurring event into a standalone event. The
+    /// occurrence keeps its overridden details and attachments, and the series gets
+    /// an EXDATE for it.
+    pub async fn detach_event_occurrence(&self, master_id: Uuid, recurrence_id: chrono::DateTime<Utc>) -> Result<Event, AppError> {
+        let master = self.get_event_by_id(master_id).await?
+            .ok_or(AppError::NotFoundError("Event not found".to_string()))?;
+
+        if !master.is_recurring() {
+            return Err(AppError::ValidationError("Event is not recurring".to_string()));
+        }
+
+        let existing = sqlx::query_as::<_, Event>(
+            "SELECT id, calendar_id, title, description, location, geo_lat, geo_lon, start_time, end_time, is_all_day, rrule, exdates, parent_id, recurrence_id, alarm_minutes, sequence, uid, resource_name, created_at, updated_at FROM events WHERE parent_id = ? AND recurrence_id = ?"
+        )
+        .bind(master_id.to_string())
+        .bind(recurrence_id)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        let occurrence = match existing {
+            Some(exception) => exception,
+            None => {
+                let duration = master.end_time - master.start_time;
+                Event {
+                    start_time: recurrence_id,
+                    end_time: recurrence_id + duration,
+                    ..master.clone()
+                }
+            }
+        };
+        let standalone = Event {
+            rrule: None,
+            exdates: Vec::new(),
+            parent_id: None,
+            recurrence_id: None,
+            ..occurrence
+        };
+
+        let mut tx = self.pool.begin().await?;
+        let id = self.insert_event_copy(&mut tx, &standalone, master.calendar_id, None).await?;
+        self.exclude_occurrence(&mut tx, &master, recurrence_id).await?;
+        tx.commit().await?;
+
+        self.bump_ctag(master.calendar_id).await?;
+
+        self.get_event_by_id(id).await?
+            .ok_or_else(|| AppError::InternalServerError("Failed to fetch detached event".to_string()))
+    }
+
+    // Moving and copying events between calendars
+
+    /// Move an event, together with its overridden instances, to another calendar
+    pub async fn move_event(&self, id: Uuid, target_calendar_id: Uuid) -> Result<Event, AppError> {
+        let event = self.get_event_by_id(id).await?
+            .ok_or(AppError::NotFoundError("Event not found".to_string()))?;
+
+        if event.is_exception() {
+            return Err(AppError::ValidationError("Overridden instances move with their series".to_string()));
+        }
+        if event.calendar_id == target_calendar_id {
+            return Ok(event);
+        }
+        self.check_event_quota(target_calendar_id, 1).await?;
+
+        sqlx::query("UPDATE events SET calendar_id = ?, updated_at = ? WHERE id = ? OR parent_id = ?")
+            .bind(target_calendar_id.to_string())
+            .bind(self.now())
+            .bind(id.to_string())
+            .bind(id.to_string())
+            .execute(&self.pool)
+            .await?;
+        // Earlier versions go along, so they stay restorable in the new calendar
+        sqlx::query("UPDATE event_versions SET calendar_id = ? WHERE event_id = ? OR parent_id = ?")
+            .bind(target_calendar_id.to_string())
+            .bind(id.to_string())
+            .bind(id.to_string())
+            .execute(&self.pool)
+            .await?;
+
+        self.bump_ctag(event.calendar_id).await?;
+        self.bump_ctag(target_calendar_id).await?;
+
+        self.get_event_by_id(id).await?
+            .ok_or_else(|| AppError::InternalServerError("Failed to fetch moved event".to_string()))
+    }
+
+    /// Copy an event, together with its overridden instances and attachments, into
+    /// another calendar, returning the new series master
+    pub async fn copy_event(&self, id: Uuid, target_calendar_id: Uuid) -> Result<Event, AppError> {
+        let event = self.get_event_by_id(id).await?
+            .ok_or(AppError::NotFoundError("Event not found".to_string()))?;
+
+        if event.is_exception() {
+            return Err(AppError::ValidationError("Overridden instances are copied with their series".to_string()));
+        }
+
+        self.check_event_quota(target_calendar_id, 1).await?;
+
+        let components = self.get_calendar_object_events(&event).await?;
+        let mut master_id = None;
+
+        let mut tx = self.pool.begin().await?;
+        for component in &components {
+            let copy_id = self.insert_event_copy(&mut tx, component, target_calendar_id, master_id).await?;
+            master_id.get_or_insert(copy_id);
+        }
+        tx.commit().await?;
+
+        let master_id = master_id
+            .ok_or_else(|| AppError::InternalServerError("Failed to copy event".to_string()))?;
+
+        self.bump_ctag(target_calendar_id).await?;
+
+        self.get_event_by_id(master_id).await?
+            .ok_or_else(|| AppError::InternalServerError("Failed to fetch copied event".to_string()))
+    }
+
+    /// Insert a copy of a single event row and its attachments as part of a transaction,
+    /// returning the new id. Attachment files are written straight away.
+    async fn insert_event_copy(&self, tx: &mut Transaction<'_, Sqlite>, event: &Event, calendar_id: Uuid, parent_id: Option<Uuid>) -> Result<Uuid, AppError> {
+        let now = self.now();
+        let id = Uuid::new_v4();
+        let exdates = Some(format_ical_date_list(&event.exdates)).filter(|e| !e.is_empty());
+
+        sqlx::query(
+            "INSERT INTO events (id, calendar_id, title, description, location, geo_lat, geo_lon, start_time, end_time, is_all_day, rrule, exdates, parent_id, recurrence_id, alarm_minutes, created_at, updated_at) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)"
+        )
+        .bind(id.to_string())
+        .bind(calendar_id.to_string())
+        .bind(&event.title)
+        .bind(&event.description)
+        .bind(&event.location)
+        .bind(event.geo_lat)
+        .bind(event.geo_lon)
+        .bind(event.start_time)
+        .bind(event.end_time)
+        .bind(event.is_all_day)
+        .bind(&event.rrule)
+        .bind(exdates)
+        .bind(parent_id.map(|p| p.to_string()))
+        .bind(event.recurrence_id)
+        .bind(event.alarm_minutes)
+        .bind(now)
+        .bind(now)
+        .execute(&mut **tx)
+        .await?;
+
+        let attachments = sqlx::query_as::<_, Attachment>(
+            "SELECT id, event_id, filename, content_type, size, created_at FROM attachments WHERE event_id = ? ORDER BY created_at"
+        )
+        .bind(event.id.to_string())
+        .fetch_all(&mut **tx)
+        .await?;
+
+        for attachment in attachments {
+            let data = self.attachments.load(attachment.id).await?;
+            let attachment_id = Uuid::new_v4();
+            self.attachments.save(attachment_id, &data).await?;
+
+            sqlx::query(
+                "INSERT INTO attachments (id, event_id, filename, content_type, size, created_at) VALUES (?, ?, ?, ?, ?, ?)"
+            )
+            .bind(attachment_id.to_string())
+            .bind(id.to_string())
+            .bind(&attachment.filename)
+            .bind(&attachment.content_type)
+            .bind(attachment.size)
+            .bind(now)
+            .execute(&mut **tx)
+            .await?;
+        }
+
+        Ok(id)
+    }
+
+    // Task and journal operations
+    pub async fn get_tasks_by_calendar_id(&self, calendar_id: Uuid) -> Result<Vec<Task>, AppError> {
+        let tasks = sqlx::query_as::<_, Task>(
+            "SELECT id, calendar_id, uid, summary, description, due, status, created_at, updated_at FROM tasks WHERE calendar_id = ? ORDER BY due IS NULL, due, created_at"
+        )
+        .bind(calendar_id.to_string())
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(tasks)
+    }
+
+    pub async fn create_task(&self, calendar_id: Uuid, new_task: NewTask) -> Result<Task, AppError> {
+        let now = self.now();
+        let id = Uuid::new_v4();
+
+        sqlx::query(
+            "INSERT INTO tasks (id, calendar_id, uid, summary, description, due, status, created_at, updated_at) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)"
+        )
+        .bind(id.to_string())
+        .bind(calendar_id.to_string())
+        .bind(&new_task.uid)
+        .bind(&new_task.summary)
+        .bind(&new_task.description)
+        .bind(new_task.due)
+        .bind(&new_task.status)
+        .bind(now)
+        .bind(now)
+        .execute(&self.pool)
+        .await?;
+
+        sqlx::query_as::<_, Task>(
+            "SELECT id, calendar_id, uid, summary, description, due, status, created_at, updated_at FROM tasks WHERE id = ?"
+        )
+        .bind(id.to_string())
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or_else(|| AppError::InternalServerError("Failed to fetch created task".to_string()))
+    }
+
+    pub async fn get_journals_by_calendar_id(&self, calendar_id: Uuid) -> Result<Vec<Journal>, AppError> {
+        let journals = sqlx::query_as::<_, Journal>(
+            "SELECT id, calendar_id, uid, summary, description, dtstart, resource_name, created_at, updated_at FROM journals WHERE calendar_id = ? ORDER BY dtstart DESC, created_at DESC"
+        )
+        .bind(calendar_id.to_string())
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(journals)
+    }
+
+    pub async fn create_journal(&self, calendar_id: Uuid, new_journal: NewJournal) -> Result<Journal, AppError> {
+        self.insert_journal(calendar_id, new_journal, None).await
+    }
+
+    /// Create a journal entry uploaded over CalDAV under the resource name the client
+    /// chose. Callers check that neither its UID nor the name is taken in the calendar.
+    pub async fn create_journal_resource(&self, calendar_id: Uuid, new_journal: NewJournal, resource_name: &str) -> Result<Journal, AppError> {
+        self.insert_journal(calendar_id, new_journal, Some(resource_name)).await
+    }
+
+    async fn insert_journal(&self, calendar_id: Uuid, new_journal: NewJournal, resource_name: Option<&str>) -> Result<Journal, AppError> {
+        let now = self.now();
+        let id = Uuid::new_v4();
+
+        sqlx::query(
+            "INSERT INTO journals (id, calendar_id, uid, summary, description, dtstart, resource_name, created_at, updated_at) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)"
+        )
+        .bind(id.to_string())
+        .bind(calendar_id.to_string())
+        .bind(&new_journal.uid)
+        .bind(&new_journal.summary)
+        .bind(&new_journal.description)
+        .bind(new_journal.dtstart)
+        .bind(resource_name)
+        .bind(now)
+        .bind(now)
+        .execute(&self.pool)
+        .await?;
+
+        let journal = sqlx::query_as::<_, Journal>(
+            "SELECT id, calendar_id, uid, summary, description, dtstart, resource_name, created_at, updated_at FROM journals WHERE id = ?"
+        )
+        .bind(id.to_string())
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or_else(|| AppError::InternalServerError("Failed to fetch created journal entry".to_string()))?;
+
+        self.bump_ctag(calendar_id).await?;
+        Ok(journal)
+    }
+
+    pub async fn get_journal_by_id(&self, id: Uuid) -> Result<Option<Journal>, AppError> {
+        let journal = sqlx::query_as::<_, Journal>(
+            "SELECT id, calendar_id, uid, summary, description, dtstart, resource_name, created_at, updated_at FROM journals WHERE id = ?"
+        )
+        .bind(id.to_string())
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(journal)
+    }
+
+    /// Journal entry of a calendar with the given UID; entries without a UID of
+    /// their own are identified by their id
+    pub async fn get_journal_by_uid(&self, calendar_id: Uuid, uid: &str) -> Result<Option<Journal>, AppError> {
+        let journal = sqlx::query_as::<_, Journal>(
+            "SELECT id, calendar_id, uid, summary, description, dtstart, resource_name, created_at, updated_at FROM journals WHERE calendar_id = ? AND COALESCE(uid, id) = ?"
+        )
+        .bind(calendar_id.to_string())
+        .bind(uid)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(journal)
+    }
+
+    /// Journal entry stored under a CalDAV resource name
+    pub async fn get_journal_by_resource_name(&self, calendar_id: Uuid, name: &str) -> Result<Option<Journal>, AppError> {
+        let journal = sqlx::query_as::<_, Journal>(
+            "SELECT id, calendar_id, uid, summary, description, dtstart, resource_name, created_at, updated_at FROM journals WHERE calendar_id = ? AND COALESCE(resource_name, id || '.ics') = ?"
+        )
+        .bind(calendar_id.to_string())
+        .bind(name)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(journal)
+    }
+
+    /// Replace the content of a journal entry, as a CalDAV PUT does; properties
+    /// missing from `journal` are removed
+    pub async fn replace_journal(&self, id: Uuid, journal: NewJournal) -> Result<Journal, AppError> {
+        let existing = self.get_journal_by_id(id).await?
+            .ok_or(AppError::NotFoundError("Journal entry not found".to_string()))?;
+
+        sqlx::query(
+            "UPDATE journals SET uid = ?, summary = ?, description = ?, dtstart = ?, updated_at = ? WHERE id = ?"
+        )
+        .bind(journal.uid.or(existing.uid))
+        .bind(&journal.summary)
+        .bind(&journal.description)
+        .bind(journal.dtstart)
+        .bind(self.now())
+        .bind(id.to_string())
+        .execute(&self.pool)
+        .await?;
+
+        self.bump_ctag(existing.calendar_id).await?;
+        self.get_journal_by_id(id).await?
+            .ok_or_else(|| AppError::InternalServerError("Failed to fetch updated journal entry".to_string()))
+    }
+
+    pub async fn delete_journal(&self, id: Uuid) -> Result<(), AppError> {
+        let Some(journal) = self.get_journal_by_id(id).await? else {
+            return Ok(());
+        };
+
+        sqlx::query("DELETE FROM journals WHERE id = ?")
+            .bind(id.to_string())
+            .execute(&self.pool)
+            .await?;
+
+        self.bump_ctag(journal.calendar_id).await
+    }
+
+    /// Store a batch of imported components in one transaction; on error nothing of the
+    /// batch is stored
+    pub async fn import_batch(&self, calendar_id: Uuid, batch: &ImportBatch) -> Result<(), AppError> {
+        self.check_event_quota(calendar_id, batch.events.len()).await?;
+
+        let now = self.now();
+        let mut tx = self.pool.begin().await?;
+
+        for event in &batch.events {
+            let (start_time, end_time) = stored_times(event.start_time, event.end_time, event.is_all_day)?;
+            sqlx::query(
+                "INSERT INTO events (id, calendar_id, title, description, location, geo_lat, geo_lon, start_time, end_time, is_all_day, rrule, alarm_minutes, created_at, updated_at) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, COALESCE(?, (SELECT default_alarm_minutes FROM calendars WHERE id = ?)), ?, ?)"
+            )
+            .bind(Uuid::new_v4().to_string())
+            .bind(calendar_id.to_string())
+            .bind(&event.title)
+            .bind(&event.description)
+            .bind(&event.location)
+            .bind(event.geo_lat)
+            .bind(event.geo_lon)
+            .bind(start_time)
+            .bind(end_time)
+            .bind(event.is_all_day)
+            .bind(&event.rrule)
+            .bind(event.alarm_minutes)
+            .bind(calendar_id.to_string())
+            .bind(now)
+            .bind(now)
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        for task in &batch.tasks {
+            sqlx::query(
+                "INSERT INTO tasks (id, calendar_id, uid, summary, description, due, status, created_at, updated_at) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)"
+            )
+            .bind(Uuid::new_v4().to_string())
+            .bind(calendar_id.to_string())
+            .bind(&task.uid)
+            .bind(&task.summary)
+            .bind(&task.description)
+            .bind(task.due)
+            .bind(&task.status)
+            .bind(now)
+            .bind(now)
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        for journal in &batch.journals {
+            sqlx::query(
+                "INSERT INTO journals (id, calendar_id, uid, summary, description, dtstart, created_at, updated_at) VALUES (?, ?, ?, ?, ?, ?, ?, ?)"
+            )
+            .bind(Uuid::new_v4().to_string())
+            .bind(calendar_id.to_string())
+            .bind(&journal.uid)
+            .bind(&journal.summary)
+            .bind(&journal.description)
+            .bind(journal.dtstart)
+            .bind(now)
+            .bind(now)
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        tx.commit().await?;
+
+        if !batch.events.is_empty() {
+            self.bump_ctag(calendar_id).await?;
+        }
+        Ok(())
+    }
+
+    // Change tracking and WebDAV-Push
+
+    /// Increment the calendar's ctag and notify push subscribers of the change
+    pub async fn bump_ctag(&self, calendar_id: Uuid) -> Result<(), AppError> {
+        sqlx::query("UPDATE calendars SET ctag = ctag + 1 WHERE id = ?")
+            .bind(calendar_id.to_string())
+            .execute(&self.pool)
+            .await?;
+
+        self.notify_push_subscribers(calendar_id).await?;
+        Ok(())
+    }
+
+    /// Force clients to download the whole calendar again, e.g. after the data was
+    /// repaired by hand: every object gets a new ETag and the ctag changes
+    pub async fn resync_calendar(&self, calendar_id: Uuid) -> Result<Calendar, AppError> {
+        sqlx::query("UPDATE calendars SET sync_epoch = sync_epoch + 1 WHERE id = ?")
+            .bind(calendar_id.to_string())
+            .execute(&self.pool)
+            .await?;
+        self.bump_ctag(calendar_id).await?;
+
+        self.get_calendar_by_id(calendar_id).await?
+            .ok_or(AppError::NotFoundError("Calendar not found".to_string()))
+    }
+
+    /// Make another existing user the owner of a calendar. Events, shares and links stay
+    /// as they are; a share the new owner held is dropped, and the previous owner keeps
+    /// full access through a new share if asked to. Returns the new owner.
+    pub async fn transfer_calendar(&self, calendar_id: Uuid, transfer: TransferCalendar) -> Result<User, AppError> {
+        let calendar = self.get_calendar_by_id(calendar_id).await?
             .ok_or(AppError::NotFoundError("Calendar not found".to_string()))?;
-        
-        let events = self.get_events_by_calendar_id(calendar_id).await?;
-        
-        let mut ical_content = format!(
-            "BEGIN:VCALENDAR\r\n\
-             VERSION:2.0\r\n\
-             PRODID:-//My CalDAV Server//EN\r\n\
-             CALSCALE:GREGORIAN\r\n\
-             X-WR-CALNAME:{}\r\n",
-            escape_ical_text(&calendar.name)
+        if calendar.group_id.is_some() {
+            return Err(AppError::ValidationError("Group calendars belong to their group and can't be transferred".to_string()));
+        }
+        if transfer.confirm_name.trim() != calendar.name {
+            return Err(AppError::invalid_field("confirm_name", "Type the calendar's name to confirm the transfer"));
+        }
+
+        let new_owner = self.get_user_by_email(transfer.new_owner_email.trim()).await?
+            .ok_or_else(|| AppError::invalid_field("new_owner_email", "No account with this email"))?;
+        if new_owner.id == calendar.user_id {
+            return Err(AppError::invalid_field("new_owner_email", "This account already owns the calendar"));
+        }
+        if !self.can_create_calendar(new_owner.id).await? {
+            return Err(AppError::QuotaExceeded(format!("{} can't own any more calendars", new_owner.email)));
+        }
+
+        let previous_owner = self.get_user_by_id(calendar.user_id).await?
+            .ok_or_else(|| AppError::NotFoundError("User not found".to_string()))?;
+
+        let now = self.now();
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("UPDATE calendars SET user_id = ?, is_default = 0, updated_at = ? WHERE id = ?")
+            .bind(new_owner.id.to_string())
+            .bind(now)
+            .bind(calendar_id.to_string())
+            .execute(&mut *tx)
+            .await?;
+
+        sqlx::query("DELETE FROM shares WHERE calendar_id = ? AND (shared_with_user_id = ? OR shared_with_email = ?)")
+            .bind(calendar_id.to_string())
+            .bind(new_owner.id.to_string())
+            .bind(&new_owner.email)
+            .execute(&mut *tx)
+            .await?;
+
+        if transfer.keep_access {
+            sqlx::query(
+                "INSERT INTO shares (id, calendar_id, user_id, shared_with_user_id, shared_with_email, permission_level, created_at) VALUES (?, ?, ?, ?, ?, ?, ?)"
+            )
+            .bind(Uuid::new_v4().to_string())
+            .bind(calendar_id.to_string())
+            .bind(new_owner.id.to_string())
+            .bind(previous_owner.id.to_string())
+            .bind(&previous_owner.email)
+            .bind(PermissionLevel::Admin.as_str())
+            .bind(now)
+            .execute(&mut *tx)
+            .await?;
+        }
+        tx.commit().await?;
+        self.ensure_default_calendar(previous_owner.id).await?;
+        self.ensure_default_calendar(new_owner.id).await?;
+
+        // The calendar moves between the owners' calendar homes
+        self.bump_ctag(calendar_id).await?;
+
+        Ok(new_owner)
+    }
+
+    pub async fn get_group_by_id(&self, id: Uuid) -> Result<Option<Group>, AppError> {
+        let group = sqlx::query_as::<_, Group>(
+            "SELECT id, name, created_by, created_at, updated_at FROM user_groups WHERE id = ?"
+        )
+        .bind(id.to_string())
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(group)
+    }
+
+    /// Groups the user belongs to, with their role in each
+    pub async fn get_groups_for_user(&self, user_id: Uuid) -> Result<Vec<UserGroup>, AppError> {
+        let rows = sqlx::query(
+            "SELECT g.id, g.name, g.created_by, g.created_at, g.updated_at, m.role \
+             FROM user_groups g JOIN group_members m ON m.group_id = g.id \
+             WHERE m.user_id = ? ORDER BY g.name"
+        )
+        .bind(user_id.to_string())
+        .fetch_all(&self.pool)
+        .await?;
+
+        rows.iter()
+            .map(|row| {
+                Ok(UserGroup {
+                    group: Group::from_row(row)?,
+                    role: GroupRole::from_str(&row.try_get::<String, _>("role")?),
+                })
+            })
+            .collect()
+    }
+
+    /// The user's role in the group, or `None` when they aren't a member
+    pub async fn get_group_role(&self, group_id: Uuid, user_id: Uuid) -> Result<Option<GroupRole>, AppError> {
+        let role: Option<String> = sqlx::query_scalar("SELECT role FROM group_members WHERE group_id = ? AND user_id = ?")
+            .bind(group_id.to_string())
+            .bind(user_id.to_string())
+            .fetch_optional(&self.pool)
+            .await?;
+
+        Ok(role.as_deref().map(GroupRole::from_str))
+    }
+
+    pub async fn get_group_members(&self, group_id: Uuid) -> Result<Vec<GroupMember>, AppError> {
+        let members = sqlx::query_as::<_, GroupMember>(
+            "SELECT m.group_id, m.user_id, u.name, u.email, m.role, m.created_at \
+             FROM group_members m JOIN users u ON u.id = m.user_id \
+             WHERE m.group_id = ? ORDER BY u.name, u.email"
+        )
+        .bind(group_id.to_string())
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(members)
+    }
+
+    pub async fn get_group_calendars(&self, group_id: Uuid) -> Result<Vec<Calendar>, AppError> {
+        let calendars = sqlx::query_as::<_, Calendar>(
+            "SELECT id, user_id, name, description, color, is_public, ctag, sort_order, sync_epoch, group_id, default_alarm_minutes, is_birthdays, holiday_pack, is_default, created_at, updated_at FROM calendars WHERE group_id = ? ORDER BY sort_order, created_at"
+        )
+        .bind(group_id.to_string())
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(calendars)
+    }
+
+    /// Create a group with its creator as the first admin
+    pub async fn create_group(&self, user_id: Uuid, new_group: NewGroup) -> Result<Group, AppError> {
+        let name = new_group.name.trim();
+        if name.is_empty() {
+            return Err(AppError::invalid_field("name", "Group name is required"));
+        }
+
+        let now = self.now();
+        let id = Uuid::new_v4();
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("INSERT INTO user_groups (id, name, created_by, created_at, updated_at) VALUES (?, ?, ?, ?, ?)")
+            .bind(id.to_string())
+            .bind(name)
+            .bind(user_id.to_string())
+            .bind(now)
+            .bind(now)
+            .execute(&mut *tx)
+            .await?;
+
+        sqlx::query("INSERT INTO group_members (group_id, user_id, role, created_at) VALUES (?, ?, ?, ?)")
+            .bind(id.to_string())
+            .bind(user_id.to_string())
+            .bind(GroupRole::Admin.as_str())
+            .bind(now)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+
+        self.get_group_by_id(id).await?
+            .ok_or_else(|| AppError::InternalServerError("Failed to fetch created group".to_string()))
+    }
+
+    /// Add an existing account to the group
+    pub async fn add_group_member(&self, group_id: Uuid, new_member: NewGroupMember) -> Result<GroupMember, AppError> {
+        let user = self.get_user_by_email(new_member.email.trim()).await?
+            .ok_or_else(|| AppError::invalid_field("email", "No account with this email"))?;
+        if self.get_group_role(group_id, user.id).await?.is_some() {
+            return Err(AppError::invalid_field("email", "This account is already a member"));
+        }
+
+        sqlx::query("INSERT INTO group_members (group_id, user_id, role, created_at) VALUES (?, ?, ?, ?)")
+            .bind(group_id.to_string())
+            .bind(user.id.to_string())
+            .bind(new_member.role.as_str())
+            .bind(self.now())
+            .execute(&self.pool)
+            .await?;
+        self.bump_group_calendars(group_id).await?;
+
+        self.get_group_members(group_id).await?
+            .into_iter()
+            .find(|member| member.user_id == user.id)
+            .ok_or_else(|| AppError::InternalServerError("Failed to fetch added member".to_string()))
+    }
+
+    /// Change a member's role; a demoted admin's group calendars pass to another admin
+    pub async fn update_group_member_role(&self, group_id: Uuid, user_id: Uuid, role: GroupRole) -> Result<(), AppError> {
+        let current = self.get_group_role(group_id, user_id).await?
+            .ok_or(AppError::NotFoundError("Group member not found".to_string()))?;
+        let successor = if current == GroupRole::Admin && role != GroupRole::Admin {
+            self.ensure_other_admin(group_id, user_id).await?
+        } else {
+            None
+        };
+
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("UPDATE group_members SET role = ? WHERE group_id = ? AND user_id = ?")
+            .bind(role.as_str())
+            .bind(group_id.to_string())
+            .bind(user_id.to_string())
+            .execute(&mut *tx)
+            .await?;
+
+        if let Some(successor) = successor {
+            sqlx::query("UPDATE calendars SET user_id = ?, updated_at = ? WHERE group_id = ? AND user_id = ?")
+                .bind(successor.to_string())
+                .bind(self.now())
+                .bind(group_id.to_string())
+                .bind(user_id.to_string())
+                .execute(&mut *tx)
+                .await?;
+        }
+        tx.commit().await?;
+        self.bump_group_calendars(group_id).await?;
+
+        Ok(())
+    }
+
+    /// Remove a member; group calendars they were the owner of record for pass to another admin
+    pub async fn remove_group_member(&self, group_id: Uuid, user_id: Uuid) -> Result<(), AppError> {
+        let role = self.get_group_role(group_id, user_id).await?
+            .ok_or(AppError::NotFoundError("Group member not found".to_string()))?;
+        let successor = self.ensure_other_admin(group_id, user_id).await;
+        let successor = match (role, successor) {
+            (_, Ok(successor)) => successor,
+            (GroupRole::Admin, Err(e)) => return Err(e),
+            (GroupRole::Member, Err(_)) => None,
+        };
+
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("DELETE FROM group_members WHERE group_id = ? AND user_id = ?")
+            .bind(group_id.to_string())
+            .bind(user_id.to_string())
+            .execute(&mut *tx)
+            .await?;
+
+        if let Some(successor) = successor {
+            sqlx::query("UPDATE calendars SET user_id = ?, updated_at = ? WHERE group_id = ? AND user_id = ?")
+                .bind(successor.to_string())
+                .bind(self.now())
+                .bind(group_id.to_string())
+                .bind(user_id.to_string())
+                .execute(&mut *tx)
+                .await?;
+        }
+        tx.commit().await?;
+        self.bump_group_calendars(group_id).await?;
+
+        Ok(())
+    }
+
+    /// Another admin of the group, failing when `user_id` is the last one
+    async fn ensure_other_admin(&self, group_id: Uuid, user_id: Uuid) -> Result<Option<Uuid>, AppError> {
+        let admin: Option<String> = sqlx::query_scalar(
+            "SELECT user_id FROM group_members WHERE group_id = ? AND role = 'admin' AND user_id != ? ORDER BY created_at LIMIT 1"
+        )
+        .bind(group_id.to_string())
+        .bind(user_id.to_string())
+        .fetch_optional(&self.pool)
+        .await?;
+
+        match admin {
+            Some(admin) => Ok(Some(Uuid::parse_str(&admin)?)),
+            None => Err(AppError::ValidationError("A group needs at least one admin".to_string())),
+        }
+    }
+
+    /// Create a calendar owned by the group, recorded against the creating admin
+    pub async fn create_group_calendar(&self, group_id: Uuid, user_id: Uuid, new_calendar: NewCalendar) -> Result<Calendar, AppError> {
+        let calendar = self.create_calendar(user_id, new_calendar).await?;
+        // Group calendars are never anyone's default
+        sqlx::query("UPDATE calendars SET group_id = ?, is_default = 0 WHERE id = ?")
+            .bind(group_id.to_string())
+            .bind(calendar.id.to_string())
+            .execute(&self.pool)
+            .await?;
+        self.ensure_default_calendar(user_id).await?;
+
+        self.get_calendar_by_id(calendar.id).await?
+            .ok_or_else(|| AppError::InternalServerError("Failed to fetch created calendar".to_string()))
+    }
+
+    /// Delete a group together with the calendars it owns
+    pub async fn delete_group(&self, group_id: Uuid) -> Result<(), AppError> {
+        for calendar in self.get_group_calendars(group_id).await? {
+            self.delete_calendar(calendar.id).await?;
+        }
+
+        // Memberships go with the group
+        sqlx::query("DELETE FROM user_groups WHERE id = ?")
+            .bind(group_id.to_string())
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Membership changes alter who sees the group's calendars in their calendar home
+    async fn bump_group_calendars(&self, group_id: Uuid) -> Result<(), AppError> {
+        for calendar in self.get_group_calendars(group_id).await? {
+            self.bump_ctag(calendar.id).await?;
+        }
+        Ok(())
+    }
+
+    pub async fn get_delegation_by_id(&self, id: Uuid) -> Result<Option<Delegation>, AppError> {
+        let delegation = sqlx::query_as::<_, Delegation>(&format!("{} WHERE d.id = ?", DELEGATION_SELECT))
+            .bind(id.to_string())
+            .fetch_optional(&self.pool)
+            .await?;
+
+        Ok(delegation)
+    }
+
+    /// Delegations the user has granted to others and received from them
+    pub async fn get_delegations(&self, user_id: Uuid) -> Result<Delegations, AppError> {
+        let granted = sqlx::query_as::<_, Delegation>(&format!("{} WHERE d.owner_id = ? ORDER BY delegate.name", DELEGATION_SELECT))
+            .bind(user_id.to_string())
+            .fetch_all(&self.pool)
+            .await?;
+
+        let received = sqlx::query_as::<_, Delegation>(&format!("{} WHERE d.delegate_id = ? ORDER BY owner.name", DELEGATION_SELECT))
+            .bind(user_id.to_string())
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(Delegations { granted, received })
+    }
+
+    /// Permission `delegate_id` holds on all of `owner_id`'s personal calendars
+    pub async fn get_delegation_permission(&self, owner_id: Uuid, delegate_id: Uuid) -> Result<Option<PermissionLevel>, AppError> {
+        let level: Option<String> = sqlx::query_scalar("SELECT permission_level FROM delegations WHERE owner_id = ? AND delegate_id = ?")
+            .bind(owner_id.to_string())
+            .bind(delegate_id.to_string())
+            .fetch_optional(&self.pool)
+            .await?;
+
+        Ok(level.as_deref().and_then(PermissionLevel::from_str))
+    }
+
+    /// Let another user act on all of the owner's calendars; granting again changes the permission
+    pub async fn create_delegation(&self, owner_id: Uuid, new_delegation: NewDelegation) -> Result<Delegation, AppError> {
+        let permission = match PermissionLevel::from_str(&new_delegation.permission) {
+            Some(permission @ (PermissionLevel::Read | PermissionLevel::Write)) => permission,
+            _ => return Err(AppError::invalid_field("permission", "Delegates get read or write access")),
+        };
+        let delegate = self.get_user_by_email(new_delegation.delegate_email.trim()).await?
+            .ok_or_else(|| AppError::invalid_field("delegate_email", "No account with this email"))?;
+        if delegate.id == owner_id {
+            return Err(AppError::invalid_field("delegate_email", "You can't delegate to yourself"));
+        }
+
+        sqlx::query(
+            "INSERT INTO delegations (id, owner_id, delegate_id, permission_level, created_at) VALUES (?, ?, ?, ?, ?) \
+             ON CONFLICT (owner_id, delegate_id) DO UPDATE SET permission_level = excluded.permission_level"
+        )
+        .bind(Uuid::new_v4().to_string())
+        .bind(owner_id.to_string())
+        .bind(delegate.id.to_string())
+        .bind(permission.as_str())
+        .bind(self.now())
+        .execute(&self.pool)
+        .await?;
+
+        sqlx::query_as::<_, Delegation>(&format!("{} WHERE d.owner_id = ? AND d.delegate_id = ?", DELEGATION_SELECT))
+            .bind(owner_id.to_string())
+            .bind(delegate.id.to_string())
+            .fetch_optional(&self.pool)
+            .await?
+            .ok_or_else(|| AppError::InternalServerError("Failed to fetch created delegation".to_string()))
+    }
+
+    pub async fn delete_delegation(&self, id: Uuid) -> Result<(), AppError> {
+        sqlx::query("DELETE FROM delegations WHERE id = ?")
+            .bind(id.to_string())
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    pub async fn get_push_subscription_by_id(&self, id: Uuid) -> Result<Option<PushSubscription>, AppError> {
+        let subscription = sqlx::query_as::<_, PushSubscription>(
+            "SELECT id, calendar_id, user_id, push_resource, expires_at, created_at FROM push_subscriptions WHERE id = ?"
+        )
+        .bind(id.to_string())
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(subscription)
+    }
+
+    /// Register a push subscription, replacing an existing one for the same push resource
+    pub async fn create_push_subscription(
+        &self,
+        calendar_id: Uuid,
+        user_id: Uuid,
+        push_resource: &str,
+        expires_at: chrono::DateTime<Utc>,
+    ) -> Result<PushSubscription, AppError> {
+        let now = self.now();
+        let id = Uuid::new_v4();
+
+        sqlx::query("DELETE FROM push_subscriptions WHERE calendar_id = ? AND push_resource = ?")
+            .bind(calendar_id.to_string())
+            .bind(push_resource)
+            .execute(&self.pool)
+            .await?;
+
+        let (held,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM push_subscriptions WHERE user_id = ?")
+            .bind(user_id.to_string())
+            .fetch_one(&self.pool)
+            .await?;
+        if held as usize >= push::MAX_PUSH_SUBSCRIPTIONS_PER_USER {
+            return Err(AppError::QuotaExceeded(format!("A user can hold at most {} push subscriptions", push::MAX_PUSH_SUBSCRIPTIONS_PER_USER)));
+        }
+
+        sqlx::query(
+            "INSERT INTO push_subscriptions (id, calendar_id, user_id, push_resource, expires_at, created_at) VALUES (?, ?, ?, ?, ?, ?)"
+        )
+        .bind(id.to_string())
+        .bind(calendar_id.to_string())
+        .bind(user_id.to_string())
+        .bind(push_resource)
+        .bind(expires_at)
+        .bind(now)
+        .execute(&self.pool)
+        .await?;
+
+        let subscription = self.get_push_subscription_by_id(id).await?
+            .ok_or_else(|| AppError::InternalServerError("Failed to fetch created push subscription".to_string()))?;
+
+        Ok(subscription)
+    }
+
+    pub async fn delete_push_subscription(&self, id: Uuid) -> Result<(), AppError> {
+        sqlx::query("DELETE FROM push_subscriptions WHERE id = ?")
+            .bind(id.to_string())
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Remove push subscriptions that expired before `now`
+    pub async fn delete_expired_push_subscriptions(&self, now: chrono::DateTime<Utc>) -> Result<u64, AppError> {
+        let result = sqlx::query("DELETE FROM push_subscriptions WHERE expires_at < ?")
+            .bind(now)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    /// Send a push message to every active subscription of a calendar.
+    /// Delivery happens in the background so request handling is not delayed.
+    async fn notify_push_subscribers(&self, calendar_id: Uuid) -> Result<(), AppError> {
+        self.delete_expired_push_subscriptions(self.now()).await?;
+
+        let subscriptions = sqlx::query_as::<_, PushSubscription>(
+            "SELECT id, calendar_id, user_id, push_resource, expires_at, created_at FROM push_subscriptions WHERE calendar_id = ?"
+        )
+        .bind(calendar_id.to_string())
+        .fetch_all(&self.pool)
+        .await?;
+
+        if subscriptions.is_empty() {
+            return Ok(());
+        }
+
+        let body = format!(
+            r#"<?xml version="1.0" encoding="utf-8"?>
+<P:push-message xmlns:P="https://bitfire.at/webdav-push">
+    <P:topic>{}</P:topic>
+</P:push-message>"#,
+            calendar_id
         );
-        
-        for event in &events {
-            let ical_event = ICalendarEvent::from(event);
-            ical_content.push_str(&ical_event.to_ical_string());
+
+        for subscription in subscriptions {
+            let service = self.clone();
+            let body = body.clone();
+            tokio::spawn(async move {
+                // The host may resolve elsewhere by now than when it was registered
+                if let Err(e) = push::check_push_resource(&subscription.push_resource).await {
+                    tracing::warn!("Not delivering push message to {}: {}", subscription.push_resource, e);
+                    return;
+                }
+                let result = service.http_client
+                    .post(&subscription.push_resource)
+                    .header("Content-Type", "application/xml; charset=utf-8")
+                    .header("TTL", "86400")
+                    .body(body)
+                    .send()
+                    .await;
+
+                match result {
+                    Ok(response) if response.status() == reqwest::StatusCode::NOT_FOUND
+                        || response.status() == reqwest::StatusCode::GONE => {
+                        // The push service no longer knows this subscription
+                        tracing::info!("Removing stale push subscription {}", subscription.id);
+                        if let Err(e) = service.delete_push_subscription(subscription.id).await {
+                            tracing::error!("Failed to remove push subscription: {:?}", e);
+                        }
+                    }
+                    Ok(response) if !response.status().is_success() => {
+                        tracing::warn!("Push delivery to {} failed with status {}", subscription.push_resource, response.status());
+                    }
+                    Ok(_) => {}
+                    Err(e) => {
+                        tracing::warn!("Push delivery to {} failed: {}", subscription.push_resource, e);
+                    }
+                }
+            });
         }
-        
-        ical_content.push_str("END:VCALENDAR\r\n");
-        
-        Ok(ical_content)
+
+        Ok(())
+    }
+
+    // Share operations
+    pub async fn get_shares_by_calendar_id(&self, calendar_id: Uuid) -> Result<Vec<Share>, AppError> {
+        let shares = sqlx::query_as::<_, Share>(
+            "SELECT id, calendar_id, user_id, shared_with_user_id, shared_with_email, permission_level, created_at FROM shares WHERE calendar_id = ?"
+        )
+        .bind(calendar_id.to_string())
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(shares)
     }
     
-    /// Search events by title or description
-    pub async fn search_events(&self, user_id: Uuid, query: &str) -> Result<Vec<Event>, AppError> {
-        let calendars = self.get_calendars_by_user_id(user_id).await?;
-        let mut results = Vec::new();
+    #[allow(dead_code)]
+    pub async fn get_all_shares(&self) -> Result<Vec<Share>, AppError> {
+        let shares = sqlx::query_as::<_, Share>(
+            "SELECT id, calendar_id, user_id, shared_with_user_id, shared_with_email, permission_level, created_at FROM shares"
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(shares)
+    }
+
+    pub async fn create_share(&self, calendar_id: Uuid, user_id: Uuid, new_share: NewShare) -> Result<Share, AppError> {
+        let now = self.now();
+        let id = Uuid::new_v4();
         
-        for calendar in calendars {
-            let events = sqlx::query_as::<_, Event>(
-                "SELECT id, calendar_id, title, description, location, start_time, end_time, is_all_day, created_at, updated_at 
-                 FROM events 
-                 WHERE calendar_id = ? AND (title LIKE ? OR description LIKE ?)"
-            )
-            .bind(calendar.id.to_string())
-            .bind(format!("%{}%", query))
-            .bind(format!("%{}%", query))
-            .fetch_all(&self.pool)
+        // Try to find user by email
+        let shared_with_user = self.get_user_by_email(&new_share.shared_with_email).await?;
+        
+        sqlx::query(
+            "INSERT INTO shares (id, calendar_id, user_id, shared_with_user_id, shared_with_email, permission_level, created_at) VALUES (?, ?, ?, ?, ?, ?, ?)"
+        )
+        .bind(id.to_string())
+        .bind(calendar_id.to_string())
+        .bind(user_id.to_string())
+        .bind(shared_with_user.as_ref().map(|u| u.id.to_string()))
+        .bind(&new_share.shared_with_email)
+        .bind(&new_share.permission)
+        .bind(now)
+        .execute(&self.pool)
+        .await?;
+
+        // Fetch the share back
+        let share = sqlx::query_as::<_, Share>(
+            "SELECT id, calendar_id, user_id, shared_with_user_id, shared_with_email, permission_level, created_at FROM shares WHERE id = ?"
+        )
+        .bind(id.to_string())
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or_else(|| AppError::InternalServerError("Failed to fetch created share".to_string()))?;
+
+        Ok(share)
+    }
+
+    pub async fn get_share_by_id(&self, id: Uuid) -> Result<Option<Share>, AppError> {
+        let share = sqlx::query_as::<_, Share>(
+            "SELECT id, calendar_id, user_id, shared_with_user_id, shared_with_email, permission_level, created_at FROM shares WHERE id = ?"
+        )
+        .bind(id.to_string())
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(share)
+    }
+
+    // Share links
+
+    pub async fn get_share_link_by_id(&self, id: Uuid) -> Result<Option<ShareLink>, AppError> {
+        let link = sqlx::query_as::<_, ShareLink>(
+            "SELECT id, calendar_id, created_by, permission_level, expires_at, created_at, redeemed_count, revoked_at FROM share_links WHERE id = ?"
+        )
+        .bind(id.to_string())
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(link)
+    }
+
+    /// A calendar's share links that can still be redeemed, newest first, with their URLs
+    pub async fn get_share_links(&self, calendar_id: Uuid) -> Result<Vec<ShareLinkDetails>, AppError> {
+        let now = self.now();
+        let links = sqlx::query_as::<_, ShareLink>(
+            "SELECT id, calendar_id, created_by, permission_level, expires_at, created_at, redeemed_count, revoked_at FROM share_links WHERE calendar_id = ? ORDER BY created_at DESC"
+        )
+        .bind(calendar_id.to_string())
+        .fetch_all(&self.pool)
+        .await?;
+
+        links.into_iter()
+            .filter(|link| link.is_active(now))
+            .map(|link| self.share_link_details(link))
+            .collect()
+    }
+
+    pub fn share_link_details(&self, link: ShareLink) -> Result<ShareLinkDetails, AppError> {
+        let claims = ShareLinkClaims {
+            sub: link.id.to_string(),
+            cal: link.calendar_id.to_string(),
+        };
+        let token = encode(&Header::default(), &claims, &EncodingKey::from_secret(self.jwt_secret.as_bytes()))?;
+        let url = self.url(&format!("/share/{}", token));
+        Ok(ShareLinkDetails { link, url })
+    }
+
+    /// Create a link granting read or write access to a calendar, optionally expiring
+    pub async fn create_share_link(&self, calendar_id: Uuid, created_by: Uuid, new_link: NewShareLink) -> Result<ShareLink, AppError> {
+        let permission = match PermissionLevel::from_str(&new_link.permission) {
+            Some(permission @ (PermissionLevel::Read | PermissionLevel::Write)) => permission,
+            _ => return Err(AppError::invalid_field("permission", "Share links grant read or write access")),
+        };
+        let expires_at = match new_link.expires_in_days {
+            Some(days) if days == 0 || days > MAX_SHARE_LINK_DAYS => {
+                return Err(AppError::invalid_field(
+                    "expires_in_days",
+                    format!("expires_in_days must be between 1 and {}", MAX_SHARE_LINK_DAYS),
+                ));
+            }
+            Some(days) => Some(self.now() + chrono::Duration::days(days.into())),
+            None => None,
+        };
+        let id = Uuid::new_v4();
+
+        sqlx::query(
+            "INSERT INTO share_links (id, calendar_id, created_by, permission_level, expires_at, created_at) VALUES (?, ?, ?, ?, ?, ?)"
+        )
+        .bind(id.to_string())
+        .bind(calendar_id.to_string())
+        .bind(created_by.to_string())
+        .bind(permission.as_str())
+        .bind(expires_at)
+        .bind(self.now())
+        .execute(&self.pool)
+        .await?;
+
+        self.get_share_link_by_id(id).await?
+            .ok_or_else(|| AppError::InternalServerError("Failed to fetch created share link".to_string()))
+    }
+
+    /// Stop a link from being redeemed. Grants it created earlier stay until their shares are removed.
+    pub async fn revoke_share_link(&self, id: Uuid) -> Result<(), AppError> {
+        sqlx::query("UPDATE share_links SET revoked_at = ? WHERE id = ? AND revoked_at IS NULL")
+            .bind(self.now())
+            .bind(id.to_string())
+            .execute(&self.pool)
             .await?;
-            
-            results.extend(events);
+
+        Ok(())
+    }
+
+    /// Look up the link behind a share URL; forged, unknown, revoked and expired links are not found
+    pub async fn resolve_share_link(&self, value: &str) -> Result<ShareLink, AppError> {
+        let not_found = || AppError::NotFoundError("Share link not found or expired".to_string());
+        let claims = jsonwebtoken::decode::<ShareLinkClaims>(
+            value,
+            &jsonwebtoken::DecodingKey::from_secret(self.jwt_secret.as_bytes()),
+            &Self::validation_without_exp(),
+        )
+        .map_err(|_| not_found())?
+        .claims;
+        let id = Uuid::parse_str(&claims.sub).map_err(|_| not_found())?;
+
+        self.get_share_link_by_id(id).await?
+            .filter(|link| link.is_active(self.now()) && link.calendar_id.to_string() == claims.cal)
+            .ok_or_else(not_found)
+    }
+
+    /// Redeem a share link for a user, granting the link's permission on its calendar.
+    /// An existing share of the user is upgraded, never downgraded; the calendar's owner
+    /// gains nothing. Returns the calendar's ID.
+    pub async fn redeem_share_link(&self, value: &str, user_id: Uuid) -> Result<Uuid, AppError> {
+        let link = self.resolve_share_link(value).await?;
+        let user = self.get_user_by_id(user_id).await?
+            .ok_or_else(|| AppError::AuthenticationError("User not found".to_string()))?;
+        let calendar = self.get_calendar_by_id(link.calendar_id).await?
+            .ok_or_else(|| AppError::NotFoundError("Calendar not found".to_string()))?;
+        if calendar.user_id == user_id {
+            return Ok(calendar.id);
         }
-        
-        Ok(results)
+        let permission = PermissionLevel::from_str(&link.permission_level)
+            .ok_or_else(|| AppError::InternalServerError("Invalid share link permission".to_string()))?;
+
+        let mut tx = self.pool.begin().await?;
+        let existing: Option<(String, String)> = sqlx::query_as(
+            "SELECT id, permission_level FROM shares WHERE calendar_id = ? AND (shared_with_user_id = ? OR shared_with_email = ?) ORDER BY created_at LIMIT 1"
+        )
+        .bind(calendar.id.to_string())
+        .bind(user_id.to_string())
+        .bind(&user.email)
+        .fetch_optional(&mut *tx)
+        .await?;
+
+        match existing {
+            Some((id, level)) => {
+                if PermissionLevel::from_str(&level).is_none_or(|level| level < permission) {
+                    sqlx::query("UPDATE shares SET permission_level = ? WHERE id = ?")
+                        .bind(permission.as_str())
+                        .bind(id)
+                        .execute(&mut *tx)
+                        .await?;
+                }
+            }
+            None => {
+                sqlx::query(
+                    "INSERT INTO shares (id, calendar_id, user_id, shared_with_user_id, shared_with_email, permission_level, created_at) VALUES (?, ?, ?, ?, ?, ?, ?)"
+                )
+                .bind(Uuid::new_v4().to_string())
+                .bind(calendar.id.to_string())
+                .bind(link.created_by.to_string())
+                .bind(user_id.to_string())
+                .bind(&user.email)
+                .bind(permission.as_str())
+                .bind(self.now())
+                .execute(&mut *tx)
+                .await?;
+            }
+        }
+
+        sqlx::query("UPDATE share_links SET redeemed_count = redeemed_count + 1 WHERE id = ?")
+            .bind(link.id.to_string())
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+
+        Ok(calendar.id)
     }
 
-    pub async fn create_calendar(&self, user_id: Uuid, new_calendar: NewCalendar) -> Result<Calendar, AppError> {
-        let now = Utc::now();
+    // Calendar subscriptions
+
+    /// Normalize and check a feed URL; `webcal://` is the same feed over HTTPS
+    fn subscription_url(url: &str) -> Result<String, AppError> {
+        let url = url.trim();
+        let url = match url.strip_prefix("webcal://") {
+            Some(rest) => format!("https://{}", rest),
+            None => url.to_string(),
+        };
+        if !(url.starts_with("https://") || url.starts_with("http://")) || url.contains(char::is_whitespace) {
+            return Err(AppError::invalid_field("url", "Subscription URL must be an http(s) or webcal URL"));
+        }
+        Ok(url)
+    }
+
+    pub async fn get_subscriptions_by_user_id(&self, user_id: Uuid) -> Result<Vec<CalendarSubscription>, AppError> {
+        let subscriptions = sqlx::query_as::<_, CalendarSubscription>(
+            "SELECT id, user_id, name, url, color, is_visible, created_at, updated_at FROM calendar_subscriptions WHERE user_id = ? ORDER BY name"
+        )
+        .bind(user_id.to_string())
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(subscriptions)
+    }
+
+    pub async fn get_subscription_by_id(&self, id: Uuid) -> Result<Option<CalendarSubscription>, AppError> {
+        let subscription = sqlx::query_as::<_, CalendarSubscription>(
+            "SELECT id, user_id, name, url, color, is_visible, created_at, updated_at FROM calendar_subscriptions WHERE id = ?"
+        )
+        .bind(id.to_string())
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(subscription)
+    }
+
+    pub async fn create_subscription(&self, user_id: Uuid, new_subscription: NewCalendarSubscription) -> Result<CalendarSubscription, AppError> {
+        let name = new_subscription.name.trim();
+        if name.is_empty() {
+            return Err(AppError::invalid_field("name", "Subscription name is required"));
+        }
+        let url = Self::subscription_url(&new_subscription.url)?;
+        let now = self.now();
         let id = Uuid::new_v4();
-        
+
         sqlx::query(
-            "INSERT INTO calendars (id, user_id, name, description, color, is_public, created_at, updated_at) VALUES (?, ?, ?, ?, ?, ?, ?, ?)"
+            "INSERT INTO calendar_subscriptions (id, user_id, name, url, color, is_visible, created_at, updated_at) VALUES (?, ?, ?, ?, ?, 1, ?, ?)"
         )
         .bind(id.to_string())
         .bind(user_id.to_string())
-        .bind(&new_calendar.name)
-        .bind(&new_calendar.description)
-        .bind(&new_calendar.color)
-        .bind(new_calendar.is_public)
+        .bind(name)
+        .bind(url)
+        .bind(new_subscription.color)
+        .bind(now)
+        .bind(now)
+        .execute(&self.pool)
+        .await?;
+
+        self.get_subscription_by_id(id).await?
+            .ok_or_else(|| AppError::InternalServerError("Failed to fetch created subscription".to_string()))
+    }
+
+    pub async fn update_subscription(&self, id: Uuid, updates: UpdateCalendarSubscription) -> Result<CalendarSubscription, AppError> {
+        let current = self.get_subscription_by_id(id).await?
+            .ok_or(AppError::NotFoundError("Subscription not found".to_string()))?;
+        let name = match updates.name {
+            Some(name) if name.trim().is_empty() => {
+                return Err(AppError::invalid_field("name", "Subscription name is required"));
+            }
+            Some(name) => name.trim().to_string(),
+            None => current.name,
+        };
+
+        sqlx::query("UPDATE calendar_subscriptions SET name = ?, color = ?, is_visible = ?, updated_at = ? WHERE id = ?")
+            .bind(name)
+            .bind(updates.color.unwrap_or(current.color))
+            .bind(updates.is_visible.unwrap_or(current.is_visible))
+            .bind(self.now())
+            .bind(id.to_string())
+            .execute(&self.pool)
+            .await?;
+
+        self.get_subscription_by_id(id).await?
+            .ok_or(AppError::NotFoundError("Subscription not found".to_string()))
+    }
+
+    pub async fn delete_subscription(&self, id: Uuid) -> Result<(), AppError> {
+        sqlx::query("DELETE FROM calendar_subscriptions WHERE id = ?")
+            .bind(id.to_string())
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    // Contacts
+
+    pub async fn get_contacts_by_user_id(&self, user_id: Uuid) -> Result<Vec<Contact>, AppError> {
+        let contacts = sqlx::query_as::<_, Contact>(
+            "SELECT id, user_id, name, email, birthday, anniversary, created_at, updated_at FROM contacts WHERE user_id = ? ORDER BY name"
+        )
+        .bind(user_id.to_string())
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(contacts)
+    }
+
+    pub async fn get_contact_by_id(&self, id: Uuid) -> Result<Option<Contact>, AppError> {
+        let contact = sqlx::query_as::<_, Contact>(
+            "SELECT id, user_id, name, email, birthday, anniversary, created_at, updated_at FROM contacts WHERE id = ?"
+        )
+        .bind(id.to_string())
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(contact)
+    }
+
+    pub async fn create_contact(&self, user_id: Uuid, new_contact: NewContact) -> Result<Contact, AppError> {
+        let name = new_contact.name.trim();
+        if name.is_empty() {
+            return Err(AppError::invalid_field("name", "Contact name is required"));
+        }
+        let now = self.now();
+        let id = Uuid::new_v4();
+
+        sqlx::query(
+            "INSERT INTO contacts (id, user_id, name, email, birthday, anniversary, created_at, updated_at) VALUES (?, ?, ?, ?, ?, ?, ?, ?)"
+        )
+        .bind(id.to_string())
+        .bind(user_id.to_string())
+        .bind(name)
+        .bind(new_contact.email.filter(|e| !e.trim().is_empty()))
+        .bind(new_contact.birthday)
+        .bind(new_contact.anniversary)
         .bind(now)
         .bind(now)
         .execute(&self.pool)
         .await?;
 
-        // Fetch the calendar back
-        let calendar = self.get_calendar_by_id(id).await?
-            .ok_or_else(|| AppError::InternalServerError("Failed to fetch created calendar".to_string()))?;
+        self.refresh_birthday_calendar(user_id).await?;
+
+        self.get_contact_by_id(id).await?
+            .ok_or_else(|| AppError::InternalServerError("Failed to fetch created contact".to_string()))
+    }
+
+    pub async fn update_contact(&self, id: Uuid, updates: UpdateContact) -> Result<Contact, AppError> {
+        let current = self.get_contact_by_id(id).await?
+            .ok_or(AppError::NotFoundError("Contact not found".to_string()))?;
+        let name = match updates.name {
+            Some(name) if name.trim().is_empty() => {
+                return Err(AppError::invalid_field("name", "Contact name is required"));
+            }
+            Some(name) => name.trim().to_string(),
+            None => current.name,
+        };
+
+        sqlx::query("UPDATE contacts SET name = ?, email = ?, birthday = ?, anniversary = ?, updated_at = ? WHERE id = ?")
+            .bind(name)
+            .bind(updates.email.unwrap_or(current.email).filter(|e| !e.trim().is_empty()))
+            .bind(updates.birthday.unwrap_or(current.birthday))
+            .bind(updates.anniversary.unwrap_or(current.anniversary))
+            .bind(self.now())
+            .bind(id.to_string())
+            .execute(&self.pool)
+            .await?;
+
+        self.refresh_birthday_calendar(current.user_id).await?;
+
+        self.get_contact_by_id(id).await?
+            .ok_or(AppError::NotFoundError("Contact not found".to_string()))
+    }
+
+    pub async fn delete_contact(&self, id: Uuid) -> Result<(), AppError> {
+        let Some(contact) = self.get_contact_by_id(id).await? else {
+            return Ok(());
+        };
+
+        sqlx::query("DELETE FROM contacts WHERE id = ?")
+            .bind(id.to_string())
+            .execute(&self.pool)
+            .await?;
+
+        self.refresh_birthday_calendar(contact.user_id).await
+    }
+
+    /// Regenerate the user's Birthdays calendar from their contacts: a yearly all-day
+    /// event for every birthday and anniversary. The calendar is created with the first
+    /// date and kept once the last one is removed, so clients don't lose the collection.
+    /// Event ids derive from the contact and timestamps are the contact's, so ETags only
+    /// change for contacts that changed.
+    async fn refresh_birthday_calendar(&self, user_id: Uuid) -> Result<(), AppError> {
+        let contacts = self.get_contacts_by_user_id(user_id).await?;
+        let existing = sqlx::query_as::<_, Calendar>(
+            "SELECT id, user_id, name, description, color, is_public, ctag, sort_order, sync_epoch, group_id, default_alarm_minutes, is_birthdays, holiday_pack, is_default, created_at, updated_at FROM calendars WHERE user_id = ? AND is_birthdays = 1"
+        )
+        .bind(user_id.to_string())
+        .fetch_optional(&self.pool)
+        .await?;
+
+        let calendar_id = match existing {
+            Some(calendar) => calendar.id,
+            None if contacts.iter().any(|c| c.birthday.is_some() || c.anniversary.is_some()) => {
+                let id = Uuid::new_v4();
+                let now = self.now();
+                sqlx::query(
+                    "INSERT INTO calendars (id, user_id, name, description, color, is_public, is_birthdays, created_at, updated_at) VALUES (?, ?, ?, ?, ?, 0, 1, ?, ?)"
+                )
+                .bind(id.to_string())
+                .bind(user_id.to_string())
+                .bind(BIRTHDAYS_CALENDAR_NAME)
+                .bind("Birthdays and anniversaries of your contacts")
+                .bind(BIRTHDAYS_CALENDAR_COLOR)
+                .bind(now)
+                .bind(now)
+                .execute(&self.pool)
+                .await?;
+                id
+            }
+            None => return Ok(()),
+        };
+
+        let mut events = Vec::new();
+        for contact in &contacts {
+            for (kind, date) in [("birthday", contact.birthday), ("anniversary", contact.anniversary)] {
+                let Some(date) = date else {
+                    continue;
+                };
+                events.push(GeneratedEvent {
+                    id: Uuid::new_v5(&contact.id, kind.as_bytes()),
+                    title: format!("{}'s {}", contact.name, kind),
+                    date,
+                    yearly: true,
+                    created_at: contact.created_at,
+                    updated_at: contact.updated_at,
+                });
+            }
+        }
+
+        self.replace_generated_events(calendar_id, &events).await
+    }
+
+    /// Replace every event of a generated calendar in one transaction
+    async fn replace_generated_events(&self, calendar_id: Uuid, events: &[GeneratedEvent]) -> Result<(), AppError> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("DELETE FROM events WHERE calendar_id = ?")
+            .bind(calendar_id.to_string())
+            .execute(&mut *tx)
+            .await?;
 
-        Ok(calendar)
+        for event in events {
+            let start = event.date.and_time(chrono::NaiveTime::MIN).and_utc();
+            sqlx::query(
+                "INSERT INTO events (id, calendar_id, title, start_time, end_time, is_all_day, rrule, created_at, updated_at) VALUES (?, ?, ?, ?, ?, 1, ?, ?, ?)"
+            )
+            .bind(event.id.to_string())
+            .bind(calendar_id.to_string())
+            .bind(&event.title)
+            .bind(start)
+            .bind(start + chrono::Duration::days(1))
+            .bind(event.yearly.then_some("FREQ=YEARLY"))
+            .bind(event.created_at)
+            .bind(event.updated_at)
+            .execute(&mut *tx)
+            .await?;
+        }
+        tx.commit().await?;
+
+        self.bump_ctag(calendar_id).await
     }
 
-    pub async fn update_calendar(&self, id: Uuid, updates: UpdateCalendar) -> Result<Calendar, AppError> {
-        let now = Utc::now();
-        
-        if let Some(name) = updates.name {
-            sqlx::query("UPDATE calendars SET name = ?, updated_at = ? WHERE id = ?")
-                .bind(name)
-                .bind(now)
+    // Holiday calendars
+
+    /// Every holiday pack, with the user's calendar for the packs they enabled
+    pub async fn get_holiday_packs(&self, user_id: Uuid) -> Result<Vec<HolidayPackStatus>, AppError> {
+        let calendars = self.get_calendars_by_user_id(user_id).await?;
+
+        Ok(holidays::PACKS.iter()
+            .map(|pack| HolidayPackStatus {
+                id: pack.id.to_string(),
+                name: pack.name.to_string(),
+                calendar_id: calendars.iter()
+                    .find(|c| c.holiday_pack.as_deref() == Some(pack.id))
+                    .map(|c| c.id),
+            })
+            .collect())
+    }
+
+    /// Give the user a read-only calendar of a pack's holidays. Enabling a pack again
+    /// regenerates its calendar for the years around the current one.
+    pub async fn enable_holiday_pack(&self, user_id: Uuid, pack_id: &str) -> Result<Calendar, AppError> {
+        let pack = holidays::find(pack_id)
+            .ok_or(AppError::NotFoundError("Holiday pack not found".to_string()))?;
+        let now = self.now();
+
+        let existing = self.get_calendars_by_user_id(user_id).await?
+            .into_iter()
+            .find(|c| c.holiday_pack.as_deref() == Some(pack.id));
+        let (calendar_id, created_at) = match existing {
+            Some(calendar) => (calendar.id, calendar.created_at),
+            None => {
+                let id = Uuid::new_v4();
+                sqlx::query(
+                    "INSERT INTO calendars (id, user_id, name, description, color, is_public, holiday_pack, created_at, updated_at) VALUES (?, ?, ?, ?, ?, 0, ?, ?, ?)"
+                )
                 .bind(id.to_string())
-                .execute(&self.pool)
-                .await?;
-        }
-        
-        if let Some(description) = updates.description {
-            sqlx::query("UPDATE calendars SET description = ?, updated_at = ? WHERE id = ?")
-                .bind(description)
+                .bind(user_id.to_string())
+                .bind(format!("Holidays ({})", pack.name))
+                .bind("Public holidays, generated by the server")
+                .bind(HOLIDAYS_CALENDAR_COLOR)
+                .bind(pack.id)
                 .bind(now)
-                .bind(id.to_string())
-                .execute(&self.pool)
-                .await?;
-        }
-        
-        if let Some(color) = updates.color {
-            sqlx::query("UPDATE calendars SET color = ?, updated_at = ? WHERE id = ?")
-                .bind(color)
                 .bind(now)
-                .bind(id.to_string())
                 .execute(&self.pool)
                 .await?;
+                (id, now)
+            }
+        };
+
+        let (years_before, years_after) = holidays::HOLIDAY_YEARS;
+        let events: Vec<_> = (now.year() - years_before..=now.year() + years_after)
+            .flat_map(|year| pack.holidays_in(year))
+            .map(|(name, date)| GeneratedEvent {
+                id: Uuid::new_v5(&calendar_id, format!("{} {}", date, name).as_bytes()),
+                title: name.to_string(),
+                date,
+                yearly: false,
+                created_at,
+                updated_at: created_at,
+            })
+            .collect();
+        self.replace_generated_events(calendar_id, &events).await?;
+
+        self.get_calendar_by_id(calendar_id).await?
+            .ok_or_else(|| AppError::InternalServerError("Failed to fetch holiday calendar".to_string()))
+    }
+
+    /// Remove the user's calendar of a holiday pack, if they enabled it
+    pub async fn disable_holiday_pack(&self, user_id: Uuid, pack_id: &str) -> Result<(), AppError> {
+        let calendar = self.get_calendars_by_user_id(user_id).await?
+            .into_iter()
+            .find(|c| c.holiday_pack.as_deref() == Some(pack_id));
+
+        match calendar {
+            Some(calendar) => self.delete_calendar(calendar.id).await,
+            None => Ok(()),
         }
-        
-        if let Some(is_public) = updates.is_public {
-            sqlx::query("UPDATE calendars SET is_public = ?, updated_at = ? WHERE id = ?")
-                .bind(is_public)
-                .bind(now)
-                .bind(id.to_string())
-                .execute(&self.pool)
-                .await?;
+    }
+
+    // Feed tokens
+
+    pub async fn get_feed_token_by_id(&self, id: Uuid) -> Result<Option<FeedToken>, AppError> {
+        let token = sqlx::query_as::<_, FeedToken>(
+            "SELECT id, calendar_id, created_by, label, created_at, last_fetched_at, fetch_count, revoked_at FROM feed_tokens WHERE id = ?"
+        )
+        .bind(id.to_string())
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(token)
+    }
+
+    /// Feed tokens of a calendar, revoked ones included, with their URLs and fetchers
+    pub async fn get_feed_tokens(&self, calendar_id: Uuid) -> Result<Vec<FeedTokenDetails>, AppError> {
+        let tokens = sqlx::query_as::<_, FeedToken>(
+            "SELECT id, calendar_id, created_by, label, created_at, last_fetched_at, fetch_count, revoked_at FROM feed_tokens WHERE calendar_id = ? ORDER BY created_at"
+        )
+        .bind(calendar_id.to_string())
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut details = Vec::with_capacity(tokens.len());
+        for token in tokens {
+            details.push(self.feed_token_details(token).await?);
         }
-        
-        self.get_calendar_by_id(id).await?.ok_or(AppError::NotFoundError("Calendar not found".to_string()))
+        Ok(details)
     }
 
-    pub async fn delete_calendar(&self, id: Uuid) -> Result<(), AppError> {
-        // First delete all events in this calendar
-        sqlx::query("DELETE FROM events WHERE calendar_id = ?")
-            .bind(id.to_string())
-            .execute(&self.pool)
-            .await?;
-        
-        // Then delete all shares for this calendar
-        sqlx::query("DELETE FROM shares WHERE calendar_id = ?")
+    pub async fn feed_token_details(&self, token: FeedToken) -> Result<FeedTokenDetails, AppError> {
+        let clients = sqlx::query_as::<_, FeedTokenClient>(
+            "SELECT ip, first_fetched_at, last_fetched_at, fetch_count FROM feed_token_clients WHERE token_id = ? ORDER BY last_fetched_at DESC"
+        )
+        .bind(token.id.to_string())
+        .fetch_all(&self.pool)
+        .await?;
+        let url = self.url(&format!("/feeds/{}.ics", self.feed_token_value(&token)?));
+
+        Ok(FeedTokenDetails { token, url, clients })
+    }
+
+    pub async fn create_feed_token(&self, calendar_id: Uuid, created_by: Uuid, new_token: NewFeedToken) -> Result<FeedToken, AppError> {
+        let label = new_token.label.trim();
+        if label.is_empty() {
+            return Err(AppError::invalid_field("label", "Feed label is required"));
+        }
+        let id = Uuid::new_v4();
+
+        sqlx::query(
+            "INSERT INTO feed_tokens (id, calendar_id, created_by, label, created_at) VALUES (?, ?, ?, ?, ?)"
+        )
+        .bind(id.to_string())
+        .bind(calendar_id.to_string())
+        .bind(created_by.to_string())
+        .bind(label)
+        .bind(self.now())
+        .execute(&self.pool)
+        .await?;
+
+        self.get_feed_token_by_id(id).await?
+            .ok_or_else(|| AppError::InternalServerError("Failed to fetch created feed token".to_string()))
+    }
+
+    /// Stop a feed link from working. The token is kept so its history stays visible.
+    pub async fn revoke_feed_token(&self, id: Uuid) -> Result<(), AppError> {
+        sqlx::query("UPDATE feed_tokens SET revoked_at = ? WHERE id = ? AND revoked_at IS NULL")
+            .bind(self.now())
             .bind(id.to_string())
             .execute(&self.pool)
             .await?;
-        
-        // Finally delete the calendar
-        sqlx::query("DELETE FROM calendars WHERE id = ?")
-            .bind(id.to_string())
+
+        Ok(())
+    }
+
+    /// Look up the token behind a feed URL; forged, unknown and revoked tokens are not found
+    pub async fn resolve_feed_token(&self, value: &str) -> Result<FeedToken, AppError> {
+        let not_found = || AppError::NotFoundError("Feed not found".to_string());
+        let claims = jsonwebtoken::decode::<FeedClaims>(
+            value,
+            &jsonwebtoken::DecodingKey::from_secret(self.jwt_secret.as_bytes()),
+            &Self::validation_without_exp(),
+        )
+        .map_err(|_| not_found())?
+        .claims;
+        let id = Uuid::parse_str(&claims.sub).map_err(|_| not_found())?;
+
+        self.get_feed_token_by_id(id).await?
+            .filter(|token| token.revoked_at.is_none() && token.calendar_id.to_string() == claims.cal)
+            .ok_or_else(not_found)
+    }
+
+    /// Count a fetch of a feed from `ip`
+    pub async fn record_feed_fetch(&self, token_id: Uuid, ip: &str) -> Result<(), AppError> {
+        let now = self.now();
+
+        sqlx::query("UPDATE feed_tokens SET last_fetched_at = ?, fetch_count = fetch_count + 1 WHERE id = ?")
+            .bind(now)
+            .bind(token_id.to_string())
             .execute(&self.pool)
             .await?;
-        
+
+        sqlx::query(
+            "INSERT INTO feed_token_clients (token_id, ip, first_fetched_at, last_fetched_at) VALUES (?, ?, ?, ?) \
+             ON CONFLICT (token_id, ip) DO UPDATE SET last_fetched_at = excluded.last_fetched_at, fetch_count = fetch_count + 1"
+        )
+        .bind(token_id.to_string())
+        .bind(ip)
+        .bind(now)
+        .bind(now)
+        .execute(&self.pool)
+        .await?;
+
         Ok(())
     }
 
-    // Event operations
-    pub async fn get_event_by_id(&self, id: Uuid) -> Result<Option<Event>, AppError> {
-        let event = sqlx::query_as::<_, Event>(
-            "SELECT id, calendar_id, title, description, location, start_time, end_time, is_all_day, created_at, updated_at FROM events WHERE id = ?"
+    // User feed tokens
+
+    pub async fn get_user_feed_token_by_id(&self, id: Uuid) -> Result<Option<UserFeedToken>, AppError> {
+        let token = sqlx::query_as::<_, UserFeedToken>(
+            "SELECT id, user_id, label, calendar_ids, created_at, last_fetched_at, fetch_count, revoked_at FROM user_feed_tokens WHERE id = ?"
         )
         .bind(id.to_string())
         .fetch_optional(&self.pool)
         .await?;
 
-        Ok(event)
+        Ok(token)
     }
 
-    pub async fn get_events_by_calendar_id(&self, calendar_id: Uuid) -> Result<Vec<Event>, AppError> {
-        let events = sqlx::query_as::<_, Event>(
-            "SELECT id, calendar_id, title, description, location, start_time, end_time, is_all_day, created_at, updated_at FROM events WHERE calendar_id = ?"
+    /// A user's feed tokens, revoked ones included, with their URLs
+    pub async fn get_user_feed_tokens(&self, user_id: Uuid) -> Result<Vec<UserFeedTokenDetails>, AppError> {
+        let tokens = sqlx::query_as::<_, UserFeedToken>(
+            "SELECT id, user_id, label, calendar_ids, created_at, last_fetched_at, fetch_count, revoked_at FROM user_feed_tokens WHERE user_id = ? ORDER BY created_at"
         )
-        .bind(calendar_id.to_string())
+        .bind(user_id.to_string())
         .fetch_all(&self.pool)
         .await?;
 
-        Ok(events)
+        tokens.into_iter().map(|token| self.user_feed_token_details(token)).collect()
     }
 
-    pub async fn create_event(&self, calendar_id: Uuid, new_event: NewEvent) -> Result<Event, AppError> {
-        let now = Utc::now();
+    pub fn user_feed_token_details(&self, token: UserFeedToken) -> Result<UserFeedTokenDetails, AppError> {
+        let url = self.url(&format!("/feeds/{}/all.ics", self.user_feed_token_value(&token)?));
+        Ok(UserFeedTokenDetails { token, url })
+    }
+
+    /// Create a link to a feed merging the given calendars, or all of the user's calendars.
+    /// Only calendars the user owns can be selected.
+    pub async fn create_user_feed_token(&self, user_id: Uuid, new_token: NewUserFeedToken) -> Result<UserFeedToken, AppError> {
+        let label = new_token.label.trim();
+        if label.is_empty() {
+            return Err(AppError::invalid_field("label", "Feed label is required"));
+        }
+
+        let calendar_ids = match new_token.calendar_ids {
+            Some(ids) => {
+                if ids.is_empty() {
+                    return Err(AppError::invalid_field("calendar_ids", "Select at least one calendar"));
+                }
+                let owned: Vec<Uuid> = self.get_calendars_by_user_id(user_id).await?.iter().map(|c| c.id).collect();
+                if let Some(id) = ids.iter().find(|id| !owned.contains(id)) {
+                    return Err(AppError::invalid_field("calendar_ids", format!("Calendar {} is not one of yours", id)));
+                }
+                Some(ids.iter().map(Uuid::to_string).collect::<Vec<_>>().join(","))
+            }
+            None => None,
+        };
         let id = Uuid::new_v4();
-        
+
         sqlx::query(
-            "INSERT INTO events (id, calendar_id, title, description, location, start_time, end_time, is_all_day, created_at, updated_at) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)"
+            "INSERT INTO user_feed_tokens (id, user_id, label, calendar_ids, created_at) VALUES (?, ?, ?, ?, ?)"
         )
         .bind(id.to_string())
-        .bind(calendar_id.to_string())
-        .bind(&new_event.title)
-        .bind(&new_event.description)
-        .bind(&new_event.location)
-        .bind(new_event.start_time)
-        .bind(new_event.end_time)
-        .bind(new_event.is_all_day)
-        .bind(now)
-        .bind(now)
+        .bind(user_id.to_string())
+        .bind(label)
+        .bind(calendar_ids)
+        .bind(self.now())
         .execute(&self.pool)
         .await?;
 
-        // Fetch the event back
-        let event = self.get_event_by_id(id).await?
-            .ok_or_else(|| AppError::InternalServerError("Failed to fetch created event".to_string()))?;
+        self.get_user_feed_token_by_id(id).await?
+            .ok_or_else(|| AppError::InternalServerError("Failed to fetch created feed token".to_string()))
+    }
 
-        Ok(event)
+    pub async fn revoke_user_feed_token(&self, id: Uuid) -> Result<(), AppError> {
+        sqlx::query("UPDATE user_feed_tokens SET revoked_at = ? WHERE id = ? AND revoked_at IS NULL")
+            .bind(self.now())
+            .bind(id.to_string())
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
     }
 
-    pub async fn update_event(&self, id: Uuid, updates: UpdateEvent) -> Result<Event, AppError> {
-        let now = Utc::now();
-        
-        if let Some(title) = updates.title {
-            sqlx::query("UPDATE events SET title = ?, updated_at = ? WHERE id = ?")
-                .bind(title)
-                .bind(now)
-                .bind(id.to_string())
-                .execute(&self.pool)
-                .await?;
-        }
-        
-        if let Some(description) = updates.description {
-            sqlx::query("UPDATE events SET description = ?, updated_at = ? WHERE id = ?")
-                .bind(description)
-                .bind(now)
-                .bind(id.to_string())
-                .execute(&self.pool)
-                .await?;
-        }
-        
-        if let Some(location) = updates.location {
-            sqlx::query("UPDATE events SET location = ?, updated_at = ? WHERE id = ?")
-                .bind(location)
-                .bind(now)
-                .bind(id.to_string())
-                .execute(&self.pool)
-                .await?;
-        }
-        
-        if let Some(start_time) = updates.start_time {
-            sqlx::query("UPDATE events SET start_time = ?, updated_at = ? WHERE id = ?")
-                .bind(start_time)
-                .bind(now)
-                .bind(id.to_string())
-                .execute(&self.pool)
-                .await?;
-        }
-        
-        if let Some(end_time) = updates.end_time {
-            sqlx::query("UPDATE events SET end_time = ?, updated_at = ? WHERE id = ?")
-                .bind(end_time)
-                .bind(now)
-                .bind(id.to_string())
-                .execute(&self.pool)
-                .await?;
-        }
-        
-        if let Some(is_all_day) = updates.is_all_day {
-            sqlx::query("UPDATE events SET is_all_day = ?, updated_at = ? WHERE id = ?")
-                .bind(is_all_day)
-                .bind(now)
-                .bind(id.to_string())
-                .execute(&self.pool)
-                .await?;
-        }
-        
-        self.get_event_by_id(id).await?.ok_or(AppError::NotFoundError("Event not found".to_string()))
+    /// Look up the token behind a user feed URL; forged, unknown and revoked tokens are not found
+    pub async fn resolve_user_feed_token(&self, value: &str) -> Result<UserFeedToken, AppError> {
+        let not_found = || AppError::NotFoundError("Feed not found".to_string());
+        let claims = jsonwebtoken::decode::<UserFeedClaims>(
+            value,
+            &jsonwebtoken::DecodingKey::from_secret(self.jwt_secret.as_bytes()),
+            &Self::validation_without_exp(),
+        )
+        .map_err(|_| not_found())?
+        .claims;
+        let id = Uuid::parse_str(&claims.sub).map_err(|_| not_found())?;
+
+        self.get_user_feed_token_by_id(id).await?
+            .filter(|token| token.revoked_at.is_none() && token.user_id.to_string() == claims.usr)
+            .ok_or_else(not_found)
     }
 
-    pub async fn delete_event(&self, id: Uuid) -> Result<(), AppError> {
-        sqlx::query("DELETE FROM events WHERE id = ?")
-            .bind(id.to_string())
+    pub async fn record_user_feed_fetch(&self, token_id: Uuid) -> Result<(), AppError> {
+        sqlx::query("UPDATE user_feed_tokens SET last_fetched_at = ?, fetch_count = fetch_count + 1 WHERE id = ?")
+            .bind(self.now())
+            .bind(token_id.to_string())
             .execute(&self.pool)
             .await?;
+
         Ok(())
     }
 
-    // Share operations
-    pub async fn get_shares_by_calendar_id(&self, calendar_id: Uuid) -> Result<Vec<Share>, AppError> {
-        let shares = sqlx::query_as::<_, Share>(
-            "SELECT id, calendar_id, user_id, shared_with_user_id, shared_with_email, permission_level, created_at FROM shares WHERE calendar_id = ?"
+    /// Export the calendars of a user feed as one iCalendar file. Every event carries
+    /// its calendar's color so clients can still tell the calendars apart; selected
+    /// calendars that were deleted or given away since are skipped.
+    pub async fn export_user_feed_ics(&self, token: &UserFeedToken) -> Result<String, AppError> {
+        let calendars: Vec<Calendar> = self.get_calendars_by_user_id(token.user_id).await?
+            .into_iter()
+            .filter(|calendar| token.calendar_ids.as_ref().is_none_or(|ids| ids.contains(&calendar.id)))
+            .collect();
+
+        let mut ical_content = format!(
+            "BEGIN:VCALENDAR\r\n\
+             VERSION:2.0\r\n\
+             PRODID:-//My CalDAV Server//EN\r\n\
+             CALSCALE:GREGORIAN\r\n\
+             X-WR-CALNAME:{}\r\n",
+            escape_ical_text(&token.label)
+        );
+
+        for calendar in &calendars {
+            for event in self.get_events_by_calendar_id(calendar.id).await? {
+                let mut ical_event = self.ical_event(&event).await?;
+                ical_event.color = calendar.color.clone();
+                ical_content.push_str(&ical_event.to_ical_string());
+            }
+        }
+
+        ical_content.push_str("END:VCALENDAR\r\n");
+
+        Ok(ical_content)
+    }
+
+    /// Get the highest permission granted to a user by the shares of a calendar,
+    /// matching shares addressed to the user's id or email
+    pub async fn get_share_permission(&self, calendar_id: Uuid, user_id: Uuid) -> Result<Option<PermissionLevel>, AppError> {
+        let levels: Vec<String> = sqlx::query_scalar(
+            "SELECT s.permission_level FROM shares s JOIN users u ON u.id = ? \
+             WHERE s.calendar_id = ? AND (s.shared_with_user_id = u.id OR s.shared_with_email = u.email)"
         )
+        .bind(user_id.to_string())
         .bind(calendar_id.to_string())
         .fetch_all(&self.pool)
         .await?;
 
-        Ok(shares)
+        Ok(levels.iter().filter_map(|l| PermissionLevel::from_str(l)).max())
     }
-    
-    pub async fn get_all_shares(&self) -> Result<Vec<Share>, AppError> {
-        let shares = sqlx::query_as::<_, Share>(
-            "SELECT id, calendar_id, user_id, shared_with_user_id, shared_with_email, permission_level, created_at FROM shares"
+
+    /// Calendars other users have shared with `user_id`, each with the highest
+    /// permission any of its shares grants. The user's own calendars are excluded.
+    pub async fn get_calendars_shared_with_user(&self, user_id: Uuid) -> Result<Vec<(Calendar, PermissionLevel)>, AppError> {
+        let rows = sqlx::query(
+            "SELECT c.id, c.user_id, c.name, c.description, c.color, c.is_public, c.ctag, c.sort_order, c.sync_epoch, c.group_id, c.default_alarm_minutes, c.is_birthdays, c.holiday_pack, c.is_default, c.created_at, c.updated_at, s.permission_level \
+             FROM shares s JOIN calendars c ON c.id = s.calendar_id JOIN users u ON u.id = ? \
+             WHERE (s.shared_with_user_id = u.id OR s.shared_with_email = u.email) AND c.user_id != u.id \
+             ORDER BY c.sort_order, c.created_at"
         )
+        .bind(user_id.to_string())
         .fetch_all(&self.pool)
         .await?;
 
-        Ok(shares)
-    }
+        let mut shared: Vec<(Calendar, PermissionLevel)> = Vec::new();
+        for row in &rows {
+            let calendar = Calendar::from_row(row)?;
+            let Some(level) = PermissionLevel::from_str(&row.try_get::<String, _>("permission_level")?) else {
+                continue;
+            };
+            match shared.iter_mut().find(|(c, _)| c.id == calendar.id) {
+                Some((_, existing)) => *existing = (*existing).max(level),
+                None => shared.push((calendar, level)),
+            }
+        }
 
-    pub async fn create_share(&self, calendar_id: Uuid, user_id: Uuid, new_share: NewShare) -> Result<Share, AppError> {
-        let now = Utc::now();
-        let id = Uuid::new_v4();
-        
-        // Try to find user by email
-        let shared_with_user = self.get_user_by_email(&new_share.shared_with_email).await?;
-        
-        sqlx::query(
-            "INSERT INTO shares (id, calendar_id, user_id, shared_with_user_id, shared_with_email, permission_level, created_at) VALUES (?, ?, ?, ?, ?, ?, ?)"
+        // Calendars of the user's groups, at the access their role grants
+        let rows = sqlx::query(
+            "SELECT c.id, c.user_id, c.name, c.description, c.color, c.is_public, c.ctag, c.sort_order, c.sync_epoch, c.group_id, c.default_alarm_minutes, c.is_birthdays, c.holiday_pack, c.is_default, c.created_at, c.updated_at, m.role \
+             FROM group_members m JOIN calendars c ON c.group_id = m.group_id \
+             WHERE m.user_id = ? AND c.user_id != m.user_id \
+             ORDER BY c.sort_order, c.created_at"
         )
-        .bind(id.to_string())
-        .bind(calendar_id.to_string())
         .bind(user_id.to_string())
-        .bind(shared_with_user.as_ref().map(|u| u.id.to_string()))
-        .bind(&new_share.shared_with_email)
-        .bind(&new_share.permission)
-        .bind(now)
-        .execute(&self.pool)
+        .fetch_all(&self.pool)
         .await?;
 
-        // Fetch the share back
-        let share = sqlx::query_as::<_, Share>(
-            "SELECT id, calendar_id, user_id, shared_with_user_id, shared_with_email, permission_level, created_at FROM shares WHERE id = ?"
+        for row in &rows {
+            let calendar = Calendar::from_row(row)?;
+            let level = GroupRole::from_str(&row.try_get::<String, _>("role")?).permission();
+            match shared.iter_mut().find(|(c, _)| c.id == calendar.id) {
+                Some((_, existing)) => *existing = (*existing).max(level),
+                None => shared.push((calendar, level)),
+            }
+        }
+
+        // Personal calendars of users who delegated to this one
+        let rows = sqlx::query(
+            "SELECT c.id, c.user_id, c.name, c.description, c.color, c.is_public, c.ctag, c.sort_order, c.sync_epoch, c.group_id, c.default_alarm_minutes, c.is_birthdays, c.holiday_pack, c.is_default, c.created_at, c.updated_at, d.permission_level \
+             FROM delegations d JOIN calendars c ON c.user_id = d.owner_id \
+             WHERE d.delegate_id = ? AND c.group_id IS NULL AND c.is_birthdays = 0 AND c.holiday_pack IS NULL \
+             ORDER BY c.sort_order, c.created_at"
         )
-        .bind(id.to_string())
-        .fetch_optional(&self.pool)
-        .await?
-        .ok_or_else(|| AppError::InternalServerError("Failed to fetch created share".to_string()))?;
+        .bind(user_id.to_string())
+        .fetch_all(&self.pool)
+        .await?;
 
-        Ok(share)
+        for row in &rows {
+            let calendar = Calendar::from_row(row)?;
+            let Some(level) = PermissionLevel::from_str(&row.try_get::<String, _>("permission_level")?) else {
+                continue;
+            };
+            match shared.iter_mut().find(|(c, _)| c.id == calendar.id) {
+                Some((_, existing)) => *existing = (*existing).max(level),
+                None => shared.push((calendar, level)),
+            }
+        }
+
+        Ok(shared)
     }
 
     pub async fn delete_share(&self, id: Uuid) -> Result<(), AppError> {
@@ -0,0 +1,400 @@
+//! Instance settings stored in the database, written by the first-run setup wizard,
+//! and each user's own web UI settings
+
+use std::collections::HashMap;
+
+use chrono::{DateTime, NaiveTime, Utc, Weekday};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+
+use crate::i18n::Language;
+use crate::models::double_option;
+
+/// Instance name shown until the wizard sets one
+pub const DEFAULT_INSTANCE_NAME: &str = "My CalDAV Server";
+
+const KEY_INSTANCE_NAME: &str = "instance_name";
+const KEY_BASE_URL: &str = "base_url";
+const KEY_REGISTRATION: &str = "registration";
+const KEY_SMTP_HOST: &str = "smtp_host";
+const KEY_SMTP_PORT: &str = "smtp_port";
+const KEY_SMTP_USERNAME: &str = "smtp_username";
+const KEY_SMTP_PASSWORD: &str = "smtp_password";
+const KEY_SMTP_FROM: &str = "smtp_from";
+
+const KEY_THEME: &str = "theme";
+const KEY_LANGUAGE: &str = "language";
+const KEY_TIMEZONE: &str = "timezone";
+const KEY_TIME_FORMAT: &str = "time_format";
+const KEY_WEEK_START: &str = "week_start";
+const KEY_WORK_START: &str = "work_start";
+const KEY_WORK_END: &str = "work_end";
+const KEY_WORK_DAYS: &str = "work_days";
+const KEY_WORK_FREE_BUSY: &str = "work_free_busy";
+const KEY_SYNC_LOG: &str = "sync_log";
+
+/// Cookie remembering the theme, for pages shown before signing in
+pub const THEME_COOKIE: &str = "theme";
+
+/// Default SMTP submission port
+pub const DEFAULT_SMTP_PORT: u16 = 587;
+
+/// Who may create accounts through the registration endpoints
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RegistrationPolicy {
+    /// Anyone can register
+    #[default]
+    Open,
+    /// Only admins create accounts
+    Closed,
+}
+
+impl RegistrationPolicy {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RegistrationPolicy::Open => "open",
+            RegistrationPolicy::Closed => "closed",
+        }
+    }
+
+    pub fn from_str(s: &str) -> Self {
+        match s {
+            "closed" => RegistrationPolicy::Closed,
+            _ => RegistrationPolicy::Open,
+        }
+    }
+}
+
+/// Outgoing mail server
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SmtpSettings {
+    pub host: String,
+    pub port: u16,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    /// Sender address of outgoing mail
+    pub from_address: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct InstanceSettings {
+    pub instance_name: String,
+    /// Public URL of the server; the `BASE_URL` environment variable takes precedence
+    pub base_url: String,
+    pub registration: RegistrationPolicy,
+    pub smtp: Option<SmtpSettings>,
+}
+
+impl Default for InstanceSettings {
+    fn default() -> Self {
+        Self {
+            instance_name: DEFAULT_INSTANCE_NAME.to_string(),
+            base_url: String::new(),
+            registration: RegistrationPolicy::default(),
+            smtp: None,
+        }
+    }
+}
+
+impl InstanceSettings {
+    /// Read settings from the rows of the settings table; missing keys keep their defaults
+    pub fn from_pairs(pairs: Vec<(String, String)>) -> Self {
+        let values: HashMap<String, String> = pairs.into_iter().collect();
+        let get = |key: &str| values.get(key).filter(|v| !v.is_empty()).cloned();
+
+        let smtp = get(KEY_SMTP_HOST).map(|host| SmtpSettings {
+            host,
+            port: get(KEY_SMTP_PORT).and_then(|p| p.parse().ok()).unwrap_or(DEFAULT_SMTP_PORT),
+            username: get(KEY_SMTP_USERNAME),
+            password: get(KEY_SMTP_PASSWORD),
+            from_address: get(KEY_SMTP_FROM).unwrap_or_default(),
+        });
+
+        Self {
+            instance_name: get(KEY_INSTANCE_NAME).unwrap_or_else(|| DEFAULT_INSTANCE_NAME.to_string()),
+            base_url: get(KEY_BASE_URL).unwrap_or_default(),
+            registration: get(KEY_REGISTRATION)
+                .map(|r| RegistrationPolicy::from_str(&r))
+                .unwrap_or_default(),
+            smtp,
+        }
+    }
+
+    /// Rows to store in the settings table. SMTP keys are always written so that
+    /// removing the mail server clears them.
+    pub fn to_pairs(&self) -> Vec<(&'static str, String)> {
+        let smtp = self.smtp.as_ref();
+        vec![
+            (KEY_INSTANCE_NAME, self.instance_name.clone()),
+            (KEY_BASE_URL, self.base_url.clone()),
+            (KEY_REGISTRATION, self.registration.as_str().to_string()),
+            (KEY_SMTP_HOST, smtp.map(|s| s.host.clone()).unwrap_or_default()),
+            (KEY_SMTP_PORT, smtp.map(|s| s.port.to_string()).unwrap_or_default()),
+            (KEY_SMTP_USERNAME, smtp.and_then(|s| s.username.clone()).unwrap_or_default()),
+            (KEY_SMTP_PASSWORD, smtp.and_then(|s| s.password.clone()).unwrap_or_default()),
+            (KEY_SMTP_FROM, smtp.map(|s| s.from_address.clone()).unwrap_or_default()),
+        ]
+    }
+}
+
+/// Settings pinned by the configuration file or environment. They take precedence over
+/// the values stored by the setup wizard.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SettingsOverrides {
+    pub registration: Option<RegistrationPolicy>,
+    pub smtp: Option<SmtpSettings>,
+}
+
+impl SettingsOverrides {
+    pub fn apply(&self, settings: &mut InstanceSettings) {
+        if let Some(registration) = self.registration {
+            settings.registration = registration;
+        }
+        if let Some(smtp) = &self.smtp {
+            settings.smtp = Some(smtp.clone());
+        }
+    }
+}
+
+/// Color theme of the web UI
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Theme {
+    /// Light or dark, following the operating system
+    #[default]
+    System,
+    Light,
+    Dark,
+}
+
+impl Theme {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Theme::System => "system",
+            Theme::Light => "light",
+            Theme::Dark => "dark",
+        }
+    }
+
+    pub fn from_str(s: &str) -> Self {
+        match s {
+            "light" => Theme::Light,
+            "dark" => Theme::Dark,
+            _ => Theme::System,
+        }
+    }
+}
+
+/// Clock used to show times of day
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TimeFormat {
+    #[default]
+    #[serde(rename = "24h")]
+    H24,
+    #[serde(rename = "12h")]
+    H12,
+}
+
+impl TimeFormat {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TimeFormat::H24 => "24h",
+            TimeFormat::H12 => "12h",
+        }
+    }
+
+    pub fn from_str(s: &str) -> Self {
+        match s {
+            "12h" => TimeFormat::H12,
+            _ => TimeFormat::H24,
+        }
+    }
+
+    /// `strftime` pattern of a time of day
+    pub fn pattern(&self) -> &'static str {
+        match self {
+            TimeFormat::H24 => "%H:%M",
+            TimeFormat::H12 => "%-I:%M %p",
+        }
+    }
+}
+
+/// Hours of the week a user is usually available, on their own clock
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WorkingHours {
+    pub start: NaiveTime,
+    pub end: NaiveTime,
+    pub days: Vec<Weekday>,
+    /// Whether free-busy lookups report the time outside these hours as unavailable
+    #[serde(default)]
+    pub free_busy: bool,
+}
+
+impl Default for WorkingHours {
+    /// 9 to 5, Monday to Friday
+    fn default() -> Self {
+        Self {
+            start: NaiveTime::from_hms_opt(9, 0, 0).unwrap_or_default(),
+            end: NaiveTime::from_hms_opt(17, 0, 0).unwrap_or_default(),
+            days: vec![Weekday::Mon, Weekday::Tue, Weekday::Wed, Weekday::Thu, Weekday::Fri],
+            free_busy: false,
+        }
+    }
+}
+
+impl WorkingHours {
+    /// Read working hours from a user's settings rows; missing or broken values keep
+    /// their defaults
+    fn from_values(values: &HashMap<String, String>) -> Self {
+        let defaults = Self::default();
+        let time = |key| values.get(key).and_then(|t: &String| NaiveTime::parse_from_str(t, "%H:%M").ok());
+        Self {
+            start: time(KEY_WORK_START).unwrap_or(defaults.start),
+            end: time(KEY_WORK_END).unwrap_or(defaults.end),
+            days: values.get(KEY_WORK_DAYS)
+                .map(|days| days.split(',').filter_map(|d| d.parse().ok()).collect())
+                .unwrap_or(defaults.days),
+            free_busy: values.get(KEY_WORK_FREE_BUSY).is_some_and(|v| v == "true"),
+        }
+    }
+
+    pub fn is_working_day(&self, day: Weekday) -> bool {
+        self.days.contains(&day)
+    }
+
+    /// Whether the hour starting at `hour` overlaps working hours on `day`
+    pub fn covers_hour(&self, day: Weekday, hour: u32) -> bool {
+        let Some(from) = NaiveTime::from_hms_opt(hour, 0, 0) else {
+            return false;
+        };
+        let until = from + chrono::Duration::hours(1);
+        self.is_working_day(day) && from < self.end && (until > self.start || until == NaiveTime::MIN)
+    }
+}
+
+/// A user's own settings for the web UI
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UserSettings {
+    pub theme: Theme,
+    /// Language of the web UI and of mails to the user; `None` follows the browser
+    pub language: Option<Language>,
+    /// Time zone times are shown in; UTC unless detected at registration or chosen
+    pub timezone: Tz,
+    pub time_format: TimeFormat,
+    /// First day of the week in the calendar grid
+    pub week_start: Weekday,
+    pub working_hours: WorkingHours,
+    /// Whether the user's CalDAV requests are kept in their sync log
+    pub sync_log: bool,
+}
+
+impl Default for UserSettings {
+    fn default() -> Self {
+        Self {
+            theme: Theme::default(),
+            language: None,
+            timezone: Tz::default(),
+            time_format: TimeFormat::default(),
+            week_start: Weekday::Mon,
+            working_hours: WorkingHours::default(),
+            sync_log: false,
+        }
+    }
+}
+
+impl UserSettings {
+    /// Read settings from the user's rows of the user_settings table; missing keys keep
+    /// their defaults
+    pub fn from_pairs(pairs: Vec<(String, String)>) -> Self {
+        let values: HashMap<String, String> = pairs.into_iter().collect();
+
+        Self {
+            theme: values.get(KEY_THEME).map(|t| Theme::from_str(t)).unwrap_or_default(),
+            language: values.get(KEY_LANGUAGE).and_then(|l| Language::from_code(l)),
+            timezone: values.get(KEY_TIMEZONE).and_then(|tz| tz.parse().ok()).unwrap_or_default(),
+            time_format: values.get(KEY_TIME_FORMAT).map(|f| TimeFormat::from_str(f)).unwrap_or_default(),
+            week_start: values.get(KEY_WEEK_START).and_then(|d| d.parse().ok()).unwrap_or(Weekday::Mon),
+            working_hours: WorkingHours::from_values(&values),
+            sync_log: values.get(KEY_SYNC_LOG).is_some_and(|v| v == "true"),
+        }
+    }
+
+    /// Rows to store in the user_settings table
+    pub fn to_pairs(&self) -> Vec<(&'static str, String)> {
+        vec![
+            (KEY_THEME, self.theme.as_str().to_string()),
+            (KEY_LANGUAGE, self.language.map(|l| l.as_str().to_string()).unwrap_or_default()),
+            (KEY_TIMEZONE, self.timezone.name().to_string()),
+            (KEY_TIME_FORMAT, self.time_format.as_str().to_string()),
+            (KEY_WEEK_START, self.week_start.to_string()),
+            (KEY_WORK_START, self.working_hours.start.format("%H:%M").to_string()),
+            (KEY_WORK_END, self.working_hours.end.format("%H:%M").to_string()),
+            (KEY_WORK_DAYS, self.working_hours.days.iter().map(Weekday::to_string).collect::<Vec<_>>().join(",")),
+            (KEY_WORK_FREE_BUSY, self.working_hours.free_busy.to_string()),
+            (KEY_SYNC_LOG, self.sync_log.to_string()),
+        ]
+    }
+
+    pub fn apply(&mut self, update: UpdateUserSettings) {
+        if let Some(theme) = update.theme {
+            self.theme = theme;
+        }
+        if let Some(language) = update.language {
+            self.language = language;
+        }
+        if let Some(timezone) = update.timezone {
+            self.timezone = timezone;
+        }
+        if let Some(time_format) = update.time_format {
+            self.time_format = time_format;
+        }
+        if let Some(week_start) = update.week_start {
+            self.week_start = week_start;
+        }
+        if let Some(working_hours) = update.working_hours {
+            self.working_hours = working_hours;
+        }
+        if let Some(sync_log) = update.sync_log {
+            self.sync_log = sync_log;
+        }
+    }
+
+    /// The seven weekdays in grid order, starting with [`Self::week_start`]
+    pub fn weekdays(&self) -> Vec<Weekday> {
+        std::iter::successors(Some(self.week_start), |day| Some(day.succ())).take(7).collect()
+    }
+
+    /// `time` on the user's clock
+    pub fn local_time(&self, time: DateTime<Utc>) -> DateTime<Tz> {
+        time.with_timezone(&self.timezone)
+    }
+
+    /// Date and time of day on the user's clock, e.g. `2030-05-02 14:30`
+    pub fn format_date_time(&self, time: DateTime<Utc>) -> String {
+        self.local_time(time).format(&format!("%Y-%m-%d {}", self.time_format.pattern())).to_string()
+    }
+
+    /// [`Self::format_date_time`] followed by the zone's abbreviation, for times shown
+    /// away from a calendar
+    pub fn format_timestamp(&self, time: DateTime<Utc>) -> String {
+        self.local_time(time).format(&format!("%Y-%m-%d {} %Z", self.time_format.pattern())).to_string()
+    }
+}
+
+/// Changes to a user's settings; fields left out are kept
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct UpdateUserSettings {
+    pub theme: Option<Theme>,
+    /// `Some(None)` goes back to following the browser
+    #[serde(default, deserialize_with = "double_option", skip_serializing_if = "Option::is_none")]
+    pub language: Option<Option<Language>>,
+    /// IANA name such as `Europe/Berlin`
+    pub timezone: Option<Tz>,
+    pub time_format: Option<TimeFormat>,
+    /// `Mon`, `Sun`, ...
+    pub week_start: Option<Weekday>,
+    pub working_hours: Option<WorkingHours>,
+    /// Turning the sync log off deletes it
+    pub sync_log: Option<bool>,
+}
@@ -0,0 +1,101 @@
+//! Local file storage for event attachments
+
+use std::path::PathBuf;
+use std::time::SystemTime;
+use uuid::Uuid;
+
+use crate::error::AppError;
+
+/// Default directory attachment files are stored in
+pub const DEFAULT_ATTACHMENTS_DIR: &str = "./data/attachments";
+
+/// Default maximum size of a single attachment (10 MiB)
+pub const DEFAULT_MAX_ATTACHMENT_SIZE: usize = 10 * 1024 * 1024;
+
+/// Stores attachment contents as files named after the attachment id
+#[derive(Debug, Clone)]
+pub struct AttachmentStorage {
+    root: PathBuf,
+    max_size: usize,
+}
+
+impl Default for AttachmentStorage {
+    fn default() -> Self {
+        Self::new(DEFAULT_ATTACHMENTS_DIR, DEFAULT_MAX_ATTACHMENT_SIZE)
+    }
+}
+
+impl AttachmentStorage {
+    pub fn new(root: impl Into<PathBuf>, max_size: usize) -> Self {
+        Self {
+            root: root.into(),
+            max_size,
+        }
+    }
+
+    pub fn max_size(&self) -> usize {
+        self.max_size
+    }
+
+    fn path(&self, id: Uuid) -> PathBuf {
+        self.root.join(id.to_string())
+    }
+
+    /// Write the contents of an attachment, rejecting files above the size limit
+    pub async fn save(&self, id: Uuid, data: &[u8]) -> Result<(), AppError> {
+        if data.len() > self.max_size {
+            return Err(AppError::ValidationError(format!(
+                "Attachment exceeds the maximum size of {} bytes",
+                self.max_size
+            )));
+        }
+
+        tokio::fs::create_dir_all(&self.root).await.map_err(storage_error)?;
+        tokio::fs::write(self.path(id), data).await.map_err(storage_error)
+    }
+
+    /// Read the contents of an attachment
+    pub async fn load(&self, id: Uuid) -> Result<Vec<u8>, AppError> {
+        match tokio::fs::read(self.path(id)).await {
+            Ok(data) => Ok(data),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                Err(AppError::NotFoundError("Attachment file not found".to_string()))
+            }
+            Err(e) => Err(storage_error(e)),
+        }
+    }
+
+    /// Ids of the stored files last written before `cutoff`; files not named after an
+    /// attachment id are left out
+    pub async fn stored_before(&self, cutoff: SystemTime) -> Result<Vec<Uuid>, AppError> {
+        let mut entries = match tokio::fs::read_dir(&self.root).await {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(storage_error(e)),
+        };
+
+        let mut ids = Vec::new();
+        while let Some(entry) = entries.next_entry().await.map_err(storage_error)? {
+            let Some(id) = entry.file_name().to_str().and_then(|name| Uuid::parse_str(name).ok()) else {
+                continue;
+            };
+            let modified = entry.metadata().await.and_then(|metadata| metadata.modified()).map_err(storage_error)?;
+            if modified < cutoff {
+                ids.push(id);
+            }
+        }
+        Ok(ids)
+    }
+
+    /// Delete the contents of an attachment; missing files are ignored
+    pub async fn remove(&self, id: Uuid) -> Result<(), AppError> {
+        match tokio::fs::remove_file(self.path(id)).await {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(storage_error(e)),
+            _ => Ok(()),
+        }
+    }
+}
+
+fn storage_error(e: std::io::Error) -> AppError {
+    AppError::InternalServerError(format!("Attachment storage error: {}", e))
+}
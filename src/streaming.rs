@@ -0,0 +1,86 @@
+//! Response bodies generated while they are sent
+//!
+//! Large REPORTs and calendar exports are written by a task that reads the calendar a
+//! page at a time and sends the rendered chunks through a bounded channel, so memory
+//! stays flat however many events a calendar holds. The status line is sent before the
+//! body is generated: a failure halfway aborts the body, which clients see as a broken
+//! download and retry.
+
+use std::future::Future;
+
+use axum::body::Body;
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::response::Response;
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+
+use crate::error::AppError;
+use crate::etag;
+use crate::models::Calendar;
+use crate::services::CalendarService;
+
+/// Events read from the database at a time
+pub const PAGE_SIZE: i64 = 500;
+
+/// Chunks rendered ahead of the client before the producer waits
+const BUFFERED_CHUNKS: usize = 8;
+
+/// Where a streamed body's producer sends its chunks
+pub struct Chunks {
+    sender: mpsc::Sender<Result<String, std::io::Error>>,
+}
+
+impl Chunks {
+    /// Send the next part of the body; fails once the client has gone away
+    pub async fn send(&self, chunk: String) -> Result<(), AppError> {
+        if chunk.is_empty() {
+            return Ok(());
+        }
+        self.sender.send(Ok(chunk)).await
+            .map_err(|_| AppError::InternalServerError("Client closed the connection".to_string()))
+    }
+}
+
+/// Body whose chunks `produce` sends as it renders them
+pub fn body<F, Fut>(produce: F) -> Body
+where
+    F: FnOnce(Chunks) -> Fut,
+    Fut: Future<Output = Result<(), AppError>> + Send + 'static,
+{
+    let (sender, receiver) = mpsc::channel(BUFFERED_CHUNKS);
+    let producer = produce(Chunks { sender: sender.clone() });
+    tokio::spawn(async move {
+        if let Err(e) = producer.await {
+            if sender.is_closed() {
+                tracing::debug!("Streamed response abandoned by the client");
+            } else {
+                tracing::error!("Streamed response failed: {}", e);
+                let _ = sender.send(Err(std::io::Error::other(e.to_string()))).await;
+            }
+        }
+    });
+    Body::from_stream(ReceiverStream::new(receiver))
+}
+
+/// A whole calendar as one iCalendar file, streamed, or 304 if the client's copy is
+/// current. Journal entries are only included with `journals`.
+pub fn calendar_ics_response(service: &CalendarService, headers: &HeaderMap, calendar: Calendar, journals: bool) -> Response {
+    let etag = etag::calendar_etag(&calendar);
+    if etag::not_modified(headers, &etag) {
+        return etag::not_modified_response(&etag);
+    }
+    Response::builder()
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, "text/calendar; charset=utf-8")
+        .header(header::ETAG, etag)
+        .body(calendar_ics(service, calendar, journals))
+        .unwrap()
+}
+
+/// Body of a whole calendar as one iCalendar file, written a page of events at a time
+pub fn calendar_ics(service: &CalendarService, calendar: Calendar, journals: bool) -> Body {
+    let service = service.clone();
+    body(move |chunks| async move {
+        service.write_calendar_ics(&calendar, journals, &chunks).await
+    })
+}
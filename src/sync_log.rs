@@ -0,0 +1,128 @@
+//! Opt-in log of a user's CalDAV requests, for finding out why a device doesn't sync
+//!
+//! Users turn the log on in their settings. From then on every CalDAV request made with
+//! their account is kept with its method, path, status, client and duration, along with
+//! the error the client was given. Requests refused because the password was wrong are
+//! logged too, under the account the client tried to sign in to. Only the newest
+//! [`MAX_ENTRIES_PER_USER`] requests are kept; turning the log off deletes it.
+
+use std::collections::HashMap;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, Row};
+use uuid::Uuid;
+
+/// Requests kept per user; older ones are deleted as new ones arrive
+pub const MAX_ENTRIES_PER_USER: usize = 200;
+
+/// Longest user agent stored, in characters
+const MAX_USER_AGENT_LENGTH: usize = 255;
+
+/// CalDAV request as it is logged
+#[derive(Debug, Clone)]
+pub struct NewSyncLogEntry {
+    pub method: String,
+    pub path: String,
+    pub status: u16,
+    pub user_agent: Option<String>,
+    pub duration_ms: i64,
+    pub error: Option<String>,
+}
+
+impl NewSyncLogEntry {
+    /// Entry of a request, with the user agent cut to a sensible length
+    pub fn new(method: &str, path: &str, status: u16, user_agent: Option<&str>, duration_ms: i64, error: Option<String>) -> Self {
+        Self {
+            method: method.to_string(),
+            path: path.to_string(),
+            status,
+            user_agent: user_agent.map(|agent| agent.chars().take(MAX_USER_AGENT_LENGTH).collect()),
+            duration_ms,
+            error,
+        }
+    }
+}
+
+/// Logged CalDAV request
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SyncLogEntry {
+    pub id: Uuid,
+    pub method: String,
+    pub path: String,
+    pub status: u16,
+    pub user_agent: Option<String>,
+    pub duration_ms: i64,
+    /// Message of the error response, for requests that failed with one
+    pub error: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl SyncLogEntry {
+    pub fn is_failure(&self) -> bool {
+        self.status >= 400
+    }
+
+    /// Name the client is listed under
+    pub fn client(&self) -> &str {
+        self.user_agent.as_deref().filter(|agent| !agent.is_empty()).unwrap_or("Unknown client")
+    }
+}
+
+impl FromRow<'_, sqlx::sqlite::SqliteRow> for SyncLogEntry {
+    fn from_row(row: &'_ sqlx::sqlite::SqliteRow) -> Result<Self, sqlx::Error> {
+        let id: String = row.try_get("id")?;
+        let id = Uuid::parse_str(&id).map_err(|e| sqlx::Error::ColumnDecode {
+            index: "id".to_string(),
+            source: Box::new(e),
+        })?;
+        let status: i64 = row.try_get("status")?;
+
+        Ok(SyncLogEntry {
+            id,
+            method: row.try_get("method")?,
+            path: row.try_get("path")?,
+            status: status as u16,
+            user_agent: row.try_get("user_agent")?,
+            duration_ms: row.try_get("duration_ms")?,
+            error: row.try_get("error")?,
+            created_at: row.try_get("created_at")?,
+        })
+    }
+}
+
+/// Requests of one client in the log
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SyncLogClient {
+    pub name: String,
+    pub requests: usize,
+    pub failures: usize,
+    pub last_seen: DateTime<Utc>,
+    pub last_status: u16,
+}
+
+/// The clients in a log, most recently seen first; `entries` are newest first
+pub fn clients(entries: &[SyncLogEntry]) -> Vec<SyncLogClient> {
+    let mut clients: Vec<SyncLogClient> = Vec::new();
+    let mut positions: HashMap<&str, usize> = HashMap::new();
+
+    for entry in entries {
+        let position = *positions.entry(entry.client()).or_insert_with(|| {
+            clients.push(SyncLogClient {
+                name: entry.client().to_string(),
+                requests: 0,
+                failures: 0,
+                last_seen: entry.created_at,
+                last_status: entry.status,
+            });
+            clients.len() - 1
+        });
+        let client = &mut clients[position];
+        client.requests += 1;
+        if entry.is_failure() {
+            client.failures += 1;
+        }
+    }
+
+    clients
+}
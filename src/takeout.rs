@@ -0,0 +1,377 @@
+//! Migration from Google Calendar through a Google Takeout archive
+//!
+//! Takeout exports every calendar as an `.ics` file in a zip archive
+//! (`Takeout/Calendar/<name>.ics`). Each file becomes a new calendar named after its
+//! `X-WR-CALNAME`, in the color the file gives, if any, or the next one of [`COLORS`].
+//! Events keep their UID, recurrence rule and first reminder. Instances Google stores as
+//! separate events (with a RECURRENCE-ID) become overrides of their series; cancelled
+//! instances and EXDATEs are excluded from it. Fetching calendars from Google directly
+//! would need an OAuth client registered for every instance, so the archive is the way in.
+
+use std::collections::{BTreeMap, HashMap};
+use std::io::Read;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::error::AppError;
+use crate::handlers::{parse_ical_time, parse_ical_times, parse_icalendar, unescape_ical_text};
+use crate::import::{self, ComponentReader};
+use crate::models::{ComponentImportCount, NewCalendar, NewEvent, UpdateEvent, UpdateOccurrence};
+use crate::services::CalendarService;
+use crate::validation::{self, Validate};
+
+/// Largest accepted archive, and largest calendar file in it
+pub const MAX_ARCHIVE_SIZE: usize = 64 * 1024 * 1024;
+
+/// Colors given in turn to calendars whose file names none
+pub const COLORS: [&str; 6] = ["#3B82F6", "#10B981", "#F59E0B", "#EF4444", "#8B5CF6", "#EC4899"];
+
+/// Outcome of a Takeout import
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TakeoutSummary {
+    pub calendars: Vec<CalendarSummary>,
+    /// Files of the archive that aren't calendars
+    pub skipped_files: Vec<String>,
+}
+
+/// Outcome of importing one calendar file
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct CalendarSummary {
+    /// Path of the file in the archive
+    pub file: String,
+    pub name: String,
+    /// The calendar created for the file; `None` if it couldn't be created
+    pub calendar_id: Option<Uuid>,
+    pub color: String,
+    /// Events, instance overrides and cancelled instances of the file
+    pub events: ComponentImportCount,
+    /// Components other than events, with their counts
+    pub skipped: BTreeMap<String, usize>,
+    /// Reasons for failed events, naming them by title
+    pub errors: Vec<String>,
+}
+
+impl CalendarSummary {
+    fn fail(&mut self, item: &str, e: impl std::fmt::Display) {
+        self.events.failed += 1;
+        if self.errors.len() < import::MAX_REPORTED_ERRORS {
+            self.errors.push(format!("{}: {}", item, e));
+        }
+    }
+}
+
+/// Calendar file read from the archive
+#[derive(Debug, Clone)]
+pub struct CalendarFile {
+    pub path: String,
+    pub data: String,
+}
+
+/// The calendar files of a Takeout archive, and the paths of the other files in it
+pub fn read_archive(data: &[u8]) -> Result<(Vec<CalendarFile>, Vec<String>), AppError> {
+    let invalid = |e: zip::result::ZipError| AppError::ValidationError(format!("Expected a Google Takeout (.zip) archive: {}", e));
+    let mut archive = zip::ZipArchive::new(std::io::Cursor::new(data)).map_err(invalid)?;
+    let mut calendars = Vec::new();
+    let mut skipped = Vec::new();
+
+    for index in 0..archive.len() {
+        let entry = archive.by_index(index).map_err(invalid)?;
+        if entry.is_dir() {
+            continue;
+        }
+        let path = entry.name().to_string();
+        if !path.to_ascii_lowercase().ends_with(".ics") {
+            skipped.push(path);
+            continue;
+        }
+
+        // The size in the header can't be trusted, so stop reading past the limit
+        let mut bytes = Vec::new();
+        entry.take(MAX_ARCHIVE_SIZE as u64 + 1).read_to_end(&mut bytes)
+            .map_err(|e| AppError::ValidationError(format!("Failed to read {}: {}", path, e)))?;
+        if bytes.len() > MAX_ARCHIVE_SIZE {
+            return Err(AppError::ValidationError(format!("{} is too large", path)));
+        }
+        calendars.push(CalendarFile { path, data: String::from_utf8_lossy(&bytes).into_owned() });
+    }
+
+    Ok((calendars, skipped))
+}
+
+/// Import every calendar of a Takeout archive into new calendars of `user_id`
+pub async fn import(service: &CalendarService, user_id: Uuid, archive: &[u8]) -> Result<TakeoutSummary, AppError> {
+    let (files, skipped_files) = read_archive(archive)?;
+    if files.is_empty() {
+        return Err(AppError::ValidationError("The archive has no calendars; export Calendar in Google Takeout".to_string()));
+    }
+
+    let mut summary = TakeoutSummary { calendars: Vec::new(), skipped_files };
+    for (index, file) in files.iter().enumerate() {
+        let calendar = import_calendar(service, user_id, file, COLORS[index % COLORS.len()]).await;
+        tracing::info!(%user_id, file = %file.path, imported = calendar.events.imported, failed = calendar.events.failed, "Takeout calendar imported");
+        summary.calendars.push(calendar);
+    }
+    Ok(summary)
+}
+
+/// A series as Google exports it: the master event and instances stored on their own
+#[derive(Debug, Default)]
+struct Series {
+    uid: String,
+    master: Option<Component>,
+    instances: Vec<Component>,
+}
+
+/// A VEVENT with the properties the import needs beyond [`parse_icalendar`]
+#[derive(Debug)]
+struct Component {
+    data: String,
+    recurrence_id: Option<String>,
+    cancelled: bool,
+    exdates: Vec<String>,
+}
+
+impl Component {
+    /// Title for the summary, without failing on events that have none
+    fn title(&self) -> String {
+        self.data.lines()
+            .find_map(|line| line.strip_prefix("SUMMARY:"))
+            .map(unescape_ical_text)
+            .unwrap_or_else(|| "(No title)".to_string())
+    }
+
+    fn event(&self) -> Result<NewEvent, AppError> {
+        let event = parse_icalendar(&self.data)?;
+        event.validate()?;
+        Ok(event)
+    }
+
+    fn recurrence_id(&self) -> Result<Option<DateTime<Utc>>, AppError> {
+        self.recurrence_id.as_deref().map(parse_ical_time).transpose()
+    }
+}
+
+async fn import_calendar(service: &CalendarService, user_id: Uuid, file: &CalendarFile, fallback_color: &str) -> CalendarSummary {
+    let properties = calendar_properties(&file.data);
+    let name = properties.get("X-WR-CALNAME").map(|name| unescape_ical_text(name))
+        .filter(|name| !name.trim().is_empty())
+        .unwrap_or_else(|| file_stem(&file.path));
+    let color = ["X-APPLE-CALENDAR-COLOR", "COLOR"].iter()
+        .filter_map(|key| properties.get(*key))
+        // Apple writes #RRGGBBAA
+        .filter_map(|color| color.get(..7))
+        .find(|color| validation::is_hex_color(color))
+        .unwrap_or(fallback_color)
+        .to_uppercase();
+
+    let mut summary = CalendarSummary {
+        file: file.path.clone(),
+        name: name.chars().take(validation::MAX_NAME_LENGTH).collect(),
+        color: color.clone(),
+        ..Default::default()
+    };
+
+    let (series, skipped) = match read_series(service, &file.data) {
+        Ok(series) => series,
+        Err(e) => {
+            summary.fail(&summary.file.clone(), e);
+            return summary;
+        }
+    };
+    summary.skipped = skipped;
+
+    let new_calendar = NewCalendar {
+        name: summary.name.clone(),
+        description: properties.get("X-WR-CALDESC").map(|description| unescape_ical_text(description))
+            .filter(|description| description.chars().count() <= validation::MAX_DESCRIPTION_LENGTH),
+        color: Some(color),
+        is_public: false,
+        default_alarm_minutes: None,
+    };
+    let calendar = match service.create_calendar(user_id, new_calendar).await {
+        Ok(calendar) => calendar,
+        Err(e) => {
+            let count: usize = series.iter().map(|series| series.master.iter().count() + series.instances.len()).sum();
+            summary.fail(&summary.name.clone(), e);
+            summary.events.failed += count.saturating_sub(1);
+            return summary;
+        }
+    };
+    summary.calendar_id = Some(calendar.id);
+
+    for series in series {
+        import_series(service, calendar.id, series, &mut summary).await;
+    }
+    summary
+}
+
+/// Group the VEVENTs of a calendar file by UID, counting other components by type
+fn read_series(service: &CalendarService, data: &str) -> Result<(Vec<Series>, BTreeMap<String, usize>), AppError> {
+    let mut reader = ComponentReader::new(service.quotas().max_ics_size);
+    let mut components = reader.push(data.as_bytes())?;
+    components.extend(reader.finish()?);
+
+    let mut series: Vec<Series> = Vec::new();
+    let mut by_uid: HashMap<String, usize> = HashMap::new();
+    let mut skipped = BTreeMap::new();
+    for component in components {
+        match component.kind.as_str() {
+            "VEVENT" => {}
+            "VTIMEZONE" => continue,
+            other => {
+                *skipped.entry(other.to_string()).or_default() += 1;
+                continue;
+            }
+        }
+
+        let mut uid = None;
+        let mut event = Component { data: component.data, recurrence_id: None, cancelled: false, exdates: Vec::new() };
+        let mut depth = 0usize;
+        for line in event.data.lines() {
+            if line.starts_with("BEGIN:") {
+                depth += 1;
+            } else if line.starts_with("END:") {
+                depth = depth.saturating_sub(1);
+            } else if depth == 1 {
+                if let Some(value) = line.strip_prefix("UID:") {
+                    uid = Some(value.trim().to_string());
+                } else if line.starts_with("RECURRENCE-ID") {
+                    event.recurrence_id = Some(line.to_string());
+                } else if line.starts_with("EXDATE") {
+                    event.exdates.push(line.to_string());
+                } else if let Some(value) = line.strip_prefix("STATUS:") {
+                    event.cancelled = value.trim().eq_ignore_ascii_case("CANCELLED");
+                }
+            }
+        }
+
+        // Events without a UID can't have instances stored apart, so each is a series
+        let uid = uid.unwrap_or_else(|| Uuid::new_v4().to_string());
+        let index = *by_uid.entry(uid.clone()).or_insert_with(|| {
+            series.push(Series { uid, ..Default::default() });
+            series.len() - 1
+        });
+        match event.recurrence_id {
+            Some(_) => series[index].instances.push(event),
+            None => series[index].master = Some(event),
+        }
+    }
+
+    Ok((series, skipped))
+}
+
+async fn import_series(service: &CalendarService, calendar_id: Uuid, series: Series, summary: &mut CalendarSummary) {
+    let Some(master) = series.master else {
+        // Instances of a series only partly shared with the user come without their
+        // master, and are kept as events of their own
+        for instance in series.instances {
+            if instance.cancelled {
+                *summary.skipped.entry("Cancelled instance".to_string()).or_default() += 1;
+                continue;
+            }
+            match instance.event() {
+                Ok(event) => match service.create_event(calendar_id, event).await {
+                    Ok(_) => summary.events.imported += 1,
+                    Err(e) => summary.fail(&instance.title(), e),
+                },
+                Err(e) => summary.fail(&instance.title(), e),
+            }
+        }
+        return;
+    };
+
+    let stored = match master.event() {
+        Ok(event) => service.create_event_with_uid(calendar_id, event, &series.uid).await,
+        Err(e) => Err(e),
+    };
+    let stored = match stored {
+        Ok(stored) => stored,
+        Err(e) => {
+            summary.fail(&master.title(), &e);
+            // Instances of a series that couldn't be stored have nothing to attach to
+            for instance in &series.instances {
+                summary.fail(&instance.title(), "Its series could not be imported");
+            }
+            return;
+        }
+    };
+    summary.events.imported += 1;
+
+    for line in &master.exdates {
+        let excluded = match parse_ical_times(line) {
+            Ok(times) => times,
+            Err(e) => {
+                summary.fail(&master.title(), e);
+                continue;
+            }
+        };
+        for time in excluded {
+            if let Err(e) = service.delete_event_occurrence(stored.id, time).await {
+                summary.fail(&master.title(), e);
+            }
+        }
+    }
+
+    for instance in series.instances {
+        let outcome = match instance.recurrence_id() {
+            Ok(Some(recurrence_id)) if instance.cancelled => {
+                service.delete_event_occurrence(stored.id, recurrence_id).await
+            }
+            Ok(Some(recurrence_id)) => match instance.event() {
+                Ok(event) => service.update_event_occurrence(stored.id, UpdateOccurrence {
+                    recurrence_id,
+                    changes: UpdateEvent {
+                        title: Some(event.title),
+                        description: Some(event.description),
+                        location: Some(event.location),
+                        start_time: Some(event.start_time),
+                        end_time: Some(event.end_time),
+                        is_all_day: Some(event.is_all_day),
+                        rrule: None,
+                        geo_lat: Some(event.geo_lat),
+                        geo_lon: Some(event.geo_lon),
+                        alarm_minutes: Some(event.alarm_minutes),
+                    },
+                }).await.map(|_| ()),
+                Err(e) => Err(e),
+            },
+            Ok(None) => Ok(()),
+            Err(e) => Err(e),
+        };
+        match outcome {
+            Ok(()) => summary.events.imported += 1,
+            Err(e) => summary.fail(&instance.title(), e),
+        }
+    }
+}
+
+/// Properties of the VCALENDAR itself, such as `X-WR-CALNAME`, with folded lines joined
+fn calendar_properties(data: &str) -> HashMap<String, String> {
+    let mut lines: Vec<String> = Vec::new();
+    for line in data.lines() {
+        let line = line.trim_end_matches('\r');
+        if line.starts_with("BEGIN:") && !line.eq_ignore_ascii_case("BEGIN:VCALENDAR") {
+            break;
+        }
+        match (line.strip_prefix([' ', '\t']), lines.last_mut()) {
+            (Some(rest), Some(last)) => last.push_str(rest),
+            _ => lines.push(line.to_string()),
+        }
+    }
+
+    lines.into_iter()
+        .filter_map(|line| {
+            let (name, value) = line.split_once(':')?;
+            let name = name.split(';').next().unwrap_or(name).to_ascii_uppercase();
+            Some((name, value.trim().to_string()))
+        })
+        .collect()
+}
+
+/// Name of a calendar file without its folders and extension
+fn file_stem(path: &str) -> String {
+    let name = path.rsplit('/').next().unwrap_or(path);
+    name.get(..name.len().saturating_sub(4)).unwrap_or(name).to_string()
+}
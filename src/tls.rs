@@ -0,0 +1,98 @@
+//! Optional native TLS termination, from certificate files or with certificates
+//! obtained over ACME (e.g. Let's Encrypt), for deployments without a reverse proxy
+
+use std::net::SocketAddr;
+use axum::Router;
+use axum_server::tls_rustls::RustlsConfig;
+use rustls_acme::{caches::DirCache, AcmeConfig};
+use serde::{Deserialize, Serialize};
+use tokio_stream::StreamExt;
+
+/// Default directory ACME account keys and certificates are cached in
+pub const DEFAULT_ACME_CACHE_DIR: &str = "./data/acme";
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub enum TlsConfig {
+    /// PEM-encoded certificate chain and private key
+    Files { cert_path: String, key_path: String },
+    /// Certificates for `domains` requested and renewed automatically via the
+    /// TLS-ALPN-01 challenge, so the server must be reachable on port 443
+    Acme {
+        domains: Vec<String>,
+        contact_email: Option<String>,
+        cache_dir: String,
+        /// Use the Let's Encrypt staging directory, for trying out a setup
+        staging: bool,
+    },
+}
+
+impl TlsConfig {
+    /// Read TLS settings from `TLS_*` and `ACME_*` variables. Certificate files take
+    /// precedence; without either, the server speaks plain HTTP.
+    pub fn from_env() -> Option<Self> {
+        if let (Ok(cert_path), Ok(key_path)) = (std::env::var("TLS_CERT_PATH"), std::env::var("TLS_KEY_PATH")) {
+            return Some(TlsConfig::Files { cert_path, key_path });
+        }
+
+        let domains: Vec<String> = std::env::var("ACME_DOMAINS").ok()?
+            .split(',')
+            .map(|d| d.trim().to_string())
+            .filter(|d| !d.is_empty())
+            .collect();
+        if domains.is_empty() {
+            return None;
+        }
+
+        Some(TlsConfig::Acme {
+            domains,
+            contact_email: std::env::var("ACME_EMAIL").ok(),
+            cache_dir: std::env::var("ACME_CACHE_DIR").unwrap_or_else(|_| DEFAULT_ACME_CACHE_DIR.to_string()),
+            staging: std::env::var("ACME_STAGING")
+                .ok()
+                .and_then(|s| s.parse().ok())
+                .unwrap_or(false),
+        })
+    }
+
+    /// Public URL implied by the configuration, used when no base URL is set
+    pub fn base_url(&self) -> Option<String> {
+        match self {
+            TlsConfig::Files { .. } => None,
+            TlsConfig::Acme { domains, .. } => domains.first().map(|d| format!("https://{}", d)),
+        }
+    }
+}
+
+/// Serve `app` over HTTPS on `addr` until the server stops
+pub async fn serve(addr: SocketAddr, app: Router, tls: &TlsConfig) -> Result<(), Box<dyn std::error::Error>> {
+    let service = app.into_make_service_with_connect_info::<SocketAddr>();
+
+    match tls {
+        TlsConfig::Files { cert_path, key_path } => {
+            let config = RustlsConfig::from_pem_file(cert_path, key_path).await?;
+            axum_server::bind_rustls(addr, config).serve(service).await?;
+        }
+        TlsConfig::Acme { domains, contact_email, cache_dir, staging } => {
+            let mut state = AcmeConfig::new(domains.clone())
+                .contact(contact_email.iter().map(|email| format!("mailto:{}", email)))
+                .cache(DirCache::new(cache_dir.clone()))
+                .directory_lets_encrypt(!staging)
+                .state();
+            let acceptor = state.axum_acceptor(state.default_rustls_config());
+
+            // Drives certificate orders and renewals
+            tokio::spawn(async move {
+                while let Some(event) = state.next().await {
+                    match event {
+                        Ok(event) => tracing::info!("ACME: {:?}", event),
+                        Err(e) => tracing::error!("ACME error: {:?}", e),
+                    }
+                }
+            });
+
+            axum_server::bind(addr).acceptor(acceptor).serve(service).await?;
+        }
+    }
+
+    Ok(())
+}
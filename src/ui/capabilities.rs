@@ -0,0 +1,12 @@
+/// What the signed-in user may do and which optional features are enabled, so pages
+/// only offer links and buttons that will work. The server still checks every action.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Capabilities {
+    pub is_admin: bool,
+    /// False once the user owns as many calendars as the quota allows
+    pub can_create_calendar: bool,
+    /// Single sign-on with an OIDC provider is configured
+    pub oidc_enabled: bool,
+    /// The server was built with the experimental JMAP endpoint
+    pub jmap_enabled: bool,
+}
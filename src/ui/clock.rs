@@ -0,0 +1,125 @@
+//! Times shown and entered on the clock of the user a page is rendered for
+//!
+//! All-day events are stored at midnight UTC and fall on the same dates wherever they are
+//! looked at, so their times are shown and entered as stored instead of converted. They
+//! are entered with date pickers, ending on their last day, while the stored end is the
+//! midnight after it.
+
+use chrono::{DateTime, Duration, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Utc};
+use chrono_tz::Tz;
+
+use crate::models::Event;
+use crate::ui::{t_with, user_settings};
+
+/// Format of `datetime-local` inputs
+const INPUT_FORMAT: &str = "%Y-%m-%dT%H:%M";
+
+/// Format of `date` inputs
+const DATE_INPUT_FORMAT: &str = "%Y-%m-%d";
+
+/// `time` of an event on the user's clock
+pub fn event_time(time: DateTime<Utc>, all_day: bool) -> DateTime<Tz> {
+    if all_day {
+        time.with_timezone(&Tz::UTC)
+    } else {
+        user_settings().local_time(time)
+    }
+}
+
+/// Time of day of `time` in the user's 12- or 24-hour format
+pub fn time_of_day(time: &DateTime<Tz>) -> String {
+    time.format(user_settings().time_format.pattern()).to_string()
+}
+
+/// Date and time of day of `time` on the user's clock, with the zone's abbreviation
+pub fn format_timestamp(time: DateTime<Utc>) -> String {
+    user_settings().format_timestamp(time)
+}
+
+/// Type of the inputs for the times of an event
+pub fn input_type(all_day: bool) -> &'static str {
+    if all_day { "date" } else { "datetime-local" }
+}
+
+/// Value of an [`input_type`] input showing `time` of an event
+pub fn input_value(time: DateTime<Utc>, all_day: bool) -> String {
+    let format = if all_day { DATE_INPUT_FORMAT } else { INPUT_FORMAT };
+    event_time(time, all_day).format(format).to_string()
+}
+
+/// Value of the end input of an event ending at `time`; all-day events show their last day
+pub fn end_input_value(time: DateTime<Utc>, all_day: bool) -> String {
+    if all_day {
+        input_value(time - Duration::days(1), true)
+    } else {
+        input_value(time, false)
+    }
+}
+
+/// The time an [`input_type`] input was set to; the earlier one when the clock was set
+/// back over it, `None` when it's malformed or skipped by a change to summer time. All-day
+/// events start at midnight of the chosen day, also when the input still held a time.
+pub fn parse_input_value(value: &str, all_day: bool) -> Option<DateTime<Utc>> {
+    if all_day {
+        let day = NaiveDate::parse_from_str(value.get(..10)?, DATE_INPUT_FORMAT).ok()?;
+        return Some(day.and_time(NaiveTime::MIN).and_utc());
+    }
+    let naive = NaiveDateTime::parse_from_str(value, INPUT_FORMAT).ok()?;
+    user_settings().timezone.from_local_datetime(&naive).earliest().map(|time| time.with_timezone(&Utc))
+}
+
+/// The end an end input was set to; all-day events end at midnight after their last day
+pub fn parse_end_input_value(value: &str, all_day: bool) -> Option<DateTime<Utc>> {
+    let end = parse_input_value(value, all_day)?;
+    Some(if all_day { end + Duration::days(1) } else { end })
+}
+
+/// Midnight at the start of `day` on the user's clock, or the first moment of the day
+/// where a clock change skips midnight
+pub fn start_of_day(day: NaiveDate) -> DateTime<Utc> {
+    let timezone = user_settings().timezone;
+    (0..24)
+        .filter_map(|hour| day.and_hms_opt(hour, 0, 0))
+        .find_map(|time| timezone.from_local_datetime(&time).earliest())
+        .map_or_else(|| day.and_time(NaiveTime::MIN).and_utc(), |time| time.with_timezone(&Utc))
+}
+
+/// First and last day of `event` on the user's clock. An event ending at midnight ends on
+/// the day before, as all-day events do.
+pub fn event_days(event: &Event) -> (NaiveDate, NaiveDate) {
+    let first = event_time(event.start_time, event.is_all_day).date_naive();
+    let end = event_time(event.end_time, event.is_all_day);
+    let last = if end.time() == NaiveTime::MIN {
+        end.date_naive().pred_opt().unwrap_or(first)
+    } else {
+        end.date_naive()
+    };
+    (first, last.max(first))
+}
+
+/// Whether `event` ends on a later day than it starts
+pub fn ends_on_different_day(event: &Event) -> bool {
+    let (first, last) = event_days(event);
+    first != last
+}
+
+/// How long `event` takes, in whole days for all-day events and in days, hours and
+/// minutes otherwise, e.g. `2 d 1 h 30 min`
+pub fn format_duration(event: &Event) -> String {
+    let duration = event.end_time - event.start_time;
+    if event.is_all_day {
+        return t_with("duration.days", &[("count", &duration.num_days().max(1).to_string())]);
+    }
+
+    let minutes = duration.num_minutes().max(0);
+    let parts: Vec<String> = [("duration.days", minutes / (24 * 60)), ("duration.hours", minutes / 60 % 24), ("duration.minutes", minutes % 60)]
+        .into_iter()
+        .filter(|(_, count)| *count > 0)
+        .map(|(key, count)| t_with(key, &[("count", &count.to_string())]))
+        .collect();
+    if parts.is_empty() {
+        t_with("duration.minutes", &[("count", "0")])
+    } else {
+        parts.join(" ")
+    }
+}
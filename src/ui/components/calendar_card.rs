@@ -1,6 +1,7 @@
 use dioxus::prelude::*;
 
 use crate::models::Calendar;
+use crate::ui::{t, t_with};
 
 #[component]
 pub fn CalendarCard(calendar: Calendar) -> Element {
@@ -11,11 +12,11 @@ pub fn CalendarCard(calendar: Calendar) -> Element {
                 p { class: "calendar-description", "{desc}" }
             }
             div { class: "calendar-actions",
-                a { href: "/web/calendars/{calendar.id}", class: "btn btn-sm btn-secondary", "View" }
-                a { href: "/web/calendars/{calendar.id}/edit", class: "btn btn-sm btn-outline", "Edit" }
+                a { href: "/web/calendars/{calendar.id}", class: "btn btn-sm btn-secondary", {t("common.view")} }
+                a { href: "/web/calendars/{calendar.id}/edit", class: "btn btn-sm btn-outline", {t("common.edit")} }
             }
             if calendar.is_public {
-                span { class: "badge badge-public", "Public" }
+                span { class: "badge badge-public", {t("common.public")} }
             }
         }
     }
@@ -32,11 +33,11 @@ pub fn CalendarListItem(calendar: Calendar, event_count: usize) -> Element {
                 }
             }
             div { class: "calendar-stats",
-                span { "{event_count} events" }
+                span { {t_with("calendars.event_count", &[("count", &event_count.to_string())])} }
             }
             div { class: "calendar-actions",
-                a { href: "/web/calendars/{calendar.id}", class: "btn btn-sm btn-secondary", "View" }
-                a { href: "/web/calendars/{calendar.id}/edit", class: "btn btn-sm btn-outline", "Edit" }
+                a { href: "/web/calendars/{calendar.id}", class: "btn btn-sm btn-secondary", {t("common.view")} }
+                a { href: "/web/calendars/{calendar.id}/edit", class: "btn btn-sm btn-outline", {t("common.edit")} }
             }
         }
     }
@@ -0,0 +1,106 @@
+use dioxus::prelude::*;
+use uuid::Uuid;
+
+use crate::models::Calendar;
+use crate::ui::t;
+use crate::validation::{MAX_EVENT_LOCATION_LENGTH, MAX_EVENT_TITLE_LENGTH};
+
+/// Dialog for adding an event without leaving the page. `event-dialog.js` checks it and
+/// sends it as JSON; the server's field errors show up under their inputs.
+#[component]
+pub fn EventDialog(
+    calendars: Vec<Calendar>,
+    selected_calendar_id: Option<Uuid>,
+    start_time: String,
+    end_time: String,
+) -> Element {
+    rsx! {
+        dialog { class: "modal", open: true,
+            form {
+                action: "/web/events/quick",
+                method: "post",
+                "data-event-dialog": true,
+                "data-end-before-start": t("event_form.end_before_start"),
+                h2 { {t("event_form.new_title")} }
+                p { class: "field-error", "data-error-for": "" }
+
+                div { class: "form-group",
+                    label { r#for: "dialog_title", {t("event_form.title")} }
+                    input {
+                        r#type: "text",
+                        id: "dialog_title",
+                        name: "title",
+                        required: true,
+                        maxlength: "{MAX_EVENT_TITLE_LENGTH}",
+                        placeholder: t("event_form.title_placeholder")
+                    }
+                    p { class: "field-error", "data-error-for": "title" }
+                }
+
+                div { class: "form-group",
+                    label { r#for: "dialog_calendar_id", {t("event_form.calendar")} }
+                    select { id: "dialog_calendar_id", name: "calendar_id", required: true,
+                        for calendar in calendars {
+                            option {
+                                value: "{calendar.id}",
+                                selected: Some(calendar.id) == selected_calendar_id,
+                                "{calendar.name}"
+                            }
+                        }
+                    }
+                    p { class: "field-error", "data-error-for": "calendar_id" }
+                }
+
+                div { class: "form-row",
+                    div { class: "form-group",
+                        label { r#for: "dialog_start_time", {t("event_form.start")} }
+                        input {
+                            r#type: "datetime-local",
+                            id: "dialog_start_time",
+                            name: "start_time",
+                            required: true,
+                            value: "{start_time}"
+                        }
+                        p { class: "field-error", "data-error-for": "start_time" }
+                    }
+                    div { class: "form-group",
+                        label { r#for: "dialog_end_time", {t("event_form.end")} }
+                        input {
+                            r#type: "datetime-local",
+                            id: "dialog_end_time",
+                            name: "end_time",
+                            required: true,
+                            value: "{end_time}"
+                        }
+                        p { class: "field-error", "data-error-for": "end_time" }
+                    }
+                }
+
+                div { class: "form-group",
+                    label { class: "checkbox-label",
+                        input { r#type: "checkbox", name: "is_all_day" }
+                        span { {t("event_form.all_day")} }
+                    }
+                }
+
+                div { class: "form-group",
+                    label { r#for: "dialog_location", {t("event_form.location")} }
+                    input {
+                        r#type: "text",
+                        id: "dialog_location",
+                        name: "location",
+                        maxlength: "{MAX_EVENT_LOCATION_LENGTH}",
+                        placeholder: t("event_form.location_placeholder")
+                    }
+                    p { class: "field-error", "data-error-for": "location" }
+                }
+
+                div { class: "form-actions",
+                    button { r#type: "button", class: "btn btn-secondary", "data-close-dialog": true, {t("common.cancel")} }
+                    a { href: "/web/events/new", class: "btn btn-outline", {t("event_form.more_options")} }
+                    button { r#type: "submit", class: "btn btn-primary", {t("events.create")} }
+                }
+            }
+        }
+    }
+}
@@ -1,13 +1,16 @@
 use dioxus::prelude::*;
 
+use uuid::Uuid;
+
 use crate::models::Event;
+use crate::ui::{ends_on_different_day, event_days, event_time, format_duration, month_abbr, t, t_with, time_of_day};
 
 #[component]
 pub fn EventItem(event: Event) -> Element {
-    let start_day = event.start_time.format("%d").to_string();
-    let start_month = event.start_time.format("%b").to_string();
-    let start_time = event.start_time.format("%H:%M").to_string();
-    let end_time = event.end_time.format("%H:%M").to_string();
+    let start = event_time(event.start_time, event.is_all_day);
+    let start_day = start.format("%d").to_string();
+    let start_month = month_abbr(&start);
+    let times = event_times(&event);
     
     rsx! {
         div { class: "event-item",
@@ -17,13 +20,13 @@ pub fn EventItem(event: Event) -> Element {
             }
             div { class: "event-info",
                 h4 { "{event.title}" }
-                p { class: "event-time", "{start_time} - {end_time}" }
+                p { class: "event-time", "{times}" }
                 if let Some(loc) = &event.location {
                     p { class: "event-location", "📍 {loc}" }
                 }
             }
             div { class: "event-actions",
-                a { href: "/web/events/{event.id}/edit", class: "btn btn-sm btn-outline", "Edit" }
+                a { href: "/web/events/{event.id}/edit", class: "btn btn-sm btn-outline", {t("common.edit")} }
             }
         }
     }
@@ -31,9 +34,9 @@ pub fn EventItem(event: Event) -> Element {
 
 #[component]
 pub fn EventListItem(event: Event, show_calendar: bool, calendar_name: Option<String>) -> Element {
-    let start_date = event.start_time.format("%Y-%m-%d").to_string();
-    let start_time = event.start_time.format("%H:%M").to_string();
-    let end_time = event.end_time.format("%H:%M").to_string();
+    let start = event_time(event.start_time, event.is_all_day);
+    let start_date = start.format("%Y-%m-%d").to_string();
+    let times = event_times(&event);
     
     rsx! {
         div { class: "event-list-item",
@@ -41,7 +44,7 @@ pub fn EventListItem(event: Event, show_calendar: bool, calendar_name: Option<St
                 h4 { 
                     a { href: "/web/events/{event.id}", "{event.title}" }
                 }
-                p { class: "event-time", "{start_date} {start_time} - {end_time}" }
+                p { class: "event-time", "{start_date} {times}" }
                 if show_calendar {
                     if let Some(name) = calendar_name {
                         p { class: "event-calendar", "📅 {name}" }
@@ -50,13 +53,71 @@ pub fn EventListItem(event: Event, show_calendar: bool, calendar_name: Option<St
                 if let Some(loc) = &event.location {
                     p { class: "event-location", "📍 {loc}" }
                 }
+                if event.is_recurring() {
+                    p { class: "event-recurrence", "🔁 ", {t("events.repeats")} }
+                }
             }
             div { class: "event-actions",
-                a { href: "/web/events/{event.id}/edit", class: "btn btn-sm btn-outline", "Edit" }
-                form { action: "/web/events/{event.id}/delete", method: "post", class: "inline-form",
-                    button { type: "submit", class: "btn btn-sm btn-danger", "Delete" }
+                a { href: "/web/events/{event.id}/edit", class: "btn btn-sm btn-outline", {t("common.edit")} }
+                // With htmx the item is removed in place; lists listening for
+                // `events-changed` reload themselves
+                form {
+                    action: "/web/events/{event.id}/delete",
+                    method: "post",
+                    class: "inline-form",
+                    "hx-post": "/web/events/{event.id}/delete",
+                    "hx-target": "closest .event-list-item",
+                    "hx-swap": "outerHTML",
+                    "hx-confirm": t("events.delete_confirm"),
+                    button { type: "submit", class: "btn btn-sm btn-danger", {t("common.delete")} }
                 }
             }
         }
     }
 }
+
+/// Events of a calendar, reloaded from its fragment endpoint whenever an event is deleted
+#[component]
+pub fn EventList(events: Vec<Event>, calendar_id: Uuid) -> Element {
+    rsx! {
+        div {
+            id: "event-list",
+            "hx-get": "/web/calendars/{calendar_id}/fragments/events",
+            "hx-trigger": "events-changed from:body",
+            "hx-swap": "outerHTML",
+            if events.is_empty() {
+                div { class: "empty-state",
+                    div { class: "empty-icon", "📌" }
+                    h2 { {t("events.empty_title")} }
+                    p { {t("calendar_detail.no_events")} }
+                    a { href: "/web/events/new?calendar={calendar_id}", class: "btn btn-primary", {t("events.create")} }
+                }
+            } else {
+                div { class: "event-list",
+                    for event in events {
+                        EventListItem {
+                            event: event,
+                            show_calendar: false,
+                            calendar_name: None
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Times of day an event takes after its start date, with the last day of one spanning
+/// several days, and how long it lasts
+fn event_times(event: &Event) -> String {
+    let (_, last) = event_days(event);
+    let until = t_with("events.until", &[("date", &last.to_string())]);
+    let start = time_of_day(&event_time(event.start_time, event.is_all_day));
+    let end = time_of_day(&event_time(event.end_time, event.is_all_day));
+    match (event.is_all_day, ends_on_different_day(event)) {
+        (true, false) => t("events.all_day"),
+        (true, true) => format!("{}, {} ({})", t("events.all_day"), until, format_duration(event)),
+        (false, false) => format!("{} - {} ({})", start, end, format_duration(event)),
+        (false, true) => format!("{} - {} {} ({})", start, last, end, format_duration(event)),
+    }
+}
@@ -1,10 +1,17 @@
 use dioxus::prelude::*;
 
+use crate::flash::take_flash;
+
+/// The flash left for this page by the form submission that led here, if any
 #[component]
-pub fn FlashMessage(message: String, flash_type: String) -> Element {
+pub fn FlashMessage() -> Element {
+    let Some(flash) = take_flash() else {
+        return rsx! {};
+    };
+
     rsx! {
-        div { class: "flash-message flash-{flash_type}",
-            "{message}"
+        div { class: "flash-message flash-{flash.kind.as_str()}",
+            "{flash.message}"
         }
     }
 }
@@ -72,7 +72,7 @@ pub fn Select(
                 for (value, text) in options {
                     option {
                         value: "{value}",
-                        selected: selected.as_ref().map_or(false, |s| s == &value),
+                        selected: selected.as_ref() == Some(&value),
                         "{text}"
                     }
                 }
@@ -146,3 +146,15 @@ pub fn DateTimeInput(
         }
     }
 }
+
+/// Hidden `timezone` field the browser fills with its IANA time zone, so new accounts
+/// start out showing times on the user's own clock
+#[component]
+pub fn DetectedTimeZone() -> Element {
+    rsx! {
+        input { r#type: "hidden", id: "timezone", name: "timezone" }
+        script {
+            dangerous_inner_html: "document.getElementById('timezone').value = Intl.DateTimeFormat().resolvedOptions().timeZone || '';"
+        }
+    }
+}
@@ -3,11 +3,17 @@ mod flash_message;
 mod stat_card;
 mod calendar_card;
 mod event_item;
+mod event_dialog;
 mod share_item;
 mod form_inputs;
+mod new_calendar_link;
 
 pub use navbar::*;
 pub use stat_card::*;
 pub use calendar_card::*;
 pub use event_item::*;
+pub use event_dialog::*;
 pub use share_item::*;
+pub use new_calendar_link::*;
+pub use flash_message::FlashMessage;
+pub use form_inputs::DetectedTimeZone;
@@ -1,26 +1,43 @@
 use dioxus::prelude::*;
 
-use crate::models::{User, UserRole};
+use crate::models::User;
+use crate::settings::Theme;
+use crate::ui::{t, user_settings, Capabilities};
 
 #[component]
-pub fn Navbar(current_user: Option<User>) -> Element {
+pub fn Navbar(current_user: Option<User>, capabilities: Capabilities) -> Element {
+    // Switches between light and dark; the system theme counts as light
+    let (toggle_to, toggle_label) = match user_settings().theme {
+        Theme::Dark => (Theme::Light, t("nav.light_mode")),
+        Theme::Light | Theme::System => (Theme::Dark, t("nav.dark_mode")),
+    };
+
     rsx! {
         nav { class: "navbar",
             div { class: "nav-brand",
-                a { href: "/", "My CalDAV Server" }
+                a { href: "/", {t("app.name")} }
             }
             div { class: "nav-menu",
-                if let Some(user) = current_user {
-                    a { href: "/web/dashboard", "Dashboard" }
-                    a { href: "/web/calendars", "Calendars" }
-                    a { href: "/web/events", "Events" }
-                    if user.role == UserRole::Admin {
-                        a { href: "/web/admin", class: "nav-admin", "Admin" }
+                if current_user.is_some() {
+                    a { href: "/web/dashboard", {t("nav.dashboard")} }
+                    a { href: "/web/calendars", {t("nav.calendars")} }
+                    a { href: "/web/events", {t("nav.events")} }
+                    a { href: "/web/schedule", {t("nav.schedule")} }
+                    a { href: "/web/groups", {t("nav.groups")} }
+                    a { href: "/web/contacts", {t("nav.contacts")} }
+                    a { href: "/web/insights", {t("nav.insights")} }
+                    a { href: "/web/settings", {t("nav.settings")} }
+                    if capabilities.is_admin {
+                        a { href: "/web/admin", class: "nav-admin", {t("nav.admin")} }
                     }
-                    a { href: "/web/logout", class: "nav-logout", "Logout" }
+                    a { href: "/web/logout", class: "nav-logout", {t("nav.logout")} }
                 } else {
-                    a { href: "/web/login", "Login" }
-                    a { href: "/web/register", "Register" }
+                    a { href: "/web/login", {t("auth.login")} }
+                    a { href: "/web/register", {t("auth.register")} }
+                }
+                form { action: "/web/theme", method: "post", class: "inline-form theme-toggle",
+                    input { r#type: "hidden", name: "theme", value: "{toggle_to.as_str()}" }
+                    button { r#type: "submit", class: "btn btn-sm btn-outline", "{toggle_label}" }
                 }
             }
         }
@@ -0,0 +1,20 @@
+use dioxus::prelude::*;
+
+use crate::ui::t;
+
+/// Link to the new calendar form, shown disabled once the calendar quota is used up
+#[component]
+pub fn NewCalendarLink(allowed: bool, label: String, class: String) -> Element {
+    rsx! {
+        if allowed {
+            a { href: "/web/calendars/new", class: "{class}", "{label}" }
+        } else {
+            span {
+                class: "{class} btn-disabled",
+                title: t("calendars.quota_reached"),
+                aria_disabled: "true",
+                "{label}"
+            }
+        }
+    }
+}
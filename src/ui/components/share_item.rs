@@ -1,19 +1,46 @@
 use dioxus::prelude::*;
 
 use crate::models::Share;
+use crate::ui::t;
 
 #[component]
 pub fn ShareItem(share: Share) -> Element {
-    let email = share.shared_with_email.clone().unwrap_or_else(|| "Unknown".to_string());
+    let email = share.shared_with_email.clone().unwrap_or_else(|| t("common.unknown"));
     
     rsx! {
         div { class: "share-item",
             div { class: "share-info",
                 span { class: "share-email", "{email}" }
-                span { class: "share-permission badge", "{share.permission_level}" }
+                span { class: "share-permission badge", {t(&format!("shares.permission.{}", share.permission_level))} }
             }
-            form { action: "/web/shares/{share.id}/delete", method: "post", class: "inline-form",
-                button { type: "submit", class: "btn btn-sm btn-danger", "Remove" }
+            form {
+                action: "/web/shares/{share.id}/delete",
+                method: "post",
+                class: "inline-form",
+                "hx-post": "/web/shares/{share.id}/delete",
+                "hx-target": "#share-list",
+                "hx-swap": "outerHTML",
+                button { type: "submit", class: "btn btn-sm btn-danger", {t("shares.remove")} }
+            }
+        }
+    }
+}
+
+/// Shares of a calendar; adding or removing one swaps in a fresh copy
+#[component]
+pub fn ShareList(shares: Vec<Share>) -> Element {
+    rsx! {
+        div { id: "share-list",
+            if shares.is_empty() {
+                div { class: "empty-state",
+                    p { {t("shares.empty")} }
+                }
+            } else {
+                div { class: "share-list",
+                    for share in shares {
+                        ShareItem { share: share }
+                    }
+                }
             }
         }
     }
@@ -0,0 +1,32 @@
+//! Translated messages for the language of the page being rendered
+
+use chrono::{Datelike, Weekday};
+
+use crate::i18n::{translate, translate_with, Language};
+use crate::ui::user_settings;
+
+/// Language the current page is rendered in, as negotiated by
+/// [`crate::middleware::user_settings_middleware`]
+pub fn language() -> Language {
+    user_settings().language.unwrap_or_default()
+}
+
+/// The message for `key` in the language of the current page
+pub fn t(key: &str) -> String {
+    translate(language(), key)
+}
+
+/// [`t`] with the `{name}` arguments of the message filled in
+pub fn t_with(key: &str, args: &[(&str, &str)]) -> String {
+    translate_with(language(), key, args)
+}
+
+/// Abbreviated name of the month of `date`, as `%b` formats it in English
+pub fn month_abbr(date: &impl Datelike) -> String {
+    t(&format!("months.{}", date.month()))
+}
+
+/// Abbreviated name of `day`, as `%a` formats it in English
+pub fn weekday_abbr(day: Weekday) -> String {
+    t(&format!("weekdays.{}", day.number_from_monday()))
+}
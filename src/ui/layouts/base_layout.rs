@@ -1,18 +1,18 @@
 use dioxus::prelude::*;
 
 use crate::models::User;
-use crate::ui::components::Navbar;
+use crate::settings::Theme;
+use crate::ui::components::{FlashMessage, Navbar};
+use crate::ui::{t, user_settings, Capabilities};
 
 #[component]
 pub fn BaseLayout(
     current_user: Option<User>,
-    flash_message: Option<String>,
-    flash_type: Option<String>,
+    capabilities: Capabilities,
     title: Option<String>,
     children: Element,
 ) -> Element {
-    let page_title = title.unwrap_or_else(|| "My CalDAV Server".to_string());
-    let ftype = flash_type.unwrap_or_else(|| "info".to_string());
+    let page_title = title.unwrap_or_else(|| t("app.name"));
     
     rsx! {
         head {
@@ -20,21 +20,27 @@ pub fn BaseLayout(
             meta { name: "viewport", content: "width=device-width, initial-scale=1.0" }
             title { "{page_title}" }
             link { rel: "stylesheet", href: "/static/css/style.css" }
+            ThemeStylesheet {}
+            // Partial updates of lists; every form still works as a full page load without it
+            script { src: "https://unpkg.com/htmx.org@2.0.4", defer: true }
+            script { src: "/static/js/event-dialog.js", defer: true }
+            script { src: "/static/js/all-day.js", defer: true }
         }
         body {
-            Navbar { current_user: current_user.clone() }
+            Navbar { current_user: current_user.clone(), capabilities: capabilities }
             
             main { class: "container",
-                if let Some(msg) = flash_message {
-                    div { class: "flash-message flash-{ftype}", "{msg}" }
-                }
+                FlashMessage {}
                 
                 {children}
             }
             
             footer { class: "footer",
-                p { "© 2026 My CalDAV Server" }
+                p { {t("app.footer")} }
             }
+
+            // Dialogs loaded by htmx, such as the one for new events
+            div { id: "modal" }
         }
     }
 }
@@ -45,21 +51,39 @@ pub fn AuthLayout(children: Element) -> Element {
         head {
             meta { charset: "UTF-8" }
             meta { name: "viewport", content: "width=device-width, initial-scale=1.0" }
-            title { "My CalDAV Server" }
+            title { {t("app.name")} }
             link { rel: "stylesheet", href: "/static/css/style.css" }
+            ThemeStylesheet {}
         }
         body {
-            Navbar { current_user: None }
+            Navbar { current_user: None, capabilities: Capabilities::default() }
             
             main { class: "container",
                 {children}
             }
             
             footer { class: "footer",
-                p { "© 2026 My CalDAV Server" }
+                p { {t("app.footer")} }
             }
         }
     }
 }
 
-
+/// The dark theme's colors over the light ones of the main stylesheet, always or while
+/// the operating system is in dark mode
+#[component]
+fn ThemeStylesheet() -> Element {
+    match user_settings().theme {
+        Theme::Light => rsx! {
+            meta { name: "color-scheme", content: "light" }
+        },
+        Theme::Dark => rsx! {
+            meta { name: "color-scheme", content: "dark" }
+            link { rel: "stylesheet", href: "/static/css/themes/dark.css" }
+        },
+        Theme::System => rsx! {
+            meta { name: "color-scheme", content: "light dark" }
+            link { rel: "stylesheet", href: "/static/css/themes/dark.css", media: "(prefers-color-scheme: dark)" }
+        },
+    }
+}
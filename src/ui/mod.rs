@@ -1,5 +1,17 @@
+mod capabilities;
 mod components;
 mod pages;
 mod layouts;
+mod i18n;
+mod clock;
+mod render;
+mod user_settings;
 
+pub use capabilities::Capabilities;
 pub use pages::*;
+pub use i18n::{language, month_abbr, t, t_with, weekday_abbr};
+pub use clock::{end_input_value, ends_on_different_day, event_days, event_time, format_duration, format_timestamp, input_type, input_value, parse_end_input_value, parse_input_value, start_of_day, time_of_day};
+pub use render::{render_fragment, render_to_html};
+pub use user_settings::{user_settings, with_user_settings};
+// List fragments swapped in by htmx
+pub use components::{EventDialog, EventList, ShareList};
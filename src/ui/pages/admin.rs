@@ -1,26 +1,30 @@
 use dioxus::prelude::*;
-use crate::models::{User, UserRole};
+use crate::dav_usage::UnsupportedFeature;
+use crate::models::{AuditLogEntry, User, UserRole};
 use crate::ui::layouts::BaseLayout;
+use crate::ui::{format_timestamp, t, Capabilities};
+use uuid::Uuid;
 
 #[derive(Props, PartialEq, Clone)]
 pub struct AdminPageProps {
     pub current_user: User,
+    pub capabilities: Capabilities,
     pub users: Vec<User>,
-    pub flash_message: Option<String>,
-    pub flash_type: Option<String>,
+    pub audit_log: Vec<AuditLogEntry>,
+    /// `None` while counting is switched off
+    pub unsupported_dav_features: Option<Vec<UnsupportedFeature>>,
 }
 
 #[allow(non_snake_case)]
 pub fn AdminPage(props: AdminPageProps) -> Element {
-    let flash_message = props.flash_message.clone();
-    let flash_type = props.flash_type.clone();
+    let dav_usage_enabled = props.unsupported_dav_features.is_some();
+    let dav_features = props.unsupported_dav_features.clone().unwrap_or_default();
     
     rsx! {
         BaseLayout {
             current_user: Some(props.current_user.clone()),
-            title: Some("Admin Panel - My CalDAV Server".to_string()),
-            flash_message: flash_message,
-            flash_type: flash_type,
+            capabilities: props.capabilities.clone(),
+            title: Some(t("admin.page_title")),
             
             div {
                 class: "admin-page",
@@ -29,10 +33,18 @@ pub fn AdminPage(props: AdminPageProps) -> Element {
                 div {
                     class: "page-header",
                     div {
-                        h1 { "Admin Panel" }
+                        h1 { {t("admin.heading")} }
                         p {
                             class: "subtitle",
-                            "Manage users and system settings"
+                            {t("admin.subtitle")}
+                        }
+                    }
+                    div {
+                        class: "page-actions",
+                        a {
+                            href: "/web/admin/backups",
+                            class: "btn btn-outline",
+                            {t("admin.backups")}
                         }
                     }
                 }
@@ -55,7 +67,7 @@ pub fn AdminPage(props: AdminPageProps) -> Element {
                             }
                             span {
                                 class: "stat-label",
-                                "Total Users"
+                                {t("admin.total_users")}
                             }
                         }
                     }
@@ -74,7 +86,7 @@ pub fn AdminPage(props: AdminPageProps) -> Element {
                             }
                             span {
                                 class: "stat-label",
-                                "Admins"
+                                {t("admin.admins")}
                             }
                         }
                     }
@@ -93,7 +105,7 @@ pub fn AdminPage(props: AdminPageProps) -> Element {
                             }
                             span {
                                 class: "stat-label",
-                                "Regular Users"
+                                {t("admin.regular_users")}
                             }
                         }
                     }
@@ -105,18 +117,18 @@ pub fn AdminPage(props: AdminPageProps) -> Element {
                     
                     div {
                         class: "section-header",
-                        h2 { "User Management" }
+                        h2 { {t("admin.user_management")} }
                     }
                     
                     table {
                         class: "admin-table",
                         thead {
                             tr {
-                                th { "Name" }
-                                th { "Email" }
-                                th { "Role" }
-                                th { "Created" }
-                                th { "Actions" }
+                                th { {t("admin.name")} }
+                                th { {t("admin.email")} }
+                                th { {t("admin.role_label")} }
+                                th { {t("admin.created")} }
+                                th { {t("admin.actions")} }
                             }
                         }
                         tbody {
@@ -136,7 +148,10 @@ pub fn AdminPage(props: AdminPageProps) -> Element {
                                                 UserRole::Admin => "badge badge-admin",
                                                 UserRole::User => "badge badge-user",
                                             },
-                                            "{user.role}"
+                                            match user.role {
+                                                UserRole::Admin => t("admin.role.admin"),
+                                                UserRole::User => t("admin.role.user"),
+                                            }
                                         }
                                     }
                                     td {
@@ -156,24 +171,50 @@ pub fn AdminPage(props: AdminPageProps) -> Element {
                                                     option {
                                                         value: "user",
                                                         selected: user.role == UserRole::User,
-                                                        "User"
+                                                        {t("admin.role.user")}
                                                     }
                                                     option {
                                                         value: "admin",
                                                         selected: user.role == UserRole::Admin,
-                                                        "Admin"
+                                                        {t("admin.role.admin")}
                                                     }
                                                 }
                                                 button {
                                                     type: "submit",
                                                     class: "btn btn-primary btn-sm",
-                                                    "Update"
+                                                    {t("admin.update")}
+                                                }
+                                            }
+                                            if user.role != UserRole::Admin {
+                                                form {
+                                                    method: "post",
+                                                    action: "/web/admin/users/{user.id}/impersonate",
+                                                    class: "inline-form",
+                                                    button {
+                                                        type: "submit",
+                                                        class: "btn btn-outline btn-sm",
+                                                        {t("admin.impersonate")}
+                                                    }
+                                                }
+                                            }
+                                            form {
+                                                method: "post",
+                                                action: "/web/admin/users/{user.id}/purge",
+                                                class: "inline-form",
+                                                label {
+                                                    input { r#type: "checkbox", name: "confirm", required: true }
+                                                    " ", {t("admin.purge_confirm")}
+                                                }
+                                                button {
+                                                    type: "submit",
+                                                    class: "btn btn-danger btn-sm",
+                                                    {t("admin.purge")}
                                                 }
                                             }
                                         } else {
                                             span {
                                                 class: "text-muted",
-                                                "(Current user)"
+                                                {t("admin.current_user")}
                                             }
                                         }
                                     }
@@ -183,16 +224,111 @@ pub fn AdminPage(props: AdminPageProps) -> Element {
                     }
                 }
                 
+                // Audit log
+                div {
+                    class: "dashboard-section",
+
+                    div {
+                        class: "section-header",
+                        h2 { {t("admin.audit_log")} }
+                    }
+
+                    if props.audit_log.is_empty() {
+                        p {
+                            class: "text-muted",
+                            {t("admin.no_audit")}
+                        }
+                    } else {
+                        table {
+                            class: "admin-table",
+                            thead {
+                                tr {
+                                    th { {t("admin.time")} }
+                                    th { {t("admin.role.admin")} }
+                                    th { {t("admin.impersonated_user")} }
+                                    th { {t("admin.action")} }
+                                    th { {t("admin.details")} }
+                                }
+                            }
+                            tbody {
+                                for entry in props.audit_log.iter() {
+                                    tr {
+                                        td { {format_timestamp(entry.created_at)} }
+                                        td { "{user_label(&props.users, Some(entry.actor_id))}" }
+                                        td { "{user_label(&props.users, entry.impersonated_user_id)}" }
+                                        td { "{entry.action}" }
+                                        td { "{entry.details.clone().unwrap_or_default()}" }
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+
+                // Unsupported DAV features clients asked for
+                div {
+                    class: "dashboard-section",
+
+                    div {
+                        class: "section-header",
+                        h2 { {t("admin.unsupported_dav")} }
+                    }
+
+                    if !dav_usage_enabled {
+                        p {
+                            class: "text-muted",
+                            {t("admin.dav_usage_disabled")}
+                        }
+                    } else if dav_features.is_empty() {
+                        p {
+                            class: "text-muted",
+                            {t("admin.dav_usage_empty")}
+                        }
+                    } else {
+                        table {
+                            class: "admin-table",
+                            thead {
+                                tr {
+                                    th { {t("admin.kind")} }
+                                    th { {t("admin.feature")} }
+                                    th { {t("admin.requests")} }
+                                }
+                            }
+                            tbody {
+                                for feature in dav_features.iter() {
+                                    tr {
+                                        td { "{feature.kind}" }
+                                        td { code { "{feature.name}" } }
+                                        td { "{feature.count}" }
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+
                 // Back to dashboard link
                 div {
                     class: "back-link",
                     a {
                         href: "/web/dashboard",
                         class: "btn btn-outline",
-                        "← Back to Dashboard"
+                        {t("common.back_to_dashboard")}
                     }
                 }
             }
         }
     }
 }
+
+/// Email of the user with the given id, falling back to the raw id for deleted users
+fn user_label(users: &[User], id: Option<Uuid>) -> String {
+    match id {
+        Some(id) => users
+            .iter()
+            .find(|u| u.id == id)
+            .map(|u| u.email.clone())
+            .unwrap_or_else(|| id.to_string()),
+        None => "-".to_string(),
+    }
+}
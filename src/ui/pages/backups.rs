@@ -0,0 +1,85 @@
+use dioxus::prelude::*;
+use crate::backup::BackupInfo;
+use crate::models::User;
+use crate::ui::layouts::BaseLayout;
+use crate::ui::{format_timestamp, t, t_with, Capabilities};
+
+#[component]
+pub fn BackupsPage(
+    current_user: User,
+    capabilities: Capabilities,
+    backups: Vec<BackupInfo>,
+    backup_dir: String,
+) -> Element {
+    rsx! {
+        BaseLayout {
+            current_user: Some(current_user),
+            capabilities: capabilities.clone(),
+            title: Some(t("backups.page_title")),
+
+            div { class: "admin-page",
+                div { class: "page-header",
+                    div {
+                        h1 { {t("admin.backups")} }
+                        p { class: "subtitle", {t_with("backups.subtitle", &[("dir", &backup_dir)])} }
+                    }
+                    div { class: "page-actions",
+                        form { method: "post", action: "/web/admin/backups", class: "inline-form",
+                            button { r#type: "submit", class: "btn btn-primary", {t("backups.back_up_now")} }
+                        }
+                    }
+                }
+
+                div { class: "dashboard-section",
+                    if backups.is_empty() {
+                        p { class: "text-muted", {t("backups.empty")} }
+                    } else {
+                        table { class: "admin-table",
+                            thead {
+                                tr {
+                                    th { {t("backups.taken")} }
+                                    th { {t("backups.file")} }
+                                    th { {t("backups.size")} }
+                                    th { "" }
+                                }
+                            }
+                            tbody {
+                                for backup in backups {
+                                    tr {
+                                        td { {format_timestamp(backup.created_at)} }
+                                        td { code { "{backup.name}" } }
+                                        td { "{format_size(backup.size)}" }
+                                        td {
+                                            a {
+                                                href: "/web/admin/backups/{backup.name}",
+                                                class: "btn btn-outline btn-sm",
+                                                {t("backups.download")}
+                                            }
+                                        }
+                                    }
+                                }
+                            }
+                        }
+                    }
+                    p { class: "text-muted",
+                        {t("backups.restore_hint")}
+                        code { "my_cal_dav_server restore <file>" }
+                        {t("backups.restore_hint_end")}
+                    }
+                }
+
+                div { class: "back-link",
+                    a { href: "/web/admin", class: "btn btn-outline", {t("backups.back_to_admin")} }
+                }
+            }
+        }
+    }
+}
+
+fn format_size(bytes: u64) -> String {
+    match bytes {
+        b if b >= 1024 * 1024 => format!("{:.1} MB", b as f64 / (1024.0 * 1024.0)),
+        b if b >= 1024 => format!("{:.1} KB", b as f64 / 1024.0),
+        b => format!("{} B", b),
+    }
+}
@@ -1,18 +1,21 @@
 use dioxus::prelude::*;
 
-use crate::models::{User, Calendar, Event, Share};
+use crate::models::{User, Calendar, Event, Share, ShareLinkDetails};
 use crate::ui::layouts::BaseLayout;
-use crate::ui::components::{EventListItem, ShareItem};
+use crate::ui::{format_timestamp, t, t_with, Capabilities};
+use crate::ui::components::{EventList, ShareList};
 
 #[component]
 pub fn CalendarDetailPage(
     current_user: User,
+    capabilities: Capabilities,
     calendar: Calendar,
     events: Vec<Event>,
     shares: Vec<Share>,
+    share_links: Vec<ShareLinkDetails>,
+    /// Whether the user may hand out access, and so sees the share links
+    can_manage_shares: bool,
     caldav_url: String,
-    flash_message: Option<String>,
-    flash_type: Option<String>,
 ) -> Element {
     let calendar_id = calendar.id;
     let is_public = calendar.is_public;
@@ -20,22 +23,47 @@ pub fn CalendarDetailPage(
     let shares_count = shares.len();
     let calendar_name = calendar.name.clone();
     let calendar_description = calendar.description.clone();
+    let is_owner = calendar.user_id == current_user.id;
+    let is_birthdays = calendar.is_birthdays;
+    let is_generated = calendar.is_generated();
+    let link_rows: Vec<(String, String, String, String, i64)> = share_links
+        .iter()
+        .map(|l| (
+            l.link.id.to_string(),
+            l.url.clone(),
+            t(&format!("shares.permission.{}", l.link.permission_level)),
+            l.link.expires_at
+                .map(|e| t_with("calendar_detail.expires", &[("date", &format_timestamp(e))]))
+                .unwrap_or_else(|| t("calendar_detail.no_expiry")),
+            l.link.redeemed_count,
+        ))
+        .collect();
     
     rsx! {
         BaseLayout {
             current_user: Some(current_user),
+            capabilities: capabilities.clone(),
             title: Some(format!("{} - My CalDAV Server", calendar_name)),
-            flash_message: flash_message,
-            flash_type: flash_type,
             
             div { class: "page-header",
                 div { class: "calendar-header",
                     div { class: "calendar-title",
                         h1 { "{calendar_name}" }
                     }
-                    div { class: "page-actions",
-                        a { href: "/web/calendars/{calendar_id}/edit", class: "btn btn-outline", "Edit Calendar" }
-                        a { href: "/web/events/new?calendar={calendar_id}", class: "btn btn-primary", "+ New Event" }
+                    if is_birthdays {
+                        div { class: "page-actions",
+                            a { href: "/web/contacts", class: "btn btn-outline", {t("calendar_detail.manage_contacts")} }
+                        }
+                    } else if is_generated {
+                        div { class: "page-actions",
+                            a { href: "/web/settings", class: "btn btn-outline", {t("calendar_detail.holiday_settings")} }
+                        }
+                    } else {
+                        div { class: "page-actions",
+                            a { href: "/web/calendars/{calendar_id}/edit", class: "btn btn-outline", {t("calendar_form.edit_title")} }
+                            a { href: "/web/calendars/{calendar_id}/journal", class: "btn btn-outline", {t("calendar_detail.journal")} }
+                            a { href: "/web/events/new?calendar={calendar_id}", class: "btn btn-primary", {t("events.new")} }
+                        }
                     }
                 }
                 if let Some(desc) = calendar_description {
@@ -45,97 +73,128 @@ pub fn CalendarDetailPage(
 
             div { class: "calendar-info-bar",
                 div { class: "info-item",
-                    span { class: "info-label", "Status:" }
+                    span { class: "info-label", {t("calendar_detail.status")} }
                     if is_public {
-                        span { class: "badge badge-public", "Public" }
+                        span { class: "badge badge-public", {t("common.public")} }
                     } else {
-                        span { class: "badge badge-private", "Private" }
+                        span { class: "badge badge-private", {t("common.private")} }
                     }
                 }
                 div { class: "info-item",
-                    span { class: "info-label", "Events:" }
+                    span { class: "info-label", {t("calendar_detail.events")} }
                     span { class: "info-value", "{events_count}" }
                 }
                 div { class: "info-item",
-                    span { class: "info-label", "Shares:" }
+                    span { class: "info-label", {t("calendar_detail.shares")} }
                     span { class: "info-value", "{shares_count}" }
                 }
             }
 
             div { class: "tabs",
-                button { class: "tab-btn active", "data-tab": "events", "Events" }
-                button { class: "tab-btn", "data-tab": "shares", "Shares" }
-                button { class: "tab-btn", "data-tab": "settings", "Settings" }
+                button { class: "tab-btn active", "data-tab": "events", {t("nav.events")} }
+                button { class: "tab-btn", "data-tab": "shares", {t("dashboard.shares")} }
+                button { class: "tab-btn", "data-tab": "settings", {t("nav.settings")} }
             }
 
             div { class: "tab-content active", id: "events-tab",
-                if events.is_empty() {
-                    div { class: "empty-state",
-                        div { class: "empty-icon", "📌" }
-                        h2 { "No events yet" }
-                        p { "This calendar doesn't have any events." }
-                        a { href: "/web/events/new?calendar={calendar_id}", class: "btn btn-primary", "Create Event" }
-                    }
-                } else {
-                    div { class: "event-list",
-                        for event in events {
-                            EventListItem { 
-                                event: event, 
-                                show_calendar: false, 
-                                calendar_name: None 
-                            }
-                        }
-                    }
-                }
+                EventList { events: events, calendar_id: calendar_id }
             }
 
             div { class: "tab-content", id: "shares-tab",
                 div { class: "section-header",
-                    h3 { "Calendar Shares" }
-                    button { class: "btn btn-primary", "+ Add Share" }
+                    h3 { {t("calendar_detail.calendar_shares")} }
+                    button { class: "btn btn-primary", {t("calendar_detail.add_share")} }
                 }
                 
-                if shares.is_empty() {
-                    div { class: "empty-state",
-                        p { "This calendar is not shared with anyone." }
+                ShareList { shares: shares }
+
+                if can_manage_shares {
+                    div { class: "section-header",
+                        h3 { {t("calendar_detail.share_links")} }
+                    }
+                    p { class: "form-hint",
+                        {t("calendar_detail.share_links_hint")}
                     }
-                } else {
-                    div { class: "share-list",
-                        for share in shares {
-                            ShareItem { share: share }
+                    if link_rows.is_empty() {
+                        div { class: "empty-state",
+                            p { {t("calendar_detail.no_share_links")} }
                         }
                     }
+                    for (link_id, url, permission, expiry, redeemed) in link_rows {
+                        div { class: "share-item",
+                            div { class: "share-info",
+                                code { "{url}" }
+                                span { class: "share-permission badge", "{permission}" }
+                                span { class: "badge", "{expiry}" }
+                                span { class: "badge", {t_with("calendar_detail.used", &[("count", &redeemed.to_string())])} }
+                            }
+                            form {
+                                action: "/web/share-links/{link_id}/revoke",
+                                method: "post",
+                                class: "inline-form",
+                                button { r#type: "submit", class: "btn btn-sm btn-danger", {t("calendar_detail.revoke")} }
+                            }
+                        }
+                    }
+                    form {
+                        action: "/web/calendars/{calendar_id}/share-links",
+                        method: "post",
+                        class: "form-row",
+                        div { class: "form-group",
+                            label { r#for: "link_permission", {t("shares.permission_label")} }
+                            select { id: "link_permission", name: "permission",
+                                option { value: "read", {t("shares.permission.read")} }
+                                option { value: "write", {t("shares.permission.write")} }
+                            }
+                        }
+                        div { class: "form-group",
+                            label { r#for: "link_expiry", {t("calendar_detail.expires_label")} }
+                            select { id: "link_expiry", name: "expires_in_days",
+                                option { value: "1", {t("calendar_detail.after_1_day")} }
+                                option { value: "7", selected: true, {t("calendar_detail.after_7_days")} }
+                                option { value: "30", {t("calendar_detail.after_30_days")} }
+                                option { value: "", {t("calendar_detail.never")} }
+                            }
+                        }
+                        button { r#type: "submit", class: "btn btn-secondary", {t("calendar_detail.create_link")} }
+                    }
                 }
                 
                 // Share Modal placeholder
                 div { id: "share-modal", class: "modal",
                     div { class: "modal-content",
                         div { class: "modal-header",
-                            h3 { "Share Calendar" }
+                            h3 { {t("calendar_detail.share_calendar")} }
                             button { class: "modal-close", "×" }
                         }
-                        form { action: "/web/calendars/{calendar_id}/shares", method: "post",
+                        form {
+                            action: "/web/calendars/{calendar_id}/shares",
+                            method: "post",
+                            "hx-post": "/web/calendars/{calendar_id}/shares",
+                            "hx-target": "#share-list",
+                            "hx-swap": "outerHTML",
+                            "hx-on::after-request": "if (event.detail.successful) this.reset()",
                             div { class: "form-group",
-                                label { r#for: "shared_with_email", "Email Address" }
+                                label { r#for: "shared_with_email", {t("calendar_detail.email_address")} }
                                 input {
                                     r#type: "email",
                                     id: "shared_with_email",
                                     name: "shared_with_email",
                                     required: true,
-                                    placeholder: "Enter email address"
+                                    placeholder: t("calendar_detail.email_placeholder")
                                 }
                             }
                             div { class: "form-group",
-                                label { r#for: "permission", "Permission" }
+                                label { r#for: "permission", {t("shares.permission_label")} }
                                 select { id: "permission", name: "permission",
-                                    option { value: "read", "Read Only" }
-                                    option { value: "write", "Read & Write" }
-                                    option { value: "admin", "Full Access" }
+                                    option { value: "read", {t("shares.permission.read")} }
+                                    option { value: "write", {t("shares.permission.write")} }
+                                    option { value: "admin", {t("shares.permission.admin")} }
                                 }
                             }
                             div { class: "form-actions",
-                                button { r#type: "button", class: "btn btn-secondary", "Cancel" }
-                                button { r#type: "submit", class: "btn btn-primary", "Share" }
+                                button { r#type: "button", class: "btn btn-secondary", {t("common.cancel")} }
+                                button { r#type: "submit", class: "btn btn-primary", {t("calendar_detail.share")} }
                             }
                         }
                     }
@@ -144,13 +203,50 @@ pub fn CalendarDetailPage(
 
             div { class: "tab-content", id: "settings-tab",
                 div { class: "settings-section",
-                    h3 { "CalDAV Access" }
-                    p { "Use these settings to access this calendar from your CalDAV client:" }
+                    h3 { {t("calendar_detail.caldav_access")} }
+                    p { {t("calendar_detail.caldav_hint")} }
                     div { class: "config-item",
-                        label { "Calendar URL:" }
+                        label { {t("calendar_detail.calendar_url")} }
                         code { "{caldav_url}/calendars/{calendar_id}/" }
                     }
                 }
+                div { class: "settings-section",
+                    h3 { {t("calendar_detail.resync")} }
+                    p { {t("calendar_detail.resync_hint")} }
+                    form { action: "/web/calendars/{calendar_id}/resync", method: "post",
+                        button { r#type: "submit", class: "btn btn-secondary", {t("calendar_detail.resync_button")} }
+                    }
+                }
+                if !is_generated {
+                    div { class: "settings-section",
+                        h3 { {t("history.deleted_section")} }
+                        p { {t("history.deleted_section_hint")} }
+                        a { href: "/web/calendars/{calendar_id}/deleted", class: "btn btn-secondary", {t("history.show_deleted")} }
+                    }
+                }
+                if is_owner && !is_generated {
+                    div { class: "danger-zone",
+                        h3 { {t("calendar_detail.transfer")} }
+                        p { {t("calendar_detail.transfer_hint")} }
+                        form { action: "/web/calendars/{calendar_id}/transfer", method: "post",
+                            div { class: "form-group",
+                                label { r#for: "new_owner_email", {t("calendar_detail.new_owner_email")} }
+                                input { r#type: "email", id: "new_owner_email", name: "new_owner_email", required: true }
+                            }
+                            div { class: "form-group",
+                                label { r#for: "confirm_name", {t_with("calendar_detail.confirm_name", &[("name", &calendar_name)])} }
+                                input { r#type: "text", id: "confirm_name", name: "confirm_name", required: true, autocomplete: "off" }
+                            }
+                            div { class: "form-group",
+                                label {
+                                    input { r#type: "checkbox", name: "keep_access" }
+                                    " ", {t("calendar_detail.keep_access")}
+                                }
+                            }
+                            button { r#type: "submit", class: "btn btn-danger", {t("calendar_detail.transfer_button")} }
+                        }
+                    }
+                }
             }
         }
     }
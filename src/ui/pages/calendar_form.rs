@@ -3,15 +3,30 @@ use uuid::Uuid;
 
 use crate::models::{User, Calendar};
 use crate::ui::layouts::BaseLayout;
+use crate::ui::{t, t_with, Capabilities};
+use crate::validation::MAX_NAME_LENGTH;
+
+/// Reminder choices offered for a calendar's default, in minutes before the start, with
+/// the key of their label
+const REMINDER_CHOICES: [(i64, &str); 7] = [
+    (0, "reminders.at_start"),
+    (5, "reminders.5_minutes"),
+    (10, "reminders.10_minutes"),
+    (15, "reminders.15_minutes"),
+    (30, "reminders.30_minutes"),
+    (60, "reminders.1_hour"),
+    (1440, "reminders.1_day"),
+];
 
 #[component]
 pub fn CalendarFormPage(
     current_user: User,
+    capabilities: Capabilities,
     is_edit: bool,
     calendar_id: Option<Uuid>,
     calendar: Option<Calendar>,
 ) -> Element {
-    let title = if is_edit { "Edit Calendar" } else { "New Calendar" };
+    let title = if is_edit { t("calendar_form.edit_title") } else { t("calendar_form.new_title") };
     let action = if is_edit {
         format!("/web/calendars/{}", calendar_id.unwrap())
     } else {
@@ -22,10 +37,14 @@ pub fn CalendarFormPage(
     let description = calendar.as_ref().and_then(|c| c.description.clone()).unwrap_or_default();
     let color = calendar.as_ref().and_then(|c| c.color.clone()).unwrap_or_else(|| "#3B82F6".to_string());
     let is_public = calendar.as_ref().map(|c| c.is_public).unwrap_or(false);
+    let default_alarm_minutes = calendar.as_ref().and_then(|c| c.default_alarm_minutes);
+    // A default set through the API may not be one of the choices
+    let custom_alarm_minutes = default_alarm_minutes.filter(|m| !REMINDER_CHOICES.iter().any(|(choice, _)| choice == m));
     
     rsx! {
         BaseLayout {
             current_user: Some(current_user),
+            capabilities: capabilities.clone(),
             title: Some(format!("{} - My CalDAV Server", title)),
             
             div { class: "page-header",
@@ -35,30 +54,31 @@ pub fn CalendarFormPage(
             div { class: "form-container",
                 form { action: "{action}", method: "post",
                     div { class: "form-group",
-                        label { r#for: "name", "Calendar Name *" }
+                        label { r#for: "name", {t("calendar_form.name")} }
                         input {
                             r#type: "text",
                             id: "name",
                             name: "name",
                             required: true,
+                            maxlength: "{MAX_NAME_LENGTH}",
                             value: "{name}",
-                            placeholder: "Enter calendar name"
+                            placeholder: t("calendar_form.name_placeholder")
                         }
                     }
                     
                     div { class: "form-group",
-                        label { r#for: "description", "Description" }
+                        label { r#for: "description", {t("common.description")} }
                         textarea {
                             id: "description",
                             name: "description",
                             rows: "3",
-                            placeholder: "Enter calendar description (optional)",
+                            placeholder: t("calendar_form.description_placeholder"),
                             "{description}"
                         }
                     }
                     
                     div { class: "form-group",
-                        label { r#for: "color", "Color" }
+                        label { r#for: "color", {t("common.color")} }
                         div { class: "color-picker",
                             input {
                                 r#type: "color",
@@ -69,6 +89,20 @@ pub fn CalendarFormPage(
                         }
                     }
                     
+                    div { class: "form-group",
+                        label { r#for: "default_alarm_minutes", {t("calendar_form.default_reminder")} }
+                        select { id: "default_alarm_minutes", name: "default_alarm_minutes",
+                            option { value: "", selected: default_alarm_minutes.is_none(), {t("common.none")} }
+                            for (minutes, label) in REMINDER_CHOICES {
+                                option { value: "{minutes}", selected: default_alarm_minutes == Some(minutes), {t(label)} }
+                            }
+                            if let Some(minutes) = custom_alarm_minutes {
+                                option { value: "{minutes}", selected: true, {t_with("reminders.minutes", &[("minutes", &minutes.to_string())])} }
+                            }
+                        }
+                        p { class: "form-hint", {t("calendar_form.default_reminder_hint")} }
+                    }
+
                     div { class: "form-group",
                         label { class: "checkbox-label",
                             input {
@@ -76,18 +110,18 @@ pub fn CalendarFormPage(
                                 name: "is_public",
                                 checked: is_public
                             }
-                            span { "Make this calendar public" }
+                            span { {t("calendar_form.make_public")} }
                         }
-                        p { class: "form-hint", "Public calendars can be viewed by anyone with the link." }
+                        p { class: "form-hint", {t("calendar_form.public_hint")} }
                     }
                     
                     div { class: "form-actions",
-                        a { href: "/web/calendars", class: "btn btn-secondary", "Cancel" }
+                        a { href: "/web/calendars", class: "btn btn-secondary", {t("common.cancel")} }
                         button { r#type: "submit", class: "btn btn-primary", 
                             if is_edit {
-                                "Update Calendar"
+                                {t("calendar_form.update")}
                             } else {
-                                "Create Calendar"
+                                {t("calendars.create")}
                             }
                         }
                     }
@@ -97,15 +131,15 @@ pub fn CalendarFormPage(
             if is_edit {
                 if let Some(id) = calendar_id {
                     div { class: "danger-zone",
-                        h3 { "Danger Zone" }
-                        p { "Deleting a calendar will permanently remove all its events. This action cannot be undone." }
+                        h3 { {t("calendar_form.danger_zone")} }
+                        p { {t("calendar_form.delete_hint")} }
                         form { 
                             action: "/web/calendars/{id}/delete", 
                             method: "post",
                             button { 
                                 r#type: "submit", 
                                 class: "btn btn-danger",
-                                "Delete Calendar" 
+                                {t("calendar_form.delete")} 
                             }
                         }
                     }
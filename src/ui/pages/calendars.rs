@@ -2,50 +2,130 @@ use dioxus::prelude::*;
 use std::collections::HashMap;
 use uuid::Uuid;
 
-use crate::models::{User, Calendar};
+use crate::models::{User, Calendar, Delegation};
 use crate::ui::layouts::BaseLayout;
+use crate::ui::components::NewCalendarLink;
+use crate::ui::{t, t_with, Capabilities};
 
 #[component]
 pub fn CalendarsPage(
     current_user: User,
+    capabilities: Capabilities,
     calendars: Vec<Calendar>,
     event_counts: HashMap<Uuid, usize>,
+    /// Calendars the user hid from their dashboard
+    hidden: Vec<Uuid>,
+    /// Delegations received, one per account the user can switch to
+    delegations: Vec<Delegation>,
+    /// Delegation of the account being viewed; `None` for the user's own calendars
+    account: Option<Delegation>,
 ) -> Element {
+    let viewing_own = account.is_none();
+    let heading = match &account {
+        Some(delegation) => t_with("calendars.heading_delegated", &[("name", &delegation.owner_name)]),
+        None => t("calendars.heading"),
+    };
+
     rsx! {
         BaseLayout {
             current_user: Some(current_user),
-            title: Some("Calendars - My CalDAV Server".to_string()),
+            capabilities: capabilities.clone(),
+            title: Some(t("calendars.page_title")),
             
             div { class: "page-header",
-                h1 { "My Calendars" }
-                a { href: "/web/calendars/new", class: "btn btn-primary", "+ New Calendar" }
+                h1 { "{heading}" }
+                if viewing_own {
+                    div { class: "page-actions",
+                        a { href: "/web/import/google", class: "btn btn-outline", {t("calendars.import_google")} }
+                        NewCalendarLink { allowed: capabilities.can_create_calendar, label: t("calendars.new"), class: "btn btn-primary".to_string() }
+                    }
+                }
+            }
+
+            if !delegations.is_empty() {
+                nav { class: "account-switcher",
+                    span { class: "text-muted", {t("calendars.account")} }
+                    a {
+                        href: "/web/calendars",
+                        class: if viewing_own { "btn btn-sm btn-secondary" } else { "btn btn-sm btn-outline" },
+                        {t("calendars.mine")}
+                    }
+                    for delegation in delegations {
+                        a {
+                            href: "/web/calendars?account={delegation.owner_id}",
+                            class: if account.as_ref().is_some_and(|a| a.id == delegation.id) { "btn btn-sm btn-secondary" } else { "btn btn-sm btn-outline" },
+                            "{delegation.owner_name}"
+                        }
+                    }
+                }
             }
             
-            if calendars.is_empty() {
+            if calendars.is_empty() && !viewing_own {
+                div { class: "empty-state",
+                    h2 { {t("calendars.none")} }
+                    p { {t("calendars.account_empty")} }
+                }
+            } else if calendars.is_empty() {
                 div { class: "empty-state",
                     div { class: "empty-icon", "📅" }
-                    h2 { "No calendars yet" }
-                    p { "Create your first calendar to get started." }
-                    a { href: "/web/calendars/new", class: "btn btn-primary", "Create Calendar" }
+                    h2 { {t("calendars.empty_title")} }
+                    p { {t("calendars.empty_hint")} }
+                    NewCalendarLink { allowed: capabilities.can_create_calendar, label: t("calendars.create"), class: "btn btn-primary".to_string() }
                 }
             } else {
-                div { class: "calendar-list",
+                div { id: "calendar-list", class: "calendar-list",
                     for calendar in calendars {
                         CalendarListItem { 
                             calendar: calendar.clone(), 
-                            event_count: *event_counts.get(&calendar.id).unwrap_or(&0)
+                            event_count: *event_counts.get(&calendar.id).unwrap_or(&0),
+                            can_edit: viewing_own && !calendar.is_generated(),
+                            can_arrange: viewing_own,
+                            hidden: hidden.contains(&calendar.id),
                         }
                     }
                 }
+                if viewing_own {
+                    p { class: "text-muted", {t("calendars.drag_hint")} }
+                    script { dangerous_inner_html: REORDER_SCRIPT }
+                }
             }
         }
     }
 }
 
+/// Drag-to-reorder for the calendar list, saving the new order when a calendar is dropped
+const REORDER_SCRIPT: &str = r#"
+(function () {
+    const list = document.getElementById('calendar-list');
+    let dragged = null;
+    list.addEventListener('dragstart', (e) => { dragged = e.target.closest('[data-calendar-id]'); });
+    list.addEventListener('dragover', (e) => {
+        const target = e.target.closest('[data-calendar-id]');
+        if (!dragged || !target || target === dragged) return;
+        e.preventDefault();
+        const after = e.clientY > target.getBoundingClientRect().top + target.offsetHeight / 2;
+        list.insertBefore(dragged, after ? target.nextSibling : target);
+    });
+    list.addEventListener('drop', (e) => {
+        e.preventDefault();
+        const ids = [...list.querySelectorAll('[data-calendar-id]')].map((item) => item.dataset.calendarId);
+        fetch('/web/calendars/order', {
+            method: 'POST',
+            headers: { 'Content-Type': 'application/x-www-form-urlencoded' },
+            body: new URLSearchParams({ calendar_ids: ids.join(',') }),
+        });
+        dragged = null;
+    });
+})();
+"#;
+
 #[component]
-fn CalendarListItem(calendar: Calendar, event_count: usize) -> Element {
+fn CalendarListItem(calendar: Calendar, event_count: usize, can_edit: bool, can_arrange: bool, hidden: bool) -> Element {
     rsx! {
-        div { class: "calendar-list-item",
+        div {
+            class: if hidden { "calendar-list-item calendar-hidden" } else { "calendar-list-item" },
+            draggable: if can_arrange { "true" } else { "false" },
+            "data-calendar-id": "{calendar.id}",
             div { class: "calendar-info",
                 h3 { "{calendar.name}" }
                 if let Some(desc) = &calendar.description {
@@ -53,14 +133,35 @@ fn CalendarListItem(calendar: Calendar, event_count: usize) -> Element {
                 }
             }
             div { class: "calendar-stats",
-                span { "{event_count} events" }
+                span { {t_with("calendars.event_count", &[("count", &event_count.to_string())])} }
                 if calendar.is_public {
-                    span { class: "badge badge-public", "Public" }
+                    span { class: "badge badge-public", {t("common.public")} }
+                }
+                if calendar.is_default {
+                    span { class: "badge badge-default", {t("calendars.default")} }
+                }
+                if hidden {
+                    span { class: "badge", {t("calendars.hidden")} }
                 }
             }
             div { class: "calendar-actions",
-                a { href: "/web/calendars/{calendar.id}", class: "btn btn-sm btn-secondary", "View" }
-                a { href: "/web/calendars/{calendar.id}/edit", class: "btn btn-sm btn-outline", "Edit" }
+                a { href: "/web/calendars/{calendar.id}", class: "btn btn-sm btn-secondary", {t("common.view")} }
+                if can_edit {
+                    a { href: "/web/calendars/{calendar.id}/edit", class: "btn btn-sm btn-outline", {t("common.edit")} }
+                }
+                if can_edit && !calendar.is_default && calendar.group_id.is_none() {
+                    form { method: "post", action: "/web/calendars/{calendar.id}/default", class: "inline-form",
+                        button { r#type: "submit", class: "btn btn-sm btn-outline", {t("calendars.make_default")} }
+                    }
+                }
+                if can_arrange {
+                    form { method: "post", action: "/web/calendars/{calendar.id}/visibility", class: "inline-form",
+                        input { r#type: "hidden", name: "hidden", value: if hidden { "false" } else { "true" } }
+                        button { r#type: "submit", class: "btn btn-sm btn-outline",
+                            if hidden { {t("calendars.show")} } else { {t("calendars.hide")} }
+                        }
+                    }
+                }
             }
         }
     }
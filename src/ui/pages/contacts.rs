@@ -0,0 +1,71 @@
+use dioxus::prelude::*;
+
+use crate::models::{Contact, User};
+use crate::ui::layouts::BaseLayout;
+use crate::ui::{t, Capabilities};
+
+#[component]
+pub fn ContactsPage(
+    current_user: User,
+    capabilities: Capabilities,
+    contacts: Vec<Contact>,
+) -> Element {
+    rsx! {
+        BaseLayout {
+            current_user: Some(current_user),
+            capabilities: capabilities.clone(),
+            title: Some(t("contacts.page_title")),
+
+            div { class: "page-header",
+                div {
+                    h1 { {t("nav.contacts")} }
+                    p { class: "subtitle", {t("contacts.subtitle")} }
+                }
+            }
+
+            div { class: "dashboard-section",
+                if contacts.is_empty() {
+                    p { class: "text-muted", {t("contacts.empty")} }
+                } else {
+                    table { class: "admin-table",
+                        thead {
+                            tr {
+                                th { {t("admin.name")} }
+                                th { {t("admin.email")} }
+                                th { {t("contacts.birthday")} }
+                                th { {t("contacts.anniversary")} }
+                                th { "" }
+                            }
+                        }
+                        tbody {
+                            for contact in contacts {
+                                tr {
+                                    td { "{contact.name}" }
+                                    td { {contact.email.clone().unwrap_or_default()} }
+                                    td { {contact.birthday.map(|d| d.to_string()).unwrap_or_default()} }
+                                    td { {contact.anniversary.map(|d| d.to_string()).unwrap_or_default()} }
+                                    td {
+                                        form { action: "/web/contacts/{contact.id}/delete", method: "post", class: "inline-form",
+                                            button { r#type: "submit", class: "btn btn-sm btn-danger", {t("common.delete")} }
+                                        }
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+
+            div { class: "dashboard-section",
+                h2 { {t("contacts.new")} }
+                form { action: "/web/contacts", method: "post", class: "inline-form",
+                    input { r#type: "text", name: "name", placeholder: t("admin.name"), required: true }
+                    input { r#type: "email", name: "email", placeholder: t("contacts.email_placeholder") }
+                    label { {t("contacts.birthday")} " " input { r#type: "date", name: "birthday" } }
+                    label { {t("contacts.anniversary")} " " input { r#type: "date", name: "anniversary" } }
+                    button { r#type: "submit", class: "btn btn-primary", {t("contacts.add")} }
+                }
+            }
+        }
+    }
+}
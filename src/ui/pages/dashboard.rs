@@ -1,17 +1,23 @@
 use dioxus::prelude::*;
 
 use crate::models::{User, Calendar, Event};
+use crate::quota::QuotaUsage;
 use crate::ui::layouts::BaseLayout;
-use crate::ui::components::{StatCard, CalendarCard, EventItem};
+use crate::ui::{t, t_with, Capabilities};
+use crate::ui::components::{StatCard, CalendarCard, EventItem, NewCalendarLink};
 
 #[component]
 pub fn DashboardPage(
     current_user: User,
+    capabilities: Capabilities,
     calendar_count: usize,
     event_count: usize,
     share_count: usize,
+    usage: Vec<QuotaUsage>,
     calendars: Vec<Calendar>,
     upcoming_events: Vec<Event>,
+    /// Events with conflicting changes waiting to be resolved
+    conflicted_events: Vec<Event>,
     caldav_url: String,
 ) -> Element {
     let user_name = current_user.name.clone();
@@ -20,30 +26,45 @@ pub fn DashboardPage(
     rsx! {
         BaseLayout {
             current_user: Some(current_user),
-            title: Some("Dashboard - My CalDAV Server".to_string()),
+            capabilities: capabilities.clone(),
+            title: Some(t("dashboard.page_title")),
             
             div { class: "dashboard",
                 div { class: "dashboard-header",
-                    h1 { "Welcome, {user_name}!" }
-                    p { class: "subtitle", "Manage your calendars and events" }
+                    h1 { {t_with("dashboard.welcome", &[("name", &user_name)])} }
+                    p { class: "subtitle", {t("dashboard.subtitle")} }
                 }
                 
+                if !conflicted_events.is_empty() {
+                    div { class: "dashboard-section conflict-notice",
+                        h2 { {t("edit_conflicts.dashboard_heading")} }
+                        p { {t("edit_conflicts.dashboard_hint")} }
+                        ul {
+                            for event in conflicted_events {
+                                li {
+                                    a { href: "/web/events/{event.id}/edit-conflicts", "{event.title}" }
+                                }
+                            }
+                        }
+                    }
+                }
+
                 div { class: "dashboard-stats",
-                    StatCard { icon: "📅".to_string(), number: calendar_count, label: "Calendars".to_string() }
-                    StatCard { icon: "📌".to_string(), number: event_count, label: "Events".to_string() }
-                    StatCard { icon: "🔗".to_string(), number: share_count, label: "Shares".to_string() }
+                    StatCard { icon: "📅".to_string(), number: calendar_count, label: t("nav.calendars") }
+                    StatCard { icon: "📌".to_string(), number: event_count, label: t("nav.events") }
+                    StatCard { icon: "🔗".to_string(), number: share_count, label: t("dashboard.shares") }
                 }
                 
                 div { class: "dashboard-section",
                     div { class: "section-header",
-                        h2 { "Your Calendars" }
-                        a { href: "/web/calendars/new", class: "btn btn-primary", "+ New Calendar" }
+                        h2 { {t("dashboard.your_calendars")} }
+                        NewCalendarLink { allowed: capabilities.can_create_calendar, label: t("calendars.new"), class: "btn btn-primary".to_string() }
                     }
                     
                     if calendars.is_empty() {
                         div { class: "empty-state",
-                            p { "You don't have any calendars yet." }
-                            a { href: "/web/calendars/new", class: "btn btn-secondary", "Create your first calendar" }
+                            p { {t("dashboard.no_calendars")} }
+                            NewCalendarLink { allowed: capabilities.can_create_calendar, label: t("dashboard.create_first_calendar"), class: "btn btn-secondary".to_string() }
                         }
                     } else {
                         div { class: "calendar-grid",
@@ -56,13 +77,19 @@ pub fn DashboardPage(
                 
                 div { class: "dashboard-section",
                     div { class: "section-header",
-                        h2 { "Upcoming Events" }
-                        a { href: "/web/events/new", class: "btn btn-primary", "+ New Event" }
+                        h2 { {t("dashboard.upcoming_events")} }
+                        a {
+                            href: "/web/events/new",
+                            class: "btn btn-primary",
+                            "hx-get": "/web/events/quick",
+                            "hx-target": "#modal",
+                            {t("events.new")}
+                        }
                     }
                     
                     if upcoming_events.is_empty() {
                         div { class: "empty-state",
-                            p { "No upcoming events." }
+                            p { {t("dashboard.no_upcoming_events")} }
                         }
                     } else {
                         div { class: "event-list",
@@ -73,21 +100,43 @@ pub fn DashboardPage(
                     }
                 }
                 
+                if !usage.is_empty() {
+                    div { class: "dashboard-section",
+                        h2 { {t("dashboard.storage")} }
+                        div { class: "usage-list",
+                            for item in usage {
+                                div { class: "usage-row",
+                                    span { class: "usage-label", "{item.label}" }
+                                    div { class: "usage-track",
+                                        div { class: "usage-bar", style: "width: {item.percent()}%" }
+                                    }
+                                    span { class: "usage-value",
+                                        match item.limit {
+                                            Some(limit) => format!("{} / {}", item.used, limit),
+                                            None => format!("{}", item.used),
+                                        }
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+                
                 div { class: "dashboard-section",
-                    h2 { "CalDAV Configuration" }
+                    h2 { {t("dashboard.caldav_configuration")} }
                     div { class: "config-info",
-                        p { "Use the following settings to connect your CalDAV client:" }
+                        p { {t("dashboard.caldav_hint")} }
                         div { class: "config-item",
-                            label { "Server URL:" }
+                            label { {t("dashboard.server_url")} }
                             code { "{caldav_url}" }
                         }
                         div { class: "config-item",
-                            label { "Username:" }
+                            label { {t("dashboard.username")} }
                             code { "{user_email}" }
                         }
                         div { class: "config-item",
-                            label { "Password:" }
-                            code { "Your account password" }
+                            label { {t("dashboard.password")} }
+                            code { {t("dashboard.password_hint")} }
                         }
                     }
                 }
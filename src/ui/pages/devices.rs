@@ -0,0 +1,86 @@
+use dioxus::prelude::*;
+
+use crate::devices::{Device, DeviceCredential};
+use crate::models::User;
+use crate::ui::layouts::BaseLayout;
+use crate::ui::{format_timestamp, t, Capabilities};
+
+/// The CalDAV clients that synced with the user's account, with buttons to revoke them
+#[component]
+pub fn DevicesPage(
+    current_user: User,
+    capabilities: Capabilities,
+    devices: Vec<Device>,
+) -> Element {
+    rsx! {
+        BaseLayout {
+            current_user: Some(current_user),
+            capabilities: capabilities.clone(),
+            title: Some(t("devices.page_title")),
+
+            div { class: "page-header",
+                div {
+                    h1 { {t("devices.heading")} }
+                    p { class: "subtitle", {t("devices.subtitle")} }
+                }
+                div { class: "page-actions",
+                    a { href: "/web/settings", class: "btn btn-secondary", {t("devices.back")} }
+                }
+            }
+
+            div { class: "dashboard-section",
+                if devices.is_empty() {
+                    p { class: "text-muted", {t("devices.none")} }
+                } else {
+                    table { class: "admin-table",
+                        thead {
+                            tr {
+                                th { {t("devices.client")} }
+                                th { {t("devices.credential")} }
+                                th { {t("devices.last_sync")} }
+                                th { {t("devices.ip")} }
+                                th { {t("devices.requests")} }
+                                th { {t("devices.status")} }
+                                th {}
+                            }
+                        }
+                        tbody {
+                            for device in devices {
+                                tr {
+                                    td { "{device.user_agent}" }
+                                    td {
+                                        match device.credential {
+                                            DeviceCredential::Password => t("devices.credential_password"),
+                                            DeviceCredential::Token => t("devices.credential_token"),
+                                        }
+                                    }
+                                    td { {format_timestamp(device.last_seen_at)} }
+                                    td { "{device.ip.clone().unwrap_or_default()}" }
+                                    td { "{device.request_count}" }
+                                    td {
+                                        if device.revoked_at.is_some() {
+                                            span { class: "badge badge-private", {t("devices.revoked")} }
+                                        } else {
+                                            span { class: "badge badge-public", {t("devices.active")} }
+                                        }
+                                    }
+                                    td {
+                                        if device.revoked_at.is_none() {
+                                            form { action: "/web/settings/devices/{device.id}/revoke", method: "post", class: "inline-form",
+                                                button { r#type: "submit", class: "btn btn-sm btn-danger", {t("devices.revoke")} }
+                                            }
+                                        }
+                                        form { action: "/web/settings/devices/{device.id}/remove", method: "post", class: "inline-form",
+                                            button { r#type: "submit", class: "btn btn-sm btn-secondary", {t("devices.remove")} }
+                                        }
+                                    }
+                                }
+                            }
+                        }
+                    }
+                    p { class: "form-hint", {t("devices.hint")} }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,89 @@
+use dioxus::prelude::*;
+
+use crate::edit_conflicts::ConflictEntry;
+use crate::models::{Calendar, Event, User};
+use crate::ui::layouts::BaseLayout;
+use crate::ui::{format_timestamp, t, t_with, Capabilities};
+
+use super::field_value;
+
+/// Conflict copies of an event, each next to the stored event, to keep one of the two
+#[component]
+pub fn EditConflictsPage(
+    current_user: User,
+    capabilities: Capabilities,
+    event: Event,
+    calendar: Calendar,
+    can_resolve: bool,
+    entries: Vec<ConflictEntry>,
+) -> Element {
+    rsx! {
+        BaseLayout {
+            current_user: Some(current_user),
+            capabilities: capabilities.clone(),
+            title: Some(t("edit_conflicts.page_title")),
+
+            div { class: "page-header",
+                div {
+                    h1 { {t_with("edit_conflicts.heading", &[("title", &event.title)])} }
+                    p { class: "subtitle", {t_with("edit_conflicts.subtitle", &[("calendar", &calendar.name)])} }
+                }
+                div { class: "page-actions",
+                    a { href: "/web/events/{event.id}/edit", class: "btn btn-secondary", {t("edit_conflicts.back")} }
+                }
+            }
+
+            if entries.is_empty() {
+                p { class: "text-muted", {t("edit_conflicts.empty")} }
+            }
+
+            for entry in entries {
+                div { class: "history-entry",
+                    div { class: "section-header",
+                        h3 { {format_timestamp(entry.conflict.created_at)} }
+                        span { class: "badge badge-default",
+                            match &entry.conflict.client {
+                                Some(client) => client.clone(),
+                                None => t("edit_conflicts.web"),
+                            }
+                        }
+                    }
+                    if entry.changes.is_empty() {
+                        p { class: "text-muted", {t("edit_conflicts.no_changes")} }
+                    } else {
+                        table { class: "admin-table",
+                            thead {
+                                tr {
+                                    th { {t("history.field")} }
+                                    th { {t("edit_conflicts.current")} }
+                                    th { {t("edit_conflicts.conflicting")} }
+                                }
+                            }
+                            tbody {
+                                for field in entry.changes.iter().copied() {
+                                    tr {
+                                        td { {t(&format!("history.fields.{}", field.name()))} }
+                                        td { {field_value(field, &event)} }
+                                        td { class: "history-added", {field_value(field, &entry.theirs)} }
+                                    }
+                                }
+                            }
+                        }
+                    }
+                    if can_resolve {
+                        div { class: "form-actions",
+                            form { action: "/web/events/{event.id}/edit-conflicts/{entry.conflict.id}/resolve", method: "post", class: "inline-form",
+                                input { r#type: "hidden", name: "keep", value: "current" }
+                                button { r#type: "submit", class: "btn btn-secondary", {t("edit_conflicts.keep_current")} }
+                            }
+                            form { action: "/web/events/{event.id}/edit-conflicts/{entry.conflict.id}/resolve", method: "post", class: "inline-form",
+                                input { r#type: "hidden", name: "keep", value: "conflict" }
+                                button { r#type: "submit", class: "btn btn-primary", {t("edit_conflicts.keep_conflict")} }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,25 @@
+use dioxus::prelude::*;
+
+use crate::ui::layouts::AuthLayout;
+use crate::ui::t;
+
+#[component]
+pub fn ErrorPage(status: u16, title: String, message: String, request_id: Option<String>) -> Element {
+    rsx! {
+        AuthLayout {
+            div { class: "auth-container",
+                div { class: "auth-card error-page",
+                    p { class: "error-status", "{status}" }
+                    h1 { "{title}" }
+                    p { "{message}" }
+                    if let Some(id) = request_id {
+                        p { class: "error-request-id", {t("error.request_id")}, code { "{id}" } }
+                    }
+                    div { class: "auth-link",
+                        a { href: "/web/dashboard", {t("error.back")} }
+                    }
+                }
+            }
+        }
+    }
+}
@@ -1,19 +1,34 @@
 use dioxus::prelude::*;
 use uuid::Uuid;
 
-use crate::models::{User, Calendar, Event};
+use crate::models::{Attachment, User, Calendar, Event};
 use crate::ui::layouts::BaseLayout;
+use crate::ui::{end_input_value, input_type, input_value, t, t_with, Capabilities};
+use crate::validation::{MAX_EVENT_LOCATION_LENGTH, MAX_EVENT_TITLE_LENGTH};
+
+/// Recurrence rules offered in the event form, with the key of their label
+const RECURRENCE_PRESETS: [(&str, &str); 5] = [
+    ("", "event_form.repeat.never"),
+    ("FREQ=DAILY", "event_form.repeat.daily"),
+    ("FREQ=WEEKLY", "event_form.repeat.weekly"),
+    ("FREQ=MONTHLY", "event_form.repeat.monthly"),
+    ("FREQ=YEARLY", "event_form.repeat.yearly"),
+];
 
 #[component]
 pub fn EventFormPage(
     current_user: User,
+    capabilities: Capabilities,
     is_edit: bool,
     event_id: Option<Uuid>,
     event: Option<Event>,
+    attachments: Vec<Attachment>,
     calendars: Vec<Calendar>,
     selected_calendar_id: Option<Uuid>,
+    /// Conflict copies of the edited event waiting to be resolved
+    conflicts: usize,
 ) -> Element {
-    let title = if is_edit { "Edit Event" } else { "New Event" };
+    let title = if is_edit { t("event_form.edit_title") } else { t("event_form.new_title") };
     let action = if is_edit {
         format!("/web/events/{}/edit", event_id.unwrap())
     } else {
@@ -23,44 +38,76 @@ pub fn EventFormPage(
     let event_title = event.as_ref().map(|e| e.title.clone()).unwrap_or_default();
     let description = event.as_ref().and_then(|e| e.description.clone()).unwrap_or_default();
     let location = event.as_ref().and_then(|e| e.location.clone()).unwrap_or_default();
+    let geo_lat = event.as_ref().and_then(|e| e.geo_lat).map(|v| v.to_string()).unwrap_or_default();
+    let geo_lon = event.as_ref().and_then(|e| e.geo_lon).map(|v| v.to_string()).unwrap_or_default();
     let start_time = event.as_ref()
-        .map(|e| e.start_time.format("%Y-%m-%dT%H:%M").to_string())
+        .map(|e| input_value(e.start_time, e.is_all_day))
         .unwrap_or_default();
     let end_time = event.as_ref()
-        .map(|e| e.end_time.format("%Y-%m-%dT%H:%M").to_string())
+        .map(|e| end_input_value(e.end_time, e.is_all_day))
         .unwrap_or_default();
     let is_all_day = event.as_ref().map(|e| e.is_all_day).unwrap_or(false);
+    // Lets saving tell whether the event changed since the form was opened
+    let base_version = event.as_ref().map(|e| e.updated_at.to_rfc3339()).unwrap_or_default();
+    let rrule = event.as_ref().and_then(|e| e.rrule.clone()).unwrap_or_default();
+    let is_recurring = event.as_ref().map(|e| e.is_recurring()).unwrap_or(false);
+    let is_exception = event.as_ref().map(|e| e.is_exception()).unwrap_or(false);
+    // Keep a custom rule from a CalDAV client selectable alongside the presets
+    let custom_rrule = if RECURRENCE_PRESETS.iter().any(|(value, _)| *value == rrule) {
+        None
+    } else {
+        Some(rrule.clone())
+    };
     let calendar_id = event.as_ref()
         .map(|e| e.calendar_id)
         .or(selected_calendar_id)
         .unwrap_or_default();
     let calendars_clone = calendars.clone();
+    let duplicate_calendars = calendars.clone();
+    let attachment_rows: Vec<(Uuid, String, String, String)> = attachments
+        .iter()
+        .map(|a| (a.id, a.url(), a.filename.clone(), format_size(a.size)))
+        .collect();
     
     rsx! {
         BaseLayout {
             current_user: Some(current_user),
+            capabilities: capabilities.clone(),
             title: Some(format!("{} - My CalDAV Server", title)),
             
             div { class: "page-header",
                 h1 { "{title}" }
             }
 
+            if conflicts > 0 {
+                div { class: "conflict-notice",
+                    p { {t_with("edit_conflicts.form_notice", &[("count", &conflicts.to_string())])} }
+                    a { href: "/web/events/{event_id.unwrap_or_default()}/edit-conflicts", class: "btn btn-secondary", {t("edit_conflicts.resolve")} }
+                }
+            }
+
             div { class: "form-container",
                 form { action: "{action}", method: "post",
+                    if is_edit {
+                        input { r#type: "hidden", name: "base_version", value: "{base_version}" }
+                    }
                     div { class: "form-group",
-                        label { r#for: "title", "Event Title *" }
+                        label { r#for: "title", {t("event_form.title")} }
                         input {
                             r#type: "text",
                             id: "title",
                             name: "title",
                             required: true,
+                            maxlength: "{MAX_EVENT_TITLE_LENGTH}",
                             value: "{event_title}",
-                            placeholder: "Enter event title"
+                            placeholder: t("event_form.title_placeholder")
                         }
                     }
                     
                     div { class: "form-group",
-                        label { r#for: "calendar_id", "Calendar *" }
+                        label { r#for: "calendar_id",
+                            if is_edit { {t("event_form.calendar_move")} } else { {t("event_form.calendar")} }
+                        }
                         select { id: "calendar_id", name: "calendar_id", required: true,
                             for cal in calendars_clone {
                                 option { 
@@ -74,9 +121,9 @@ pub fn EventFormPage(
                     
                     div { class: "form-row",
                         div { class: "form-group",
-                            label { r#for: "start_time", "Start Time *" }
+                            label { r#for: "start_time", {t("event_form.start")} }
                             input {
-                                r#type: "datetime-local",
+                                r#type: input_type(is_all_day),
                                 id: "start_time",
                                 name: "start_time",
                                 required: true,
@@ -84,9 +131,9 @@ pub fn EventFormPage(
                             }
                         }
                         div { class: "form-group",
-                            label { r#for: "end_time", "End Time *" }
+                            label { r#for: "end_time", {t("event_form.end")} }
                             input {
-                                r#type: "datetime-local",
+                                r#type: input_type(is_all_day),
                                 id: "end_time",
                                 name: "end_time",
                                 required: true,
@@ -103,39 +150,91 @@ pub fn EventFormPage(
                                 id: "is_all_day",
                                 checked: is_all_day
                             }
-                            span { "All-day event" }
+                            span { {t("event_form.all_day")} }
                         }
                     }
                     
+                    if !is_exception {
+                        div { class: "form-group",
+                            label { r#for: "rrule", {t("event_form.repeat_label")} }
+                            select { id: "rrule", name: "rrule",
+                                for (value, label) in RECURRENCE_PRESETS {
+                                    option {
+                                        value: "{value}",
+                                        selected: value == rrule,
+                                        {t(label)}
+                                    }
+                                }
+                                if let Some(custom) = custom_rrule {
+                                    option { value: "{custom}", selected: true, {t_with("event_form.repeat.custom", &[("rule", &custom)])} }
+                                }
+                            }
+                        }
+                    }
+
+                    if is_recurring {
+                        RecurrenceScopeFields { occurrence: start_time.clone(), all_day: is_all_day }
+                    }
+                    
                     div { class: "form-group",
-                        label { r#for: "location", "Location" }
+                        label { r#for: "location", {t("event_form.location")} }
                         input {
                             r#type: "text",
                             id: "location",
                             name: "location",
+                            maxlength: "{MAX_EVENT_LOCATION_LENGTH}",
                             value: "{location}",
-                            placeholder: "Enter location (optional)"
+                            placeholder: t("event_form.location_placeholder")
+                        }
+                    }
+                    
+                    div { class: "form-row",
+                        div { class: "form-group",
+                            label { r#for: "geo_lat", {t("event_form.latitude")} }
+                            input {
+                                r#type: "number",
+                                id: "geo_lat",
+                                name: "geo_lat",
+                                step: "any",
+                                min: "-90",
+                                max: "90",
+                                value: "{geo_lat}",
+                                placeholder: t("common.optional")
+                            }
+                        }
+                        div { class: "form-group",
+                            label { r#for: "geo_lon", {t("event_form.longitude")} }
+                            input {
+                                r#type: "number",
+                                id: "geo_lon",
+                                name: "geo_lon",
+                                step: "any",
+                                min: "-180",
+                                max: "180",
+                                value: "{geo_lon}",
+                                placeholder: t("common.optional")
+                            }
                         }
                     }
                     
                     div { class: "form-group",
-                        label { r#for: "description", "Description" }
+                        label { r#for: "description", {t("common.description")} }
                         textarea {
                             id: "description",
                             name: "description",
                             rows: "4",
-                            placeholder: "Enter event description (optional)",
+                            placeholder: t("event_form.description_placeholder"),
                             "{description}"
                         }
                     }
                     
                     div { class: "form-actions",
-                        a { href: "/web/dashboard", class: "btn btn-secondary", "Cancel" }
+                        a { href: "/web/dashboard", class: "btn btn-secondary", {t("common.cancel")} }
                         button { r#type: "submit", class: "btn btn-primary", 
                             if is_edit {
-                                "Update Event"
+                                {t("event_form.update")}
                             } else {
-                                "Create Event"
+                                {t("events.create")}
                             }
                         }
                     }
@@ -144,16 +243,102 @@ pub fn EventFormPage(
 
             if is_edit {
                 if let Some(id) = event_id {
+                    div { class: "form-container",
+                        h3 { {t("event_form.attachments")} }
+                        if attachments.is_empty() {
+                            p { class: "form-hint", {t("event_form.no_attachments")} }
+                        }
+                        for (attachment_id, url, filename, size) in attachment_rows {
+                            div { class: "share-item",
+                                a { href: "{url}", "{filename}" }
+                                span { class: "badge", "{size}" }
+                                form {
+                                    action: "/web/attachments/{attachment_id}/delete",
+                                    method: "post",
+                                    class: "inline-form",
+                                    button { r#type: "submit", class: "btn btn-sm btn-danger", {t("shares.remove")} }
+                                }
+                            }
+                        }
+                        form {
+                            action: "/web/events/{id}/attachments",
+                            method: "post",
+                            enctype: "multipart/form-data",
+                            div { class: "form-group",
+                                input { r#type: "file", name: "file", required: true }
+                            }
+                            button { r#type: "submit", class: "btn btn-secondary", {t("event_form.upload")} }
+                        }
+                    }
+
+                    if !is_exception {
+                        div { class: "form-container",
+                            h3 { {t("event_form.duplicate")} }
+                            p { class: "form-hint",
+                                {t("event_form.duplicate_hint")}
+                            }
+                            form {
+                                action: "/web/events/{id}/duplicate",
+                                method: "post",
+                                div { class: "form-group",
+                                    label { r#for: "duplicate_calendar_id", {t("event_form.copy_to")} }
+                                    select { id: "duplicate_calendar_id", name: "calendar_id", required: true,
+                                        for cal in duplicate_calendars {
+                                            option {
+                                                value: "{cal.id}",
+                                                selected: cal.id == calendar_id,
+                                                "{cal.name}"
+                                            }
+                                        }
+                                    }
+                                }
+                                button { r#type: "submit", class: "btn btn-secondary", {t("event_form.duplicate")} }
+                            }
+                        }
+                    }
+
+                    div { class: "form-container",
+                        h3 { {t("history.section")} }
+                        p { class: "form-hint", {t("history.section_hint")} }
+                        a { href: "/web/events/{id}/history", class: "btn btn-secondary", {t("history.view")} }
+                    }
+
+                    if is_recurring {
+                        div { class: "form-container",
+                            h3 { {t("event_form.detach_title")} }
+                            p { class: "form-hint",
+                                {t("event_form.detach_hint")}
+                            }
+                            form {
+                                action: "/web/events/{id}/detach",
+                                method: "post",
+                                div { class: "form-group",
+                                    label { {t("event_form.occurrence_start")} }
+                                    input {
+                                        r#type: input_type(is_all_day),
+                                        name: "occurrence",
+                                        required: true,
+                                        value: "{start_time}"
+                                    }
+                                }
+                                button { r#type: "submit", class: "btn btn-secondary", {t("event_form.detach")} }
+                            }
+                        }
+                    }
+
                     div { class: "danger-zone",
-                        h3 { "Danger Zone" }
-                        p { "Deleting this event cannot be undone." }
+                        h3 { {t("calendar_form.danger_zone")} }
+                        p { {t("event_form.delete_hint")} }
                         form { 
                             action: "/web/events/{id}/delete", 
                             method: "post",
+                            if is_recurring {
+                                RecurrenceScopeFields { occurrence: start_time.clone(), all_day: is_all_day }
+                            }
                             button { 
                                 r#type: "submit", 
                                 class: "btn btn-danger",
-                                "Delete Event" 
+                                {t("event_form.delete")} 
                             }
                         }
                     }
@@ -162,3 +347,41 @@ pub fn EventFormPage(
         }
     }
 }
+
+/// Choice between changing a single occurrence or the whole series
+#[component]
+fn RecurrenceScopeFields(occurrence: String, all_day: bool) -> Element {
+    rsx! {
+        div { class: "form-group",
+            label { {t("event_form.apply_to")} }
+            label { class: "checkbox-label",
+                input { r#type: "radio", name: "scope", value: "all", checked: true }
+                span { {t("event_form.scope_all")} }
+            }
+            label { class: "checkbox-label",
+                input { r#type: "radio", name: "scope", value: "this" }
+                span { {t("event_form.scope_this")} }
+            }
+        }
+        div { class: "form-group",
+            label { {t("event_form.occurrence_start")} }
+            input {
+                r#type: input_type(all_day),
+                name: "occurrence",
+                value: "{occurrence}"
+            }
+            p { class: "form-hint", {t("event_form.scope_hint")} }
+        }
+    }
+}
+
+/// Human-readable file size
+fn format_size(bytes: i64) -> String {
+    if bytes >= 1024 * 1024 {
+        format!("{:.1} MB", bytes as f64 / (1024.0 * 1024.0))
+    } else if bytes >= 1024 {
+        format!("{:.1} KB", bytes as f64 / 1024.0)
+    } else {
+        format!("{} B", bytes)
+    }
+}
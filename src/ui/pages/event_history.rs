@@ -0,0 +1,198 @@
+use dioxus::prelude::*;
+use uuid::Uuid;
+
+use crate::event_history::{EventField, EventVersion, HistoryEntry, VersionAction};
+use crate::models::{Calendar, Event, User};
+use crate::ui::layouts::BaseLayout;
+use crate::ui::{format_timestamp, t, t_with, Capabilities};
+
+/// Earlier versions of an event, newest first, each restorable by those who may edit it
+#[component]
+pub fn EventHistoryPage(
+    current_user: User,
+    capabilities: Capabilities,
+    event_id: Uuid,
+    title: String,
+    calendar: Calendar,
+    /// Whether the event is deleted, so restoring brings it back
+    deleted: bool,
+    can_restore: bool,
+    entries: Vec<HistoryEntry>,
+) -> Element {
+    let back = if deleted {
+        format!("/web/calendars/{}/deleted", calendar.id)
+    } else {
+        format!("/web/events/{}/edit", event_id)
+    };
+
+    rsx! {
+        BaseLayout {
+            current_user: Some(current_user),
+            capabilities: capabilities.clone(),
+            title: Some(t("history.page_title")),
+
+            div { class: "page-header",
+                div {
+                    h1 { {t_with("history.heading", &[("title", &title)])} }
+                    p { class: "subtitle",
+                        if deleted {
+                            {t_with("history.deleted_from", &[("calendar", &calendar.name)])}
+                        } else {
+                            {t_with("history.in_calendar", &[("calendar", &calendar.name)])}
+                        }
+                    }
+                }
+                div { class: "page-actions",
+                    a { href: "{back}", class: "btn btn-secondary", {t("history.back")} }
+                }
+            }
+
+            if entries.is_empty() {
+                p { class: "text-muted", {t("history.empty")} }
+            }
+
+            for entry in entries {
+                div { class: "history-entry",
+                    div { class: "section-header",
+                        h3 { {format_timestamp(entry.version.recorded_at)} }
+                        ActionBadge { action: entry.version.action }
+                    }
+                    if entry.version.action == VersionAction::Deleted {
+                        p { {t_with("history.deleted_at", &[("start", &format_timestamp(entry.version.event.start_time))])} }
+                    } else if let Some(after) = &entry.after {
+                        if entry.changes.is_empty() {
+                            p { class: "text-muted", {t("history.no_changes")} }
+                        } else {
+                            table { class: "admin-table",
+                                thead {
+                                    tr {
+                                        th { {t("history.field")} }
+                                        th { {t("history.before")} }
+                                        th { {t("history.after")} }
+                                    }
+                                }
+                                tbody {
+                                    for field in entry.changes.iter().copied() {
+                                        tr {
+                                            td { {t(&format!("history.fields.{}", field.name()))} }
+                                            td { class: "history-removed", {field_value(field, &entry.version.event)} }
+                                            td { class: "history-added", {field_value(field, after)} }
+                                        }
+                                    }
+                                }
+                            }
+                        }
+                    }
+                    if can_restore {
+                        form { action: "/web/events/{event_id}/history/{entry.version.id}/restore", method: "post", class: "inline-form",
+                            button { r#type: "submit", class: "btn btn-secondary",
+                                if entry.version.action == VersionAction::Deleted {
+                                    {t("history.undelete")}
+                                } else {
+                                    {t("history.restore")}
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Deleted events of a calendar, as they were when they were deleted
+#[component]
+pub fn DeletedEventsPage(
+    current_user: User,
+    capabilities: Capabilities,
+    calendar: Calendar,
+    can_restore: bool,
+    deleted: Vec<EventVersion>,
+) -> Element {
+    rsx! {
+        BaseLayout {
+            current_user: Some(current_user),
+            capabilities: capabilities.clone(),
+            title: Some(t("history.deleted_page_title")),
+
+            div { class: "page-header",
+                div {
+                    h1 { {t_with("history.deleted_heading", &[("calendar", &calendar.name)])} }
+                    p { class: "subtitle", {t("history.deleted_subtitle")} }
+                }
+                div { class: "page-actions",
+                    a { href: "/web/calendars/{calendar.id}", class: "btn btn-secondary", {t("history.back_to_calendar")} }
+                }
+            }
+
+            if deleted.is_empty() {
+                p { class: "text-muted", {t("history.no_deleted")} }
+            } else {
+                table { class: "admin-table",
+                    thead {
+                        tr {
+                            th { {t("history.fields.title")} }
+                            th { {t("history.fields.start_time")} }
+                            th { {t("history.deleted_on")} }
+                            th { {t("admin.actions")} }
+                        }
+                    }
+                    tbody {
+                        for version in deleted {
+                            tr {
+                                td { "{version.event.title}" }
+                                td { {format_timestamp(version.event.start_time)} }
+                                td { {format_timestamp(version.recorded_at)} }
+                                td {
+                                    div { class: "form-actions",
+                                        a { href: "/web/events/{version.event.id}/history", class: "btn btn-secondary", {t("history.view")} }
+                                        if can_restore {
+                                            form { action: "/web/events/{version.event.id}/history/{version.id}/restore", method: "post", class: "inline-form",
+                                                button { r#type: "submit", class: "btn btn-primary", {t("history.undelete")} }
+                                            }
+                                        }
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Whether a version was left by a change or a deletion
+#[component]
+fn ActionBadge(action: VersionAction) -> Element {
+    let (class, label) = match action {
+        VersionAction::Updated => ("badge badge-default", t("history.changed")),
+        VersionAction::Deleted => ("badge badge-private", t("history.deleted")),
+    };
+
+    rsx! {
+        span { class: "{class}", "{label}" }
+    }
+}
+
+/// A field of an event as it is shown in a change
+pub fn field_value(field: EventField, event: &Event) -> String {
+    let none = || t("common.none");
+    match field {
+        EventField::Title => event.title.clone(),
+        EventField::Description => event.description.clone().unwrap_or_else(none),
+        EventField::Location => event.location.clone().unwrap_or_else(none),
+        EventField::Geo => event.geo_lat.zip(event.geo_lon)
+            .map(|(lat, lon)| format!("{}, {}", lat, lon))
+            .unwrap_or_else(none),
+        EventField::StartTime => format_timestamp(event.start_time),
+        EventField::EndTime => format_timestamp(event.end_time),
+        EventField::IsAllDay => t(if event.is_all_day { "history.yes" } else { "history.no" }),
+        EventField::Rrule => event.rrule.clone().unwrap_or_else(none),
+        EventField::Exdates if event.exdates.is_empty() => none(),
+        EventField::Exdates => event.exdates.iter().map(|date| format_timestamp(*date)).collect::<Vec<_>>().join(", "),
+        EventField::AlarmMinutes => event.alarm_minutes
+            .map(|minutes| t_with("history.minutes_before", &[("count", &minutes.to_string())]))
+            .unwrap_or_else(none),
+    }
+}
@@ -4,11 +4,13 @@ use uuid::Uuid;
 
 use crate::models::{User, Calendar, Event};
 use crate::ui::layouts::BaseLayout;
+use crate::ui::{t, Capabilities};
 use crate::ui::components::EventListItem;
 
 #[component]
 pub fn EventsPage(
     current_user: User,
+    capabilities: Capabilities,
     events: Vec<Event>,
     calendars: Vec<Calendar>,
     calendar_names: HashMap<Uuid, String>,
@@ -17,38 +19,39 @@ pub fn EventsPage(
     rsx! {
         BaseLayout {
             current_user: Some(current_user),
-            title: Some("Events - My CalDAV Server".to_string()),
+            capabilities: capabilities.clone(),
+            title: Some(t("events.page_title")),
             
             div { class: "page-header",
-                h1 { "All Events" }
-                a { href: "/web/events/new", class: "btn btn-primary", "+ New Event" }
+                h1 { {t("events.heading")} }
+                a { href: "/web/events/new", class: "btn btn-primary", {t("events.new")} }
             }
             
             div { class: "filter-bar",
                 form { method: "get", action: "/web/events",
-                    label { r#for: "calendar", "Filter by Calendar:" }
+                    label { r#for: "calendar", {t("events.filter_label")} }
                     select { 
                         id: "calendar", 
                         name: "calendar",
-                        option { value: "", "All Calendars" }
+                        option { value: "", {t("events.all_calendars")} }
                         for cal in calendars.clone() {
                             option { 
                                 value: "{cal.id}",
-                                selected: selected_calendar.map_or(false, |id| id == cal.id),
+                                selected: selected_calendar == Some(cal.id),
                                 "{cal.name}"
                             }
                         }
                     }
-                    button { r#type: "submit", class: "btn btn-sm btn-secondary", "Filter" }
+                    button { r#type: "submit", class: "btn btn-sm btn-secondary", {t("events.filter")} }
                 }
             }
             
             if events.is_empty() {
                 div { class: "empty-state",
                     div { class: "empty-icon", "📌" }
-                    h2 { "No events yet" }
-                    p { "Create your first event to get started." }
-                    a { href: "/web/events/new", class: "btn btn-primary", "Create Event" }
+                    h2 { {t("events.empty_title")} }
+                    p { {t("events.empty_hint")} }
+                    a { href: "/web/events/new", class: "btn btn-primary", {t("events.create")} }
                 }
             } else {
                 div { class: "event-list",
@@ -0,0 +1,183 @@
+use dioxus::prelude::*;
+
+use crate::models::{Calendar, Group, GroupMember, GroupRole, User, UserGroup};
+use crate::ui::layouts::BaseLayout;
+use crate::ui::{t, Capabilities};
+
+#[component]
+pub fn GroupsPage(
+    current_user: User,
+    capabilities: Capabilities,
+    groups: Vec<UserGroup>,
+) -> Element {
+    rsx! {
+        BaseLayout {
+            current_user: Some(current_user),
+            capabilities: capabilities.clone(),
+            title: Some(t("groups.page_title")),
+
+            div { class: "page-header",
+                div {
+                    h1 { {t("nav.groups")} }
+                    p { class: "subtitle", {t("groups.subtitle")} }
+                }
+            }
+
+            div { class: "dashboard-section",
+                if groups.is_empty() {
+                    p { class: "text-muted", {t("groups.empty")} }
+                } else {
+                    table { class: "admin-table",
+                        thead {
+                            tr {
+                                th { {t("admin.name")} }
+                                th { {t("groups.your_role")} }
+                                th { "" }
+                            }
+                        }
+                        tbody {
+                            for entry in groups {
+                                tr {
+                                    td { "{entry.group.name}" }
+                                    td { {t(&format!("groups.role.{}", entry.role.as_str()))} }
+                                    td {
+                                        a { href: "/web/groups/{entry.group.id}", class: "btn btn-outline btn-sm", {t("groups.open")} }
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+
+            div { class: "dashboard-section",
+                h2 { {t("groups.new")} }
+                form { action: "/web/groups", method: "post", class: "inline-form",
+                    input { r#type: "text", name: "name", placeholder: t("groups.name_placeholder"), required: true }
+                    button { r#type: "submit", class: "btn btn-primary", {t("groups.create")} }
+                }
+            }
+        }
+    }
+}
+
+#[component]
+pub fn GroupDetailPage(
+    current_user: User,
+    capabilities: Capabilities,
+    group: Group,
+    role: GroupRole,
+    members: Vec<GroupMember>,
+    calendars: Vec<Calendar>,
+) -> Element {
+    let group_id = group.id;
+    let is_admin = role == GroupRole::Admin;
+    let current_user_id = current_user.id;
+
+    rsx! {
+        BaseLayout {
+            current_user: Some(current_user),
+            capabilities: capabilities.clone(),
+            title: Some(format!("{} - My CalDAV Server", group.name)),
+
+            div { class: "page-header",
+                div {
+                    h1 { "{group.name}" }
+                    p { class: "subtitle",
+                        {t("groups.principal")}
+                        code { "/principals/groups/{group_id}/" }
+                    }
+                }
+            }
+
+            div { class: "dashboard-section",
+                h2 { {t("nav.calendars")} }
+                if calendars.is_empty() {
+                    p { class: "text-muted", {t("groups.no_calendars")} }
+                } else {
+                    ul { class: "calendar-list",
+                        for calendar in calendars {
+                            li {
+                                a { href: "/web/calendars/{calendar.id}", "{calendar.name}" }
+                            }
+                        }
+                    }
+                }
+                if is_admin {
+                    form { action: "/web/groups/{group_id}/calendars", method: "post", class: "inline-form",
+                        input { r#type: "text", name: "name", placeholder: t("groups.calendar_name_placeholder"), required: true }
+                        button { r#type: "submit", class: "btn btn-primary", {t("groups.create_calendar")} }
+                    }
+                }
+            }
+
+            div { class: "dashboard-section",
+                h2 { {t("groups.members")} }
+                table { class: "admin-table",
+                    thead {
+                        tr {
+                            th { {t("admin.name")} }
+                            th { {t("admin.email")} }
+                            th { {t("admin.role_label")} }
+                            th { "" }
+                        }
+                    }
+                    tbody {
+                        for member in members {
+                            tr {
+                                td { "{member.name}" }
+                                td { "{member.email}" }
+                                td {
+                                    if is_admin {
+                                        form { action: "/web/groups/{group_id}/members/{member.user_id}/role", method: "post", class: "inline-form",
+                                            select { name: "role",
+                                                option { value: "member", selected: member.role == GroupRole::Member, {t("groups.role.member")} }
+                                                option { value: "admin", selected: member.role == GroupRole::Admin, {t("groups.role.admin")} }
+                                            }
+                                            button { r#type: "submit", class: "btn btn-outline btn-sm", {t("groups.save")} }
+                                        }
+                                    } else {
+                                        {t(&format!("groups.role.{}", member.role.as_str()))}
+                                    }
+                                }
+                                td {
+                                    if is_admin || member.user_id == current_user_id {
+                                        form { action: "/web/groups/{group_id}/members/{member.user_id}/remove", method: "post", class: "inline-form",
+                                            button { r#type: "submit", class: "btn btn-danger btn-sm",
+                                                if member.user_id == current_user_id { {t("groups.leave")} } else { {t("shares.remove")} }
+                                            }
+                                        }
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+                if is_admin {
+                    form { action: "/web/groups/{group_id}/members", method: "post", class: "inline-form",
+                        input { r#type: "email", name: "email", placeholder: t("groups.member_email_placeholder"), required: true }
+                        select { name: "role",
+                            option { value: "member", {t("groups.role.member")} }
+                            option { value: "admin", {t("groups.role.admin")} }
+                        }
+                        button { r#type: "submit", class: "btn btn-primary", {t("groups.add_member")} }
+                    }
+                }
+            }
+
+            if is_admin {
+                div { class: "danger-zone",
+                    h3 { {t("groups.delete")} }
+                    p { {t("groups.delete_hint")} }
+                    form { action: "/web/groups/{group_id}/delete", method: "post",
+                        button { r#type: "submit", class: "btn btn-danger", {t("groups.delete")} }
+                    }
+                }
+            }
+
+            div { class: "back-link",
+                a { href: "/web/groups", class: "btn btn-outline", {t("groups.back")} }
+            }
+        }
+    }
+}
@@ -0,0 +1,106 @@
+use dioxus::prelude::*;
+
+use crate::models::User;
+use crate::takeout::TakeoutSummary;
+use crate::ui::layouts::BaseLayout;
+use crate::ui::{t, t_with, Capabilities};
+
+/// Guided migration from Google Calendar; shows what was imported once an archive
+/// was uploaded
+#[component]
+pub fn ImportGooglePage(
+    current_user: User,
+    capabilities: Capabilities,
+    summary: Option<TakeoutSummary>,
+) -> Element {
+    rsx! {
+        BaseLayout {
+            current_user: Some(current_user),
+            capabilities: capabilities.clone(),
+            title: Some(t("import_google.page_title")),
+
+            div { class: "page-header",
+                div {
+                    h1 { {t("import_google.heading")} }
+                    p { class: "subtitle", {t("import_google.subtitle")} }
+                }
+            }
+
+            if let Some(summary) = summary {
+                div { class: "dashboard-section",
+                    h2 { {t("import_google.result")} }
+                    table { class: "admin-table",
+                        thead {
+                            tr {
+                                th { {t("import_google.calendar")} }
+                                th { {t("import_google.imported")} }
+                                th { {t("import_google.failed")} }
+                                th { {t("import_google.skipped")} }
+                            }
+                        }
+                        tbody {
+                            for calendar in summary.calendars {
+                                tr {
+                                    td {
+                                        span { class: "import-calendar",
+                                            span { class: "calendar-color-indicator", style: "background-color: {calendar.color}" }
+                                            match calendar.calendar_id {
+                                                Some(id) => rsx! { a { href: "/web/calendars/{id}", "{calendar.name}" } },
+                                                None => rsx! { "{calendar.name}" },
+                                            }
+                                        }
+                                    }
+                                    td { "{calendar.events.imported}" }
+                                    td { "{calendar.events.failed}" }
+                                    td {
+                                        {calendar.skipped.iter().map(|(kind, count)| format!("{} × {}", count, kind)).collect::<Vec<_>>().join(", ")}
+                                    }
+                                }
+                                if !calendar.errors.is_empty() {
+                                    tr {
+                                        td { colspan: "4",
+                                            ul { class: "import-errors",
+                                                for error in calendar.errors {
+                                                    li { "{error}" }
+                                                }
+                                            }
+                                        }
+                                    }
+                                }
+                            }
+                        }
+                    }
+                    if !summary.skipped_files.is_empty() {
+                        p { class: "text-muted",
+                            {t_with("import_google.skipped_files", &[("count", &summary.skipped_files.len().to_string())])}
+                        }
+                    }
+                    a { href: "/web/calendars", class: "btn btn-primary", {t("import_google.done")} }
+                }
+            }
+
+            div { class: "dashboard-section",
+                h2 { {t("import_google.steps")} }
+                ol { class: "import-steps",
+                    li {
+                        {t("import_google.step_takeout")}
+                        " "
+                        a { href: "https://takeout.google.com/", target: "_blank", rel: "noopener", "takeout.google.com" }
+                    }
+                    li { {t("import_google.step_select")} }
+                    li { {t("import_google.step_download")} }
+                    li { {t("import_google.step_upload")} }
+                }
+                form {
+                    action: "/web/import/google",
+                    method: "post",
+                    enctype: "multipart/form-data",
+                    class: "inline-form",
+                    input { r#type: "file", name: "file", accept: ".zip,application/zip", required: true }
+                    button { r#type: "submit", class: "btn btn-primary", {t("import_google.import")} }
+                }
+                p { class: "text-muted", {t("import_google.hint")} }
+            }
+        }
+    }
+}
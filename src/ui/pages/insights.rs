@@ -0,0 +1,101 @@
+use dioxus::prelude::*;
+use uuid::Uuid;
+
+use crate::models::{User, Calendar, ForecastWeek};
+use crate::ui::layouts::BaseLayout;
+use crate::ui::{month_abbr, t, t_with, Capabilities};
+
+/// Forecast lengths offered on the insights page
+const WEEK_OPTIONS: [u32; 4] = [4, 8, 12, 26];
+
+#[component]
+pub fn InsightsPage(
+    current_user: User,
+    capabilities: Capabilities,
+    calendars: Vec<Calendar>,
+    selected_calendar: Option<Uuid>,
+    weeks: u32,
+    forecast: Vec<ForecastWeek>,
+) -> Element {
+    // Scale bars against the busiest week
+    let max_hours = forecast.iter().map(|w| w.hours).fold(0.0_f64, f64::max);
+    let total_hours: f64 = forecast.iter().map(|w| w.hours).sum();
+    let average_hours = if forecast.is_empty() { 0.0 } else { total_hours / forecast.len() as f64 };
+    let rows: Vec<(String, f64, String)> = forecast
+        .iter()
+        .map(|w| (
+            format!("{} {}", month_abbr(&w.week_start), w.week_start.format("%d")),
+            bar_width(w.hours, max_hours),
+            t_with("insights.week_value", &[
+                ("hours", &format!("{:.1}", w.hours)),
+                ("count", &w.event_count.to_string()),
+            ]),
+        ))
+        .collect();
+    
+    rsx! {
+        BaseLayout {
+            current_user: Some(current_user),
+            capabilities: capabilities.clone(),
+            title: Some(t("insights.page_title")),
+            
+            div { class: "page-header",
+                h1 { {t("nav.insights")} }
+            }
+            
+            div { class: "filter-bar",
+                form { method: "get", action: "/web/insights",
+                    label { r#for: "calendar", {t("insights.calendar")} }
+                    select { id: "calendar", name: "calendar",
+                        option { value: "", {t("events.all_calendars")} }
+                        for cal in calendars {
+                            option {
+                                value: "{cal.id}",
+                                selected: selected_calendar == Some(cal.id),
+                                "{cal.name}"
+                            }
+                        }
+                    }
+                    label { r#for: "weeks", {t("insights.weeks")} }
+                    select { id: "weeks", name: "weeks",
+                        for option_weeks in WEEK_OPTIONS {
+                            option {
+                                value: "{option_weeks}",
+                                selected: option_weeks == weeks,
+                                "{option_weeks}"
+                            }
+                        }
+                    }
+                    button { r#type: "submit", class: "btn btn-sm btn-secondary", {t("insights.show")} }
+                }
+            }
+            
+            div { class: "dashboard-section",
+                h2 { {t("insights.forecast")} }
+                p { class: "form-hint",
+                    {t_with("insights.forecast_hint", &[
+                        ("total", &format!("{:.1}", total_hours)),
+                        ("average", &format!("{:.1}", average_hours)),
+                    ])}
+                }
+                
+                div { class: "forecast-chart",
+                    for (label, width, value) in rows {
+                        div { class: "forecast-row",
+                            span { class: "forecast-label", "{label}" }
+                            div { class: "forecast-track",
+                                div { class: "forecast-bar", style: "width: {width}%" }
+                            }
+                            span { class: "forecast-value", "{value}" }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Bar width in percent of the busiest week
+fn bar_width(hours: f64, max_hours: f64) -> f64 {
+    if max_hours > 0.0 { (hours / max_hours * 100.0).round() } else { 0.0 }
+}
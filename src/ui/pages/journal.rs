@@ -0,0 +1,92 @@
+use dioxus::prelude::*;
+
+use crate::models::{Calendar, Journal, User};
+use crate::ui::layouts::BaseLayout;
+use crate::ui::{month_abbr, t, t_with, Capabilities};
+
+#[component]
+pub fn JournalPage(
+    current_user: User,
+    capabilities: Capabilities,
+    calendar: Calendar,
+    journals: Vec<Journal>,
+    /// Whether the user may add and delete entries
+    can_edit: bool,
+) -> Element {
+    let calendar_id = calendar.id;
+    let calendar_name = calendar.name.clone();
+
+    rsx! {
+        BaseLayout {
+            current_user: Some(current_user),
+            capabilities: capabilities.clone(),
+            title: Some(t_with("journal.page_title", &[("calendar", &calendar_name)])),
+
+            div { class: "page-header",
+                div {
+                    h1 { {t("calendar_detail.journal")} }
+                    p { class: "subtitle", {t_with("journal.subtitle", &[("calendar", &calendar_name)])} }
+                }
+            }
+
+            if can_edit {
+                div { class: "dashboard-section",
+                    h2 { {t("journal.new_entry")} }
+                    form { action: "/web/calendars/{calendar_id}/journal", method: "post",
+                        div { class: "form-row",
+                            div { class: "form-group",
+                                label { r#for: "summary", {t("journal.title")} }
+                                input { r#type: "text", id: "summary", name: "summary", placeholder: t("journal.title_placeholder") }
+                            }
+                            div { class: "form-group",
+                                label { r#for: "date", {t("journal.date")} }
+                                input { r#type: "date", id: "date", name: "date" }
+                            }
+                        }
+                        div { class: "form-group",
+                            label { r#for: "description", {t("journal.note")} }
+                            textarea { id: "description", name: "description", rows: "4" }
+                        }
+                        div { class: "form-actions",
+                            button { r#type: "submit", class: "btn btn-primary", {t("journal.add")} }
+                        }
+                    }
+                }
+            }
+
+            div { class: "dashboard-section",
+                if journals.is_empty() {
+                    p { class: "text-muted", {t("journal.empty")} }
+                } else {
+                    div { class: "event-list",
+                        for journal in journals {
+                            div { class: "event-item",
+                                div { class: "event-date",
+                                    span { class: "event-day", {journal.dtstart.map(|d| d.format("%d").to_string()).unwrap_or_default()} }
+                                    span { class: "event-month", {journal.dtstart.map(|d| format!("{} {}", month_abbr(&d), d.format("%Y"))).unwrap_or_default()} }
+                                }
+                                div { class: "event-info",
+                                    h4 { {journal.summary.clone().unwrap_or_else(|| t("journal.untitled"))} }
+                                    if let Some(description) = &journal.description {
+                                        p { "{description}" }
+                                    }
+                                }
+                                if can_edit {
+                                    div { class: "event-actions",
+                                        form { action: "/web/calendars/{calendar_id}/journal/{journal.id}/delete", method: "post", class: "inline-form",
+                                            button { r#type: "submit", class: "btn btn-sm btn-danger", {t("common.delete")} }
+                                        }
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+
+            div { class: "back-link",
+                a { href: "/web/calendars/{calendar_id}", class: "btn btn-outline", {t_with("journal.back", &[("calendar", &calendar_name)])} }
+            }
+        }
+    }
+}
@@ -1,40 +1,49 @@
 use dioxus::prelude::*;
 
+use crate::ui::components::FlashMessage;
 use crate::ui::layouts::AuthLayout;
+use crate::ui::t;
 
 #[component]
-pub fn LoginPage(flash_message: Option<String>, flash_type: Option<String>) -> Element {
+pub fn LoginPage(oidc_enabled: bool, registration_open: bool) -> Element {
     rsx! {
         AuthLayout {
             div { class: "auth-container",
                 div { class: "auth-card",
-                    h1 { "Login" }
+                    h1 { {t("auth.login")} }
+                    FlashMessage {}
                     form { action: "/web/login", method: "post",
                         div { class: "form-group",
-                            label { r#for: "email", "Email" }
+                            label { r#for: "email", {t("admin.email")} }
                             input {
                                 r#type: "email",
                                 id: "email",
                                 name: "email",
                                 required: true,
-                                placeholder: "Enter your email"
+                                placeholder: t("auth.email_placeholder")
                             }
                         }
                         div { class: "form-group",
-                            label { r#for: "password", "Password" }
+                            label { r#for: "password", {t("auth.password")} }
                             input {
                                 r#type: "password",
                                 id: "password",
                                 name: "password",
                                 required: true,
-                                placeholder: "Enter your password"
+                                placeholder: t("auth.password_placeholder")
                             }
                         }
-                        button { r#type: "submit", class: "btn btn-primary", "Login" }
+                        button { r#type: "submit", class: "btn btn-primary", {t("auth.login")} }
                     }
-                    p { class: "auth-link",
-                        "Don't have an account? "
-                        a { href: "/web/register", "Register here" }
+                    if oidc_enabled {
+                        div { class: "auth-divider", {t("auth.or")} }
+                        a { href: "/web/login/oidc", class: "btn btn-outline btn-block", {t("auth.sso")} }
+                    }
+                    if registration_open {
+                        p { class: "auth-link",
+                            {t("auth.no_account")}
+                            a { href: "/web/register", {t("auth.register_here")} }
+                        }
                     }
                 }
             }
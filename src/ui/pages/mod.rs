@@ -6,7 +6,21 @@ mod calendar_detail;
 mod calendar_form;
 mod events;
 mod event_form;
+mod event_history;
+mod edit_conflicts;
 mod admin;
+mod backups;
+mod insights;
+mod schedule;
+mod settings;
+mod groups;
+mod contacts;
+mod import_google;
+mod journal;
+mod setup;
+mod sync_log;
+mod devices;
+mod error;
 
 pub use login::*;
 pub use register::*;
@@ -16,4 +30,18 @@ pub use calendar_detail::*;
 pub use calendar_form::*;
 pub use events::*;
 pub use event_form::*;
+pub use event_history::*;
+pub use edit_conflicts::*;
 pub use admin::*;
+pub use backups::*;
+pub use insights::*;
+pub use schedule::*;
+pub use settings::*;
+pub use groups::*;
+pub use contacts::*;
+pub use import_google::*;
+pub use journal::*;
+pub use setup::*;
+pub use sync_log::*;
+pub use devices::*;
+pub use error::*;
@@ -1,60 +1,64 @@
 use dioxus::prelude::*;
 
+use crate::ui::components::{DetectedTimeZone, FlashMessage};
 use crate::ui::layouts::AuthLayout;
+use crate::ui::t;
 
 #[component]
-pub fn RegisterPage(flash_message: Option<String>, flash_type: Option<String>) -> Element {
+pub fn RegisterPage() -> Element {
     rsx! {
         AuthLayout {
             div { class: "auth-container",
                 div { class: "auth-card",
-                    h1 { "Register" }
+                    h1 { {t("auth.register")} }
+                    FlashMessage {}
                     form { action: "/web/register", method: "post",
                         div { class: "form-group",
-                            label { r#for: "name", "Name" }
+                            label { r#for: "name", {t("admin.name")} }
                             input {
                                 r#type: "text",
                                 id: "name",
                                 name: "name",
                                 required: true,
-                                placeholder: "Enter your name"
+                                placeholder: t("auth.name_placeholder")
                             }
                         }
                         div { class: "form-group",
-                            label { r#for: "email", "Email" }
+                            label { r#for: "email", {t("admin.email")} }
                             input {
                                 r#type: "email",
                                 id: "email",
                                 name: "email",
                                 required: true,
-                                placeholder: "Enter your email"
+                                placeholder: t("auth.email_placeholder")
                             }
                         }
                         div { class: "form-group",
-                            label { r#for: "password", "Password" }
+                            label { r#for: "password", {t("auth.password")} }
                             input {
                                 r#type: "password",
                                 id: "password",
                                 name: "password",
                                 required: true,
-                                placeholder: "Enter your password"
+                                placeholder: t("auth.password_placeholder")
                             }
                         }
                         div { class: "form-group",
-                            label { r#for: "confirm_password", "Confirm Password" }
+                            label { r#for: "confirm_password", {t("auth.confirm_password")} }
                             input {
                                 r#type: "password",
                                 id: "confirm_password",
                                 name: "confirm_password",
                                 required: true,
-                                placeholder: "Confirm your password"
+                                placeholder: t("auth.confirm_placeholder")
                             }
                         }
-                        button { r#type: "submit", class: "btn btn-primary", "Register" }
+                        DetectedTimeZone {}
+                        button { r#type: "submit", class: "btn btn-primary", {t("auth.register")} }
                     }
                     p { class: "auth-link",
-                        "Already have an account? "
-                        a { href: "/web/login", "Login here" }
+                        {t("auth.have_account")}
+                        a { href: "/web/login", {t("auth.login_here")} }
                     }
                 }
             }
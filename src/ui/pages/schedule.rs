@@ -0,0 +1,258 @@
+use chrono::{Datelike, Days, Months, NaiveDate, NaiveTime, Timelike};
+use dioxus::prelude::*;
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::models::{Event, User};
+use crate::ui::layouts::BaseLayout;
+use crate::ui::{event_days, event_time, month_abbr, t, time_of_day, user_settings, weekday_abbr, Capabilities};
+
+/// Layout of the schedule grid
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ScheduleView {
+    #[default]
+    Month,
+    Week,
+}
+
+impl ScheduleView {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ScheduleView::Month => "month",
+            ScheduleView::Week => "week",
+        }
+    }
+
+    /// First day and number of days of the grid showing `date`, in whole weeks starting
+    /// on `week_start`
+    pub fn days(&self, date: NaiveDate, week_start: chrono::Weekday) -> (NaiveDate, u64) {
+        let week_of = |day: NaiveDate| day - Days::new(day.weekday().days_since(week_start) as u64);
+        match self {
+            ScheduleView::Month => {
+                let first = date.with_day(1).unwrap_or(date);
+                let last = first + Months::new(1) - Days::new(1);
+                let start = week_of(first);
+                (start, ((week_of(last) - start).num_days() + 7) as u64)
+            }
+            ScheduleView::Week => (week_of(date), 7),
+        }
+    }
+
+    /// The date one grid before or after `date`
+    fn step(&self, date: NaiveDate, forward: bool) -> NaiveDate {
+        match (self, forward) {
+            (ScheduleView::Month, true) => date + Months::new(1),
+            (ScheduleView::Month, false) => date - Months::new(1),
+            (ScheduleView::Week, true) => date + Days::new(7),
+            (ScheduleView::Week, false) => date - Days::new(7),
+        }
+    }
+}
+
+/// Link, label and class of an event in a grid cell
+#[derive(Clone, PartialEq)]
+struct GridEvent {
+    id: Uuid,
+    label: String,
+    class: &'static str,
+}
+
+/// Class, start of a new event, and events of one hour of a day in the week grid
+type HourSlot = (&'static str, String, Vec<GridEvent>);
+
+/// Month or week grid of the events in the user's visible calendars. Weeks start on the
+/// user's first day of the week, and working hours and days stand out. Events show on
+/// every day they cover: all-day events ahead of the day's other events in the month and
+/// in a row of their own above the hours of the week, and events running past midnight
+/// continue from the first hour of the following days.
+#[component]
+pub fn SchedulePage(
+    current_user: User,
+    capabilities: Capabilities,
+    view: ScheduleView,
+    date: NaiveDate,
+    today: NaiveDate,
+    events: Vec<Event>,
+) -> Element {
+    let settings = user_settings();
+    let hours = settings.working_hours.clone();
+    let (first_day, day_count) = view.days(date, settings.week_start);
+    let days: Vec<NaiveDate> = first_day.iter_days().take(day_count as usize).collect();
+
+    let covers = |event: &Event, day: NaiveDate| {
+        let (first, last) = event_days(event);
+        first <= day && day <= last
+    };
+    let events_on = |day: NaiveDate, hour: Option<u32>| -> Vec<GridEvent> {
+        events.iter()
+            .filter(|event| !event.is_all_day && covers(event, day))
+            .filter_map(|event| {
+                let start = event_time(event.start_time, false);
+                if start.date_naive() == day {
+                    let in_cell = hour.is_none_or(|h| start.hour() == h);
+                    return in_cell.then(|| GridEvent {
+                        id: event.id,
+                        label: format!("{} {}", time_of_day(&start), event.title),
+                        class: "schedule-event",
+                    });
+                }
+                // The rest of an event that started on an earlier day
+                hour.is_none_or(|h| h == 0).then(|| GridEvent {
+                    id: event.id,
+                    label: format!("… {}", event.title),
+                    class: "schedule-event schedule-event-continued",
+                })
+            })
+            .collect()
+    };
+    let all_day_on = |day: NaiveDate| -> Vec<GridEvent> {
+        events.iter()
+            .filter(|event| event.is_all_day && covers(event, day))
+            .map(|event| GridEvent { id: event.id, label: event.title.clone(), class: "schedule-event schedule-all-day-event" })
+            .collect()
+    };
+
+    let heading = match view {
+        ScheduleView::Month => format!("{} {}", month_abbr(&date), date.year()),
+        ScheduleView::Week => format!("{} – {}", days[0], days[6]),
+    };
+    let weekdays: Vec<String> = settings.weekdays().into_iter().map(weekday_abbr).collect();
+
+    // Start of an event added from a cell, in the format of the dialog's inputs
+    let start_at = |day: NaiveDate, time: NaiveTime| day.and_time(time).format("%Y-%m-%dT%H:%M").to_string();
+
+    let month_cells: Vec<(String, u32, String, Vec<GridEvent>)> = days.iter()
+        .map(|day| {
+            let mut class = "schedule-day".to_string();
+            if day.month() != date.month() {
+                class.push_str(" schedule-other-month");
+            }
+            if !hours.is_working_day(day.weekday()) {
+                class.push_str(" schedule-day-off");
+            }
+            if *day == today {
+                class.push_str(" schedule-today");
+            }
+            let mut day_events = all_day_on(*day);
+            day_events.extend(events_on(*day, None));
+            (class, day.day(), start_at(*day, hours.start), day_events)
+        })
+        .collect();
+
+    let week_columns: Vec<String> = days.iter()
+        .map(|day| format!("{} {}", weekday_abbr(day.weekday()), day.day()))
+        .collect();
+    let all_day_row: Vec<Vec<GridEvent>> = days.iter().map(|day| all_day_on(*day)).collect();
+    let week_rows: Vec<(String, Vec<HourSlot>)> = (0..24)
+        .map(|hour| {
+            let label = NaiveTime::from_hms_opt(hour, 0, 0).unwrap_or_default()
+                .format(settings.time_format.pattern())
+                .to_string();
+            let slots = days.iter()
+                .map(|day| {
+                    let class = if hours.covers_hour(day.weekday(), hour) { "schedule-slot schedule-working" } else { "schedule-slot" };
+                    let start = start_at(*day, NaiveTime::from_hms_opt(hour, 0, 0).unwrap_or_default());
+                    (class, start, events_on(*day, Some(hour)))
+                })
+                .collect();
+            (label, slots)
+        })
+        .collect();
+
+    let link = |date: NaiveDate, view: ScheduleView| format!("/web/schedule?view={}&date={}", view.as_str(), date);
+    let previous = link(view.step(date, false), view);
+    let next = link(view.step(date, true), view);
+    let current = link(today, view);
+    let month_link = link(date, ScheduleView::Month);
+    let week_link = link(date, ScheduleView::Week);
+    let toggle = |shown: ScheduleView| if shown == view { "btn btn-sm btn-primary" } else { "btn btn-sm btn-outline" };
+
+    rsx! {
+        BaseLayout {
+            current_user: Some(current_user),
+            capabilities: capabilities.clone(),
+            title: Some(t("schedule.page_title")),
+
+            div { class: "page-header",
+                h1 { "{heading}" }
+                div { class: "schedule-nav",
+                    a { href: "{previous}", class: "btn btn-sm btn-outline", title: t("schedule.previous"), "‹" }
+                    a { href: "{current}", class: "btn btn-sm btn-outline", {t("schedule.today")} }
+                    a { href: "{next}", class: "btn btn-sm btn-outline", title: t("schedule.next"), "›" }
+                    a { href: "{month_link}", class: toggle(ScheduleView::Month), {t("schedule.month")} }
+                    a { href: "{week_link}", class: toggle(ScheduleView::Week), {t("schedule.week")} }
+                    a {
+                        href: "/web/events/new",
+                        class: "btn btn-sm btn-primary",
+                        "hx-get": "/web/events/quick",
+                        "hx-target": "#modal",
+                        {t("events.new")}
+                    }
+                }
+            }
+
+            match view {
+                ScheduleView::Month => rsx! {
+                    div { class: "schedule-month",
+                        for name in weekdays {
+                            div { class: "schedule-weekday", "{name}" }
+                        }
+                        for (class, number, start, day_events) in month_cells {
+                            div { class: "{class}",
+                                span { class: "schedule-day-number", "{number}" }
+                                AddEventLink { start: start }
+                                for event in day_events {
+                                    a { href: "/web/events/{event.id}/edit", class: "{event.class}", "{event.label}" }
+                                }
+                            }
+                        }
+                    }
+                },
+                ScheduleView::Week => rsx! {
+                    div { class: "schedule-week",
+                        div { class: "schedule-corner" }
+                        for column in week_columns {
+                            div { class: "schedule-weekday", "{column}" }
+                        }
+                        div { class: "schedule-hour", {t("events.all_day")} }
+                        for day_events in all_day_row {
+                            div { class: "schedule-slot schedule-all-day",
+                                for event in day_events {
+                                    a { href: "/web/events/{event.id}/edit", class: "{event.class}", "{event.label}" }
+                                }
+                            }
+                        }
+                        for (label, slots) in week_rows {
+                            div { class: "schedule-hour", "{label}" }
+                            for (class, start, slot_events) in slots {
+                                div { class: "{class}",
+                                    AddEventLink { start: start }
+                                    for event in slot_events {
+                                        a { href: "/web/events/{event.id}/edit", class: "{event.class}", "{event.label}" }
+                                    }
+                                }
+                            }
+                        }
+                    }
+                },
+            }
+        }
+    }
+}
+
+/// Opens the event dialog for an event starting at `start`; a plain link to the event
+/// form without htmx
+#[component]
+fn AddEventLink(start: String) -> Element {
+    rsx! {
+        a {
+            href: "/web/events/new",
+            class: "schedule-add",
+            title: t("schedule.add_event"),
+            "hx-get": "/web/events/quick?start={start}",
+            "hx-target": "#modal",
+            "+"
+        }
+    }
+}
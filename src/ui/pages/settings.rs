@@ -0,0 +1,311 @@
+use dioxus::prelude::*;
+
+use crate::models::{Delegations, HolidayPackStatus, LoginActivity, NotificationChannel, NotificationPreference, User, UserIdentity};
+use crate::i18n::Language;
+use crate::settings::{Theme, TimeFormat, UserSettings};
+use crate::ui::layouts::BaseLayout;
+use crate::ui::{format_timestamp, t, t_with, weekday_abbr, Capabilities};
+
+#[component]
+pub fn SettingsPage(
+    current_user: User,
+    capabilities: Capabilities,
+    identities: Vec<UserIdentity>,
+    delegations: Delegations,
+    login_activity: LoginActivity,
+    notification_preferences: Vec<NotificationPreference>,
+    holiday_packs: Vec<HolidayPackStatus>,
+    user_settings: UserSettings,
+) -> Element {
+    let user = current_user.clone();
+    let last_login = login_activity.last_login_at.map(format_timestamp).unwrap_or_else(|| t("settings.never"));
+    let last_failed_login = login_activity.last_failed_login_at.map(format_timestamp).unwrap_or_else(|| t("settings.never"));
+    let locked_until = login_activity.locked_until.map(format_timestamp);
+    let week_days = user_settings.weekdays();
+    let work_start = user_settings.working_hours.start.format("%H:%M").to_string();
+    let work_end = user_settings.working_hours.end.format("%H:%M").to_string();
+
+    rsx! {
+        BaseLayout {
+            current_user: Some(current_user),
+            capabilities: capabilities.clone(),
+            title: Some(t("settings.page_title")),
+
+            div { class: "page-header",
+                h1 { {t("nav.settings")} }
+            }
+
+            div { class: "settings-section",
+                h3 { {t("settings.account")} }
+                p { strong { {t("settings.name")} } "{user.name}" }
+                p { strong { {t("settings.email")} } "{user.email}" }
+                p { strong { {t("settings.last_login")} } "{last_login}" }
+                p { strong { {t("settings.last_failed_login")} } "{last_failed_login}" }
+                if let Some(until) = locked_until {
+                    p { class: "text-danger", {t_with("settings.locked_until", &[("until", &until)])} }
+                }
+            }
+
+            div { class: "settings-section",
+                h3 { {t("settings.appearance")} }
+                form { action: "/web/theme", method: "post", class: "inline-form",
+                    label { r#for: "theme", {t("settings.theme_label")} }
+                    select { id: "theme", name: "theme",
+                        for theme in [Theme::System, Theme::Light, Theme::Dark] {
+                            option { value: "{theme.as_str()}", selected: theme == user_settings.theme, {t(&format!("settings.theme.{}", theme.as_str()))} }
+                        }
+                    }
+                    button { r#type: "submit", class: "btn btn-sm btn-primary", {t("groups.save")} }
+                }
+                form { action: "/web/settings/language", method: "post", class: "inline-form",
+                    label { r#for: "language", {t("settings.language")} }
+                    select { id: "language", name: "language",
+                        option { value: "", selected: user_settings.language.is_none(), {t("settings.language_browser")} }
+                        for language in Language::ALL {
+                            option { value: "{language.as_str()}", selected: user_settings.language == Some(language), "{language.native_name()}" }
+                        }
+                    }
+                    button { r#type: "submit", class: "btn btn-sm btn-primary", {t("groups.save")} }
+                }
+                p { class: "form-hint", {t("settings.language_hint")} }
+                form { action: "/web/settings/time", method: "post", class: "inline-form",
+                    label { r#for: "timezone", {t("settings.timezone")} }
+                    select { id: "timezone", name: "timezone",
+                        for timezone in chrono_tz::TZ_VARIANTS {
+                            option { value: "{timezone.name()}", selected: timezone == user_settings.timezone, "{timezone.name()}" }
+                        }
+                    }
+                    label { r#for: "time_format", {t("settings.time_format")} }
+                    select { id: "time_format", name: "time_format",
+                        for time_format in [TimeFormat::H24, TimeFormat::H12] {
+                            option { value: "{time_format.as_str()}", selected: time_format == user_settings.time_format, {t(&format!("settings.time_format_{}", time_format.as_str()))} }
+                        }
+                    }
+                    button { r#type: "submit", class: "btn btn-sm btn-primary", {t("groups.save")} }
+                }
+            }
+
+            div { class: "settings-section",
+                h3 { {t("settings.week")} }
+                form { action: "/web/settings/week", method: "post",
+                    div { class: "form-group",
+                        label { r#for: "week_start", {t("settings.week_start")} }
+                        select { id: "week_start", name: "week_start",
+                            for day in week_days.clone() {
+                                option { value: "{day}", selected: day == user_settings.week_start, {weekday_abbr(day)} }
+                            }
+                        }
+                    }
+                    div { class: "form-group",
+                        label { r#for: "work_start", {t("settings.working_hours")} }
+                        div { class: "inline-form",
+                            input { r#type: "time", id: "work_start", name: "work_start", required: true, value: "{work_start}" }
+                            span { "–" }
+                            input { r#type: "time", id: "work_end", name: "work_end", required: true, value: "{work_end}" }
+                        }
+                    }
+                    div { class: "form-group",
+                        span { {t("settings.working_days")} }
+                        div { class: "inline-form",
+                            for day in week_days {
+                                label { class: "checkbox-label",
+                                    input { r#type: "checkbox", name: "work_day", value: "{day}", checked: user_settings.working_hours.is_working_day(day) }
+                                    span { {weekday_abbr(day)} }
+                                }
+                            }
+                        }
+                    }
+                    div { class: "form-group",
+                        label { class: "checkbox-label",
+                            input { r#type: "checkbox", name: "free_busy", checked: user_settings.working_hours.free_busy }
+                            span { {t("settings.working_hours_free_busy")} }
+                        }
+                        p { class: "form-hint", {t("settings.working_hours_free_busy_hint")} }
+                    }
+                    button { r#type: "submit", class: "btn btn-sm btn-primary", {t("groups.save")} }
+                }
+            }
+
+            div { class: "settings-section",
+                h3 { {t("settings.connect_device")} }
+                p { class: "text-muted",
+                    {t("settings.connect_device_hint")}
+                }
+                div { class: "form-actions",
+                    a { href: "/web/settings/connection.mobileconfig", class: "btn btn-primary", {t("settings.download_apple")} }
+                    a { href: "/web/settings/connection.json", class: "btn btn-secondary", {t("settings.download_json")} }
+                }
+                p { class: "text-muted",
+                    {t("settings.sync_log_hint")}
+                    " "
+                    a { href: "/web/settings/sync-log", {t("settings.sync_log_link")} }
+                }
+                p { class: "text-muted",
+                    {t("settings.devices_hint")}
+                    " "
+                    a { href: "/web/settings/devices", {t("settings.devices_link")} }
+                }
+                if capabilities.jmap_enabled {
+                    p { class: "text-muted",
+                        {t("settings.jmap_hint")}
+                        code { "/.well-known/jmap" }
+                        {t("settings.jmap_hint_end")}
+                    }
+                }
+            }
+
+            div { class: "settings-section",
+                h3 { {t("settings.notifications")} }
+                p { class: "text-muted",
+                    {t("settings.notifications_hint")}
+                }
+
+                if notification_preferences.is_empty() {
+                    p { class: "text-muted", {t("settings.no_calendars")} }
+                } else {
+                    ul { class: "identity-list",
+                        for preference in notification_preferences {
+                            li { class: "identity-item",
+                                div { class: "identity-info",
+                                    strong { "{preference.calendar_name}" }
+                                }
+                                form { action: "/web/settings/notifications/{preference.calendar_id}", method: "post", class: "inline-form",
+                                    select { name: "channel",
+                                        option { value: "none", selected: preference.channel == NotificationChannel::None, {t("settings.no_reminders")} }
+                                        option { value: "email", selected: preference.channel == NotificationChannel::Email, {t("admin.email")} }
+                                    }
+                                    button { r#type: "submit", class: "btn btn-sm btn-primary", {t("groups.save")} }
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+
+            div { class: "settings-section",
+                h3 { {t("settings.holidays")} }
+                p { class: "text-muted",
+                    {t("settings.holidays_hint")}
+                }
+                ul { class: "identity-list",
+                    for pack in holiday_packs {
+                        li { class: "identity-item",
+                            div { class: "identity-info",
+                                if let Some(calendar_id) = pack.calendar_id {
+                                    a { href: "/web/calendars/{calendar_id}", strong { "{pack.name}" } }
+                                } else {
+                                    strong { "{pack.name}" }
+                                }
+                            }
+                            form { action: "/web/settings/holidays/{pack.id}", method: "post", class: "inline-form",
+                                if pack.calendar_id.is_some() {
+                                    input { r#type: "hidden", name: "enabled", value: "false" }
+                                    button { r#type: "submit", class: "btn btn-sm btn-outline", {t("shares.remove")} }
+                                } else {
+                                    input { r#type: "hidden", name: "enabled", value: "true" }
+                                    button { r#type: "submit", class: "btn btn-sm btn-primary", {t("settings.add")} }
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+
+            div { class: "settings-section",
+                h3 { {t("settings.delegates")} }
+                p { class: "text-muted",
+                    {t("settings.delegates_hint")}
+                }
+
+                if delegations.granted.is_empty() {
+                    p { class: "text-muted", {t("settings.no_delegates")} }
+                } else {
+                    ul { class: "identity-list",
+                        for delegation in delegations.granted {
+                            li { class: "identity-item",
+                                div { class: "identity-info",
+                                    strong { "{delegation.delegate_name}" }
+                                    span { class: "text-muted", " {delegation.delegate_email} · ", {t(&format!("shares.permission.{}", delegation.permission_level))} }
+                                }
+                                form { action: "/web/settings/delegations/{delegation.id}/delete", method: "post", class: "inline-form",
+                                    button { r#type: "submit", class: "btn btn-sm btn-danger", {t("shares.remove")} }
+                                }
+                            }
+                        }
+                    }
+                }
+
+                form { action: "/web/settings/delegations", method: "post", class: "inline-form",
+                    input { r#type: "email", name: "delegate_email", required: true, placeholder: t("settings.delegate_email_placeholder") }
+                    select { name: "permission",
+                        option { value: "write", {t("shares.permission.write")} }
+                        option { value: "read", {t("shares.permission.read")} }
+                    }
+                    button { r#type: "submit", class: "btn btn-primary", {t("settings.add_delegate")} }
+                }
+
+                if !delegations.received.is_empty() {
+                    h4 { {t("settings.delegated_to_you")} }
+                    ul { class: "identity-list",
+                        for delegation in delegations.received {
+                            li { class: "identity-item",
+                                div { class: "identity-info",
+                                    a { href: "/web/calendars?account={delegation.owner_id}", strong { "{delegation.owner_name}" } }
+                                    span { class: "text-muted", " {delegation.owner_email} · ", {t(&format!("shares.permission.{}", delegation.permission_level))} }
+                                }
+                                form { action: "/web/settings/delegations/{delegation.id}/delete", method: "post", class: "inline-form",
+                                    button { r#type: "submit", class: "btn btn-sm btn-outline", {t("settings.stop")} }
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+
+            div { class: "settings-section",
+                h3 { {t("settings.identities")} }
+                p { class: "text-muted",
+                    {t("settings.identities_hint")}
+                }
+
+                if identities.is_empty() {
+                    p { class: "text-muted", {t("settings.no_identities")} }
+                } else {
+                    ul { class: "identity-list",
+                        for identity in identities {
+                            li { class: "identity-item",
+                                div { class: "identity-info",
+                                    strong { "{identity.email.clone().unwrap_or_else(|| identity.subject.clone())}" }
+                                    span { class: "text-muted", " ", {t_with("settings.via", &[("issuer", &identity.issuer)])} }
+                                }
+                                form { action: "/web/settings/identities/{identity.id}/unlink", method: "post", class: "inline-form",
+                                    input {
+                                        r#type: "password",
+                                        name: "password",
+                                        required: true,
+                                        placeholder: t("settings.current_password")
+                                    }
+                                    button { r#type: "submit", class: "btn btn-sm btn-danger", {t("settings.unlink")} }
+                                }
+                            }
+                        }
+                    }
+                }
+
+                if capabilities.oidc_enabled {
+                    form { action: "/web/settings/identities/link", method: "post", class: "inline-form",
+                        input {
+                            r#type: "password",
+                            name: "password",
+                            required: true,
+                            placeholder: t("settings.current_password")
+                        }
+                        button { r#type: "submit", class: "btn btn-primary", {t("settings.link_identity")} }
+                    }
+                } else {
+                    p { class: "text-muted", {t("settings.sso_disabled")} }
+                }
+            }
+        }
+    }
+}
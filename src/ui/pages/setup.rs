@@ -0,0 +1,143 @@
+use dioxus::prelude::*;
+
+use crate::ui::components::{DetectedTimeZone, FlashMessage};
+use crate::ui::layouts::AuthLayout;
+use crate::ui::t;
+
+/// First-run setup wizard, shown while the instance has no accounts
+#[component]
+pub fn SetupPage(base_url: String) -> Element {
+    rsx! {
+        AuthLayout {
+            div { class: "auth-container",
+                div { class: "auth-card",
+                    h1 { {t("setup.heading")} }
+                    p { class: "form-hint", {t("setup.intro")} }
+                    FlashMessage {}
+                    form { action: "/setup", method: "post",
+                        h2 { {t("setup.administrator")} }
+                        div { class: "form-group",
+                            label { r#for: "name", {t("admin.name")} }
+                            input {
+                                r#type: "text",
+                                id: "name",
+                                name: "name",
+                                required: true,
+                                placeholder: t("auth.name_placeholder")
+                            }
+                        }
+                        div { class: "form-group",
+                            label { r#for: "email", {t("admin.email")} }
+                            input {
+                                r#type: "email",
+                                id: "email",
+                                name: "email",
+                                required: true,
+                                placeholder: t("auth.email_placeholder")
+                            }
+                        }
+                        div { class: "form-group",
+                            label { r#for: "password", {t("auth.password")} }
+                            input {
+                                r#type: "password",
+                                id: "password",
+                                name: "password",
+                                required: true,
+                                placeholder: t("setup.password_placeholder")
+                            }
+                        }
+                        div { class: "form-group",
+                            label { r#for: "confirm_password", {t("auth.confirm_password")} }
+                            input {
+                                r#type: "password",
+                                id: "confirm_password",
+                                name: "confirm_password",
+                                required: true,
+                                placeholder: t("setup.confirm_placeholder")
+                            }
+                        }
+
+                        h2 { {t("setup.instance")} }
+                        div { class: "form-group",
+                            label { r#for: "instance_name", {t("setup.instance_name")} }
+                            input {
+                                r#type: "text",
+                                id: "instance_name",
+                                name: "instance_name",
+                                required: true,
+                                value: "My CalDAV Server"
+                            }
+                        }
+                        div { class: "form-group",
+                            label { r#for: "base_url", {t("setup.base_url")} }
+                            input {
+                                r#type: "url",
+                                id: "base_url",
+                                name: "base_url",
+                                value: "{base_url}",
+                                placeholder: "https://calendar.example.com"
+                            }
+                            p { class: "form-hint", {t("setup.base_url_hint")} }
+                        }
+                        div { class: "form-group",
+                            label { r#for: "registration", {t("setup.registration")} }
+                            select { id: "registration", name: "registration",
+                                option { value: "open", {t("setup.registration_open")} }
+                                option { value: "closed", {t("setup.registration_closed")} }
+                            }
+                        }
+
+                        h2 { {t("setup.mail")} }
+                        div { class: "form-group",
+                            label { r#for: "smtp_host", {t("setup.smtp_host")} }
+                            input {
+                                r#type: "text",
+                                id: "smtp_host",
+                                name: "smtp_host",
+                                placeholder: "smtp.example.com"
+                            }
+                            p { class: "form-hint", {t("setup.smtp_host_hint")} }
+                        }
+                        div { class: "form-group",
+                            label { r#for: "smtp_port", {t("setup.smtp_port")} }
+                            input {
+                                r#type: "number",
+                                id: "smtp_port",
+                                name: "smtp_port",
+                                value: "587"
+                            }
+                        }
+                        div { class: "form-group",
+                            label { r#for: "smtp_username", {t("setup.smtp_username")} }
+                            input {
+                                r#type: "text",
+                                id: "smtp_username",
+                                name: "smtp_username"
+                            }
+                        }
+                        div { class: "form-group",
+                            label { r#for: "smtp_password", {t("setup.smtp_password")} }
+                            input {
+                                r#type: "password",
+                                id: "smtp_password",
+                                name: "smtp_password"
+                            }
+                        }
+                        div { class: "form-group",
+                            label { r#for: "smtp_from", {t("setup.sender")} }
+                            input {
+                                r#type: "email",
+                                id: "smtp_from",
+                                name: "smtp_from",
+                                placeholder: "calendar@example.com"
+                            }
+                        }
+
+                        DetectedTimeZone {}
+                        button { r#type: "submit", class: "btn btn-primary", {t("setup.complete")} }
+                    }
+                }
+            }
+        }
+    }
+}
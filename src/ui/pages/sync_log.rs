@@ -0,0 +1,151 @@
+use dioxus::prelude::*;
+
+use crate::models::User;
+use crate::sync_log::{SyncLogClient, SyncLogEntry};
+use crate::ui::layouts::BaseLayout;
+use crate::ui::{format_timestamp, t, t_with, Capabilities};
+
+/// The user's logged CalDAV requests by client; `client` narrows the requests to one
+#[component]
+pub fn SyncLogPage(
+    current_user: User,
+    capabilities: Capabilities,
+    enabled: bool,
+    clients: Vec<SyncLogClient>,
+    entries: Vec<SyncLogEntry>,
+    client: Option<String>,
+) -> Element {
+    rsx! {
+        BaseLayout {
+            current_user: Some(current_user),
+            capabilities: capabilities.clone(),
+            title: Some(t("sync_log.page_title")),
+
+            div { class: "page-header",
+                div {
+                    h1 { {t("sync_log.heading")} }
+                    p { class: "subtitle", {t("sync_log.subtitle")} }
+                }
+                div { class: "page-actions",
+                    a { href: "/web/settings", class: "btn btn-secondary", {t("sync_log.back")} }
+                }
+            }
+
+            div { class: "settings-section",
+                if enabled {
+                    p { {t_with("sync_log.enabled", &[("count", &crate::sync_log::MAX_ENTRIES_PER_USER.to_string())])} }
+                    div { class: "form-actions",
+                        form { action: "/web/settings/sync-log/clear", method: "post", class: "inline-form",
+                            button { r#type: "submit", class: "btn btn-secondary", {t("sync_log.clear")} }
+                        }
+                        form { action: "/web/settings/sync-log", method: "post", class: "inline-form",
+                            input { r#type: "hidden", name: "enabled", value: "false" }
+                            button { r#type: "submit", class: "btn btn-danger", {t("sync_log.disable")} }
+                        }
+                    }
+                } else {
+                    p { class: "text-muted", {t("sync_log.disabled")} }
+                    form { action: "/web/settings/sync-log", method: "post", class: "inline-form",
+                        input { r#type: "hidden", name: "enabled", value: "true" }
+                        button { r#type: "submit", class: "btn btn-primary", {t("sync_log.enable")} }
+                    }
+                }
+            }
+
+            if enabled {
+                div { class: "dashboard-section",
+                    h2 { {t("sync_log.clients")} }
+                    if clients.is_empty() {
+                        p { class: "text-muted", {t("sync_log.no_requests")} }
+                    } else {
+                        table { class: "admin-table",
+                            thead {
+                                tr {
+                                    th { {t("sync_log.client")} }
+                                    th { {t("sync_log.requests")} }
+                                    th { {t("sync_log.failures")} }
+                                    th { {t("sync_log.last_seen")} }
+                                    th { {t("sync_log.last_status")} }
+                                }
+                            }
+                            tbody {
+                                for entry in clients {
+                                    tr {
+                                        td {
+                                            // User agents can hold anything, so the browser encodes them
+                                            form { action: "/web/settings/sync-log", method: "get",
+                                                input { r#type: "hidden", name: "client", value: "{entry.name}" }
+                                                button { r#type: "submit", class: "sync-log-client", "{entry.name}" }
+                                            }
+                                        }
+                                        td { "{entry.requests}" }
+                                        td { "{entry.failures}" }
+                                        td { {format_timestamp(entry.last_seen)} }
+                                        td { StatusBadge { status: entry.last_status } }
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+
+                if !entries.is_empty() {
+                    div { class: "dashboard-section",
+                        div { class: "section-header",
+                            h2 {
+                                match &client {
+                                    Some(client) => t_with("sync_log.requests_of", &[("client", client)]),
+                                    None => t("sync_log.recent_requests"),
+                                }
+                            }
+                            if client.is_some() {
+                                a { href: "/web/settings/sync-log", {t("sync_log.all_clients")} }
+                            }
+                        }
+                        table { class: "admin-table",
+                            thead {
+                                tr {
+                                    th { {t("admin.time")} }
+                                    if client.is_none() {
+                                        th { {t("sync_log.client")} }
+                                    }
+                                    th { {t("sync_log.request")} }
+                                    th { {t("sync_log.status")} }
+                                    th { {t("sync_log.duration")} }
+                                    th { {t("sync_log.error")} }
+                                }
+                            }
+                            tbody {
+                                for entry in entries {
+                                    tr {
+                                        td { {format_timestamp(entry.created_at)} }
+                                        if client.is_none() {
+                                            td { "{entry.client()}" }
+                                        }
+                                        td { code { "{entry.method} {entry.path}" } }
+                                        td { StatusBadge { status: entry.status } }
+                                        td { {t_with("sync_log.milliseconds", &[("count", &entry.duration_ms.to_string())])} }
+                                        td { "{entry.error.clone().unwrap_or_default()}" }
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// HTTP status with its reason, green for success and red for failure
+#[component]
+fn StatusBadge(status: u16) -> Element {
+    let reason = axum::http::StatusCode::from_u16(status).ok()
+        .and_then(|status| status.canonical_reason())
+        .unwrap_or_default();
+    let class = if status >= 400 { "badge badge-private" } else { "badge badge-public" };
+
+    rsx! {
+        span { class: "{class}", "{status} {reason}" }
+    }
+}
@@ -0,0 +1,38 @@
+//! Rendering of pages and htmx fragments to HTML with `dioxus_ssr`. Every web handler
+//! renders through here, so all pages get the same document shell and error handling.
+
+use axum::response::Html;
+use dioxus::prelude::*;
+
+use crate::error::AppError;
+use crate::ui::language;
+
+/// Render a whole page. A panicking component becomes an error naming the template
+/// instead of tearing down the request.
+#[track_caller]
+pub fn render_to_html(template: &str, element: Element) -> Result<String, AppError> {
+    let content = render_element(template, element)?;
+    Ok(format!(r#"<!DOCTYPE html>
+<html lang="{}">
+{}
+</html>"#, language().as_str(), content))
+}
+
+/// Render part of a page for an htmx request to swap in
+#[track_caller]
+pub fn render_fragment(template: &str, element: Element) -> Result<Html<String>, AppError> {
+    render_element(template, element).map(Html)
+}
+
+#[track_caller]
+fn render_element(template: &str, element: Element) -> Result<String, AppError> {
+    let caller = std::panic::Location::caller();
+    std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| dioxus_ssr::render_element(element)))
+        .map_err(|panic| {
+            let reason = panic.downcast_ref::<&str>().map(|s| s.to_string())
+                .or_else(|| panic.downcast_ref::<String>().cloned())
+                .unwrap_or_default();
+            tracing::error!(template, handler = %caller, reason, "Template rendering failed");
+            AppError::InternalServerError(format!("Rendering {} failed", template))
+        })
+}
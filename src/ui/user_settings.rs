@@ -0,0 +1,22 @@
+//! Settings of the user a page is rendered for. They are set for the whole request by
+//! [`crate::middleware::user_settings_middleware`], so layouts and components can read
+//! them without every page passing them down.
+
+use std::future::Future;
+
+use crate::settings::UserSettings;
+
+tokio::task_local! {
+    static USER_SETTINGS: UserSettings;
+}
+
+/// Run `future` with `settings` as the settings pages are rendered with
+pub async fn with_user_settings<F: Future>(settings: UserSettings, future: F) -> F::Output {
+    USER_SETTINGS.scope(settings, future).await
+}
+
+/// Settings of the user the current page is rendered for; the defaults outside of a
+/// web UI request
+pub fn user_settings() -> UserSettings {
+    USER_SETTINGS.try_with(Clone::clone).unwrap_or_default()
+}
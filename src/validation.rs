@@ -0,0 +1,196 @@
+//! Checks of what users send to create or change accounts, calendars, events and shares
+//!
+//! Handlers validate request bodies before passing them on, so a bad field is answered
+//! with a field-level error: a `400` naming the field for JSON clients, and a flash
+//! message on the form in the web UI. Objects uploaded over CalDAV are stored as the
+//! client wrote them.
+
+use chrono::{DateTime, Utc};
+
+use crate::error::AppError;
+use crate::models::{BatchOperation, NewCalendar, NewEvent, NewShare, NewUser, PermissionLevel, UpdateCalendar, UpdateEvent};
+
+/// Longest user or calendar name, in characters
+pub const MAX_NAME_LENGTH: usize = 255;
+
+/// Longest email address, as allowed by SMTP
+pub const MAX_EMAIL_LENGTH: usize = 254;
+
+/// Shortest accepted password
+pub const MIN_PASSWORD_LENGTH: usize = 6;
+
+/// Longest accepted password; bcrypt only looks at the first 72 bytes anyway
+pub const MAX_PASSWORD_LENGTH: usize = 1024;
+
+/// Longest event title, in characters
+pub const MAX_EVENT_TITLE_LENGTH: usize = 255;
+
+/// Longest event location, in characters
+pub const MAX_EVENT_LOCATION_LENGTH: usize = 255;
+
+/// Longest calendar or event description, in characters
+pub const MAX_DESCRIPTION_LENGTH: usize = 10_000;
+
+/// Largest INTERVAL accepted in a recurrence rule
+pub const MAX_RRULE_INTERVAL: u64 = 1_000;
+
+/// Largest COUNT accepted in a recurrence rule; series are never expanded further
+pub const MAX_RRULE_COUNT: u64 = 10_000;
+
+/// Request bodies that can be checked before they reach the service
+pub trait Validate {
+    /// The first broken rule, as a field error
+    fn validate(&self) -> Result<(), AppError>;
+}
+
+impl Validate for NewUser {
+    fn validate(&self) -> Result<(), AppError> {
+        required("name", "Name", &self.name, MAX_NAME_LENGTH)?;
+        email("email", &self.email)?;
+        let length = self.password.chars().count();
+        if length < MIN_PASSWORD_LENGTH {
+            return Err(AppError::invalid_field("password", format!("Password must be at least {} characters", MIN_PASSWORD_LENGTH)));
+        }
+        if length > MAX_PASSWORD_LENGTH {
+            return Err(AppError::invalid_field("password", format!("Password must be at most {} characters", MAX_PASSWORD_LENGTH)));
+        }
+        Ok(())
+    }
+}
+
+impl Validate for NewCalendar {
+    fn validate(&self) -> Result<(), AppError> {
+        required("name", "Name", &self.name, MAX_NAME_LENGTH)?;
+        optional("description", "Description", self.description.as_deref(), MAX_DESCRIPTION_LENGTH)?;
+        color("color", self.color.as_deref())
+    }
+}
+
+impl Validate for UpdateCalendar {
+    fn validate(&self) -> Result<(), AppError> {
+        if let Some(name) = &self.name {
+            required("name", "Name", name, MAX_NAME_LENGTH)?;
+        }
+        optional("description", "Description", self.description.clone().flatten().as_deref(), MAX_DESCRIPTION_LENGTH)?;
+        color("color", self.color.clone().flatten().as_deref())
+    }
+}
+
+impl Validate for NewEvent {
+    fn validate(&self) -> Result<(), AppError> {
+        required("title", "Title", &self.title, MAX_EVENT_TITLE_LENGTH)?;
+        optional("location", "Location", self.location.as_deref(), MAX_EVENT_LOCATION_LENGTH)?;
+        optional("description", "Description", self.description.as_deref(), MAX_DESCRIPTION_LENGTH)?;
+        rrule("rrule", self.rrule.as_deref())?;
+        event_times(self.start_time, self.end_time)
+    }
+}
+
+impl Validate for UpdateEvent {
+    fn validate(&self) -> Result<(), AppError> {
+        if let Some(title) = &self.title {
+            required("title", "Title", title, MAX_EVENT_TITLE_LENGTH)?;
+        }
+        optional("location", "Location", self.location.clone().flatten().as_deref(), MAX_EVENT_LOCATION_LENGTH)?;
+        optional("description", "Description", self.description.clone().flatten().as_deref(), MAX_DESCRIPTION_LENGTH)?;
+        rrule("rrule", self.rrule.as_deref())?;
+        // With one end left out, the service checks against the stored event
+        match (self.start_time, self.end_time) {
+            (Some(start), Some(end)) => event_times(start, end),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl Validate for NewShare {
+    fn validate(&self) -> Result<(), AppError> {
+        email("shared_with_email", &self.shared_with_email)?;
+        if PermissionLevel::from_str(&self.permission).is_none() {
+            return Err(AppError::invalid_field("permission", "Permission must be read, write or admin"));
+        }
+        Ok(())
+    }
+}
+
+impl Validate for BatchOperation {
+    fn validate(&self) -> Result<(), AppError> {
+        match self {
+            BatchOperation::Create { event, .. } => event.validate(),
+            BatchOperation::Update { changes, .. } => changes.validate(),
+            BatchOperation::Delete { .. } => Ok(()),
+        }
+    }
+}
+
+/// Check that an event doesn't end before it starts. The service checks this for every
+/// event it stores, whichever way it arrives; an event may take no time at all.
+pub fn event_times(start: DateTime<Utc>, end: DateTime<Utc>) -> Result<(), AppError> {
+    if end < start {
+        return Err(AppError::invalid_field("end_time", "End must not be before the start"));
+    }
+    Ok(())
+}
+
+/// Whether `value` is a color written as `#RRGGBB`
+pub fn is_hex_color(value: &str) -> bool {
+    value.len() == 7 && value.starts_with('#') && value[1..].chars().all(|c| c.is_ascii_hexdigit())
+}
+
+/// A field that must not be blank, of at most `max` characters
+fn required(field: &str, label: &str, value: &str, max: usize) -> Result<(), AppError> {
+    if value.trim().is_empty() {
+        return Err(AppError::invalid_field(field, format!("{} is required", label)));
+    }
+    optional(field, label, Some(value), max)
+}
+
+/// A field of at most `max` characters, if given
+fn optional(field: &str, label: &str, value: Option<&str>, max: usize) -> Result<(), AppError> {
+    match value {
+        Some(value) if value.chars().count() > max => {
+            Err(AppError::invalid_field(field, format!("{} must be at most {} characters", label, max)))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// INTERVAL and COUNT of a recurrence rule, if given. Other rule parts are left to
+/// the expansion, which skips what it doesn't support.
+fn rrule(field: &str, value: Option<&str>) -> Result<(), AppError> {
+    for part in value.unwrap_or_default().split(';') {
+        let Some((key, value)) = part.split_once('=') else {
+            continue;
+        };
+        let (label, max) = match key.trim().to_ascii_uppercase().as_str() {
+            "INTERVAL" => ("interval", MAX_RRULE_INTERVAL),
+            "COUNT" => ("count", MAX_RRULE_COUNT),
+            _ => continue,
+        };
+        if !value.trim().parse::<u64>().is_ok_and(|n| (1..=max).contains(&n)) {
+            return Err(AppError::invalid_field(field, format!("Recurrence {} must be between 1 and {}", label, max)));
+        }
+    }
+    Ok(())
+}
+
+fn color(field: &str, value: Option<&str>) -> Result<(), AppError> {
+    match value {
+        Some(value) if !is_hex_color(value) => Err(AppError::invalid_field(field, "Color must look like #RRGGBB")),
+        _ => Ok(()),
+    }
+}
+
+/// An address of the form `name@host`; whether it receives mail is left to the mail
+/// server
+fn email(field: &str, value: &str) -> Result<(), AppError> {
+    let valid = value.len() <= MAX_EMAIL_LENGTH
+        && !value.chars().any(char::is_whitespace)
+        && value.split_once('@').is_some_and(|(local, domain)| {
+            !local.is_empty() && !domain.is_empty() && !domain.contains('@')
+        });
+    if valid {
+        Ok(())
+    } else {
+        Err(AppError::invalid_field(field, "Email must look like name@example.com"))
+    }
+}
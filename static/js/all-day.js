@@ -0,0 +1,16 @@
+// All-day switch of the event form and dialog: turns the start and end inputs into date
+// pickers and back, keeping the chosen days. All-day events end on their last day.
+(function () {
+    document.addEventListener('change', function (event) {
+        var toggle = event.target;
+        if (!toggle.matches('input[name="is_all_day"]') || !toggle.form) {
+            return;
+        }
+        [['start_time', 'T09:00'], ['end_time', 'T10:00']].forEach(function (field) {
+            var input = toggle.form.elements[field[0]];
+            var day = input.value.slice(0, 10);
+            input.type = toggle.checked ? 'date' : 'datetime-local';
+            input.value = day && !toggle.checked ? day + field[1] : day;
+        });
+    });
+})();
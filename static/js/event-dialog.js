@@ -0,0 +1,68 @@
+// Event dialog: checks the form before sending it as JSON and shows the server's field
+// errors next to their inputs. The dialog itself is loaded into #modal by htmx.
+(function () {
+    function isDialogForm(form) {
+        return form instanceof HTMLFormElement && form.matches('[data-event-dialog]');
+    }
+
+    function checkTimes(form) {
+        var start = form.elements.start_time;
+        var end = form.elements.end_time;
+        // Input values sort like the times they stand for; an all-day event may end on
+        // the day it starts
+        var endsFirst = start.value && end.value
+            && (end.type === 'date' ? end.value < start.value : end.value <= start.value);
+        end.setCustomValidity(endsFirst ? form.dataset.endBeforeStart : '');
+    }
+
+    document.addEventListener('input', function (event) {
+        if (isDialogForm(event.target.form)) {
+            checkTimes(event.target.form);
+        }
+    });
+
+    document.addEventListener('click', function (event) {
+        if (event.target.matches('[data-close-dialog]')) {
+            event.target.closest('dialog').remove();
+        }
+    });
+
+    document.addEventListener('submit', function (event) {
+        var form = event.target;
+        if (!isDialogForm(form)) {
+            return;
+        }
+        event.preventDefault();
+        form.querySelectorAll('[data-error-for]').forEach(function (error) {
+            error.textContent = '';
+        });
+        checkTimes(form);
+        if (!form.reportValidity()) {
+            return;
+        }
+
+        var body = {
+            calendar_id: form.elements.calendar_id.value,
+            title: form.elements.title.value,
+            location: form.elements.location.value || null,
+            start_time: form.elements.start_time.value,
+            end_time: form.elements.end_time.value,
+            is_all_day: form.elements.is_all_day.checked
+        };
+        fetch(form.action, {
+            method: 'POST',
+            headers: { 'Content-Type': 'application/json', 'Accept': 'application/json' },
+            body: JSON.stringify(body)
+        }).then(function (response) {
+            if (response.ok) {
+                window.location.reload();
+                return;
+            }
+            return response.json().then(function (error) {
+                var target = form.querySelector('[data-error-for="' + (error.field || '') + '"]')
+                    || form.querySelector('[data-error-for=""]');
+                target.textContent = error.message;
+            });
+        });
+    });
+})();
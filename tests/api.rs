@@ -0,0 +1,2779 @@
+//! End-to-end tests of setup, login and the JSON API
+
+mod common;
+
+use axum::{body::Body, http::{Method, Request, StatusCode}};
+use chrono::{Duration, TimeZone, Utc};
+use common::{assert_snapshot, authed_request, dav_request, json_request, TestApp, TestResponse, ADMIN_EMAIL, ADMIN_PASSWORD};
+use my_cal_dav_server::{missing_keys, Clock, Config, Language, MemoryMailer, MockClock};
+use serde_json::json;
+
+#[tokio::test]
+async fn registration_is_closed_until_setup_is_done() {
+    let app = TestApp::new().await;
+
+    let response = app.register("Early Bird", "early@example.com", "secret123").await;
+    assert_eq!(response.status, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn setup_creates_admin_and_can_only_run_once() {
+    let app = TestApp::new().await;
+
+    let response = app.setup(ADMIN_EMAIL, ADMIN_PASSWORD).await;
+    assert_eq!(response.status, StatusCode::FOUND);
+    assert_eq!(response.header("location"), Some("/web/dashboard"));
+    assert!(response.header("set-cookie").is_some_and(|c| c.starts_with("auth_token=")));
+
+    let response = app.setup("intruder@example.com", "secret123").await;
+    assert_eq!(response.status, StatusCode::SEE_OTHER);
+    assert_eq!(response.header("location"), Some("/web/login"));
+
+    let response = app.login("intruder@example.com", "secret123").await;
+    assert_eq!(response.status, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn login_accepts_valid_and_rejects_invalid_credentials() {
+    let app = TestApp::with_admin().await;
+
+    let response = app.login(ADMIN_EMAIL, ADMIN_PASSWORD).await;
+    assert_eq!(response.status, StatusCode::OK);
+    let body = response.json();
+    assert!(body["token"].as_str().is_some_and(|t| !t.is_empty()));
+    assert_eq!(body["user"]["email"], ADMIN_EMAIL);
+
+    let response = app.login(ADMIN_EMAIL, "wrong-password").await;
+    assert_eq!(response.status, StatusCode::UNAUTHORIZED);
+
+    let response = app.send(authed_request(Method::GET, "/api/auth/calendars", "not-a-token")).await;
+    assert_eq!(response.status, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn calendar_and_event_crud() {
+    let app = TestApp::with_admin().await;
+    let token = app.user_token("alice@example.com", "secret123").await;
+
+    let calendar = app.create_calendar(&token, "Team").await;
+    let calendar_id = calendar["id"].as_str().unwrap().to_string();
+
+    let response = app.send(authed_request(Method::GET, "/api/auth/calendars", &token)).await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert!(response.json().as_array().unwrap().iter().any(|c| c["name"] == "Team"));
+
+    let response = app.send(json_request(
+        Method::PUT,
+        &format!("/api/auth/calendars/{}", calendar_id),
+        Some(&token),
+        json!({ "name": "Renamed" }),
+    )).await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.json()["name"], "Renamed");
+
+    let start = Utc::now() + Duration::days(1);
+    let response = app.send(json_request(Method::POST, "/api/auth/events", Some(&token), json!({
+        "calendar_id": calendar_id,
+        "event": {
+            "title": "Planning",
+            "description": null,
+            "location": "Room 1",
+            "start_time": start,
+            "end_time": start + Duration::hours(1),
+            "is_all_day": false,
+            "rrule": null,
+            "geo_lat": null,
+            "geo_lon": null,
+        },
+    }))).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+    let event_id = response.json()["id"].as_str().unwrap().to_string();
+
+    let response = app.send(authed_request(Method::GET, &format!("/api/auth/calendars/{}/events", calendar_id), &token)).await;
+    assert_eq!(response.status, StatusCode::OK);
+    let events = response.json();
+    assert_eq!(events.as_array().unwrap().len(), 1);
+    assert_eq!(events[0]["title"], "Planning");
+    assert!(events[0]["next_occurrence"].is_string());
+
+    let response = app.send(json_request(
+        Method::PUT,
+        &format!("/api/auth/events/{}", event_id),
+        Some(&token),
+        json!({ "title": "Planning (moved)" }),
+    )).await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.json()["title"], "Planning (moved)");
+    assert_eq!(response.json()["location"], "Room 1");
+
+    let response = app.send(json_request(
+        Method::PUT,
+        &format!("/api/auth/events/{}", event_id),
+        Some(&token),
+        json!({ "location": null }),
+    )).await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert!(response.json()["location"].is_null());
+    assert_eq!(response.json()["title"], "Planning (moved)");
+
+    let response = app.send(authed_request(Method::DELETE, &format!("/api/auth/events/{}", event_id), &token)).await;
+    assert_eq!(response.status, StatusCode::NO_CONTENT);
+
+    let response = app.send(authed_request(Method::GET, &format!("/api/auth/events/{}", event_id), &token)).await;
+    assert_eq!(response.status, StatusCode::NOT_FOUND);
+
+    let response = app.send(authed_request(Method::DELETE, &format!("/api/auth/calendars/{}", calendar_id), &token)).await;
+    assert_eq!(response.status, StatusCode::NO_CONTENT);
+}
+
+#[tokio::test]
+async fn other_users_cannot_read_a_private_calendar() {
+    let app = TestApp::with_admin().await;
+    let alice = app.user_token("alice@example.com", "secret123").await;
+    let bob = app.user_token("bob@example.com", "secret123").await;
+
+    let calendar = app.create_calendar(&alice, "Private").await;
+    let uri = format!("/api/auth/calendars/{}/events", calendar["id"].as_str().unwrap());
+
+    let response = app.send(authed_request(Method::GET, &uri, &bob)).await;
+    assert!(response.status.is_client_error(), "got {}", response.status);
+}
+
+#[tokio::test]
+async fn web_errors_render_an_error_page() {
+    let app = TestApp::with_admin().await;
+    let alice = app.user_token("alice@example.com", "secret123").await;
+    let bob = app.user_token("bob@example.com", "secret123").await;
+    let calendar = app.create_calendar(&alice, "Private").await;
+
+    let uri = format!("/web/calendars/{}", uuid::Uuid::new_v4());
+    let response = app.send(authed_request(Method::GET, &uri, &alice)).await;
+    assert_eq!(response.status, StatusCode::NOT_FOUND);
+    assert!(response.header("content-type").is_some_and(|c| c.starts_with("text/html")));
+    assert!(response.body.contains("Page not found"));
+    assert!(response.body.contains(response.header("x-request-id").unwrap()));
+
+    let uri = format!("/web/calendars/{}", calendar["id"].as_str().unwrap());
+    let response = app.send(authed_request(Method::GET, &uri, &bob)).await;
+    assert_eq!(response.status, StatusCode::FORBIDDEN);
+    assert!(response.body.contains("Access denied"));
+}
+
+#[tokio::test]
+async fn only_admins_can_trigger_pruning() {
+    let app = TestApp::with_admin().await;
+    let user = app.user_token("alice@example.com", "secret123").await;
+
+    let response = app.send(authed_request(Method::POST, "/api/admin/maintenance/prune", &user)).await;
+    assert_eq!(response.status, StatusCode::UNAUTHORIZED);
+
+    let response = app.login(ADMIN_EMAIL, ADMIN_PASSWORD).await;
+    let admin = response.json()["token"].as_str().unwrap().to_string();
+
+    let response = app.send(authed_request(Method::POST, "/api/admin/maintenance/prune", &admin)).await;
+    assert_eq!(response.status, StatusCode::OK);
+    let report = response.json();
+    assert_eq!(report["expired_push_subscriptions"], 0);
+    assert_eq!(report["audit_log_entries"], 0);
+    assert_eq!(report["event_versions"], 0);
+    assert_eq!(report["login_attempts"], 0);
+    assert_eq!(report["orphaned_rows"], 0);
+    assert_eq!(report["orphaned_attachment_files"], 0);
+}
+
+#[tokio::test]
+async fn sign_in_attempts_are_pruned_after_the_retention_period() {
+    let clock = MockClock::new(Utc.with_ymd_and_hms(2030, 5, 2, 9, 0, 0).unwrap());
+    let app = TestApp::with_clock(clock.clone()).await;
+    app.setup(ADMIN_EMAIL, ADMIN_PASSWORD).await;
+    app.user_token("alice@example.com", "secret123").await;
+    assert_eq!(app.login("alice@example.com", "wrong").await.status, StatusCode::UNAUTHORIZED);
+
+    clock.advance(Duration::days(91));
+    let admin = app.login(ADMIN_EMAIL, ADMIN_PASSWORD).await.json()["token"].as_str().unwrap().to_string();
+    let prune = || app.send(authed_request(Method::POST, "/api/admin/maintenance/prune", &admin));
+    assert_eq!(prune().await.json()["login_attempts"], 2);
+    // The admin's own sign-in just now is kept
+    assert_eq!(prune().await.json()["login_attempts"], 0);
+}
+
+#[tokio::test]
+async fn pruning_removes_rows_and_files_left_without_a_parent() {
+    use sqlx::{ConnectOptions, Connection};
+
+    let data_dir = tempfile::tempdir().unwrap();
+    let attachments_dir = tempfile::tempdir().unwrap();
+    let database = data_dir.path().join("calendar.db");
+    let config = Config {
+        attachments_dir: attachments_dir.path().to_string_lossy().into_owned(),
+        ..Config::default()
+    };
+    let app = TestApp::on_disk(&database, config).await;
+    app.setup(ADMIN_EMAIL, ADMIN_PASSWORD).await;
+    let admin = app.login(ADMIN_EMAIL, ADMIN_PASSWORD).await.json()["token"].as_str().unwrap().to_string();
+
+    // Written the way older versions could, without foreign keys enforced
+    let mut connection = format!("sqlite:{}", database.display()).parse::<sqlx::sqlite::SqliteConnectOptions>().unwrap()
+        .foreign_keys(false)
+        .connect().await.unwrap();
+    sqlx::query("INSERT INTO contacts (id, user_id, name, created_at, updated_at) VALUES ('c1', 'gone', 'Dana', '2030-01-01T00:00:00Z', '2030-01-01T00:00:00Z')")
+        .execute(&mut connection).await.unwrap();
+    connection.close().await.unwrap();
+
+    let stale = attachments_dir.path().join(uuid::Uuid::new_v4().to_string());
+    let fresh = attachments_dir.path().join(uuid::Uuid::new_v4().to_string());
+    std::fs::write(&stale, b"left behind").unwrap();
+    std::fs::write(&fresh, b"still uploading").unwrap();
+    let two_hours_ago = std::time::SystemTime::now() - std::time::Duration::from_secs(7200);
+    std::fs::File::options().write(true).open(&stale).unwrap().set_modified(two_hours_ago).unwrap();
+
+    let response = app.send(authed_request(Method::POST, "/api/admin/maintenance/prune", &admin)).await;
+    let report = response.json();
+    assert_eq!(report["orphaned_rows"], 1, "{}", response.body);
+    assert_eq!(report["orphaned_attachment_files"], 1);
+    assert!(!stale.exists());
+    assert!(fresh.exists());
+}
+
+#[tokio::test]
+async fn admins_can_take_and_download_backups() {
+    // VACUUM INTO doesn't write out in-memory databases
+    let data_dir = tempfile::tempdir().unwrap();
+    let backup_dir = tempfile::tempdir().unwrap();
+    let mut config = Config::default();
+    config.backup.dir = backup_dir.path().to_string_lossy().into_owned();
+    let app = TestApp::on_disk(&data_dir.path().join("calendar.db"), config).await;
+    app.setup(ADMIN_EMAIL, ADMIN_PASSWORD).await;
+    let user = app.user_token("alice@example.com", "secret123").await;
+    let admin = app.login(ADMIN_EMAIL, ADMIN_PASSWORD).await.json()["token"].as_str().unwrap().to_string();
+
+    let response = app.send(authed_request(Method::POST, "/web/admin/backups", &user)).await;
+    assert_eq!(response.status, StatusCode::FORBIDDEN);
+
+    let response = app.send(authed_request(Method::POST, "/web/admin/backups", &admin)).await;
+    assert_eq!(response.status, StatusCode::SEE_OTHER);
+
+    let names: Vec<String> = std::fs::read_dir(backup_dir.path()).unwrap()
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+        .collect();
+    assert_eq!(names.len(), 1);
+    assert!(names[0].starts_with("calendar-") && names[0].ends_with(".db"));
+
+    let response = app.send(authed_request(Method::GET, "/web/admin/backups", &admin)).await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert!(response.body.contains(&names[0]));
+
+    let response = app.send(authed_request(Method::GET, &format!("/web/admin/backups/{}", names[0]), &admin)).await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert!(response.body.starts_with("SQLite format 3"));
+
+    let response = app.send(authed_request(Method::GET, "/web/admin/backups/..%2Fcalendar.db", &admin)).await;
+    assert_eq!(response.status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn responses_carry_the_request_id() {
+    let app = TestApp::new().await;
+
+    let response = app.send(Request::get("/health").body(Body::empty()).unwrap()).await;
+    assert!(response.header("x-request-id").is_some_and(|id| !id.is_empty()));
+
+    let request = Request::get("/health")
+        .header("x-request-id", "sync-1234")
+        .body(Body::empty())
+        .unwrap();
+    let response = app.send(request).await;
+    assert_eq!(response.header("x-request-id"), Some("sync-1234"));
+}
+
+#[tokio::test]
+async fn time_dependent_logic_follows_the_injected_clock() {
+    // A Wednesday; the forecast week starts on Monday 2030-01-07
+    let clock = MockClock::new(Utc.with_ymd_and_hms(2030, 1, 9, 12, 0, 0).unwrap());
+    let app = TestApp::with_clock(clock.clone()).await;
+    app.setup(ADMIN_EMAIL, ADMIN_PASSWORD).await;
+    let token = app.user_token("alice@example.com", "secret123").await;
+
+    let calendar = app.create_calendar(&token, "Work").await;
+    let calendar_id = calendar["id"].as_str().unwrap();
+    let start = Utc.with_ymd_and_hms(2030, 1, 10, 10, 0, 0).unwrap();
+    let response = app.send(json_request(Method::POST, "/api/auth/events", Some(&token), json!({
+        "calendar_id": calendar_id,
+        "event": {
+            "title": "Workshop",
+            "description": null,
+            "location": null,
+            "start_time": start,
+            "end_time": start + Duration::hours(2),
+            "is_all_day": false,
+            "rrule": null,
+            "geo_lat": null,
+            "geo_lon": null,
+        },
+    }))).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+
+    let uri = format!("/api/auth/forecast?weeks=1&calendars={}", calendar_id);
+    let response = app.send(authed_request(Method::GET, &uri, &token)).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+    let forecast = response.json();
+    assert_eq!(forecast[0]["week_start"], "2030-01-07");
+    assert_eq!(forecast[0]["hours"], 2.0);
+
+    // Login tokens are valid for 24 hours
+    clock.advance(Duration::hours(25));
+    let response = app.send(authed_request(Method::GET, "/api/auth/calendars", &token)).await;
+    assert_eq!(response.status, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn htmx_requests_get_list_fragments() {
+    let app = TestApp::with_admin().await;
+    let alice = app.user_token("alice@example.com", "secret123").await;
+    app.user_token("bob@example.com", "secret123").await;
+    let calendar = app.create_calendar(&alice, "Team").await;
+    let uri = format!("/web/calendars/{}/shares", calendar["id"].as_str().unwrap());
+
+    let share_form = |htmx: bool| {
+        let mut request = Request::post(&uri)
+            .header("authorization", format!("Bearer {}", alice))
+            .header("content-type", "application/x-www-form-urlencoded");
+        if htmx {
+            request = request.header("hx-request", "true");
+        }
+        request.body(Body::from("shared_with_email=bob%40example.com&permission=read")).unwrap()
+    };
+
+    let response = app.send(share_form(false)).await;
+    assert_eq!(response.status, StatusCode::SEE_OTHER);
+
+    let response = app.send(share_form(true)).await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert!(response.body.contains("id=\"share-list\""));
+    assert!(response.body.contains("bob@example.com"));
+    assert!(!response.body.contains("<html"));
+}
+
+#[tokio::test]
+async fn users_manage_their_own_subscriptions() {
+    let app = TestApp::with_admin().await;
+    let alice = app.user_token("alice@example.com", "secret123").await;
+    let bob = app.user_token("bob@example.com", "secret123").await;
+
+    let response = app.send(json_request(Method::POST, "/api/auth/subscriptions", Some(&alice), json!({
+        "name": "Holidays",
+        "url": "webcal://example.com/holidays.ics",
+    }))).await;
+    assert_eq!(response.status, StatusCode::CREATED);
+    let subscription = response.json();
+    assert_eq!(subscription["url"], "https://example.com/holidays.ics");
+    assert_eq!(subscription["is_visible"], true);
+    let uri = format!("/api/auth/subscriptions/{}", subscription["id"].as_str().unwrap());
+
+    let response = app.send(json_request(Method::POST, "/api/auth/subscriptions", Some(&alice), json!({
+        "name": "Local file",
+        "url": "file:///etc/passwd",
+    }))).await;
+    assert_eq!(response.status, StatusCode::BAD_REQUEST);
+
+    let response = app.send(json_request(Method::PATCH, &uri, Some(&alice), json!({ "is_visible": false }))).await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.json()["is_visible"], false);
+    assert_eq!(response.json()["name"], "Holidays");
+
+    // Other users can neither see nor change it
+    let response = app.send(authed_request(Method::GET, "/api/auth/subscriptions", &bob)).await;
+    assert_eq!(response.json().as_array().unwrap().len(), 0);
+    let response = app.send(authed_request(Method::DELETE, &uri, &bob)).await;
+    assert_eq!(response.status, StatusCode::NOT_FOUND);
+
+    let response = app.send(authed_request(Method::DELETE, &uri, &alice)).await;
+    assert_eq!(response.status, StatusCode::NO_CONTENT);
+    let response = app.send(authed_request(Method::GET, "/api/auth/subscriptions", &alice)).await;
+    assert_eq!(response.json().as_array().unwrap().len(), 0);
+}
+
+#[tokio::test]
+async fn feed_links_are_revocable_one_by_one() {
+    let app = TestApp::with_admin().await;
+    let alice = app.user_token("alice@example.com", "secret123").await;
+    let bob = app.user_token("bob@example.com", "secret123").await;
+    let calendar = app.create_calendar(&alice, "Family").await;
+    let feeds = format!("/api/auth/calendars/{}/feeds", calendar["id"].as_str().unwrap());
+
+    let response = app.send(json_request(Method::POST, &feeds, Some(&bob), json!({ "label": "Stolen" }))).await;
+    assert!(response.status.is_client_error(), "got {}", response.status);
+
+    let mut urls = Vec::new();
+    let mut ids = Vec::new();
+    for label in ["Grandma", "Wiki"] {
+        let response = app.send(json_request(Method::POST, &feeds, Some(&alice), json!({ "label": label }))).await;
+        assert_eq!(response.status, StatusCode::CREATED, "{}", response.body);
+        let feed = response.json();
+        urls.push(feed["url"].as_str().unwrap().to_string());
+        ids.push(feed["id"].as_str().unwrap().to_string());
+    }
+
+    let response = app.send(Request::builder().uri(&urls[0]).body(Body::empty()).unwrap()).await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert!(response.body.starts_with("BEGIN:VCALENDAR"));
+
+    let response = app.send(authed_request(Method::DELETE, &format!("/api/auth/feeds/{}", ids[0]), &alice)).await;
+    assert_eq!(response.status, StatusCode::NO_CONTENT);
+
+    let response = app.send(Request::builder().uri(&urls[0]).body(Body::empty()).unwrap()).await;
+    assert_eq!(response.status, StatusCode::NOT_FOUND);
+    let response = app.send(Request::builder().uri(&urls[1]).body(Body::empty()).unwrap()).await;
+    assert_eq!(response.status, StatusCode::OK);
+
+    let response = app.send(authed_request(Method::GET, &feeds, &alice)).await;
+    let listed = response.json();
+    let revoked = listed.as_array().unwrap().iter().find(|f| f["id"] == ids[0].as_str()).unwrap();
+    assert!(revoked["revoked_at"].is_string());
+    assert_eq!(revoked["fetch_count"], 1);
+    assert_eq!(revoked["clients"].as_array().unwrap().len(), 1);
+
+    let tampered = urls[1].replace(".ics", "x.ics");
+    let response = app.send(Request::builder().uri(&tampered).body(Body::empty()).unwrap()).await;
+    assert_eq!(response.status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn imports_are_parsed_as_they_stream_in() {
+    let app = TestApp::with_admin().await;
+    let token = app.user_token("alice@example.com", "secret123").await;
+    let calendar = app.create_calendar(&token, "Imported").await;
+    let calendar_id = calendar["id"].as_str().unwrap();
+
+    let ics = "BEGIN:VCALENDAR\r\nVERSION:2.0\r\n\
+        BEGIN:VEVENT\r\nUID:a\r\nDTSTART:20300101T090000Z\r\nDTEND:20300101T100000Z\r\nSUMMARY:Quarterly\r\n  planning\r\nEND:VEVENT\r\n\
+        BEGIN:VTODO\r\nUID:b\r\nSUMMARY:Prepare slides\r\nEND:VTODO\r\n\
+        BEGIN:VEVENT\r\nUID:c\r\nSUMMARY:No start\r\nEND:VEVENT\r\n\
+        BEGIN:VFREEBUSY\r\nUID:d\r\nEND:VFREEBUSY\r\n\
+        END:VCALENDAR\r\n";
+    // Chunk boundaries fall inside lines and between a line and its continuation
+    let chunks: Vec<Result<String, std::io::Error>> = ics.as_bytes()
+        .chunks(7)
+        .map(|chunk| Ok(String::from_utf8(chunk.to_vec()).unwrap()))
+        .collect();
+    let request = Request::builder()
+        .method(Method::POST)
+        .uri(format!("/api/auth/calendars/{}/import", calendar_id))
+        .header("authorization", format!("Bearer {}", token))
+        .header("content-type", "text/calendar")
+        .body(Body::from_stream(tokio_stream::iter(chunks)))
+        .unwrap();
+
+    let response = app.send(request).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+    let summary = response.json();
+    assert_eq!(summary["events"]["imported"], 1);
+    assert_eq!(summary["events"]["failed"], 1);
+    assert_eq!(summary["tasks"]["imported"], 1);
+    assert_eq!(summary["skipped"]["VFREEBUSY"], 1);
+
+    let response = app.send(authed_request(Method::GET, &format!("/api/auth/calendars/{}/events", calendar_id), &token)).await;
+    let events = response.json();
+    assert_eq!(events.as_array().unwrap().len(), 1);
+    assert_eq!(events[0]["title"], "Quarterly planning");
+
+    let response = app.send(json_request(Method::POST, &format!("/api/auth/calendars/{}/import", calendar_id), Some(&token), json!({}))).await;
+    assert_eq!(response.status, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn outlook_files_are_read_in_compatibility_mode() {
+    let app = TestApp::with_admin().await;
+    let token = app.user_token("alice@example.com", "secret123").await;
+    let calendar_id = app.create_calendar(&token, "Outlook").await["id"].as_str().unwrap().to_string();
+    let import = |query: &str, ics: &[u8]| Request::builder()
+        .method(Method::POST)
+        .uri(format!("/api/auth/calendars/{}/import{}", calendar_id, query))
+        .header("authorization", format!("Bearer {}", token))
+        .header("content-type", "text/calendar")
+        .body(Body::from(ics.to_vec()))
+        .unwrap();
+
+    let response = app.send(import("", include_bytes!("fixtures/outlook/outlook-16.ics"))).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+    let summary = response.json();
+    assert_eq!(summary["events"]["imported"], 2);
+    assert_eq!(summary["quirks"], json!(["windows-time-zones", "language-parameters", "cdo-all-day"]));
+
+    // Exchange 2010 ends lines with a bare CR
+    let response = app.send(import("", include_bytes!("fixtures/outlook/exchange-2010-cr.ics"))).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+    assert_eq!(response.json()["events"]["imported"], 1);
+
+    // Files from other producers only get the rewrites when asked for them
+    let ics = b"BEGIN:VCALENDAR\r\nPRODID:-//Example//Sync//EN\r\nBEGIN:VEVENT\r\nUID:tokyo\r\n\
+        DTSTART;TZID=Tokyo Standard Time:20300301T090000\r\nDTEND;TZID=Tokyo Standard Time:20300301T100000\r\n\
+        SUMMARY:Call with Tokyo\r\nEND:VEVENT\r\nEND:VCALENDAR\r\n";
+    let response = app.send(import("?compat=outlook", ics)).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+    assert_eq!(response.json()["quirks"], json!(["windows-time-zones"]));
+    let response = app.send(import("?compat=lotus", ics)).await;
+    assert_eq!(response.status, StatusCode::BAD_REQUEST);
+
+    let response = app.send(authed_request(Method::GET, &format!("/api/auth/calendars/{}/events", calendar_id), &token)).await;
+    let events = response.json();
+    let event = |title: &str| events.as_array().unwrap().iter().find(|event| event["title"] == title).cloned().unwrap();
+    let sync = event("Team sync");
+    assert_eq!(sync["start_time"], "2030-01-07T08:30:00Z");
+    assert_eq!(sync["location"], "Conference Room 2");
+    assert_eq!(sync["alarm_minutes"], 15);
+    let fair = event("Messe");
+    assert_eq!(fair["is_all_day"], true);
+    assert_eq!(fair["start_time"], "2030-01-14T00:00:00Z");
+    assert_eq!(fair["end_time"], "2030-01-16T00:00:00Z");
+    assert_eq!(event("Budget review")["start_time"], "2030-06-10T18:00:00Z");
+    assert_eq!(event("Call with Tokyo")["start_time"], "2030-03-01T00:00:00Z");
+}
+
+#[tokio::test]
+async fn ical_objects_are_validated_line_by_line() {
+    let app = TestApp::with_admin().await;
+    let token = app.user_token("alice@example.com", "secret123").await;
+    let validate = |token: Option<&str>, ics: &str| {
+        let mut request = Request::builder()
+            .method(Method::POST)
+            .uri("/api/auth/ical/validate")
+            .header("content-type", "text/calendar");
+        if let Some(token) = token {
+            request = request.header("authorization", format!("Bearer {}", token));
+        }
+        request.body(Body::from(ics.to_string())).unwrap()
+    };
+
+    let ics = "BEGIN:VCALENDAR\r\nVERSION:2.0\r\nPRODID:-//Example//EN\r\nBEGIN:VEVENT\r\nUID:ok\r\n\
+        DTSTAMP:20300101T000000Z\r\nDTSTART:20300107T090000Z\r\nDTEND:20300107T100000Z\r\n\
+        SUMMARY:Standup\r\nRRULE:FREQ=WEEKLY;COUNT=5\r\nEND:VEVENT\r\nEND:VCALENDAR\r\n";
+    let response = app.send(validate(Some(&token), ics)).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+    let report = response.json();
+    assert_eq!(report["valid"], true, "{}", report);
+    assert_eq!(report["warnings"], json!([]));
+    assert_eq!(report["components"]["VEVENT"], 1);
+
+    let ics = "BEGIN:VCALENDAR\nVERSION:2.0\nPRODID:-//Phone//EN\nBEGIN:VEVENT\nDTSTAMP:20300101T000000Z\n\
+        DTSTART;TZID=Mars/Olympus_Mons:20300107T090000\nDTEND:20300107T100000Z\n\
+        SUMMARY:Standup with notes\nthat were not folded\n\
+        RRULE:FREQ=WEEKLY;COUNT=5;UNTIL=20300301T000000Z\nEND:VEVENT\nEND:VCALENDAR\n";
+    let report = app.send(validate(Some(&token), ics)).await.json();
+    assert_eq!(report["valid"], false);
+    let issues = |kind: &str| report[kind].as_array().unwrap().iter()
+        .map(|issue| (issue["code"].as_str().unwrap().to_string(), issue["line"].as_u64().unwrap()))
+        .collect::<Vec<_>>();
+    let errors = issues("errors");
+    for expected in [("folding", 9), ("missing-uid", 4), ("invalid-rrule", 10), ("unknown-time-zone", 6)] {
+        assert!(errors.contains(&(expected.0.to_string(), expected.1)), "{:?} missing from {:?}", expected, errors);
+    }
+    assert_eq!(issues("warnings"), vec![("line-endings".to_string(), 1)]);
+
+    let response = app.send(validate(None, ics)).await;
+    assert_eq!(response.status, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn events_round_trip_through_csv_with_a_column_mapping() {
+    let app = TestApp::with_admin().await;
+    let token = app.user_token("alice@example.com", "secret123").await;
+    let calendar_id = app.create_calendar(&token, "Spreadsheet").await["id"].as_str().unwrap().to_string();
+    let csv_request = |uri: String, csv: &str| Request::builder()
+        .method(Method::POST)
+        .uri(uri)
+        .header("authorization", format!("Bearer {}", token))
+        .header("content-type", "text/csv")
+        .body(Body::from(csv.to_string()))
+        .unwrap();
+
+    let csv = "Subject,Begins,Finishes,Where,Notes\n\
+        Planning,2030-01-07 09:00,2030-01-07 10:30,Room 1,\"Agenda, then lunch\"\n\
+        Offsite,2030-01-08,2030-01-09,,\n\
+        Backwards,2030-01-07 10:00,2030-01-07 09:00,,\n\
+        ,2030-01-07 11:00,,,\n";
+
+    // The preview suggests what the header names give away and leaves the rest to the user
+    let response = app.send(csv_request(format!("/api/auth/calendars/{}/import/csv/preview", calendar_id), csv)).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+    let preview = response.json();
+    assert_eq!(preview["columns"], json!(["Subject", "Begins", "Finishes", "Where", "Notes"]));
+    assert_eq!(preview["mapping"]["title"], "Subject");
+    assert_eq!(preview["mapping"]["location"], "Where");
+    assert!(preview["mapping"]["start_time"].is_null());
+    assert_eq!(preview["rows"][0][4], "Agenda, then lunch");
+
+    let uri = format!("/api/auth/calendars/{}/import/csv", calendar_id);
+    let response = app.send(csv_request(uri.clone(), csv)).await;
+    assert_eq!(response.status, StatusCode::BAD_REQUEST);
+    assert_eq!(response.json()["field"], "start_time");
+
+    // Times without an offset are read in the chosen time zone; dates make all-day events
+    let mapping = "start_time=Begins&end_time=Finishes&description=Notes&timezone=Europe/Berlin";
+    let response = app.send(csv_request(format!("{}?{}", uri, mapping), csv)).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+    let summary = response.json();
+    assert_eq!(summary["events"]["imported"], 2);
+    assert_eq!(summary["events"]["failed"], 2);
+    assert_eq!(summary["errors"][0], "Row 4: Validation error: end_time: End must not be before the start");
+    assert_eq!(summary["errors"][1], "Row 5: Validation error: title: Title is required");
+
+    let events = app.send(authed_request(Method::GET, &format!("/api/auth/calendars/{}/events", calendar_id), &token)).await.json();
+    let planning = events.as_array().unwrap().iter().find(|event| event["title"] == "Planning").unwrap();
+    assert_eq!(planning["start_time"], "2030-01-07T08:00:00Z");
+    assert_eq!(planning["end_time"], "2030-01-07T09:30:00Z");
+    assert_eq!(planning["location"], "Room 1");
+    let offsite = events.as_array().unwrap().iter().find(|event| event["title"] == "Offsite").unwrap();
+    assert_eq!(offsite["is_all_day"], true);
+    assert_eq!(offsite["end_time"], "2030-01-10T00:00:00Z");
+
+    // The export reads back without a mapping
+    let response = app.send(authed_request(Method::GET, &format!("/api/auth/calendars/{}/events.csv", calendar_id), &token)).await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.header("content-type"), Some("text/csv; charset=utf-8"));
+    assert!(response.body.starts_with("id,title,start_time,end_time,all_day,location,description,rrule\n"), "{}", response.body);
+    assert!(response.body.contains(",Planning,2030-01-07T08:00:00+00:00,2030-01-07T09:30:00+00:00,false,Room 1,\"Agenda, then lunch\","), "{}", response.body);
+    assert!(response.body.contains(",Offsite,2030-01-08,2030-01-09,true,,,"), "{}", response.body);
+
+    let copy = app.create_calendar(&token, "Copy").await["id"].as_str().unwrap().to_string();
+    let response = app.send(csv_request(format!("/api/auth/calendars/{}/import/csv", copy), &response.body)).await;
+    assert_eq!(response.json()["events"]["imported"], 2, "{}", response.body);
+    let copied = app.send(authed_request(Method::GET, &format!("/api/auth/calendars/{}/events", copy), &token)).await.json();
+    let offsite = copied.as_array().unwrap().iter().find(|event| event["title"] == "Offsite").unwrap();
+    assert_eq!(offsite["end_time"], "2030-01-10T00:00:00Z");
+}
+
+#[tokio::test]
+async fn google_takeout_archives_become_calendars() {
+    use std::io::Write;
+
+    let app = TestApp::with_admin().await;
+    let token = app.user_token("alice@example.com", "secret123").await;
+
+    let primary = "BEGIN:VCALENDAR\r\nPRODID:-//Google Inc//Google Calendar 70.9054//EN\r\nVERSION:2.0\r\n\
+        X-WR-CALNAME:alice@example.com\r\nX-WR-TIMEZONE:Europe/Berlin\r\n\
+        BEGIN:VEVENT\r\nDTSTART;TZID=Europe/Berlin:20300107T090000\r\nDTEND;TZID=Europe/Berlin:20300107T091500\r\n\
+        RRULE:FREQ=WEEKLY;COUNT=4\r\nEXDATE;TZID=Europe/Berlin:20300114T090000\r\nUID:standup@google.com\r\n\
+        SUMMARY:Standup\\, team\r\nBEGIN:VALARM\r\nACTION:DISPLAY\r\nTRIGGER:-P0DT0H10M0S\r\nEND:VALARM\r\nEND:VEVENT\r\n\
+        BEGIN:VEVENT\r\nDTSTART;TZID=Europe/Berlin:20300121T100000\r\nDTEND;TZID=Europe/Berlin:20300121T101500\r\n\
+        RECURRENCE-ID;TZID=Europe/Berlin:20300121T090000\r\nUID:standup@google.com\r\nSUMMARY:Standup (moved)\r\nEND:VEVENT\r\n\
+        BEGIN:VEVENT\r\nDTSTART;TZID=Europe/Berlin:20300128T090000\r\nDTEND;TZID=Europe/Berlin:20300128T091500\r\n\
+        RECURRENCE-ID;TZID=Europe/Berlin:20300128T090000\r\nUID:standup@google.com\r\nSTATUS:CANCELLED\r\nSUMMARY:Standup\r\nEND:VEVENT\r\n\
+        BEGIN:VEVENT\r\nUID:broken@google.com\r\nSUMMARY:Broken\r\nEND:VEVENT\r\n\
+        BEGIN:VTODO\r\nUID:todo@google.com\r\nSUMMARY:Not an event\r\nEND:VTODO\r\n\
+        END:VCALENDAR\r\n";
+    let holidays = "BEGIN:VCALENDAR\r\nVERSION:2.0\r\nX-WR-CALNAME:Holidays in Germany\r\nX-APPLE-CALENDAR-COLOR:#ff0000ff\r\n\
+        BEGIN:VEVENT\r\nDTSTART;VALUE=DATE:20301225\r\nDTEND;VALUE=DATE:20301226\r\nUID:xmas@google.com\r\nSUMMARY:Christmas Day\r\nEND:VEVENT\r\n\
+        END:VCALENDAR\r\n";
+    let mut archive = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+    for (name, data) in [
+        ("Takeout/Calendar/alice@example.com.ics", primary),
+        ("Takeout/Calendar/Holidays in Germany.ics", holidays),
+        ("Takeout/archive_browser.html", "<html></html>"),
+    ] {
+        archive.start_file(name, zip::write::SimpleFileOptions::default()).unwrap();
+        archive.write_all(data.as_bytes()).unwrap();
+    }
+    let archive = archive.finish().unwrap().into_inner();
+
+    let response = app.send(Request::builder()
+        .method(Method::POST)
+        .uri("/api/auth/import/google")
+        .header("authorization", format!("Bearer {}", token))
+        .header("content-type", "application/zip")
+        .body(Body::from(archive))
+        .unwrap()).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+    let summary = response.json();
+    assert_eq!(summary["skipped_files"], json!(["Takeout/archive_browser.html"]));
+    let primary = &summary["calendars"][0];
+    assert_eq!(primary["name"], "alice@example.com");
+    assert_eq!(primary["events"]["imported"], 3);
+    assert_eq!(primary["events"]["failed"], 1);
+    assert_eq!(primary["skipped"]["VTODO"], 1);
+    assert_eq!(primary["errors"][0], "Broken: Validation error: Missing DTSTART");
+    let holidays = &summary["calendars"][1];
+    assert_eq!(holidays["name"], "Holidays in Germany");
+    assert_eq!(holidays["color"], "#FF0000");
+    assert_eq!(holidays["events"]["imported"], 1);
+
+    // Local times are read in their zone, and the series keeps its reminder, exclusions and override
+    let uri = format!("/api/auth/calendars/{}/events", primary["calendar_id"].as_str().unwrap());
+    let events = app.send(authed_request(Method::GET, &uri, &token)).await.json();
+    let events = events.as_array().unwrap();
+    assert_eq!(events.len(), 2, "{:?}", events);
+    let standup = events.iter().find(|event| event["parent_id"].is_null()).unwrap();
+    assert_eq!(standup["title"], "Standup, team");
+    assert_eq!(standup["start_time"], "2030-01-07T08:00:00Z");
+    assert_eq!(standup["alarm_minutes"], 10);
+    assert_eq!(standup["uid"], "standup@google.com");
+    assert_eq!(standup["exdates"], json!(["2030-01-14T08:00:00Z", "2030-01-28T08:00:00Z"]));
+    let moved = events.iter().find(|event| !event["parent_id"].is_null()).unwrap();
+    assert_eq!(moved["title"], "Standup (moved)");
+    assert_eq!(moved["recurrence_id"], "2030-01-21T08:00:00Z");
+    assert_eq!(moved["start_time"], "2030-01-21T09:00:00Z");
+
+    let calendars = app.send(authed_request(Method::GET, "/api/auth/calendars", &token)).await.json();
+    let imported = calendars.as_array().unwrap().iter().find(|calendar| calendar["name"] == "Holidays in Germany").unwrap();
+    assert_eq!(imported["color"], "#FF0000");
+
+    // The web UI walks through the export, and reports files that aren't archives
+    let response = app.send(authed_request(Method::GET, "/web/import/google", &token)).await;
+    assert!(response.body.contains("takeout.google.com"), "{}", response.body);
+    let boundary = "takeout-boundary";
+    let response = app.send(Request::post("/web/import/google")
+        .header("cookie", format!("auth_token={}", token))
+        .header("content-type", format!("multipart/form-data; boundary={}", boundary))
+        .body(Body::from(format!("--{b}\r\nContent-Disposition: form-data; name=\"file\"; filename=\"calendar.ics\"\r\n\r\nBEGIN:VCALENDAR\r\n--{b}--\r\n", b = boundary)))
+        .unwrap()).await;
+    assert_eq!(response.status, StatusCode::SEE_OTHER);
+    assert_eq!(response.header("location"), Some("/web/import/google"));
+}
+
+#[tokio::test]
+async fn dashboard_lists_upcoming_events_across_calendars() {
+    let clock = MockClock::new(Utc.with_ymd_and_hms(2030, 3, 4, 8, 0, 0).unwrap());
+    let app = TestApp::with_clock(clock.clone()).await;
+    app.setup(ADMIN_EMAIL, ADMIN_PASSWORD).await;
+    let token = app.user_token("alice@example.com", "secret123").await;
+    let work = app.create_calendar(&token, "Work").await;
+    let home = app.create_calendar(&token, "Home").await;
+
+    let now = clock.now();
+    for (calendar, title, start, rrule) in [
+        (&work, "Weekly sync", now - Duration::days(400), Some("FREQ=WEEKLY")),
+        (&home, "Plumber visit", now + Duration::days(3), None),
+        (&home, "Summer holiday", now + Duration::days(90), None),
+    ] {
+        let response = app.send(json_request(Method::POST, "/api/auth/events", Some(&token), json!({
+            "calendar_id": calendar["id"],
+            "event": {
+                "title": title,
+                "description": null,
+                "location": null,
+                "start_time": start,
+                "end_time": start + Duration::hours(1),
+                "is_all_day": false,
+                "rrule": rrule,
+                "geo_lat": null,
+                "geo_lon": null,
+            },
+        }))).await;
+        assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+    }
+
+    let response = app.send(authed_request(Method::GET, "/web/dashboard", &token)).await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert!(response.body.contains("Weekly sync"));
+    assert!(response.body.contains("Plumber visit"));
+    assert!(!response.body.contains("Summer holiday"));
+}
+
+#[tokio::test]
+async fn navigation_only_links_admin_pages_for_admins() {
+    let app = TestApp::new().await;
+    app.setup(ADMIN_EMAIL, ADMIN_PASSWORD).await;
+    let admin = app.login(ADMIN_EMAIL, ADMIN_PASSWORD).await.json()["token"].as_str().unwrap().to_string();
+    let user = app.user_token("alice@example.com", "secret123").await;
+
+    let response = app.send(authed_request(Method::GET, "/web/dashboard", &admin)).await;
+    assert!(response.body.contains("href=\"/web/admin\""), "{}", response.body);
+    assert!(!response.body.contains("btn-disabled"));
+
+    let response = app.send(authed_request(Method::GET, "/web/dashboard", &user)).await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert!(!response.body.contains("href=\"/web/admin\""));
+}
+
+#[tokio::test]
+async fn conflicts_include_instances_of_recurring_events() {
+    let app = TestApp::new().await;
+    app.setup(ADMIN_EMAIL, ADMIN_PASSWORD).await;
+    let token = app.user_token("alice@example.com", "secret123").await;
+    let calendar = app.create_calendar(&token, "Work").await;
+
+    // Mondays 9:00-9:30, starting two months before the checked week
+    let response = app.send(json_request(Method::POST, "/api/auth/events", Some(&token), json!({
+        "calendar_id": calendar["id"],
+        "event": {
+            "title": "Standup",
+            "description": null,
+            "location": null,
+            "start_time": "2030-01-07T09:00:00Z",
+            "end_time": "2030-01-07T09:30:00Z",
+            "is_all_day": false,
+            "rrule": "FREQ=WEEKLY",
+            "geo_lat": null,
+            "geo_lon": null,
+        },
+    }))).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+    let standup_id = response.json()["id"].as_str().unwrap().to_string();
+
+    let monday = "/api/auth/events/conflicts?start=2030-03-04T09:15:00Z&end=2030-03-04T10:00:00Z";
+    let response = app.send(authed_request(Method::GET, monday, &token)).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+    let conflicts = response.json();
+    assert_eq!(conflicts.as_array().unwrap().len(), 1);
+    assert_eq!(conflicts[0]["title"], "Standup");
+    assert_eq!(conflicts[0]["start_time"], "2030-03-04T09:00:00Z");
+
+    let tuesday = "/api/auth/events/conflicts?start=2030-03-05T09:15:00Z&end=2030-03-05T10:00:00Z";
+    let response = app.send(authed_request(Method::GET, tuesday, &token)).await;
+    assert!(response.json().as_array().unwrap().is_empty());
+
+    let response = app.send(authed_request(Method::GET, &format!("{}&exclude={}", monday, standup_id), &token)).await;
+    assert!(response.json().as_array().unwrap().is_empty());
+
+    let response = app.send(authed_request(Method::GET, "/api/auth/events/conflicts?start=2030-03-04T10:00:00Z&end=2030-03-04T09:00:00Z", &token)).await;
+    assert_eq!(response.status, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn user_feeds_merge_the_selected_calendars() {
+    let app = TestApp::with_admin().await;
+    let alice = app.user_token("alice@example.com", "secret123").await;
+    let bob = app.user_token("bob@example.com", "secret123").await;
+    let mut calendars = Vec::new();
+    for (name, color) in [("Work", "#1e88e5"), ("Home", "#43a047"), ("Private", "#8e24aa")] {
+        let response = app.send(json_request(Method::POST, "/api/auth/calendars", Some(&alice), json!({
+            "name": name,
+            "description": null,
+            "color": color,
+            "is_public": false,
+        }))).await;
+        let calendar = response.json();
+        let response = app.send(json_request(Method::POST, "/api/auth/events", Some(&alice), json!({
+            "calendar_id": calendar["id"],
+            "event": {
+                "title": format!("{} event", name),
+                "description": null,
+                "location": null,
+                "start_time": "2030-03-04T09:00:00Z",
+                "end_time": "2030-03-04T10:00:00Z",
+                "is_all_day": false,
+                "rrule": null,
+                "geo_lat": null,
+                "geo_lon": null,
+            },
+        }))).await;
+        assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+        calendars.push(calendar);
+    }
+    let bobs = app.create_calendar(&bob, "Bob's").await;
+
+    let response = app.send(json_request(Method::POST, "/api/auth/user-feeds", Some(&alice), json!({
+        "label": "Kitchen TV",
+        "calendar_ids": [calendars[0]["id"], bobs["id"]],
+    }))).await;
+    assert_eq!(response.status, StatusCode::BAD_REQUEST);
+
+    let response = app.send(json_request(Method::POST, "/api/auth/user-feeds", Some(&alice), json!({
+        "label": "Kitchen TV",
+        "calendar_ids": [calendars[0]["id"], calendars[1]["id"]],
+    }))).await;
+    assert_eq!(response.status, StatusCode::CREATED, "{}", response.body);
+    let feed = response.json();
+    let url = feed["url"].as_str().unwrap().to_string();
+    assert!(url.ends_with("/all.ics"));
+
+    let response = app.send(Request::builder().uri(&url).body(Body::empty()).unwrap()).await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert!(response.body.contains("SUMMARY:Work event"));
+    assert!(response.body.contains("SUMMARY:Home event"));
+    assert!(!response.body.contains("Private event"));
+    assert!(response.body.contains("COLOR:#1e88e5"));
+    assert!(response.body.contains("COLOR:#43a047"));
+
+    let revoke = format!("/api/auth/user-feeds/{}", feed["id"].as_str().unwrap());
+    let response = app.send(authed_request(Method::DELETE, &revoke, &bob)).await;
+    assert_eq!(response.status, StatusCode::NOT_FOUND);
+    let response = app.send(authed_request(Method::DELETE, &revoke, &alice)).await;
+    assert_eq!(response.status, StatusCode::NO_CONTENT);
+
+    let response = app.send(Request::builder().uri(&url).body(Body::empty()).unwrap()).await;
+    assert_eq!(response.status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn errors_carry_a_code_message_and_field() {
+    let app = TestApp::new().await;
+    app.setup(ADMIN_EMAIL, ADMIN_PASSWORD).await;
+    let token = app.user_token("alice@example.com", "secret123").await;
+
+    let response = app.send(authed_request(
+        Method::GET,
+        "/api/auth/events/conflicts?start=2025-03-10T10:00:00Z&end=2025-03-10T09:00:00Z",
+        &token,
+    )).await;
+    assert_eq!(response.status, StatusCode::BAD_REQUEST);
+    let body = response.json();
+    assert_eq!(body["code"], "validation_failed");
+    assert_eq!(body["message"], "end must be after start");
+    assert_eq!(body["field"], "end");
+    assert_eq!(body["status"], 400);
+
+    let response = app.register("Alice again", "alice@example.com", "secret123").await;
+    assert_eq!(response.status, StatusCode::BAD_REQUEST);
+    assert_eq!(response.json()["field"], "email");
+
+    let missing = format!("/api/auth/calendars/{}", uuid::Uuid::new_v4());
+    let response = app.send(authed_request(Method::DELETE, &missing, &token)).await;
+    assert_eq!(response.status, StatusCode::NOT_FOUND);
+    let body = response.json();
+    assert_eq!(body["code"], "not_found");
+    assert!(body.get("field").is_none());
+
+    let response = app.send(authed_request(Method::GET, "/api/auth/calendars", "not-a-jwt")).await;
+    assert_eq!(response.status, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn event_batches_report_each_operation_and_can_be_atomic() {
+    let app = TestApp::new().await;
+    app.setup(ADMIN_EMAIL, ADMIN_PASSWORD).await;
+    let token = app.user_token("alice@example.com", "secret123").await;
+    let calendar = app.create_calendar(&token, "Imports").await;
+    let event = |title: &str| json!({
+        "title": title,
+        "description": null,
+        "location": null,
+        "start_time": "2030-01-07T09:00:00Z",
+        "end_time": "2030-01-07T10:00:00Z",
+        "is_all_day": false,
+        "rrule": null,
+        "geo_lat": null,
+        "geo_lon": null,
+    });
+
+    let response = app.send(json_request(Method::POST, "/api/auth/events/batch", Some(&token), json!({
+        "operations": [
+            { "op": "create", "calendar_id": calendar["id"], "event": event("First") },
+            { "op": "create", "calendar_id": calendar["id"], "event": event("Second") },
+            { "op": "delete", "event_id": uuid::Uuid::new_v4() },
+        ],
+    }))).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+    let results = response.json();
+    assert_eq!(results[0]["index"], 0);
+    assert_eq!(results[0]["status"], "applied");
+    assert_eq!(results[1]["event"]["title"], "Second");
+    assert_eq!(results[2]["status"], "not_found");
+    let first = results[0]["event"]["id"].clone();
+    let second = results[1]["event"]["id"].clone();
+
+    let response = app.send(json_request(Method::POST, "/api/auth/events/batch", Some(&token), json!({
+        "operations": [
+            { "op": "update", "event_id": first, "changes": { "location": "Room 2" } },
+            { "op": "delete", "event_id": second },
+        ],
+    }))).await;
+    let results = response.json();
+    assert_eq!(results[0]["event"]["location"], "Room 2");
+    assert_eq!(results[1]["status"], "applied");
+
+    // One stale update makes an atomic batch apply nothing
+    let response = app.send(json_request(Method::POST, "/api/auth/events/batch", Some(&token), json!({
+        "atomic": true,
+        "operations": [
+            { "op": "create", "calendar_id": calendar["id"], "event": event("Third") },
+            { "op": "update", "event_id": first, "base_version": "2000-01-01T00:00:00Z", "changes": { "title": "Stale" } },
+        ],
+    }))).await;
+    let results = response.json();
+    assert_eq!(results[0]["status"], "skipped");
+    assert_eq!(results[1]["status"], "conflict");
+
+    let uri = format!("/api/auth/calendars/{}/events", calendar["id"].as_str().unwrap());
+    let events = app.send(authed_request(Method::GET, &uri, &token)).await.json();
+    let titles: Vec<_> = events.as_array().unwrap().iter().map(|e| e["title"].as_str().unwrap()).collect();
+    assert_eq!(titles, vec!["First"]);
+}
+
+#[tokio::test]
+async fn offline_sync_reports_stale_mutations_as_conflicts_and_applies_the_rest() {
+    let app = TestApp::with_admin().await;
+    let token = app.user_token("alice@example.com", "secret123").await;
+    let calendar = app.create_calendar(&token, "Phone").await;
+    let event = |title: &str| json!({
+        "title": title,
+        "description": null,
+        "location": null,
+        "start_time": "2030-01-07T09:00:00Z",
+        "end_time": "2030-01-07T10:00:00Z",
+        "is_all_day": false,
+        "rrule": null,
+        "geo_lat": null,
+        "geo_lon": null,
+    });
+    let commit = |mutations: serde_json::Value| json_request(Method::POST, "/api/auth/sync/commit", Some(&token), json!({ "mutations": mutations }));
+
+    let response = app.send(commit(json!([
+        { "client_id": "a", "op": "create", "calendar_id": calendar["id"], "event": event("Standup") },
+        { "client_id": "b", "op": "create", "calendar_id": calendar["id"], "event": event("Review") },
+    ]))).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+    let created = response.json();
+    let standup = created[0]["event"].clone();
+    let review = created[1]["event"].clone();
+
+    // The standup was edited elsewhere after the phone last synced
+    let response = app.send(json_request(Method::PUT, &format!("/api/auth/events/{}", standup["id"].as_str().unwrap()), Some(&token), json!({
+        "title": "Standup (moved)",
+    }))).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+
+    let response = app.send(commit(json!([
+        { "client_id": "1", "op": "update", "event_id": standup["id"], "base_version": standup["updated_at"], "changes": { "title": "Standup (phone)" } },
+        { "client_id": "2", "op": "update", "event_id": review["id"], "base_version": review["updated_at"], "changes": { "location": "Room 2" } },
+        { "client_id": "3", "op": "create", "calendar_id": calendar["id"], "event": event("Lunch") },
+    ]))).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+    let results = response.json();
+    assert_eq!(results[0]["client_id"], "1");
+    assert_eq!(results[0]["status"], "conflict");
+    assert_eq!(results[0]["event"]["title"], "Standup (moved)");
+    assert_eq!(results[1]["status"], "applied");
+    assert_eq!(results[1]["event"]["location"], "Room 2");
+    assert_eq!(results[2]["status"], "applied");
+
+    let uri = format!("/api/auth/calendars/{}/events", calendar["id"].as_str().unwrap());
+    let events = app.send(authed_request(Method::GET, &uri, &token)).await.json();
+    let mut titles: Vec<_> = events.as_array().unwrap().iter().map(|e| e["title"].as_str().unwrap()).collect();
+    titles.sort();
+    assert_eq!(titles, vec!["Lunch", "Review", "Standup (moved)"]);
+
+    let mutations: Vec<_> = (0..501).map(|i| json!({ "client_id": i.to_string(), "op": "delete", "event_id": review["id"], "base_version": review["updated_at"] })).collect();
+    let response = app.send(commit(json!(mutations))).await;
+    assert_eq!(response.status, StatusCode::BAD_REQUEST);
+    assert_eq!(response.json()["field"], "mutations");
+}
+
+#[tokio::test]
+async fn events_can_be_duplicated_into_the_same_or_another_calendar() {
+    let app = TestApp::new().await;
+    app.setup(ADMIN_EMAIL, ADMIN_PASSWORD).await;
+    let token = app.user_token("alice@example.com", "secret123").await;
+    let work = app.create_calendar(&token, "Work").await;
+    let home = app.create_calendar(&token, "Home").await;
+
+    let response = app.send(json_request(Method::POST, "/api/auth/events", Some(&token), json!({
+        "calendar_id": work["id"],
+        "event": {
+            "title": "Standup",
+            "description": "Daily sync",
+            "location": "Room 1",
+            "start_time": "2030-01-07T09:00:00Z",
+            "end_time": "2030-01-07T09:30:00Z",
+            "is_all_day": false,
+            "rrule": "FREQ=WEEKLY",
+            "geo_lat": null,
+            "geo_lon": null,
+        },
+    }))).await;
+    let original = response.json();
+    let uri = format!("/api/auth/events/{}/duplicate", original["id"].as_str().unwrap());
+
+    let response = app.send(authed_request(Method::POST, &uri, &token)).await;
+    assert_eq!(response.status, StatusCode::CREATED, "{}", response.body);
+    let copy = response.json();
+    assert_ne!(copy["id"], original["id"]);
+    assert_eq!(copy["calendar_id"], work["id"]);
+    for field in ["title", "description", "location", "start_time", "end_time", "rrule"] {
+        assert_eq!(copy[field], original[field], "{}", field);
+    }
+
+    let response = app.send(json_request(Method::POST, &uri, Some(&token), json!({ "calendar_id": home["id"] }))).await;
+    assert_eq!(response.status, StatusCode::CREATED, "{}", response.body);
+    assert_eq!(response.json()["calendar_id"], home["id"]);
+
+    // Copying into someone else's calendar is refused
+    let other = app.user_token("bob@example.com", "secret123").await;
+    let foreign = app.create_calendar(&other, "Bob's").await;
+    let response = app.send(json_request(Method::POST, &uri, Some(&token), json!({ "calendar_id": foreign["id"] }))).await;
+    assert!(response.status.is_client_error());
+}
+
+#[tokio::test]
+async fn share_links_grant_scoped_access_until_revoked_or_expired() {
+    let clock = MockClock::new(Utc.with_ymd_and_hms(2030, 1, 1, 12, 0, 0).unwrap());
+    let app = TestApp::with_clock(clock.clone()).await;
+    app.setup(ADMIN_EMAIL, ADMIN_PASSWORD).await;
+    let alice = app.user_token("alice@example.com", "secret123").await;
+    let calendar = app.create_calendar(&alice, "Team").await;
+    let calendar_id = calendar["id"].as_str().unwrap();
+    let links_uri = format!("/api/auth/calendars/{}/share-links", calendar_id);
+
+    let response = app.send(json_request(Method::POST, &links_uri, Some(&alice), json!({ "permission": "admin" }))).await;
+    assert_eq!(response.status, StatusCode::BAD_REQUEST);
+    assert_eq!(response.json()["field"], "permission");
+
+    let response = app.send(json_request(Method::POST, &links_uri, Some(&alice), json!({ "permission": "read", "expires_in_days": 1 }))).await;
+    assert_eq!(response.status, StatusCode::CREATED, "{}", response.body);
+    let link = response.json();
+    let url = link["url"].as_str().unwrap();
+    let token = url.rsplit('/').next().unwrap().to_string();
+    let path = &url[url.find("/share/").unwrap()..];
+
+    // Opening the link signed out remembers it; signing in then redeems it
+    let bob = app.user_token("bob@example.com", "secret123").await;
+    let response = app.send(Request::builder().uri(path).body(Body::empty()).unwrap()).await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert!(response.header("set-cookie").unwrap().starts_with(&format!("share_link={}", token)));
+
+    let response = app.send(Request::builder()
+        .method(Method::POST)
+        .uri("/web/login")
+        .header("content-type", "application/x-www-form-urlencoded")
+        .header("cookie", format!("share_link={}", token))
+        .body(Body::from("email=bob%40example.com&password=secret123"))
+        .unwrap()).await;
+    assert_eq!(response.header("location"), Some("/web/share-links/continue"));
+
+    let response = app.send(Request::builder()
+        .uri("/web/share-links/continue")
+        .header("cookie", format!("auth_token={}; share_link={}", bob, token))
+        .body(Body::empty())
+        .unwrap()).await;
+    assert_eq!(response.status, StatusCode::SEE_OTHER);
+    assert!(response.header("location").unwrap().starts_with(&format!("/web/calendars/{}", calendar_id)));
+
+    let shares = app.send(authed_request(Method::GET, &format!("/api/auth/calendars/{}/shares", calendar_id), &alice)).await.json();
+    assert_eq!(shares.as_array().unwrap().len(), 1);
+    assert_eq!(shares[0]["shared_with_email"], "bob@example.com");
+    assert_eq!(shares[0]["permission_level"], "read");
+    let events_uri = format!("/api/auth/calendars/{}/events", calendar_id);
+    assert_eq!(app.send(authed_request(Method::GET, &events_uri, &bob)).await.status, StatusCode::OK);
+
+    // Revoked links can't be redeemed any more
+    let response = app.send(json_request(Method::POST, &links_uri, Some(&alice), json!({ "permission": "write" }))).await;
+    let write_link = response.json();
+    let write_token = write_link["url"].as_str().unwrap().rsplit('/').next().unwrap().to_string();
+    let revoke = format!("/api/auth/share-links/{}", write_link["id"].as_str().unwrap());
+    assert_eq!(app.send(authed_request(Method::DELETE, &revoke, &alice)).await.status, StatusCode::NO_CONTENT);
+    let carol = app.user_token("carol@example.com", "secret123").await;
+    let response = app.send(json_request(Method::POST, "/api/auth/share-links/redeem", Some(&carol), json!({ "token": write_token }))).await;
+    assert_eq!(response.status, StatusCode::NOT_FOUND);
+
+    // Neither can expired ones
+    clock.advance(Duration::days(2));
+    let carol = app.login("carol@example.com", "secret123").await.json()["token"].as_str().unwrap().to_string();
+    let response = app.send(json_request(Method::POST, "/api/auth/share-links/redeem", Some(&carol), json!({ "token": token }))).await;
+    assert_eq!(response.status, StatusCode::NOT_FOUND);
+    let alice = app.login("alice@example.com", "secret123").await.json()["token"].as_str().unwrap().to_string();
+    let links = app.send(authed_request(Method::GET, &links_uri, &alice)).await.json();
+    assert!(links.as_array().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn calendars_can_be_transferred_to_another_user() {
+    let app = TestApp::new().await;
+    app.setup(ADMIN_EMAIL, ADMIN_PASSWORD).await;
+    let alice = app.user_token("alice@example.com", "secret123").await;
+    let bob = app.user_token("bob@example.com", "secret123").await;
+    let calendar = app.create_calendar(&alice, "Household").await;
+    let calendar_id = calendar["id"].as_str().unwrap();
+    let transfer = format!("/api/auth/calendars/{}/transfer", calendar_id);
+
+    let response = app.send(json_request(Method::POST, "/api/auth/events", Some(&alice), json!({
+        "calendar_id": calendar_id,
+        "event": {
+            "title": "Bins out",
+            "description": null,
+            "location": null,
+            "start_time": "2030-01-07T19:00:00Z",
+            "end_time": "2030-01-07T19:15:00Z",
+            "is_all_day": false,
+            "rrule": "FREQ=WEEKLY",
+            "geo_lat": null,
+            "geo_lon": null,
+        },
+    }))).await;
+    assert_eq!(response.status, StatusCode::OK);
+    let shares = format!("/api/auth/calendars/{}/shares", calendar_id);
+    let response = app.send(json_request(Method::POST, &shares, Some(&alice), json!({
+        "shared_with_email": "carol@example.com",
+        "permission": "read",
+    }))).await;
+    assert_eq!(response.status, StatusCode::OK);
+
+    let response = app.send(json_request(Method::POST, &transfer, Some(&alice), json!({
+        "new_owner_email": "bob@example.com",
+        "confirm_name": "Wrong name",
+    }))).await;
+    assert_eq!(response.status, StatusCode::BAD_REQUEST);
+    assert_eq!(response.json()["field"], "confirm_name");
+
+    let response = app.send(json_request(Method::POST, &transfer, Some(&alice), json!({
+        "new_owner_email": "bob@example.com",
+        "confirm_name": "Household",
+        "keep_access": true,
+    }))).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+    assert_ne!(response.json()["user_id"], calendar["user_id"]);
+
+    let bobs = app.send(authed_request(Method::GET, "/api/auth/calendars", &bob)).await.json();
+    assert!(bobs.as_array().unwrap().iter().any(|c| c["id"] == calendar_id));
+    let events = format!("/api/auth/calendars/{}/events", calendar_id);
+    let response = app.send(authed_request(Method::GET, &events, &bob)).await;
+    assert_eq!(response.json()[0]["title"], "Bins out");
+
+    // Carol's share stays and Alice keeps full access, but can't transfer again
+    let response = app.send(authed_request(Method::GET, &shares, &alice)).await;
+    assert_eq!(response.status, StatusCode::OK);
+    let emails: Vec<_> = response.json().as_array().unwrap().iter()
+        .map(|s| s["shared_with_email"].as_str().unwrap().to_string())
+        .collect();
+    assert!(emails.contains(&"carol@example.com".to_string()));
+    assert!(emails.contains(&"alice@example.com".to_string()));
+    let response = app.send(json_request(Method::POST, &transfer, Some(&alice), json!({
+        "new_owner_email": "alice@example.com",
+        "confirm_name": "Household",
+    }))).await;
+    assert_eq!(response.status, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn groups_own_calendars_and_grant_access_by_membership() {
+    let app = TestApp::new().await;
+    app.setup(ADMIN_EMAIL, ADMIN_PASSWORD).await;
+    let alice = app.user_token("alice@example.com", "secret123").await;
+    let bob = app.user_token("bob@example.com", "secret123").await;
+    let carol = app.user_token("carol@example.com", "secret123").await;
+
+    let response = app.send(json_request(Method::POST, "/api/auth/groups", Some(&alice), json!({ "name": "Family" }))).await;
+    assert_eq!(response.status, StatusCode::CREATED, "{}", response.body);
+    let group_id = response.json()["id"].as_str().unwrap().to_string();
+    let members = format!("/api/auth/groups/{}/members", group_id);
+
+    let response = app.send(json_request(Method::POST, &members, Some(&alice), json!({ "email": "bob@example.com" }))).await;
+    assert_eq!(response.status, StatusCode::CREATED, "{}", response.body);
+    assert_eq!(response.json()["role"], "member");
+    let bob_id = response.json()["user_id"].as_str().unwrap().to_string();
+
+    // Only admins manage the group, and outsiders can't see it at all
+    let response = app.send(json_request(Method::POST, &members, Some(&bob), json!({ "email": "carol@example.com" }))).await;
+    assert_eq!(response.status, StatusCode::UNAUTHORIZED);
+    let response = app.send(authed_request(Method::GET, &format!("/api/auth/groups/{}", group_id), &carol)).await;
+    assert_eq!(response.status, StatusCode::NOT_FOUND);
+
+    let response = app.send(json_request(Method::POST, &format!("/api/auth/groups/{}/calendars", group_id), Some(&alice), json!({
+        "name": "Family Plans",
+        "description": null,
+        "color": null,
+        "is_public": false,
+    }))).await;
+    assert_eq!(response.status, StatusCode::CREATED, "{}", response.body);
+    assert_eq!(response.json()["group_id"], group_id.as_str());
+    let calendar_id = response.json()["id"].as_str().unwrap().to_string();
+
+    // Bob can add events and sees the calendar in his CalDAV home
+    let response = app.send(json_request(Method::POST, "/api/auth/events", Some(&bob), json!({
+        "calendar_id": calendar_id,
+        "event": {
+            "title": "Grandma's birthday",
+            "description": null,
+            "location": null,
+            "start_time": "2030-03-02T12:00:00Z",
+            "end_time": "2030-03-02T15:00:00Z",
+            "is_all_day": false,
+            "rrule": null,
+            "geo_lat": null,
+            "geo_lon": null,
+        },
+    }))).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+    let response = app.send(dav_request("PROPFIND", "/calendars/", ("bob@example.com", "secret123"), Some("1"), "")).await;
+    assert!(response.body.contains(&calendar_id), "{}", response.body);
+
+    let response = app.send(dav_request("PROPFIND", &format!("/principals/groups/{}/", group_id), ("bob@example.com", "secret123"), Some("0"), "")).await;
+    assert_eq!(response.status, StatusCode::MULTI_STATUS);
+    assert!(response.body.contains("mailto:alice@example.com"), "{}", response.body);
+    let response = app.send(dav_request("PROPFIND", &format!("/principals/groups/{}/", group_id), ("carol@example.com", "secret123"), Some("0"), "")).await;
+    assert_eq!(response.status, StatusCode::NOT_FOUND);
+
+    // The last admin can't leave; a removed member loses access
+    let alice_id = app.send(authed_request(Method::GET, &members, &alice)).await.json().as_array().unwrap().iter()
+        .find(|m| m["email"] == "alice@example.com").unwrap()["user_id"].as_str().unwrap().to_string();
+    let response = app.send(authed_request(Method::DELETE, &format!("{}/{}", members, alice_id), &alice)).await;
+    assert_eq!(response.status, StatusCode::BAD_REQUEST);
+    let response = app.send(authed_request(Method::DELETE, &format!("{}/{}", members, bob_id), &alice)).await;
+    assert_eq!(response.status, StatusCode::NO_CONTENT);
+    let response = app.send(authed_request(Method::GET, &format!("/api/auth/calendars/{}/events", calendar_id), &bob)).await;
+    assert_eq!(response.status, StatusCode::UNAUTHORIZED);
+
+    let response = app.send(authed_request(Method::DELETE, &format!("/api/auth/groups/{}", group_id), &alice)).await;
+    assert_eq!(response.status, StatusCode::NO_CONTENT);
+    let response = app.send(authed_request(Method::GET, &format!("/api/auth/calendars/{}/events", calendar_id), &alice)).await;
+    assert_eq!(response.status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn demoted_admins_hand_over_the_group_calendars_they_created() {
+    let app = TestApp::new().await;
+    app.setup(ADMIN_EMAIL, ADMIN_PASSWORD).await;
+    let alice = app.user_token("alice@example.com", "secret123").await;
+    let bob = app.user_token("bob@example.com", "secret123").await;
+
+    let response = app.send(json_request(Method::POST, "/api/auth/groups", Some(&alice), json!({ "name": "Family" }))).await;
+    let group_id = response.json()["id"].as_str().unwrap().to_string();
+    let members = format!("/api/auth/groups/{}/members", group_id);
+    let response = app.send(json_request(Method::POST, &members, Some(&alice), json!({ "email": "bob@example.com", "role": "admin" }))).await;
+    let bob_id = response.json()["user_id"].as_str().unwrap().to_string();
+    let response = app.send(json_request(Method::POST, &format!("/api/auth/groups/{}/calendars", group_id), Some(&bob), json!({
+        "name": "Family Plans",
+        "description": null,
+        "color": null,
+        "is_public": false,
+    }))).await;
+    assert_eq!(response.status, StatusCode::CREATED, "{}", response.body);
+    let calendar_id = response.json()["id"].as_str().unwrap().to_string();
+
+    let response = app.send(json_request(Method::PUT, &format!("{}/{}", members, bob_id), Some(&alice), json!({ "role": "member" }))).await;
+    assert!(response.status.is_success(), "{}", response.body);
+
+    // Bob keeps editing events as a member, but the calendar is no longer his to delete
+    let response = app.send(authed_request(Method::DELETE, &format!("/api/auth/calendars/{}", calendar_id), &bob)).await;
+    assert_eq!(response.status, StatusCode::UNAUTHORIZED);
+    let response = app.send(authed_request(Method::GET, &format!("/api/auth/calendars/{}/events", calendar_id), &bob)).await;
+    assert_eq!(response.status, StatusCode::OK);
+
+    // Nor can a group calendar leave the group by transfer
+    let response = app.send(json_request(Method::POST, &format!("/api/auth/calendars/{}/transfer", calendar_id), Some(&alice), json!({
+        "new_owner_email": "bob@example.com",
+        "confirm_name": "Family Plans",
+        "keep_access": false,
+    }))).await;
+    assert_eq!(response.status, StatusCode::BAD_REQUEST, "{}", response.body);
+
+    let response = app.send(authed_request(Method::DELETE, &format!("/api/auth/calendars/{}", calendar_id), &alice)).await;
+    assert_eq!(response.status, StatusCode::NO_CONTENT);
+}
+
+#[tokio::test]
+async fn delegates_act_on_all_of_the_owners_calendars() {
+    let app = TestApp::new().await;
+    app.setup(ADMIN_EMAIL, ADMIN_PASSWORD).await;
+    let alice = app.user_token("alice@example.com", "secret123").await;
+    let bob = app.user_token("bob@example.com", "secret123").await;
+    let calendar = app.create_calendar(&alice, "Work").await;
+    let calendar_id = calendar["id"].as_str().unwrap();
+    let events = format!("/api/auth/calendars/{}/events", calendar_id);
+
+    let response = app.send(authed_request(Method::GET, &events, &bob)).await;
+    assert_eq!(response.status, StatusCode::UNAUTHORIZED);
+
+    let response = app.send(json_request(Method::POST, "/api/auth/delegations", Some(&alice), json!({
+        "delegate_email": "alice@example.com",
+        "permission": "write",
+    }))).await;
+    assert_eq!(response.json()["field"], "delegate_email");
+
+    let response = app.send(json_request(Method::POST, "/api/auth/delegations", Some(&alice), json!({
+        "delegate_email": "bob@example.com",
+        "permission": "read",
+    }))).await;
+    assert_eq!(response.status, StatusCode::CREATED, "{}", response.body);
+    let delegation_id = response.json()["id"].as_str().unwrap().to_string();
+
+    // Read access covers calendars created later, but no writes
+    let later = app.create_calendar(&alice, "Private").await;
+    let response = app.send(dav_request("PROPFIND", "/calendars/", ("bob@example.com", "secret123"), Some("1"), "")).await;
+    assert!(response.body.contains(calendar_id), "{}", response.body);
+    assert!(response.body.contains(later["id"].as_str().unwrap()), "{}", response.body);
+    let event = json!({
+        "calendar_id": calendar_id,
+        "event": {
+            "title": "Board meeting",
+            "description": null,
+            "location": null,
+            "start_time": "2030-05-06T09:00:00Z",
+            "end_time": "2030-05-06T10:00:00Z",
+            "is_all_day": false,
+            "rrule": null,
+            "geo_lat": null,
+            "geo_lon": null,
+        },
+    });
+    let response = app.send(json_request(Method::POST, "/api/auth/events", Some(&bob), event.clone())).await;
+    assert_eq!(response.status, StatusCode::UNAUTHORIZED);
+
+    // Granting again upgrades the delegation
+    let response = app.send(json_request(Method::POST, "/api/auth/delegations", Some(&alice), json!({
+        "delegate_email": "bob@example.com",
+        "permission": "write",
+    }))).await;
+    assert_eq!(response.json()["id"], delegation_id.as_str());
+    let response = app.send(json_request(Method::POST, "/api/auth/events", Some(&bob), event)).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+    let received = app.send(authed_request(Method::GET, "/api/auth/delegations", &bob)).await.json();
+    assert_eq!(received["received"][0]["owner_email"], "alice@example.com");
+
+    // Delegates can't manage the calendars themselves, and the delegate can end it
+    let response = app.send(authed_request(Method::DELETE, &format!("/api/auth/calendars/{}", calendar_id), &bob)).await;
+    assert_eq!(response.status, StatusCode::UNAUTHORIZED);
+    let response = app.send(authed_request(Method::DELETE, &format!("/api/auth/delegations/{}", delegation_id), &bob)).await;
+    assert_eq!(response.status, StatusCode::NO_CONTENT);
+    let response = app.send(authed_request(Method::GET, &events, &bob)).await;
+    assert_eq!(response.status, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn user_responses_never_include_the_password_hash() {
+    let app = TestApp::new().await;
+    app.setup(ADMIN_EMAIL, ADMIN_PASSWORD).await;
+    let token = app.user_token("alice@example.com", "secret123").await;
+    let login = app.login("alice@example.com", "secret123").await;
+    assert!(!login.body.contains("password_hash"));
+    assert!(!login.body.contains("$2"));
+    let user_id = login.json()["user"]["id"].as_str().unwrap().to_string();
+    let uri = format!("/api/users/{}", user_id);
+
+    let response = app.send(Request::builder().uri(&uri).body(Body::empty()).unwrap()).await;
+    assert_eq!(response.status, StatusCode::UNAUTHORIZED);
+
+    let response = app.send(authed_request(Method::GET, &uri, &token)).await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.json()["email"], "alice@example.com");
+    assert!(response.json().get("password_hash").is_none());
+
+    let admin = app.login(ADMIN_EMAIL, ADMIN_PASSWORD).await.json()["token"].as_str().unwrap().to_string();
+    let response = app.send(authed_request(Method::GET, "/api/admin/users", &admin)).await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert!(!response.body.contains("password_hash"));
+}
+
+#[tokio::test]
+async fn deleting_a_user_removes_the_shares_they_received() {
+    let app = TestApp::with_admin().await;
+    let alice = app.user_token("alice@example.com", "secret123").await;
+    app.user_token("bob@example.com", "secret123").await;
+    let bob_id = app.login("bob@example.com", "secret123").await.json()["user"]["id"].as_str().unwrap().to_string();
+    let calendar_id = app.create_calendar(&alice, "Family").await["id"].as_str().unwrap().to_string();
+    let shares = format!("/api/auth/calendars/{}/shares", calendar_id);
+    let response = app.send(json_request(Method::POST, &shares, Some(&alice), json!({
+        "shared_with_email": "bob@example.com",
+        "permission": "read",
+    }))).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+
+    let admin = app.login(ADMIN_EMAIL, ADMIN_PASSWORD).await.json()["token"].as_str().unwrap().to_string();
+    let response = app.send(authed_request(Method::DELETE, &format!("/api/admin/users/{}", bob_id), &admin)).await;
+    assert!(response.status.is_success(), "{}", response.body);
+
+    let response = app.send(authed_request(Method::GET, &shares, &alice)).await;
+    assert_eq!(response.json(), json!([]));
+}
+
+#[tokio::test]
+async fn deleted_users_hand_over_group_calendars_and_purged_users_keep_their_account() {
+    let app = TestApp::with_admin().await;
+    let alice = app.user_token("alice@example.com", "secret123").await;
+    let bob = app.user_token("bob@example.com", "secret123").await;
+    let admin = app.login(ADMIN_EMAIL, ADMIN_PASSWORD).await.json()["token"].as_str().unwrap().to_string();
+    let alice_id = app.login("alice@example.com", "secret123").await.json()["user"]["id"].as_str().unwrap().to_string();
+    let bob_id = app.login("bob@example.com", "secret123").await.json()["user"]["id"].as_str().unwrap().to_string();
+
+    let response = app.send(json_request(Method::POST, "/api/auth/groups", Some(&alice), json!({ "name": "Family" }))).await;
+    let group_id = response.json()["id"].as_str().unwrap().to_string();
+    let members = format!("/api/auth/groups/{}/members", group_id);
+    app.send(json_request(Method::POST, &members, Some(&alice), json!({ "email": "bob@example.com" }))).await;
+    let response = app.send(json_request(Method::POST, &format!("/api/auth/groups/{}/calendars", group_id), Some(&alice), json!({
+        "name": "Family Plans",
+        "description": null,
+        "color": null,
+        "is_public": false,
+    }))).await;
+    let group_calendar = format!("/api/auth/calendars/{}/events", response.json()["id"].as_str().unwrap());
+
+    let response = app.send(authed_request(Method::DELETE, &format!("/api/admin/users/{}", alice_id), &admin)).await;
+    assert!(response.status.is_success(), "{}", response.body);
+    let response = app.send(authed_request(Method::GET, &members, &bob)).await;
+    assert_eq!(response.json().as_array().unwrap().len(), 1);
+    assert_eq!(response.json()[0]["role"], "admin");
+    assert_eq!(app.send(authed_request(Method::GET, &group_calendar, &bob)).await.status, StatusCode::OK);
+
+    app.create_calendar(&bob, "Private").await;
+    let response = app.send(authed_request(Method::POST, &format!("/api/admin/users/{}/purge", bob_id), &bob)).await;
+    assert_eq!(response.status, StatusCode::UNAUTHORIZED);
+    let response = app.send(authed_request(Method::POST, &format!("/api/admin/users/{}/purge", bob_id), &admin)).await;
+    assert!(response.status.is_success(), "{}", response.body);
+
+    let response = app.send(authed_request(Method::GET, "/api/auth/calendars", &bob)).await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert!(response.json().as_array().unwrap().iter().all(|calendar| calendar["group_id"] == group_id.as_str()), "{}", response.body);
+    assert_eq!(app.send(authed_request(Method::GET, &group_calendar, &bob)).await.status, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn calendar_and_event_lookups_by_id_respect_access() {
+    let app = TestApp::new().await;
+    app.setup(ADMIN_EMAIL, ADMIN_PASSWORD).await;
+    let alice = app.user_token("alice@example.com", "secret123").await;
+    let bob = app.user_token("bob@example.com", "secret123").await;
+    let calendar = app.create_calendar(&alice, "Private").await;
+    let calendar_id = calendar["id"].as_str().unwrap();
+    let response = app.send(json_request(Method::POST, "/api/auth/events", Some(&alice), json!({
+        "calendar_id": calendar_id,
+        "event": {
+            "title": "Doctor",
+            "description": null,
+            "location": null,
+            "start_time": "2030-02-01T08:00:00Z",
+            "end_time": "2030-02-01T09:00:00Z",
+            "is_all_day": false,
+            "rrule": null,
+            "geo_lat": null,
+            "geo_lon": null,
+        },
+    }))).await;
+    let event_id = response.json()["id"].as_str().unwrap().to_string();
+    let calendar_uri = format!("/api/calendars/{}", calendar_id);
+    let event_uri = format!("/api/events/{}", event_id);
+    let anonymous = |uri: &str| Request::builder().uri(uri).body(Body::empty()).unwrap();
+
+    for uri in [&calendar_uri, &event_uri] {
+        assert_eq!(app.send(anonymous(uri)).await.status, StatusCode::NOT_FOUND);
+        assert_eq!(app.send(authed_request(Method::GET, uri, &bob)).await.status, StatusCode::NOT_FOUND);
+        assert_eq!(app.send(authed_request(Method::GET, uri, &alice)).await.status, StatusCode::OK);
+    }
+
+    // Share recipients can read it, and so can everyone once it is public
+    let response = app.send(json_request(Method::POST, &format!("/api/auth/calendars/{}/shares", calendar_id), Some(&alice), json!({
+        "shared_with_email": "bob@example.com",
+        "permission": "read",
+    }))).await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(app.send(authed_request(Method::GET, &event_uri, &bob)).await.status, StatusCode::OK);
+
+    let response = app.send(json_request(Method::PUT, &calendar_uri.replace("/api/", "/api/auth/"), Some(&alice), json!({ "is_public": true }))).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+    assert_eq!(app.send(anonymous(&calendar_uri)).await.status, StatusCode::OK);
+    assert_eq!(app.send(anonymous(&event_uri)).await.status, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn cors_is_limited_to_configured_origins_and_skips_the_web_ui() {
+    let config = Config {
+        cors_origins: vec!["https://app.example.com".to_string()],
+        ..Config::default()
+    };
+    let app = TestApp::with_config(config).await;
+    app.setup(ADMIN_EMAIL, ADMIN_PASSWORD).await;
+    let token = app.user_token("alice@example.com", "secret123").await;
+    let preflight = |uri: &str, origin: &str| Request::builder()
+        .method(Method::OPTIONS)
+        .uri(uri)
+        .header("Origin", origin)
+        .header("Access-Control-Request-Method", "POST")
+        .header("Access-Control-Request-Headers", "authorization, content-type")
+        .body(Body::empty())
+        .unwrap();
+
+    // Preflights are answered without credentials
+    let response = app.send(preflight("/api/auth/calendars", "https://app.example.com")).await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.header("access-control-allow-origin"), Some("https://app.example.com"));
+    assert!(response.header("access-control-allow-methods").unwrap().contains("POST"));
+    assert_eq!(response.header("access-control-allow-credentials"), None);
+
+    let response = app.send(preflight("/api/auth/calendars", "https://evil.example.com")).await;
+    assert_eq!(response.header("access-control-allow-origin"), None);
+    let response = app.send(preflight("/web/calendars", "https://app.example.com")).await;
+    assert_eq!(response.header("access-control-allow-origin"), None);
+
+    let mut request = authed_request(Method::GET, "/api/auth/calendars", &token);
+    request.headers_mut().insert("Origin", "https://app.example.com".parse().unwrap());
+    let response = app.send(request).await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.header("access-control-allow-origin"), Some("https://app.example.com"));
+
+    // A CalDAV OPTIONS probe is not a preflight
+    let mut request = dav_request("OPTIONS", "/calendars/", ("alice@example.com", "secret123"), None, "");
+    request.headers_mut().insert("Origin", "https://app.example.com".parse().unwrap());
+    let response = app.send(request).await;
+    assert!(response.header("dav").is_some());
+}
+
+#[tokio::test]
+async fn repeated_failed_logins_lock_the_account_and_notify_the_owner() {
+    let clock = MockClock::new(Utc.with_ymd_and_hms(2030, 5, 2, 9, 0, 0).unwrap());
+    let mailer = MemoryMailer::new();
+    let app = TestApp::with_mailer(clock.clone(), mailer.clone()).await;
+    app.setup(ADMIN_EMAIL, ADMIN_PASSWORD).await;
+    let token = app.user_token("alice@example.com", "secret123").await;
+
+    // A success in between resets the count
+    for _ in 0..4 {
+        assert_eq!(app.login("alice@example.com", "wrong").await.status, StatusCode::UNAUTHORIZED);
+    }
+    assert_eq!(app.login("alice@example.com", "secret123").await.status, StatusCode::OK);
+    for _ in 0..4 {
+        assert_eq!(app.login("alice@example.com", "wrong").await.status, StatusCode::UNAUTHORIZED);
+    }
+    assert!(mailer.sent().is_empty());
+
+    // The fifth consecutive failure locks, here over CalDAV
+    let response = app.send(dav_request("PROPFIND", "/calendars/", ("alice@example.com", "wrong"), Some("1"), "")).await;
+    assert_eq!(response.status, StatusCode::UNAUTHORIZED);
+    let response = app.login("alice@example.com", "secret123").await;
+    assert_eq!(response.status, StatusCode::UNAUTHORIZED);
+    assert!(response.json()["message"].as_str().unwrap().contains("locked"));
+
+    let sent = mailer.sent();
+    assert_eq!(sent.len(), 1);
+    assert_eq!(sent[0].to, "alice@example.com");
+    assert!(sent[0].body.contains("5 failed sign-in attempts"));
+
+    let response = app.send(Request::builder()
+        .uri("/web/settings")
+        .header("cookie", format!("auth_token={}", token))
+        .body(Body::empty())
+        .unwrap()).await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert!(response.body.contains("2030-05-02 09:00 UTC"));
+    assert!(response.body.contains("Locked after too many failed logins until 2030-05-02 09:15 UTC"));
+
+    clock.advance(Duration::minutes(15));
+    assert_eq!(app.login("alice@example.com", "secret123").await.status, StatusCode::OK);
+    assert_eq!(mailer.sent().len(), 1);
+}
+
+#[tokio::test]
+async fn events_get_the_calendar_default_reminder_and_users_choose_notifications() {
+    let app = TestApp::with_admin().await;
+    let token = app.user_token("alice@example.com", "secret123").await;
+    let calendar = app.create_calendar(&token, "Work").await;
+    let calendar_id = calendar["id"].as_str().unwrap();
+    assert!(calendar["default_alarm_minutes"].is_null());
+
+    let response = app.send(json_request(Method::PUT, &format!("/api/auth/calendars/{}", calendar_id), Some(&token), json!({
+        "default_alarm_minutes": -5,
+    }))).await;
+    assert_eq!(response.status, StatusCode::BAD_REQUEST);
+    let response = app.send(json_request(Method::PUT, &format!("/api/auth/calendars/{}", calendar_id), Some(&token), json!({
+        "default_alarm_minutes": 10,
+    }))).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+    assert_eq!(response.json()["default_alarm_minutes"], 10);
+
+    // Events without a reminder get the default; explicit ones keep theirs
+    let start = Utc.with_ymd_and_hms(2030, 3, 4, 9, 0, 0).unwrap();
+    for (title, alarm_minutes, expected) in [("Standup", None, 10), ("Review", Some(30), 30)] {
+        let response = app.send(json_request(Method::POST, "/api/auth/events", Some(&token), json!({
+            "calendar_id": calendar_id,
+            "event": {
+                "title": title,
+                "description": null,
+                "location": null,
+                "start_time": start,
+                "end_time": start + Duration::hours(1),
+                "is_all_day": false,
+                "rrule": null,
+                "geo_lat": null,
+                "geo_lon": null,
+                "alarm_minutes": alarm_minutes,
+            },
+        }))).await;
+        assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+        assert_eq!(response.json()["alarm_minutes"], expected);
+    }
+
+    let response = app.send(authed_request(Method::GET, &format!("/api/calendars/{}/export", calendar_id), &token)).await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert!(response.body.contains("BEGIN:VALARM"));
+    assert!(response.body.contains("TRIGGER:-PT10M"));
+    assert!(response.body.contains("TRIGGER:-PT30M"));
+
+    // Reminders stored by CalDAV clients are kept
+    let ics = "BEGIN:VCALENDAR\r\nVERSION:2.0\r\nPRODID:-//Tests//EN\r\nBEGIN:VEVENT\r\nUID:retro\r\n\
+               DTSTART:20300305T090000Z\r\nDTEND:20300305T100000Z\r\nSUMMARY:Retro\r\n\
+               BEGIN:VALARM\r\nACTION:DISPLAY\r\nTRIGGER:-PT15M\r\nEND:VALARM\r\nEND:VEVENT\r\nEND:VCALENDAR\r\n";
+    let response = app.send(dav_request("PUT", &format!("/calendars/{}/retro.ics", calendar_id), ("alice@example.com", "secret123"), None, ics)).await;
+    assert_eq!(response.status, StatusCode::CREATED, "{}", response.body);
+    let response = app.send(authed_request(Method::GET, &format!("/api/auth/calendars/{}/events", calendar_id), &token)).await;
+    let events = response.json();
+    let retro = events.as_array().unwrap().iter().find(|e| e["title"] == "Retro").unwrap();
+    assert_eq!(retro["alarm_minutes"], 15);
+
+    // Notifications default to none and are chosen per calendar
+    let response = app.send(authed_request(Method::GET, "/api/auth/notification-preferences", &token)).await;
+    assert_eq!(response.status, StatusCode::OK);
+    let preference = response.json().as_array().unwrap().iter().find(|p| p["calendar_id"] == calendar_id).cloned().unwrap();
+    assert_eq!(preference["channel"], "none");
+
+    let uri = format!("/api/auth/notification-preferences/{}", calendar_id);
+    let response = app.send(json_request(Method::PUT, &uri, Some(&token), json!({ "channel": "email" }))).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+    assert_eq!(response.json()["channel"], "email");
+    let response = app.send(authed_request(Method::GET, "/api/auth/notification-preferences", &token)).await;
+    assert!(response.json().as_array().unwrap().iter().any(|p| p["calendar_id"] == calendar_id && p["channel"] == "email"));
+
+    // Calendars the user can't see are refused
+    let bob = app.user_token("bob@example.com", "secret123").await;
+    let response = app.send(json_request(Method::PUT, &uri, Some(&bob), json!({ "channel": "email" }))).await;
+    assert_eq!(response.status, StatusCode::NOT_FOUND);
+
+    let response = app.send(Request::builder()
+        .uri("/web/settings")
+        .header("cookie", format!("auth_token={}", token))
+        .body(Body::empty())
+        .unwrap()).await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert!(response.body.contains(&format!("/web/settings/notifications/{}", calendar_id)));
+}
+
+#[tokio::test]
+async fn holiday_packs_add_read_only_holiday_calendars() {
+    let app = TestApp::with_clock(MockClock::new(Utc.with_ymd_and_hms(2030, 1, 15, 9, 0, 0).unwrap())).await;
+    app.setup(ADMIN_EMAIL, ADMIN_PASSWORD).await;
+    let token = app.user_token("alice@example.com", "secret123").await;
+
+    let response = app.send(authed_request(Method::GET, "/api/auth/holiday-packs", &token)).await;
+    assert_eq!(response.status, StatusCode::OK);
+    let packs = response.json();
+    assert!(packs.as_array().unwrap().iter().all(|p| p["calendar_id"].is_null()));
+    assert!(packs.as_array().unwrap().iter().any(|p| p["id"] == "de-by"));
+
+    let response = app.send(authed_request(Method::PUT, "/api/auth/holiday-packs/atlantis", &token)).await;
+    assert_eq!(response.status, StatusCode::NOT_FOUND);
+
+    // Regions include their country's holidays
+    let response = app.send(authed_request(Method::PUT, "/api/auth/holiday-packs/de-by", &token)).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+    let calendar = response.json();
+    assert_eq!(calendar["holiday_pack"], "de-by");
+    let calendar_id = calendar["id"].as_str().unwrap().to_string();
+
+    let response = app.send(authed_request(Method::GET, &format!("/api/auth/calendars/{}/events", calendar_id), &token)).await;
+    let events = response.json();
+    let on = |title: &str, date: &str| events.as_array().unwrap().iter()
+        .any(|e| e["title"] == title && e["start_time"].as_str().unwrap().starts_with(date) && e["is_all_day"] == true);
+    assert!(on("Karfreitag", "2030-04-19"));
+    assert!(on("Fronleichnam", "2030-06-20"));
+    assert!(on("Tag der Deutschen Einheit", "2031-10-03"));
+
+    let response = app.send(authed_request(Method::PUT, "/api/auth/holiday-packs/us", &token)).await;
+    let us_calendar_id = response.json()["id"].as_str().unwrap().to_string();
+    let response = app.send(authed_request(Method::GET, &format!("/api/auth/calendars/{}/events", us_calendar_id), &token)).await;
+    let events = response.json();
+    let on = |title: &str, date: &str| events.as_array().unwrap().iter()
+        .any(|e| e["title"] == title && e["start_time"].as_str().unwrap().starts_with(date));
+    assert!(on("Memorial Day", "2030-05-27"));
+    assert!(on("Thanksgiving Day", "2030-11-28"));
+
+    // The calendar is read-only and shows up over CalDAV
+    let response = app.send(dav_request("PROPFIND", "/calendars/", ("alice@example.com", "secret123"), Some("1"), "")).await;
+    assert!(response.body.contains("Holidays (Germany (Bavaria))"));
+    let response = app.send(json_request(Method::PUT, &format!("/api/auth/calendars/{}", calendar_id), Some(&token), json!({ "name": "Mine" }))).await;
+    assert_eq!(response.status, StatusCode::UNAUTHORIZED);
+
+    let response = app.send(authed_request(Method::DELETE, "/api/auth/holiday-packs/de-by", &token)).await;
+    assert_eq!(response.status, StatusCode::NO_CONTENT);
+    let response = app.send(authed_request(Method::GET, "/api/auth/holiday-packs", &token)).await;
+    let packs = response.json();
+    let enabled: Vec<_> = packs.as_array().unwrap().iter().filter(|p| !p["calendar_id"].is_null()).map(|p| p["id"].as_str().unwrap()).collect();
+    assert_eq!(enabled, ["us"]);
+}
+
+#[tokio::test]
+async fn users_order_hide_and_recolor_the_calendars_they_see() {
+    let app = TestApp::with_admin().await;
+    let alice = app.user_token("alice@example.com", "secret123").await;
+    let bob = app.user_token("bob@example.com", "secret123").await;
+    let work = app.create_calendar(&alice, "Work").await["id"].as_str().unwrap().to_string();
+    let home = app.create_calendar(&alice, "Home").await["id"].as_str().unwrap().to_string();
+    let response = app.send(json_request(Method::POST, &format!("/api/auth/calendars/{}/shares", work), Some(&alice), json!({
+        "shared_with_email": "bob@example.com",
+        "permission": "read",
+    }))).await;
+    assert_eq!(response.status, StatusCode::OK);
+
+    let order = |response: &common::TestResponse| response.json().as_array().unwrap().iter()
+        .map(|p| p["calendar_id"].as_str().unwrap().to_string())
+        .collect::<Vec<_>>();
+
+    let response = app.send(json_request(Method::PUT, "/api/auth/calendar-preferences", Some(&alice), json!({
+        "calendar_ids": [home],
+    }))).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+    // Calendars left out follow the listed ones
+    let ids = order(&response);
+    assert_eq!(ids[0], home);
+    assert!(ids.contains(&work));
+
+    // Read-only recipients recolor shared calendars for themselves only
+    let uri = format!("/api/auth/calendar-preferences/{}", work);
+    let response = app.send(json_request(Method::PUT, &uri, Some(&bob), json!({ "color": "red" }))).await;
+    assert_eq!(response.status, StatusCode::BAD_REQUEST);
+    let response = app.send(json_request(Method::PUT, &uri, Some(&bob), json!({ "color": "#112233", "hidden": true }))).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+    assert_eq!(response.json()["color"], "#112233");
+    assert_eq!(response.json()["hidden"], true);
+    let response = app.send(authed_request(Method::GET, &format!("/api/calendars/{}", work), &alice)).await;
+    assert_ne!(response.json()["color"], "#112233");
+
+    let uri = format!("/api/auth/calendar-preferences/{}", home);
+    let response = app.send(json_request(Method::PUT, &uri, Some(&bob), json!({ "hidden": true }))).await;
+    assert_eq!(response.status, StatusCode::NOT_FOUND);
+
+    // calendar-order over CalDAV is per user, even on read-only shares
+    let proppatch = r#"<?xml version="1.0" encoding="utf-8"?>
+<D:propertyupdate xmlns:D="DAV:" xmlns:A="http://apple.com/ns/ical/">
+  <D:set><D:prop><A:calendar-order>7</A:calendar-order></D:prop></D:set>
+</D:propertyupdate>"#;
+    let response = app.send(dav_request("PROPPATCH", &format!("/calendars/{}/", work), ("bob@example.com", "secret123"), None, proppatch)).await;
+    assert_eq!(response.status, StatusCode::MULTI_STATUS);
+    assert!(response.body.contains("200 OK"), "{}", response.body);
+    let response = app.send(dav_request("PROPFIND", &format!("/calendars/{}/", work), ("bob@example.com", "secret123"), Some("0"), "")).await;
+    assert!(response.body.contains(">7</"), "{}", response.body);
+    assert!(response.body.contains("#112233"));
+    let response = app.send(dav_request("PROPFIND", &format!("/calendars/{}/", work), ("alice@example.com", "secret123"), Some("0"), "")).await;
+    assert!(!response.body.contains(">7</"));
+
+    let rename = proppatch.replace("<A:calendar-order>7</A:calendar-order>", "<D:displayname>Mine</D:displayname>");
+    let response = app.send(dav_request("PROPPATCH", &format!("/calendars/{}/", work), ("bob@example.com", "secret123"), None, &rename)).await;
+    assert!(response.body.contains("403 Forbidden"), "{}", response.body);
+
+    // The calendars page saves drag-and-drop order and hides calendars from the dashboard
+    let form = |uri: &str, body: String| Request::post(uri)
+        .header("authorization", format!("Bearer {}", alice))
+        .header("content-type", "application/x-www-form-urlencoded")
+        .body(Body::from(body))
+        .unwrap();
+    let response = app.send(form("/web/calendars/order", format!("calendar_ids={},{}", work, home))).await;
+    assert_eq!(response.status, StatusCode::NO_CONTENT);
+    let response = app.send(form(&format!("/web/calendars/{}/visibility", home), "hidden=true".to_string())).await;
+    assert_eq!(response.status, StatusCode::SEE_OTHER);
+
+    let page = |uri: &str| Request::builder()
+        .uri(uri)
+        .header("cookie", format!("auth_token={}", alice))
+        .body(Body::empty())
+        .unwrap();
+    let response = app.send(page("/web/calendars")).await;
+    assert!(response.body.find(&work).unwrap() < response.body.find(&home).unwrap());
+    assert!(response.body.contains("Hidden"));
+    let response = app.send(page("/web/dashboard")).await;
+    assert!(response.body.contains(&work));
+    assert!(!response.body.contains(&home));
+}
+
+#[tokio::test]
+async fn new_events_go_to_the_default_calendar() {
+    let app = TestApp::with_admin().await;
+    let token = app.user_token("alice@example.com", "secret123").await;
+    let calendars = app.send(authed_request(Method::GET, "/api/auth/calendars", &token)).await.json();
+    let calendar_id = |name: &str| calendars.as_array().unwrap().iter()
+        .find(|c| c["name"] == name)
+        .map(|c| c["id"].as_str().unwrap().to_string())
+        .unwrap();
+    let (personal, work) = (calendar_id("Personal"), calendar_id("Work"));
+
+    // The first calendar created is the default
+    let defaults: Vec<_> = calendars.as_array().unwrap().iter().filter(|c| c["is_default"] == true).collect();
+    assert_eq!(defaults.len(), 1);
+    assert_eq!(defaults[0]["id"], personal.as_str());
+
+    let start = Utc.with_ymd_and_hms(2030, 3, 4, 9, 0, 0).unwrap();
+    let quick_add = json!({
+        "event": {
+            "title": "Lunch",
+            "description": null,
+            "location": null,
+            "start_time": start,
+            "end_time": start + Duration::hours(1),
+            "is_all_day": false,
+            "rrule": null,
+            "geo_lat": null,
+            "geo_lon": null,
+        },
+    });
+    let response = app.send(json_request(Method::POST, "/api/auth/events", Some(&token), quick_add.clone())).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+    assert_eq!(response.json()["calendar_id"], personal.as_str());
+
+    let response = app.send(json_request(Method::PUT, &format!("/api/auth/calendars/{}", work), Some(&token), json!({ "is_default": true }))).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+    assert_eq!(response.json()["is_default"], true);
+    let response = app.send(authed_request(Method::GET, &format!("/api/calendars/{}", personal), &token)).await;
+    assert_eq!(response.json()["is_default"], false);
+    let response = app.send(json_request(Method::PUT, &format!("/api/auth/calendars/{}", work), Some(&token), json!({ "is_default": false }))).await;
+    assert_eq!(response.status, StatusCode::BAD_REQUEST);
+
+    let response = app.send(json_request(Method::POST, "/api/auth/events", Some(&token), quick_add.clone())).await;
+    assert_eq!(response.json()["calendar_id"], work.as_str());
+
+    // CalDAV clients find it on the principal
+    let expand = r#"<?xml version="1.0" encoding="utf-8"?>
+<d:expand-property xmlns:d="DAV:">
+    <d:property name="schedule-default-calendar-URL" namespace="urn:ietf:params:xml:ns:caldav"/>
+</d:expand-property>"#;
+    let response = app.send(dav_request("REPORT", "/calendars/", ("alice@example.com", "secret123"), Some("0"), expand)).await;
+    assert!(response.body.contains(&format!("<d:href>/calendars/{}/</d:href>", work)), "{}", response.body);
+
+    // Deleting the default hands over to the first remaining calendar
+    let response = app.send(authed_request(Method::DELETE, &format!("/api/auth/calendars/{}", work), &token)).await;
+    assert!(response.status.is_success());
+    let response = app.send(authed_request(Method::GET, &format!("/api/calendars/{}", personal), &token)).await;
+    assert_eq!(response.json()["is_default"], true);
+
+    // The web UI sets it from the calendars list and preselects it for new events
+    let other = app.create_calendar(&token, "Side Project").await["id"].as_str().unwrap().to_string();
+    let response = app.send(Request::post(format!("/web/calendars/{}/default", other))
+        .header("authorization", format!("Bearer {}", token))
+        .body(Body::empty())
+        .unwrap()).await;
+    assert_eq!(response.status, StatusCode::SEE_OTHER);
+    let response = app.send(Request::builder()
+        .uri("/web/events/new")
+        .header("cookie", format!("auth_token={}", token))
+        .body(Body::empty())
+        .unwrap()).await;
+    assert!(response.body.contains(&format!("value=\"{}\" selected", other)), "{}", response.body);
+}
+
+#[tokio::test]
+async fn the_theme_is_stored_per_user_and_remembered_in_a_cookie() {
+    let app = TestApp::with_admin().await;
+    let token = app.user_token("alice@example.com", "secret123").await;
+    let page = |uri: &str, cookie: &str| Request::builder()
+        .uri(uri)
+        .header("cookie", cookie)
+        .body(Body::empty())
+        .unwrap();
+    let dark = r#"href="/static/css/themes/dark.css""#;
+
+    let response = app.send(authed_request(Method::GET, "/api/auth/settings", &token)).await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.json()["theme"], "system");
+    let response = app.send(page("/web/dashboard", &format!("auth_token={}", token))).await;
+    assert!(response.body.contains("media=\"(prefers-color-scheme: dark)\""));
+
+    let response = app.send(json_request(Method::PUT, "/api/auth/settings", Some(&token), json!({ "theme": "sepia" }))).await;
+    assert!(response.status.is_client_error());
+    let response = app.send(json_request(Method::PUT, "/api/auth/settings", Some(&token), json!({ "theme": "dark" }))).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+    assert_eq!(response.json()["theme"], "dark");
+
+    // The stored theme wins over the cookie
+    let response = app.send(page("/web/settings", &format!("auth_token={}; theme=light", token))).await;
+    assert!(response.body.contains(dark));
+    assert!(!response.body.contains("prefers-color-scheme"));
+    assert!(response.body.contains("Light mode"));
+
+    // Signed-out pages follow the cookie set by the toggle
+    let response = app.send(Request::post("/web/theme")
+        .header("content-type", "application/x-www-form-urlencoded")
+        .header("referer", "http://localhost/web/login")
+        .body(Body::from("theme=dark"))
+        .unwrap()).await;
+    assert_eq!(response.status, StatusCode::SEE_OTHER);
+    assert_eq!(response.header("location"), Some("/web/login"));
+    assert!(response.header("set-cookie").unwrap().starts_with("theme=dark;"));
+    let response = app.send(page("/web/login", "theme=dark")).await;
+    assert!(response.body.contains(dark));
+    assert!(!response.body.contains("prefers-color-scheme"));
+    let response = app.send(page("/web/login", "")).await;
+    assert!(response.body.contains("prefers-color-scheme"));
+}
+
+#[tokio::test]
+async fn pages_and_mails_follow_the_chosen_language_or_the_browser() {
+    assert_eq!(missing_keys(Language::De), Vec::<&str>::new());
+
+    let clock = MockClock::new(Utc.with_ymd_and_hms(2030, 5, 2, 9, 0, 0).unwrap());
+    let mailer = MemoryMailer::new();
+    let app = TestApp::with_mailer(clock.clone(), mailer.clone()).await;
+    app.setup(ADMIN_EMAIL, ADMIN_PASSWORD).await;
+    let token = app.user_token("alice@example.com", "secret123").await;
+    let page = |uri: &str, cookie: &str, accept_language: &str| Request::builder()
+        .uri(uri)
+        .header("cookie", cookie)
+        .header("accept-language", accept_language)
+        .body(Body::empty())
+        .unwrap();
+
+    // Signed out, the browser's preference decides
+    let response = app.send(page("/web/login", "", "fr-CH, de;q=0.9, en;q=0.8")).await;
+    assert!(response.body.contains(r#"<html lang="de">"#));
+    assert!(response.body.contains("Geben Sie Ihr Passwort ein"));
+    let response = app.send(page("/web/login", "", "fr-CH, de;q=0.5, en;q=0.8")).await;
+    assert!(response.body.contains(r#"<html lang="en">"#));
+    assert!(response.body.contains("Enter your password"));
+
+    let auth = format!("auth_token={}", token);
+    let response = app.send(page("/web/dashboard", &auth, "de-DE")).await;
+    assert!(response.body.contains("Willkommen"));
+
+    // A chosen language wins over the browser, and back to the browser with null
+    let response = app.send(json_request(Method::PUT, "/api/auth/settings", Some(&token), json!({ "language": "en" }))).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+    assert_eq!(response.json()["language"], "en");
+    let response = app.send(page("/web/dashboard", &auth, "de-DE")).await;
+    assert!(response.body.contains("Welcome"));
+    let response = app.send(json_request(Method::PUT, "/api/auth/settings", Some(&token), json!({ "language": null }))).await;
+    assert!(response.json()["language"].is_null());
+
+    let response = app.send(Request::post("/web/settings/language")
+        .header("cookie", &auth)
+        .header("content-type", "application/x-www-form-urlencoded")
+        .body(Body::from("language=de"))
+        .unwrap()).await;
+    assert_eq!(response.status, StatusCode::SEE_OTHER);
+    let response = app.send(page("/web/settings", &auth, "en-US")).await;
+    assert!(response.body.contains("Einstellungen"));
+    assert!(response.body.contains(r#"<option value="de" selected"#));
+
+    // Mails go out in the chosen language
+    for _ in 0..5 {
+        app.login("alice@example.com", "wrong").await;
+    }
+    let sent = mailer.sent();
+    assert_eq!(sent.len(), 1);
+    assert_eq!(sent[0].subject, "Ihr Konto wurde gesperrt");
+    assert!(sent[0].body.contains("nach 5 fehlgeschlagenen Anmeldeversuchen"));
+}
+
+#[tokio::test]
+async fn times_are_shown_and_entered_in_the_users_time_zone() {
+    let app = TestApp::with_admin().await;
+    let response = app.send(json_request(Method::POST, "/api/auth/register", None, json!({
+        "name": "Alice",
+        "email": "alice@example.com",
+        "password": "secret123",
+        "timezone": "Europe/Berlin",
+    }))).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+    let token = app.login("alice@example.com", "secret123").await.json()["token"].as_str().unwrap().to_string();
+    let response = app.send(authed_request(Method::GET, "/api/auth/settings", &token)).await;
+    assert_eq!(response.json()["timezone"], "Europe/Berlin");
+    assert_eq!(response.json()["time_format"], "24h");
+
+    // A browser reporting something unknown leaves the account on UTC
+    let response = app.send(json_request(Method::POST, "/api/auth/register", None, json!({
+        "name": "Bob",
+        "email": "bob@example.com",
+        "password": "secret123",
+        "timezone": "Mars/Olympus_Mons",
+    }))).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+    let bob = app.login("bob@example.com", "secret123").await.json()["token"].as_str().unwrap().to_string();
+    let response = app.send(authed_request(Method::GET, "/api/auth/settings", &bob)).await;
+    assert_eq!(response.json()["timezone"], "UTC");
+
+    // Form times are on the user's clock: 14:00 in Berlin summer time is 12:00 UTC
+    let calendar_id = app.create_calendar(&token, "Trips").await["id"].as_str().unwrap().to_string();
+    let response = app.send(Request::post("/web/events/new")
+        .header("cookie", format!("auth_token={}", token))
+        .header("content-type", "application/x-www-form-urlencoded")
+        .body(Body::from(format!("title=Flight&calendar_id={}&start_time=2030-07-01T14:00&end_time=2030-07-01T15:30", calendar_id)))
+        .unwrap()).await;
+    assert!(response.status.is_redirection(), "{}", response.body);
+    let response = app.send(authed_request(Method::GET, &format!("/api/auth/calendars/{}/events", calendar_id), &token)).await;
+    assert_eq!(response.json()[0]["start_time"], "2030-07-01T12:00:00Z");
+
+    let calendar_page = format!("/web/calendars/{}", calendar_id);
+    let response = app.send(authed_request(Method::GET, &calendar_page, &token)).await;
+    assert!(response.body.contains("2030-07-01 14:00 - 15:30"), "{}", response.body);
+
+    let response = app.send(Request::post("/web/settings/time")
+        .header("cookie", format!("auth_token={}", token))
+        .header("content-type", "application/x-www-form-urlencoded")
+        .body(Body::from("timezone=America%2FNew_York&time_format=12h"))
+        .unwrap()).await;
+    assert!(response.status.is_redirection());
+    let response = app.send(authed_request(Method::GET, &calendar_page, &token)).await;
+    assert!(response.body.contains("2030-07-01 8:00 AM - 9:30 AM"), "{}", response.body);
+
+    let response = app.send(json_request(Method::PUT, "/api/auth/settings", Some(&token), json!({ "timezone": "Nowhere/Special" }))).await;
+    assert!(response.status.is_client_error());
+}
+
+#[tokio::test]
+async fn weeks_start_and_working_hours_follow_the_users_preferences() {
+    let app = TestApp::with_admin().await;
+    let token = app.user_token("alice@example.com", "secret123").await;
+    let calendar_id = app.create_calendar(&token, "Team").await["id"].as_str().unwrap().to_string();
+    let start = Utc.with_ymd_and_hms(2030, 7, 2, 10, 0, 0).unwrap();
+    let response = app.send(json_request(Method::POST, "/api/auth/events", Some(&token), json!({
+        "calendar_id": calendar_id,
+        "event": {
+            "title": "Standup",
+            "description": null,
+            "location": null,
+            "start_time": start,
+            "end_time": start + Duration::minutes(30),
+            "is_all_day": false,
+            "rrule": "FREQ=WEEKLY;COUNT=4",
+            "geo_lat": null,
+            "geo_lon": null,
+        },
+    }))).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+
+    let response = app.send(authed_request(Method::GET, "/api/auth/settings", &token)).await;
+    assert_eq!(response.json()["week_start"], "Mon");
+    assert_eq!(response.json()["working_hours"]["days"], json!(["Mon", "Tue", "Wed", "Thu", "Fri"]));
+
+    let hours = json!({ "start": "12:00:00", "end": "08:00:00", "days": ["Tue"], "free_busy": true });
+    let response = app.send(json_request(Method::PUT, "/api/auth/settings", Some(&token), json!({ "working_hours": hours }))).await;
+    assert!(response.status.is_client_error());
+    let hours = json!({ "start": "08:00:00", "end": "12:00:00", "days": ["Tue", "Wed"], "free_busy": true });
+    let response = app.send(json_request(Method::PUT, "/api/auth/settings", Some(&token), json!({ "week_start": "Sun", "working_hours": hours }))).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+    assert_eq!(response.json()["week_start"], "Sun");
+
+    // The month grid starts on Sunday and shows every instance of the series
+    let response = app.send(authed_request(Method::GET, "/web/schedule?view=month&date=2030-07-15", &token)).await;
+    assert_eq!(response.status, StatusCode::OK);
+    let sunday = response.body.find(">Sun<").unwrap();
+    assert!(sunday < response.body.find(">Mon<").unwrap());
+    assert_eq!(response.body.matches("10:00 Standup").count(), 4);
+
+    // The week grid highlights the working hours of Tuesday and Wednesday
+    let response = app.send(authed_request(Method::GET, "/web/schedule?view=week&date=2030-07-03", &token)).await;
+    assert!(response.body.contains("2030-06-30 – 2030-07-06"), "{}", response.body);
+    assert_eq!(response.body.matches("schedule-slot schedule-working").count(), 8);
+    assert_eq!(response.body.matches("10:00 Standup").count(), 1);
+
+    // Free-busy reports the meeting, and the time outside working hours as unavailable
+    let query = r#"<?xml version="1.0" encoding="utf-8"?>
+<C:free-busy-query xmlns:C="urn:ietf:params:xml:ns:caldav">
+    <C:time-range start="20300702T000000Z" end="20300703T000000Z"/>
+</C:free-busy-query>"#;
+    let uri = format!("/calendars/{}/", calendar_id);
+    let response = app.send(dav_request("REPORT", &uri, ("alice@example.com", "secret123"), Some("1"), query)).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+    assert_eq!(response.header("content-type"), Some("text/calendar; charset=utf-8"));
+    assert!(response.body.contains("FREEBUSY;FBTYPE=BUSY:20300702T100000Z/20300702T103000Z\r\n"), "{}", response.body);
+    assert!(response.body.contains("FREEBUSY;FBTYPE=BUSY-UNAVAILABLE:20300702T000000Z/20300702T080000Z\r\n"));
+    assert!(response.body.contains("FREEBUSY;FBTYPE=BUSY-UNAVAILABLE:20300702T120000Z/20300703T000000Z\r\n"));
+
+    let response = app.send(dav_request("REPORT", &uri, ("alice@example.com", "secret123"), Some("1"),
+        r#"<C:free-busy-query xmlns:C="urn:ietf:params:xml:ns:caldav"/>"#)).await;
+    assert_eq!(response.status, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn events_are_added_from_the_dialog_with_checked_fields() {
+    let app = TestApp::with_admin().await;
+    let token = app.user_token("alice@example.com", "secret123").await;
+    let calendar_id = app.create_calendar(&token, "Work").await["id"].as_str().unwrap().to_string();
+    let response = app.send(json_request(Method::PUT, "/api/auth/settings", Some(&token), json!({ "timezone": "Europe/Berlin" }))).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+
+    // The dashboard and the grid open the dialog in place of the event form
+    let response = app.send(authed_request(Method::GET, "/web/dashboard", &token)).await;
+    assert!(response.body.contains(r##"hx-get="/web/events/quick" hx-target="#modal""##), "{}", response.body);
+    let response = app.send(authed_request(Method::GET, "/web/schedule?view=week&date=2030-07-02", &token)).await;
+    assert!(response.body.contains("/web/events/quick?start=2030-07-02T09:00"));
+
+    let response = app.send(authed_request(Method::GET, "/web/events/quick?start=2030-07-02T09:00", &token)).await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert!(response.body.starts_with("<dialog"), "{}", response.body);
+    assert!(response.body.contains(r#"value="2030-07-02T09:00""#));
+    assert!(response.body.contains(r#"value="2030-07-02T10:00""#));
+    assert!(response.body.contains(r#"maxlength="255""#));
+    let response = app.send(authed_request(Method::GET, "/web/events/quick?start=tomorrow", &token)).await;
+    assert_eq!(response.status, StatusCode::BAD_REQUEST);
+
+    let submit = |title: &str, start: &str, end: &str| {
+        let mut request = json_request(Method::POST, "/web/events/quick", Some(&token), json!({
+            "calendar_id": calendar_id,
+            "title": title,
+            "location": "",
+            "start_time": start,
+            "end_time": end,
+            "is_all_day": false,
+        }));
+        request.headers_mut().insert("accept", "application/json".parse().unwrap());
+        request
+    };
+
+    // Broken rules come back as JSON naming the field, for the dialog to show
+    let response = app.send(submit("Review", "2030-07-02T10:00", "2030-07-02T09:00")).await;
+    assert_eq!(response.status, StatusCode::BAD_REQUEST);
+    assert_eq!(response.json()["field"], "end_time");
+    let response = app.send(submit("   ", "2030-07-02T09:00", "2030-07-02T10:00")).await;
+    assert_eq!(response.json()["field"], "title");
+    let response = app.send(submit(&"x".repeat(256), "2030-07-02T09:00", "2030-07-02T10:00")).await;
+    assert_eq!(response.json()["field"], "title");
+    let response = app.send(submit("Review", "2030-07-02", "2030-07-02T10:00")).await;
+    assert_eq!(response.json()["field"], "start_time");
+
+    let response = app.send(submit(" Review ", "2030-07-02T09:00", "2030-07-02T10:00")).await;
+    assert_eq!(response.status, StatusCode::CREATED, "{}", response.body);
+    assert_eq!(response.json()["title"], "Review");
+    assert_eq!(response.json()["start_time"], "2030-07-02T07:00:00Z");
+    assert_eq!(response.json()["location"], json!(null));
+
+    // Without asking for JSON, errors still get the HTML error page
+    let mut request = submit("Review", "2030-07-02T10:00", "2030-07-02T09:00");
+    request.headers_mut().remove("accept");
+    let response = app.send(request).await;
+    assert_eq!(response.status, StatusCode::BAD_REQUEST);
+    assert!(response.header("content-type").unwrap().starts_with("text/html"));
+}
+
+#[tokio::test]
+async fn request_bodies_are_checked_field_by_field() {
+    let app = TestApp::with_admin().await;
+    let field = |response: &TestResponse| {
+        assert_eq!(response.status, StatusCode::BAD_REQUEST, "{}", response.body);
+        response.json()["field"].as_str().unwrap().to_string()
+    };
+
+    assert_eq!(field(&app.register("Alice", "alice.example.com", "secret123").await), "email");
+    assert_eq!(field(&app.register("Alice", "alice@example.com", "123").await), "password");
+    assert_eq!(field(&app.register(" ", "alice@example.com", "secret123").await), "name");
+    let token = app.user_token("alice@example.com", "secret123").await;
+
+    let calendar = |body: serde_json::Value| json_request(Method::POST, "/api/auth/calendars", Some(&token), body);
+    let response = app.send(calendar(json!({ "name": "Work", "description": null, "color": "blue", "is_public": false }))).await;
+    assert_eq!(field(&response), "color");
+    assert_eq!(response.json()["message"], "Color must look like #RRGGBB");
+    let response = app.send(calendar(json!({ "name": "Work", "description": "x".repeat(10_001), "color": null, "is_public": false }))).await;
+    assert_eq!(field(&response), "description");
+    let calendar_id = app.create_calendar(&token, "Work").await["id"].as_str().unwrap().to_string();
+    let uri = format!("/api/auth/calendars/{}", calendar_id);
+    let response = app.send(json_request(Method::PUT, &uri, Some(&token), json!({ "name": "" }))).await;
+    assert_eq!(field(&response), "name");
+
+    let event = |title: &str| json!({
+        "title": title,
+        "description": null,
+        "location": null,
+        "start_time": "2030-01-07T09:00:00Z",
+        "end_time": "2030-01-07T10:00:00Z",
+        "is_all_day": false,
+        "rrule": null,
+        "geo_lat": null,
+        "geo_lon": null,
+    });
+    let response = app.send(json_request(Method::POST, "/api/auth/events", Some(&token), json!({
+        "calendar_id": calendar_id,
+        "event": event(&"x".repeat(256)),
+    }))).await;
+    assert_eq!(field(&response), "title");
+    let response = app.send(json_request(Method::POST, "/api/auth/events", Some(&token), json!({
+        "calendar_id": calendar_id,
+        "event": event("Planning"),
+    }))).await;
+    let event_id = response.json()["id"].as_str().unwrap().to_string();
+    let uri = format!("/api/auth/events/{}", event_id);
+    let response = app.send(json_request(Method::PUT, &uri, Some(&token), json!({ "location": "x".repeat(256) }))).await;
+    assert_eq!(field(&response), "location");
+
+    // A bad operation fails the whole batch, naming its position
+    let response = app.send(json_request(Method::POST, "/api/auth/events/batch", Some(&token), json!({
+        "operations": [
+            { "op": "create", "calendar_id": calendar_id, "event": event("Review") },
+            { "op": "update", "event_id": event_id, "changes": { "title": "" } },
+        ],
+    }))).await;
+    assert_eq!(field(&response), "operations[1].title");
+    let response = app.send(authed_request(Method::GET, &format!("/api/auth/calendars/{}/events", calendar_id), &token)).await;
+    assert_eq!(response.json().as_array().unwrap().len(), 1);
+
+    let uri = format!("/api/auth/calendars/{}/shares", calendar_id);
+    let response = app.send(json_request(Method::POST, &uri, Some(&token), json!({ "shared_with_email": "bob", "permission": "read" }))).await;
+    assert_eq!(field(&response), "shared_with_email");
+    let response = app.send(json_request(Method::POST, &uri, Some(&token), json!({ "shared_with_email": "bob@example.com", "permission": "owner" }))).await;
+    assert_eq!(field(&response), "permission");
+
+    // Web forms come back with the message as a flash
+    let response = app.send(Request::post("/web/calendars/new")
+        .header("cookie", format!("auth_token={}", token))
+        .header("content-type", "application/x-www-form-urlencoded")
+        .body(Body::from("name=Home&color=%23zzzzzz"))
+        .unwrap()).await;
+    assert!(response.status.is_redirection());
+    assert_eq!(response.header("location"), Some("/web/calendars/new"));
+    let flash = response.header("set-cookie").unwrap().split(';').next().unwrap().to_string();
+    let response = app.send(Request::get("/web/calendars/new")
+        .header("cookie", format!("auth_token={}; {}", token, flash))
+        .body(Body::empty())
+        .unwrap()).await;
+    assert!(response.body.contains("Color must look like #RRGGBB"), "{}", response.body);
+}
+
+#[tokio::test]
+async fn flash_messages_come_only_from_signed_cookies_and_show_once() {
+    let app = TestApp::with_admin().await;
+    let token = app.user_token("alice@example.com", "secret123").await;
+    let page = |cookie: String| Request::get("/web/calendars")
+        .header("cookie", cookie)
+        .body(Body::empty())
+        .unwrap();
+
+    // Messages in the URL are not shown
+    let response = app.send(authed_request(Method::GET, "/web/calendars?message=%3Cb%3EInjected%3C%2Fb%3E&flash_type=error", &token)).await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert!(!response.body.contains("Injected"));
+
+    let calendar = app.create_calendar(&token, "Home").await;
+    let uri = format!("/web/calendars/{}/delete", calendar["id"].as_str().unwrap());
+    let response = app.send(authed_request(Method::POST, &uri, &token)).await;
+    assert_eq!(response.header("location"), Some("/web/calendars"));
+    let set_cookie = response.header("set-cookie").unwrap();
+    assert!(set_cookie.contains("HttpOnly"), "{}", set_cookie);
+    let flash = set_cookie.split(';').next().unwrap().to_string();
+
+    // The signed cookie's message is shown once, and the cookie is cleared with it
+    let response = app.send(page(format!("auth_token={}; {}", token, flash))).await;
+    assert!(response.body.contains("flash-success"), "{}", response.body);
+    assert!(response.header("set-cookie").is_some_and(|cookie| cookie.starts_with("flash=;") && cookie.contains("Max-Age=0")));
+    let response = app.send(page(format!("auth_token={}", token))).await;
+    assert!(!response.body.contains("flash-success"));
+
+    // A cookie that wasn't signed by the server is ignored
+    let (header, rest) = flash.split_once('.').unwrap();
+    let forged = format!("{}.{}x", header, rest);
+    let response = app.send(page(format!("auth_token={}; {}", token, forged))).await;
+    assert!(!response.body.contains("flash-success"));
+    assert!(response.header("set-cookie").is_none());
+}
+
+#[tokio::test]
+async fn events_cannot_end_before_they_start_and_overlaps_are_reported() {
+    let app = TestApp::with_admin().await;
+    let token = app.user_token("alice@example.com", "secret123").await;
+    let calendar_id = app.create_calendar(&token, "Work").await["id"].as_str().unwrap().to_string();
+    let create = |uri: &str, title: &str, start: &str, end: &str, is_all_day: bool| json_request(Method::POST, uri, Some(&token), json!({
+        "calendar_id": calendar_id,
+        "event": {
+            "title": title,
+            "description": null,
+            "location": null,
+            "start_time": start,
+            "end_time": end,
+            "is_all_day": is_all_day,
+            "rrule": null,
+            "geo_lat": null,
+            "geo_lon": null,
+        },
+    }));
+
+    let response = app.send(create("/api/auth/events", "Standup", "2030-01-07T10:00:00Z", "2030-01-07T09:00:00Z", false)).await;
+    assert_eq!(response.status, StatusCode::BAD_REQUEST);
+    assert_eq!(response.json()["field"], "end_time");
+    let response = app.send(create("/api/auth/events", "Standup", "2030-01-07T09:00:00Z", "2030-01-07T10:00:00Z", false)).await;
+    assert!(response.json().get("conflicts").is_none());
+    let standup = response.json()["id"].as_str().unwrap().to_string();
+    app.send(create("/api/auth/events", "Holiday", "2030-01-07T00:00:00Z", "2030-01-08T00:00:00Z", true)).await;
+
+    // Moving only the end is checked against the stored start, in the service
+    let uri = format!("/api/auth/events/{}", standup);
+    let response = app.send(json_request(Method::PUT, &uri, Some(&token), json!({ "end_time": "2030-01-07T08:00:00Z" }))).await;
+    assert_eq!(response.status, StatusCode::BAD_REQUEST);
+    assert_eq!(response.json()["field"], "end_time");
+    let response = app.send(json_request(Method::POST, "/api/auth/events/batch", Some(&token), json!({
+        "operations": [{ "op": "update", "event_id": standup, "changes": { "start_time": "2030-01-07T11:00:00Z" } }],
+    }))).await;
+    assert_eq!(response.status, StatusCode::BAD_REQUEST);
+
+    // Overlaps are stored, and reported on request; all-day events don't count
+    let response = app.send(create("/api/auth/events?check_conflicts=true", "Review", "2030-01-07T09:30:00Z", "2030-01-07T10:30:00Z", false)).await;
+    assert_eq!(response.status, StatusCode::OK);
+    let conflicts = response.json()["conflicts"].as_array().unwrap().clone();
+    assert_eq!(conflicts.len(), 1);
+    assert_eq!(conflicts[0]["id"], standup.as_str());
+    let uri = format!("/api/auth/events/{}?check_conflicts=true", response.json()["id"].as_str().unwrap());
+    let response = app.send(json_request(Method::PUT, &uri, Some(&token), json!({
+        "start_time": "2030-01-07T10:00:00Z",
+        "end_time": "2030-01-07T11:00:00Z",
+    }))).await;
+    assert_eq!(response.json()["conflicts"], json!([]));
+
+    // The web form warns about them on the next page
+    let response = app.send(Request::post("/web/events/new")
+        .header("cookie", format!("auth_token={}", token))
+        .header("content-type", "application/x-www-form-urlencoded")
+        .body(Body::from(format!("calendar_id={}&title=Lunch&start_time=2030-01-07T09%3A45&end_time=2030-01-07T10%3A15", calendar_id)))
+        .unwrap()).await;
+    assert!(response.status.is_redirection());
+    let flash = response.header("set-cookie").unwrap().split(';').next().unwrap().to_string();
+    let response = app.send(Request::get(response.header("location").unwrap())
+        .header("cookie", format!("auth_token={}; {}", token, flash))
+        .body(Body::empty())
+        .unwrap()).await;
+    assert!(response.body.contains("flash-warning"), "{}", response.body);
+    assert!(response.body.contains("Event created, but it overlaps Standup, Review"), "{}", response.body);
+}
+
+#[tokio::test]
+async fn all_day_events_take_whole_days() {
+    let app = TestApp::with_admin().await;
+    let token = app.user_token("alice@example.com", "secret123").await;
+    let calendar_id = app.create_calendar(&token, "Work").await["id"].as_str().unwrap().to_string();
+
+    // Times of an all-day event are brought to whole days when stored
+    let response = app.send(json_request(Method::POST, "/api/auth/events", Some(&token), json!({
+        "calendar_id": calendar_id,
+        "event": {
+            "title": "Offsite",
+            "description": null,
+            "location": null,
+            "start_time": "2030-01-07T09:00:00Z",
+            "end_time": "2030-01-08T17:00:00Z",
+            "is_all_day": true,
+            "rrule": null,
+            "geo_lat": null,
+            "geo_lon": null,
+        },
+    }))).await;
+    assert_eq!(response.json()["start_time"], "2030-01-07T00:00:00Z");
+    assert_eq!(response.json()["end_time"], "2030-01-09T00:00:00Z");
+    let offsite = response.json()["id"].as_str().unwrap().to_string();
+
+    // The form takes days, ending on the last one
+    let response = app.send(Request::post("/web/events/new")
+        .header("cookie", format!("auth_token={}", token))
+        .header("content-type", "application/x-www-form-urlencoded")
+        .body(Body::from(format!("calendar_id={}&title=Conference&start_time=2030-01-09&end_time=2030-01-10&is_all_day=on", calendar_id)))
+        .unwrap()).await;
+    assert!(response.status.is_redirection());
+    let uri = format!("/api/auth/calendars/{}/events", calendar_id);
+    let events = app.send(authed_request(Method::GET, &uri, &token)).await.json();
+    let conference = events.as_array().unwrap().iter().find(|event| event["title"] == "Conference").unwrap().clone();
+    assert_eq!(conference["start_time"], "2030-01-09T00:00:00Z");
+    assert_eq!(conference["end_time"], "2030-01-11T00:00:00Z");
+
+    let response = app.send(authed_request(Method::GET, &format!("/web/events/{}/edit", conference["id"].as_str().unwrap()), &token)).await;
+    assert!(response.body.contains(r#"type="date""#), "{}", response.body);
+    assert!(response.body.contains(r#"value="2030-01-10""#), "{}", response.body);
+
+    // In the week, they fill the all-day row on every day they cover
+    let response = app.send(authed_request(Method::GET, "/web/schedule?view=week&date=2030-01-07", &token)).await;
+    assert!(response.body.contains("schedule-all-day"), "{}", response.body);
+    let offsite_link = format!(r#"href="/web/events/{}/edit" class="schedule-event schedule-all-day-event""#, offsite);
+    assert_eq!(response.body.matches(&offsite_link).count(), 2, "{}", response.body);
+    assert_eq!(response.body.matches("schedule-all-day-event").count(), 4);
+}
+
+#[tokio::test]
+async fn multi_day_events_show_their_span() {
+    let app = TestApp::with_admin().await;
+    let token = app.user_token("alice@example.com", "secret123").await;
+    let calendar_id = app.create_calendar(&token, "Work").await["id"].as_str().unwrap().to_string();
+    let create = |title: &str, start: &str, end: &str, is_all_day: bool| json_request(Method::POST, "/api/auth/events", Some(&token), json!({
+        "calendar_id": calendar_id,
+        "event": {
+            "title": title,
+            "description": null,
+            "location": null,
+            "start_time": start,
+            "end_time": end,
+            "is_all_day": is_all_day,
+            "rrule": null,
+            "geo_lat": null,
+            "geo_lon": null,
+        },
+    }));
+    app.send(create("Night shift", "2030-01-07T22:00:00Z", "2030-01-09T08:30:00Z", false)).await;
+    app.send(create("Late call", "2030-01-10T22:00:00Z", "2030-01-11T00:00:00Z", false)).await;
+    app.send(create("Offsite", "2030-01-07T00:00:00Z", "2030-01-10T00:00:00Z", true)).await;
+
+    // Lists name the last day and the duration; ending at midnight stays on the day
+    let response = app.send(authed_request(Method::GET, &format!("/web/calendars/{}", calendar_id), &token)).await;
+    assert!(response.body.contains("2030-01-07 22:00 - 2030-01-09 08:30 (1 d 10 h 30 min)"), "{}", response.body);
+    assert!(response.body.contains("2030-01-10 22:00 - 00:00 (2 h)"), "{}", response.body);
+    assert!(response.body.contains("2030-01-07 All day, until 2030-01-09 (3 d)"), "{}", response.body);
+
+    // The grid shows the rest of the night shift on the following days
+    let response = app.send(authed_request(Method::GET, "/web/schedule?view=week&date=2030-01-07", &token)).await;
+    assert!(response.body.contains("22:00 Night shift"));
+    assert_eq!(response.body.matches("… Night shift").count(), 2, "{}", response.body);
+    assert!(!response.body.contains("… Late call"));
+    let response = app.send(authed_request(Method::GET, "/web/schedule?view=month&date=2030-01-07", &token)).await;
+    assert_eq!(response.body.matches("… Night shift").count(), 2);
+    assert_eq!(response.body.matches(">Offsite<").count(), 3);
+}
+
+#[tokio::test]
+async fn signed_out_pages_render_as_in_their_snapshots() {
+    let app = TestApp::new().await;
+    let page = |uri: &str, accept_language: &str| Request::builder()
+        .uri(uri)
+        .header("accept-language", accept_language)
+        .body(Body::empty())
+        .unwrap();
+
+    let response = app.send(page("/setup", "en")).await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_snapshot("setup.en", &response.body);
+
+    app.setup(ADMIN_EMAIL, ADMIN_PASSWORD).await;
+    for (uri, name) in [("/web/login", "login"), ("/web/register", "register")] {
+        for language in ["en", "de"] {
+            let response = app.send(page(uri, language)).await;
+            assert_eq!(response.status, StatusCode::OK);
+            assert_snapshot(&format!("{}.{}", name, language), &response.body);
+        }
+    }
+}
+
+#[tokio::test]
+async fn earlier_versions_of_changed_and_deleted_events_can_be_restored() {
+    let mut config = Config::default();
+    config.retention.event_versions = 2;
+    let app = TestApp::with_config(config).await;
+    app.setup(ADMIN_EMAIL, ADMIN_PASSWORD).await;
+    let alice = app.user_token("alice@example.com", "secret123").await;
+    let calendar_id = app.create_calendar(&alice, "Work").await["id"].as_str().unwrap().to_string();
+    let start = Utc::now() + Duration::days(1);
+    let response = app.send(json_request(Method::POST, "/api/auth/events", Some(&alice), json!({
+        "calendar_id": calendar_id,
+        "event": {
+            "title": "Planning",
+            "description": null,
+            "location": null,
+            "start_time": start,
+            "end_time": start + Duration::hours(1),
+            "is_all_day": false,
+            "rrule": null,
+            "geo_lat": null,
+            "geo_lon": null,
+        },
+    }))).await;
+    let event = format!("/api/auth/events/{}", response.json()["id"].as_str().unwrap());
+    let history = format!("{}/history", event);
+
+    // Putting back an unchanged event leaves no version; only the newest two are kept
+    for title in ["Planning", "Planning v2", "Planning v3", "Planning v4"] {
+        let response = app.send(json_request(Method::PUT, &event, Some(&alice), json!({ "title": title }))).await;
+        assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+    }
+    let entries = app.send(authed_request(Method::GET, &history, &alice)).await.json();
+    assert_eq!(entries.as_array().unwrap().len(), 2);
+    assert_eq!(entries[0]["action"], "updated");
+    assert_eq!(entries[0]["event"]["title"], "Planning v3");
+    assert_eq!(entries[0]["changes"], json!(["title"]));
+
+    let restore = format!("{}/{}/restore", history, entries[1]["id"]);
+    let response = app.send(authed_request(Method::POST, &restore, &alice)).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+    assert_eq!(response.json()["title"], "Planning v2");
+    let entries = app.send(authed_request(Method::GET, &history, &alice)).await.json();
+    assert_eq!(entries[0]["event"]["title"], "Planning v4");
+
+    // Others can neither see nor restore the history
+    let bob = app.user_token("bob@example.com", "secret123").await;
+    let response = app.send(authed_request(Method::GET, &history, &bob)).await;
+    assert_eq!(response.status, StatusCode::UNAUTHORIZED);
+
+    // A deleted event is listed with its last state and comes back under its id
+    app.send(authed_request(Method::DELETE, &event, &alice)).await;
+    let deleted = format!("/api/auth/calendars/{}/deleted-events", calendar_id);
+    let versions = app.send(authed_request(Method::GET, &deleted, &alice)).await.json();
+    assert_eq!(versions[0]["action"], "deleted");
+    assert_eq!(versions[0]["event"]["title"], "Planning v2");
+    let response = app.send(authed_request(Method::GET, &format!("/web/calendars/{}/deleted", calendar_id), &alice)).await;
+    assert!(response.body.contains("Planning v2"));
+    let response = app.send(authed_request(Method::GET, &format!("/web/events/{}/history", versions[0]["event"]["id"].as_str().unwrap()), &alice)).await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert!(response.body.contains("Restore event"));
+
+    let restore = format!("{}/{}/restore", history, versions[0]["id"]);
+    let response = app.send(authed_request(Method::POST, &restore, &alice)).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+    let response = app.send(authed_request(Method::GET, &event, &alice)).await;
+    assert_eq!(response.json()["title"], "Planning v2");
+    let versions = app.send(authed_request(Method::GET, &deleted, &alice)).await.json();
+    assert_eq!(versions, json!([]));
+}
+
+#[tokio::test]
+async fn impersonation_can_edit_events_but_not_grant_access() {
+    let app = TestApp::with_admin().await;
+    let alice = app.user_token("alice@example.com", "secret123").await;
+    app.user_token("bob@example.com", "secret123").await;
+    let alice_id = app.login("alice@example.com", "secret123").await.json()["user"]["id"].as_str().unwrap().to_string();
+    let calendar_id = app.create_calendar(&alice, "Chores").await["id"].as_str().unwrap().to_string();
+    let admin = app.login(ADMIN_EMAIL, ADMIN_PASSWORD).await.json()["token"].as_str().unwrap().to_string();
+
+    let response = app.send(authed_request(Method::POST, &format!("/api/admin/users/{}/impersonate", alice_id), &admin)).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+    let token = response.json()["token"].as_str().unwrap().to_string();
+
+    let response = app.send(authed_request(Method::GET, "/api/auth/calendars", &token)).await;
+    assert_eq!(response.status, StatusCode::OK);
+    let response = app.send(json_request(Method::POST, "/api/auth/events", Some(&token), json!({
+        "calendar_id": calendar_id,
+        "event": {
+            "title": "Fix the fence",
+            "description": null,
+            "location": null,
+            "start_time": "2030-01-07T10:00:00Z",
+            "end_time": "2030-01-07T11:00:00Z",
+            "is_all_day": false,
+            "rrule": null,
+            "geo_lat": null,
+            "geo_lon": null,
+        },
+    }))).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+
+    for (uri, body) in [
+        (format!("/api/auth/calendars/{}/shares", calendar_id), json!({ "shared_with_email": "bob@example.com", "permission": "write" })),
+        (format!("/api/auth/calendars/{}/share-links", calendar_id), json!({ "permission": "write" })),
+        (format!("/api/auth/calendars/{}/feeds", calendar_id), json!({ "label": "Mine now" })),
+        (format!("/api/auth/calendars/{}/transfer", calendar_id), json!({ "new_owner_email": "bob@example.com", "confirm_name": "Chores" })),
+        ("/api/auth/delegations".to_string(), json!({ "delegate_email": "bob@example.com", "permission": "write" })),
+        ("/api/auth/user-feeds".to_string(), json!({ "label": "Mine now" })),
+    ] {
+        let response = app.send(json_request(Method::POST, &uri, Some(&token), body)).await;
+        assert_eq!(response.status, StatusCode::FORBIDDEN, "{}", uri);
+    }
+    let response = app.send(Request::builder()
+        .method(Method::POST)
+        .uri("/web/settings/delegations")
+        .header("content-type", "application/x-www-form-urlencoded")
+        .header("cookie", format!("auth_token={}", token))
+        .body(Body::from("delegate_email=bob%40example.com&permission=write"))
+        .unwrap()).await;
+    assert_eq!(response.status, StatusCode::FORBIDDEN);
+
+    // Nothing was handed out behind the user's back
+    let response = app.send(authed_request(Method::GET, &format!("/api/auth/calendars/{}/shares", calendar_id), &alice)).await;
+    assert_eq!(response.json(), json!([]));
+    let response = app.send(authed_request(Method::GET, "/api/auth/delegations", &alice)).await;
+    assert_eq!(response.json()["granted"], json!([]));
+}
+
+#[tokio::test]
+async fn calendar_listings_embed_the_next_instance_of_older_series() {
+    let clock = MockClock::new(Utc.with_ymd_and_hms(2030, 3, 4, 8, 0, 0).unwrap());
+    let app = TestApp::with_clock(clock.clone()).await;
+    app.setup(ADMIN_EMAIL, ADMIN_PASSWORD).await;
+    let token = app.user_token("alice@example.com", "secret123").await;
+    let calendar_id = app.create_calendar(&token, "Team").await["id"].as_str().unwrap().to_string();
+
+    // A weekly series that started last month, and more one-off events than are embedded
+    let series_start = Utc.with_ymd_and_hms(2030, 2, 4, 9, 0, 0).unwrap();
+    let mut events = vec![("Standup", series_start, Some("FREQ=WEEKLY"))];
+    for day in 10..16 {
+        events.push(("Review", Utc.with_ymd_and_hms(2030, 3, day, 14, 0, 0).unwrap(), None));
+    }
+    for (title, start, rrule) in events {
+        let response = app.send(json_request(Method::POST, "/api/auth/events", Some(&token), json!({
+            "calendar_id": calendar_id,
+            "event": {
+                "title": title,
+                "description": null,
+                "location": null,
+                "start_time": start,
+                "end_time": start + Duration::minutes(30),
+                "is_all_day": false,
+                "rrule": rrule,
+                "geo_lat": null,
+                "geo_lon": null,
+            },
+        }))).await;
+        assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+    }
+
+    let response = app.send(authed_request(Method::GET, "/api/auth/calendars?include_events=true", &token)).await;
+    assert_eq!(response.status, StatusCode::OK);
+    let calendars = response.json();
+    let calendar = calendars.as_array().unwrap().iter().find(|c| c["id"] == calendar_id.as_str()).unwrap();
+    let upcoming = calendar["upcoming_events"].as_array().unwrap();
+    assert_eq!(upcoming.len(), 5);
+    assert_eq!(upcoming[0]["title"], "Standup");
+    assert_eq!(upcoming[0]["start_time"], "2030-03-04T09:00:00Z");
+    assert_eq!(upcoming[0]["is_recurring"], true);
+    assert_eq!(upcoming[4]["start_time"], "2030-03-13T14:00:00Z");
+}
+
+#[tokio::test]
+async fn nearby_search_reaches_across_the_antimeridian_and_the_poles() {
+    let app = TestApp::with_admin().await;
+    let token = app.user_token("alice@example.com", "secret123").await;
+    let response = app.send(json_request(Method::POST, "/api/auth/calendars", Some(&token), json!({
+        "name": "Pacific",
+        "description": null,
+        "color": "#3B82F6",
+        "is_public": true,
+    }))).await;
+    let calendar_id = response.json()["id"].as_str().unwrap().to_string();
+
+    for (title, lat, lon) in [("Suva", 0.0, 179.95), ("Rabi", 0.0, -179.95), ("Station", 89.9, 180.0)] {
+        let response = app.send(json_request(Method::POST, "/api/auth/events", Some(&token), json!({
+            "calendar_id": calendar_id,
+            "event": {
+                "title": title,
+                "description": null,
+                "location": null,
+                "start_time": "2030-01-07T10:00:00Z",
+                "end_time": "2030-01-07T11:00:00Z",
+                "is_all_day": false,
+                "rrule": null,
+                "geo_lat": lat,
+                "geo_lon": lon,
+            },
+        }))).await;
+        assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+    }
+
+    let nearby = |lat: f64, lon: f64| {
+        let uri = format!(
+            "/api/public/events/nearby?lat={}&lon={}&radius_km=50&start=2030-01-07T00:00:00Z&end=2030-01-08T00:00:00Z",
+            lat, lon
+        );
+        app.send(Request::builder().uri(uri).body(Body::empty()).unwrap())
+    };
+    let titles = |response: TestResponse| -> Vec<String> {
+        response.json().as_array().unwrap().iter().map(|e| e["title"].as_str().unwrap().to_string()).collect()
+    };
+
+    assert_eq!(titles(nearby(0.0, 179.9).await), ["Suva", "Rabi"]);
+    assert_eq!(titles(nearby(0.0, -179.9).await), ["Rabi", "Suva"]);
+    // Near the pole every longitude is close by
+    assert_eq!(titles(nearby(89.9, 0.0).await), ["Station"]);
+}
+
+#[tokio::test]
+async fn nearby_search_lists_each_instance_of_older_series() {
+    let app = TestApp::with_admin().await;
+    let token = app.user_token("alice@example.com", "secret123").await;
+    let response = app.send(json_request(Method::POST, "/api/auth/calendars", Some(&token), json!({
+        "name": "Running club",
+        "description": null,
+        "color": "#3B82F6",
+        "is_public": true,
+    }))).await;
+    let calendar_id = response.json()["id"].as_str().unwrap().to_string();
+
+    for (title, start, end, rrule) in [
+        ("Monday run", "2029-01-01T18:00:00Z", "2029-01-01T19:00:00Z", Some("FREQ=WEEKLY")),
+        ("Kick-off", "2029-01-01T17:00:00Z", "2029-01-01T18:00:00Z", None),
+    ] {
+        let response = app.send(json_request(Method::POST, "/api/auth/events", Some(&token), json!({
+            "calendar_id": calendar_id,
+            "event": {
+                "title": title,
+                "description": null,
+                "location": null,
+                "start_time": start,
+                "end_time": end,
+                "is_all_day": false,
+                "rrule": rrule,
+                "geo_lat": 52.52,
+                "geo_lon": 13.40,
+            },
+        }))).await;
+        assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+    }
+
+    let uri = "/api/public/events/nearby?lat=52.5&lon=13.4&radius_km=50&start=2030-03-04T00:00:00Z&end=2030-03-18T00:00:00Z";
+    let response = app.send(Request::builder().uri(uri).body(Body::empty()).unwrap()).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+    let instances: Vec<(String, String)> = response.json().as_array().unwrap().iter()
+        .map(|e| (e["title"].as_str().unwrap().to_string(), e["start_time"].as_str().unwrap().to_string()))
+        .collect();
+    assert_eq!(instances, [
+        ("Monday run".to_string(), "2030-03-04T18:00:00Z".to_string()),
+        ("Monday run".to_string(), "2030-03-11T18:00:00Z".to_string()),
+    ]);
+}
@@ -0,0 +1,1096 @@
+//! End-to-end CalDAV client handshake: discovery, listing, creating a calendar and
+//! syncing an event through it
+
+mod common;
+
+use axum::body::Body;
+use axum::extract::ConnectInfo;
+use axum::http::{Method, Request, StatusCode};
+use common::{authed_request, dav_request, json_request, TestApp, ADMIN_EMAIL, ADMIN_PASSWORD};
+use my_cal_dav_server::Config;
+use serde_json::json;
+use std::net::SocketAddr;
+
+const EMAIL: &str = "carol@example.com";
+const PASSWORD: &str = "secret123";
+
+const PROPFIND_BODY: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<d:propfind xmlns:d="DAV:" xmlns:cs="http://calendarserver.org/ns/">
+    <d:prop>
+        <d:resourcetype/>
+        <d:displayname/>
+        <cs:getctag/>
+    </d:prop>
+</d:propfind>"#;
+
+const CALENDAR_QUERY_BODY: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<c:calendar-query xmlns:d="DAV:" xmlns:c="urn:ietf:params:xml:ns:caldav">
+    <d:prop>
+        <d:getetag/>
+        <c:calendar-data/>
+    </d:prop>
+    <c:filter>
+        <c:comp-filter name="VCALENDAR">
+            <c:comp-filter name="VEVENT"/>
+        </c:comp-filter>
+    </c:filter>
+</c:calendar-query>"#;
+
+fn event_ics(summary: &str) -> String {
+    format!(
+        "BEGIN:VCALENDAR\r\n\
+         VERSION:2.0\r\n\
+         PRODID:-//Tests//EN\r\n\
+         BEGIN:VEVENT\r\n\
+         UID:test-event-1\r\n\
+         DTSTART:20300101T090000Z\r\n\
+         DTEND:20300101T100000Z\r\n\
+         SUMMARY:{}\r\n\
+         END:VEVENT\r\n\
+         END:VCALENDAR\r\n",
+        summary
+    )
+}
+
+async fn app_with_user() -> TestApp {
+    let app = TestApp::with_admin().await;
+    let response = app.register("Carol", EMAIL, PASSWORD).await;
+    assert_eq!(response.status, StatusCode::OK);
+    app
+}
+
+#[tokio::test]
+async fn discovery_redirects_to_the_caldav_root() {
+    let app = TestApp::new().await;
+
+    let request = Request::builder()
+        .method("PROPFIND")
+        .uri("/.well-known/caldav")
+        .body(Body::empty())
+        .unwrap();
+
+    let response = app.send(request).await;
+    assert_eq!(response.status, StatusCode::MOVED_PERMANENTLY);
+    assert_eq!(response.header("location"), Some("/calendars/"));
+}
+
+#[tokio::test]
+async fn requests_without_valid_credentials_are_rejected() {
+    let app = app_with_user().await;
+
+    let response = app.send(dav_request("PROPFIND", "/calendars/", (EMAIL, "wrong"), Some("1"), PROPFIND_BODY)).await;
+    assert_eq!(response.status, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn client_handshake_and_event_sync() {
+    let app = app_with_user().await;
+    let credentials = (EMAIL, PASSWORD);
+
+    let response = app.send(dav_request("OPTIONS", "/calendars/", credentials, None, "")).await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert!(response.header("dav").is_some_and(|d| d.contains("calendar-access")));
+
+    // Default calendars are listed
+    let response = app.send(dav_request("PROPFIND", "/calendars/", credentials, Some("1"), PROPFIND_BODY)).await;
+    assert_eq!(response.status, StatusCode::MULTI_STATUS);
+    assert!(response.body.contains("Personal"));
+
+    // Create a calendar
+    let mkcol = r#"<?xml version="1.0" encoding="utf-8"?>
+<d:mkcol xmlns:d="DAV:"><d:set><d:prop><d:displayname>Synced</d:displayname></d:prop></d:set></d:mkcol>"#;
+    let response = app.send(dav_request("MKCOL", "/calendars/new", credentials, None, mkcol)).await;
+    assert_eq!(response.status, StatusCode::CREATED);
+    let calendar = response.header("location").expect("MKCOL returns the new calendar").to_string();
+
+    let response = app.send(dav_request("PROPFIND", &calendar, credentials, Some("0"), PROPFIND_BODY)).await;
+    assert_eq!(response.status, StatusCode::MULTI_STATUS);
+    assert!(response.body.contains("Synced"));
+
+    // Upload an event under a client-chosen name
+    let response = app.send(dav_request("PUT", &format!("{}test-event-1.ics", calendar), credentials, None, &event_ics("Kickoff"))).await;
+    assert_eq!(response.status, StatusCode::CREATED);
+    let event = response.header("location").expect("PUT returns the stored resource").to_string();
+    assert!(response.header("etag").is_some());
+
+    let response = app.send(dav_request("REPORT", &calendar, credentials, Some("1"), CALENDAR_QUERY_BODY)).await;
+    assert_eq!(response.status, StatusCode::MULTI_STATUS);
+    assert!(response.body.contains("SUMMARY:Kickoff"));
+
+    // Replace it and read it back
+    let response = app.send(dav_request("PUT", &event, credentials, None, &event_ics("Kickoff (moved)"))).await;
+    assert_eq!(response.status, StatusCode::NO_CONTENT);
+
+    let response = app.send(dav_request("GET", &event, credentials, None, "")).await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert!(response.body.contains("SUMMARY:Kickoff (moved)"));
+
+    // Remove the event, then the calendar
+    let response = app.send(dav_request("DELETE", &event, credentials, None, "")).await;
+    assert_eq!(response.status, StatusCode::NO_CONTENT);
+
+    let response = app.send(dav_request("GET", &event, credentials, None, "")).await;
+    assert_eq!(response.status, StatusCode::NOT_FOUND);
+
+    let response = app.send(dav_request("DELETE", &calendar, credentials, None, "")).await;
+    assert_eq!(response.status, StatusCode::NO_CONTENT);
+}
+
+#[tokio::test]
+async fn unsupported_methods_advertise_allowed_ones() {
+    let app = app_with_user().await;
+
+    let response = app.send(dav_request("LOCK", "/calendars/", (EMAIL, PASSWORD), None, "")).await;
+    assert_eq!(response.status, StatusCode::METHOD_NOT_ALLOWED);
+    assert!(response.header("allow").is_some_and(|a| a.contains("PROPFIND")));
+}
+
+#[tokio::test]
+async fn shared_calendars_are_listed_read_only() {
+    let app = app_with_user().await;
+    let credentials = (EMAIL, PASSWORD);
+
+    let response = app.login(ADMIN_EMAIL, ADMIN_PASSWORD).await;
+    let admin_token = response.json()["token"].as_str().unwrap().to_string();
+    let calendar = app.create_calendar(&admin_token, "Team").await;
+    let calendar_id = calendar["id"].as_str().unwrap();
+
+    let response = app.send(json_request(Method::POST, &format!("/api/auth/calendars/{}/shares", calendar_id), Some(&admin_token), json!({
+        "shared_with_email": EMAIL,
+        "permission": "read",
+    }))).await;
+    assert_eq!(response.status, StatusCode::OK, "share creation failed: {}", response.body);
+
+    let propfind = r#"<?xml version="1.0" encoding="utf-8"?>
+<d:propfind xmlns:d="DAV:"><d:prop><d:displayname/><d:current-user-privilege-set/></d:prop></d:propfind>"#;
+    let response = app.send(dav_request("PROPFIND", "/calendars/", credentials, Some("1"), propfind)).await;
+    assert_eq!(response.status, StatusCode::MULTI_STATUS);
+
+    let shared = response.body.split("<d:response>")
+        .find(|r| r.contains(&format!("/calendars/{}/", calendar_id)))
+        .expect("shared calendar is listed in the calendar home");
+    assert!(shared.contains("Team"));
+    assert!(shared.contains("<d:read/>"));
+    assert!(!shared.contains("<d:write/>"));
+
+    // Read-only shares can't be written to
+    let response = app.send(dav_request("PUT", &format!("/calendars/{}/test-event-1.ics", calendar_id), credentials, None, &event_ics("Nope"))).await;
+    assert_eq!(response.status, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn quotas_are_enforced_with_insufficient_storage() {
+    let mut config = Config::default();
+    config.onboarding.calendars = Vec::new();
+    config.quotas.max_calendars_per_user = Some(1);
+    config.quotas.max_events_per_calendar = Some(1);
+    let app = TestApp::with_config(config).await;
+    app.setup(ADMIN_EMAIL, ADMIN_PASSWORD).await;
+    let token = app.user_token(EMAIL, PASSWORD).await;
+    let credentials = (EMAIL, PASSWORD);
+
+    let calendar = app.create_calendar(&token, "Only").await;
+    let response = app.send(json_request(Method::POST, "/api/auth/calendars", Some(&token), json!({
+        "name": "One too many",
+        "description": null,
+        "color": null,
+        "is_public": false,
+    }))).await;
+    assert_eq!(response.status, StatusCode::INSUFFICIENT_STORAGE);
+
+    let collection = format!("/calendars/{}", calendar["id"].as_str().unwrap());
+    let response = app.send(dav_request("PUT", &format!("{}/first.ics", collection), credentials, None, &event_ics("First"))).await;
+    assert_eq!(response.status, StatusCode::CREATED);
+    let second = event_ics("Second").replace("UID:test-event-1", "UID:test-event-2");
+    let response = app.send(dav_request("PUT", &format!("{}/second.ics", collection), credentials, None, &second)).await;
+    assert_eq!(response.status, StatusCode::INSUFFICIENT_STORAGE);
+    assert!(response.body.contains("<d:quota-not-exceeded/>"), "{}", response.body);
+
+    let response = app.send(authed_request(Method::GET, "/web/dashboard", &token)).await;
+    assert!(response.body.contains("1 / 1"), "{}", response.body);
+    assert!(response.body.contains("btn-disabled"), "{}", response.body);
+}
+
+#[tokio::test]
+async fn put_rejects_oversized_and_non_calendar_bodies() {
+    let mut config = Config::default();
+    config.quotas.max_ics_size = 1024;
+    let app = TestApp::with_config(config).await;
+    app.setup(ADMIN_EMAIL, ADMIN_PASSWORD).await;
+    let token = app.user_token(EMAIL, PASSWORD).await;
+    let calendar = app.create_calendar(&token, "Limited").await;
+    let object = format!("/calendars/{}/limited.ics", calendar["id"].as_str().unwrap());
+    let credentials = (EMAIL, PASSWORD);
+
+    let response = app.send(dav_request("PUT", &object, credentials, None, &event_ics(&"x".repeat(2048)))).await;
+    assert_eq!(response.status, StatusCode::PAYLOAD_TOO_LARGE);
+    assert!(response.body.contains("<cal:max-resource-size/>"), "{}", response.body);
+    assert!(response.body.contains("1024 bytes"), "{}", response.body);
+
+    let mut request = dav_request("PUT", &object, credentials, None, &event_ics("Wrong type"));
+    request.headers_mut().insert("content-type", "application/json".parse().unwrap());
+    let response = app.send(request).await;
+    assert_eq!(response.status, StatusCode::UNSUPPORTED_MEDIA_TYPE);
+    assert!(response.body.contains("<cal:supported-calendar-data/>"), "{}", response.body);
+
+    let mut request = dav_request("PUT", &object, credentials, None, "SUMMARY:Not a calendar");
+    request.headers_mut().insert("content-type", "text/calendar".parse().unwrap());
+    let response = app.send(request).await;
+    assert_eq!(response.status, StatusCode::BAD_REQUEST);
+    assert!(response.body.contains("<cal:valid-calendar-data/>"), "{}", response.body);
+
+    let response = app.send(dav_request("PUT", &object, credentials, None, &event_ics("Fits"))).await;
+    assert_eq!(response.status, StatusCode::CREATED);
+}
+
+#[tokio::test]
+async fn rejected_puts_name_the_failed_precondition() {
+    let app = app_with_user().await;
+    let token = app.login(EMAIL, PASSWORD).await.json()["token"].as_str().unwrap().to_string();
+    let calendar = format!("/calendars/{}/", app.create_calendar(&token, "Strict").await["id"].as_str().unwrap());
+    let credentials = (EMAIL, PASSWORD);
+
+    let todo = "BEGIN:VCALENDAR\r\nVERSION:2.0\r\nBEGIN:VTODO\r\nUID:todo-1\r\nSUMMARY:Buy milk\r\nEND:VTODO\r\nEND:VCALENDAR\r\n";
+    let response = app.send(dav_request("PUT", &format!("{}todo-1.ics", calendar), credentials, None, todo)).await;
+    assert_eq!(response.status, StatusCode::FORBIDDEN);
+    assert!(response.body.contains("<cal:supported-calendar-component/>"), "{}", response.body);
+
+    let no_start = event_ics("Undated").replace("DTSTART:20300101T090000Z\r\n", "");
+    let response = app.send(dav_request("PUT", &format!("{}undated.ics", calendar), credentials, None, &no_start)).await;
+    assert_eq!(response.status, StatusCode::BAD_REQUEST);
+    assert!(response.body.contains("<cal:valid-calendar-data/>"), "{}", response.body);
+    assert!(response.body.contains("Missing DTSTART"), "{}", response.body);
+
+    // Uploading a stored event again under another name would duplicate it
+    let response = app.send(dav_request("PUT", &format!("{}original.ics", calendar), credentials, None, &event_ics("Original"))).await;
+    let event = response.header("location").expect("PUT returns the stored resource").to_string();
+    let response = app.send(dav_request("GET", &event, credentials, None, "")).await;
+    let copy = response.body.replace("SUMMARY:Original", "SUMMARY:Copy");
+    let response = app.send(dav_request("PUT", &format!("{}copy.ics", calendar), credentials, None, &copy)).await;
+    assert_eq!(response.status, StatusCode::CONFLICT);
+    assert!(response.body.contains("<cal:no-uid-conflict>"), "{}", response.body);
+    assert!(response.body.contains(&format!("<d:href>{}</d:href>", event)), "{}", response.body);
+
+    // Replacing the event under its own name is no conflict
+    let response = app.send(dav_request("PUT", &event, credentials, None, &copy)).await;
+    assert_eq!(response.status, StatusCode::NO_CONTENT);
+}
+
+#[tokio::test]
+async fn objects_keep_the_name_and_uid_clients_gave_them() {
+    let app = app_with_user().await;
+    let token = app.login(EMAIL, PASSWORD).await.json()["token"].as_str().unwrap().to_string();
+    let calendar = format!("/calendars/{}/", app.create_calendar(&token, "Named").await["id"].as_str().unwrap());
+    let credentials = (EMAIL, PASSWORD);
+
+    let object = format!("{}standup.ics", calendar);
+    let response = app.send(dav_request("PUT", &object, credentials, None, &event_ics("Standup"))).await;
+    assert_eq!(response.status, StatusCode::CREATED);
+    assert!(response.header("location").unwrap().ends_with(&object), "{:?}", response.header("location"));
+
+    let response = app.send(dav_request("GET", &object, credentials, None, "")).await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert!(response.body.contains("UID:test-event-1"), "{}", response.body);
+
+    let response = app.send(dav_request("PROPFIND", &calendar, credentials, Some("1"), "")).await;
+    assert!(response.body.contains(&format!("<d:href>{}</d:href>", object)), "{}", response.body);
+
+    // The UID belongs to standup.ics, so no other resource may use it
+    let response = app.send(dav_request("PUT", &format!("{}duplicate.ics", calendar), credentials, None, &event_ics("Duplicate"))).await;
+    assert_eq!(response.status, StatusCode::CONFLICT);
+    assert!(response.body.contains(&format!("<d:href>{}</d:href>", object)), "{}", response.body);
+
+    // Nor can replacing standup.ics change it
+    let renamed = event_ics("Standup").replace("UID:test-event-1", "UID:test-event-2");
+    let response = app.send(dav_request("PUT", &object, credentials, None, &renamed)).await;
+    assert_eq!(response.status, StatusCode::CONFLICT);
+    assert!(response.body.contains("<cal:no-uid-conflict>"), "{}", response.body);
+
+    let response = app.send(dav_request("PUT", &object, credentials, None, &event_ics("Standup (late)"))).await;
+    assert_eq!(response.status, StatusCode::NO_CONTENT);
+
+    let response = app.send(dav_request("DELETE", &object, credentials, None, "")).await;
+    assert_eq!(response.status, StatusCode::NO_CONTENT);
+    let response = app.send(dav_request("GET", &object, credentials, None, "")).await;
+    assert_eq!(response.status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn unsupported_dav_features_are_counted_when_enabled() {
+    let config = Config {
+        dav_usage_stats: true,
+        ..Config::default()
+    };
+    let app = TestApp::with_config(config).await;
+    app.setup(ADMIN_EMAIL, ADMIN_PASSWORD).await;
+    let token = app.user_token(EMAIL, PASSWORD).await;
+    let calendar = app.create_calendar(&token, "Stats").await;
+    let collection = format!("/calendars/{}", calendar["id"].as_str().unwrap());
+    let credentials = (EMAIL, PASSWORD);
+
+    let propfind = r#"<?xml version="1.0" encoding="utf-8"?>
+<d:propfind xmlns:d="DAV:" xmlns:c="urn:ietf:params:xml:ns:caldav">
+    <d:prop>
+        <d:displayname/>
+        <c:max-attendees-per-instance/>
+    </d:prop>
+</d:propfind>"#;
+    for _ in 0..2 {
+        let response = app.send(dav_request("PROPFIND", &collection, credentials, Some("0"), propfind)).await;
+        assert_eq!(response.status, StatusCode::MULTI_STATUS);
+    }
+    let sync = r#"<?xml version="1.0" encoding="utf-8"?>
+<d:sync-collection xmlns:d="DAV:"><d:sync-token/><d:prop><d:getetag/></d:prop></d:sync-collection>"#;
+    app.send(dav_request("REPORT", &collection, credentials, Some("1"), sync)).await;
+    let response = app.send(dav_request("LOCK", &collection, credentials, None, "")).await;
+    assert_eq!(response.status, StatusCode::METHOD_NOT_ALLOWED);
+
+    let admin = app.login(ADMIN_EMAIL, ADMIN_PASSWORD).await.json()["token"].as_str().unwrap().to_string();
+    let response = app.send(authed_request(Method::GET, "/api/admin/dav-usage", &admin)).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+    let features = response.json();
+    let count = |kind: &str, name: &str| features.as_array().unwrap().iter()
+        .find(|f| f["kind"] == kind && f["name"] == name)
+        .map(|f| f["count"].as_u64().unwrap());
+    assert_eq!(count("property", "{urn:ietf:params:xml:ns:caldav}max-attendees-per-instance"), Some(2));
+    assert_eq!(count("report", "{DAV:}sync-collection"), Some(1));
+    assert_eq!(count("method", "LOCK"), Some(1));
+    assert_eq!(count("property", "{DAV:}displayname"), None);
+
+    let response = app.send(authed_request(Method::GET, "/api/admin/dav-usage", &token)).await;
+    assert!(response.status.is_client_error());
+}
+
+#[tokio::test]
+async fn contact_birthdays_fill_a_read_only_birthdays_calendar() {
+    let app = app_with_user().await;
+    let credentials = (EMAIL, PASSWORD);
+    let token = app.login(EMAIL, PASSWORD).await.json()["token"].as_str().unwrap().to_string();
+
+    let response = app.send(json_request(Method::POST, "/api/auth/contacts", Some(&token), json!({
+        "name": "Ada",
+        "birthday": "1990-12-10",
+        "anniversary": "2015-06-20",
+    }))).await;
+    assert_eq!(response.status, StatusCode::CREATED, "{}", response.body);
+    let contact_id = response.json()["id"].as_str().unwrap().to_string();
+
+    let response = app.send(authed_request(Method::GET, "/api/auth/calendars", &token)).await;
+    let calendars = response.json();
+    let birthdays = calendars.as_array().unwrap().iter().find(|c| c["is_birthdays"] == true)
+        .expect("the Birthdays calendar is created with the first birthday");
+    assert_eq!(birthdays["name"], "Birthdays");
+    let calendar_id = birthdays["id"].as_str().unwrap().to_string();
+
+    let response = app.send(dav_request("REPORT", &format!("/calendars/{}/", calendar_id), credentials, Some("1"), CALENDAR_QUERY_BODY)).await;
+    assert_eq!(response.status, StatusCode::MULTI_STATUS);
+    assert!(response.body.contains("Ada&apos;s birthday"));
+    assert!(response.body.contains("Ada&apos;s anniversary"));
+    assert!(response.body.contains("RRULE:FREQ=YEARLY"));
+    assert!(response.body.contains("DTSTART;VALUE=DATE:19901210"));
+
+    // Clients see it read-only, and writes are refused even for the owner
+    let propfind = r#"<?xml version="1.0" encoding="utf-8"?>
+<d:propfind xmlns:d="DAV:"><d:prop><d:current-user-privilege-set/></d:prop></d:propfind>"#;
+    let response = app.send(dav_request("PROPFIND", "/calendars/", credentials, Some("1"), propfind)).await;
+    let listed = response.body.split("<d:response>")
+        .find(|r| r.contains(&format!("/calendars/{}/", calendar_id)))
+        .expect("the Birthdays calendar is listed in the calendar home");
+    assert!(!listed.contains("<d:write/>"));
+    let response = app.send(dav_request("PUT", &format!("/calendars/{}/test-event-1.ics", calendar_id), credentials, None, &event_ics("Nope"))).await;
+    assert_eq!(response.status, StatusCode::UNAUTHORIZED);
+    let response = app.send(authed_request(Method::DELETE, &format!("/api/auth/calendars/{}", calendar_id), &token)).await;
+    assert_eq!(response.status, StatusCode::UNAUTHORIZED);
+
+    // Changing and deleting contacts regenerates the calendar
+    let response = app.send(json_request(Method::PUT, &format!("/api/auth/contacts/{}", contact_id), Some(&token), json!({
+        "name": "Ada Lovelace",
+        "anniversary": null,
+    }))).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+    let response = app.send(authed_request(Method::GET, &format!("/api/auth/calendars/{}/events", calendar_id), &token)).await;
+    let events = response.json();
+    let titles: Vec<_> = events.as_array().unwrap().iter().map(|e| e["title"].as_str().unwrap()).collect();
+    assert_eq!(titles, ["Ada Lovelace's birthday"]);
+
+    let response = app.send(authed_request(Method::DELETE, &format!("/api/auth/contacts/{}", contact_id), &token)).await;
+    assert_eq!(response.status, StatusCode::NO_CONTENT);
+    let response = app.send(authed_request(Method::GET, &format!("/api/auth/calendars/{}/events", calendar_id), &token)).await;
+    assert!(response.json().as_array().unwrap().is_empty());
+
+    let response = app.send(Request::builder()
+        .uri(format!("/web/calendars/{}", calendar_id))
+        .header("cookie", format!("auth_token={}", token))
+        .body(Body::empty())
+        .unwrap()).await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert!(response.body.contains("Manage Contacts"));
+    assert!(!response.body.contains("+ New Event"));
+}
+
+#[tokio::test]
+async fn journal_entries_round_trip_as_vjournal() {
+    let app = app_with_user().await;
+    let credentials = (EMAIL, PASSWORD);
+    let journal_ics = |summary: &str| format!(
+        "BEGIN:VCALENDAR\r\n\
+         VERSION:2.0\r\n\
+         PRODID:-//Tests//EN\r\n\
+         BEGIN:VJOURNAL\r\n\
+         UID:test-journal-1\r\n\
+         DTSTART:20300102T080000Z\r\n\
+         SUMMARY:{}\r\n\
+         DESCRIPTION:Shipped login\\, fixed sync\r\n\
+         END:VJOURNAL\r\n\
+         END:VCALENDAR\r\n",
+        summary
+    );
+
+    let mkcol = r#"<?xml version="1.0" encoding="utf-8"?>
+<d:mkcol xmlns:d="DAV:"><d:set><d:prop><d:displayname>Notes</d:displayname></d:prop></d:set></d:mkcol>"#;
+    let response = app.send(dav_request("MKCOL", "/calendars/new", credentials, None, mkcol)).await;
+    let calendar = response.header("location").expect("MKCOL returns the new calendar").to_string();
+
+    // Journals are advertised for regular calendars
+    let propfind = r#"<?xml version="1.0" encoding="utf-8"?>
+<d:propfind xmlns:d="DAV:" xmlns:c="urn:ietf:params:xml:ns:caldav"><d:prop><c:supported-calendar-component-set/></d:prop></d:propfind>"#;
+    let response = app.send(dav_request("PROPFIND", &calendar, credentials, Some("0"), propfind)).await;
+    assert!(response.body.contains(r#"name="VEVENT""#));
+    assert!(response.body.contains(r#"name="VJOURNAL""#));
+
+    let response = app.send(dav_request("PUT", &format!("{}test-journal-1.ics", calendar), credentials, None, &journal_ics("Standup"))).await;
+    assert_eq!(response.status, StatusCode::CREATED, "{}", response.body);
+    let journal = response.header("location").expect("PUT returns the stored resource").to_string();
+    let etag = response.header("etag").expect("PUT returns an ETag").to_string();
+
+    let response = app.send(dav_request("GET", &journal, credentials, None, "")).await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert!(response.body.contains("BEGIN:VJOURNAL"));
+    assert!(response.body.contains("UID:test-journal-1"));
+    assert!(response.body.contains("SUMMARY:Standup"));
+    assert!(response.body.contains("DESCRIPTION:Shipped login\\, fixed sync"));
+    assert!(response.body.contains("DTSTART:20300102T080000Z"));
+
+    let response = app.send(dav_request("PROPFIND", &calendar, credentials, Some("1"), PROPFIND_BODY)).await;
+    assert!(response.body.contains(&journal));
+    let response = app.send(dav_request("REPORT", &calendar, credentials, Some("1"), CALENDAR_QUERY_BODY)).await;
+    assert!(response.body.contains("SUMMARY:Standup"));
+
+    // Replace it, only if unchanged since it was read
+    let mut request = dav_request("PUT", &journal, credentials, None, &journal_ics("Standup (edited)"));
+    request.headers_mut().insert("If-Match", etag.parse().unwrap());
+    let response = app.send(request).await;
+    assert_eq!(response.status, StatusCode::NO_CONTENT);
+    let response = app.send(dav_request("GET", &journal, credentials, None, "")).await;
+    assert!(response.body.contains("SUMMARY:Standup (edited)"));
+
+    let token = app.login(EMAIL, PASSWORD).await.json()["token"].as_str().unwrap().to_string();
+    let response = app.send(Request::builder()
+        .uri(format!("/web{}journal", calendar))
+        .header("cookie", format!("auth_token={}", token))
+        .body(Body::empty())
+        .unwrap()).await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert!(response.body.contains("Standup (edited)"));
+
+    let response = app.send(dav_request("DELETE", &journal, credentials, None, "")).await;
+    assert_eq!(response.status, StatusCode::NO_CONTENT);
+    let response = app.send(dav_request("GET", &journal, credentials, None, "")).await;
+    assert_eq!(response.status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn all_day_events_are_written_as_dates() {
+    let app = app_with_user().await;
+    let credentials = (EMAIL, PASSWORD);
+    let all_day_ics = |dates: &str| format!(
+        "BEGIN:VCALENDAR\r\n\
+         VERSION:2.0\r\n\
+         PRODID:-//Tests//EN\r\n\
+         BEGIN:VEVENT\r\n\
+         UID:test-holiday-1\r\n\
+         {}\
+         SUMMARY:Offsite\r\n\
+         END:VEVENT\r\n\
+         END:VCALENDAR\r\n",
+        dates
+    );
+
+    let mkcol = r#"<?xml version="1.0" encoding="utf-8"?>
+<d:mkcol xmlns:d="DAV:"><d:set><d:prop><d:displayname>Team</d:displayname></d:prop></d:set></d:mkcol>"#;
+    let response = app.send(dav_request("MKCOL", "/calendars/new", credentials, None, mkcol)).await;
+    let calendar = response.header("location").expect("MKCOL returns the new calendar").to_string();
+
+    // Without DTEND, an all-day event takes up its day
+    let response = app.send(dav_request("PUT", &format!("{}test-holiday-1.ics", calendar), credentials, None, &all_day_ics("DTSTART;VALUE=DATE:20300107\r\n"))).await;
+    assert_eq!(response.status, StatusCode::CREATED, "{}", response.body);
+    let event = response.header("location").expect("PUT returns the stored resource").to_string();
+    let response = app.send(dav_request("GET", &event, credentials, None, "")).await;
+    assert!(response.body.contains("DTSTART;VALUE=DATE:20300107\r\n"), "{}", response.body);
+    assert!(response.body.contains("DTEND;VALUE=DATE:20300108\r\n"), "{}", response.body);
+
+    // DTEND is exclusive, so a three-day event ends the day after its last
+    let dates = "DTSTART;VALUE=DATE:20300107\r\nDTEND;VALUE=DATE:20300110\r\n";
+    let response = app.send(dav_request("PUT", &event, credentials, None, &all_day_ics(dates))).await;
+    assert!(response.status.is_success(), "{}", response.body);
+    let response = app.send(dav_request("GET", &event, credentials, None, "")).await;
+    assert!(response.body.contains(dates), "{}", response.body);
+}
+
+#[tokio::test]
+async fn sync_log_keeps_requests_per_client_when_turned_on() {
+    let app = app_with_user().await;
+    let token = app.login(EMAIL, PASSWORD).await.json()["token"].as_str().unwrap().to_string();
+    let propfind = |password: &str, client: &str| {
+        let mut request = dav_request("PROPFIND", "/calendars/", (EMAIL, password), Some("1"), PROPFIND_BODY);
+        request.headers_mut().insert("user-agent", client.parse().unwrap());
+        request
+    };
+    let page = |uri: &str| Request::builder()
+        .uri(uri)
+        .header("cookie", format!("auth_token={}", token))
+        .body(Body::empty())
+        .unwrap();
+    let set_sync_log = |enabled: bool| json_request(Method::PUT, "/api/auth/settings", Some(&token), json!({ "sync_log": enabled }));
+
+    // Nothing is kept until the user turns the log on
+    app.send(propfind(PASSWORD, "DAVx5/4.4")).await;
+    let response = app.send(page("/web/settings/sync-log")).await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert!(!response.body.contains("PROPFIND /calendars/"));
+
+    let response = app.send(set_sync_log(true)).await;
+    assert_eq!(response.json()["sync_log"], true);
+    assert_eq!(app.send(propfind(PASSWORD, "DAVx5/4.4")).await.status, StatusCode::MULTI_STATUS);
+    assert_eq!(app.send(propfind("changed-elsewhere", "iOS/17.4 dataaccessd/1.0")).await.status, StatusCode::UNAUTHORIZED);
+
+    let response = app.send(page("/web/settings/sync-log")).await;
+    assert_eq!(response.body.matches("PROPFIND /calendars/").count(), 2, "{}", response.body);
+    assert!(response.body.contains("207 Multi-Status"));
+    assert!(response.body.contains("401 Unauthorized"));
+    assert!(response.body.contains("iOS/17.4 dataaccessd/1.0"));
+
+    let response = app.send(page("/web/settings/sync-log?client=DAVx5%2F4.4")).await;
+    assert!(response.body.contains("Requests of DAVx5/4.4"));
+    assert_eq!(response.body.matches("PROPFIND /calendars/").count(), 1);
+
+    // Turning the log off deletes it
+    app.send(set_sync_log(false)).await;
+    app.send(set_sync_log(true)).await;
+    let response = app.send(page("/web/settings/sync-log")).await;
+    assert!(!response.body.contains("PROPFIND /calendars/"));
+}
+
+#[tokio::test]
+async fn revoked_devices_can_no_longer_sync() {
+    let app = app_with_user().await;
+    let token = app.login(EMAIL, PASSWORD).await.json()["token"].as_str().unwrap().to_string();
+    let propfind = |client: &str| {
+        let mut request = dav_request("PROPFIND", "/calendars/", (EMAIL, PASSWORD), Some("1"), PROPFIND_BODY);
+        request.headers_mut().insert("user-agent", client.parse().unwrap());
+        request
+    };
+
+    assert_eq!(app.send(propfind("DAVx5/4.4")).await.status, StatusCode::MULTI_STATUS);
+    assert_eq!(app.send(propfind("DAVx5/4.4")).await.status, StatusCode::MULTI_STATUS);
+    assert_eq!(app.send(propfind("Thunderbird/128.0")).await.status, StatusCode::MULTI_STATUS);
+
+    let response = app.send(authed_request(Method::GET, "/web/settings/devices", &token)).await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert!(response.body.contains("Thunderbird/128.0"));
+    let row = response.body.split("<tr>").find(|row| row.contains("DAVx5/4.4")).unwrap();
+    assert!(row.contains("Password"));
+    assert!(row.contains("<td>2</td>"), "{}", row);
+    let device = row.split("/web/settings/devices/").nth(1).unwrap().split('/').next().unwrap().to_string();
+
+    let response = app.send(authed_request(Method::POST, &format!("/web/settings/devices/{}/revoke", device), &token)).await;
+    assert_eq!(response.status, StatusCode::SEE_OTHER);
+    assert_eq!(app.send(propfind("DAVx5/4.4")).await.status, StatusCode::UNAUTHORIZED);
+    assert_eq!(app.send(propfind("Thunderbird/128.0")).await.status, StatusCode::MULTI_STATUS);
+    let response = app.send(authed_request(Method::GET, "/web/settings/devices", &token)).await;
+    assert!(response.body.contains("Revoked"));
+
+    // Removing the device lets it sync again
+    app.send(authed_request(Method::POST, &format!("/web/settings/devices/{}/remove", device), &token)).await;
+    assert_eq!(app.send(propfind("DAVx5/4.4")).await.status, StatusCode::MULTI_STATUS);
+}
+
+#[tokio::test]
+async fn unchanged_objects_and_calendars_are_answered_with_304() {
+    let app = app_with_user().await;
+    let credentials = (EMAIL, PASSWORD);
+    let conditional_get = |uri: &str, etag: &str| {
+        let mut request = dav_request("GET", uri, credentials, None, "");
+        request.headers_mut().insert("If-None-Match", etag.parse().unwrap());
+        request
+    };
+
+    let mkcol = r#"<?xml version="1.0" encoding="utf-8"?>
+<d:mkcol xmlns:d="DAV:"><d:set><d:prop><d:displayname>Polled</d:displayname></d:prop></d:set></d:mkcol>"#;
+    let response = app.send(dav_request("MKCOL", "/calendars/new", credentials, None, mkcol)).await;
+    let calendar = response.header("location").unwrap().to_string();
+    let response = app.send(dav_request("PUT", &format!("{}test-event-1.ics", calendar), credentials, None, &event_ics("Kickoff"))).await;
+    let event = response.header("location").unwrap().to_string();
+    let etag = response.header("etag").unwrap().to_string();
+
+    let response = app.send(dav_request("GET", &event, credentials, None, "")).await;
+    assert_eq!(response.header("etag"), Some(etag.as_str()));
+    let response = app.send(conditional_get(&event, &etag)).await;
+    assert_eq!(response.status, StatusCode::NOT_MODIFIED);
+    assert!(response.body.is_empty());
+
+    let response = app.send(dav_request("GET", &calendar, credentials, None, "")).await;
+    let calendar_etag = response.header("etag").expect("calendars have an ETag").to_string();
+    assert_eq!(app.send(conditional_get(&calendar, &calendar_etag)).await.status, StatusCode::NOT_MODIFIED);
+
+    // An edit changes both ETags, so the old ones fetch the new content
+    let response = app.send(dav_request("PUT", &event, credentials, None, &event_ics("Kickoff (moved)"))).await;
+    let new_etag = response.header("etag").unwrap().to_string();
+    assert_ne!(new_etag, etag);
+    let response = app.send(conditional_get(&event, &etag)).await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert!(response.body.contains("SUMMARY:Kickoff (moved)"));
+    let response = app.send(dav_request("REPORT", &calendar, credentials, Some("1"), CALENDAR_QUERY_BODY)).await;
+    assert!(response.body.contains(new_etag.trim_matches('"')));
+    let response = app.send(conditional_get(&calendar, &calendar_etag)).await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert!(response.body.contains("SUMMARY:Kickoff (moved)"));
+}
+
+#[tokio::test]
+async fn calendars_spanning_several_pages_are_reported_and_exported_whole() {
+    let app = app_with_user().await;
+    let token = app.login(EMAIL, PASSWORD).await.json()["token"].as_str().unwrap().to_string();
+    let credentials = (EMAIL, PASSWORD);
+    let calendar_id = app.create_calendar(&token, "Archive").await["id"].as_str().unwrap().to_string();
+
+    let count = 1100;
+    let mut ics = String::from("BEGIN:VCALENDAR\r\nVERSION:2.0\r\n");
+    for n in 0..count {
+        ics.push_str(&format!(
+            "BEGIN:VEVENT\r\nUID:archived-{n}\r\nDTSTART:20300101T090000Z\r\nDTEND:20300101T100000Z\r\nSUMMARY:Entry {n}\r\nEND:VEVENT\r\n"
+        ));
+    }
+    ics.push_str("END:VCALENDAR\r\n");
+    let request = Request::builder()
+        .method(Method::POST)
+        .uri(format!("/api/auth/calendars/{}/import", calendar_id))
+        .header("authorization", format!("Bearer {}", token))
+        .header("content-type", "text/calendar")
+        .body(Body::from(ics))
+        .unwrap();
+    let response = app.send(request).await;
+    assert_eq!(response.json()["events"]["imported"], count);
+
+    let calendar = format!("/calendars/{}/", calendar_id);
+    let response = app.send(dav_request("REPORT", &calendar, credentials, Some("1"), CALENDAR_QUERY_BODY)).await;
+    assert_eq!(response.status, StatusCode::MULTI_STATUS);
+    assert_eq!(response.body.matches("<d:response>").count(), count);
+    assert!(response.body.trim_end().ends_with("</d:multistatus>"));
+
+    let response = app.send(dav_request("GET", &calendar, credentials, None, "")).await;
+    assert_eq!(response.body.matches("BEGIN:VEVENT").count(), count);
+    assert!(response.body.ends_with("END:VCALENDAR\r\n"));
+    let response = app.send(authed_request(Method::GET, &format!("/api/calendars/{}/export", calendar_id), &token)).await;
+    assert_eq!(response.body.matches("BEGIN:VEVENT").count(), count);
+}
+
+#[tokio::test]
+async fn large_multistatus_responses_are_compressed_unless_turned_off() {
+    for compression in [true, false] {
+        let config = Config { compression, ..Config::default() };
+        let app = TestApp::with_config(config).await;
+        app.setup(ADMIN_EMAIL, ADMIN_PASSWORD).await;
+        let token = app.user_token(EMAIL, PASSWORD).await;
+        let credentials = (EMAIL, PASSWORD);
+        let calendar = format!("/calendars/{}/", app.create_calendar(&token, "Busy").await["id"].as_str().unwrap());
+        for n in 0..10 {
+            let body = event_ics(&format!("Meeting {}", n)).replace("test-event-1", &format!("event-{}", n));
+            let response = app.send(dav_request("PUT", &format!("{}event-{}.ics", calendar, n), credentials, None, &body)).await;
+            assert_eq!(response.status, StatusCode::CREATED);
+        }
+        let report = |encoding: Option<&str>| {
+            let mut request = dav_request("REPORT", &calendar, credentials, Some("1"), CALENDAR_QUERY_BODY);
+            if let Some(encoding) = encoding {
+                request.headers_mut().insert("accept-encoding", encoding.parse().unwrap());
+            }
+            request
+        };
+
+        let response = app.send(report(Some("gzip"))).await;
+        assert_eq!(response.status, StatusCode::MULTI_STATUS);
+        assert_eq!(response.header("content-encoding"), compression.then_some("gzip"));
+        let response = app.send(report(Some("deflate"))).await;
+        assert_eq!(response.header("content-encoding"), compression.then_some("deflate"));
+
+        // Clients that don't ask for it get plain XML
+        let response = app.send(report(None)).await;
+        assert_eq!(response.header("content-encoding"), None);
+        assert!(response.body.contains("SUMMARY:Meeting 9"));
+    }
+}
+
+#[tokio::test]
+async fn stale_writes_are_kept_as_conflicts_to_resolve() {
+    let app = app_with_user().await;
+    let token = app.login(EMAIL, PASSWORD).await.json()["token"].as_str().unwrap().to_string();
+    let calendar_id = app.create_calendar(&token, "Shared desk").await["id"].as_str().unwrap().to_string();
+    let object = format!("/calendars/{}/standup.ics", calendar_id);
+    let credentials = (EMAIL, PASSWORD);
+    let revision = |summary: &str, sequence: u32| {
+        event_ics(summary).replace("SUMMARY:", &format!("SEQUENCE:{}\r\nSUMMARY:", sequence))
+    };
+
+    let response = app.send(dav_request("PUT", &object, credentials, None, &revision("Standup", 2))).await;
+    assert_eq!(response.status, StatusCode::CREATED);
+    let etag = response.header("etag").unwrap().to_string();
+
+    // The phone saves first; the laptop still has the ETag both started from
+    let mut request = dav_request("PUT", &object, credentials, None, &revision("Standup (phone)", 2));
+    request.headers_mut().insert("If-Match", etag.parse().unwrap());
+    assert_eq!(app.send(request).await.status, StatusCode::NO_CONTENT);
+    let mut request = dav_request("PUT", &object, credentials, None, &revision("Standup (laptop)", 2));
+    request.headers_mut().insert("If-Match", etag.parse().unwrap());
+    assert_eq!(app.send(request).await.status, StatusCode::PRECONDITION_FAILED);
+
+    // A client without If-Match gives itself away by an older SEQUENCE, however often it retries
+    for _ in 0..2 {
+        let response = app.send(dav_request("PUT", &object, credentials, None, &revision("Standup (tablet)", 1))).await;
+        assert_eq!(response.status, StatusCode::CONFLICT, "{}", response.body);
+    }
+    let response = app.send(dav_request("GET", &object, credentials, None, "")).await;
+    assert!(response.body.contains("SUMMARY:Standup (phone)"), "{}", response.body);
+    assert!(response.body.contains("SEQUENCE:2"), "{}", response.body);
+
+    let events = app.send(authed_request(Method::GET, &format!("/api/auth/calendars/{}/events", calendar_id), &token)).await.json();
+    let event_id = events[0]["id"].as_str().unwrap().to_string();
+    let conflicts = app.send(authed_request(Method::GET, &format!("/api/auth/events/{}/edit-conflicts", event_id), &token)).await.json();
+    let conflicts = conflicts.as_array().unwrap();
+    assert_eq!(conflicts.len(), 2, "{:?}", conflicts);
+    assert_eq!(conflicts[0]["version"]["title"], "Standup (laptop)");
+    assert_eq!(conflicts[1]["version"]["title"], "Standup (tablet)");
+    assert_eq!(conflicts[0]["changes"], json!(["title"]));
+
+    // The edit form also notices when the event changed after it was opened
+    let response = app.send(Request::builder()
+        .method(Method::POST)
+        .uri(format!("/web/events/{}/edit", event_id))
+        .header("content-type", "application/x-www-form-urlencoded")
+        .header("cookie", format!("auth_token={}", token))
+        .body(Body::from(format!(
+            "title=Standup+%28browser%29&calendar_id={}&start_time=2030-01-01T09%3A00&end_time=2030-01-01T10%3A00&base_version=2000-01-01T00%3A00%3A00Z",
+            calendar_id
+        )))
+        .unwrap()).await;
+    assert_eq!(response.header("location"), Some(format!("/web/events/{}/edit-conflicts", event_id).as_str()));
+
+    let page = |uri: String| Request::builder()
+        .uri(uri)
+        .header("cookie", format!("auth_token={}", token))
+        .body(Body::empty())
+        .unwrap();
+    let response = app.send(page("/web/dashboard".to_string())).await;
+    assert!(response.body.contains(&format!("/web/events/{}/edit-conflicts", event_id)), "{}", response.body);
+    let response = app.send(page(format!("/web/events/{}/edit-conflicts", event_id))).await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert!(response.body.contains("Standup (browser)"), "{}", response.body);
+
+    let conflicts = app.send(authed_request(Method::GET, &format!("/api/auth/events/{}/edit-conflicts", event_id), &token)).await.json();
+    let ids: Vec<String> = conflicts.as_array().unwrap().iter().map(|c| c["id"].as_str().unwrap().to_string()).collect();
+    assert_eq!(ids.len(), 3);
+    let resolve = |conflict: &str, keep: &str| json_request(
+        Method::POST,
+        &format!("/api/auth/events/{}/edit-conflicts/{}/resolve", event_id, conflict),
+        Some(&token),
+        json!({ "keep": keep }),
+    );
+    let response = app.send(resolve(&ids[0], "conflict")).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+    assert_eq!(response.json()["title"], "Standup (laptop)");
+    for id in &ids[1..] {
+        assert_eq!(app.send(resolve(id, "current")).await.json()["title"], "Standup (laptop)");
+    }
+    let conflicts = app.send(authed_request(Method::GET, &format!("/api/auth/events/{}/edit-conflicts", event_id), &token)).await.json();
+    assert_eq!(conflicts, json!([]));
+
+    // Applying the conflict is a change like any other, so the phone's version can come back
+    let history = app.send(authed_request(Method::GET, &format!("/api/auth/events/{}/history", event_id), &token)).await.json();
+    assert_eq!(history[0]["event"]["title"], "Standup (phone)");
+}
+
+#[tokio::test]
+async fn series_with_huge_intervals_are_rejected_or_expanded_safely() {
+    let app = app_with_user().await;
+    let token = app.login(EMAIL, PASSWORD).await.json()["token"].as_str().unwrap().to_string();
+    let calendar_id = app.create_calendar(&token, "Far apart").await["id"].as_str().unwrap().to_string();
+
+    // The API refuses such rules outright
+    let response = app.send(json_request(Method::POST, "/api/auth/events", Some(&token), json!({
+        "calendar_id": calendar_id,
+        "event": {
+            "title": "Every eleven million years",
+            "description": null,
+            "location": null,
+            "start_time": "2030-01-01T09:00:00Z",
+            "end_time": "2030-01-01T10:00:00Z",
+            "is_all_day": false,
+            "rrule": "FREQ=DAILY;INTERVAL=4000000000",
+            "geo_lat": null,
+            "geo_lon": null,
+        },
+    }))).await;
+    assert_eq!(response.status, StatusCode::BAD_REQUEST);
+    assert_eq!(response.json()["field"], "rrule");
+
+    // CalDAV clients are taken at their word, so listings must cope with the rule; the
+    // series lies in the past, so its second instance is worked out and overflows
+    let object = format!("/calendars/{}/far-apart.ics", calendar_id);
+    for freq in ["DAILY", "WEEKLY"] {
+        let ics = event_ics("Far apart")
+            .replace("2030", "2020")
+            .replace("SUMMARY:", &format!("RRULE:FREQ={};INTERVAL=4000000000\r\nSUMMARY:", freq));
+        let response = app.send(dav_request("PUT", &object, (EMAIL, PASSWORD), None, &ics)).await;
+        assert!(response.status.is_success(), "{}", response.body);
+
+        let response = app.send(authed_request(Method::GET, "/api/auth/calendars?include_events=true", &token)).await;
+        assert_eq!(response.status, StatusCode::OK);
+        let response = app.send(Request::builder()
+            .uri("/web/dashboard")
+            .header("cookie", format!("auth_token={}", token))
+            .body(Body::empty())
+            .unwrap()).await;
+        assert_eq!(response.status, StatusCode::OK);
+    }
+}
+
+#[tokio::test]
+async fn basic_auth_requests_are_rate_limited_by_address() {
+    let config = Config {
+        rate_limit_requests: 2,
+        ..Config::default()
+    };
+    let app = TestApp::with_config(config).await;
+    app.setup(ADMIN_EMAIL, ADMIN_PASSWORD).await;
+    let token = app.user_token(EMAIL, PASSWORD).await;
+    let calendar = format!("/calendars/{}", app.create_calendar(&token, "Busy").await["id"].as_str().unwrap());
+    let report = |credentials: (&str, &str), address: &str| {
+        let mut request = dav_request("REPORT", &calendar, credentials, Some("1"), CALENDAR_QUERY_BODY);
+        request.extensions_mut().insert(ConnectInfo(address.parse::<SocketAddr>().unwrap()));
+        app.send(request)
+    };
+
+    // Naming someone else's account uses up the sender's budget, not theirs
+    for _ in 0..2 {
+        assert_eq!(report((EMAIL, "guess"), "203.0.113.9:4000").await.status, StatusCode::UNAUTHORIZED);
+    }
+    assert_eq!(report((EMAIL, PASSWORD), "198.51.100.7:4000").await.status, StatusCode::MULTI_STATUS);
+
+    // Nor does switching accounts buy more requests
+    let response = report(("someone@example.com", "guess"), "203.0.113.9:4001").await;
+    assert_eq!(response.status, StatusCode::TOO_MANY_REQUESTS);
+}
+
+#[tokio::test]
+async fn push_subscriptions_only_reach_public_https_hosts() {
+    let app = app_with_user().await;
+    let token = app.login(EMAIL, PASSWORD).await.json()["token"].as_str().unwrap().to_string();
+    let calendar = format!("/calendars/{}", app.create_calendar(&token, "Watched").await["id"].as_str().unwrap());
+    let register = |resource: &str| dav_request("POST", &calendar, (EMAIL, PASSWORD), None, &format!(
+        r#"<P:push-register xmlns:P="https://bitfire.at/webdav-push"><P:subscription><P:web-push-subscription><P:push-resource>{}</P:push-resource></P:web-push-subscription></P:subscription></P:push-register>"#,
+        resource
+    ));
+
+    for internal in [
+        "http://93.184.215.14/push",
+        "https://127.0.0.1/push",
+        "https://localhost:8080/push",
+        "https://10.0.0.5/push",
+        "https://169.254.169.254/latest/meta-data",
+        "https://[::1]/push",
+        "https://[::ffff:192.168.1.1]/push",
+    ] {
+        let response = app.send(register(internal)).await;
+        assert_eq!(response.status, StatusCode::BAD_REQUEST, "{}: {}", internal, response.body);
+    }
+
+    let response = app.send(register("https://93.184.215.14/push")).await;
+    assert_eq!(response.status, StatusCode::CREATED, "{}", response.body);
+}
+
+#[tokio::test]
+async fn mkcalendar_creates_a_calendar_named_by_its_displayname() {
+    let app = app_with_user().await;
+    let credentials = (EMAIL, PASSWORD);
+    let body = r#"<?xml version="1.0" encoding="utf-8"?>
+<c:mkcalendar xmlns:d="DAV:" xmlns:c="urn:ietf:params:xml:ns:caldav">
+    <d:set><d:prop><d:displayname>Holidays</d:displayname></d:prop></d:set>
+</c:mkcalendar>"#;
+
+    let response = app.send(dav_request("MKCALENDAR", "/calendars/new", credentials, None, body)).await;
+    assert_eq!(response.status, StatusCode::CREATED);
+    let calendar = response.header("location").unwrap().to_string();
+    let response = app.send(dav_request("PROPFIND", &calendar, credentials, Some("0"), PROPFIND_BODY)).await;
+    assert!(response.body.contains("<d:displayname>Holidays</d:displayname>"), "{}", response.body);
+
+    // Without a body the calendar gets a placeholder name
+    let response = app.send(dav_request("MKCOL", "/calendars/new", credentials, None, "")).await;
+    assert_eq!(response.status, StatusCode::CREATED);
+    let calendar = response.header("location").unwrap().to_string();
+    let response = app.send(dav_request("PROPFIND", &calendar, credentials, Some("0"), PROPFIND_BODY)).await;
+    assert!(response.body.contains("<d:displayname>New Calendar</d:displayname>"), "{}", response.body);
+}
+
+#[tokio::test]
+async fn proppatch_applies_all_properties_or_none() {
+    let app = app_with_user().await;
+    let token = app.login(EMAIL, PASSWORD).await.json()["token"].as_str().unwrap().to_string();
+    let calendar_id = app.create_calendar(&token, "Work").await["id"].as_str().unwrap().to_string();
+    let calendar = format!("/calendars/{}/", calendar_id);
+    let proppatch = |name: &str, color: &str| format!(
+        r#"<?xml version="1.0" encoding="utf-8"?>
+<d:propertyupdate xmlns:d="DAV:" xmlns:a="http://apple.com/ns/ical/">
+    <d:set><d:prop><d:displayname>{}</d:displayname><a:calendar-color>{}</a:calendar-color></d:prop></d:set>
+</d:propertyupdate>"#,
+        name, color
+    );
+    let stored = || async {
+        let calendars = app.send(authed_request(Method::GET, "/api/auth/calendars", &token)).await.json();
+        calendars.as_array().unwrap().iter().find(|c| c["id"] == calendar_id.as_str()).unwrap().clone()
+    };
+
+    let response = app.send(dav_request("PROPPATCH", &calendar, (EMAIL, PASSWORD), None, &proppatch("Office", "#10b981FF"))).await;
+    assert_eq!(response.status, StatusCode::MULTI_STATUS);
+    assert!(response.body.contains("200 OK"), "{}", response.body);
+    assert_eq!(stored().await["name"], "Office");
+    assert_eq!(stored().await["color"], "#10B981");
+
+    // One bad color fails the name change along with it
+    let response = app.send(dav_request("PROPPATCH", &calendar, (EMAIL, PASSWORD), None, &proppatch("Desk", "green"))).await;
+    assert_eq!(response.status, StatusCode::MULTI_STATUS);
+    assert!(response.body.contains("409 Conflict"), "{}", response.body);
+    assert!(response.body.contains("424 Failed Dependency"), "{}", response.body);
+    assert_eq!(stored().await["name"], "Office");
+}
+
+#[tokio::test]
+async fn deleting_a_calendar_takes_its_events_along() {
+    let app = app_with_user().await;
+    let credentials = (EMAIL, PASSWORD);
+    let token = app.login(EMAIL, PASSWORD).await.json()["token"].as_str().unwrap().to_string();
+    let calendar_id = app.create_calendar(&token, "Doomed").await["id"].as_str().unwrap().to_string();
+    let calendar = format!("/calendars/{}/", calendar_id);
+    let object = format!("{}standup.ics", calendar);
+    let response = app.send(dav_request("PUT", &object, credentials, None, &event_ics("Standup"))).await;
+    assert_eq!(response.status, StatusCode::CREATED);
+
+    let response = app.send(dav_request("DELETE", &format!("{}missing.ics", calendar), credentials, None, "")).await;
+    assert_eq!(response.status, StatusCode::NOT_FOUND);
+
+    // Writing to a shared calendar doesn't extend to deleting it
+    let response = app.send(json_request(Method::POST, &format!("/api/auth/calendars/{}/shares", calendar_id), Some(&token), json!({
+        "shared_with_email": ADMIN_EMAIL,
+        "permission": "write",
+    }))).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+    let response = app.send(dav_request("DELETE", &calendar, (ADMIN_EMAIL, ADMIN_PASSWORD), None, "")).await;
+    assert_eq!(response.status, StatusCode::UNAUTHORIZED);
+
+    let response = app.send(dav_request("DELETE", &calendar, credentials, None, "")).await;
+    assert_eq!(response.status, StatusCode::NO_CONTENT);
+    let response = app.send(dav_request("GET", &object, credentials, None, "")).await;
+    assert_eq!(response.status, StatusCode::NOT_FOUND);
+    let response = app.send(dav_request("PROPFIND", &calendar, credentials, Some("0"), PROPFIND_BODY)).await;
+    assert_eq!(response.status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn copy_and_move_place_objects_in_another_calendar() {
+    let app = app_with_user().await;
+    let credentials = (EMAIL, PASSWORD);
+    let token = app.login(EMAIL, PASSWORD).await.json()["token"].as_str().unwrap().to_string();
+    let home = format!("/calendars/{}/", app.create_calendar(&token, "Home").await["id"].as_str().unwrap());
+    let work = format!("/calendars/{}/", app.create_calendar(&token, "Work").await["id"].as_str().unwrap());
+    let object = format!("{}standup.ics", home);
+    app.send(dav_request("PUT", &object, credentials, None, &event_ics("Standup"))).await;
+    let transfer = |method: &str, destination: String| {
+        let mut request = dav_request(method, &object, credentials, None, "");
+        request.headers_mut().insert("Destination", destination.parse().unwrap());
+        app.send(request)
+    };
+
+    // A copy is a separate event with a UID of its own
+    let response = transfer("COPY", format!("{}copy.ics", work)).await;
+    assert_eq!(response.status, StatusCode::CREATED);
+    assert!(response.header("location").unwrap().ends_with(&format!("{}copy.ics", work)));
+    assert!(response.header("etag").is_some());
+    let copy = app.send(dav_request("GET", &format!("{}copy.ics", work), credentials, None, "")).await;
+    assert!(copy.body.contains("SUMMARY:Standup"), "{}", copy.body);
+    assert!(!copy.body.contains("UID:test-event-1"), "{}", copy.body);
+    assert_eq!(app.send(dav_request("GET", &object, credentials, None, "")).await.status, StatusCode::OK);
+
+    // A moved event keeps its UID and leaves its old calendar
+    let response = transfer("MOVE", format!("{}moved.ics", work)).await;
+    assert_eq!(response.status, StatusCode::CREATED);
+    let moved = app.send(dav_request("GET", &format!("{}moved.ics", work), credentials, None, "")).await;
+    assert!(moved.body.contains("UID:test-event-1"), "{}", moved.body);
+    assert_eq!(app.send(dav_request("GET", &object, credentials, None, "")).await.status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn copy_and_move_honor_overwrite_uids_and_access() {
+    let app = app_with_user().await;
+    let credentials = (EMAIL, PASSWORD);
+    let token = app.login(EMAIL, PASSWORD).await.json()["token"].as_str().unwrap().to_string();
+    let home = format!("/calendars/{}/", app.create_calendar(&token, "Home").await["id"].as_str().unwrap());
+    let work = format!("/calendars/{}/", app.create_calendar(&token, "Work").await["id"].as_str().unwrap());
+    let object = format!("{}standup.ics", home);
+    app.send(dav_request("PUT", &object, credentials, None, &event_ics("Standup"))).await;
+    let transfer = |method: &str, credentials: (&str, &str), source: &str, destination: String, overwrite: Option<&str>| {
+        let mut request = dav_request(method, source, credentials, None, "");
+        request.headers_mut().insert("Destination", destination.parse().unwrap());
+        if let Some(overwrite) = overwrite {
+            request.headers_mut().insert("Overwrite", overwrite.parse().unwrap());
+        }
+        app.send(request)
+    };
+
+    let response = transfer("COPY", credentials, &object, object.clone(), None).await;
+    assert_eq!(response.status, StatusCode::FORBIDDEN);
+
+    // Overwrite: F keeps an existing destination
+    let other = event_ics("Retro").replace("UID:test-event-1", "UID:test-event-2");
+    app.send(dav_request("PUT", &format!("{}retro.ics", work), credentials, None, &other)).await;
+    let response = transfer("COPY", credentials, &object, format!("{}retro.ics", work), Some("F")).await;
+    assert_eq!(response.status, StatusCode::PRECONDITION_FAILED);
+    let response = app.send(dav_request("GET", &format!("{}retro.ics", work), credentials, None, "")).await;
+    assert!(response.body.contains("SUMMARY:Retro"), "{}", response.body);
+    let response = transfer("COPY", credentials, &object, format!("{}retro.ics", work), Some("T")).await;
+    assert_eq!(response.status, StatusCode::NO_CONTENT);
+
+    // A move can't bring a UID into a calendar where another resource already has it
+    app.send(dav_request("PUT", &format!("{}standup.ics", work), credentials, None, &event_ics("Standup (work)"))).await;
+    let response = transfer("MOVE", credentials, &object, format!("{}elsewhere.ics", work), None).await;
+    assert_eq!(response.status, StatusCode::CONFLICT);
+    assert!(response.body.contains("<cal:no-uid-conflict>"), "{}", response.body);
+    assert!(response.body.contains(&format!("{}standup.ics", work)), "{}", response.body);
+    assert_eq!(app.send(dav_request("GET", &object, credentials, None, "")).await.status, StatusCode::OK);
+
+    // Reading a calendar is enough to copy out of it, but moving out needs write access
+    let admin = app.login(ADMIN_EMAIL, ADMIN_PASSWORD).await.json()["token"].as_str().unwrap().to_string();
+    let team_id = app.create_calendar(&admin, "Team").await["id"].as_str().unwrap().to_string();
+    let team_object = format!("/calendars/{}/planning.ics", team_id);
+    let planning = event_ics("Planning").replace("UID:test-event-1", "UID:team-planning");
+    app.send(dav_request("PUT", &team_object, (ADMIN_EMAIL, ADMIN_PASSWORD), None, &planning)).await;
+    let response = app.send(json_request(Method::POST, &format!("/api/auth/calendars/{}/shares", team_id), Some(&admin), json!({
+        "shared_with_email": EMAIL,
+        "permission": "read",
+    }))).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+
+    let response = transfer("MOVE", credentials, &team_object, format!("{}planning.ics", home), None).await;
+    assert_eq!(response.status, StatusCode::UNAUTHORIZED);
+    let response = transfer("COPY", credentials, &team_object, format!("{}planning.ics", home), None).await;
+    assert_eq!(response.status, StatusCode::CREATED);
+    let response = app.send(dav_request("GET", &team_object, (ADMIN_EMAIL, ADMIN_PASSWORD), None, "")).await;
+    assert_eq!(response.status, StatusCode::OK);
+}
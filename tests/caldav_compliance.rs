@@ -0,0 +1,381 @@
+//! Protocol compliance checks: the request sequences DAVx5 and Apple Calendar send,
+//! with the exact status codes and XML elements those clients depend on. A failure
+//! here usually means a client stops syncing, even if the web UI still works.
+
+mod common;
+
+use axum::body::Body;
+use axum::http::{header, HeaderValue, Method, Request, StatusCode};
+use common::{authed_request, dav_request, json_request, TestApp, TestResponse};
+use serde_json::json;
+
+const EMAIL: &str = "dave@example.com";
+const PASSWORD: &str = "secret123";
+const CREDENTIALS: (&str, &str) = (EMAIL, PASSWORD);
+
+/// Collection properties DAVx5 asks for when it refreshes the calendar list
+const PROPFIND_COLLECTIONS: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<d:propfind xmlns:d="DAV:" xmlns:c="urn:ietf:params:xml:ns:caldav" xmlns:cs="http://calendarserver.org/ns/" xmlns:a="http://apple.com/ns/ical/">
+    <d:prop>
+        <d:resourcetype/>
+        <d:displayname/>
+        <d:current-user-privilege-set/>
+        <c:supported-calendar-component-set/>
+        <cs:getctag/>
+        <a:calendar-color/>
+        <d:quota-available-bytes/>
+    </d:prop>
+</d:propfind>"#;
+
+/// Member listing Apple Calendar uses to compare ETags
+const PROPFIND_ETAGS: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<d:propfind xmlns:d="DAV:"><d:prop><d:getetag/><d:resourcetype/></d:prop></d:propfind>"#;
+
+const PROPFIND_CTAG: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<d:propfind xmlns:d="DAV:" xmlns:cs="http://calendarserver.org/ns/"><d:prop><cs:getctag/></d:prop></d:propfind>"#;
+
+const CALENDAR_QUERY: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<c:calendar-query xmlns:d="DAV:" xmlns:c="urn:ietf:params:xml:ns:caldav">
+    <d:prop><d:getetag/><c:calendar-data/></d:prop>
+    <c:filter><c:comp-filter name="VCALENDAR"><c:comp-filter name="VEVENT"/></c:comp-filter></c:filter>
+</c:calendar-query>"#;
+
+const MKCALENDAR: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<c:mkcalendar xmlns:d="DAV:" xmlns:c="urn:ietf:params:xml:ns:caldav">
+    <d:set><d:prop><d:displayname>Compliance</d:displayname></d:prop></d:set>
+</c:mkcalendar>"#;
+
+fn multiget(hrefs: &[&str]) -> String {
+    let hrefs: String = hrefs.iter().map(|h| format!("<d:href>{}</d:href>", h)).collect();
+    format!(
+        r#"<?xml version="1.0" encoding="utf-8"?>
+<c:calendar-multiget xmlns:d="DAV:" xmlns:c="urn:ietf:params:xml:ns:caldav">
+    <d:prop><d:getetag/><c:calendar-data/></d:prop>
+    {}
+</c:calendar-multiget>"#,
+        hrefs
+    )
+}
+
+fn event_ics(uid: &str, summary: &str) -> String {
+    format!(
+        "BEGIN:VCALENDAR\r\n\
+         VERSION:2.0\r\n\
+         PRODID:-//Compliance//EN\r\n\
+         BEGIN:VEVENT\r\n\
+         UID:{}\r\n\
+         DTSTAMP:20300101T000000Z\r\n\
+         DTSTART:20300105T090000Z\r\n\
+         DTEND:20300105T100000Z\r\n\
+         SUMMARY:{}\r\n\
+         END:VEVENT\r\n\
+         END:VCALENDAR\r\n",
+        uid, summary
+    )
+}
+
+/// Add a precondition header such as `If-Match` to a request
+fn with_header(mut request: Request<Body>, name: header::HeaderName, value: &str) -> Request<Body> {
+    request.headers_mut().insert(name, HeaderValue::from_str(value).unwrap());
+    request
+}
+
+/// The `response` element of a multistatus for `href`
+fn response_for<'a>(body: &'a str, href: &str) -> Option<&'a str> {
+    body.split("<d:response>")
+        .skip(1)
+        .find(|r| r.contains(&format!("<d:href>{}</d:href>", href)))
+}
+
+/// Unescaped text of the first `<tag>…</tag>` in `xml`
+fn element_text(xml: &str, tag: &str) -> Option<String> {
+    let open = format!("<{}>", tag);
+    let start = xml.find(&open)? + open.len();
+    let end = xml[start..].find(&format!("</{}>", tag))? + start;
+    Some(
+        xml[start..end]
+            .replace("&quot;", "\"")
+            .replace("&apos;", "'")
+            .replace("&lt;", "<")
+            .replace("&gt;", ">")
+            .replace("&amp;", "&"),
+    )
+}
+
+fn assert_multistatus(response: &TestResponse) {
+    assert_eq!(response.status, StatusCode::MULTI_STATUS, "{}", response.body);
+    assert!(
+        response.header("content-type").is_some_and(|c| c.starts_with("application/xml")),
+        "multistatus must be XML, got {:?}",
+        response.header("content-type"),
+    );
+    assert!(response.body.starts_with("<?xml"));
+    assert!(response.body.contains(r#"<d:multistatus xmlns:d="DAV:""#));
+    assert!(response.body.contains(r#"xmlns:cal="urn:ietf:params:xml:ns:caldav""#));
+}
+
+async fn ctag(app: &TestApp, calendar: &str) -> String {
+    let response = app.send(dav_request("PROPFIND", calendar, CREDENTIALS, Some("0"), PROPFIND_CTAG)).await;
+    assert_multistatus(&response);
+    element_text(&response.body, "cs:getctag").expect("calendar has a getctag")
+}
+
+async fn app_with_user() -> TestApp {
+    let app = TestApp::with_admin().await;
+    let response = app.register("Dave", EMAIL, PASSWORD).await;
+    assert_eq!(response.status, StatusCode::OK);
+    app
+}
+
+#[tokio::test]
+async fn discovery_redirects_for_every_method() {
+    let app = TestApp::new().await;
+
+    for method in ["GET", "PROPFIND", "OPTIONS"] {
+        let request = Request::builder()
+            .method(method)
+            .uri("/.well-known/caldav")
+            .body(Body::empty())
+            .unwrap();
+        let response = app.send(request).await;
+        assert_eq!(response.status, StatusCode::MOVED_PERMANENTLY, "{} /.well-known/caldav", method);
+        assert_eq!(response.header("location"), Some("/calendars/"));
+    }
+}
+
+#[tokio::test]
+async fn options_advertise_calendar_access() {
+    let app = app_with_user().await;
+
+    let response = app.send(dav_request("OPTIONS", "/calendars/", CREDENTIALS, None, "")).await;
+    assert_eq!(response.status, StatusCode::OK);
+    let dav = response.header("dav").expect("DAV header");
+    assert!(dav.split(", ").any(|c| c == "1"));
+    assert!(dav.split(", ").any(|c| c == "calendar-access"));
+    let allow = response.header("allow").expect("Allow header");
+    for method in ["PROPFIND", "REPORT"] {
+        assert!(allow.contains(method), "root must allow {}", method);
+    }
+
+    let response = app.send(dav_request("OPTIONS", "/calendars/new", CREDENTIALS, None, "")).await;
+    assert!(response.header("allow").is_some_and(|a| a.contains("MKCALENDAR")));
+}
+
+#[tokio::test]
+async fn unauthenticated_requests_get_401() {
+    let app = app_with_user().await;
+
+    let request = Request::builder()
+        .method("PROPFIND")
+        .uri("/calendars/")
+        .header("Depth", "1")
+        .body(Body::from(PROPFIND_COLLECTIONS))
+        .unwrap();
+    let response = app.send(request).await;
+    assert_eq!(response.status, StatusCode::UNAUTHORIZED);
+
+    let response = app.send(dav_request("PROPFIND", "/calendars/", (EMAIL, "wrong"), Some("1"), PROPFIND_COLLECTIONS)).await;
+    assert_eq!(response.status, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn calendar_list_has_the_properties_clients_require() {
+    let app = app_with_user().await;
+
+    let response = app.send(dav_request("PROPFIND", "/calendars/", CREDENTIALS, Some("1"), PROPFIND_COLLECTIONS)).await;
+    assert_multistatus(&response);
+
+    let responses: Vec<&str> = response.body.split("<d:response>").skip(1).collect();
+    assert!(!responses.is_empty(), "new accounts have calendars");
+    for calendar in responses {
+        assert!(calendar.contains("<d:resourcetype><d:collection/><cal:calendar/></d:resourcetype>"));
+        assert!(element_text(calendar, "d:displayname").is_some_and(|n| !n.is_empty()));
+        assert!(calendar.contains(r#"<cal:comp name="VEVENT"/>"#));
+        assert!(element_text(calendar, "cs:getctag").is_some());
+        assert!(calendar.contains("<d:privilege><d:write/></d:privilege>"), "own calendars are writable");
+        assert!(calendar.contains("<d:status>HTTP/1.1 200 OK</d:status>"));
+        // Unknown properties are reported in a 404 propstat, not dropped
+        assert!(calendar.contains("<d:quota-available-bytes/>"));
+        assert!(calendar.contains("<d:status>HTTP/1.1 404 Not Found</d:status>"));
+    }
+}
+
+#[tokio::test]
+async fn create_sync_and_delete_an_event() {
+    let app = app_with_user().await;
+
+    // Apple Calendar creates calendars with MKCALENDAR
+    let response = app.send(dav_request("MKCALENDAR", "/calendars/new", CREDENTIALS, None, MKCALENDAR)).await;
+    assert_eq!(response.status, StatusCode::CREATED);
+    let calendar = response.header("location").expect("MKCALENDAR returns a Location").to_string();
+    assert!(calendar.ends_with('/'));
+
+    let response = app.send(dav_request("PROPFIND", &calendar, CREDENTIALS, Some("0"), PROPFIND_COLLECTIONS)).await;
+    assert_multistatus(&response);
+    assert_eq!(element_text(&response.body, "d:displayname").as_deref(), Some("Compliance"));
+    let initial_ctag = ctag(&app, &calendar).await;
+
+    // Create without overwriting: If-None-Match: *
+    let put = dav_request("PUT", &format!("{}compliance-1.ics", calendar), CREDENTIALS, None, &event_ics("compliance-1", "Standup"));
+    let response = app.send(with_header(put, header::IF_NONE_MATCH, "*")).await;
+    assert_eq!(response.status, StatusCode::CREATED);
+    let event = response.header("location").expect("PUT returns the resource URL").to_string();
+    let etag = response.header("etag").expect("PUT returns an ETag").to_string();
+    assert!(etag.starts_with('"') && etag.ends_with('"'), "ETags are quoted: {}", etag);
+
+    let put = dav_request("PUT", &event, CREDENTIALS, None, &event_ics("compliance-1", "Standup"));
+    let response = app.send(with_header(put, header::IF_NONE_MATCH, "*")).await;
+    assert_eq!(response.status, StatusCode::PRECONDITION_FAILED, "If-None-Match: * must not overwrite");
+
+    // The change is visible through the ctag and the member ETags
+    let synced_ctag = ctag(&app, &calendar).await;
+    assert_ne!(initial_ctag, synced_ctag);
+
+    let response = app.send(dav_request("PROPFIND", &calendar, CREDENTIALS, Some("1"), PROPFIND_ETAGS)).await;
+    assert_multistatus(&response);
+    let member = response_for(&response.body, &event).expect("event is listed at Depth 1");
+    assert_eq!(element_text(member, "d:getetag").as_deref(), Some(etag.as_str()));
+    assert!(member.contains("<d:resourcetype/>"));
+
+    // DAVx5 fetches changed members with calendar-multiget
+    let response = app.send(dav_request("REPORT", &calendar, CREDENTIALS, Some("1"), &multiget(&[&event]))).await;
+    assert_multistatus(&response);
+    let member = response_for(&response.body, &event).expect("multiget returns the requested href");
+    assert_eq!(element_text(member, "d:getetag").as_deref(), Some(etag.as_str()));
+    let data = element_text(member, "cal:calendar-data").expect("calendar-data is returned");
+    assert!(data.contains("BEGIN:VCALENDAR") && data.contains("SUMMARY:Standup"));
+    assert_eq!(response.body.matches("<d:response>").count(), 1, "multiget returns only the requested hrefs");
+
+    // Update only what the client last saw: If-Match
+    let put = dav_request("PUT", &event, CREDENTIALS, None, &event_ics("compliance-1", "Standup (moved)"));
+    let response = app.send(with_header(put, header::IF_MATCH, "\"not-the-etag\"")).await;
+    assert_eq!(response.status, StatusCode::PRECONDITION_FAILED);
+
+    let put = dav_request("PUT", &event, CREDENTIALS, None, &event_ics("compliance-1", "Standup (moved)"));
+    let response = app.send(with_header(put, header::IF_MATCH, &etag)).await;
+    assert_eq!(response.status, StatusCode::NO_CONTENT);
+    assert!(response.header("etag").is_some());
+
+    let put = dav_request("PUT", &format!("{}missing.ics", calendar), CREDENTIALS, None, &event_ics("missing", "Missing"));
+    let response = app.send(with_header(put, header::IF_MATCH, &etag)).await;
+    assert_eq!(response.status, StatusCode::PRECONDITION_FAILED, "If-Match requires an existing resource");
+
+    let response = app.send(dav_request("REPORT", &calendar, CREDENTIALS, Some("1"), CALENDAR_QUERY)).await;
+    assert_multistatus(&response);
+    assert!(response.body.contains("SUMMARY:Standup (moved)"));
+    assert_ne!(ctag(&app, &calendar).await, synced_ctag);
+
+    let response = app.send(dav_request("GET", &event, CREDENTIALS, None, "")).await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert!(response.header("content-type").is_some_and(|c| c.starts_with("text/calendar")));
+    assert!(response.header("etag").is_some());
+
+    // Delete, and the resource is gone from every listing
+    let before_delete = ctag(&app, &calendar).await;
+    let response = app.send(dav_request("DELETE", &event, CREDENTIALS, None, "")).await;
+    assert_eq!(response.status, StatusCode::NO_CONTENT);
+
+    let response = app.send(dav_request("GET", &event, CREDENTIALS, None, "")).await;
+    assert_eq!(response.status, StatusCode::NOT_FOUND);
+
+    let response = app.send(dav_request("REPORT", &calendar, CREDENTIALS, Some("1"), CALENDAR_QUERY)).await;
+    assert_multistatus(&response);
+    assert!(response_for(&response.body, &event).is_none());
+    assert_ne!(ctag(&app, &calendar).await, before_delete);
+}
+
+#[tokio::test]
+async fn unsupported_methods_get_405_or_501() {
+    let app = app_with_user().await;
+
+    for (method, status) in [("LOCK", StatusCode::METHOD_NOT_ALLOWED), ("ACL", StatusCode::METHOD_NOT_ALLOWED), ("BREW", StatusCode::NOT_IMPLEMENTED)] {
+        let response = app.send(dav_request(method, "/calendars/", CREDENTIALS, None, "")).await;
+        assert_eq!(response.status, status, "{} on the calendar home", method);
+        assert!(response.header("allow").is_some_and(|a| a.contains("PROPFIND")));
+    }
+}
+
+#[tokio::test]
+async fn malformed_bodies_are_rejected() {
+    let app = app_with_user().await;
+
+    let response = app.send(dav_request("PROPFIND", "/calendars/", CREDENTIALS, Some("1"), "<d:propfind xmlns:d=\"DAV:\">")).await;
+    assert_eq!(response.status, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn resync_changes_every_etag_and_the_ctag() {
+    let app = app_with_user().await;
+    let token = app.login(EMAIL, PASSWORD).await.json()["token"].as_str().unwrap().to_string();
+    let calendar_id = app.create_calendar(&token, "Repaired").await["id"].as_str().unwrap().to_string();
+    let calendar = format!("/calendars/{}/", calendar_id);
+
+    let response = app.send(dav_request("PUT", &format!("{}repair-1.ics", calendar), CREDENTIALS, None, &event_ics("repair-1", "Repaired"))).await;
+    assert_eq!(response.status, StatusCode::CREATED);
+    let event = response.header("location").unwrap().to_string();
+    let etag = response.header("etag").unwrap().to_string();
+    let before = ctag(&app, &calendar).await;
+
+    let response = app.send(authed_request(Method::POST, &format!("/api/auth/calendars/{}/resync", calendar_id), &token)).await;
+    assert_eq!(response.status, StatusCode::OK);
+
+    assert_ne!(ctag(&app, &calendar).await, before);
+    let response = app.send(dav_request("PROPFIND", &calendar, CREDENTIALS, Some("1"), PROPFIND_ETAGS)).await;
+    let member = response_for(&response.body, &event).expect("event is still listed");
+    let new_etag = element_text(member, "d:getetag").unwrap();
+    assert_ne!(new_etag, etag);
+
+    // The new ETag is the one preconditions are checked against
+    let put = dav_request("PUT", &event, CREDENTIALS, None, &event_ics("repair-1", "Repaired again"));
+    let response = app.send(with_header(put, header::IF_MATCH, &etag)).await;
+    assert_eq!(response.status, StatusCode::PRECONDITION_FAILED);
+    let put = dav_request("PUT", &event, CREDENTIALS, None, &event_ics("repair-1", "Repaired again"));
+    let response = app.send(with_header(put, header::IF_MATCH, &new_etag)).await;
+    assert_eq!(response.status, StatusCode::NO_CONTENT);
+}
+
+#[tokio::test]
+async fn principal_reports_used_during_account_setup() {
+    let app = app_with_user().await;
+    let token = app.login(EMAIL, PASSWORD).await.json()["token"].as_str().unwrap().to_string();
+    let response = app.send(json_request(Method::POST, "/api/auth/groups", Some(&token), json!({ "name": "Book Club" }))).await;
+    let group = format!("/principals/groups/{}/", response.json()["id"].as_str().unwrap());
+
+    // Apple Calendar expands the principal's groups and calendar home in one request
+    let expand = r#"<?xml version="1.0" encoding="utf-8"?>
+<d:expand-property xmlns:d="DAV:">
+    <d:property name="displayname"/>
+    <d:property name="calendar-home-set" namespace="urn:ietf:params:xml:ns:caldav"/>
+    <d:property name="group-membership"><d:property name="displayname"/></d:property>
+    <d:property name="calendar-proxy-write-for" namespace="http://calendarserver.org/ns/"><d:property name="displayname"/></d:property>
+</d:expand-property>"#;
+    let response = app.send(dav_request("REPORT", "/calendars/", CREDENTIALS, Some("0"), expand)).await;
+    assert_multistatus(&response);
+    let principal = response_for(&response.body, "/calendars/").expect("the principal is reported");
+    assert_eq!(element_text(principal, "d:displayname").as_deref(), Some("Dave"));
+    assert!(response.body.contains("<cal:calendar-home-set><d:href>/calendars/</d:href></cal:calendar-home-set>"), "{}", response.body);
+    assert!(response.body.contains("<cs:calendar-proxy-write-for/>"), "{}", response.body);
+    let membership = response_for(&response.body, &group).expect("groups are expanded into responses");
+    assert_eq!(element_text(membership, "d:displayname").as_deref(), Some("Book Club"));
+
+    // Searching for invitees or delegates by name or email address
+    let search = |text: &str| format!(
+        r#"<?xml version="1.0" encoding="utf-8"?>
+<d:principal-property-search xmlns:d="DAV:" xmlns:cs="http://calendarserver.org/ns/" test="anyof">
+    <d:property-search><d:prop><d:displayname/></d:prop><d:match match-type="starts-with">{0}</d:match></d:property-search>
+    <d:property-search><d:prop><cs:email-address-set/></d:prop><d:match match-type="starts-with">{0}</d:match></d:property-search>
+    <d:prop><d:displayname/><cs:email-address-set/></d:prop>
+</d:principal-property-search>"#,
+        text
+    );
+    let response = app.send(dav_request("REPORT", "/calendars/", CREDENTIALS, Some("0"), &search("dave@"))).await;
+    assert_multistatus(&response);
+    assert!(response_for(&response.body, "/calendars/").is_some(), "{}", response.body);
+    assert!(response_for(&response.body, &group).is_none(), "{}", response.body);
+    assert!(response.body.contains("<cs:email-address>dave@example.com</cs:email-address>"), "{}", response.body);
+
+    let response = app.send(dav_request("REPORT", &group, CREDENTIALS, Some("0"), &search("book"))).await;
+    assert_multistatus(&response);
+    assert!(response_for(&response.body, &group).is_some(), "{}", response.body);
+    assert!(response_for(&response.body, "/calendars/").is_none(), "{}", response.body);
+}
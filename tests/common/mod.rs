@@ -0,0 +1,225 @@
+//! Shared helpers for the integration tests: the application router on a fresh
+//! in-memory database, and shortcuts for creating accounts and calendars through
+//! the HTTP API.
+
+#![allow(dead_code)]
+
+use axum::{
+    body::{to_bytes, Body},
+    http::{header, HeaderMap, Method, Request, StatusCode},
+    Router,
+};
+use base64::{engine::general_purpose::STANDARD as BASE64_STANDARD, Engine};
+use my_cal_dav_server::{build_app, build_app_with_clock, build_app_with_config, build_app_with_mailer, initialize_database, Config, MemoryMailer, MockClock};
+use serde_json::{json, Value};
+use sqlx::sqlite::{SqlitePool, SqlitePoolOptions};
+use std::sync::Arc;
+use tower::ServiceExt;
+
+pub const ADMIN_EMAIL: &str = "admin@example.com";
+pub const ADMIN_PASSWORD: &str = "admin-password";
+
+/// Response with its body read into a string
+pub struct TestResponse {
+    pub status: StatusCode,
+    pub headers: HeaderMap,
+    pub body: String,
+}
+
+impl TestResponse {
+    pub fn json(&self) -> Value {
+        serde_json::from_str(&self.body)
+            .unwrap_or_else(|e| panic!("response is not JSON ({}): {}", e, self.body))
+    }
+
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.get(name).and_then(|v| v.to_str().ok())
+    }
+}
+
+/// Empty, migrated in-memory database
+async fn memory_pool() -> SqlitePool {
+    // Every connection to :memory: opens a separate database, so keep a single one
+    let pool = SqlitePoolOptions::new()
+        .max_connections(1)
+        .connect("sqlite::memory:")
+        .await
+        .expect("failed to open in-memory database");
+    initialize_database(&pool).await.expect("failed to run migrations");
+    pool
+}
+
+/// Empty, migrated database in the file `path`, for tests that need a real file such as backups
+async fn file_pool(path: &std::path::Path) -> SqlitePool {
+    let pool = SqlitePoolOptions::new()
+        .max_connections(1)
+        .connect(&format!("sqlite:{}?mode=rwc", path.display()))
+        .await
+        .expect("failed to open database file");
+    initialize_database(&pool).await.expect("failed to run migrations");
+    pool
+}
+
+pub struct TestApp {
+    router: Router,
+}
+
+impl TestApp {
+    /// Router on an empty, migrated database; no account exists yet
+    pub async fn new() -> Self {
+        Self { router: build_app(memory_pool().await) }
+    }
+
+    /// Router on an empty database with a custom configuration
+    pub async fn with_config(config: Config) -> Self {
+        Self { router: build_app_with_config(memory_pool().await, &config) }
+    }
+
+    /// Router on an empty database stored in the file `path`, with a custom configuration
+    pub async fn on_disk(path: &std::path::Path, config: Config) -> Self {
+        Self { router: build_app_with_config(file_pool(path).await, &config) }
+    }
+
+    /// Router on an empty database that tells time by `clock`
+    pub async fn with_clock(clock: MockClock) -> Self {
+        Self { router: build_app_with_clock(memory_pool().await, &Config::default(), Arc::new(clock)) }
+    }
+
+    /// Router on an empty database that tells time by `clock` and keeps sent mail in `mailer`
+    pub async fn with_mailer(clock: MockClock, mailer: MemoryMailer) -> Self {
+        Self { router: build_app_with_mailer(memory_pool().await, &Config::default(), Arc::new(clock), Arc::new(mailer)) }
+    }
+
+    /// Router on a database where setup has been completed with the admin account
+    pub async fn with_admin() -> Self {
+        let app = Self::new().await;
+        let response = app.setup(ADMIN_EMAIL, ADMIN_PASSWORD).await;
+        assert_eq!(response.status, StatusCode::FOUND, "setup failed: {}", response.body);
+        app
+    }
+
+    pub async fn send(&self, request: Request<Body>) -> TestResponse {
+        let response = self.router.clone().oneshot(request).await.expect("router never fails");
+        let status = response.status();
+        let headers = response.headers().clone();
+        let bytes = to_bytes(response.into_body(), usize::MAX).await.expect("failed to read body");
+
+        TestResponse {
+            status,
+            headers,
+            body: String::from_utf8_lossy(&bytes).into_owned(),
+        }
+    }
+
+    /// Submit the first-run setup form with open registration
+    pub async fn setup(&self, email: &str, password: &str) -> TestResponse {
+        let form = format!(
+            "name=Admin&email={}&password={}&confirm_password={}&instance_name=Test&base_url=&registration=open",
+            email.replace('@', "%40"),
+            password,
+            password,
+        );
+        let request = Request::builder()
+            .method(Method::POST)
+            .uri("/setup")
+            .header(header::CONTENT_TYPE, "application/x-www-form-urlencoded")
+            .body(Body::from(form))
+            .unwrap();
+
+        self.send(request).await
+    }
+
+    pub async fn register(&self, name: &str, email: &str, password: &str) -> TestResponse {
+        self.send(json_request(Method::POST, "/api/auth/register", None, json!({
+            "name": name,
+            "email": email,
+            "password": password,
+        }))).await
+    }
+
+    pub async fn login(&self, email: &str, password: &str) -> TestResponse {
+        self.send(json_request(Method::POST, "/api/auth/login", None, json!({
+            "email": email,
+            "password": password,
+        }))).await
+    }
+
+    /// Register an account and return its JWT
+    pub async fn user_token(&self, email: &str, password: &str) -> String {
+        let response = self.register("Test User", email, password).await;
+        assert_eq!(response.status, StatusCode::OK, "registration failed: {}", response.body);
+
+        let response = self.login(email, password).await;
+        assert_eq!(response.status, StatusCode::OK, "login failed: {}", response.body);
+        response.json()["token"].as_str().expect("login response has a token").to_string()
+    }
+
+    /// Create a calendar and return it as JSON
+    pub async fn create_calendar(&self, token: &str, name: &str) -> Value {
+        let response = self.send(json_request(Method::POST, "/api/auth/calendars", Some(token), json!({
+            "name": name,
+            "description": null,
+            "color": "#3B82F6",
+            "is_public": false,
+        }))).await;
+        assert_eq!(response.status, StatusCode::OK, "calendar creation failed: {}", response.body);
+        response.json()
+    }
+}
+
+/// JSON request, authenticated with a bearer token when given
+pub fn json_request(method: Method, uri: &str, token: Option<&str>, body: Value) -> Request<Body> {
+    let mut builder = Request::builder()
+        .method(method)
+        .uri(uri)
+        .header(header::CONTENT_TYPE, "application/json");
+    if let Some(token) = token {
+        builder = builder.header(header::AUTHORIZATION, format!("Bearer {}", token));
+    }
+    builder.body(Body::from(body.to_string())).unwrap()
+}
+
+/// Request without a body, authenticated with a bearer token
+pub fn authed_request(method: Method, uri: &str, token: &str) -> Request<Body> {
+    Request::builder()
+        .method(method)
+        .uri(uri)
+        .header(header::AUTHORIZATION, format!("Bearer {}", token))
+        .body(Body::empty())
+        .unwrap()
+}
+
+/// WebDAV request with Basic Auth, as sent by calendar clients
+pub fn dav_request(method: &str, uri: &str, credentials: (&str, &str), depth: Option<&str>, body: &str) -> Request<Body> {
+    let auth = BASE64_STANDARD.encode(format!("{}:{}", credentials.0, credentials.1));
+    let mut builder = Request::builder()
+        .method(Method::from_bytes(method.as_bytes()).unwrap())
+        .uri(uri)
+        .header(header::AUTHORIZATION, format!("Basic {}", auth));
+    if let Some(depth) = depth {
+        builder = builder.header("Depth", depth);
+    }
+    if !body.is_empty() {
+        let content_type = if body.starts_with("BEGIN:VCALENDAR") {
+            "text/calendar; charset=utf-8"
+        } else {
+            "application/xml; charset=utf-8"
+        };
+        builder = builder.header(header::CONTENT_TYPE, content_type);
+    }
+    builder.body(Body::from(body.to_string())).unwrap()
+}
+
+/// Compare a rendered page with `tests/snapshots/{name}.html`. With `UPDATE_SNAPSHOTS=1`
+/// the snapshot is written instead, so changes to the markup show up in the diff.
+pub fn assert_snapshot(name: &str, actual: &str) {
+    let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/snapshots").join(format!("{}.html", name));
+    if std::env::var("UPDATE_SNAPSHOTS").is_ok_and(|v| v == "1") {
+        std::fs::create_dir_all(path.parent().unwrap()).expect("failed to create the snapshot directory");
+        std::fs::write(&path, actual).expect("failed to write the snapshot");
+        return;
+    }
+    let expected = std::fs::read_to_string(&path)
+        .unwrap_or_else(|e| panic!("no snapshot {} ({}); run with UPDATE_SNAPSHOTS=1 to create it", path.display(), e));
+    assert!(expected == actual, "{} differs from its snapshot; run with UPDATE_SNAPSHOTS=1 and review the diff\n{}", name, actual);
+}
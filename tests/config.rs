@@ -0,0 +1,112 @@
+//! Loading and validating the TOML configuration file
+
+use my_cal_dav_server::Config;
+use std::io::Write;
+
+fn config_file(contents: &str) -> tempfile::NamedTempFile {
+    let mut file = tempfile::NamedTempFile::new().unwrap();
+    file.write_all(contents.as_bytes()).unwrap();
+    file
+}
+
+#[test]
+fn file_values_override_defaults() {
+    let file = config_file(
+        r#"
+        port = 9090
+        base_url = "https://cal.example.com"
+        event_history_retention_days = 30
+        login_history_retention_days = 14
+
+        [database]
+        url = "sqlite::memory:"
+        busy_timeout_secs = 10
+
+        [limits]
+        rate_limit_requests = 5
+        max_failed_logins = 3
+        "#,
+    );
+
+    let config = Config::from_file(file.path().to_str().unwrap()).unwrap();
+    assert_eq!(config.port, 9090);
+    assert_eq!(config.base_url, "https://cal.example.com");
+    assert_eq!(config.database_url, "sqlite::memory:");
+    assert_eq!(config.database_pool.busy_timeout_secs, 10);
+    assert_eq!(config.database_pool.max_connections, Config::default().database_pool.max_connections);
+    assert_eq!(config.rate_limit_requests, 5);
+    assert_eq!(config.rate_limit_window_secs, Config::default().rate_limit_window_secs);
+    assert_eq!(config.lockout.max_failed_logins, 3);
+    assert_eq!(config.lockout.lockout_minutes, Config::default().lockout.lockout_minutes);
+    assert_eq!(config.retention.event_version_days, Some(30));
+    assert_eq!(config.retention.login_attempt_days, 14);
+    assert_eq!(config.retention.event_versions, Config::default().retention.event_versions);
+    config.validate().unwrap();
+}
+
+#[test]
+fn unknown_keys_are_rejected() {
+    let file = config_file("prot = 9090\n");
+    assert!(Config::from_file(file.path().to_str().unwrap()).is_err());
+}
+
+#[test]
+fn invalid_values_fail_validation() {
+    let file = config_file("base_url = \"cal.example.com\"\n");
+    let config = Config::from_file(file.path().to_str().unwrap()).unwrap();
+    assert!(config.validate().is_err());
+
+    let file = config_file("[tls]\ncert_path = \"/missing/cert.pem\"\nkey_path = \"/missing/key.pem\"\n");
+    let config = Config::from_file(file.path().to_str().unwrap()).unwrap();
+    assert!(config.validate().is_err());
+
+    let file = config_file("[database]\nmax_connections = 0\n");
+    let config = Config::from_file(file.path().to_str().unwrap()).unwrap();
+    assert!(config.validate().is_err());
+
+    let file = config_file("[limits]\nlockout_minutes = 0\n");
+    let config = Config::from_file(file.path().to_str().unwrap()).unwrap();
+    assert!(config.validate().is_err());
+
+    let file = config_file("cors_origins = [\"https://app.example.com/calendar\"]\n");
+    let config = Config::from_file(file.path().to_str().unwrap()).unwrap();
+    assert!(config.validate().is_err());
+
+    let file = config_file("cors_origins = [\"https://app.example.com/\", \"*\"]\n");
+    let config = Config::from_file(file.path().to_str().unwrap()).unwrap();
+    assert_eq!(config.cors_origins, ["https://app.example.com", "*"]);
+    config.validate().unwrap();
+}
+
+#[test]
+fn secrets_are_read_from_files() {
+    let secret = config_file("from-a-file\n");
+    let path = secret.path().to_str().unwrap().replace('\\', "/");
+    let file = config_file(&format!(
+        "[jwt]\nsecret_file = \"{path}\"\n\n[oidc]\nissuer = \"https://id.example.com\"\nclient_id = \"calendar\"\nclient_secret_file = \"{path}\"\nredirect_url = \"https://cal.example.com/cb\"\n"
+    ));
+
+    let config = Config::from_file(file.path().to_str().unwrap()).unwrap();
+    assert_eq!(config.jwt_secret, "from-a-file");
+    assert_eq!(config.oidc.unwrap().client_secret, "from-a-file");
+}
+
+#[test]
+fn ambiguous_or_unreadable_secrets_are_rejected() {
+    let file = config_file("[jwt]\nsecret = \"inline\"\nsecret_file = \"/run/secrets/jwt\"\n");
+    assert!(Config::from_file(file.path().to_str().unwrap()).is_err());
+
+    let file = config_file("[jwt]\nsecret_file = \"/missing/jwt_secret\"\n");
+    assert!(Config::from_file(file.path().to_str().unwrap()).is_err());
+
+    let empty = config_file("\n");
+    let file = config_file(&format!("[jwt]\nsecret_file = \"{}\"\n", empty.path().to_str().unwrap().replace('\\', "/")));
+    assert!(Config::from_file(file.path().to_str().unwrap()).is_err());
+}
+
+#[cfg(not(feature = "keyring"))]
+#[test]
+fn keyring_secrets_need_the_keyring_feature() {
+    let file = config_file("[jwt]\nsecret = \"keyring:jwt\"\n");
+    assert!(Config::from_file(file.path().to_str().unwrap()).is_err());
+}
@@ -0,0 +1,77 @@
+//! The experimental JMAP for Calendars endpoint
+
+#![cfg(feature = "jmap")]
+
+mod common;
+
+use axum::http::{Method, StatusCode};
+use common::{authed_request, json_request, TestApp};
+use serde_json::json;
+
+const EMAIL: &str = "carol@example.com";
+const PASSWORD: &str = "secret123";
+
+#[tokio::test]
+async fn session_describes_the_account_and_api() {
+    let app = TestApp::with_admin().await;
+    let token = app.user_token(EMAIL, PASSWORD).await;
+    let user_id = app.login(EMAIL, PASSWORD).await.json()["user"]["id"].as_str().unwrap().to_string();
+
+    let response = app.send(authed_request(Method::GET, "/jmap/session", &token)).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+    let session = response.json();
+    assert_eq!(session["username"], EMAIL);
+    assert_eq!(session["primaryAccounts"]["urn:ietf:params:jmap:calendars"], user_id.as_str());
+    assert_eq!(session["accounts"][&user_id]["name"], EMAIL);
+    assert!(session["apiUrl"].as_str().unwrap().ends_with("/jmap/api"), "{}", session);
+
+    let response = app.send(json_request(Method::GET, "/jmap/session", None, json!(null))).await;
+    assert_eq!(response.status, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn events_created_with_set_come_back_from_get() {
+    let app = TestApp::with_admin().await;
+    let token = app.user_token(EMAIL, PASSWORD).await;
+    let user_id = app.login(EMAIL, PASSWORD).await.json()["user"]["id"].as_str().unwrap().to_string();
+    let calendar_id = app.create_calendar(&token, "Work").await["id"].as_str().unwrap().to_string();
+    let call = |method: &str, arguments: serde_json::Value| json_request(Method::POST, "/jmap/api", Some(&token), json!({
+        "using": ["urn:ietf:params:jmap:core", "urn:ietf:params:jmap:calendars"],
+        "methodCalls": [[method, arguments, "c1"]],
+    }));
+
+    let response = app.send(call("CalendarEvent/set", json!({
+        "accountId": user_id,
+        "create": {
+            "standup": {
+                "calendarIds": { calendar_id.clone(): true },
+                "title": "Standup",
+                "start": "2030-01-01T09:00:00",
+                "duration": "PT15M",
+            },
+        },
+    }))).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+    let set = response.json();
+    let event_id = set["methodResponses"][0][1]["created"]["standup"]["id"].as_str()
+        .unwrap_or_else(|| panic!("{}", set)).to_string();
+
+    let response = app.send(call("CalendarEvent/get", json!({
+        "accountId": user_id,
+        "ids": [event_id, "missing"],
+    }))).await;
+    let get = response.json();
+    let [name, result, call_id] = get["methodResponses"][0].as_array().unwrap().as_slice() else {
+        panic!("{}", get);
+    };
+    assert_eq!(name, "CalendarEvent/get");
+    assert_eq!(call_id, "c1");
+    assert_eq!(result["notFound"], json!(["missing"]));
+    let event = &result["list"][0];
+    assert_eq!(event["id"], event_id.as_str());
+    assert_eq!(event["title"], "Standup");
+    assert_eq!(event["start"], "2030-01-01T09:00:00");
+    assert_eq!(event["duration"], "PT15M");
+    assert_eq!(event["calendarIds"], json!({ calendar_id: true }));
+    assert_eq!(result["state"], set["methodResponses"][0][1]["newState"]);
+}